@@ -1,20 +1,25 @@
-use std::io::Write;
-
 use super::AccessFlag;
 
 impl AccessFlag {
-    pub fn write_jimple_list(output: &mut dyn Write, list: &[Self]) -> Result<(), std::io::Error> {
+    /// Lists the flags as Jimple modifiers, each followed by a space.
+    pub fn get_jimple_list(list: &[Self]) -> String {
+        let mut result = String::new();
         for entry in list {
             match entry {
                 Self::Interface | Self::Annotation | Self::Enum | Self::Constructor => (),
                 Self::Abstract => {
                     if !list.contains(&Self::Interface) {
-                        write!(output, "{entry} ")?;
+                        result.push_str(&format!("{entry} "));
+                    }
+                }
+                Self::DeclaredSynchronized => {
+                    if !list.contains(&Self::Synchronized) {
+                        result.push_str(&format!("{} ", Self::Synchronized));
                     }
                 }
-                _ => write!(output, "{entry} ")?,
+                _ => result.push_str(&format!("{entry} ")),
             }
         }
-        Ok(())
+        result
     }
 }
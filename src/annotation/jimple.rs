@@ -1,80 +1,148 @@
 use std::io::Write;
 
 use super::{Annotation, AnnotationParameter, AnnotationParameterValue, AnnotationVisibility};
+use crate::doc::Doc;
+use crate::literal::Literal;
+use crate::output::{jimple_identifier, write_jimple_line, OutputOptions};
+
+/// Indentation of continuation lines when wrapping long annotation parameter lists.
+const CONTINUATION_INDENT: usize = 4;
 
 impl AnnotationParameterValue {
-    pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+    pub fn get_jimple_doc(&self) -> Doc {
         match self {
-            Self::Literal(literal) => write!(output, "{literal}"),
-            Self::Enum(type_name, constant) => write!(output, "{type_name}.{constant}"),
-            Self::Array(array) => {
-                write!(output, "{{")?;
-                let mut first = true;
-                for value in array {
-                    if first {
-                        first = false;
-                    } else {
-                        write!(output, ", ")?;
-                    }
-                    value.write_jimple(output)?;
-                }
-                write!(output, "}}")
+            Self::Literal(literal) => Doc::text(literal.to_string()),
+            Self::Enum(type_name, constant) => {
+                Doc::text(format!("{type_name}.{}", jimple_identifier(constant)))
             }
-            Self::SubAnnotation(annotation) => annotation.write_jimple(output, -1),
+            Self::Array(array) => Doc::Concat(vec![
+                Doc::text("{"),
+                Doc::join(array.iter().map(Self::get_jimple_doc), &Doc::text(", ")),
+                Doc::text("}"),
+            ]),
+            Self::SubAnnotation(annotation) => annotation.get_jimple_doc(),
         }
     }
 }
 
 impl AnnotationParameter {
-    pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
-        write!(output, "{} = ", self.name)?;
-        self.value.write_jimple(output)
+    pub fn get_jimple_doc(&self) -> Doc {
+        Doc::Concat(vec![
+            Doc::text(format!("{} = ", jimple_identifier(&self.name))),
+            self.value.get_jimple_doc(),
+        ])
     }
 }
 
 impl Annotation {
-    pub fn write_jimple(
-        &self,
-        output: &mut dyn Write,
-        indent_level: i32,
-    ) -> Result<(), std::io::Error> {
-        if indent_level >= 0 {
-            for _ in 0..indent_level {
-                write!(output, "    ")?;
+    /// Produces comments describing the data of folded system annotations.
+    fn get_folded_comments(&self) -> Vec<String> {
+        let value = self.get_parameter("value");
+        match (self.annotation_type.get_name().as_ref(), value) {
+            ("dalvik.annotation.Signature", Some(AnnotationParameterValue::Array(values))) => {
+                let signature = values
+                    .iter()
+                    .filter_map(|value| match value {
+                        AnnotationParameterValue::Literal(Literal::String(value)) => {
+                            Some(value.as_ref())
+                        }
+                        _ => None,
+                    })
+                    .collect::<String>();
+                vec![format!("signature: {signature}")]
+            }
+            (
+                "dalvik.annotation.EnclosingClass",
+                Some(AnnotationParameterValue::Literal(Literal::Class(class))),
+            ) => vec![format!("enclosing class: {class}")],
+            (
+                "dalvik.annotation.EnclosingMethod",
+                Some(AnnotationParameterValue::Literal(Literal::Method(method))),
+            ) => vec![format!("enclosing method: <{method}>")],
+            ("dalvik.annotation.InnerClass", _) => match self.get_parameter("name") {
+                Some(AnnotationParameterValue::Literal(Literal::String(name))) => {
+                    vec![format!("inner class: {name}")]
+                }
+                _ => vec!["anonymous inner class".to_string()],
+            },
+            ("dalvik.annotation.MemberClasses", Some(AnnotationParameterValue::Array(values))) => {
+                let classes = values
+                    .iter()
+                    .filter_map(|value| match value {
+                        AnnotationParameterValue::Literal(Literal::Class(class)) => {
+                            Some(class.to_string())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                vec![format!("member classes: {}", classes.join(", "))]
             }
+            _ => Vec::new(),
         }
+    }
 
-        write!(output, "@{}(", self.annotation_type)?;
-
-        let mut first = true;
-        for parameter in &self.parameters {
-            if first {
-                first = false;
+    /// Builds the lines of a list of annotations. Unless requested otherwise, system
+    /// annotations with known meaning are reduced to comments.
+    pub fn get_jimple_list_docs(list: &[Self], options: &OutputOptions) -> Vec<Doc> {
+        let mut result = Vec::new();
+        for annotation in list {
+            if options.hidden_annotations.contains(&annotation.visibility) {
+                continue;
+            }
+            if options.show_system_annotations || !annotation.is_folded() {
+                let comment = match annotation.visibility {
+                    AnnotationVisibility::Build => " // build",
+                    AnnotationVisibility::System => " // system",
+                    AnnotationVisibility::Runtime => "",
+                };
+                result.push(Doc::Concat(vec![
+                    annotation.get_jimple_doc(),
+                    Doc::text(comment),
+                ]));
             } else {
-                write!(output, ", ")?;
+                result.extend(
+                    annotation
+                        .get_folded_comments()
+                        .into_iter()
+                        .map(|comment| Doc::text(format!("// {comment}"))),
+                );
             }
-            parameter.write_jimple(output)?;
         }
+        result
+    }
 
-        write!(output, ")")?;
-        if indent_level >= 0 {
-            match self.visibility {
-                AnnotationVisibility::Build => write!(output, " // build")?,
-                AnnotationVisibility::System => write!(output, " // system")?,
-                AnnotationVisibility::Runtime => (),
-            };
-
-            writeln!(output)?;
+    /// Writes out a list of annotations, one per line.
+    pub fn write_jimple_list(
+        output: &mut dyn Write,
+        list: &[Self],
+        indent_level: usize,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        for doc in Self::get_jimple_list_docs(list, options) {
+            write_jimple_line(output, indent_level * 4, doc, options)?;
         }
-
         Ok(())
     }
+
+    /// Builds the annotation as it is written inline, without a comment on its visibility. The
+    /// parameters are put on continuation lines if they don't fit on the line.
+    pub fn get_jimple_doc(&self) -> Doc {
+        Doc::Concat(vec![
+            Doc::text(format!("@{}(", self.annotation_type)),
+            Doc::list(
+                self.parameters
+                    .iter()
+                    .map(AnnotationParameter::get_jimple_doc)
+                    .collect(),
+                CONTINUATION_INDENT,
+            ),
+            Doc::text(")"),
+        ])
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use itertools::Itertools;
-
     use super::*;
     use crate::error::ParseErrorDisplayed;
     use crate::tokenizer::Tokenizer;
@@ -87,6 +155,7 @@ mod tests {
         data.split(['\r', '\n'])
             .map(|line| line.trim())
             .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
             .join(" ")
             .replace("( ", "(")
             .replace(" )", ")")
@@ -174,17 +243,111 @@ mod tests {
             let annotation;
             (input, annotation) = Annotation::read(&input, false)?;
 
-            let mut cursor = std::io::Cursor::new(Vec::new());
-            annotation.write_jimple(&mut cursor, -1).unwrap();
-
             assert_eq!(
-                String::from_utf8_lossy(&cursor.into_inner()),
+                annotation.get_jimple_doc().render(0, None),
                 normalize(expected_result)
             );
+            if expected_result.starts_with("@dalvik.annotation.MemberClasses") {
+                // Parameters go on a continuation line if the annotation doesn't fit
+                assert_eq!(
+                    annotation.get_jimple_doc().render(4, Some(20)),
+                    format!(
+                        "    @{}(\n        {})",
+                        annotation.annotation_type,
+                        annotation.parameters[0].get_jimple_doc().render(0, None)
+                    )
+                );
+            }
         }
 
         input.expect_eof()?;
 
         Ok(())
     }
+
+    #[test]
+    fn write_folded_annotations() -> Result<(), ParseErrorDisplayed> {
+        let mut input = tokenizer(
+            r#"
+            .annotation system Ldalvik/annotation/Signature;
+                value = {
+                    "<T:",
+                    "Ljava/lang/Object;",
+                    ">",
+                    "Ljava/lang/Object;"
+                }
+            .end annotation
+
+            .annotation system Ldalvik/annotation/EnclosingMethod;
+                value = Lj2/b;->run()V
+            .end annotation
+
+            .annotation system Ldalvik/annotation/InnerClass;
+                accessFlags = 0x0
+                name = null
+            .end annotation
+
+            .annotation runtime Lz20/t;
+            .end annotation
+        "#
+            .trim(),
+        );
+
+        let mut annotations = Vec::new();
+        while input.expect_eof().is_err() {
+            input = input.expect_directive("annotation")?;
+
+            let annotation;
+            (input, annotation) = Annotation::read(&input, false)?;
+            annotations.push(annotation);
+        }
+
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        Annotation::write_jimple_list(&mut cursor, &annotations, 1, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&cursor.into_inner()),
+            r#"    // signature: <T:Ljava/lang/Object;>Ljava/lang/Object;
+    // enclosing method: <void j2.b.run()>
+    // anonymous inner class
+    @z20.t()
+"#
+        );
+
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        Annotation::write_jimple_list(
+            &mut cursor,
+            &annotations,
+            0,
+            &OutputOptions {
+                show_system_annotations: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&cursor.into_inner())
+                .lines()
+                .count(),
+            4
+        );
+
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        Annotation::write_jimple_list(
+            &mut cursor,
+            &annotations,
+            1,
+            &OutputOptions {
+                hidden_annotations: vec![AnnotationVisibility::System],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&cursor.into_inner()),
+            "    @z20.t()\n"
+        );
+
+        Ok(())
+    }
 }
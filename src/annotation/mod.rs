@@ -6,7 +6,8 @@ use crate::tokenizer::Tokenizer;
 mod jimple;
 mod smali;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum AnnotationVisibility {
     Build,
     Runtime,
@@ -55,3 +56,71 @@ pub struct Annotation {
     pub visibility: AnnotationVisibility,
     pub parameters: Vec<AnnotationParameter>,
 }
+
+/// System annotations that are rendered in a more readable form rather than printed out as is.
+const FOLDED_ANNOTATIONS: [&str; 6] = [
+    "dalvik.annotation.Signature",
+    "dalvik.annotation.Throws",
+    "dalvik.annotation.EnclosingClass",
+    "dalvik.annotation.EnclosingMethod",
+    "dalvik.annotation.InnerClass",
+    "dalvik.annotation.MemberClasses",
+];
+
+impl Annotation {
+    pub fn get_parameter(&self, name: &str) -> Option<&AnnotationParameterValue> {
+        self.parameters
+            .iter()
+            .find(|parameter| parameter.name == name)
+            .map(|parameter| &parameter.value)
+    }
+
+    pub fn is_folded(&self) -> bool {
+        self.visibility == AnnotationVisibility::System
+            && FOLDED_ANNOTATIONS.contains(&self.annotation_type.get_name().as_ref())
+    }
+
+    pub fn find<'a>(annotations: &'a [Self], annotation_type: &str) -> Option<&'a Self> {
+        annotations
+            .iter()
+            .find(|annotation| annotation.annotation_type.get_name() == annotation_type)
+    }
+
+    /// Returns the class containing an inner class, either from the `EnclosingClass` or the
+    /// `EnclosingMethod` system annotation.
+    pub fn get_enclosing_class(annotations: &[Self]) -> Option<Type> {
+        if let Some(AnnotationParameterValue::Literal(Literal::Class(class))) =
+            Self::find(annotations, "dalvik.annotation.EnclosingClass")
+                .and_then(|annotation| annotation.get_parameter("value"))
+        {
+            return Some(class.clone());
+        }
+
+        match Self::find(annotations, "dalvik.annotation.EnclosingMethod")
+            .and_then(|annotation| annotation.get_parameter("value"))
+        {
+            Some(AnnotationParameterValue::Literal(Literal::Method(method))) => {
+                Some(method.object_type.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns exception types listed in the `Throws` system annotation.
+    pub fn get_thrown_exceptions(annotations: &[Self]) -> Vec<Type> {
+        match Self::find(annotations, "dalvik.annotation.Throws")
+            .and_then(|annotation| annotation.get_parameter("value"))
+        {
+            Some(AnnotationParameterValue::Array(values)) => values
+                .iter()
+                .filter_map(|value| match value {
+                    AnnotationParameterValue::Literal(Literal::Class(exception)) => {
+                        Some(exception.clone())
+                    }
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
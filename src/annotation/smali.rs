@@ -1,17 +1,57 @@
+use std::io::Write;
+
 use super::{Annotation, AnnotationParameter, AnnotationParameterValue, AnnotationVisibility};
 use crate::error::ParseError;
 use crate::literal::Literal;
 use crate::r#type::Type;
-use crate::tokenizer::Tokenizer;
+use crate::tokenizer::{quote_identifier, Tokenizer};
 
 impl AnnotationParameterValue {
+    pub fn write_smali(&self, output: &mut dyn Write, indent: &str) -> Result<(), std::io::Error> {
+        match self {
+            Self::Literal(value) => write!(output, "{}", value.to_smali()),
+            Self::Enum(enum_type, value) => {
+                let enum_type = enum_type.to_descriptor();
+                let value = quote_identifier(value);
+                write!(output, ".enum {enum_type}->{value}:{enum_type}")
+            }
+            Self::Array(entries) => {
+                if entries.is_empty() {
+                    return write!(output, "{{}}");
+                }
+
+                writeln!(output, "{{")?;
+                let inner_indent = format!("{indent}    ");
+                for (index, entry) in entries.iter().enumerate() {
+                    write!(output, "{inner_indent}")?;
+                    entry.write_smali(output, &inner_indent)?;
+                    if index + 1 < entries.len() {
+                        write!(output, ",")?;
+                    }
+                    writeln!(output)?;
+                }
+                write!(output, "{indent}}}")
+            }
+            Self::SubAnnotation(annotation) => {
+                writeln!(
+                    output,
+                    ".subannotation {}",
+                    annotation.annotation_type.to_descriptor()
+                )?;
+                annotation.write_smali_parameters(output, &format!("{indent}    "))?;
+                write!(output, "{indent}.end subannotation")
+            }
+        }
+    }
+
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
-        if input.expect_directive("enum").is_ok() {
+        let directive = input.peek_directive();
+        if directive == Some("enum") {
             let input = input.expect_directive("enum")?;
             let (input, enum_type) = Type::read(&input)?;
             let input = input.expect_char('-')?;
             let input = input.expect_char('>')?;
-            let (input, value) = input.read_keyword()?;
+            let (input, value) = input.read_identifier()?;
             let input = input.expect_char(':')?;
 
             let (input2, enum_type2) = Type::read(&input)?;
@@ -21,11 +61,11 @@ impl AnnotationParameterValue {
             let input = input2;
 
             Ok((input, Self::Enum(enum_type, value)))
-        } else if input.expect_directive("subannotation").is_ok() {
+        } else if directive == Some("subannotation") {
             let input = input.expect_directive("subannotation")?;
             let (input, annotation) = Annotation::read(&input, true)?;
             Ok((input, Self::SubAnnotation(annotation)))
-        } else if input.expect_char('{').is_ok() {
+        } else if input.peek_char() == Some('{') {
             let mut input = input.expect_char('{')?;
             let mut entries = Vec::new();
             if input.expect_char('}').is_err() {
@@ -53,7 +93,7 @@ impl AnnotationParameterValue {
 
 impl AnnotationParameter {
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
-        let (input, name) = input.read_keyword()?;
+        let (input, name) = input.read_identifier()?;
         let input = input.expect_char('=')?;
         let (input, value) = AnnotationParameterValue::read(&input)?;
         let input = input.expect_eol()?;
@@ -62,6 +102,34 @@ impl AnnotationParameter {
 }
 
 impl Annotation {
+    fn write_smali_parameters(
+        &self,
+        output: &mut dyn Write,
+        indent: &str,
+    ) -> Result<(), std::io::Error> {
+        for parameter in &self.parameters {
+            write!(output, "{indent}{} = ", quote_identifier(&parameter.name))?;
+            parameter.value.write_smali(output, indent)?;
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_smali(&self, output: &mut dyn Write, indent: &str) -> Result<(), std::io::Error> {
+        let visibility = match self.visibility {
+            AnnotationVisibility::Build => "build",
+            AnnotationVisibility::Runtime => "runtime",
+            AnnotationVisibility::System => "system",
+        };
+        writeln!(
+            output,
+            "{indent}.annotation {visibility} {}",
+            self.annotation_type.to_descriptor()
+        )?;
+        self.write_smali_parameters(output, &format!("{indent}    "))?;
+        writeln!(output, "{indent}.end annotation")
+    }
+
     pub fn read(input: &Tokenizer, subannotation: bool) -> Result<(Tokenizer, Self), ParseError> {
         let (input, visibility) = if subannotation {
             (input.clone(), AnnotationVisibility::Build)
@@ -203,7 +271,7 @@ mod tests {
                             AnnotationParameter {
                                 name: "stringValue".to_string(),
                                 value: AnnotationParameterValue::Literal(Literal::String(
-                                    "8".to_string()
+                                    "8".into()
                                 )),
                             },
                             AnnotationParameter {
@@ -214,7 +282,7 @@ mod tests {
                                     parameters: vec![AnnotationParameter {
                                         name: "stringValue".to_string(),
                                         value: AnnotationParameterValue::Literal(Literal::String(
-                                            "9".to_string()
+                                            "9".into()
                                         )),
                                     },]
                                 }),
@@ -330,10 +398,10 @@ mod tests {
                     name: "value".to_string(),
                     value: AnnotationParameterValue::Array(vec![
                         AnnotationParameterValue::Literal(Literal::String(
-                            "Ljava/lang/Enum<".to_string()
+                            "Ljava/lang/Enum<".into()
                         )),
-                        AnnotationParameterValue::Literal(Literal::String("Lj2/b;".to_string())),
-                        AnnotationParameterValue::Literal(Literal::String(">;".to_string())),
+                        AnnotationParameterValue::Literal(Literal::String("Lj2/b;".into())),
+                        AnnotationParameterValue::Literal(Literal::String(">;".into())),
                     ]),
                 }],
             }
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::r#type::{CallSignature, MethodSignature};
+
+/// Parameter names of all methods declared in the program, from their debug information. Call
+/// arguments are annotated with these names to make calls with many similar arguments readable.
+#[derive(Debug, Default)]
+pub struct ArgumentNames {
+    methods: HashMap<MethodSignature, Vec<Option<String>>>,
+    /// Names by method name and call signature for calls via subclasses, `None` if the
+    /// declarations disagree
+    overloads: HashMap<(String, CallSignature), Option<Vec<Option<String>>>>,
+}
+
+impl ArgumentNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            let names = method
+                .parameters
+                .iter()
+                .map(|parameter| parameter.name.clone())
+                .collect::<Vec<_>>();
+            if names.iter().all(Option::is_none) {
+                continue;
+            }
+
+            let signature = method.get_signature(&class.class_type);
+            self.overloads
+                .entry((
+                    signature.method_name.clone(),
+                    signature.call_signature.clone(),
+                ))
+                .and_modify(|existing| {
+                    if existing.as_ref() != Some(&names) {
+                        *existing = None;
+                    }
+                })
+                .or_insert_with(|| Some(names.clone()));
+            self.methods.insert(signature, names);
+        }
+    }
+
+    fn get(&self, signature: &MethodSignature) -> Option<&[Option<String>]> {
+        self.methods
+            .get(signature)
+            .or_else(|| {
+                self.overloads
+                    .get(&(
+                        signature.method_name.clone(),
+                        signature.call_signature.clone(),
+                    ))?
+                    .as_ref()
+            })
+            .map(Vec::as_slice)
+    }
+
+    /// Attaches the parameter names of the called methods to all calls in the class.
+    pub fn apply(&self, class: &mut Class) {
+        if self.methods.is_empty() {
+            return;
+        }
+        for method in &mut class.methods {
+            for instruction in &mut method.instructions {
+                let Instruction::Command {
+                    command,
+                    parameters,
+                } = instruction
+                else {
+                    continue;
+                };
+                if !command.starts_with("invoke-") {
+                    continue;
+                }
+                let Some(signature) = parameters.iter().find_map(|parameter| match parameter {
+                    CommandParameter::Method(signature) => Some(signature),
+                    _ => None,
+                }) else {
+                    continue;
+                };
+                let Some(names) = self.get(signature) else {
+                    continue;
+                };
+
+                // Names are given per register, wide arguments occupy two
+                let mut register_names = Vec::new();
+                if !command.starts_with("invoke-static") {
+                    register_names.push(None);
+                }
+                for (parameter_type, name) in
+                    signature.call_signature.parameter_types.iter().zip(names)
+                {
+                    register_names.push(name.clone());
+                    for _ in 1..parameter_type.register_count() {
+                        register_names.push(None);
+                    }
+                }
+                parameters.push(CommandParameter::ArgumentNames(register_names));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn argument_names() -> Result<(), ParseErrorDisplayed> {
+        let callee = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public update(JZ)V
+                    .locals 0
+                    .param p1, "userId"    # J
+                    .end param
+                    .param p3, "enabled"    # Z
+                    .end param
+                    return-void
+                .end method
+            "#,
+        )?;
+        let mut caller = read_optimized_class(
+            r#"
+                .class public Lb;
+                .super La;
+
+                .method static run(Lb;J)V
+                    .locals 1
+                    const/4 v0, 0x1
+                    invoke-virtual {p0, p1, p2, v0}, Lb;->update(JZ)V
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut names = ArgumentNames::new();
+        names.add_class(&callee);
+        names.apply(&mut caller);
+
+        let mut output = Vec::new();
+        caller.methods[0]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    static void run(b @p0, long @p1)
+    {
+        invoke-virtual p0.<void b.update(long, bool)>(/* userId= */ p1, p2, /* enabled= */ true);
+        return;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
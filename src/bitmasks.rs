@@ -0,0 +1,415 @@
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, Register};
+use crate::method::Method;
+
+/// Flag constants of a framework class along with the methods taking or returning such flags.
+#[derive(Debug)]
+struct FlagTable {
+    class: &'static str,
+    /// Name of the class declaring the constants, as written in Java code
+    constants_class: &'static str,
+    /// Methods taking flags, with the index of the parameter
+    setters: &'static [(&'static str, usize)],
+    /// Methods returning flags
+    getters: &'static [&'static str],
+    flags: &'static [(&'static str, i64)],
+}
+
+const FLAG_TABLES: [FlagTable; 3] = [
+    FlagTable {
+        class: "android.content.Intent",
+        constants_class: "Intent",
+        setters: &[("setFlags", 0), ("addFlags", 0), ("removeFlags", 0)],
+        getters: &["getFlags"],
+        flags: &[
+            ("FLAG_GRANT_READ_URI_PERMISSION", 0x1),
+            ("FLAG_GRANT_WRITE_URI_PERMISSION", 0x2),
+            ("FLAG_FROM_BACKGROUND", 0x4),
+            ("FLAG_DEBUG_LOG_RESOLUTION", 0x8),
+            ("FLAG_EXCLUDE_STOPPED_PACKAGES", 0x10),
+            ("FLAG_INCLUDE_STOPPED_PACKAGES", 0x20),
+            ("FLAG_GRANT_PERSISTABLE_URI_PERMISSION", 0x40),
+            ("FLAG_GRANT_PREFIX_URI_PERMISSION", 0x80),
+            ("FLAG_ACTIVITY_REQUIRE_DEFAULT", 0x200),
+            ("FLAG_ACTIVITY_REQUIRE_NON_BROWSER", 0x400),
+            ("FLAG_ACTIVITY_MATCH_EXTERNAL", 0x800),
+            ("FLAG_ACTIVITY_LAUNCH_ADJACENT", 0x1000),
+            ("FLAG_ACTIVITY_RETAIN_IN_RECENTS", 0x2000),
+            ("FLAG_ACTIVITY_TASK_ON_HOME", 0x4000),
+            ("FLAG_ACTIVITY_CLEAR_TASK", 0x8000),
+            ("FLAG_ACTIVITY_NO_ANIMATION", 0x10000),
+            ("FLAG_ACTIVITY_REORDER_TO_FRONT", 0x20000),
+            ("FLAG_ACTIVITY_NO_USER_ACTION", 0x40000),
+            ("FLAG_ACTIVITY_NEW_DOCUMENT", 0x80000),
+            ("FLAG_ACTIVITY_LAUNCHED_FROM_HISTORY", 0x100000),
+            ("FLAG_ACTIVITY_RESET_TASK_IF_NEEDED", 0x200000),
+            ("FLAG_ACTIVITY_BROUGHT_TO_FRONT", 0x400000),
+            ("FLAG_ACTIVITY_EXCLUDE_FROM_RECENTS", 0x800000),
+            ("FLAG_ACTIVITY_PREVIOUS_IS_TOP", 0x1000000),
+            ("FLAG_ACTIVITY_FORWARD_RESULT", 0x2000000),
+            ("FLAG_ACTIVITY_CLEAR_TOP", 0x4000000),
+            ("FLAG_ACTIVITY_MULTIPLE_TASK", 0x8000000),
+            ("FLAG_ACTIVITY_NEW_TASK", 0x10000000),
+            ("FLAG_ACTIVITY_SINGLE_TOP", 0x20000000),
+            ("FLAG_ACTIVITY_NO_HISTORY", 0x40000000),
+        ],
+    },
+    FlagTable {
+        class: "android.app.PendingIntent",
+        constants_class: "PendingIntent",
+        setters: &[
+            ("getActivity", 3),
+            ("getBroadcast", 3),
+            ("getService", 3),
+            ("getForegroundService", 3),
+        ],
+        getters: &[],
+        flags: &[
+            ("FLAG_MUTABLE", 0x2000000),
+            ("FLAG_IMMUTABLE", 0x4000000),
+            ("FLAG_UPDATE_CURRENT", 0x8000000),
+            ("FLAG_CANCEL_CURRENT", 0x10000000),
+            ("FLAG_NO_CREATE", 0x20000000),
+            ("FLAG_ONE_SHOT", 0x40000000),
+        ],
+    },
+    FlagTable {
+        class: "android.view.Window",
+        constants_class: "WindowManager.LayoutParams",
+        setters: &[
+            ("addFlags", 0),
+            ("clearFlags", 0),
+            ("setFlags", 0),
+            ("setFlags", 1),
+        ],
+        getters: &[],
+        flags: &[
+            ("FLAG_ALLOW_LOCK_WHILE_SCREEN_ON", 0x1),
+            ("FLAG_DIM_BEHIND", 0x2),
+            ("FLAG_BLUR_BEHIND", 0x4),
+            ("FLAG_NOT_FOCUSABLE", 0x8),
+            ("FLAG_NOT_TOUCHABLE", 0x10),
+            ("FLAG_NOT_TOUCH_MODAL", 0x20),
+            ("FLAG_TOUCHABLE_WHEN_WAKING", 0x40),
+            ("FLAG_KEEP_SCREEN_ON", 0x80),
+            ("FLAG_LAYOUT_IN_SCREEN", 0x100),
+            ("FLAG_LAYOUT_NO_LIMITS", 0x200),
+            ("FLAG_FULLSCREEN", 0x400),
+            ("FLAG_FORCE_NOT_FULLSCREEN", 0x800),
+            ("FLAG_DITHER", 0x1000),
+            ("FLAG_SECURE", 0x2000),
+            ("FLAG_SCALED", 0x4000),
+            ("FLAG_IGNORE_CHEEK_PRESSES", 0x8000),
+            ("FLAG_LAYOUT_INSET_DECOR", 0x10000),
+            ("FLAG_ALT_FOCUSABLE_IM", 0x20000),
+            ("FLAG_WATCH_OUTSIDE_TOUCH", 0x40000),
+            ("FLAG_SHOW_WHEN_LOCKED", 0x80000),
+            ("FLAG_SHOW_WALLPAPER", 0x100000),
+            ("FLAG_TURN_SCREEN_ON", 0x200000),
+            ("FLAG_DISMISS_KEYGUARD", 0x400000),
+            ("FLAG_SPLIT_TOUCH", 0x800000),
+            ("FLAG_HARDWARE_ACCELERATED", 0x1000000),
+            ("FLAG_LAYOUT_IN_OVERSCAN", 0x2000000),
+            ("FLAG_TRANSLUCENT_STATUS", 0x4000000),
+            ("FLAG_TRANSLUCENT_NAVIGATION", 0x8000000),
+            ("FLAG_LOCAL_FOCUS_MODE", 0x10000000),
+            ("FLAG_LAYOUT_ATTACHED_IN_DECOR", 0x40000000),
+            ("FLAG_DRAWS_SYSTEM_BAR_BACKGROUNDS", 0x80000000),
+        ],
+    },
+];
+
+/// Recognizes bitwise operations, returning whether the operation works on long values.
+fn get_bit_operation(command: &str) -> Option<bool> {
+    let (operation, rest) = command.split_once('-')?;
+    if !matches!(operation, "and" | "or" | "xor") {
+        return None;
+    }
+    match rest.split('/').next()? {
+        "int" => Some(false),
+        "long" => Some(true),
+        _ => None,
+    }
+}
+
+/// Decomposes a value into flags of the table, unknown bits are listed as a number. An `and`
+/// operation with most bits set is usually clearing flags, the complement is decomposed then.
+fn decompose(table: &FlagTable, value: u64, is_and: bool) -> String {
+    let (value, negated) = if is_and && value.count_ones() > 16 {
+        (!value & 0xffff_ffff, true)
+    } else {
+        (value, false)
+    };
+    let mut names = Vec::new();
+    let mut remaining = value;
+    for (name, flag) in table.flags {
+        let flag = *flag as u64;
+        if remaining & flag == flag {
+            names.push(format!("{}.{name}", table.constants_class));
+            remaining &= !flag;
+        }
+    }
+    if remaining != 0 || names.is_empty() {
+        names.push(format!("{remaining:#x}"));
+    }
+    let result = names.join(" | ");
+    if negated {
+        format!("~({result})")
+    } else {
+        result
+    }
+}
+
+impl Method {
+    /// Finds the first instruction reading a register after instruction `i` within the same
+    /// basic block.
+    fn find_next_use(&self, i: usize, register: &Register) -> Option<usize> {
+        for (j, instruction) in self.instructions.iter().enumerate().skip(i + 1) {
+            if matches!(instruction, Instruction::Label(_)) {
+                return None;
+            }
+            if instruction.count_register_uses(register) > 0 {
+                return Some(j);
+            }
+            if instruction.ends_block() || instruction.get_result_register() == Some(register) {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// Checks whether a register holds the result of a call returning flags of the table.
+    fn is_flags_getter_result(&self, i: usize, register: &Register) -> Option<&'static FlagTable> {
+        let previous = self.instructions[..i]
+            .iter()
+            .rev()
+            .take_while(|previous| {
+                !matches!(previous, Instruction::Label(_)) && !previous.ends_block()
+            })
+            .find(|previous| previous.get_result_register() == Some(register))?;
+        let (method, _) = previous.get_call_arguments()?;
+        FLAG_TABLES.iter().find(|table| {
+            method.object_type.get_name() == table.class
+                && table.getters.contains(&method.method_name.as_str())
+        })
+    }
+
+    /// Follows the value computed by instruction `i` through further bitwise operations,
+    /// returning the flag table if it ends up passed to a method taking flags.
+    fn get_flags_destination(
+        &self,
+        mut i: usize,
+        mut register: Register,
+    ) -> Option<&'static FlagTable> {
+        loop {
+            let j = self.find_single_use(i, &register)?;
+            let instruction = &self.instructions[j];
+            if let Some((method, registers)) = instruction.get_call_arguments() {
+                let offset = registers
+                    .len()
+                    .checked_sub(method.call_signature.parameter_types.len())?;
+                let index = registers.iter().position(|current| *current == register)?;
+                let index = index.checked_sub(offset)?;
+                return FLAG_TABLES.iter().find(|table| {
+                    method.object_type.get_name() == table.class
+                        && table
+                            .setters
+                            .contains(&(method.method_name.as_str(), index))
+                });
+            }
+
+            let Instruction::Command { command, .. } = instruction else {
+                return None;
+            };
+            get_bit_operation(command.as_str())?;
+            register = instruction
+                .get_result_register()
+                .cloned()
+                .or_else(|| instruction.get_used_registers().into_iter().next())?;
+            i = j;
+        }
+    }
+
+    /// Determines the flag table a constant operand of the bitwise operation `i` belongs to,
+    /// either because the other operand holds flags or because the result is passed on as flags.
+    fn get_operation_flags(
+        &self,
+        i: usize,
+        constant: Option<&Register>,
+    ) -> Option<&'static FlagTable> {
+        let instruction = &self.instructions[i];
+        let operands = instruction.get_used_registers();
+        let getter_table = operands
+            .iter()
+            .filter(|register| Some(*register) != constant)
+            .find_map(|register| self.is_flags_getter_result(i, register));
+        getter_table.or_else(|| {
+            let result = instruction
+                .get_result_register()
+                .or_else(|| operands.first())?;
+            self.get_flags_destination(i, result.clone())
+        })
+    }
+
+    /// Attaches the unsigned value and the flag decomposition to constants used in bitwise
+    /// operations or passed to framework methods taking flags. Comments go to the statement
+    /// the constant is nested into.
+    pub fn annotate_bitmasks(&mut self) {
+        let nested = self.find_nested_expressions();
+        let mut comments = Vec::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+
+            let literal = parameters.iter().find_map(|parameter| match parameter {
+                CommandParameter::Literal(literal) => literal.get_integer(),
+                _ => None,
+            });
+            // Constant value, whether it is a long, the bitwise operation using it and flags
+            let (value, is_long, operation, table) = if let Some(is_long) =
+                get_bit_operation(command)
+            {
+                let Some(value) = literal else {
+                    continue;
+                };
+                (
+                    value,
+                    is_long,
+                    Some(command),
+                    self.get_operation_flags(i, None),
+                )
+            } else if command == "const"
+                || command.starts_with("const/")
+                || command.starts_with("const-wide")
+            {
+                let (Some(value), Some(register)) = (literal, instruction.get_result_register())
+                else {
+                    continue;
+                };
+                let Some(j) = self.find_next_use(i, register) else {
+                    continue;
+                };
+                match &self.instructions[j] {
+                    Instruction::Command { command: user, .. }
+                        if get_bit_operation(user).is_some() =>
+                    {
+                        let table = self.get_operation_flags(j, Some(register));
+                        (value, command.starts_with("const-wide"), Some(user), table)
+                    }
+                    _ => {
+                        let table = self.get_flags_destination(i, register.clone());
+                        (value, command.starts_with("const-wide"), None, table)
+                    }
+                }
+            } else {
+                continue;
+            };
+
+            let unsigned = if is_long {
+                value as u64
+            } else {
+                value as u32 as u64
+            };
+            let comment = match table.filter(|_| !is_long) {
+                Some(table) => {
+                    let is_and = operation.is_some_and(|operation| operation.starts_with("and"));
+                    format!("{unsigned:#x} = {}", decompose(table, unsigned, is_and))
+                }
+                None if operation.is_some() && value < 0 => format!("{unsigned:#x}"),
+                None => continue,
+            };
+
+            let mut target = i;
+            while let Some(&next) = nested.get(&target) {
+                target = next;
+            }
+            comments.push((target, comment));
+        }
+
+        for (target, comment) in comments {
+            if let Instruction::Command { parameters, .. } = &mut self.instructions[target] {
+                parameters.push(CommandParameter::Comment(comment));
+            }
+        }
+    }
+}
+
+impl Class {
+    /// Explains constant operands of bitwise operations, see [`Method::annotate_bitmasks`].
+    pub fn annotate_bitmasks(&mut self) {
+        for method in &mut self.methods {
+            method.annotate_bitmasks();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn bitmasks() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public run(Landroid/content/Intent;Landroid/view/Window;I)I
+                    .locals 2
+                    invoke-virtual {p1}, Landroid/content/Intent;->getFlags()I
+                    move-result v0
+                    const v1, 0x10008000
+                    or-int/2addr v0, v1
+                    invoke-virtual {p1, v0}, Landroid/content/Intent;->setFlags(I)Landroid/content/Intent;
+                    const/high16 v0, -0x80000000
+                    invoke-virtual {p2, v0}, Landroid/view/Window;->addFlags(I)V
+                    const/16 v0, 0x2000
+                    or-int/lit16 v0, v0, 0x400
+                    invoke-virtual {p2, v0}, Landroid/view/Window;->clearFlags(I)V
+                    and-int/lit16 v0, p3, -0x81
+                    invoke-virtual {p2, v0}, Landroid/view/Window;->addFlags(I)V
+                    const/high16 v0, -0x80000000
+                    xor-int/2addr v0, p3
+                    return v0
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+        class.annotate_bitmasks();
+
+        let mut output = Vec::new();
+        class.methods[0]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    public int run(android.content.Intent @p0, android.view.Window @p1, int @p2)
+    {
+        v0 = invoke-virtual p1.<int android.content.Intent.getFlags()>();
+        v0 |= 0x10008000; // 0x10008000 = Intent.FLAG_ACTIVITY_CLEAR_TASK | Intent.FLAG_ACTIVITY_NEW_TASK
+        invoke-virtual p1.<android.content.Intent android.content.Intent.setFlags(int)>(v0);
+        invoke-virtual p2.<void android.view.Window.addFlags(int)>(-0x80000000); // 0x80000000 = WindowManager.LayoutParams.FLAG_DRAWS_SYSTEM_BAR_BACKGROUNDS
+        invoke-virtual p2.<void android.view.Window.clearFlags(int)>(0x2000 | 0x400); // 0x2000 = WindowManager.LayoutParams.FLAG_SECURE; 0x400 = WindowManager.LayoutParams.FLAG_FULLSCREEN
+        invoke-virtual p2.<void android.view.Window.addFlags(int)>(p3 & -0x81); // 0xffffff7f = ~(WindowManager.LayoutParams.FLAG_KEEP_SCREEN_ON)
+        v0 = -0x80000000; // 0x80000000
+        v0 ^= p3;
+        return v0;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
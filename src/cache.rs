@@ -0,0 +1,106 @@
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::output::OutputFile;
+use crate::r#type::Type;
+
+/// Packages of libraries bundled with most apps unchanged, only these are cached.
+const SHARED_PACKAGES: [&str; 11] = [
+    "androidx.",
+    "android.support.",
+    "kotlin.",
+    "kotlinx.",
+    "com.google.android.material.",
+    "com.google.android.gms.",
+    "com.google.firebase.",
+    "com.google.gson.",
+    "okhttp3.",
+    "okio.",
+    "retrofit2.",
+];
+
+/// Cache of converted library classes shared between runs on different apps. Entries are
+/// addressed by the hash of the Smali code along with the options affecting the output, so
+/// that changed options or aarf versions never produce stale output.
+#[derive(Debug)]
+pub struct ConversionCache {
+    dir: PathBuf,
+    /// Version and options the output depends on
+    fingerprint: String,
+}
+
+impl ConversionCache {
+    pub fn new(dir: &Path, options: &str) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            fingerprint: format!("{} {options}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    /// Checks whether a class belongs to a library worth caching.
+    pub fn is_shared(class_type: &Type) -> bool {
+        let name = class_type.get_name();
+        SHARED_PACKAGES
+            .iter()
+            .any(|package| name.starts_with(package))
+    }
+
+    fn get_path(&self, input: &[u8]) -> PathBuf {
+        let mut hasher = Sha256::new();
+        hasher.update(self.fingerprint.as_bytes());
+        hasher.update([0]);
+        hasher.update(input);
+        let key = format!("{:x}", hasher.finalize());
+        self.dir.join(&key[..2]).join(format!("{key}.jimple"))
+    }
+
+    /// Looks up the converted code for the given Smali code, returning it along with the
+    /// number of warnings produced by the conversion.
+    pub fn get(&self, input: &[u8]) -> Option<(Vec<u8>, usize)> {
+        let data = std::fs::read(self.get_path(input)).ok()?;
+        let (first_line, body) = data.split_at(data.iter().position(|c| *c == b'\n')? + 1);
+        let warnings = std::str::from_utf8(first_line)
+            .ok()?
+            .trim_end()
+            .strip_prefix("// warnings: ")?
+            .parse()
+            .ok()?;
+        Some((body.to_vec(), warnings))
+    }
+
+    pub fn store(&self, input: &[u8], body: &[u8], warnings: usize) -> Result<(), std::io::Error> {
+        let path = self.get_path(input);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut output = OutputFile::create(&path, false)?;
+        writeln!(output, "// warnings: {warnings}")?;
+        output.write_all(body)?;
+        output.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_cache() {
+        let dir = std::env::temp_dir().join(format!("aarf-cache-{}", std::process::id()));
+        let cache = ConversionCache::new(&dir, "--declare-locals");
+        assert_eq!(cache.get(b".class La;"), None);
+        cache.store(b".class La;", b"class a\n", 2).unwrap();
+        assert_eq!(cache.get(b".class La;"), Some((b"class a\n".to_vec(), 2)));
+        assert_eq!(cache.get(b".class Lb;"), None);
+        assert_eq!(ConversionCache::new(&dir, "").get(b".class La;"), None);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert!(ConversionCache::is_shared(&Type::Object(
+            "androidx.core.app.ComponentActivity".to_string()
+        )));
+        assert!(!ConversionCache::is_shared(&Type::Object(
+            "com.example.MainActivity".to_string()
+        )));
+    }
+}
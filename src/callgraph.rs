@@ -0,0 +1,322 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use schemars::JsonSchema;
+use serde::ser::{Error, SerializeSeq};
+use serde::{Serialize, Serializer};
+
+use crate::class::Class;
+use crate::index::Index;
+use crate::r#type::{MethodSignature, Type};
+use crate::schema::{write_json_pretty, JsonOutput, SchemaVersion};
+
+/// Escapes a node label or identifier for the DOT format.
+fn dot_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct NodeJson<'a> {
+    /// Full method signature, referenced by the edges
+    id: &'a str,
+    class: &'a str,
+    /// Method name with parameter types
+    label: &'a str,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct EdgeJson<'a> {
+    caller: &'a str,
+    callee: &'a str,
+}
+
+/// Call graph written by the callgraph command.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "CallGraphFile")]
+pub(crate) struct CallGraphFile<N = Vec<NodeJson<'static>>, E = Vec<EdgeJson<'static>>> {
+    schema_version: SchemaVersion<{ JsonOutput::Callgraph.version() }>,
+    nodes: N,
+    edges: E,
+}
+
+/// Calls the callback for each entry of an index, skipping duplicate values of a key. The outer
+/// result reports errors reading the index, the inner one the first error of the callback.
+fn for_each_entry<E>(
+    index: &Index,
+    mut callback: impl FnMut(&str, &str) -> Result<(), E>,
+) -> Result<Result<(), E>, std::io::Error> {
+    let mut result = Ok(());
+    index.for_each(|key, values| {
+        let mut previous = None;
+        for value in values {
+            if result.is_ok() && previous != Some(value) {
+                result = callback(key, value);
+            }
+            previous = Some(value);
+        }
+    })?;
+    Ok(result)
+}
+
+/// Splits a node entry into node ID and label.
+fn split_node(entry: &str) -> (&str, &str) {
+    entry.split_once('\t').unwrap_or((entry, entry))
+}
+
+/// Nodes recorded in the index, serialized as a list without reading them into memory.
+#[derive(Debug)]
+struct IndexedNodes<'a>(&'a Index);
+
+impl Serialize for IndexedNodes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for_each_entry(self.0, |class, entry| {
+            let (id, label) = split_node(entry);
+            seq.serialize_element(&NodeJson { id, class, label })
+        })
+        .map_err(S::Error::custom)??;
+        seq.end()
+    }
+}
+
+/// Edges recorded in the index, serialized as a list without reading them into memory.
+#[derive(Debug)]
+struct IndexedEdges<'a>(&'a Index);
+
+impl Serialize for IndexedEdges<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        for_each_entry(self.0, |caller, callee| {
+            seq.serialize_element(&EdgeJson { caller, callee })
+        })
+        .map_err(S::Error::custom)??;
+        seq.end()
+    }
+}
+
+/// Interprocedural call graph of the program, built from the methods named by invoke
+/// instructions. As with the caller index, calls are attributed to the type named in the call.
+#[derive(Debug)]
+pub struct CallGraph {
+    /// Packages whose classes are included, all classes if empty
+    packages: Vec<String>,
+    /// Methods appearing in the graph keyed by class, as node ID and label separated by a tab
+    nodes: Index,
+    /// Methods called keyed by the calling method
+    edges: Index,
+}
+
+impl CallGraph {
+    /// Creates a call graph limited to calls between classes from the given packages and their
+    /// subpackages, or including all calls if no packages are given. The graph is kept on disk
+    /// if a memory limit is given.
+    pub fn new(packages: &[String], memory_limit: Option<usize>) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            packages: packages.to_vec(),
+            nodes: Index::new(memory_limit)?,
+            edges: Index::new(memory_limit)?,
+        })
+    }
+
+    fn includes(&self, class_type: &Type) -> bool {
+        let name = class_type.to_string();
+        self.packages.is_empty()
+            || self
+                .packages
+                .iter()
+                .any(|package| name.starts_with(&format!("{package}.")))
+    }
+
+    fn get_node(method: &MethodSignature) -> (String, String) {
+        let id = method.to_string();
+        let label = format!(
+            "{}({})",
+            method.method_name,
+            method
+                .call_signature
+                .parameter_types
+                .iter()
+                .map(Type::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        (method.object_type.to_string(), format!("{id}\t{label}"))
+    }
+
+    pub fn add_class(&mut self, class: &Class) -> Result<(), std::io::Error> {
+        if !self.includes(&class.class_type) {
+            return Ok(());
+        }
+        // Duplicates are skipped when reading, avoiding them within a class keeps the index small
+        let mut nodes = BTreeSet::new();
+        let mut edges = BTreeSet::new();
+        for method in &class.methods {
+            let caller = method.get_signature(&class.class_type);
+            nodes.insert(Self::get_node(&caller));
+            for call in method.get_calls(&class.class_type) {
+                if self.includes(&call.method.object_type) {
+                    nodes.insert(Self::get_node(call.method));
+                    edges.insert((caller.to_string(), call.method.to_string()));
+                }
+            }
+        }
+        for (class, entry) in nodes {
+            self.nodes.insert(&class, &entry)?;
+        }
+        for (caller, callee) in edges {
+            self.edges.insert(&caller, &callee)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the graph in DOT format, with the methods of each class grouped into a cluster.
+    pub fn write_dot(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        writeln!(output, "digraph callgraph {{")?;
+        writeln!(output, "    node [shape=box];")?;
+        let mut current_class = None;
+        for_each_entry(&self.nodes, |class, entry| {
+            if current_class.as_deref() != Some(class) {
+                if current_class.is_some() {
+                    writeln!(output, "    }}")?;
+                }
+                writeln!(output, "    subgraph \"cluster_{}\" {{", dot_label(class))?;
+                writeln!(output, "        label=\"{}\";", dot_label(class))?;
+                current_class = Some(class.to_string());
+            }
+            let (id, label) = split_node(entry);
+            writeln!(
+                output,
+                "        \"{}\" [label=\"{}\"];",
+                dot_label(id),
+                dot_label(label)
+            )
+        })??;
+        if current_class.is_some() {
+            writeln!(output, "    }}")?;
+        }
+        for_each_entry(&self.edges, |caller, callee| {
+            writeln!(
+                output,
+                "    \"{}\" -> \"{}\";",
+                dot_label(caller),
+                dot_label(callee)
+            )
+        })??;
+        writeln!(output, "}}")
+    }
+
+    pub fn write_json(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        write_json_pretty(
+            output,
+            &CallGraphFile {
+                schema_version: SchemaVersion,
+                nodes: IndexedNodes(&self.nodes),
+                edges: IndexedEdges(&self.edges),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn call_graph() -> Result<(), ParseErrorDisplayed> {
+        let classes = [
+            r#"
+                .class public Lcom/example/Main;
+                .super Ljava/lang/Object;
+
+                .method public static main()V
+                    .locals 1
+                    invoke-static {}, Lcom/example/util/Helper;->help()Ljava/lang/String;
+                    move-result-object v0
+                    invoke-virtual {v0}, Ljava/lang/String;->length()I
+                    invoke-static {}, Lcom/example/util/Helper;->help()Ljava/lang/String;
+                    return-void
+                .end method
+            "#,
+            r#"
+                .class public Lcom/example/util/Helper;
+                .super Ljava/lang/Object;
+
+                .method public static help()Ljava/lang/String;
+                    .locals 1
+                    const-string v0, "help"
+                    return-object v0
+                .end method
+            "#,
+            r#"
+                .class public Lorg/library/Other;
+                .super Ljava/lang/Object;
+
+                .method public static run()V
+                    .locals 0
+                    invoke-static {}, Lcom/example/Main;->main()V
+                    return-void
+                .end method
+            "#,
+        ];
+
+        let mut graph = CallGraph::new(&["com.example".to_string()], None).unwrap();
+        // Writing every entry out to disk shouldn't change the result
+        let mut disk_graph = CallGraph::new(&["com.example".to_string()], Some(0)).unwrap();
+        for class in classes {
+            let class = read_class(class)?;
+            graph.add_class(&class).unwrap();
+            disk_graph.add_class(&class).unwrap();
+        }
+        for write in [CallGraph::write_dot, CallGraph::write_json] {
+            let mut expected = Vec::new();
+            write(&graph, &mut expected).unwrap();
+            let mut actual = Vec::new();
+            write(&disk_graph, &mut actual).unwrap();
+            assert_eq!(String::from_utf8(actual), String::from_utf8(expected));
+        }
+
+        let mut output = Vec::new();
+        graph.write_dot(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"digraph callgraph {
+    node [shape=box];
+    subgraph "cluster_com.example.Main" {
+        label="com.example.Main";
+        "void com.example.Main.main()" [label="main()"];
+    }
+    subgraph "cluster_com.example.util.Helper" {
+        label="com.example.util.Helper";
+        "java.lang.String com.example.util.Helper.help()" [label="help()"];
+    }
+    "void com.example.Main.main()" -> "java.lang.String com.example.util.Helper.help()";
+}
+"#
+        );
+
+        let mut output = Vec::new();
+        graph.write_json(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Callgraph.assert_valid(&output);
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            output["nodes"][1],
+            serde_json::json!({
+                "id": "java.lang.String com.example.util.Helper.help()",
+                "class": "com.example.util.Helper",
+                "label": "help()"
+            })
+        );
+        assert_eq!(
+            output["edges"],
+            serde_json::json!([{
+                "caller": "void com.example.Main.main()",
+                "callee": "java.lang.String com.example.util.Helper.help()"
+            }])
+        );
+
+        Ok(())
+    }
+}
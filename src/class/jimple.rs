@@ -2,70 +2,453 @@ use std::io::Write;
 
 use super::Class;
 use crate::access_flag::AccessFlag;
-use crate::r#type::Type;
+use crate::annotation::Annotation;
+use crate::doc::Doc;
+use crate::field::Field;
+use crate::method::Method;
+use crate::output::{write_jimple_line, OutputOptions};
+
+/// Indentation of continuation lines when wrapping long interface lists.
+const CONTINUATION_INDENT: usize = 8;
 
 impl Class {
-    pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
-        if let Some(source_file) = &self.source_file {
-            writeln!(output, "// source: {}", &source_file)?;
-        }
+    pub fn write_jimple(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        self.write_jimple_nested(output, options, &[])
+    }
 
-        for annotation in &self.annotations {
-            annotation.write_jimple(output, 0)?;
+    /// Writes the class along with the already converted Jimple code of its inner classes,
+    /// which is placed at the end of the class body.
+    pub fn write_jimple_nested(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+        inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error> {
+        let mut header = self.get_jimple_comment_docs();
+        header.extend(self.get_jimple_declaration_docs(options));
+        for doc in header {
+            write_jimple_line(output, 0, doc, options)?;
         }
+        self.write_jimple_body(output, options, &self.fields, &self.methods, inner_classes)
+    }
 
-        AccessFlag::write_jimple_list(output, &self.access_flags)?;
-
-        write!(
-            output,
-            "{} {}",
-            if self.access_flags.contains(&AccessFlag::Interface) {
-                "interface"
-            } else if self.access_flags.contains(&AccessFlag::Annotation) {
-                "@interface"
-            } else if self.access_flags.contains(&AccessFlag::Enum) {
-                "enum"
-            } else {
-                "class"
-            },
-            self.class_type
-        )?;
+    /// Writes the class split into parts of at most `methods_per_part` methods, for classes too
+    /// large to be opened in an editor. The first output contains the fields, inner classes and
+    /// an index of the parts, the others contain the methods under a repeated class declaration.
+    /// Parts are named after `file_stem` as `Foo.part1.jimple` and so on.
+    pub fn write_jimple_parts(
+        &self,
+        options: &OutputOptions,
+        inner_classes: &[Vec<u8>],
+        methods_per_part: usize,
+        file_stem: &str,
+    ) -> Result<Vec<Vec<u8>>, std::io::Error> {
+        let chunks = self
+            .methods
+            .chunks(methods_per_part.max(1))
+            .collect::<Vec<_>>();
+        let part_name = |index: usize| format!("{file_stem}.part{}.jimple", index + 1);
 
-        if let Some(super_class) = &self.super_class {
-            write!(output, " extends {super_class}")?;
+        let mut header = self.get_jimple_comment_docs();
+        header.push(Doc::text(format!(
+            "// methods split into {} parts:",
+            chunks.len()
+        )));
+        for (index, chunk) in chunks.iter().enumerate() {
+            let first = chunk[0].get_signature(&self.class_type);
+            let last = chunk[chunk.len() - 1].get_signature(&self.class_type);
+            header.push(Doc::text(format!(
+                "//     {}: {first} .. {last}",
+                part_name(index)
+            )));
         }
+        header.extend(self.get_jimple_declaration_docs(options));
 
-        if !self.interfaces.is_empty() {
-            let implements = self
-                .interfaces
-                .iter()
-                .map(Type::get_name)
-                .collect::<Vec<_>>();
-            write!(output, " implements {}", implements.join(", "))?;
+        let mut main = Vec::new();
+        for doc in header {
+            write_jimple_line(&mut main, 0, doc, options)?;
+        }
+        self.write_jimple_body(&mut main, options, &self.fields, &[], inner_classes)?;
+
+        let mut result = vec![main];
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut header = vec![Doc::text(format!(
+                "// part {} of {}, fields and inner classes are in {file_stem}.jimple",
+                index + 1,
+                chunks.len()
+            ))];
+            header.extend(self.get_jimple_declaration_docs(options));
+
+            let mut part = Vec::new();
+            for doc in header {
+                write_jimple_line(&mut part, 0, doc, options)?;
+            }
+            self.write_jimple_body(&mut part, options, &[], chunk, &[])?;
+            result.push(part);
         }
-        writeln!(output)?;
-        writeln!(output, "{{")?;
+        Ok(result)
+    }
+
+    fn get_jimple_comment_docs(&self) -> Vec<Doc> {
+        self.source_file
+            .iter()
+            .map(|source_file| format!("// source: {source_file}"))
+            .chain(self.comments.iter().map(|comment| format!("// {comment}")))
+            .map(Doc::text)
+            .collect()
+    }
+
+    fn write_jimple_body(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+        fields: &[Field],
+        methods: &[Method],
+        inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error> {
+        write_jimple_line(output, 0, Doc::text("{"), options)?;
 
         let mut first = true;
-        for field in &self.fields {
+        for field in fields {
             if first {
                 first = false;
             } else {
                 writeln!(output)?;
             }
-            field.write_jimple(output)?;
+            field.write_jimple(output, options)?;
         }
 
-        for method in &self.methods {
+        for method in methods {
             if first {
                 first = false;
             } else {
                 writeln!(output)?;
             }
-            method.write_jimple(output)?;
+            method.write_jimple_member(output, options, Some(&self.class_type))?;
         }
 
-        writeln!(output, "}}")?;
+        // Inner classes are already rendered, they are merely indented
+        for inner_class in inner_classes {
+            if first {
+                first = false;
+            } else {
+                writeln!(output)?;
+            }
+            for line in String::from_utf8_lossy(inner_class).lines() {
+                if line.is_empty() {
+                    writeln!(output)?;
+                } else {
+                    writeln!(output, "    {line}")?;
+                }
+            }
+        }
+
+        write_jimple_line(output, 0, Doc::text("}"), options)
+    }
+
+    /// Writes the public API of the class: public and protected fields along with the
+    /// signatures of public and protected methods. Synthetic members and static initializers
+    /// are omitted.
+    pub fn write_jimple_api(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        let is_api = |flags: &[AccessFlag]| {
+            (flags.contains(&AccessFlag::Public) || flags.contains(&AccessFlag::Protected))
+                && !flags.contains(&AccessFlag::Synthetic)
+        };
+
+        for doc in self.get_jimple_declaration_docs(options) {
+            write_jimple_line(output, 0, doc, options)?;
+        }
+        write_jimple_line(output, 0, Doc::text("{"), options)?;
+        for field in &self.fields {
+            if is_api(&field.visibility) {
+                field.write_jimple(output, options)?;
+            }
+        }
+        for method in &self.methods {
+            if is_api(&method.visibility) && method.name != "<clinit>" {
+                method.write_jimple_declaration(output, options, &self.class_type)?;
+            }
+        }
+        write_jimple_line(output, 0, Doc::text("}"), options)
+    }
+
+    /// Checks whether the class is visible outside its package.
+    pub fn is_public(&self) -> bool {
+        self.access_flags.contains(&AccessFlag::Public)
+            || self.access_flags.contains(&AccessFlag::Protected)
+    }
+
+    /// Builds the lines of the class declaration: its annotations followed by the class
+    /// header. Implemented interfaces go on continuation lines if they don't fit on the line.
+    fn get_jimple_declaration_docs(&self, options: &OutputOptions) -> Vec<Doc> {
+        let kind = if self.access_flags.contains(&AccessFlag::Interface) {
+            "interface"
+        } else if self.access_flags.contains(&AccessFlag::Annotation) {
+            "@interface"
+        } else if self.access_flags.contains(&AccessFlag::Enum) {
+            "enum"
+        } else {
+            "class"
+        };
+        let mut header = vec![Doc::text(format!(
+            "{}{kind} {}",
+            AccessFlag::get_jimple_list(&self.access_flags),
+            self.class_type
+        ))];
+
+        if let Some(super_class) = &self.super_class {
+            header.push(Doc::text(format!(" extends {super_class}")));
+        }
+
+        if !self.interfaces.is_empty() {
+            header.push(Doc::text(" implements"));
+            header.push(Doc::group(Doc::nest(
+                CONTINUATION_INDENT,
+                Doc::Concat(vec![
+                    Doc::Break(" "),
+                    Doc::join(
+                        self.interfaces
+                            .iter()
+                            .map(|interface| Doc::text(interface.to_string())),
+                        &Doc::Concat(vec![Doc::text(","), Doc::Break(" ")]),
+                    ),
+                ]),
+            )));
+        }
+
+        let mut result = Annotation::get_jimple_list_docs(&self.annotations, options);
+        result.push(Doc::Concat(header));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::r#type::Type;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn write_api() -> Result<(), ParseErrorDisplayed> {
+        let class = read_class(
+            r#"
+                .class public Lcom/example/Api;
+                .super Ljava/lang/Object;
+
+                .field public static final VERSION:I = 0x2
+
+                .field private secret:Ljava/lang/String;
+
+                .method static constructor <clinit>()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public constructor <init>()V
+                    .locals 0
+                    invoke-direct {p0}, Ljava/lang/Object;-><init>()V
+                    return-void
+                .end method
+
+                .method protected run(I)Z
+                    .annotation system Ldalvik/annotation/Throws;
+                        value = {
+                            Ljava/io/IOException;
+                        }
+                    .end annotation
+
+                    .locals 1
+                    const/4 v0, 0x1
+                    return v0
+                .end method
+
+                .method private helper()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public synthetic access$000()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+        assert!(class.is_public());
+
+        let mut output = Vec::new();
+        class
+            .write_jimple_api(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"public class com.example.Api
+{
+    public static final int VERSION = 0x2;
+    public Api();
+    protected bool run(int @p0) throws java.io.IOException;
+}
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_nested() -> Result<(), ParseErrorDisplayed> {
+        let outer = read_class(
+            r#"
+                .class public Lcom/example/Outer;
+                .super Ljava/lang/Object;
+
+                .field private value:I
+
+                .method static constructor <clinit>()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public constructor <init>(I)V
+                    .locals 0
+                    invoke-direct {p0}, Ljava/lang/Object;-><init>()V
+                    return-void
+                .end method
+            "#,
+        )?;
+        let inner = read_class(
+            r#"
+                .class Lcom/example/Outer$Inner;
+                .super Ljava/lang/Object;
+
+                .annotation system Ldalvik/annotation/EnclosingClass;
+                    value = Lcom/example/Outer;
+                .end annotation
+
+                .annotation system Ldalvik/annotation/InnerClass;
+                    accessFlags = 0x0
+                    name = "Inner"
+                .end annotation
+            "#,
+        )?;
+        assert_eq!(outer.get_outer_class(), None);
+        assert_eq!(
+            inner.get_outer_class(),
+            Some(Type::Object("com.example.Outer".to_string()))
+        );
+
+        let options = OutputOptions::default();
+        let mut nested = Vec::new();
+        inner.write_jimple(&mut nested, &options).unwrap();
+        let mut output = Vec::new();
+        outer
+            .write_jimple_nested(&mut output, &options, &[nested])
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"public class com.example.Outer
+{
+    private int value;
+
+    static
+    {
+        return;
+    }
+
+    public Outer(int @p0)
+    {
+        invoke-direct p0.<void java.lang.Object.<init>()>();
+        return;
+    }
+
+    // enclosing class: com.example.Outer
+    // inner class: Inner
+    class com.example.Outer$Inner
+    {
+    }
+}
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_parts() -> Result<(), ParseErrorDisplayed> {
+        let class = read_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field private value:I
+
+                .method static b()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method static c(I)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method static d()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let parts = class
+            .write_jimple_parts(&OutputOptions::default(), &[], 2, "a")
+            .unwrap()
+            .into_iter()
+            .map(|part| String::from_utf8(part).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            vec![
+                r#"// methods split into 2 parts:
+//     a.part1.jimple: void a.b() .. void a.c(int)
+//     a.part2.jimple: void a.d() .. void a.d()
+public class a
+{
+    private int value;
+}
+"#,
+                r#"// part 1 of 2, fields and inner classes are in a.jimple
+public class a
+{
+    static void b()
+    {
+        return;
+    }
+
+    static void c(int @p0)
+    {
+        return;
+    }
+}
+"#,
+                r#"// part 2 of 2, fields and inner classes are in a.jimple
+public class a
+{
+    static void d()
+    {
+        return;
+    }
+}
+"#,
+            ]
+        );
+
         Ok(())
     }
 }
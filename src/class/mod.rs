@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crate::access_flag::AccessFlag;
 use crate::annotation::Annotation;
 use crate::field::Field;
@@ -7,6 +9,21 @@ use crate::r#type::Type;
 mod jimple;
 mod smali;
 
+pub use smali::FormatResult;
+
+/// Directive that a comment in Smali code outside of methods is written above.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentAnchor {
+    Super,
+    Source,
+    Interface(usize),
+    Annotation(usize),
+    Field(usize),
+    Method(usize),
+    /// End of the file, after all directives
+    End,
+}
+
 #[derive(Debug)]
 pub struct Class {
     pub class_type: Type,
@@ -15,14 +32,31 @@ pub struct Class {
     pub interfaces: Vec<Type>,
     pub source_file: Option<String>,
     pub annotations: Vec<Annotation>,
+    /// Comments to be written above the class declaration
+    pub comments: Vec<String>,
+    /// Comments found in Smali code outside of methods, kept to write them back
+    pub smali_comments: Vec<(CommentAnchor, String)>,
     pub fields: Vec<Field>,
     pub methods: Vec<Method>,
 }
 
 impl Class {
+    /// Returns the outer class if this is an inner class.
+    pub fn get_outer_class(&self) -> Option<Type> {
+        Annotation::get_enclosing_class(&self.annotations)
+    }
+
     pub fn optimize(&mut self) {
         for method in &mut self.methods {
             method.optimize();
         }
     }
+
+    /// Optimizes the methods, leaving those unoptimized whose optimization exceeds the time
+    /// limit. See [`Method::optimize_within`].
+    pub fn optimize_within(&mut self, limit: Duration) {
+        for method in &mut self.methods {
+            method.optimize_within(limit);
+        }
+    }
 }
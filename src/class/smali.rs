@@ -1,14 +1,151 @@
-use super::Class;
+use std::io::Write;
+
+use super::{Class, CommentAnchor};
 use crate::access_flag::AccessFlag;
 use crate::annotation::Annotation;
 use crate::error::ParseError;
 use crate::field::Field;
 use crate::literal::Literal;
 use crate::method::Method;
-use crate::r#type::Type;
-use crate::tokenizer::Tokenizer;
+use crate::r#type::{CallSignature, Type};
+use crate::tokenizer::{find_comments, Tokenizer};
+
+/// Outcome of [`Class::format_smali`].
+#[derive(Debug, PartialEq)]
+pub enum FormatResult {
+    /// The file is formatted already
+    Unchanged,
+    /// The file needs formatting, this is the formatted code
+    Formatted(Vec<u8>),
+    /// Formatting would drop comments from these lines, so the file has to be left alone
+    DropsComments(Vec<usize>),
+}
 
 impl Class {
+    /// Writes the comments anchored above a directive. A blank line is added before them if
+    /// the directive isn't separated from the previous one already.
+    fn write_smali_comments(
+        &self,
+        output: &mut dyn Write,
+        anchor: CommentAnchor,
+        separated: bool,
+    ) -> Result<(), std::io::Error> {
+        let mut comments = self
+            .smali_comments
+            .iter()
+            .filter(|(a, _)| *a == anchor)
+            .peekable();
+        if comments.peek().is_some() && !separated {
+            writeln!(output)?;
+        }
+        for (_, comment) in comments {
+            writeln!(output, "# {comment}")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_smali(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        write!(output, ".class ")?;
+        for flag in &self.access_flags {
+            write!(output, "{flag} ")?;
+        }
+        writeln!(output, "{}", self.class_type.to_descriptor())?;
+        self.write_smali_comments(output, CommentAnchor::Super, false)?;
+
+        let super_class = match &self.super_class {
+            Some(super_class) => super_class.clone(),
+            None if self.access_flags.contains(&AccessFlag::Enum) => {
+                Type::Object("java.lang.Enum".to_string())
+            }
+            None => Type::Object("java.lang.Object".to_string()),
+        };
+        writeln!(output, ".super {}", super_class.to_descriptor())?;
+        if let Some(source_file) = &self.source_file {
+            self.write_smali_comments(output, CommentAnchor::Source, false)?;
+            writeln!(output, ".source \"{source_file}\"")?;
+        }
+        for (i, interface) in self.interfaces.iter().enumerate() {
+            self.write_smali_comments(output, CommentAnchor::Interface(i), false)?;
+            writeln!(output, ".implements {}", interface.to_descriptor())?;
+        }
+
+        for (i, annotation) in self.annotations.iter().enumerate() {
+            writeln!(output)?;
+            self.write_smali_comments(output, CommentAnchor::Annotation(i), true)?;
+            annotation.write_smali(output, "")?;
+        }
+        for (i, field) in self.fields.iter().enumerate() {
+            writeln!(output)?;
+            self.write_smali_comments(output, CommentAnchor::Field(i), true)?;
+            field.write_smali(output)?;
+        }
+        for (i, method) in self.methods.iter().enumerate() {
+            writeln!(output)?;
+            self.write_smali_comments(output, CommentAnchor::Method(i), true)?;
+            method.write_smali(output)?;
+        }
+        self.write_smali_comments(output, CommentAnchor::End, false)?;
+        Ok(())
+    }
+
+    /// Reformats Smali code the way [`Class::write_smali`] writes it, keeping comments. Only
+    /// comments on `.param`, `.end local` lines and in data blocks are left out, baksmali
+    /// merely annotates the code there. Should any other comment get lost, the code is
+    /// rather left unformatted.
+    pub fn format_smali(input: &Tokenizer) -> Result<FormatResult, ParseError> {
+        let input = input.clone().with_trailing_comments();
+        let (_, class) = Self::read(&input)?;
+
+        let mut output = Vec::new();
+        class
+            .write_smali(&mut output)
+            .expect("writing to a buffer cannot fail");
+        if output == input.content().as_bytes() {
+            return Ok(FormatResult::Unchanged);
+        }
+
+        let mut kept = find_comments(std::str::from_utf8(&output).unwrap_or_default())
+            .into_iter()
+            .map(|(_, comment)| comment)
+            .collect::<Vec<_>>();
+        let mut in_data = false;
+        let ignored = input
+            .content()
+            .lines()
+            .map(|line| {
+                let line = line.trim_start();
+                if [".array-data", ".packed-switch", ".sparse-switch"]
+                    .iter()
+                    .any(|directive| line.starts_with(directive))
+                {
+                    in_data = true;
+                } else if line.starts_with(".end ") {
+                    in_data = false;
+                    return line.starts_with(".end local");
+                }
+                in_data || line.starts_with(".param")
+            })
+            .collect::<Vec<_>>();
+        let dropped = find_comments(input.content())
+            .into_iter()
+            .filter(|(line, comment)| {
+                if let Some(index) = kept.iter().position(|kept| kept == comment) {
+                    kept.swap_remove(index);
+                    false
+                } else {
+                    !ignored[line - 1]
+                }
+            })
+            .map(|(line, _)| line)
+            .collect::<Vec<_>>();
+
+        if dropped.is_empty() {
+            Ok(FormatResult::Formatted(output))
+        } else {
+            Ok(FormatResult::DropsComments(dropped))
+        }
+    }
+
     fn read_super_class(input: &Tokenizer) -> Result<(Tokenizer, Option<Type>), ParseError> {
         let (input, super_class) = Type::read(input)?;
         let input = input.expect_eol()?;
@@ -40,6 +177,15 @@ impl Class {
     }
 
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        Self::read_filtered(input, |_, _| true)
+    }
+
+    /// Reads a class, keeping only the methods accepted by the filter. Code of other methods
+    /// is skipped without parsing it.
+    pub fn read_filtered(
+        input: &Tokenizer,
+        filter: impl Fn(&str, &CallSignature) -> bool,
+    ) -> Result<(Tokenizer, Self), ParseError> {
         let input = input.expect_directive("class")?;
         let (input, access_flags) = AccessFlag::read_list(&input);
         let (input, class_type) = Type::read(&input)?;
@@ -52,11 +198,27 @@ impl Class {
         let mut annotations = Vec::new();
         let mut fields = Vec::new();
         let mut methods = Vec::new();
+        let mut smali_comments = Vec::new();
         while input.expect_eof().is_err() {
             let (i, directive) = input.read_directive()?;
             let start = input;
             input = i;
 
+            let anchor = match directive.as_str() {
+                "super" => CommentAnchor::Super,
+                "source" => CommentAnchor::Source,
+                "implements" => CommentAnchor::Interface(interfaces.len()),
+                "annotation" => CommentAnchor::Annotation(annotations.len()),
+                "field" => CommentAnchor::Field(fields.len()),
+                _ => CommentAnchor::Method(methods.len()),
+            };
+            smali_comments.extend(
+                start
+                    .preceding_comments()
+                    .into_iter()
+                    .map(|comment| (anchor, comment)),
+            );
+
             match directive.as_str() {
                 "super" => {
                     (input, super_class) = Self::read_super_class(&input)?;
@@ -82,13 +244,24 @@ impl Class {
                     fields.push(field);
                 }
                 "method" => {
-                    let method;
-                    (input, method) = Method::read(&input)?;
-                    methods.push(method);
+                    let (name, call_signature) = Method::read_signature(&input)?;
+                    if filter(&name, &call_signature) {
+                        let method;
+                        (input, method) = Method::read(&input)?;
+                        methods.push(method);
+                    } else {
+                        input = input.skip_block("method")?;
+                    }
                 }
                 _ => return Err(start.unexpected("a supported directive".into())),
             };
         }
+        smali_comments.extend(
+            input
+                .preceding_comments()
+                .into_iter()
+                .map(|comment| (CommentAnchor::End, comment)),
+        );
 
         Ok((
             input,
@@ -99,6 +272,8 @@ impl Class {
                 interfaces,
                 source_file,
                 annotations,
+                comments: Vec::new(),
+                smali_comments,
                 fields,
                 methods,
             },
@@ -192,4 +367,175 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn write_smali() -> Result<(), ParseErrorDisplayed> {
+        let smali = r#".class public final enum La/b;
+.super Ljava/lang/Enum;
+.source "b.java"
+.implements Ljava/lang/Runnable;
+
+.annotation runtime La/c;
+    values = {
+        .enum La/b;->X:La/b;,
+        .subannotation La/d;
+            flag = true
+            sizes = {}
+        .end subannotation
+    }
+.end annotation
+
+.field private static final X:F = 1.0e20f
+    .annotation build La/e;
+        value = 0x1t
+    .end annotation
+.end field
+
+.field static Y:D = -Infinity
+
+.method public static run(IJ[Ljava/lang/String;)D
+    .locals 2
+    .param p3
+        .annotation runtime La/f;
+            value = 'x'
+        .end annotation
+    .end param
+    # leading comment
+    .line 12
+    .local p0, "count":I
+    :try_start_0
+    const-wide/16 v0, -0x2L
+    packed-switch p0, :pswitch_data_0
+    invoke-static {p0 .. p3}, La/b;->run(IJ[Ljava/lang/String;)D
+    move-result-wide v0
+    :try_end_0
+    .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :catch_0
+    .catchall {:try_start_0 .. :try_end_0} :catch_0
+    :catch_0
+    const-wide v0, 0x7ff0000000000000L
+    return-wide v0
+    :pswitch_data_0
+    .packed-switch 0x1
+        :catch_0
+    .end packed-switch
+    :array_0
+    .array-data 2
+        0x22s
+        -0x1s
+    .end array-data
+.end method
+"#;
+        let (_, class) = Class::read(&tokenizer(smali))?;
+
+        let mut output = Vec::new();
+        class.write_smali(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), smali);
+
+        Ok(())
+    }
+
+    #[test]
+    fn windows_line_endings() -> Result<(), ParseErrorDisplayed> {
+        let smali = ".class public La;\n.super Ljava/lang/Object;\n\n.method public static run()V\n    .locals 0\n    # comment\n    return-void\n.end method\n";
+        let (_, class) = Class::read(&tokenizer(
+            &("\u{feff}".to_string() + &smali.replace('\n', "\r\n")),
+        ))?;
+
+        let mut output = Vec::new();
+        class.write_smali(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), smali);
+
+        Ok(())
+    }
+
+    #[test]
+    fn format_smali() -> Result<(), ParseErrorDisplayed> {
+        let formatted = r#".class public La;
+.super Ljava/lang/Object;
+
+# interfaces
+.implements Ljava/lang/Runnable;
+
+# NOTE: patched to always enable debug mode
+# static fields
+.field static a:I
+
+# direct methods
+.method public static b(Ljava/lang/String;)Z
+    .locals 2
+    .param p0, "s"
+    .end param
+    # force it
+    const/4 v0, 0x1    # patched
+    const v1, 0x3f800000    # 1.0f
+    .array-data 4
+        0x3f800000
+    .end array-data
+    return v0
+.end method
+
+# trailing note
+"#;
+        assert_eq!(
+            Class::format_smali(&tokenizer(formatted))?,
+            FormatResult::Unchanged
+        );
+
+        let unformatted = formatted
+            .replace("# NOTE", "\n\n# NOTE")
+            .replace(".locals 2", ".locals 2 ")
+            .replace(
+                "    .param p0, \"s\"\n    .end param",
+                "    .param p0, \"s\"    # Ljava/lang/String;",
+            )
+            .replace("0x1    # patched", "0x1 # patched")
+            .replace("0x3f800000\n", "0x3f800000    # 1.0f\n")
+            .replace(".end method\n", ".end method\n\n");
+        assert_eq!(
+            Class::format_smali(&tokenizer(&unformatted))?,
+            FormatResult::Formatted(formatted.as_bytes().to_vec())
+        );
+
+        let unsupported = formatted.replace(".field static a:I", ".field static a:I # keep");
+        assert_eq!(
+            Class::format_smali(&tokenizer(&unsupported))?,
+            FormatResult::DropsComments(vec![9])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_filtered() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public constructor <init>()V
+                    .locals 0
+                    this is not valid code
+                .end method
+
+                .method public static b(I)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public static b(J)V
+                    .locals 0
+                    return-void
+                .end method
+            "#
+            .trim(),
+        );
+        let (input, class) = Class::read_filtered(&input, |name, call_signature| {
+            name == "b" && call_signature.parameter_types == [Type::Long]
+        })?;
+        assert!(input.expect_eof().is_ok());
+        assert_eq!(class.methods.len(), 1);
+        assert_eq!(class.methods[0].parameters[0].parameter_type, Type::Long);
+
+        Ok(())
+    }
 }
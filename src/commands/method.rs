@@ -0,0 +1,243 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::class::Class;
+use crate::method::{GraphFormat, Method};
+use crate::output::OutputOptions;
+use crate::pipeline::{find_class_file, read_method};
+use crate::r#type::{MethodSignature, Type};
+use crate::stack_trace::{self, Frame, Mapping};
+use crate::tokenizer::Tokenizer;
+
+/// Reads and optimizes a single method given by its Smali or Jimple signature from a directory
+/// of Smali files.
+pub fn load_method(dir: &Path, method: &str) -> Result<Method, String> {
+    let mut method = read_method(dir, &method.parse::<MethodSignature>()?)?;
+    method.optimize();
+    Ok(method)
+}
+
+/// Writes the Jimple code of a single method.
+pub fn extract_method(
+    dir: &Path,
+    method: &str,
+    options: &OutputOptions,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    load_method(dir, method)?
+        .write_jimple(output, options)
+        .map_err(|error| format!("Failed writing method: {error}"))
+}
+
+/// Writes the commands of a method as parsed along with their IDs as JSON.
+pub fn write_instructions(dir: &Path, method: &str, output: &mut dyn Write) -> Result<(), String> {
+    let signature = method.parse::<MethodSignature>()?;
+    read_method(dir, &signature)?
+        .write_instructions_json(&signature.object_type, output)
+        .map_err(|error| format!("Failed writing instructions: {error}"))
+}
+
+/// Writes the dominator and post-dominator trees of a method's basic blocks.
+pub fn write_dominators(
+    dir: &Path,
+    method: &str,
+    format: GraphFormat,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let trees = load_method(dir, method)?.get_dominator_trees();
+    let result = match format {
+        GraphFormat::Dot => trees.write_dot(output),
+        GraphFormat::Json => trees.write_json(output),
+    };
+    result.map_err(|error| format!("Failed writing dominator trees: {error}"))
+}
+
+/// Reasons a stack frame cannot be annotated with code.
+enum FrameError {
+    /// Code isn't available, e.g. for framework classes
+    Unavailable(String),
+    /// The class is there but none of the methods can be chosen with certainty
+    Unresolved(String),
+}
+
+/// Decompiles the method referenced by a stack frame, choosing among overloads by line number.
+fn decompile_frame(
+    dir: &Path,
+    frame: &Frame,
+    options: &OutputOptions,
+) -> Result<String, FrameError> {
+    let class_type = Type::Object(frame.class_name.clone());
+    let path = find_class_file(dir, &class_type).ok_or_else(|| {
+        FrameError::Unavailable(format!("Could not find class {}", frame.class_name))
+    })?;
+    let input =
+        Tokenizer::from_file(&path).map_err(|error| FrameError::Unavailable(error.to_string()))?;
+    let (_, mut class) = Class::read_filtered(&input, |name, _| name == frame.method_name)
+        .map_err(|error| FrameError::Unavailable(error.to_string()))?;
+    class.optimize();
+
+    let method = match (class.methods.as_slice(), frame.line) {
+        ([], _) => {
+            return Err(FrameError::Unavailable(format!(
+                "Could not find method {} in {}",
+                frame.method_name,
+                path.display()
+            )))
+        }
+        (methods, Some(line)) => methods
+            .iter()
+            .find(|method| {
+                method
+                    .get_line_range()
+                    .is_some_and(|(from, to)| from <= line && line <= to)
+            })
+            .ok_or_else(|| {
+                FrameError::Unresolved(format!(
+                    "No method of {} covers line {line}",
+                    frame.class_name
+                ))
+            })?,
+        ([method], None) => method,
+        (_, None) => {
+            return Err(FrameError::Unresolved(format!(
+                "Multiple methods of {} are named {} and the frame has no line number",
+                frame.class_name, frame.method_name
+            )))
+        }
+    };
+
+    let mut code = Vec::new();
+    method
+        .write_jimple(&mut code, options)
+        .map_err(|error| FrameError::Unavailable(error.to_string()))?;
+    let code = String::from_utf8_lossy(&code);
+    Ok(frame
+        .line
+        .and_then(|line| stack_trace::excerpt(&code, line))
+        .unwrap_or_else(|| code.into_owned()))
+}
+
+/// Writes the annotated stack trace, returning the first frame that couldn't be resolved to a
+/// single method.
+fn write_annotated_frames(
+    dir: &Path,
+    trace: &str,
+    mapping: &Mapping,
+    options: &OutputOptions,
+    output: &mut dyn Write,
+) -> std::io::Result<Option<String>> {
+    let mut unresolved = None;
+    for line in trace.lines() {
+        writeln!(output, "{line}")?;
+        let Some(frame) = Frame::parse(line) else {
+            continue;
+        };
+
+        if let Some(original) = mapping.deobfuscate(&frame) {
+            write!(
+                output,
+                "    // {}.{}",
+                original.class_name, original.method_name
+            )?;
+            if let Some(line) = original.line {
+                write!(output, ":{line}")?;
+            }
+            writeln!(output)?;
+        }
+
+        let error = match decompile_frame(dir, &frame, options) {
+            Ok(code) => {
+                writeln!(output, "{code}")?;
+                continue;
+            }
+            Err(FrameError::Unavailable(error)) => error,
+            Err(FrameError::Unresolved(error)) => {
+                unresolved.get_or_insert_with(|| error.clone());
+                error
+            }
+        };
+        for line in error.lines() {
+            writeln!(output, "    // {line}")?;
+        }
+        writeln!(output)?;
+    }
+    Ok(unresolved)
+}
+
+/// Copies a Java stack trace, following each frame by the original name if the mapping knows
+/// it and by the decompiled code of the line. Fails after writing the entire trace if a frame
+/// doesn't determine which method of its class to show.
+pub fn annotate_stack_trace(
+    dir: &Path,
+    trace: &str,
+    mapping: &Mapping,
+    options: &OutputOptions,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    match write_annotated_frames(dir, trace, mapping, options, output) {
+        Ok(None) => Ok(()),
+        Ok(Some(error)) => Err(error),
+        Err(error) => Err(format!("Failed writing stack trace: {error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotate_stack_trace() {
+        let dir = std::env::temp_dir().join(format!("aarf-stack-trace-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.smali"),
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static run()V
+                    .locals 0
+                    .line 10
+                    return-void
+                .end method
+
+                .method static run(I)V
+                    .locals 0
+                    .line 20
+                    return-void
+                .end method
+            "#
+            .trim(),
+        )
+        .unwrap();
+
+        let trace = "java.lang.RuntimeException\n    at a.run(a.java:20)\n";
+        let mut output = Vec::new();
+        let result = super::annotate_stack_trace(
+            &dir,
+            trace,
+            &Mapping::default(),
+            &OutputOptions::default(),
+            &mut output,
+        );
+        assert_eq!(result, Ok(()));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("// line 20"), "{output}");
+        assert!(!output.contains("// line 10"), "{output}");
+
+        // A line outside of all overloads doesn't select any of them
+        let trace = "java.lang.RuntimeException\n    at a.run(a.java:30)\n";
+        let mut output = Vec::new();
+        let result = super::annotate_stack_trace(
+            &dir,
+            trace,
+            &Mapping::default(),
+            &OutputOptions::default(),
+            &mut output,
+        );
+        assert_eq!(result, Err("No method of a covers line 30".to_string()));
+        assert!(!String::from_utf8(output).unwrap().contains("return"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,6 @@
+//! Drivers of the individual commands, reading a directory of Smali files and writing the
+//! results to the given output.
+
+pub mod method;
+pub mod reports;
+pub mod smali;
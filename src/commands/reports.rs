@@ -0,0 +1,476 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::callgraph::CallGraph;
+use crate::deep_links::{self, DeepLinkReport};
+use crate::dex_budget::DexBudgetReport;
+use crate::dynamic_code::DynamicCodeReport;
+use crate::endpoints::EndpointReport;
+use crate::features::{FeatureFormat, MethodFeatures};
+use crate::implementations::InterfaceIndex;
+use crate::index::Index;
+use crate::keep_rules::{parse_rules, KeptReport};
+use crate::method::GraphFormat;
+use crate::output::{FileHeader, OutputOptions, ReportFormat};
+use crate::permissions::{self, PermissionReport};
+use crate::pipeline::{build_hierarchy, for_each_class, for_each_class_file, ClassSource};
+use crate::protections::ProtectionReport;
+use crate::scope::Scope;
+use crate::search::{self, Criterion};
+use crate::sql::SqlReport;
+use crate::stats::ProgramStats;
+use crate::strings::{self, StringTable};
+use crate::suspicious::SuspiciousCallReport;
+use crate::unused::UnusedMemberReport;
+use crate::webview::WebViewReport;
+use crate::xrefs::{self, Member};
+
+fn report_error(error: std::io::Error) -> String {
+    format!("Failed writing report: {error}")
+}
+
+fn read_manifest(dir: &Path) -> Result<String, String> {
+    let manifest_path = dir.join("AndroidManifest.xml");
+    std::fs::read_to_string(&manifest_path)
+        .map_err(|error| format!("Failed reading {}: {error}", manifest_path.display()))
+}
+
+/// Writes all references to a class member, returning their number. For JSON output, the
+/// references are collected in an index, kept on disk if a memory limit is given.
+pub fn find_xrefs(
+    dir: &Path,
+    scope: &Scope,
+    member: &Member,
+    format: ReportFormat,
+    index_memory_limit: Option<usize>,
+    output: &mut dyn Write,
+) -> Result<usize, String> {
+    let mut index = match format {
+        ReportFormat::Text => None,
+        ReportFormat::Json => Some(
+            Index::new(index_memory_limit)
+                .map_err(|error| format!("Failed creating index: {error}"))?,
+        ),
+    };
+    let mut result = Ok(());
+    let mut count = 0;
+    // Unoptimized code still contains calls that optimizations turn into other statements
+    for_each_class(dir, scope, |class| {
+        for reference in xrefs::find_references(&class, member) {
+            if result.is_ok() {
+                result = match &mut index {
+                    Some(index) => xrefs::add_reference_to_index(index, count, &reference),
+                    None => xrefs::write_reference(output, &reference),
+                };
+            }
+            count += 1;
+        }
+    });
+    if let Some(index) = &index {
+        result = result.and_then(|_| xrefs::write_indexed_references_json(output, member, index));
+    }
+    result.map_err(|error| format!("Failed writing references: {error}"))?;
+    Ok(count)
+}
+
+/// Writes the code locations matching all of the criteria, returning their number. Literal
+/// filters are regular expressions, the other patterns are matched as described for
+/// `Criterion`.
+pub fn search_code(
+    dir: &Path,
+    scope: &Scope,
+    calls: &[String],
+    fields: &[String],
+    literals: &[String],
+    annotations: &[String],
+    output: &mut dyn Write,
+) -> Result<usize, String> {
+    let literals = literals
+        .iter()
+        .map(|literal| regex::Regex::new(literal))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| format!("Invalid literal filter: {error}"))?;
+    let literal_filters = literals
+        .iter()
+        .map(|literal| move |value: &str| literal.is_match(value))
+        .collect::<Vec<_>>();
+    let criteria = calls
+        .iter()
+        .map(|pattern| Criterion::Call(pattern))
+        .chain(fields.iter().map(|pattern| Criterion::Field(pattern)))
+        .chain(
+            literal_filters
+                .iter()
+                .map(|filter| Criterion::Literal(filter)),
+        )
+        .chain(
+            annotations
+                .iter()
+                .map(|pattern| Criterion::Annotation(pattern)),
+        )
+        .collect::<Vec<_>>();
+    if criteria.is_empty() {
+        return Err("Specify at least one of --call, --field, --literal or --annotation.".into());
+    }
+
+    let mut result = Ok(());
+    let mut count = 0;
+    // Unoptimized code still contains calls that optimizations turn into other statements
+    for_each_class(dir, scope, |class| {
+        for hit in search::search(&class, &criteria) {
+            count += 1;
+            if result.is_ok() {
+                result = search::write_hit(output, &hit);
+            }
+        }
+    });
+    result.map_err(|error| format!("Failed writing matches: {error}"))?;
+    Ok(count)
+}
+
+/// Compares the permissions requested in `AndroidManifest.xml` to the APIs used by the code.
+pub fn report_permissions(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let requested = permissions::read_manifest_permissions(&read_manifest(dir)?);
+    let mut report = PermissionReport::new(requested);
+    for_each_class(dir, scope, |class| report.add_class(&class));
+    report.write(output).map_err(report_error)
+}
+
+/// Writes the declarations of all public classes, optionally limited to some packages.
+pub fn write_api(
+    dir: &Path,
+    scope: &Scope,
+    packages: &[String],
+    options: &OutputOptions,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut result = Ok(());
+    let mut first = true;
+    for_each_class(dir, scope, |class| {
+        let name = class.class_type.to_string();
+        let selected = packages.is_empty()
+            || packages
+                .iter()
+                .any(|package| name.starts_with(&format!("{package}.")));
+        if result.is_err() || !selected || !class.is_public() {
+            return;
+        }
+
+        if first {
+            first = false;
+        } else {
+            result = writeln!(output);
+        }
+        if result.is_ok() {
+            result = class.write_jimple_api(output, options);
+        }
+    });
+    result.map_err(|error| format!("Failed writing API: {error}"))
+}
+
+/// Writes the features of all methods, one record per method.
+pub fn write_features(
+    dir: &Path,
+    scope: &Scope,
+    format: FeatureFormat,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut result = match format {
+        FeatureFormat::Csv => MethodFeatures::write_csv_header(output),
+        FeatureFormat::Ndjson => Ok(()),
+    };
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        for method in &class.methods {
+            let features = MethodFeatures::new(&class, method);
+            if result.is_ok() {
+                result = match format {
+                    FeatureFormat::Csv => features.write_csv(output),
+                    FeatureFormat::Ndjson => features.write_json(output),
+                };
+            }
+        }
+    });
+    result.map_err(|error| format!("Failed writing features: {error}"))
+}
+
+/// Lists the link handlers declared in `AndroidManifest.xml` along with the code processing
+/// their data.
+pub fn report_deep_links(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let handlers = deep_links::read_manifest_link_handlers(&read_manifest(dir)?);
+    let mut report = DeepLinkReport::new(handlers);
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists URLs, host names and IP addresses found in the code.
+pub fn report_endpoints(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let mut report = EndpointReport::new();
+    for_each_class(dir, scope, |mut class| {
+        // Reconstructed hidden strings should be considered as well
+        class.optimize();
+        report.add_class(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists WebView configuration and JavaScript interfaces.
+pub fn report_webview(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let mut report = WebViewReport::new();
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists code loaded dynamically, either as a report or as a graph in DOT format.
+pub fn report_dynamic_code(
+    dir: &Path,
+    scope: &Scope,
+    graph: bool,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut report = DynamicCodeReport::new();
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    if graph {
+        report.write_dot(output).map_err(report_error)
+    } else {
+        report.write(output).map_err(report_error)
+    }
+}
+
+/// Lists calls of security-relevant APIs.
+pub fn report_api_calls(
+    dir: &Path,
+    scope: &Scope,
+    format: ReportFormat,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut report = SuspiciousCallReport::new();
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    match format {
+        ReportFormat::Text => report.write(output),
+        ReportFormat::Json => report.write_json(output),
+    }
+    .map_err(report_error)
+}
+
+/// Writes the call graph of the methods, optionally limited to some packages. Devirtualizing
+/// resolves interface calls to the implementations where these are unambiguous. The graph is
+/// kept on disk if a memory limit is given.
+pub fn write_callgraph(
+    dir: &Path,
+    scope: &Scope,
+    packages: &[String],
+    devirtualize: bool,
+    format: GraphFormat,
+    index_memory_limit: Option<usize>,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let hierarchy = devirtualize.then(|| build_hierarchy(ClassSource::Smali(dir)));
+    let mut graph = CallGraph::new(packages, index_memory_limit)
+        .map_err(|error| format!("Failed creating index: {error}"))?;
+    let mut result = Ok(());
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        if let Some(hierarchy) = &hierarchy {
+            hierarchy.devirtualize(&mut class);
+        }
+        if result.is_ok() {
+            result = graph.add_class(&class);
+        }
+    });
+    result
+        .and_then(|_| match format {
+            GraphFormat::Dot => graph.write_dot(output),
+            GraphFormat::Json => graph.write_json(output),
+        })
+        .map_err(|error| format!("Failed writing call graph: {error}"))
+}
+
+/// Reports how close each dex file is to the method and field reference limits.
+pub fn report_dex_budget(
+    dir: &Path,
+    depth: usize,
+    limit: usize,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut report = DexBudgetReport::new();
+    // The limit applies to entire dex files, so the scope doesn't apply
+    for_each_class_file(dir, &Scope::default(), |path, class| {
+        // Without apktool's smali_classes* directories, the code is assumed to be a single dex
+        let dex_file = FileHeader::dex_file(path.strip_prefix(dir).unwrap_or(path))
+            .unwrap_or_else(|| "classes.dex".to_string());
+        report.add_class(&dex_file, &class);
+    });
+    report.write(output, depth, limit).map_err(report_error)
+}
+
+/// Writes statistics on classes, methods and instructions.
+pub fn report_stats(
+    dir: &Path,
+    scope: &Scope,
+    limit: usize,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut stats = ProgramStats::new();
+    for_each_class(dir, scope, |mut class| {
+        // Counts reflect the Smali code as is, complexity the code after optimizations
+        stats.add_class(&class);
+        class.optimize();
+        stats.add_complexity(&class);
+    });
+    stats.write(output, limit).map_err(report_error)
+}
+
+/// Lists the implementations of interfaces declared within the scope.
+pub fn report_implementations(
+    dir: &Path,
+    scope: &Scope,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut index = InterfaceIndex::new();
+    // Implementations outside the scope count as well
+    for_each_class(dir, &Scope::default(), |class| {
+        if scope.contains_type(&class.class_type) {
+            index.add_declarations(&class);
+        }
+        index.add_references(&class);
+    });
+    index.write(output).map_err(report_error)
+}
+
+/// Lists members declared within the scope that are never referenced.
+pub fn report_unused_members(
+    dir: &Path,
+    scope: &Scope,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let mut report = UnusedMemberReport::new();
+    // References from classes outside the scope count as well
+    for_each_class(dir, &Scope::default(), |mut class| {
+        class.optimize();
+        if scope.contains_type(&class.class_type) {
+            report.add_declarations(&class);
+        }
+        report.add_references(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists unreferenced members kept only because of ProGuard rules read from a file.
+pub fn report_kept_members(
+    dir: &Path,
+    rules: Option<&Path>,
+    scope: &Scope,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let rules = match rules {
+        Some(path) => parse_rules(
+            &std::fs::read_to_string(path)
+                .map_err(|error| format!("Failed reading {}: {error}", path.display()))?,
+        ),
+        None => Vec::new(),
+    };
+    let mut report = KeptReport::new(rules);
+    // References from classes outside the scope count as well
+    for_each_class(dir, &Scope::default(), |mut class| {
+        class.optimize();
+        if scope.contains_type(&class.class_type) {
+            report.add_declarations(&class);
+        }
+        report.add_references(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists anti-tampering and anti-debugging checks.
+pub fn report_protections(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let mut report = ProtectionReport::new();
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists SQL queries and whether they are built from non-constant values.
+pub fn report_sql_queries(dir: &Path, scope: &Scope, output: &mut dyn Write) -> Result<(), String> {
+    let mut report = SqlReport::new();
+    for_each_class(dir, scope, |mut class| {
+        class.optimize();
+        report.add_class(&class);
+    });
+    report.write(output).map_err(report_error)
+}
+
+/// Lists string constants along with their locations, optionally only those matching a regular
+/// expression.
+pub fn list_strings(
+    dir: &Path,
+    scope: &Scope,
+    filter: Option<&str>,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    let filter = filter
+        .map(regex::Regex::new)
+        .transpose()
+        .map_err(|error| format!("Invalid filter: {error}"))?;
+    let mut result = Ok(());
+    for_each_class(dir, scope, |class| {
+        for constant in strings::find_string_constants(&class) {
+            if result.is_ok()
+                && filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.is_match(&constant.value))
+            {
+                result = strings::write_string_constant(output, &constant);
+            }
+        }
+    });
+    result.map_err(|error| format!("Failed writing strings: {error}"))
+}
+
+/// Lists the strings occurring more than once, even if within a single class, most frequent
+/// first. If a memory limit is given, the strings are collected in an index kept on disk rather
+/// than in memory.
+pub fn report_repeated_strings(
+    dir: &Path,
+    scope: &Scope,
+    limit: usize,
+    index_memory_limit: Option<usize>,
+    output: &mut dyn Write,
+) -> Result<(), String> {
+    if let Some(memory_limit) = index_memory_limit {
+        let mut index = Index::on_disk(memory_limit)
+            .map_err(|error| format!("Failed creating index: {error}"))?;
+        let mut result = Ok(());
+        for_each_class(dir, scope, |mut class| {
+            class.optimize();
+            if result.is_ok() {
+                result = strings::add_class_to_index(&mut index, &class);
+            }
+        });
+        return result
+            .and_then(|_| strings::write_index_report(&index, output, limit))
+            .map_err(report_error);
+    }
+
+    let mut table = StringTable::new();
+    for_each_class(dir, scope, |mut class| {
+        // Reconstructed hidden strings should be counted as well
+        class.optimize();
+        table.add_class(&class);
+    });
+    table.write_report(output, limit).map_err(report_error)
+}
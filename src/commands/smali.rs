@@ -0,0 +1,163 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::path::{Path, PathBuf};
+
+use crate::class::{Class, FormatResult};
+use crate::pipeline::find_smali_files;
+use crate::scope::Scope;
+use crate::tags::TagsFile;
+use crate::tokenizer::Tokenizer;
+
+/// Writes a tags file for the Jimple files in a directory into it.
+pub fn write_tags(dir: &Path) -> Result<(), String> {
+    let mut tags = TagsFile::new();
+    let files = walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry
+                    .path()
+                    .extension()
+                    .filter(|s| *s == "jimple")
+                    .is_some()
+        });
+    for entry in files {
+        let path = entry.path();
+        match std::fs::read_to_string(path) {
+            Ok(data) => {
+                let relative_path = path.strip_prefix(dir).unwrap_or(path);
+                let name = relative_path
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                tags.add_file(&name, &data);
+            }
+            Err(error) => eprintln!("Failed reading {}: {error}", path.display()),
+        }
+    }
+
+    let path = dir.join("tags");
+    std::fs::File::create(&path)
+        .and_then(|file| tags.write(&mut std::io::BufWriter::new(file)))
+        .map_err(|error| format!("Failed writing {}: {error}", path.display()))
+}
+
+/// Parses all Smali files in a directory and writes them back into the output directory,
+/// keeping their relative paths. Returns `false` if any file failed.
+pub fn round_trip(dir: &Path, scope: &Scope, output_dir: &Path) -> bool {
+    let mut success = true;
+    for path in find_smali_files(dir) {
+        let class = match Tokenizer::from_file(&path) {
+            Ok(input) => match Class::read(&input) {
+                Ok((_, class)) => class,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    success = false;
+                    continue;
+                }
+            },
+            Err(error) => {
+                eprintln!("{}", error);
+                success = false;
+                continue;
+            }
+        };
+        if !scope.contains_type(&class.class_type) {
+            continue;
+        }
+
+        let target = output_dir.join(path.strip_prefix(dir).unwrap_or(&path));
+        let mut output = Vec::new();
+        class.write_smali(&mut output).unwrap();
+        if let Err(error) = target
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&target, output))
+        {
+            eprintln!("Failed writing {}: {error}", target.display());
+            success = false;
+        }
+    }
+    success
+}
+
+/// Expands directories in a list of paths to the Smali files they contain.
+fn expand_smali_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(find_smali_files(path));
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+/// Rewrites Smali files in the formatting the Smali writer produces. In check mode, files
+/// are only listed if they would change. Returns `false` on errors or if check mode found
+/// files to be formatted.
+pub fn format_smali(paths: &[PathBuf], check: bool) -> bool {
+    let mut success = true;
+    for path in expand_smali_paths(paths) {
+        let input = match Tokenizer::from_file(&path) {
+            Ok(input) => input,
+            Err(error) => {
+                eprintln!("{}", error);
+                success = false;
+                continue;
+            }
+        };
+        let output = match Class::format_smali(&input) {
+            Ok(FormatResult::Unchanged) => continue,
+            Ok(FormatResult::Formatted(output)) => output,
+            Ok(FormatResult::DropsComments(lines)) => {
+                let lines = lines.iter().map(usize::to_string).collect::<Vec<_>>();
+                eprintln!(
+                    "Not formatting {}, comments would be lost (lines {})",
+                    path.display(),
+                    lines.join(", ")
+                );
+                success = false;
+                continue;
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                success = false;
+                continue;
+            }
+        };
+        if check {
+            println!("{} is not formatted", path.display());
+            success = false;
+        } else if let Err(error) = std::fs::write(&path, output) {
+            eprintln!("Failed writing {}: {error}", path.display());
+            success = false;
+        } else {
+            println!("Formatted {}", path.display());
+        }
+    }
+    success
+}
+
+/// Parses Smali files in parallel, printing a line for each file that failed. Returns `false`
+/// if there were any.
+pub fn check_smali(paths: &[PathBuf]) -> bool {
+    let files = expand_smali_paths(paths);
+    let errors = files
+        .par_iter()
+        .filter_map(|path| match Tokenizer::from_file(path) {
+            Ok(input) => Class::read(&input)
+                .err()
+                .map(|error| error.to_short_string()),
+            Err(error) => Some(format!("{}: {error}", path.display())),
+        })
+        .collect::<Vec<_>>();
+    for error in &errors {
+        println!("{error}");
+    }
+    eprintln!("{} files checked, {} failed", files.len(), errors.len());
+    errors.is_empty()
+}
@@ -0,0 +1,1081 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::access_flag::AccessFlag;
+use crate::annotation::{Annotation, AnnotationParameterValue};
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, Register, Registers};
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{CallSignature, FieldSignature, MethodSignature, Type};
+use crate::tokenizer::Tokenizer;
+
+/// Annotations turning an annotation type into a list of allowed constant values.
+const TYPEDEF_ANNOTATIONS: [&str; 9] = [
+    "androidx.annotation.IntDef",
+    "androidx.annotation.LongDef",
+    "androidx.annotation.StringDef",
+    "android.support.annotation.IntDef",
+    "android.support.annotation.LongDef",
+    "android.support.annotation.StringDef",
+    "android.annotation.IntDef",
+    "android.annotation.LongDef",
+    "android.annotation.StringDef",
+];
+
+/// Names and values of constants declared by a framework class.
+type NamedConstants = &'static [(&'static str, i64)];
+
+/// Names and values of string constants declared by a framework class.
+type NamedStrings = &'static [(&'static str, &'static str)];
+
+const CONTEXT_MODES: NamedConstants = &[
+    ("MODE_PRIVATE", 0x0),
+    ("MODE_WORLD_READABLE", 0x1),
+    ("MODE_WORLD_WRITEABLE", 0x2),
+    ("MODE_APPEND", 0x8000),
+];
+
+const CIPHER_MODES: NamedConstants = &[
+    ("ENCRYPT_MODE", 0x1),
+    ("DECRYPT_MODE", 0x2),
+    ("WRAP_MODE", 0x3),
+    ("UNWRAP_MODE", 0x4),
+];
+
+/// Well-known framework constants: method (calls also match if made on a subclass), parameter
+/// index, class declaring the constants, constants.
+const FRAMEWORK_CONSTANTS: [(&str, usize, &str, NamedConstants); 11] = [
+    (
+        "Landroid/view/View;->setVisibility(I)V",
+        0,
+        "Landroid/view/View;",
+        &[("VISIBLE", 0x0), ("INVISIBLE", 0x4), ("GONE", 0x8)],
+    ),
+    (
+        "Landroid/content/Context;->getSharedPreferences(Ljava/lang/String;I)Landroid/content/SharedPreferences;",
+        1,
+        "Landroid/content/Context;",
+        CONTEXT_MODES,
+    ),
+    (
+        "Landroid/content/Context;->openFileOutput(Ljava/lang/String;I)Ljava/io/FileOutputStream;",
+        1,
+        "Landroid/content/Context;",
+        CONTEXT_MODES,
+    ),
+    (
+        "Landroid/content/Context;->getDir(Ljava/lang/String;I)Ljava/io/File;",
+        1,
+        "Landroid/content/Context;",
+        CONTEXT_MODES,
+    ),
+    (
+        "Landroid/content/Context;->openOrCreateDatabase(Ljava/lang/String;ILandroid/database/sqlite/SQLiteDatabase$CursorFactory;)Landroid/database/sqlite/SQLiteDatabase;",
+        1,
+        "Landroid/content/Context;",
+        CONTEXT_MODES,
+    ),
+    (
+        "Ljavax/crypto/Cipher;->init(ILjava/security/Key;)V",
+        0,
+        "Ljavax/crypto/Cipher;",
+        CIPHER_MODES,
+    ),
+    (
+        "Ljavax/crypto/Cipher;->init(ILjava/security/Key;Ljava/security/spec/AlgorithmParameterSpec;)V",
+        0,
+        "Ljavax/crypto/Cipher;",
+        CIPHER_MODES,
+    ),
+    (
+        "Ljavax/crypto/Cipher;->init(ILjava/security/Key;Ljava/security/SecureRandom;)V",
+        0,
+        "Ljavax/crypto/Cipher;",
+        CIPHER_MODES,
+    ),
+    (
+        "Landroid/app/Activity;->setResult(I)V",
+        0,
+        "Landroid/app/Activity;",
+        &[("RESULT_CANCELED", 0x0), ("RESULT_OK", -0x1), ("RESULT_FIRST_USER", 0x1)],
+    ),
+    (
+        "Landroid/widget/Toast;->makeText(Landroid/content/Context;Ljava/lang/CharSequence;I)Landroid/widget/Toast;",
+        2,
+        "Landroid/widget/Toast;",
+        &[("LENGTH_SHORT", 0x0), ("LENGTH_LONG", 0x1)],
+    ),
+    (
+        "Landroid/webkit/WebSettings;->setMixedContentMode(I)V",
+        0,
+        "Landroid/webkit/WebSettings;",
+        &[
+            ("MIXED_CONTENT_ALWAYS_ALLOW", 0x0),
+            ("MIXED_CONTENT_NEVER_ALLOW", 0x1),
+            ("MIXED_CONTENT_COMPATIBILITY_MODE", 0x2),
+        ],
+    ),
+];
+
+const KEY_ALGORITHMS: NamedStrings = &[
+    ("KEY_ALGORITHM_AES", "AES"),
+    ("KEY_ALGORITHM_3DES", "DESede"),
+    ("KEY_ALGORITHM_RSA", "RSA"),
+    ("KEY_ALGORITHM_EC", "EC"),
+    ("KEY_ALGORITHM_HMAC_SHA1", "HmacSHA1"),
+    ("KEY_ALGORITHM_HMAC_SHA224", "HmacSHA224"),
+    ("KEY_ALGORITHM_HMAC_SHA256", "HmacSHA256"),
+    ("KEY_ALGORITHM_HMAC_SHA384", "HmacSHA384"),
+    ("KEY_ALGORITHM_HMAC_SHA512", "HmacSHA512"),
+];
+
+const DIGESTS: NamedStrings = &[
+    ("DIGEST_MD5", "MD5"),
+    ("DIGEST_SHA1", "SHA-1"),
+    ("DIGEST_SHA224", "SHA-224"),
+    ("DIGEST_SHA256", "SHA-256"),
+    ("DIGEST_SHA384", "SHA-384"),
+    ("DIGEST_SHA512", "SHA-512"),
+];
+
+/// Well-known framework string constants, the columns are the same as for
+/// `FRAMEWORK_CONSTANTS`. Cipher transformations only match if they consist of the algorithm
+/// name alone, the framework has no constants for complete transformations.
+const FRAMEWORK_STRINGS: [(&str, usize, &str, NamedStrings); 8] = [
+    (
+        "Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)Ljavax/crypto/Cipher;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;Ljava/lang/String;)Ljavax/crypto/Cipher;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;Ljava/security/Provider;)Ljavax/crypto/Cipher;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/KeyGenerator;->getInstance(Ljava/lang/String;)Ljavax/crypto/KeyGenerator;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/KeyGenerator;->getInstance(Ljava/lang/String;Ljava/lang/String;)Ljavax/crypto/KeyGenerator;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/spec/SecretKeySpec;-><init>([BLjava/lang/String;)V",
+        1,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljavax/crypto/Mac;->getInstance(Ljava/lang/String;)Ljavax/crypto/Mac;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        KEY_ALGORITHMS,
+    ),
+    (
+        "Ljava/security/MessageDigest;->getInstance(Ljava/lang/String;)Ljava/security/MessageDigest;",
+        0,
+        "Landroid/security/keystore/KeyProperties;",
+        DIGESTS,
+    ),
+];
+
+/// Superclasses of framework classes, so that calls made on subclasses of the classes declaring
+/// the methods in the tables above are recognized.
+const FRAMEWORK_SUPERCLASSES: [(&str, &str); 18] = [
+    (
+        "Landroid/content/ContextWrapper;",
+        "Landroid/content/Context;",
+    ),
+    (
+        "Landroid/view/ContextThemeWrapper;",
+        "Landroid/content/ContextWrapper;",
+    ),
+    (
+        "Landroid/app/Activity;",
+        "Landroid/view/ContextThemeWrapper;",
+    ),
+    (
+        "Landroid/app/Application;",
+        "Landroid/content/ContextWrapper;",
+    ),
+    ("Landroid/app/Service;", "Landroid/content/ContextWrapper;"),
+    ("Landroid/app/ListActivity;", "Landroid/app/Activity;"),
+    (
+        "Landroid/preference/PreferenceActivity;",
+        "Landroid/app/ListActivity;",
+    ),
+    ("Landroid/app/IntentService;", "Landroid/app/Service;"),
+    ("Landroid/view/ViewGroup;", "Landroid/view/View;"),
+    ("Landroid/widget/TextView;", "Landroid/view/View;"),
+    ("Landroid/widget/Button;", "Landroid/widget/TextView;"),
+    ("Landroid/widget/EditText;", "Landroid/widget/TextView;"),
+    ("Landroid/widget/ImageView;", "Landroid/view/View;"),
+    ("Landroid/widget/LinearLayout;", "Landroid/view/ViewGroup;"),
+    ("Landroid/widget/FrameLayout;", "Landroid/view/ViewGroup;"),
+    (
+        "Landroid/widget/RelativeLayout;",
+        "Landroid/view/ViewGroup;",
+    ),
+    (
+        "Landroid/widget/AbsoluteLayout;",
+        "Landroid/view/ViewGroup;",
+    ),
+    (
+        "Landroid/webkit/WebView;",
+        "Landroid/widget/AbsoluteLayout;",
+    ),
+];
+
+/// Direction in which reads of constant fields and literals are translated into each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ConstantFields {
+    /// Replace reads of `static final` fields with known values by the value
+    Inline,
+    /// Replace literals by a `static final` field of the class or its outer classes with the
+    /// same value, if there is exactly one
+    Names,
+}
+
+/// A constant value that can be compared regardless of the literal's exact integer width.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ConstantValue {
+    Integer(i64),
+    String(String),
+}
+
+impl ConstantValue {
+    fn from_literal(literal: &Literal) -> Option<Self> {
+        if let Some(value) = literal.get_integer() {
+            Some(Self::Integer(value))
+        } else {
+            literal.get_string().map(Self::String)
+        }
+    }
+}
+
+/// Whole-program table of `static final` fields with constant values and of `@IntDef` and
+/// `@StringDef` annotated method parameters.
+#[derive(Debug, Default)]
+pub struct ConstantTable {
+    constants: Vec<(FieldSignature, ConstantValue)>,
+    field_values: HashMap<FieldSignature, Literal>,
+    field_mode: Option<ConstantFields>,
+    typedef_values: HashMap<Type, Vec<ConstantValue>>,
+    parameter_annotations: HashMap<MethodSignature, Vec<Vec<Type>>>,
+    parameter_typedefs: HashMap<MethodSignature, Vec<Option<Type>>>,
+    resolved: HashMap<Type, HashMap<ConstantValue, FieldSignature>>,
+    /// Framework methods by name and signature, with the declaring class, parameter index and
+    /// constants
+    framework: HashMap<(String, CallSignature), Vec<FrameworkConstants>>,
+    superclasses: HashMap<Type, Type>,
+}
+
+/// A class along with its superclass.
+type ClassLink<'a> = (&'a Type, Option<&'a Type>);
+
+/// Constants for a parameter of a framework method.
+#[derive(Debug)]
+struct FrameworkConstants {
+    declaring_type: Type,
+    index: usize,
+    mapping: HashMap<ConstantValue, FieldSignature>,
+}
+
+impl ConstantTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the bundled table of well-known framework constants.
+    pub fn add_framework_constants(&mut self) {
+        for (method, index, object_type, constants) in FRAMEWORK_CONSTANTS {
+            let constants = constants
+                .iter()
+                .map(|(name, value)| (*name, ConstantValue::Integer(*value)));
+            self.add_framework_method(method, index, object_type, constants);
+        }
+        for (method, index, object_type, constants) in FRAMEWORK_STRINGS {
+            let constants = constants
+                .iter()
+                .map(|(name, value)| (*name, ConstantValue::String(value.to_string())));
+            self.add_framework_method(method, index, object_type, constants);
+        }
+        for (class, super_class) in FRAMEWORK_SUPERCLASSES {
+            self.superclasses
+                .insert(read_builtin_type(class), read_builtin_type(super_class));
+        }
+    }
+
+    fn add_framework_method(
+        &mut self,
+        method: &str,
+        index: usize,
+        object_type: &str,
+        constants: impl Iterator<Item = (&'static str, ConstantValue)>,
+    ) {
+        let Ok((_, method)) = MethodSignature::read(&builtin_tokenizer(method)) else {
+            panic!("Invalid entry in the framework constants table: {method}");
+        };
+        let object_type = read_builtin_type(object_type);
+
+        let field_type = method.call_signature.parameter_types[index].clone();
+        let mapping = constants
+            .map(|(name, value)| {
+                (
+                    value,
+                    FieldSignature {
+                        object_type: object_type.clone(),
+                        field_name: name.to_string(),
+                        field_type: field_type.clone(),
+                    },
+                )
+            })
+            .collect();
+        self.framework
+            .entry((method.method_name, method.call_signature))
+            .or_default()
+            .push(FrameworkConstants {
+                declaring_type: method.object_type,
+                index,
+                mapping,
+            });
+    }
+
+    /// Enables translating between constant fields and literals in the given direction.
+    pub fn set_field_mode(&mut self, mode: ConstantFields) {
+        self.field_mode = Some(mode);
+    }
+
+    /// Collects constants, typedef annotation types and annotated parameters from a class.
+    /// `resolve()` has to be called once all classes have been added.
+    pub fn add_class(&mut self, class: &Class) {
+        if let Some(super_class) = &class.super_class {
+            self.superclasses
+                .insert(class.class_type.clone(), super_class.clone());
+        }
+
+        let initialized = get_initialized_constants(class);
+        for field in &class.fields {
+            if field.visibility.contains(&AccessFlag::Static)
+                && field.visibility.contains(&AccessFlag::Final)
+            {
+                let Some(literal) = field
+                    .initial_value
+                    .as_ref()
+                    .or_else(|| initialized.get(field.name.as_str()).copied())
+                    .and_then(|literal| typed_literal(literal, &field.field_type))
+                else {
+                    continue;
+                };
+                let signature = FieldSignature {
+                    object_type: class.class_type.clone(),
+                    field_name: field.name.clone(),
+                    field_type: field.field_type.clone(),
+                };
+                if let Some(value) = ConstantValue::from_literal(&literal) {
+                    self.constants.push((signature.clone(), value));
+                }
+                self.field_values.insert(signature, literal);
+            }
+        }
+
+        if class.access_flags.contains(&AccessFlag::Annotation) {
+            if let Some(values) = TYPEDEF_ANNOTATIONS.iter().find_map(|annotation_type| {
+                match Annotation::find(&class.annotations, annotation_type)?.get_parameter("value")
+                {
+                    Some(AnnotationParameterValue::Array(values)) => Some(values),
+                    _ => None,
+                }
+            }) {
+                self.typedef_values.insert(
+                    class.class_type.clone(),
+                    values
+                        .iter()
+                        .filter_map(|value| match value {
+                            AnnotationParameterValue::Literal(literal) => {
+                                ConstantValue::from_literal(literal)
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                );
+            }
+        }
+
+        for method in &class.methods {
+            let annotations = method
+                .parameters
+                .iter()
+                .map(|parameter| {
+                    parameter
+                        .annotations
+                        .iter()
+                        .map(|annotation| annotation.annotation_type.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            if annotations.iter().any(|list| !list.is_empty()) {
+                self.parameter_annotations
+                    .insert(method.get_signature(&class.class_type), annotations);
+            }
+        }
+    }
+
+    /// Maps the values of each typedef annotation type to constant fields. Constants declared
+    /// by the class containing the annotation type are preferred, then those declared by the
+    /// annotation type itself. Otherwise a constant is only used if its value is unique.
+    pub fn resolve(&mut self) {
+        self.resolved.clear();
+        for (typedef, values) in &self.typedef_values {
+            let name = typedef.get_name();
+            let outer = name.rsplit_once('$').map(|(outer, _)| outer);
+            let mut mapping = HashMap::new();
+            for value in values {
+                let candidates = self
+                    .constants
+                    .iter()
+                    .filter(|(_, constant)| constant == value)
+                    .map(|(field, _)| field)
+                    .collect::<Vec<_>>();
+                let field = candidates
+                    .iter()
+                    .find(|field| Some(field.object_type.get_name().as_ref()) == outer)
+                    .or_else(|| {
+                        candidates
+                            .iter()
+                            .find(|field| field.object_type == *typedef)
+                    })
+                    .or_else(|| (candidates.len() == 1).then(|| &candidates[0]));
+                if let Some(field) = field {
+                    mapping.insert(value.clone(), (*field).clone());
+                }
+            }
+            self.resolved.insert(typedef.clone(), mapping);
+        }
+
+        self.parameter_typedefs.clear();
+        for (method, annotations) in &self.parameter_annotations {
+            let typedefs = annotations
+                .iter()
+                .map(|list| {
+                    list.iter()
+                        .find(|annotation_type| self.resolved.contains_key(annotation_type))
+                        .cloned()
+                })
+                .collect::<Vec<_>>();
+            if typedefs.iter().any(Option::is_some) {
+                self.parameter_typedefs.insert(method.clone(), typedefs);
+            }
+        }
+    }
+
+    /// Checks whether a class is the other class or inherits from it, as far as the class
+    /// hierarchy is known. The superclass of the class being processed is always known, even
+    /// if the class hasn't been added to the table.
+    fn is_subclass(&self, class_type: &Type, other: &Type, processed: &ClassLink<'_>) -> bool {
+        let mut current = class_type;
+        // Limit the depth in case of inheritance cycles in broken code
+        for _ in 0..100 {
+            if current == other {
+                return true;
+            }
+            let super_class = if current == processed.0 {
+                processed.1
+            } else {
+                self.superclasses.get(current)
+            };
+            match super_class {
+                Some(super_class) => current = super_class,
+                None => return false,
+            }
+        }
+        false
+    }
+
+    /// Finds the framework constants for a call. The method has to be called on the framework
+    /// class declaring it or a known subclass.
+    fn get_framework_constants(
+        &self,
+        method: &MethodSignature,
+        processed: &ClassLink<'_>,
+    ) -> Option<&FrameworkConstants> {
+        self.framework
+            .get(&(method.method_name.clone(), method.call_signature.clone()))?
+            .iter()
+            .find(|constants| {
+                self.is_subclass(&method.object_type, &constants.declaring_type, processed)
+            })
+    }
+
+    fn has_parameter_constants(&self, method: &MethodSignature, processed: &ClassLink<'_>) -> bool {
+        self.parameter_typedefs.contains_key(method)
+            || self.get_framework_constants(method, processed).is_some()
+    }
+
+    fn lookup(
+        &self,
+        method: &MethodSignature,
+        index: usize,
+        value: &Literal,
+        processed: &ClassLink<'_>,
+    ) -> Option<&FieldSignature> {
+        let value = ConstantValue::from_literal(value)?;
+        if let Some(typedef) = self
+            .parameter_typedefs
+            .get(method)
+            .and_then(|typedefs| typedefs.get(index)?.as_ref())
+        {
+            return self.resolved.get(typedef)?.get(&value);
+        }
+
+        match self.get_framework_constants(method, processed) {
+            Some(constants) if constants.index == index => constants.mapping.get(&value),
+            _ => None,
+        }
+    }
+
+    /// Replaces constants passed to typedef annotated parameters or well-known framework
+    /// methods by the fields they originate from. Only `$stackN` temporaries are considered,
+    /// other registers might be used elsewhere.
+    pub fn apply(&self, class: &mut Class) {
+        match self.field_mode {
+            Some(ConstantFields::Inline) => {
+                for method in &mut class.methods {
+                    self.inline_fields(method);
+                }
+            }
+            Some(ConstantFields::Names) => {
+                let fields = self.get_own_constants(&class.class_type);
+                for method in &mut class.methods {
+                    if method.name != "<clinit>" {
+                        name_literals(method, &fields);
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if self.parameter_typedefs.is_empty() && self.framework.is_empty() {
+            return;
+        }
+        let processed = (&class.class_type, class.super_class.as_ref());
+        for method in &mut class.methods {
+            self.apply_to_method(method, &processed);
+        }
+    }
+
+    /// Replaces reads of constant fields by their values, the field name is shown as a comment
+    /// next to the statement the value ends up in.
+    fn inline_fields(&self, method: &mut Method) {
+        let mut comments = Vec::new();
+        for (i, instruction) in method.instructions.iter_mut().enumerate() {
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            let (Some(CommandParameter::Result(register)), Some(CommandParameter::Field(field))) =
+                (parameters.first(), parameters.get(1))
+            else {
+                continue;
+            };
+            if !command.starts_with("sget") {
+                continue;
+            }
+            let Some(value) = self.field_values.get(field) else {
+                continue;
+            };
+
+            let name = format!("{}.{}", field.object_type.get_name(), field.field_name);
+            *command = match value {
+                Literal::Long(_) => "const-wide",
+                Literal::String(_) => "const-string",
+                _ => "const",
+            }
+            .to_string();
+            *parameters = vec![
+                CommandParameter::Result(register.clone()),
+                CommandParameter::Literal(value.clone()),
+            ];
+            comments.push((i, name));
+        }
+        if comments.is_empty() {
+            return;
+        }
+
+        let nested = method.find_nested_expressions();
+        for (i, name) in comments {
+            let mut target = i;
+            while let Some(&next) = nested.get(&target) {
+                target = next;
+            }
+            if let Instruction::Command { parameters, .. } = &mut method.instructions[target] {
+                parameters.push(CommandParameter::Comment(name));
+            }
+        }
+    }
+
+    /// Lists constants declared by a class and its outer classes.
+    fn get_own_constants(&self, class_type: &Type) -> Vec<(&FieldSignature, &ConstantValue)> {
+        let name = class_type.get_name();
+        self.constants
+            .iter()
+            .filter(|(field, _)| {
+                let declaring = field.object_type.get_name();
+                name == declaring
+                    || name
+                        .strip_prefix(declaring.as_ref())
+                        .is_some_and(|rest| rest.starts_with('$'))
+            })
+            .map(|(field, value)| (field, value))
+            .collect()
+    }
+
+    fn apply_to_method(&self, method: &mut Method, processed: &ClassLink<'_>) {
+        let mut replacements = Vec::new();
+        for instruction in &method.instructions {
+            if let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            {
+                let (registers, signature) = match (parameters.get(1), parameters.get(2)) {
+                    (
+                        Some(CommandParameter::Registers(Registers::List(registers))),
+                        Some(CommandParameter::Method(signature)),
+                    ) if command.starts_with("invoke-") => (registers, signature),
+                    _ => continue,
+                };
+                if !self.has_parameter_constants(signature, processed) {
+                    continue;
+                }
+
+                let mut position = if command.starts_with("invoke-static") {
+                    0
+                } else {
+                    1
+                };
+                for (index, parameter_type) in
+                    signature.call_signature.parameter_types.iter().enumerate()
+                {
+                    if let Some(register @ Register::Stack(_)) = registers.get(position) {
+                        replacements.push((register.clone(), signature, index));
+                    }
+                    position += parameter_type.register_count();
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (register, signature, index) in replacements {
+            let Some(i) = method
+                .instructions
+                .iter()
+                .position(|instruction| instruction.get_result_register() == Some(&register))
+            else {
+                continue;
+            };
+            if let Instruction::Command {
+                command,
+                parameters,
+            } = &method.instructions[i]
+            {
+                if let (true, Some(CommandParameter::Literal(value))) =
+                    (command.starts_with("const"), parameters.get(1))
+                {
+                    if let Some(field) = self.lookup(signature, index, value, processed) {
+                        changes.push((i, register.clone(), field.clone()));
+                    }
+                }
+            }
+        }
+
+        for (i, register, field) in changes {
+            let command = match field.field_type {
+                Type::Long => "sget-wide",
+                Type::Object(_) => "sget-object",
+                _ => "sget",
+            };
+            method.instructions[i] = Instruction::Command {
+                command: command.to_string(),
+                parameters: vec![
+                    CommandParameter::Result(register),
+                    CommandParameter::Field(field),
+                ],
+            };
+        }
+    }
+}
+
+fn builtin_tokenizer(data: &str) -> Tokenizer {
+    Tokenizer::new(data.to_string(), Path::new("builtin"))
+}
+
+fn read_builtin_type(data: &str) -> Type {
+    match Type::read(&builtin_tokenizer(data)) {
+        Ok((_, result)) => result,
+        Err(_) => panic!("Invalid type in the framework constants tables: {data}"),
+    }
+}
+
+/// Converts a constant to the literal type matching the field, `const` instructions don't
+/// distinguish between integers, booleans and characters.
+fn typed_literal(literal: &Literal, field_type: &Type) -> Option<Literal> {
+    match (field_type, literal) {
+        (Type::Bool, Literal::Bool(_)) | (Type::Char, Literal::Char(_)) => Some(literal.clone()),
+        (Type::Bool, _) => Some(Literal::Bool(literal.get_integer()? != 0)),
+        (Type::Char, _) => Some(Literal::Char(u16::try_from(literal.get_integer()?).ok()?)),
+        (Type::Byte | Type::Short | Type::Int, _) => {
+            Some(Literal::Int(i32::try_from(literal.get_integer()?).ok()?))
+        }
+        (Type::Long, _) => Some(Literal::Long(literal.get_integer()?)),
+        (Type::Object(name), Literal::String(_)) if name == "java.lang.String" => {
+            Some(literal.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Finds `static final` fields of the class assigned a constant exactly once by the static
+/// initializer. Only assignments before the first label are considered, later ones might be
+/// conditional.
+fn get_initialized_constants(class: &Class) -> HashMap<&str, &Literal> {
+    let mut result = HashMap::new();
+    let mut assigned = HashMap::new();
+    let Some(method) = class
+        .methods
+        .iter()
+        .find(|method| method.name == "<clinit>")
+    else {
+        return result;
+    };
+    let mut values = HashMap::new();
+    let mut unconditional = true;
+    for instruction in &method.instructions {
+        match instruction {
+            Instruction::Label(_) => unconditional = false,
+            // Raw instructions might assign any register
+            Instruction::Raw(_) => values.clear(),
+            Instruction::Command {
+                command,
+                parameters,
+            } => match (parameters.first(), parameters.get(1)) {
+                (
+                    Some(CommandParameter::Result(register)),
+                    Some(CommandParameter::Literal(literal)),
+                ) if command.starts_with("const") => {
+                    values.insert(register, literal);
+                }
+                (
+                    Some(CommandParameter::Register(register)),
+                    Some(CommandParameter::Field(field)),
+                ) if command.starts_with("sput") && field.object_type == class.class_type => {
+                    *assigned.entry(field.field_name.as_str()).or_insert(0) += 1;
+                    if let (true, Some(literal)) = (unconditional, values.get(register)) {
+                        result.insert(field.field_name.as_str(), *literal);
+                    }
+                }
+                _ => {
+                    if let Some(register) = instruction.get_result_register() {
+                        values.remove(register);
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    result.retain(|name, _| assigned.get(name) == Some(&1));
+    result
+}
+
+/// Replaces literals by the constant field with the same value. Trivial values and values
+/// shared by several fields are left alone.
+fn name_literals(method: &mut Method, fields: &[(&FieldSignature, &ConstantValue)]) {
+    for instruction in &mut method.instructions {
+        let Instruction::Command {
+            command,
+            parameters,
+        } = instruction
+        else {
+            continue;
+        };
+        let (Some(CommandParameter::Result(register)), Some(CommandParameter::Literal(literal))) =
+            (parameters.first(), parameters.get(1))
+        else {
+            continue;
+        };
+        if !command.starts_with("const") {
+            continue;
+        }
+        let Some(value) = ConstantValue::from_literal(literal) else {
+            continue;
+        };
+        if matches!(value, ConstantValue::Integer(-1..=1)) {
+            continue;
+        }
+        let is_wide = command.starts_with("const-wide");
+        let mut candidates = fields.iter().filter(|(field, constant)| {
+            **constant == value
+                && match field.field_type {
+                    Type::Long => is_wide,
+                    Type::Object(_) => command.starts_with("const-string"),
+                    _ => !is_wide && !command.starts_with("const-string"),
+                }
+        });
+        let (Some((field, _)), None) = (candidates.next(), candidates.next()) else {
+            continue;
+        };
+
+        *command = match field.field_type {
+            Type::Long => "sget-wide",
+            Type::Object(_) => "sget-object",
+            _ => "sget",
+        }
+        .to_string();
+        *parameters = vec![
+            CommandParameter::Result(register.clone()),
+            CommandParameter::Field((*field).clone()),
+        ];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn resolve_typedefs() -> Result<(), ParseErrorDisplayed> {
+        let view = read_class(
+            r#"
+                .class public Lcom/example/View;
+                .super Ljava/lang/Object;
+
+                .field public static final GONE:I = 0x8
+                .field public static final VISIBLE:I = 0x0
+
+                .method public setVisibility(I)V
+                    .locals 0
+                    .param p1    # I
+                        .annotation build Lcom/example/View$Visibility;
+                        .end annotation
+                    .end param
+                    return-void
+                .end method
+            "#,
+        )?;
+        let visibility = read_class(
+            r#"
+                .class public interface abstract annotation Lcom/example/View$Visibility;
+                .super Ljava/lang/Object;
+                .implements Ljava/lang/annotation/Annotation;
+
+                .annotation build Landroidx/annotation/IntDef;
+                    value = {
+                        0x0,
+                        0x8
+                    }
+                .end annotation
+            "#,
+        )?;
+        let mut caller = read_class(
+            r#"
+                .class public Lcom/example/Caller;
+                .super Ljava/lang/Object;
+
+                .method public hide(Lcom/example/View;)V
+                    .locals 1
+                    const/16 v0, 0x8
+                    invoke-virtual {p1, v0}, Lcom/example/View;->setVisibility(I)V
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut table = ConstantTable::new();
+        for class in [&view, &visibility, &caller] {
+            table.add_class(class);
+        }
+        table.resolve();
+
+        caller.optimize();
+        table.apply(&mut caller);
+
+        let mut output = Vec::new();
+        caller
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "invoke-virtual p1.<void com.example.View.setVisibility(int)>(<int com.example.View.GONE>);"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn framework_constants() -> Result<(), ParseErrorDisplayed> {
+        let mut caller = read_class(
+            r#"
+                .class public Lcom/example/Caller;
+                .super Lcom/example/Base;
+
+                .method public save(Ljavax/crypto/Cipher;Ljava/security/Key;)V
+                    .locals 2
+                    const-string v0, "prefs"
+                    const/4 v1, 0x0
+                    invoke-virtual {p0, v0, v1}, Lcom/example/Caller;->getSharedPreferences(Ljava/lang/String;I)Landroid/content/SharedPreferences;
+                    const/4 v0, 0x1
+                    invoke-virtual {p1, v0, p2}, Ljavax/crypto/Cipher;->init(ILjava/security/Key;)V
+                    const/4 v0, 0x5
+                    invoke-virtual {p1, v0, p2}, Ljavax/crypto/Cipher;->init(ILjava/security/Key;)V
+                    const-string v0, "AES"
+                    invoke-static {v0}, Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)Ljavax/crypto/Cipher;
+                    const-string v0, "AES/CBC/PKCS5Padding"
+                    invoke-static {v0}, Ljavax/crypto/Cipher;->getInstance(Ljava/lang/String;)Ljavax/crypto/Cipher;
+                    const-string v0, "SHA-256"
+                    invoke-static {v0}, Ljava/security/MessageDigest;->getInstance(Ljava/lang/String;)Ljava/security/MessageDigest;
+                    const/16 v1, 0x8
+                    invoke-virtual {p0, v1}, Lcom/example/Caller;->setVisibility(I)V
+                    return-void
+                .end method
+
+                .method public unrelated(Lcom/example/Store;)V
+                    .locals 2
+                    const-string v0, "prefs"
+                    const/4 v1, 0x0
+                    invoke-virtual {p1, v0, v1}, Lcom/example/Store;->getSharedPreferences(Ljava/lang/String;I)Landroid/content/SharedPreferences;
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let base = read_class(
+            r#"
+                .class public Lcom/example/Base;
+                .super Landroid/app/Activity;
+            "#,
+        )?;
+
+        let mut table = ConstantTable::new();
+        table.add_framework_constants();
+        table.add_class(&base);
+        table.resolve();
+
+        caller.optimize();
+        table.apply(&mut caller);
+
+        let mut output = Vec::new();
+        caller
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains(
+            "invoke-virtual p0.<android.content.SharedPreferences com.example.Caller.getSharedPreferences(java.lang.String, int)>(\"prefs\", <int android.content.Context.MODE_PRIVATE>);"
+        ));
+        assert!(output.contains(
+            "invoke-virtual p1.<void javax.crypto.Cipher.init(int, java.security.Key)>(<int javax.crypto.Cipher.ENCRYPT_MODE>, p2);"
+        ));
+        assert!(output.contains(
+            "invoke-virtual p1.<void javax.crypto.Cipher.init(int, java.security.Key)>(0x5, p2);"
+        ));
+        assert!(output.contains(
+            "invoke-static <javax.crypto.Cipher javax.crypto.Cipher.getInstance(java.lang.String)>(<java.lang.String android.security.keystore.KeyProperties.KEY_ALGORITHM_AES>);"
+        ));
+        assert!(output.contains(
+            "invoke-static <javax.crypto.Cipher javax.crypto.Cipher.getInstance(java.lang.String)>(\"AES/CBC/PKCS5Padding\");"
+        ));
+        assert!(output.contains(
+            "invoke-static <java.security.MessageDigest java.security.MessageDigest.getInstance(java.lang.String)>(<java.lang.String android.security.keystore.KeyProperties.DIGEST_SHA256>);"
+        ));
+
+        // Activity isn't a View and Store isn't known to be a Context
+        assert!(
+            output.contains("invoke-virtual p0.<void com.example.Caller.setVisibility(int)>(0x8);")
+        );
+        assert!(output.contains(
+            "invoke-virtual p1.<android.content.SharedPreferences com.example.Store.getSharedPreferences(java.lang.String, int)>(\"prefs\", 0x0);"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn constant_fields() -> Result<(), ParseErrorDisplayed> {
+        let source = r#"
+            .class public Lcom/example/Limits;
+            .super Ljava/lang/Object;
+
+            .field public static final MAX:I = 0x10
+            .field public static final NAME:Ljava/lang/String;
+            .field public static final OTHER:I = 0x10
+
+            .method static constructor <clinit>()V
+                .locals 1
+                const-string v0, "limits"
+                sput-object v0, Lcom/example/Limits;->NAME:Ljava/lang/String;
+                return-void
+            .end method
+
+            .method public check(I)Z
+                .locals 2
+                sget v0, Lcom/example/Limits;->MAX:I
+                if-gt p1, v0, :cond_0
+                const-string v1, "limits"
+                invoke-static {v1}, Lcom/example/Limits;->log(Ljava/lang/String;)V
+                :cond_0
+                const/4 v0, 0x0
+                return v0
+            .end method
+        "#;
+
+        let mut table = ConstantTable::new();
+        table.add_class(&read_class(source)?);
+        table.resolve();
+
+        table.set_field_mode(ConstantFields::Inline);
+        let mut class = read_class(source)?;
+        class.optimize();
+        table.apply(&mut class);
+        let mut output = Vec::new();
+        class.methods[1]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("if (p1 > 0x10) goto cond_0; // com.example.Limits.MAX"));
+
+        table.set_field_mode(ConstantFields::Names);
+        let mut class = read_class(source)?;
+        class.optimize();
+        table.apply(&mut class);
+        let mut output = Vec::new();
+        class
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("(<java.lang.String com.example.Limits.NAME>);"));
+        // The static initializer is left alone
+        assert!(output.contains("<java.lang.String com.example.Limits.NAME> = \"limits\";"));
+
+        Ok(())
+    }
+}
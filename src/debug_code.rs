@@ -0,0 +1,216 @@
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::method::Method;
+
+/// Calls checking whether debugging facilities are enabled.
+const DEBUG_CALLS: [(&str, &str); 3] = [
+    ("android.util.Log", "isLoggable"),
+    ("android.os.Debug", "isDebuggerConnected"),
+    ("android.os.Debug", "waitingForDebugger"),
+];
+
+/// Kind of check a conditional jump depends on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebugCheck {
+    /// Read of the `BuildConfig.DEBUG` field, `false` in release builds
+    BuildConfig,
+    /// Call to one of the debug checks, with the class and method name
+    Call(&'static str, &'static str),
+}
+
+impl std::fmt::Display for DebugCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BuildConfig => write!(f, "BuildConfig.DEBUG"),
+            Self::Call(class, method) => {
+                let class = class.rsplit('.').next().unwrap_or_default();
+                write!(f, "{class}.{method}()")
+            }
+        }
+    }
+}
+
+/// Recognizes an instruction producing the result of a debug check.
+fn get_debug_check(instruction: &Instruction) -> Option<DebugCheck> {
+    let Instruction::Command { parameters, .. } = instruction else {
+        return None;
+    };
+    parameters.iter().find_map(|parameter| match parameter {
+        CommandParameter::Field(field) => {
+            let class = field.object_type.get_name();
+            let class = class.rsplit('.').next().unwrap_or_default();
+            (class == "BuildConfig" && field.field_name == "DEBUG")
+                .then_some(DebugCheck::BuildConfig)
+        }
+        CommandParameter::Method(method) => DEBUG_CALLS
+            .iter()
+            .find(|(class, name)| {
+                method.object_type.get_name() == *class && method.method_name == *name
+            })
+            .map(|(class, name)| DebugCheck::Call(class, name)),
+        _ => None,
+    })
+}
+
+impl Method {
+    /// Finds conditional jumps depending on debug checks, returning their indexes along with the
+    /// check. Only checks within the same basic block as the jump are recognized.
+    pub fn find_debug_checks(&self) -> Vec<(usize, DebugCheck)> {
+        let mut result = Vec::new();
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let Instruction::Command { command, .. } = instruction else {
+                continue;
+            };
+            if command != "if-eqz" && command != "if-nez" {
+                continue;
+            }
+            let Some(register) = instruction.get_used_registers().into_iter().next() else {
+                continue;
+            };
+            for previous in self.instructions[..index].iter().rev() {
+                if matches!(previous, Instruction::Label(_)) || previous.ends_block() {
+                    break;
+                }
+                if previous.get_result_register() == Some(&register) {
+                    if let Some(check) = get_debug_check(previous) {
+                        result.push((index, check));
+                    }
+                    break;
+                }
+            }
+        }
+        result
+    }
+
+    /// Resolves jumps depending on `BuildConfig.DEBUG` as if the field were `false` and removes
+    /// the code that becomes unreachable this way.
+    pub fn assume_release(&mut self) {
+        let checks = self.find_debug_checks();
+        for (index, _) in checks
+            .into_iter()
+            .rev()
+            .filter(|(_, check)| *check == DebugCheck::BuildConfig)
+        {
+            let Instruction::Command {
+                command,
+                parameters,
+            } = &mut self.instructions[index]
+            else {
+                continue;
+            };
+            if command == "if-nez" {
+                // Never jumps
+                self.instructions.remove(index);
+                continue;
+            }
+
+            *command = "goto".to_string();
+            parameters.retain(|parameter| matches!(parameter, CommandParameter::Label(_)));
+            // Labels might be jumped to, block structure has to be kept intact
+            let end = self.instructions[index + 1..]
+                .iter()
+                .position(|instruction| {
+                    !matches!(
+                        instruction,
+                        Instruction::Command { .. }
+                            | Instruction::LineNumber(..)
+                            | Instruction::Local { .. }
+                            | Instruction::LocalRestart { .. }
+                            | Instruction::Comment(_)
+                            | Instruction::AssertNotNull { .. }
+                    )
+                })
+                .map_or(self.instructions.len(), |position| index + 1 + position);
+            self.instructions.drain(index + 1..end);
+        }
+    }
+}
+
+impl Class {
+    /// Adds a comment to methods containing code paths that only run in debug builds or with
+    /// debugging enabled.
+    pub fn mark_debug_code(&mut self) {
+        for method in &mut self.methods {
+            let mut checks = Vec::new();
+            for (_, check) in method.find_debug_checks() {
+                if !checks.contains(&check) {
+                    checks.push(check);
+                }
+            }
+            if !checks.is_empty() {
+                let checks = checks.iter().map(DebugCheck::to_string).collect::<Vec<_>>();
+                method
+                    .comments
+                    .push(format!("debug-only code guarded by {}", checks.join(", ")));
+            }
+        }
+    }
+
+    /// Shows the code effective in release builds, see [`Method::assume_release`].
+    pub fn assume_release(&mut self) {
+        for method in &mut self.methods {
+            method.assume_release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn debug_code() -> Result<(), ParseErrorDisplayed> {
+        let mut class = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public run()V
+                    .locals 2
+                    sget-boolean v0, Lcom/example/BuildConfig;->DEBUG:Z
+                    if-eqz v0, :cond_0
+                    const-string v1, "debug"
+                    invoke-static {v1}, La;->log(Ljava/lang/String;)V
+                    :cond_0
+                    invoke-static {}, Landroid/os/Debug;->isDebuggerConnected()Z
+                    move-result v0
+                    if-nez v0, :cond_1
+                    return-void
+                    :cond_1
+                    invoke-static {}, La;->exit()V
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        class.assume_release();
+        class.mark_debug_code();
+
+        let mut output = Vec::new();
+        class.methods[0]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    // debug-only code guarded by Debug.isDebuggerConnected()
+    public void run()
+    {
+        $stack0 = <bool com.example.BuildConfig.DEBUG>;
+        goto cond_0;
+
+    cond_0:
+        if (invoke-static <bool android.os.Debug.isDebuggerConnected()>() != 0) goto cond_1;
+        return;
+
+    cond_1:
+        invoke-static <void a.exit()>();
+        return;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::ResultType;
+use crate::literal::Literal;
+use crate::r#type::MethodSignature;
+use crate::xml::get_attribute;
+
+/// Manifest elements that can declare intent filters.
+const COMPONENT_TAGS: [&str; 4] = ["activity", "activity-alias", "receiver", "service"];
+
+const URI_CLASS: &str = "android.net.Uri";
+/// `Uri` methods reading query parameters, the parameter name is passed first.
+const QUERY_PARAMETER_METHODS: [&str; 3] = [
+    "getQueryParameter",
+    "getQueryParameters",
+    "getBooleanQueryParameter",
+];
+const BROWSABLE_CATEGORY: &str = "android.intent.category.BROWSABLE";
+
+/// URIs accepted by an intent filter, as declared by its `<data>` elements.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IntentFilter {
+    pub actions: Vec<String>,
+    pub categories: Vec<String>,
+    pub schemes: Vec<String>,
+    pub hosts: Vec<String>,
+    /// Paths, with prefixes and suffixes marked by `*`, patterns are kept as is
+    pub paths: Vec<String>,
+}
+
+impl IntentFilter {
+    fn add_data(&mut self, tag: &str) {
+        if let Some(scheme) = get_attribute(tag, "android:scheme") {
+            self.schemes.push(scheme);
+        }
+        if let Some(host) = get_attribute(tag, "android:host") {
+            self.hosts.push(host);
+        }
+        if let Some(path) = get_attribute(tag, "android:path") {
+            self.paths.push(path);
+        }
+        if let Some(prefix) = get_attribute(tag, "android:pathPrefix") {
+            self.paths.push(format!("{prefix}*"));
+        }
+        if let Some(suffix) = get_attribute(tag, "android:pathSuffix") {
+            self.paths.push(format!("*{suffix}"));
+        }
+        for attribute in ["android:pathPattern", "android:pathAdvancedPattern"] {
+            if let Some(pattern) = get_attribute(tag, attribute) {
+                self.paths.push(pattern);
+            }
+        }
+    }
+
+    /// Checks whether links in a browser can open the component.
+    pub fn is_browsable(&self) -> bool {
+        self.categories
+            .iter()
+            .any(|category| category == BROWSABLE_CATEGORY)
+    }
+
+    /// Lists the URI patterns accepted: each combination of scheme, host and path.
+    pub fn get_uri_patterns(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        for scheme in &self.schemes {
+            if self.hosts.is_empty() {
+                result.push(format!("{scheme}:*"));
+                continue;
+            }
+            for host in &self.hosts {
+                if self.paths.is_empty() {
+                    result.push(format!("{scheme}://{host}"));
+                }
+                for path in &self.paths {
+                    result.push(format!("{scheme}://{host}{path}"));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// A manifest component accepting URIs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkHandler {
+    /// Class name of the component, for aliases the target activity
+    pub component: String,
+    pub filters: Vec<IntentFilter>,
+}
+
+/// Resolves a component name relative to the package, as the manifest allows.
+fn resolve_component_name(package: &str, name: &str) -> String {
+    if name.starts_with('.') {
+        format!("{package}{name}")
+    } else if !name.contains('.') {
+        format!("{package}.{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Extracts the components with intent filters accepting URIs from a manifest decoded by
+/// apktool.
+pub fn read_manifest_link_handlers(manifest: &str) -> Vec<LinkHandler> {
+    let mut result = Vec::new();
+    let mut package = String::new();
+    let mut component: Option<LinkHandler> = None;
+    let mut filter: Option<IntentFilter> = None;
+    for tag in manifest.split('<').skip(1) {
+        let tag = tag.split_once('>').map_or(tag, |(tag, _)| tag);
+        let name = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .find(|name| !name.is_empty())
+            .unwrap_or_default();
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if name == "intent-filter" {
+                if let (Some(component), Some(filter)) = (&mut component, filter.take()) {
+                    if !filter.schemes.is_empty() {
+                        component.filters.push(filter);
+                    }
+                }
+            } else if COMPONENT_TAGS.contains(&name) {
+                result.extend(component.take().filter(|c| !c.filters.is_empty()));
+            }
+            continue;
+        }
+
+        match name {
+            "manifest" => package = get_attribute(tag, "package").unwrap_or_default(),
+            name if COMPONENT_TAGS.contains(&name) => {
+                let class_name = get_attribute(tag, "android:targetActivity")
+                    .or_else(|| get_attribute(tag, "android:name"))
+                    .unwrap_or_default();
+                component = Some(LinkHandler {
+                    component: resolve_component_name(&package, &class_name),
+                    filters: Vec::new(),
+                });
+                if tag.ends_with('/') {
+                    component = None;
+                }
+            }
+            "intent-filter" => filter = Some(IntentFilter::default()),
+            "action" | "category" | "data" => {
+                let Some(filter) = &mut filter else {
+                    continue;
+                };
+                match name {
+                    "action" => filter.actions.extend(get_attribute(tag, "android:name")),
+                    "category" => filter.categories.extend(get_attribute(tag, "android:name")),
+                    _ => filter.add_data(tag),
+                }
+            }
+            _ => (),
+        }
+    }
+    result
+}
+
+/// Returns the name of the outermost class, so that code in inner classes is attributed to
+/// the component.
+fn get_outer_class_name(name: &str) -> &str {
+    name.split('$').next().unwrap_or(name)
+}
+
+/// Deep links declared in the manifest along with the query parameters read by the handling
+/// components and the URIs parsed in code.
+#[derive(Debug, Default)]
+pub struct DeepLinkReport {
+    handlers: Vec<LinkHandler>,
+    parameters: BTreeMap<String, BTreeSet<String>>,
+    parsed_uris: Vec<(String, MethodSignature)>,
+}
+
+impl DeepLinkReport {
+    pub fn new(handlers: Vec<LinkHandler>) -> Self {
+        Self {
+            handlers,
+            ..Default::default()
+        }
+    }
+
+    /// Records query parameter names and URI constants used by the class.
+    pub fn add_class(&mut self, class: &Class) {
+        let class_name = class.class_type.to_string();
+        for method in &class.methods {
+            for call in method.get_calls(&class.class_type) {
+                if call.method.object_type.get_name() != URI_CLASS {
+                    continue;
+                }
+                let Some(ResultType::Literal(Literal::String(value))) = call.get_parameter(0)
+                else {
+                    continue;
+                };
+                if QUERY_PARAMETER_METHODS.contains(&call.method.method_name.as_str()) {
+                    self.parameters
+                        .entry(get_outer_class_name(&class_name).to_string())
+                        .or_default()
+                        .insert(value.to_string());
+                } else if call.method.method_name == "parse" {
+                    self.parsed_uris
+                        .push((value.to_string(), method.get_signature(&class.class_type)));
+                }
+            }
+        }
+    }
+
+    pub fn get_handlers(&self) -> &[LinkHandler] {
+        &self.handlers
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut patterns: BTreeMap<String, Vec<(&LinkHandler, &IntentFilter)>> = BTreeMap::new();
+        for handler in &self.handlers {
+            for filter in &handler.filters {
+                for pattern in filter.get_uri_patterns() {
+                    patterns.entry(pattern).or_default().push((handler, filter));
+                }
+            }
+        }
+
+        for (pattern, handlers) in &patterns {
+            writeln!(output, "{pattern}")?;
+            for (handler, filter) in handlers {
+                write!(output, "    handled by {}", handler.component)?;
+                if !filter.is_browsable() {
+                    write!(output, " (not browsable)")?;
+                }
+                writeln!(output)?;
+                if let Some(parameters) = self.parameters.get(&handler.component) {
+                    let parameters = parameters.iter().cloned().collect::<Vec<_>>();
+                    writeln!(
+                        output,
+                        "        query parameters: {}",
+                        parameters.join(", ")
+                    )?;
+                }
+            }
+
+            // The prefix before a wildcard has to match
+            let prefix = pattern.split('*').next().unwrap_or_default();
+            for (uri, location) in &self.parsed_uris {
+                if uri.starts_with(prefix) {
+                    writeln!(output, "    \"{uri}\" parsed in <{location}>")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    const MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8" standalone="no"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example">
+    <application android:label="@string/app_name">
+        <activity android:exported="true" android:name=".ItemActivity">
+            <intent-filter>
+                <action android:name="android.intent.action.VIEW"/>
+                <category android:name="android.intent.category.DEFAULT"/>
+                <category android:name="android.intent.category.BROWSABLE"/>
+                <data android:scheme="https"/>
+                <data android:host="example.com" android:pathPrefix="/item"/>
+            </intent-filter>
+            <intent-filter>
+                <action android:name="android.intent.action.MAIN"/>
+                <category android:name="android.intent.category.LAUNCHER"/>
+            </intent-filter>
+        </activity>
+        <activity android:name="com.example.Settings"/>
+        <activity-alias android:name=".Alias" android:targetActivity=".Other">
+            <intent-filter>
+                <data android:scheme="myapp" android:host="open"/>
+            </intent-filter>
+        </activity-alias>
+    </application>
+</manifest>"#;
+
+    #[test]
+    fn read_link_handlers() {
+        assert_eq!(
+            read_manifest_link_handlers(MANIFEST),
+            vec![
+                LinkHandler {
+                    component: "com.example.ItemActivity".to_string(),
+                    filters: vec![IntentFilter {
+                        actions: vec!["android.intent.action.VIEW".to_string()],
+                        categories: vec![
+                            "android.intent.category.DEFAULT".to_string(),
+                            "android.intent.category.BROWSABLE".to_string()
+                        ],
+                        schemes: vec!["https".to_string()],
+                        hosts: vec!["example.com".to_string()],
+                        paths: vec!["/item*".to_string()],
+                    }],
+                },
+                LinkHandler {
+                    component: "com.example.Other".to_string(),
+                    filters: vec![IntentFilter {
+                        schemes: vec!["myapp".to_string()],
+                        hosts: vec!["open".to_string()],
+                        ..Default::default()
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_link_report() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public Lcom/example/ItemActivity$1;
+                .super Ljava/lang/Object;
+
+                .method static handle(Landroid/net/Uri;)V
+                    .locals 1
+                    const-string v0, "id"
+                    invoke-virtual {p0, v0}, Landroid/net/Uri;->getQueryParameter(Ljava/lang/String;)Ljava/lang/String;
+                    const-string v0, "myapp://open?tab=1"
+                    invoke-static {v0}, Landroid/net/Uri;->parse(Ljava/lang/String;)Landroid/net/Uri;
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, class) = Class::read(&input)?;
+
+        let mut report = DeepLinkReport::new(read_manifest_link_handlers(MANIFEST));
+        report.add_class(&class);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"https://example.com/item*
+    handled by com.example.ItemActivity
+        query parameters: id
+myapp://open
+    handled by com.example.Other (not browsable)
+    "myapp://open?tab=1" parsed in <void com.example.ItemActivity$1.handle(android.net.Uri)>
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use std::io::Read;
+
+use super::{invalid, Reader};
+use crate::error::Error;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Number of a `classes*.dex` file name, 1 for `classes.dex`.
+fn get_dex_number(name: &str) -> Option<usize> {
+    let number = name.strip_prefix("classes")?.strip_suffix(".dex")?;
+    if number.is_empty() {
+        Some(1)
+    } else if number.starts_with('0') {
+        None
+    } else {
+        number.parse().ok().filter(|number| *number > 1)
+    }
+}
+
+/// Extracts the `classes*.dex` files from the top level of an APK (ZIP archive), in the order
+/// the runtime loads them. Returns the file names along with the data.
+pub fn read_dex_files(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    // The end of central directory record is followed by a comment of up to 64 KiB
+    let end = (0..data.len().saturating_sub(21))
+        .rev()
+        .take(0x10000 + 22)
+        .find(|pos| Reader::new(data, *pos).u32().ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| invalid("not a ZIP archive"))?;
+    let mut reader = Reader::new(data, end + 10);
+    let entries = reader.u16()?;
+    reader.u32()?; // size of the central directory
+    let mut reader = Reader::new(data, reader.usize()?);
+
+    let mut result = Vec::new();
+    for _ in 0..entries {
+        if reader.u32()? != CENTRAL_DIRECTORY_ENTRY {
+            return Err(invalid("corrupt ZIP central directory"));
+        }
+        reader.bytes(6)?; // versions and flags
+        let method = reader.u16()?;
+        reader.bytes(8)?; // modification time and CRC-32
+        let compressed_size = reader.usize()?;
+        let size = reader.usize()?;
+        let name_length = usize::from(reader.u16()?);
+        let extra_length = usize::from(reader.u16()?);
+        let comment_length = usize::from(reader.u16()?);
+        reader.bytes(8)?; // disk number and attributes
+        let header_offset = reader.usize()?;
+        let name = String::from_utf8_lossy(reader.bytes(name_length)?).to_string();
+        reader.bytes(extra_length + comment_length)?;
+
+        let Some(number) = get_dex_number(&name) else {
+            continue;
+        };
+
+        let mut header = Reader::new(data, header_offset);
+        if header.u32()? != LOCAL_FILE_HEADER {
+            return Err(invalid(format!("corrupt ZIP entry {name}")));
+        }
+        header.bytes(22)?;
+        let name_length = usize::from(header.u16()?);
+        let extra_length = usize::from(header.u16()?);
+        header.bytes(name_length + extra_length)?;
+        let compressed = header.bytes(compressed_size)?;
+
+        let contents = match method {
+            STORED => compressed.to_vec(),
+            DEFLATED => {
+                let mut contents = Vec::with_capacity(size);
+                flate2::read::DeflateDecoder::new(compressed)
+                    .read_to_end(&mut contents)
+                    .map_err(|error| invalid(format!("failed decompressing {name}: {error}")))?;
+                contents
+            }
+            other => {
+                return Err(invalid(format!(
+                    "unsupported compression method {other} of {name}"
+                )))
+            }
+        };
+        result.push((number, name, contents));
+    }
+
+    result.sort_by_key(|(number, _, _)| *number);
+    Ok(result
+        .into_iter()
+        .map(|(_, name, contents)| (name, contents))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Builds a ZIP archive from file names, compression methods and contents. Checksums and
+    /// timestamps are left zero, the reader ignores them.
+    fn build_zip(files: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        for (name, method, contents) in files {
+            let compressed = if *method == DEFLATED {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(contents).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                contents.to_vec()
+            };
+
+            let offset = data.len() as u32;
+            data.extend_from_slice(&LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&compressed);
+
+            directory.extend_from_slice(&CENTRAL_DIRECTORY_ENTRY.to_le_bytes());
+            directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            directory.extend_from_slice(&method.to_le_bytes());
+            directory.extend_from_slice(&[0; 8]);
+            directory.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(b"\x07\x00comment");
+        data
+    }
+
+    #[test]
+    fn dex_numbers() {
+        assert_eq!(get_dex_number("classes.dex"), Some(1));
+        assert_eq!(get_dex_number("classes2.dex"), Some(2));
+        assert_eq!(get_dex_number("classes10.dex"), Some(10));
+        assert_eq!(get_dex_number("classes1.dex"), None);
+        assert_eq!(get_dex_number("classes02.dex"), None);
+        assert_eq!(get_dex_number("assets/classes.dex"), None);
+        assert_eq!(get_dex_number("classes.jar"), None);
+    }
+
+    #[test]
+    fn multidex() {
+        let large = b"dex\n035\0".repeat(100);
+        let data = build_zip(&[
+            ("AndroidManifest.xml", STORED, b"manifest"),
+            ("classes10.dex", STORED, b"tenth"),
+            ("classes2.dex", DEFLATED, &large),
+            ("assets/classes3.dex", STORED, b"asset"),
+            ("classes.dex", DEFLATED, b"first"),
+        ]);
+        assert_eq!(
+            read_dex_files(&data).unwrap(),
+            [
+                ("classes.dex".to_string(), b"first".to_vec()),
+                ("classes2.dex".to_string(), large.clone()),
+                ("classes10.dex".to_string(), b"tenth".to_vec()),
+            ]
+        );
+
+        assert!(read_dex_files(&build_zip(&[])).unwrap().is_empty());
+        assert!(read_dex_files(b"dex\n035\0").is_err());
+
+        // Unsupported compression method
+        let data = build_zip(&[("classes.dex", 12, b"bzip2")]);
+        assert!(read_dex_files(&data).is_err());
+    }
+
+    #[test]
+    fn truncated_archive() {
+        let data = build_zip(&[
+            ("classes.dex", STORED, b"first"),
+            ("classes2.dex", DEFLATED, &b"second".repeat(100)),
+        ]);
+
+        // End of central directory record missing
+        assert!(read_dex_files(&data[..data.len() - 30]).is_err());
+
+        // Entry extending past the end of the archive
+        let end = data.len() - 22 - "comment".len();
+        let directory = Reader::new(&data, end + 16).usize().unwrap();
+        let mut truncated = data.clone();
+        truncated[directory + 20..directory + 24].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert!(read_dex_files(&truncated).is_err());
+    }
+}
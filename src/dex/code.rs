@@ -0,0 +1,843 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::{invalid, DexFile};
+use crate::error::Error;
+use crate::instruction::{
+    get_command, get_parameter_kinds, CommandData, CommandParameter, Instruction, ParameterKind,
+    Register, Registers,
+};
+use crate::literal::Literal;
+use crate::r#type::Type;
+
+/// Instruction formats as named in the dex documentation: size in code units, number of
+/// registers and kind of the additional operand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    F10x,
+    F12x,
+    F11n,
+    F11x,
+    F10t,
+    F20t,
+    F22x,
+    F21t,
+    F21s,
+    F21h,
+    F21c,
+    F23x,
+    F22b,
+    F22t,
+    F22s,
+    F22c,
+    F32x,
+    F30t,
+    F31t,
+    F31i,
+    F31c,
+    F35c,
+    F3rc,
+    F45cc,
+    F4rcc,
+    F51l,
+}
+
+/// Label kinds in the order labels at the same address are listed, after `try_end` which is
+/// followed by the catch directives.
+const LABEL_KINDS: [&str; 10] = [
+    "goto",
+    "cond",
+    "pswitch",
+    "sswitch",
+    "catch",
+    "catchall",
+    "try_start",
+    "pswitch_data",
+    "sswitch_data",
+    "array",
+];
+
+/// Returns the Smali name and the format of an opcode, `None` for unused opcodes.
+fn get_opcode(opcode: u8) -> Option<(&'static str, Format)> {
+    use Format::*;
+
+    let format = match opcode {
+        0x00 | 0x0e => F10x,
+        0x01 | 0x04 | 0x07 | 0x21 | 0x7b..=0x8f | 0xb0..=0xcf => F12x,
+        0x02 | 0x05 | 0x08 => F22x,
+        0x03 | 0x06 | 0x09 => F32x,
+        0x0a..=0x0d | 0x0f..=0x11 | 0x1d | 0x1e | 0x27 => F11x,
+        0x12 => F11n,
+        0x13 | 0x16 => F21s,
+        0x14 | 0x17 => F31i,
+        0x15 | 0x19 => F21h,
+        0x18 => F51l,
+        0x1a | 0x1c | 0x1f | 0x22 | 0x60..=0x6d | 0xfe | 0xff => F21c,
+        0x1b => F31c,
+        0x20 | 0x23 | 0x52..=0x5f => F22c,
+        0x24 | 0x6e..=0x72 | 0xfc => F35c,
+        0x25 | 0x74..=0x78 | 0xfd => F3rc,
+        0x26 | 0x2b | 0x2c => F31t,
+        0x28 => F10t,
+        0x29 => F20t,
+        0x2a => F30t,
+        0x2d..=0x31 | 0x44..=0x51 | 0x90..=0xaf => F23x,
+        0x32..=0x37 => F22t,
+        0x38..=0x3d => F21t,
+        0xd0..=0xd7 => F22s,
+        0xd8..=0xe2 => F22b,
+        0xfa => F45cc,
+        0xfb => F4rcc,
+        _ => return None,
+    };
+    Some((get_command(opcode)?, format))
+}
+
+/// Operands of an instruction in the order of the Smali notation.
+#[derive(Debug, Default)]
+struct Operands {
+    registers: Vec<u32>,
+    /// First register and register count of `/range` instructions
+    range: Option<(u32, u32)>,
+    literal: i64,
+    /// Constant pool indexes, `invoke-polymorphic` has two of them
+    indexes: Vec<u32>,
+    /// Branch target or payload address relative to the instruction
+    offset: Option<i32>,
+}
+
+#[derive(Debug)]
+enum Item {
+    Command(&'static str, Box<Operands>),
+    PackedSwitch(i32, Vec<i32>),
+    SparseSwitch(Vec<(i32, i32)>),
+    Array(usize, Vec<Literal>),
+}
+
+/// Decodes the instruction at the given address, returning it along with its size in code
+/// units.
+fn decode(insns: &[u16], address: usize) -> Result<(Item, usize), Error> {
+    let unit = |index: usize| {
+        insns
+            .get(address + index)
+            .copied()
+            .ok_or_else(|| invalid(format!("truncated instruction at {address:#x}")))
+    };
+    let long = |index: usize| -> Result<u32, Error> {
+        Ok(u32::from(unit(index)?) | (u32::from(unit(index + 1)?) << 16))
+    };
+
+    let first = unit(0)?;
+    let opcode = (first & 0xff) as u8;
+    let high = first >> 8;
+    let (a, b, aa) = (u32::from(high & 0xf), u32::from(high >> 4), u32::from(high));
+
+    // Data payloads are disguised as nop instructions
+    if opcode == 0 && high != 0 {
+        return match high {
+            0x01 => {
+                let count = usize::from(unit(1)?);
+                let first_key = long(2)? as i32;
+                let targets = (0..count)
+                    .map(|i| Ok(long(4 + i * 2)? as i32))
+                    .collect::<Result<_, Error>>()?;
+                Ok((Item::PackedSwitch(first_key, targets), 4 + count * 2))
+            }
+            0x02 => {
+                let count = usize::from(unit(1)?);
+                let entries = (0..count)
+                    .map(|i| Ok((long(2 + i * 2)? as i32, long(2 + (count + i) * 2)? as i32)))
+                    .collect::<Result<_, Error>>()?;
+                Ok((Item::SparseSwitch(entries), 2 + count * 4))
+            }
+            0x03 => {
+                let width = usize::from(unit(1)?);
+                let count = long(2)? as usize;
+                let size = width
+                    .checked_mul(count)
+                    .map(|bytes| 4 + bytes.div_ceil(2))
+                    .filter(|size| address + size <= insns.len())
+                    .ok_or_else(|| invalid(format!("truncated array data at {address:#x}")))?;
+                let bytes = insns[address + 4..address + size]
+                    .iter()
+                    .flat_map(|unit| unit.to_le_bytes())
+                    .collect::<Vec<_>>();
+                let elements = bytes
+                    .chunks_exact(width.max(1))
+                    .take(count)
+                    .map(|element| {
+                        let mut value = [0; 8];
+                        value[..element.len()].copy_from_slice(element);
+                        let value = i64::from_le_bytes(value);
+                        Literal::Long(value)
+                            .with_element_size(width)
+                            .ok_or_else(|| {
+                                invalid(format!(
+                                    "invalid array element size {width} at {address:#x}"
+                                ))
+                            })
+                    })
+                    .collect::<Result<_, Error>>()?;
+                Ok((Item::Array(width, elements), size))
+            }
+            other => Err(invalid(format!(
+                "unknown payload type {other:#x} at {address:#x}"
+            ))),
+        };
+    }
+
+    let (name, format) = get_opcode(opcode)
+        .ok_or_else(|| invalid(format!("unknown opcode {opcode:#04x} at {address:#x}")))?;
+    let mut operands = Operands::default();
+    let size = match format {
+        Format::F10x => 1,
+        Format::F12x => {
+            operands.registers = vec![a, b];
+            1
+        }
+        Format::F11n => {
+            operands.registers = vec![a];
+            operands.literal = i64::from((high as u8 as i8) >> 4);
+            1
+        }
+        Format::F11x => {
+            operands.registers = vec![aa];
+            1
+        }
+        Format::F10t => {
+            operands.offset = Some(i32::from(high as u8 as i8));
+            1
+        }
+        Format::F20t => {
+            operands.offset = Some(i32::from(unit(1)? as i16));
+            2
+        }
+        Format::F22x => {
+            operands.registers = vec![aa, u32::from(unit(1)?)];
+            2
+        }
+        Format::F21t => {
+            operands.registers = vec![aa];
+            operands.offset = Some(i32::from(unit(1)? as i16));
+            2
+        }
+        Format::F21s => {
+            operands.registers = vec![aa];
+            operands.literal = i64::from(unit(1)? as i16);
+            2
+        }
+        Format::F21h => {
+            operands.registers = vec![aa];
+            operands.literal = if name == "const-wide/high16" {
+                i64::from(unit(1)? as i16) << 48
+            } else {
+                i64::from(i32::from(unit(1)? as i16) << 16)
+            };
+            2
+        }
+        Format::F21c => {
+            operands.registers = vec![aa];
+            operands.indexes = vec![u32::from(unit(1)?)];
+            2
+        }
+        Format::F23x => {
+            let bc = unit(1)?;
+            operands.registers = vec![aa, u32::from(bc & 0xff), u32::from(bc >> 8)];
+            2
+        }
+        Format::F22b => {
+            let bc = unit(1)?;
+            operands.registers = vec![aa, u32::from(bc & 0xff)];
+            operands.literal = i64::from((bc >> 8) as u8 as i8);
+            2
+        }
+        Format::F22t => {
+            operands.registers = vec![a, b];
+            operands.offset = Some(i32::from(unit(1)? as i16));
+            2
+        }
+        Format::F22s => {
+            operands.registers = vec![a, b];
+            operands.literal = i64::from(unit(1)? as i16);
+            2
+        }
+        Format::F22c => {
+            operands.registers = vec![a, b];
+            operands.indexes = vec![u32::from(unit(1)?)];
+            2
+        }
+        Format::F32x => {
+            operands.registers = vec![u32::from(unit(1)?), u32::from(unit(2)?)];
+            3
+        }
+        Format::F30t => {
+            operands.offset = Some(long(1)? as i32);
+            3
+        }
+        Format::F31t => {
+            operands.registers = vec![aa];
+            operands.offset = Some(long(1)? as i32);
+            3
+        }
+        Format::F31i => {
+            operands.registers = vec![aa];
+            operands.literal = i64::from(long(1)? as i32);
+            3
+        }
+        Format::F31c => {
+            operands.registers = vec![aa];
+            operands.indexes = vec![long(1)?];
+            3
+        }
+        Format::F35c | Format::F45cc => {
+            // Register count in B, the fifth register in A
+            let count = b as usize;
+            let list = unit(2)?;
+            let all = [
+                u32::from(list & 0xf),
+                u32::from((list >> 4) & 0xf),
+                u32::from((list >> 8) & 0xf),
+                u32::from(list >> 12),
+                a,
+            ];
+            operands.registers = all
+                .get(..count)
+                .ok_or_else(|| invalid(format!("too many registers at {address:#x}")))?
+                .to_vec();
+            operands.indexes = vec![u32::from(unit(1)?)];
+            if format == Format::F45cc {
+                operands.indexes.push(u32::from(unit(3)?));
+                4
+            } else {
+                3
+            }
+        }
+        Format::F3rc | Format::F4rcc => {
+            operands.range = Some((u32::from(unit(2)?), aa));
+            operands.indexes = vec![u32::from(unit(1)?)];
+            if format == Format::F4rcc {
+                operands.indexes.push(u32::from(unit(3)?));
+                4
+            } else {
+                3
+            }
+        }
+        Format::F51l => {
+            operands.registers = vec![aa];
+            operands.literal = (i64::from(long(3)?) << 32) | i64::from(long(1)?);
+            5
+        }
+    };
+    Ok((Item::Command(name, Box::new(operands)), size))
+}
+
+/// Kind of the label an instruction branches to, this determines the label's name.
+fn get_target_kind(command: &str) -> &'static str {
+    match command {
+        "packed-switch" => "pswitch_data",
+        "sparse-switch" => "sswitch_data",
+        "fill-array-data" => "array",
+        command if command.starts_with("goto") => "goto",
+        _ => "cond",
+    }
+}
+
+fn get_target(address: usize, offset: i32) -> Result<usize, Error> {
+    address
+        .checked_add_signed(offset as isize)
+        .ok_or_else(|| invalid(format!("invalid branch target at {address:#x}")))
+}
+
+/// Labels named like apktool does: by kind, numbered in the order of their addresses.
+#[derive(Debug, Default)]
+struct Labels {
+    addresses: HashMap<&'static str, BTreeSet<usize>>,
+}
+
+impl Labels {
+    fn add(&mut self, kind: &'static str, address: usize) {
+        self.addresses.entry(kind).or_default().insert(address);
+    }
+
+    fn contains(&self, kind: &str, address: usize) -> bool {
+        self.addresses
+            .get(kind)
+            .is_some_and(|addresses| addresses.contains(&address))
+    }
+
+    fn get(&self, kind: &str, address: usize) -> String {
+        let index = self
+            .addresses
+            .get(kind)
+            .map_or(0, |addresses| addresses.range(..address).count());
+        format!("{kind}_{index:x}")
+    }
+}
+
+/// Try block with its end address and the handlers as exception type and address.
+#[derive(Debug)]
+struct TryBlock {
+    start: usize,
+    end: usize,
+    handlers: Vec<(Option<Type>, usize)>,
+}
+
+/// Instructions and debug information of a method.
+#[derive(Debug)]
+pub(super) struct Code {
+    pub locals: usize,
+    pub instructions: Vec<Instruction>,
+    pub parameter_names: Vec<Option<String>>,
+}
+
+impl DexFile<'_> {
+    pub(super) fn read_code(&self, offset: usize) -> Result<Code, Error> {
+        let mut reader = self.reader(offset);
+        let registers = u32::from(reader.u16()?);
+        let ins = u32::from(reader.u16()?);
+        reader.u16()?; // outs_size
+        let tries = reader.u16()?;
+        let debug_info_offset = reader.usize()?;
+        let size = reader.usize()?;
+        let insns = reader
+            .bytes(size.saturating_mul(2))?
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect::<Vec<_>>();
+        if tries != 0 && size % 2 == 1 {
+            reader.u16()?; // padding
+        }
+
+        let mut try_items = Vec::new();
+        for _ in 0..tries {
+            let start = reader.usize()?;
+            let count = usize::from(reader.u16()?);
+            let handler_offset = usize::from(reader.u16()?);
+            try_items.push((start, start + count, handler_offset));
+        }
+        let handlers_start = reader.pos;
+        let mut try_blocks = Vec::new();
+        for (start, end, handler_offset) in try_items {
+            let mut reader = self.reader(handlers_start + handler_offset);
+            let count = reader.sleb128()?;
+            let mut handlers = Vec::new();
+            for _ in 0..count.unsigned_abs() {
+                let exception = self.get_type(reader.uleb128()?)?.clone();
+                handlers.push((Some(exception), reader.uleb128()? as usize));
+            }
+            if count <= 0 {
+                handlers.push((None, reader.uleb128()? as usize));
+            }
+            try_blocks.push(TryBlock {
+                start,
+                end,
+                handlers,
+            });
+        }
+
+        // Parameters occupy the last registers
+        let locals = registers
+            .checked_sub(ins)
+            .ok_or_else(|| invalid("more parameter registers than registers"))?;
+        let register = |index: u32| {
+            if index >= locals {
+                Register::Parameter((index - locals) as usize)
+            } else {
+                Register::Local(index as usize)
+            }
+        };
+
+        let mut items = Vec::new();
+        let mut address = 0;
+        while address < insns.len() {
+            let (item, size) = decode(&insns, address)?;
+            items.push((address, item));
+            address += size;
+        }
+
+        // Labels need to be known before the instructions referring to them are converted
+        let mut labels = Labels::default();
+        let mut payload_sources = HashMap::new();
+        for (address, item) in &items {
+            if let Item::Command(command, operands) = item {
+                if let Some(offset) = operands.offset {
+                    let target = get_target(*address, offset)?;
+                    let kind = get_target_kind(command);
+                    labels.add(kind, target);
+                    if kind != "goto" && kind != "cond" {
+                        payload_sources.entry(target).or_insert(*address);
+                    }
+                }
+            }
+        }
+        for (address, item) in &items {
+            let (kind, targets) = match item {
+                Item::PackedSwitch(_, targets) => ("pswitch", targets.clone()),
+                Item::SparseSwitch(entries) => (
+                    "sswitch",
+                    entries.iter().map(|(_, target)| *target).collect(),
+                ),
+                _ => continue,
+            };
+            if let Some(source) = payload_sources.get(address) {
+                for target in targets {
+                    labels.add(kind, get_target(*source, target)?);
+                }
+            }
+        }
+        for try_block in &try_blocks {
+            labels.add("try_start", try_block.start);
+            labels.add("try_end", try_block.end);
+            for (exception, address) in &try_block.handlers {
+                labels.add(
+                    if exception.is_some() {
+                        "catch"
+                    } else {
+                        "catchall"
+                    },
+                    *address,
+                );
+            }
+        }
+
+        let (parameter_names, events) = if debug_info_offset != 0 {
+            self.read_debug_info(debug_info_offset, &register)?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let mut events = events.into_iter().peekable();
+
+        let mut instructions = Vec::new();
+        let mut add_labels = |address: usize, instructions: &mut Vec<Instruction>| {
+            if labels.contains("try_end", address) {
+                let end_label = labels.get("try_end", address);
+                instructions.push(Instruction::Label(end_label.clone()));
+                for try_block in try_blocks.iter().filter(|block| block.end == address) {
+                    for (exception, target) in &try_block.handlers {
+                        instructions.push(Instruction::Catch {
+                            exception: exception.clone(),
+                            start_label: labels.get("try_start", try_block.start),
+                            end_label: end_label.clone(),
+                            target: labels.get(
+                                if exception.is_some() {
+                                    "catch"
+                                } else {
+                                    "catchall"
+                                },
+                                *target,
+                            ),
+                        });
+                    }
+                }
+            }
+            for kind in LABEL_KINDS {
+                if labels.contains(kind, address) {
+                    instructions.push(Instruction::Label(labels.get(kind, address)));
+                }
+            }
+            while let Some((_, event)) =
+                events.next_if(|(event_address, _)| *event_address <= address)
+            {
+                instructions.push(event);
+            }
+        };
+
+        for (index, (address, item)) in items.iter().enumerate() {
+            add_labels(*address, &mut instructions);
+            let data = match item {
+                Item::Command(command, operands) => {
+                    // Alignment of the payload following, the Smali assembler adds it as needed
+                    if *command == "nop"
+                        && items
+                            .get(index + 1)
+                            .is_some_and(|(_, next)| !matches!(next, Item::Command(..)))
+                    {
+                        continue;
+                    }
+                    instructions.push(Instruction::Command {
+                        command: command.to_string(),
+                        parameters: self
+                            .get_parameters(command, operands, *address, &labels, &register)?,
+                    });
+                    continue;
+                }
+                // Payloads nothing refers to cannot be resolved
+                _ if !payload_sources.contains_key(address) => continue,
+                Item::PackedSwitch(first_key, targets) => {
+                    let source = payload_sources[address];
+                    let targets = targets
+                        .iter()
+                        .map(|target| Ok(labels.get("pswitch", get_target(source, *target)?)))
+                        .collect::<Result<_, Error>>()?;
+                    CommandData::PackedSwitch(i64::from(*first_key), targets)
+                }
+                Item::SparseSwitch(entries) => {
+                    let source = payload_sources[address];
+                    let targets = entries
+                        .iter()
+                        .map(|(key, target)| {
+                            Ok((
+                                Literal::Int(*key),
+                                labels.get("sswitch", get_target(source, *target)?),
+                            ))
+                        })
+                        .collect::<Result<_, Error>>()?;
+                    CommandData::SparseSwitch(targets)
+                }
+                Item::Array(width, elements) => CommandData::Array(*width, elements.clone()),
+            };
+            instructions.push(Instruction::Data(data));
+        }
+        add_labels(insns.len(), &mut instructions);
+
+        Ok(Code {
+            locals: locals as usize,
+            instructions,
+            parameter_names,
+        })
+    }
+
+    fn get_parameters(
+        &self,
+        command: &str,
+        operands: &Operands,
+        address: usize,
+        labels: &Labels,
+        register: &dyn Fn(u32) -> Register,
+    ) -> Result<Vec<CommandParameter>, Error> {
+        let kinds = get_parameter_kinds(command)
+            .ok_or_else(|| invalid(format!("unsupported instruction {command}")))?;
+        let mut registers = operands.registers.iter();
+        let mut indexes = operands.indexes.iter();
+        let missing = || invalid(format!("missing operand of {command} at {address:#x}"));
+
+        let mut result = Vec::new();
+        for kind in kinds {
+            let mut next_register = || registers.next().map(|index| register(*index));
+            let mut next_index = || indexes.next().copied().ok_or_else(missing);
+            let get_label = |kind: &str| {
+                let offset = operands.offset.ok_or_else(missing)?;
+                Ok::<_, Error>(labels.get(kind, get_target(address, offset)?))
+            };
+            result.push(match kind {
+                ParameterKind::Result => {
+                    CommandParameter::Result(next_register().ok_or_else(missing)?)
+                }
+                ParameterKind::DefaultEmptyResult => CommandParameter::DefaultEmptyResult(None),
+                ParameterKind::Register => {
+                    CommandParameter::Register(next_register().ok_or_else(missing)?)
+                }
+                ParameterKind::Registers => CommandParameter::Registers(match operands.range {
+                    Some((_, 0)) => Registers::List(Vec::new()),
+                    // Like apktool, ranges starting with a local register use local notation
+                    Some((first, count)) => match (register(first), first + count - 1) {
+                        (Register::Local(first), last) => {
+                            Registers::Range(Register::Local(first), Register::Local(last as usize))
+                        }
+                        (first, last) => Registers::Range(first, register(last)),
+                    },
+                    None => {
+                        Registers::List(registers.by_ref().map(|index| register(*index)).collect())
+                    }
+                }),
+                ParameterKind::Int => {
+                    CommandParameter::Literal(Literal::Int(operands.literal as i32))
+                }
+                ParameterKind::Long => CommandParameter::Literal(Literal::Long(operands.literal)),
+                ParameterKind::String => {
+                    CommandParameter::Literal(self.get_string_literal(next_index()?)?)
+                }
+                ParameterKind::Class => {
+                    CommandParameter::Literal(Literal::Class(self.get_type(next_index()?)?.clone()))
+                }
+                ParameterKind::MethodHandle => {
+                    CommandParameter::Literal(self.get_method_handle(next_index()?)?)
+                }
+                ParameterKind::MethodType => CommandParameter::Literal(Literal::MethodType(
+                    self.get_proto(next_index()?)?.clone(),
+                )),
+                ParameterKind::Label => {
+                    CommandParameter::Label(get_label(get_target_kind(command))?)
+                }
+                ParameterKind::Type => {
+                    CommandParameter::Type(self.get_type(next_index()?)?.clone())
+                }
+                ParameterKind::Field => {
+                    CommandParameter::Field(self.get_field(next_index()?)?.clone())
+                }
+                ParameterKind::Method => {
+                    CommandParameter::Method(self.get_method(next_index()?)?.clone())
+                }
+                ParameterKind::CallSite => {
+                    CommandParameter::CallSite(self.get_call_site(next_index()?)?)
+                }
+                ParameterKind::Data => {
+                    CommandParameter::Data(CommandData::Label(get_label(get_target_kind(command))?))
+                }
+            });
+        }
+        Ok(result)
+    }
+
+    /// Reads the parameter names and the line numbers and local variables by address.
+    #[allow(clippy::type_complexity)]
+    fn read_debug_info(
+        &self,
+        offset: usize,
+        register: &dyn Fn(u32) -> Register,
+    ) -> Result<(Vec<Option<String>>, Vec<(usize, Instruction)>), Error> {
+        let mut reader = self.reader(offset);
+        let mut line = i64::from(reader.uleb128()?);
+        let mut parameter_names = Vec::new();
+        for _ in 0..reader.uleb128()? {
+            parameter_names.push(match reader.uleb128p1()? {
+                Some(index) => Some(self.get_string(index)?.to_string()),
+                None => None,
+            });
+        }
+
+        let mut events = Vec::new();
+        let mut address = 0usize;
+        loop {
+            match reader.u8()? {
+                // DBG_END_SEQUENCE
+                0x00 => break,
+                // DBG_ADVANCE_PC
+                0x01 => address += reader.uleb128()? as usize,
+                // DBG_ADVANCE_LINE
+                0x02 => line += i64::from(reader.sleb128()?),
+                // DBG_START_LOCAL, DBG_START_LOCAL_EXTENDED
+                opcode @ (0x03 | 0x04) => {
+                    let index = reader.uleb128()?;
+                    let name = reader.uleb128p1()?;
+                    let local_type = reader.uleb128p1()?;
+                    if opcode == 0x04 {
+                        reader.uleb128p1()?; // signature
+                    }
+                    if let (Some(name), Some(local_type)) = (name, local_type) {
+                        events.push((
+                            address,
+                            Instruction::Local {
+                                register: register(index).to_string(),
+                                name: self.get_string_literal(name)?,
+                                local_type: self.get_type(local_type)?.clone(),
+                            },
+                        ));
+                    }
+                }
+                // DBG_END_LOCAL
+                0x05 => {
+                    reader.uleb128()?;
+                }
+                // DBG_RESTART_LOCAL
+                0x06 => {
+                    let index = reader.uleb128()?;
+                    events.push((
+                        address,
+                        Instruction::LocalRestart {
+                            register: register(index).to_string(),
+                        },
+                    ));
+                }
+                // DBG_SET_PROLOGUE_END, DBG_SET_EPILOGUE_BEGIN
+                0x07 | 0x08 => (),
+                // DBG_SET_FILE
+                0x09 => {
+                    reader.uleb128p1()?;
+                }
+                // Special opcodes advance both address and line
+                opcode => {
+                    let adjusted = usize::from(opcode - 0x0a);
+                    line += (adjusted % 15) as i64 - 4;
+                    address += adjusted / 15;
+                    events.push((address, Instruction::LineNumber(line, line)));
+                }
+            }
+        }
+        Ok((parameter_names, events))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_command(insns: &[u16]) -> (&'static str, Operands, usize) {
+        match decode(insns, 0).unwrap() {
+            (Item::Command(name, operands), size) => (name, *operands, size),
+            (item, _) => panic!("unexpected item {item:?}"),
+        }
+    }
+
+    #[test]
+    fn decode_register_lists() {
+        // invoke-virtual {v1, v2, v3, v4, v5}, method 7
+        let (name, operands, size) = decode_command(&[0x556e, 0x0007, 0x4321]);
+        assert_eq!(name, "invoke-virtual");
+        assert_eq!(operands.registers, [1, 2, 3, 4, 5]);
+        assert_eq!(operands.indexes, [7]);
+        assert_eq!(size, 3);
+
+        // filled-new-array {v2}, type 3
+        let (name, operands, _) = decode_command(&[0x1024, 0x0003, 0xfff2]);
+        assert_eq!(name, "filled-new-array");
+        assert_eq!(operands.registers, [2]);
+
+        // invoke-static/range {v16 .. v18}, method 0x1234
+        let (name, operands, size) = decode_command(&[0x0377, 0x1234, 0x0010]);
+        assert_eq!(name, "invoke-static/range");
+        assert_eq!(operands.range, Some((16, 3)));
+        assert_eq!(operands.indexes, [0x1234]);
+        assert_eq!(size, 3);
+
+        // invoke-polymorphic {v0, v1}, method 2, prototype 5
+        let (name, operands, size) = decode_command(&[0x20fa, 0x0002, 0x0010, 0x0005]);
+        assert_eq!(name, "invoke-polymorphic");
+        assert_eq!(operands.registers, [0, 1]);
+        assert_eq!(operands.indexes, [2, 5]);
+        assert_eq!(size, 4);
+
+        // invoke-polymorphic/range {v4 .. v5}, method 2, prototype 5
+        let (name, operands, size) = decode_command(&[0x02fb, 0x0002, 0x0004, 0x0005]);
+        assert_eq!(name, "invoke-polymorphic/range");
+        assert_eq!(operands.range, Some((4, 2)));
+        assert_eq!(operands.indexes, [2, 5]);
+        assert_eq!(size, 4);
+
+        // Six registers don't fit into the format
+        assert!(decode(&[0x606e, 0x0007, 0x4321], 0).is_err());
+        // Missing prototype index
+        assert!(decode(&[0x20fa, 0x0002, 0x0010], 0).is_err());
+    }
+
+    #[test]
+    fn decode_wide_literal() {
+        // const-wide v3, 0x0123456789abcdef
+        let (name, operands, size) = decode_command(&[0x0318, 0xcdef, 0x89ab, 0x4567, 0x0123]);
+        assert_eq!(name, "const-wide");
+        assert_eq!(operands.registers, [3]);
+        assert_eq!(operands.literal, 0x0123_4567_89ab_cdef);
+        assert_eq!(size, 5);
+
+        let (_, operands, _) = decode_command(&[0x0018, 0xffff, 0xffff, 0xffff, 0xffff]);
+        assert_eq!(operands.literal, -1);
+
+        assert!(decode(&[0x0318, 0xcdef, 0x89ab, 0x4567], 0).is_err());
+    }
+
+    #[test]
+    fn opcodes() {
+        // Each opcode with a format has a command and vice versa
+        for opcode in 0..=u8::MAX {
+            assert_eq!(
+                get_opcode(opcode).is_some(),
+                get_command(opcode).is_some(),
+                "opcode {opcode:#04x}"
+            );
+        }
+        assert_eq!(
+            get_opcode(0xfd),
+            Some(("invoke-custom/range", Format::F3rc))
+        );
+        assert_eq!(get_opcode(0x3e), None);
+    }
+}
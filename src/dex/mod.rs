@@ -0,0 +1,730 @@
+//! Reader for dex files, producing the same classes as parsing the Smali code apktool
+//! disassembles from them. See [dex format documentation](https://source.android.com/docs/core/runtime/dex-format).
+
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use crate::access_flag::AccessFlag;
+use crate::annotation::Annotation;
+use crate::class::Class;
+use crate::error::Error;
+use crate::field::Field;
+use crate::literal::Literal;
+use crate::method::{Method, MethodParameter};
+use crate::r#type::{CallSignature, FieldSignature, MethodSignature, Type};
+
+mod apk;
+mod code;
+mod values;
+
+pub use apk::read_dex_files;
+
+/// Marks a missing index, e.g. for classes without a superclass.
+const NO_INDEX: u32 = 0xffff_ffff;
+
+const HEADER_SIZE: usize = 0x70;
+const ENDIAN_CONSTANT: u32 = 0x1234_5678;
+
+const TYPE_CALL_SITE_ID_ITEM: u16 = 0x0007;
+const TYPE_METHOD_HANDLE_ITEM: u16 = 0x0008;
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidDex(message.into())
+}
+
+/// Sequential reader of the dex data, failing on reads past the end.
+#[derive(Debug, Clone)]
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(count)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| invalid(format!("unexpected end of data at offset {}", self.pos)))?;
+        let result = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads an offset or a size, these are 32-bit values.
+    fn usize(&mut self) -> Result<usize, Error> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn uleb128(&mut self) -> Result<u32, Error> {
+        let mut result = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            result |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(invalid(format!(
+            "overlong LEB128 value at offset {}",
+            self.pos
+        )))
+    }
+
+    fn sleb128(&mut self) -> Result<i32, Error> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            result |= u32::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift >= 35 {
+                return Err(invalid(format!(
+                    "overlong LEB128 value at offset {}",
+                    self.pos
+                )));
+            }
+        }
+        // Sign-extend from the last bit read
+        let unused = 32 - shift.min(32);
+        Ok((result << unused) as i32 >> unused)
+    }
+
+    /// Reads a value stored plus one, so that -1 (no value) is encoded as zero.
+    fn uleb128p1(&mut self) -> Result<Option<u32>, Error> {
+        Ok(self.uleb128()?.checked_sub(1))
+    }
+}
+
+/// Decodes the Modified UTF-8 encoding of dex strings, terminated by a zero byte.
+fn decode_mutf8(reader: &mut Reader<'_>) -> Result<String, Error> {
+    let mut units = Vec::new();
+    loop {
+        let byte = u16::from(reader.u8()?);
+        let unit = if byte == 0 {
+            break;
+        } else if byte & 0x80 == 0 {
+            byte
+        } else if byte & 0xe0 == 0xc0 {
+            ((byte & 0x1f) << 6) | (u16::from(reader.u8()?) & 0x3f)
+        } else if byte & 0xf0 == 0xe0 {
+            let second = u16::from(reader.u8()?) & 0x3f;
+            let third = u16::from(reader.u8()?) & 0x3f;
+            ((byte & 0x0f) << 12) | (second << 6) | third
+        } else {
+            return Err(invalid(format!(
+                "invalid string data at offset {}",
+                reader.pos - 1
+            )));
+        };
+        units.push(unit);
+    }
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Access flags in the order of the bits, the meaning of some bits differs between fields and
+/// methods.
+fn get_access_flags(bits: u32, is_method: bool) -> Vec<AccessFlag> {
+    let mut flags = vec![
+        (0x1, AccessFlag::Public),
+        (0x2, AccessFlag::Private),
+        (0x4, AccessFlag::Protected),
+        (0x8, AccessFlag::Static),
+        (0x10, AccessFlag::Final),
+    ];
+    if is_method {
+        flags.push((0x20, AccessFlag::Synchronized));
+        flags.push((0x40, AccessFlag::Bridge));
+        flags.push((0x80, AccessFlag::Varargs));
+    } else {
+        flags.push((0x40, AccessFlag::Volatile));
+        flags.push((0x80, AccessFlag::Transient));
+    }
+    flags.extend([
+        (0x100, AccessFlag::Native),
+        (0x200, AccessFlag::Interface),
+        (0x400, AccessFlag::Abstract),
+        (0x800, AccessFlag::Strictfp),
+        (0x1000, AccessFlag::Synthetic),
+        (0x2000, AccessFlag::Annotation),
+        (0x4000, AccessFlag::Enum),
+        (0x10000, AccessFlag::Constructor),
+        (0x20000, AccessFlag::DeclaredSynchronized),
+    ]);
+    flags
+        .into_iter()
+        .filter(|(bit, _)| bits & bit != 0)
+        .map(|(_, flag)| flag)
+        .collect()
+}
+
+/// A parsed dex file, with the constant pools resolved. Classes are read on demand.
+#[derive(Debug)]
+pub struct DexFile<'a> {
+    data: &'a [u8],
+    strings: Vec<String>,
+    types: Vec<Type>,
+    protos: Vec<CallSignature>,
+    fields: Vec<FieldSignature>,
+    methods: Vec<MethodSignature>,
+    /// Kind and field or method index of each method handle
+    method_handles: Vec<(u16, u16)>,
+    call_site_offsets: Vec<usize>,
+    class_defs_offset: usize,
+    class_defs_count: usize,
+}
+
+impl<'a> DexFile<'a> {
+    pub fn read(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE
+            || &data[0..4] != b"dex\n"
+            || data[7] != 0
+            || !data[4..7].iter().all(u8::is_ascii_digit)
+        {
+            return Err(invalid("missing dex file header"));
+        }
+
+        let mut header = Reader::new(data, 0x28);
+        if header.u32()? != ENDIAN_CONSTANT {
+            return Err(invalid("unsupported byte order"));
+        }
+        header.u32()?; // link_size
+        header.u32()?; // link_off
+        let map_offset = header.usize()?;
+        // Size and offset of each section
+        let mut section =
+            || -> Result<(usize, usize), Error> { Ok((header.usize()?, header.usize()?)) };
+        let strings = section()?;
+        let types = section()?;
+        let protos = section()?;
+        let fields = section()?;
+        let methods = section()?;
+        let class_defs = section()?;
+
+        let mut result = Self {
+            data,
+            strings: Vec::new(),
+            types: Vec::new(),
+            protos: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            method_handles: Vec::new(),
+            call_site_offsets: Vec::new(),
+            class_defs_offset: class_defs.1,
+            class_defs_count: class_defs.0,
+        };
+
+        let mut reader = result.reader(strings.1);
+        for _ in 0..strings.0 {
+            let mut string_data = result.reader(reader.usize()?);
+            string_data.uleb128()?; // length in UTF-16 code units
+            result.strings.push(decode_mutf8(&mut string_data)?);
+        }
+
+        let mut reader = result.reader(types.1);
+        for _ in 0..types.0 {
+            let descriptor = result.get_string(reader.u32()?)?;
+            let r#type = Type::from_str(descriptor)
+                .map_err(|_| invalid(format!("invalid type descriptor {descriptor}")))?;
+            result.types.push(r#type);
+        }
+
+        let mut reader = result.reader(protos.1);
+        for _ in 0..protos.0 {
+            reader.u32()?; // shorty_idx
+            let return_type = result.get_type(reader.u32()?)?.clone();
+            let parameter_types = result.read_type_list(reader.usize()?)?;
+            result.protos.push(CallSignature {
+                parameter_types,
+                return_type,
+            });
+        }
+
+        let mut reader = result.reader(fields.1);
+        for _ in 0..fields.0 {
+            let object_type = result.get_type(reader.u16()?.into())?.clone();
+            let field_type = result.get_type(reader.u16()?.into())?.clone();
+            let field_name = result.get_string(reader.u32()?)?.to_string();
+            result.fields.push(FieldSignature {
+                object_type,
+                field_name,
+                field_type,
+            });
+        }
+
+        let mut reader = result.reader(methods.1);
+        for _ in 0..methods.0 {
+            let object_type = result.get_type(reader.u16()?.into())?.clone();
+            let call_signature = result.get_proto(reader.u16()?.into())?.clone();
+            let method_name = result.get_string(reader.u32()?)?.to_string();
+            result.methods.push(MethodSignature {
+                object_type,
+                method_name,
+                call_signature,
+            });
+        }
+
+        // Sections added in later versions are only listed in the map
+        let mut reader = result.reader(map_offset);
+        for _ in 0..reader.u32()? {
+            let item_type = reader.u16()?;
+            reader.u16()?; // unused
+            let count = reader.usize()?;
+            let offset = reader.usize()?;
+            let mut items = result.reader(offset);
+            if item_type == TYPE_CALL_SITE_ID_ITEM {
+                for _ in 0..count {
+                    result.call_site_offsets.push(items.usize()?);
+                }
+            } else if item_type == TYPE_METHOD_HANDLE_ITEM {
+                for _ in 0..count {
+                    let kind = items.u16()?;
+                    items.u16()?; // unused
+                    let index = items.u16()?;
+                    items.u16()?; // unused
+                    result.method_handles.push((kind, index));
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn reader(&self, offset: usize) -> Reader<'a> {
+        Reader::new(self.data, offset)
+    }
+
+    fn get_string(&self, index: u32) -> Result<&str, Error> {
+        self.strings
+            .get(index as usize)
+            .map(String::as_str)
+            .ok_or_else(|| invalid(format!("string index {index} out of range")))
+    }
+
+    fn get_type(&self, index: u32) -> Result<&Type, Error> {
+        self.types
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("type index {index} out of range")))
+    }
+
+    fn get_proto(&self, index: u32) -> Result<&CallSignature, Error> {
+        self.protos
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("prototype index {index} out of range")))
+    }
+
+    fn get_field(&self, index: u32) -> Result<&FieldSignature, Error> {
+        self.fields
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("field index {index} out of range")))
+    }
+
+    fn get_method(&self, index: u32) -> Result<&MethodSignature, Error> {
+        self.methods
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("method index {index} out of range")))
+    }
+
+    fn read_type_list(&self, offset: usize) -> Result<Vec<Type>, Error> {
+        if offset == 0 {
+            return Ok(Vec::new());
+        }
+        let mut reader = self.reader(offset);
+        let mut result = Vec::new();
+        for _ in 0..reader.u32()? {
+            result.push(self.get_type(reader.u16()?.into())?.clone());
+        }
+        Ok(result)
+    }
+
+    pub fn class_count(&self) -> usize {
+        self.class_defs_count
+    }
+
+    /// Reads all classes defined in the file, in the order of their definitions.
+    pub fn classes(&self) -> impl Iterator<Item = Result<Class, Error>> + '_ {
+        (0..self.class_defs_count).map(|index| self.read_class(index))
+    }
+
+    /// Returns the type of a class without reading the class.
+    pub fn class_type(&self, index: usize) -> Result<&Type, Error> {
+        if index >= self.class_defs_count {
+            return Err(invalid(format!("class index {index} out of range")));
+        }
+        self.get_type(self.reader(self.class_defs_offset + index * 0x20).u32()?)
+    }
+
+    pub fn read_class(&self, index: usize) -> Result<Class, Error> {
+        let mut reader = self.reader(self.class_defs_offset + index * 0x20);
+        let class_type = self.get_type(reader.u32()?)?.clone();
+        let access_flags = get_access_flags(reader.u32()?, false);
+        let super_class = match reader.u32()? {
+            NO_INDEX => None,
+            index => Some(self.get_type(index)?.clone()),
+        }
+        // The Smali parser treats java.lang.Object as implicit superclass
+        .filter(|super_class| super_class.get_name() != "java.lang.Object");
+        let interfaces = self.read_type_list(reader.usize()?)?;
+        let source_file = match reader.u32()? {
+            NO_INDEX => None,
+            index => Some(self.get_string(index)?.to_string()),
+        };
+        let mut annotations = self.read_annotations_directory(reader.usize()?)?;
+        let class_data_offset = reader.usize()?;
+        let mut static_values = self.read_static_values(reader.usize()?)?.into_iter();
+
+        let mut fields = Vec::new();
+        let mut methods = Vec::new();
+        if class_data_offset != 0 {
+            let mut reader = self.reader(class_data_offset);
+            let static_fields = reader.uleb128()?;
+            let instance_fields = reader.uleb128()?;
+            let direct_methods = reader.uleb128()?;
+            let virtual_methods = reader.uleb128()?;
+
+            for count in [static_fields, instance_fields] {
+                let mut index = 0;
+                for _ in 0..count {
+                    index += reader.uleb128()?;
+                    let visibility = get_access_flags(reader.uleb128()?, false);
+                    let signature = self.get_field(index)?;
+                    let initial_value = if visibility.contains(&AccessFlag::Static) {
+                        static_values
+                            .next()
+                            .filter(|value| !values::is_default(value))
+                    } else {
+                        None
+                    };
+                    fields.push(Field {
+                        name: signature.field_name.clone(),
+                        field_type: signature.field_type.clone(),
+                        visibility,
+                        initial_value,
+                        annotations: annotations.fields.remove(&index).unwrap_or_default(),
+                    });
+                }
+            }
+
+            for count in [direct_methods, virtual_methods] {
+                let mut index = 0;
+                for _ in 0..count {
+                    index += reader.uleb128()?;
+                    let visibility = get_access_flags(reader.uleb128()?, true);
+                    let code_offset = reader.uleb128()? as usize;
+                    let method = self
+                        .read_method(
+                            index,
+                            visibility,
+                            code_offset,
+                            annotations.methods.remove(&index).unwrap_or_default(),
+                            annotations.parameters.remove(&index).unwrap_or_default(),
+                        )
+                        .map_err(|error| match (error, self.methods.get(index as usize)) {
+                            (Error::InvalidDex(message), Some(signature)) => {
+                                invalid(format!("{message} in method <{signature}>"))
+                            }
+                            (error, _) => error,
+                        })?;
+                    methods.push(method);
+                }
+            }
+        }
+
+        Ok(Class {
+            class_type,
+            access_flags,
+            super_class,
+            interfaces,
+            source_file,
+            annotations: annotations.class,
+            comments: Vec::new(),
+            smali_comments: Vec::new(),
+            fields,
+            methods,
+        })
+    }
+
+    fn read_method(
+        &self,
+        index: u32,
+        visibility: Vec<AccessFlag>,
+        code_offset: usize,
+        annotations: Vec<Annotation>,
+        parameter_annotations: Vec<Vec<Annotation>>,
+    ) -> Result<Method, Error> {
+        let signature = self.get_method(index)?;
+        let code = if code_offset != 0 {
+            Some(self.read_code(code_offset)?)
+        } else {
+            None
+        };
+
+        let mut parameter_annotations = parameter_annotations.into_iter();
+        let mut parameter_names = code
+            .as_ref()
+            .map(|code| code.parameter_names.clone())
+            .unwrap_or_default()
+            .into_iter();
+        let parameters = signature
+            .call_signature
+            .parameter_types
+            .iter()
+            .map(|parameter_type| MethodParameter {
+                parameter_type: parameter_type.clone(),
+                name: parameter_names.next().flatten(),
+                annotations: parameter_annotations.next().unwrap_or_default(),
+            })
+            .collect();
+
+        let (locals, instructions) = match code {
+            Some(code) => (Some(code.locals), code.instructions),
+            None => (None, Vec::new()),
+        };
+        Ok(Method {
+            name: signature.method_name.clone(),
+            visibility,
+            parameters,
+            return_type: signature.call_signature.return_type.clone(),
+            annotations,
+            comments: Vec::new(),
+            locals,
+            instructions,
+            temporaries: BTreeSet::new(),
+        })
+    }
+
+    /// Resolves a string to a literal in Smali notation.
+    fn get_string_literal(&self, index: u32) -> Result<Literal, Error> {
+        Ok(Literal::escaped_string(self.get_string(index)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    fn put_u32(data: &mut [u8], pos: usize, value: usize) {
+        data[pos..pos + 4].copy_from_slice(&(value as u32).to_le_bytes());
+    }
+
+    /// Points the offset at `pos` to the end of the data.
+    fn put_offset(data: &mut [u8], pos: usize) {
+        put_u32(data, pos, data.len());
+    }
+
+    fn push_uleb128(data: &mut Vec<u8>, mut value: usize) {
+        while value >= 0x80 {
+            data.push((value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        data.push(value as u8);
+    }
+
+    fn align(data: &mut Vec<u8>) {
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    /// Assembles a dex file with a single class, the equivalent of `CLASS_SMALI`.
+    fn build_dex() -> Vec<u8> {
+        let strings = [
+            "I",
+            "II",
+            "La;",
+            "Ljava/lang/Exception;",
+            "Ljava/lang/Object;",
+            "MAX",
+            "a.java",
+            "ok",
+            "run",
+            "value",
+        ];
+        // La;, Ljava/lang/Object;, I, Ljava/lang/Exception;
+        let types = [2, 4, 0, 3];
+        let insns: [u16; 20] = [
+            0x1071, 0x0000, 0x0001, // invoke-static {v1}, method 0
+            0x000a, // move-result v0
+            0x0038, 0x0007, // if-eqz v0, +7
+            0x002b, 0x0008, 0x0000, // packed-switch v0, +8
+            0x001a, 0x0007, // const-string v0, string 7
+            0x000f, // return v0
+            0x000d, // move-exception v0
+            0x0027, // throw v0
+            0x0100, 0x0001, 0x0001, 0x0000, 0x0005, 0x0000, // packed-switch payload
+        ];
+
+        let (string_ids, type_ids, proto_ids) = (HEADER_SIZE, 0x98, 0xa8);
+        let (field_ids, method_ids, class_defs) = (0xb4, 0xbc, 0xc4);
+        let mut dex = vec![0; 0xe4];
+        dex[0..8].copy_from_slice(b"dex\n035\0");
+        put_u32(&mut dex, 0x24, HEADER_SIZE);
+        put_u32(&mut dex, 0x28, ENDIAN_CONSTANT as usize);
+        for (pos, (count, offset)) in [
+            (strings.len(), string_ids),
+            (types.len(), type_ids),
+            (1, proto_ids),
+            (1, field_ids),
+            (1, method_ids),
+            (1, class_defs),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            put_u32(&mut dex, 0x38 + pos * 8, count);
+            put_u32(&mut dex, 0x3c + pos * 8, offset);
+        }
+
+        for (index, string) in strings.iter().enumerate() {
+            put_offset(&mut dex, string_ids + index * 4);
+            push_uleb128(&mut dex, string.len());
+            dex.extend_from_slice(string.as_bytes());
+            dex.push(0);
+        }
+        for (index, string) in types.iter().enumerate() {
+            put_u32(&mut dex, type_ids + index * 4, *string);
+        }
+
+        // Prototype (I)I, field La;->MAX:I and method La;->run(I)I
+        align(&mut dex);
+        put_u32(&mut dex, proto_ids, 1);
+        put_u32(&mut dex, proto_ids + 4, 2);
+        put_offset(&mut dex, proto_ids + 8);
+        dex.extend_from_slice(&[1, 0, 0, 0, 2, 0, 0, 0]);
+        put_u32(&mut dex, field_ids, 2 << 16);
+        put_u32(&mut dex, field_ids + 4, 5);
+        put_u32(&mut dex, method_ids, 0);
+        put_u32(&mut dex, method_ids + 4, 8);
+
+        let code = dex.len();
+        dex.extend_from_slice(&[2, 0, 1, 0, 1, 0, 1, 0]);
+        let debug_info_pos = dex.len();
+        dex.extend_from_slice(&[0; 4]);
+        dex.extend_from_slice(&(insns.len() as u32).to_le_bytes());
+        for unit in insns {
+            dex.extend_from_slice(&unit.to_le_bytes());
+        }
+        // Try block covering four code units, catching Ljava/lang/Exception; at 12
+        dex.extend_from_slice(&[0, 0, 0, 0, 4, 0, 1, 0]);
+        dex.extend_from_slice(&[1, 1, 3, 12]);
+
+        // Line 5 at address 0 and line 7 at address 11, parameter named "value"
+        put_offset(&mut dex, debug_info_pos);
+        dex.extend_from_slice(&[5, 1, 10, 0x0e, 0xb5, 0x00]);
+
+        put_u32(&mut dex, class_defs, 0);
+        put_u32(&mut dex, class_defs + 4, 0x1);
+        put_u32(&mut dex, class_defs + 8, 1);
+        put_u32(&mut dex, class_defs + 12, 0);
+        put_u32(&mut dex, class_defs + 16, 6);
+        put_offset(&mut dex, class_defs + 24);
+        dex.extend_from_slice(&[1, 0, 1, 0, 0, 0x19, 0, 0x09]);
+        push_uleb128(&mut dex, code);
+        put_offset(&mut dex, class_defs + 28);
+        dex.extend_from_slice(&[1, 0x04, 0x10]);
+
+        align(&mut dex);
+        put_offset(&mut dex, 0x34);
+        dex.extend_from_slice(&[0; 4]);
+        let size = dex.len();
+        put_u32(&mut dex, 0x20, size);
+        dex
+    }
+
+    const CLASS_SMALI: &str = r#".class public La;
+.super Ljava/lang/Object;
+.source "a.java"
+
+.field public static final MAX:I = 0x10
+
+.method public static run(I)I
+    .locals 1
+    .param p0, "value"
+    .end param
+    :try_start_0
+    .line 5
+    invoke-static {p0}, La;->run(I)I
+    move-result v0
+    :try_end_0
+    .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :catch_0
+    if-eqz v0, :cond_0
+    packed-switch v0, :pswitch_data_0
+    const-string v0, "ok"
+    :cond_0
+    :pswitch_0
+    .line 7
+    return v0
+    :catch_0
+    move-exception v0
+    throw v0
+    :pswitch_data_0
+    .packed-switch 0x1
+        :pswitch_0
+    .end packed-switch
+.end method
+"#;
+
+    #[test]
+    fn read_class() -> Result<(), ParseErrorDisplayed> {
+        let data = build_dex();
+        let dex = DexFile::read(&data).unwrap();
+        assert_eq!(dex.class_count(), 1);
+        let class = dex.read_class(0).unwrap();
+
+        let mut output = Vec::new();
+        class.write_smali(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), CLASS_SMALI);
+
+        // The Smali parser produces the same class
+        let input = Tokenizer::new(CLASS_SMALI.to_string(), std::path::Path::new("dummy"));
+        let (_, parsed) = Class::read(&input)?;
+        assert_eq!(parsed.fields, class.fields);
+        assert_eq!(parsed.methods, class.methods);
+
+        assert!(DexFile::read(b"not a dex file").is_err());
+        assert!(dex.read_class(1).is_err());
+        assert_eq!(dex.class_type(0).unwrap(), &class.class_type);
+        assert!(dex.class_type(1).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mutf8() {
+        let decode = |data: &[u8]| decode_mutf8(&mut Reader::new(data, 0));
+
+        assert_eq!(decode(b"abc\0").unwrap(), "abc");
+        // Zero characters are encoded as two bytes, so that zero terminates the string
+        assert_eq!(decode(b"a\xc0\x80b\0").unwrap(), "a\0b");
+        assert_eq!(decode(b"\xc3\xa9\xe2\x82\xac\0").unwrap(), "\u{e9}\u{20ac}");
+        // Supplementary characters are encoded as surrogate pairs
+        assert_eq!(decode(b"\xed\xa0\xbd\xed\xb8\x80\0").unwrap(), "\u{1f600}");
+        assert_eq!(decode(b"\xed\xa0\xbd!\0").unwrap(), "\u{fffd}!");
+
+        // Four-byte UTF-8 sequences aren't valid
+        assert!(decode(b"\xf0\x9f\x98\x80\0").is_err());
+        assert!(decode(b"abc").is_err());
+        assert!(decode(b"\xe2\x82").is_err());
+    }
+}
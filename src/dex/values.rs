@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use super::{invalid, DexFile, Reader};
+use crate::annotation::{
+    Annotation, AnnotationParameter, AnnotationParameterValue, AnnotationVisibility,
+};
+use crate::error::Error;
+use crate::literal::Literal;
+use crate::r#type::CallSite;
+
+/// Annotations of a class and its members, the latter keyed by field or method index.
+#[derive(Debug, Default)]
+pub(super) struct AnnotationsDirectory {
+    pub class: Vec<Annotation>,
+    pub fields: HashMap<u32, Vec<Annotation>>,
+    pub methods: HashMap<u32, Vec<Annotation>>,
+    pub parameters: HashMap<u32, Vec<Vec<Annotation>>>,
+}
+
+/// Checks whether a static field value is the default one, these aren't listed by apktool.
+pub(super) fn is_default(value: &Literal) -> bool {
+    match value {
+        Literal::Null | Literal::Bool(false) | Literal::Char(0) => true,
+        Literal::Float(value) => value.to_bits() == 0,
+        Literal::Double(value) => value.to_bits() == 0,
+        literal => literal.get_integer() == Some(0),
+    }
+}
+
+fn sign_extend(value: u64, size: usize) -> i64 {
+    let shift = 64 - 8 * size;
+    ((value << shift) as i64) >> shift
+}
+
+impl DexFile<'_> {
+    fn read_value(&self, reader: &mut Reader<'_>) -> Result<AnnotationParameterValue, Error> {
+        let header = reader.u8()?;
+        let value_type = header & 0x1f;
+        let argument = usize::from(header >> 5);
+
+        // Numbers and indexes are stored with the bytes needed only, the size is the argument
+        let read_number = |reader: &mut Reader<'_>| -> Result<u64, Error> {
+            let bytes = reader.bytes(argument + 1)?;
+            if bytes.len() > 8 {
+                return Err(invalid(format!("value of {} bytes", bytes.len())));
+            }
+            Ok(bytes
+                .iter()
+                .rev()
+                .fold(0, |value, byte| (value << 8) | u64::from(*byte)))
+        };
+        let read_index = |reader: &mut Reader<'_>| -> Result<u32, Error> {
+            u32::try_from(read_number(reader)?).map_err(|_| invalid("index out of range"))
+        };
+
+        let literal = match value_type {
+            0x00 => Literal::Byte(sign_extend(read_number(reader)?, argument + 1) as i8),
+            0x02 => Literal::Short(sign_extend(read_number(reader)?, argument + 1) as i16),
+            0x03 => Literal::Char(read_number(reader)? as u16),
+            0x04 => Literal::Int(sign_extend(read_number(reader)?, argument + 1) as i32),
+            0x06 => Literal::Long(sign_extend(read_number(reader)?, argument + 1)),
+            // Floating point values are stored with the least significant bytes dropped
+            0x10 => Literal::Float(f32::from_bits(
+                (read_number(reader)? << (8 * 3usize.saturating_sub(argument))) as u32,
+            )),
+            0x11 => Literal::Double(f64::from_bits(read_number(reader)? << (8 * (7 - argument)))),
+            0x15 => Literal::MethodType(self.get_proto(read_index(reader)?)?.clone()),
+            0x16 => self.get_method_handle(read_index(reader)?)?,
+            0x17 => self.get_string_literal(read_index(reader)?)?,
+            0x18 => Literal::Class(self.get_type(read_index(reader)?)?.clone()),
+            // Enum values are the only field references the class model can represent
+            0x19 | 0x1b => {
+                let field = self.get_field(read_index(reader)?)?;
+                return Ok(AnnotationParameterValue::Enum(
+                    field.object_type.clone(),
+                    field.field_name.clone(),
+                ));
+            }
+            0x1a => Literal::Method(self.get_method(read_index(reader)?)?.clone()),
+            0x1c => {
+                let mut values = Vec::new();
+                for _ in 0..reader.uleb128()? {
+                    values.push(self.read_value(reader)?);
+                }
+                return Ok(AnnotationParameterValue::Array(values));
+            }
+            0x1d => {
+                return Ok(AnnotationParameterValue::SubAnnotation(
+                    self.read_encoded_annotation(reader, AnnotationVisibility::Build)?,
+                ))
+            }
+            0x1e => Literal::Null,
+            0x1f => Literal::Bool(argument != 0),
+            other => return Err(invalid(format!("unknown value type {other:#x}"))),
+        };
+        Ok(AnnotationParameterValue::Literal(literal))
+    }
+
+    fn read_literal(&self, reader: &mut Reader<'_>) -> Result<Literal, Error> {
+        match self.read_value(reader)? {
+            AnnotationParameterValue::Literal(literal) => Ok(literal),
+            _ => Err(invalid("expected a constant value")),
+        }
+    }
+
+    fn read_encoded_annotation(
+        &self,
+        reader: &mut Reader<'_>,
+        visibility: AnnotationVisibility,
+    ) -> Result<Annotation, Error> {
+        let annotation_type = self.get_type(reader.uleb128()?)?.clone();
+        let mut parameters = Vec::new();
+        for _ in 0..reader.uleb128()? {
+            let name = self.get_string(reader.uleb128()?)?.to_string();
+            let value = self.read_value(reader)?;
+            parameters.push(AnnotationParameter { name, value });
+        }
+        Ok(Annotation {
+            annotation_type,
+            visibility,
+            parameters,
+        })
+    }
+
+    fn read_annotation_set(&self, offset: usize) -> Result<Vec<Annotation>, Error> {
+        let mut result = Vec::new();
+        if offset == 0 {
+            return Ok(result);
+        }
+
+        let mut reader = self.reader(offset);
+        for _ in 0..reader.u32()? {
+            let mut annotation = self.reader(reader.usize()?);
+            let visibility = match annotation.u8()? {
+                0 => AnnotationVisibility::Build,
+                1 => AnnotationVisibility::Runtime,
+                2 => AnnotationVisibility::System,
+                other => return Err(invalid(format!("unknown annotation visibility {other}"))),
+            };
+            result.push(self.read_encoded_annotation(&mut annotation, visibility)?);
+        }
+        Ok(result)
+    }
+
+    pub(super) fn read_annotations_directory(
+        &self,
+        offset: usize,
+    ) -> Result<AnnotationsDirectory, Error> {
+        let mut result = AnnotationsDirectory::default();
+        if offset == 0 {
+            return Ok(result);
+        }
+
+        let mut reader = self.reader(offset);
+        result.class = self.read_annotation_set(reader.usize()?)?;
+        let fields = reader.u32()?;
+        let methods = reader.u32()?;
+        let parameters = reader.u32()?;
+        for _ in 0..fields {
+            let index = reader.u32()?;
+            result
+                .fields
+                .insert(index, self.read_annotation_set(reader.usize()?)?);
+        }
+        for _ in 0..methods {
+            let index = reader.u32()?;
+            result
+                .methods
+                .insert(index, self.read_annotation_set(reader.usize()?)?);
+        }
+        for _ in 0..parameters {
+            let index = reader.u32()?;
+            let mut list = self.reader(reader.usize()?);
+            let mut sets = Vec::new();
+            for _ in 0..list.u32()? {
+                sets.push(self.read_annotation_set(list.usize()?)?);
+            }
+            result.parameters.insert(index, sets);
+        }
+        Ok(result)
+    }
+
+    /// Reads the initial values of static fields, fields beyond the end of the list keep
+    /// their default value.
+    pub(super) fn read_static_values(&self, offset: usize) -> Result<Vec<Literal>, Error> {
+        let mut result = Vec::new();
+        if offset == 0 {
+            return Ok(result);
+        }
+
+        let mut reader = self.reader(offset);
+        for _ in 0..reader.uleb128()? {
+            result.push(self.read_literal(&mut reader)?);
+        }
+        Ok(result)
+    }
+
+    pub(super) fn get_method_handle(&self, index: u32) -> Result<Literal, Error> {
+        let (kind, method) = self
+            .method_handles
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("method handle index {index} out of range")))?;
+        let kind = match kind {
+            0x04 => "invoke-static",
+            0x05 => "invoke-instance",
+            0x06 => "invoke-constructor",
+            0x07 => "invoke-direct",
+            0x08 => "invoke-interface",
+            other => {
+                return Err(invalid(format!(
+                    "unsupported method handle kind {other:#x}"
+                )))
+            }
+        };
+        Ok(Literal::MethodHandle(
+            kind.to_string(),
+            self.get_method(u32::from(*method))?.clone(),
+        ))
+    }
+
+    /// Reads a call site, named by its index like apktool does. The bootstrap method's
+    /// arguments start with the name and type of the method to be linked.
+    pub(super) fn get_call_site(&self, index: u32) -> Result<CallSite, Error> {
+        let offset = self
+            .call_site_offsets
+            .get(index as usize)
+            .ok_or_else(|| invalid(format!("call site index {index} out of range")))?;
+        let mut reader = self.reader(*offset);
+        let mut values = Vec::new();
+        for _ in 0..reader.uleb128()? {
+            values.push(self.read_literal(&mut reader)?);
+        }
+        if values.len() < 3 {
+            return Err(invalid(format!("incomplete call site {index}")));
+        }
+
+        let Literal::MethodHandle(_, method) = values.remove(0) else {
+            return Err(invalid(format!(
+                "call site {index} without bootstrap method"
+            )));
+        };
+        Ok(CallSite {
+            name: format!("call_site_{index}"),
+            params: values,
+            method,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r#type::{FieldSignature, Type};
+
+    fn dex_file(data: &[u8]) -> DexFile<'_> {
+        DexFile {
+            data,
+            strings: vec!["value".to_string(), "a\"b".to_string()],
+            types: vec![
+                Type::Object("a.Kind".to_string()),
+                Type::Object("a.Marker".to_string()),
+            ],
+            protos: Vec::new(),
+            fields: vec![FieldSignature {
+                object_type: Type::Object("a.Kind".to_string()),
+                field_name: "FIRST".to_string(),
+                field_type: Type::Object("a.Kind".to_string()),
+            }],
+            methods: Vec::new(),
+            method_handles: Vec::new(),
+            call_site_offsets: Vec::new(),
+            class_defs_offset: 0,
+            class_defs_count: 0,
+        }
+    }
+
+    fn read_literals(data: &[u8]) -> Result<Vec<Literal>, Error> {
+        let dex = dex_file(data);
+        let mut reader = Reader::new(data, 0);
+        let mut result = Vec::new();
+        while reader.pos < data.len() {
+            result.push(dex.read_literal(&mut reader)?);
+        }
+        Ok(result)
+    }
+
+    #[test]
+    fn read_numbers() {
+        assert_eq!(
+            read_literals(&[
+                0x00, 0xff, // byte -1
+                0x22, 0x00, 0x80, // short -0x8000
+                0x23, 0xff, 0xff, // char 0xffff
+                0x04, 0x80, // int -0x80, sign-extended from one byte
+                0x24, 0x80, 0x00, // int 0x80
+                0x46, 0x01, 0x02, 0x03, // long 0x030201
+                0x30, 0x80, 0x3f, // float 1.0 with the lower bytes dropped
+                0x31, 0xf0, 0x3f, // double 1.0 with the lower bytes dropped
+                0x1e, // null
+                0x3f, // true
+                0x1f, // false
+            ])
+            .unwrap(),
+            [
+                Literal::Byte(-1),
+                Literal::Short(-0x8000),
+                Literal::Char(0xffff),
+                Literal::Int(-0x80),
+                Literal::Int(0x80),
+                Literal::Long(0x0003_0201),
+                Literal::Float(1.0),
+                Literal::Double(1.0),
+                Literal::Null,
+                Literal::Bool(true),
+                Literal::Bool(false),
+            ]
+        );
+
+        // Truncated value
+        assert!(read_literals(&[0x24, 0x80]).is_err());
+        // Unknown value type
+        assert!(read_literals(&[0x05, 0x00]).is_err());
+        // String index out of range
+        assert!(read_literals(&[0x17, 0x02]).is_err());
+    }
+
+    #[test]
+    fn read_annotations() {
+        // Annotation set at 4 with a single annotation item at 12, offset 0 means no set
+        let data = [
+            0x00, 0x00, 0x00, 0x00, // unused
+            0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, // annotation_set_item
+            0x01, // runtime visibility
+            0x01, 0x02, // type a.Marker, two elements
+            0x00, 0x1c, 0x03, // value: array of three values
+            0x17, 0x01, // string "a\"b"
+            0x1b, 0x00, // enum a.Kind.FIRST
+            0x1d, 0x00, 0x00, // sub-annotation a.Kind without elements
+            0x00, 0x3f, // value: true
+        ];
+        let dex = dex_file(&data);
+        assert!(dex.read_annotation_set(0).unwrap().is_empty());
+        assert_eq!(
+            dex.read_annotation_set(4).unwrap(),
+            [Annotation {
+                annotation_type: Type::Object("a.Marker".to_string()),
+                visibility: AnnotationVisibility::Runtime,
+                parameters: vec![
+                    AnnotationParameter {
+                        name: "value".to_string(),
+                        value: AnnotationParameterValue::Array(vec![
+                            AnnotationParameterValue::Literal(Literal::escaped_string("a\"b")),
+                            AnnotationParameterValue::Enum(
+                                Type::Object("a.Kind".to_string()),
+                                "FIRST".to_string()
+                            ),
+                            AnnotationParameterValue::SubAnnotation(Annotation {
+                                annotation_type: Type::Object("a.Kind".to_string()),
+                                visibility: AnnotationVisibility::Build,
+                                parameters: Vec::new(),
+                            }),
+                        ]),
+                    },
+                    AnnotationParameter {
+                        name: "value".to_string(),
+                        value: AnnotationParameterValue::Literal(Literal::Bool(true)),
+                    },
+                ],
+            }]
+        );
+
+        // Unknown visibility
+        let mut broken = data;
+        broken[12] = 3;
+        assert!(dex_file(&broken).read_annotation_set(4).is_err());
+
+        // Annotations only occur in annotation values
+        assert!(dex.read_literal(&mut Reader::new(&data, 22)).is_err());
+    }
+}
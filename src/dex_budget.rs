@@ -0,0 +1,230 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::r#type::{FieldSignature, MethodSignature, Type};
+
+/// Maximal number of method or field references a single dex file can hold.
+pub const DEX_REFERENCE_LIMIT: usize = 65536;
+
+/// Method and field references of a single dex file. Like the dex tables, these include both
+/// the members declared by its classes and those referenced by their code.
+#[derive(Debug, Default)]
+struct DexReferences {
+    classes: usize,
+    methods: HashSet<MethodSignature>,
+    fields: HashSet<FieldSignature>,
+}
+
+/// Number of method and field references attributed to a package.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PackageReferences {
+    pub methods: usize,
+    pub fields: usize,
+}
+
+impl DexReferences {
+    /// Attributes the references to the packages of the types declaring the members, the way
+    /// dex method counting tools do.
+    fn get_packages(&self, depth: usize) -> Vec<(String, PackageReferences)> {
+        let mut packages = BTreeMap::<String, PackageReferences>::new();
+        for method in &self.methods {
+            packages
+                .entry(get_package(&method.object_type, depth))
+                .or_default()
+                .methods += 1;
+        }
+        for field in &self.fields {
+            packages
+                .entry(get_package(&field.object_type, depth))
+                .or_default()
+                .fields += 1;
+        }
+
+        let mut result = packages.into_iter().collect::<Vec<_>>();
+        result.sort_by(|(name1, references1), (name2, references2)| {
+            references2
+                .methods
+                .cmp(&references1.methods)
+                .then(references2.fields.cmp(&references1.fields))
+                .then(name1.cmp(name2))
+        });
+        result
+    }
+}
+
+/// Shortens the package of a type to at most `depth` components, e.g. `com.google` for
+/// `com.google.gson.Gson` with depth 2.
+fn get_package(class_type: &Type, depth: usize) -> String {
+    let Type::Object(name) = class_type else {
+        // Methods like clone() can be called on arrays
+        return "(arrays)".to_string();
+    };
+    let components = name.split('.').collect::<Vec<_>>();
+    let package = &components[..components.len() - 1];
+    if package.is_empty() {
+        "(default package)".to_string()
+    } else {
+        package[..package.len().min(depth)].join(".")
+    }
+}
+
+fn percentage(count: usize) -> f64 {
+    count as f64 * 100.0 / DEX_REFERENCE_LIMIT as f64
+}
+
+/// Report of how much of the 64K reference limit each dex file uses and which packages
+/// contribute most to it.
+#[derive(Debug, Default)]
+pub struct DexBudgetReport {
+    dex_files: BTreeMap<String, DexReferences>,
+}
+
+impl DexBudgetReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the declarations and references of a class contained in the given dex file.
+    pub fn add_class(&mut self, dex_file: &str, class: &Class) {
+        let references = self.dex_files.entry(dex_file.to_string()).or_default();
+        references.classes += 1;
+        for field in &class.fields {
+            references.fields.insert(FieldSignature {
+                object_type: class.class_type.clone(),
+                field_name: field.name.clone(),
+                field_type: field.field_type.clone(),
+            });
+        }
+        for method in &class.methods {
+            references
+                .methods
+                .insert(method.get_signature(&class.class_type));
+            for instruction in &method.instructions {
+                let Instruction::Command { parameters, .. } = instruction else {
+                    continue;
+                };
+                for parameter in parameters {
+                    match parameter {
+                        CommandParameter::Method(method) => {
+                            references.methods.insert(method.clone());
+                        }
+                        CommandParameter::Field(field) => {
+                            references.fields.insert(field.clone());
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lists the method and field reference counts of each dex file, followed by up to
+    /// `limit` packages contributing most references, their names shortened to `depth`
+    /// components.
+    pub fn write(
+        &self,
+        output: &mut dyn Write,
+        depth: usize,
+        limit: usize,
+    ) -> Result<(), std::io::Error> {
+        for (name, references) in &self.dex_files {
+            writeln!(output, "{name} ({} classes)", references.classes)?;
+            writeln!(
+                output,
+                "    method references: {:>6} of {DEX_REFERENCE_LIMIT} ({:.1}%)",
+                references.methods.len(),
+                percentage(references.methods.len())
+            )?;
+            writeln!(
+                output,
+                "    field references:  {:>6} of {DEX_REFERENCE_LIMIT} ({:.1}%)",
+                references.fields.len(),
+                percentage(references.fields.len())
+            )?;
+            writeln!(output, "    top packages:")?;
+            for (package, counts) in references.get_packages(depth).into_iter().take(limit) {
+                writeln!(
+                    output,
+                    "        {:>6} methods {:>6} fields  {package}",
+                    counts.methods, counts.fields
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn dex_budget() -> Result<(), ParseErrorDisplayed> {
+        let first = read_class(
+            r#"
+                .class public Lcom/example/app/Main;
+                .super Ljava/lang/Object;
+
+                .field private count:I
+
+                .method public run()V
+                    .locals 1
+                    iget v0, p0, Lcom/example/app/Main;->count:I
+                    invoke-static {v0}, Lcom/google/gson/internal/Util;->check(I)V
+                    invoke-static {v0}, Lcom/google/gson/internal/Util;->check(I)V
+                    invoke-virtual {p0}, Ljava/lang/Object;->hashCode()I
+                    return-void
+                .end method
+            "#,
+        )?;
+        let second = read_class(
+            r#"
+                .class public Lcom/google/gson/Gson;
+                .super Ljava/lang/Object;
+
+                .method public toJson()V
+                    .locals 0
+                    sget-object v0, Lcom/google/gson/Gson;->INSTANCE:Lcom/google/gson/Gson;
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut report = DexBudgetReport::new();
+        report.add_class("classes.dex", &first);
+        report.add_class("classes2.dex", &second);
+        report.add_class("classes2.dex", &first);
+
+        let mut output = Vec::new();
+        report.write(&mut output, 2, 10).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"classes.dex (1 classes)
+    method references:      3 of 65536 (0.0%)
+    field references:       1 of 65536 (0.0%)
+    top packages:
+             1 methods      1 fields  com.example
+             1 methods      0 fields  com.google
+             1 methods      0 fields  java.lang
+classes2.dex (2 classes)
+    method references:      4 of 65536 (0.0%)
+    field references:       2 of 65536 (0.0%)
+    top packages:
+             2 methods      1 fields  com.google
+             1 methods      1 fields  com.example
+             1 methods      0 fields  java.lang
+"#
+        );
+
+        assert_eq!(
+            get_package(&Type::Object("Main".to_string()), 2),
+            "(default package)"
+        );
+
+        Ok(())
+    }
+}
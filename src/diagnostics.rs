@@ -0,0 +1,104 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+thread_local! {
+    static WARNING_COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+static SHOW_ALL: AtomicBool = AtomicBool::new(false);
+static CATEGORIES: Mutex<BTreeMap<&'static str, Category>> = Mutex::new(BTreeMap::new());
+
+/// Warnings reported with the same format string, along with the messages printed already.
+#[derive(Debug, Default)]
+struct Category {
+    count: usize,
+    messages: HashSet<String>,
+}
+
+/// Makes repeated warnings print every time rather than only once.
+pub fn show_all_warnings(show: bool) {
+    SHOW_ALL.store(show, Ordering::Relaxed);
+}
+
+/// Prints out a warning unless the same message has been printed before, and records it in the
+/// per-thread warning count. The format string identifies the category of the warning.
+pub fn report_warning(category: &'static str, message: std::fmt::Arguments<'_>) {
+    let message = message.to_string();
+    let is_new = {
+        let mut categories = CATEGORIES.lock().unwrap_or_else(|error| error.into_inner());
+        let category = categories.entry(category).or_default();
+        category.count += 1;
+        category.messages.insert(message.clone())
+    };
+    if is_new || SHOW_ALL.load(Ordering::Relaxed) {
+        eprintln!("Warning: {message}");
+    }
+    WARNING_COUNT.with(|count| count.set(count.get() + 1));
+}
+
+/// Returns the number of warnings reported on the current thread and resets the count.
+pub fn take_warning_count() -> usize {
+    WARNING_COUNT.with(|count| count.replace(0))
+}
+
+/// Lists the number of warnings reported for each category, most frequent first.
+pub fn get_warning_counts() -> Vec<(&'static str, usize)> {
+    let categories = CATEGORIES.lock().unwrap_or_else(|error| error.into_inner());
+    let mut result = categories
+        .iter()
+        .map(|(category, data)| (*category, data.count))
+        .collect::<Vec<_>>();
+    result.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    result
+}
+
+/// Prints the warning counts by category if any repeated warnings weren't printed.
+pub fn print_warning_summary() {
+    let counts = get_warning_counts();
+    let total = counts.iter().map(|(_, count)| count).sum::<usize>();
+    let printed = CATEGORIES
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .values()
+        .map(|category| category.messages.len())
+        .sum::<usize>();
+    if SHOW_ALL.load(Ordering::Relaxed) || total == printed {
+        return;
+    }
+
+    eprintln!(
+        "{total} warnings, {} repeated ones not shown (use --show-all-warnings to see all):",
+        total - printed
+    );
+    for (category, count) in counts {
+        eprintln!("{count:>8}  {category}");
+    }
+}
+
+macro_rules! warning {
+    ($format:literal $($arg:tt)*) => {
+        $crate::diagnostics::report_warning($format, format_args!($format $($arg)*))
+    };
+}
+
+pub(crate) use warning;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deduplicate_warnings() {
+        for value in [1, 2, 1, 1] {
+            warning!("Test warning {value}");
+        }
+        assert_eq!(take_warning_count(), 4);
+
+        let counts = get_warning_counts();
+        assert!(counts.contains(&("Test warning {value}", 4)));
+        let categories = CATEGORIES.lock().unwrap();
+        assert_eq!(categories["Test warning {value}"].messages.len(), 2);
+    }
+}
@@ -0,0 +1,327 @@
+/// Document model of the output: text along with the places where it may be broken into
+/// several lines. The renderer decides on line breaks depending on the available width, so that
+/// output formats only need to describe their structure.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Doc {
+    /// Text without line breaks
+    Text(String),
+    /// Unconditional line break
+    Line,
+    /// Line break if the enclosing group doesn't fit on the line, the given text otherwise
+    Break(&'static str),
+    /// Increases the indentation of the lines started within by the given number of columns
+    Nest(usize, Box<Doc>),
+    /// Content laid out on a single line if it fits, otherwise all its breaks are taken
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    /// Creates a text node, line breaks within the text become unconditional line breaks.
+    pub fn text(text: impl Into<String>) -> Self {
+        let text = text.into();
+        if !text.contains('\n') {
+            return Self::Text(text);
+        }
+
+        let mut parts = Vec::new();
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                parts.push(Self::Line);
+            }
+            if !line.is_empty() {
+                parts.push(Self::Text(line.to_string()));
+            }
+        }
+        Self::Concat(parts)
+    }
+
+    pub fn nest(indent: usize, doc: Self) -> Self {
+        Self::Nest(indent, Box::new(doc))
+    }
+
+    pub fn group(doc: Self) -> Self {
+        Self::Group(Box::new(doc))
+    }
+
+    /// Puts a separator between the documents.
+    pub fn join(docs: impl IntoIterator<Item = Self>, separator: &Self) -> Self {
+        let mut result = Vec::new();
+        for doc in docs {
+            if !result.is_empty() {
+                result.push(separator.clone());
+            }
+            result.push(doc);
+        }
+        Self::Concat(result)
+    }
+
+    /// Separates list entries by commas, putting each entry on a continuation line indented by
+    /// `indent` columns if the list doesn't fit on the line.
+    pub fn list(entries: Vec<Self>, indent: usize) -> Self {
+        if entries.is_empty() {
+            return Self::Concat(Vec::new());
+        }
+        let entries = Self::join(
+            entries,
+            &Self::Concat(vec![Self::text(","), Self::Break(" ")]),
+        );
+        Self::group(Self::nest(
+            indent,
+            Self::Concat(vec![Self::Break(""), entries]),
+        ))
+    }
+
+    /// Removes all optional line breaks, the document will always be laid out on one line.
+    pub fn flatten(self) -> Self {
+        match self {
+            Self::Break(text) => Self::text(text),
+            Self::Nest(indent, doc) => Self::nest(indent, doc.flatten()),
+            Self::Group(doc) => doc.flatten(),
+            Self::Concat(docs) => Self::Concat(docs.into_iter().map(Self::flatten).collect()),
+            doc => doc,
+        }
+    }
+
+    /// Transforms all text within the document, e.g. to change the notation of numbers.
+    pub fn map_text(self, transform: &dyn Fn(&str) -> String) -> Self {
+        match self {
+            Self::Text(text) => Self::Text(transform(&text)),
+            Self::Nest(indent, doc) => Self::nest(indent, doc.map_text(transform)),
+            Self::Group(doc) => Self::group(doc.map_text(transform)),
+            Self::Concat(docs) => Self::Concat(
+                docs.into_iter()
+                    .map(|doc| doc.map_text(transform))
+                    .collect(),
+            ),
+            doc => doc,
+        }
+    }
+
+    /// Checks whether the remaining content up to the next line break fits into the given
+    /// width, with the first entry laid out flat.
+    fn fits(mut width: isize, first: &Self, rest: &[(usize, Mode, &Self)]) -> bool {
+        let mut stack = vec![(Mode::Flat, first)];
+        let mut rest = rest.iter().rev();
+        loop {
+            let (mode, doc) = match stack.pop() {
+                Some(entry) => entry,
+                None => match rest.next() {
+                    Some((_, mode, doc)) => (*mode, *doc),
+                    None => return true,
+                },
+            };
+            match doc {
+                Self::Text(text) => width -= text.chars().count() as isize,
+                Self::Line => return true,
+                Self::Break(_) if mode == Mode::Break => return true,
+                Self::Break(text) => width -= text.chars().count() as isize,
+                Self::Nest(_, doc) => stack.push((mode, doc)),
+                Self::Group(doc) => stack.push((mode, doc)),
+                Self::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            }
+            if width < 0 {
+                return false;
+            }
+        }
+    }
+
+    /// Renders the document starting with the given indentation. Without a maximal width all
+    /// groups are laid out on a single line.
+    pub fn render(&self, indent: usize, max_width: Option<usize>) -> String {
+        let mut result = " ".repeat(indent);
+        let mut column = indent;
+        let mut stack = vec![(indent, Mode::Break, self)];
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Self::Text(text) => {
+                    result.push_str(text);
+                    column += text.chars().count();
+                }
+                Self::Break(text) if mode == Mode::Flat => {
+                    result.push_str(text);
+                    column += text.chars().count();
+                }
+                Self::Line | Self::Break(_) => {
+                    result.push('\n');
+                    result.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Self::Nest(nested, doc) => stack.push((indent + nested, mode, doc)),
+                Self::Group(doc) => {
+                    let mode = match max_width {
+                        Some(max_width)
+                            if mode == Mode::Break
+                                && !Self::fits(
+                                    max_width as isize - column as isize,
+                                    doc,
+                                    &stack,
+                                ) =>
+                        {
+                            Mode::Break
+                        }
+                        _ => Mode::Flat,
+                    };
+                    stack.push((indent, mode, doc));
+                }
+                Self::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: Vec<Doc>) -> Doc {
+        Doc::Concat(vec![
+            Doc::text(format!("{name}(")),
+            Doc::list(arguments, 8),
+            Doc::text(")"),
+        ])
+    }
+
+    #[test]
+    fn render() {
+        let doc = Doc::Concat(vec![
+            Doc::text("v0 = "),
+            call(
+                "invoke-static <java.lang.String a.join(java.lang.String, int, int)>",
+                vec![Doc::text("\"a, (b)\""), Doc::text("p0"), Doc::text("p1")],
+            ),
+            Doc::text(";"),
+        ]);
+        assert_eq!(
+            doc.render(8, Some(120)),
+            r#"        v0 = invoke-static <java.lang.String a.join(java.lang.String, int, int)>("a, (b)", p0, p1);"#
+        );
+        assert_eq!(
+            doc.render(8, None),
+            r#"        v0 = invoke-static <java.lang.String a.join(java.lang.String, int, int)>("a, (b)", p0, p1);"#
+        );
+        assert_eq!(
+            doc.render(8, Some(60)),
+            r#"        v0 = invoke-static <java.lang.String a.join(java.lang.String, int, int)>(
+                "a, (b)",
+                p0,
+                p1);"#
+        );
+
+        let doc = Doc::Concat(vec![
+            Doc::text("return "),
+            call(
+                "invoke-virtual invoke-static <a a.get()>().<int a.add(int, int)>",
+                vec![
+                    call(
+                        "invoke-static <int a.first(int, int)>",
+                        vec![Doc::text("p0"), Doc::text("p1")],
+                    ),
+                    Doc::text("0x1"),
+                ],
+            ),
+            Doc::text(";"),
+        ]);
+        assert_eq!(
+            doc.render(8, Some(60)),
+            "        return invoke-virtual invoke-static <a a.get()>().<int a.add(int, int)>(
+                invoke-static <int a.first(int, int)>(
+                        p0,
+                        p1),
+                0x1);"
+        );
+        assert_eq!(
+            doc.clone().flatten().render(8, Some(60)),
+            doc.render(8, None)
+        );
+
+        let doc = Doc::text("if v0 <= v1 goto label1; // unrelated (comment)");
+        assert_eq!(
+            doc.render(8, Some(20)),
+            "        if v0 <= v1 goto label1; // unrelated (comment)"
+        );
+
+        let doc = Doc::nest(
+            4,
+            Doc::text("switch(v0)\n{\n    case 0x1: goto a;\n}")
+                .map_text(&|text| text.replace("0x1", "1")),
+        );
+        assert_eq!(
+            doc.render(0, None),
+            "switch(v0)\n    {\n        case 1: goto a;\n    }"
+        );
+    }
+
+    #[test]
+    fn groups() {
+        let group = |docs: Vec<Doc>| Doc::group(Doc::Concat(docs));
+
+        // A group breaks only if it doesn't fit, text following it up to the next line break counts
+        let doc = Doc::Concat(vec![
+            group(vec![Doc::text("ab"), Doc::Break(" "), Doc::text("cd")]),
+            Doc::text(";"),
+        ]);
+        assert_eq!(doc.render(0, Some(6)), "ab cd;");
+        assert_eq!(doc.render(0, Some(5)), "ab\ncd;");
+        assert_eq!(doc.render(2, Some(8)), "  ab cd;");
+        assert_eq!(doc.render(2, Some(7)), "  ab\n  cd;");
+
+        let doc = Doc::Concat(vec![
+            group(vec![Doc::text("ab"), Doc::Break(" ")]),
+            Doc::Line,
+            Doc::text("longer than the line"),
+        ]);
+        assert_eq!(doc.render(0, Some(3)), "ab \nlonger than the line");
+
+        // Breaks outside of groups are always taken
+        let doc = Doc::Concat(vec![Doc::text("a"), Doc::Break(" "), Doc::text("b")]);
+        assert_eq!(doc.render(0, None), "a\nb");
+
+        // Inner groups are laid out flat if they fit after the outer group broke
+        let doc = group(vec![
+            Doc::text("f("),
+            Doc::nest(
+                4,
+                Doc::Concat(vec![
+                    Doc::Break(""),
+                    group(vec![Doc::text("x,"), Doc::Break(" "), Doc::text("y")]),
+                    Doc::text(","),
+                    Doc::Break(" "),
+                    Doc::text("z"),
+                ]),
+            ),
+            Doc::text(")"),
+        ]);
+        assert_eq!(doc.render(0, None), "f(x, y, z)");
+        assert_eq!(doc.render(0, Some(10)), "f(x, y, z)");
+        assert_eq!(doc.render(0, Some(9)), "f(\n    x, y,\n    z)");
+        assert_eq!(doc.render(0, Some(6)), "f(\n    x,\n    y,\n    z)");
+
+        // Nesting accumulates and applies to the lines started within
+        let doc = Doc::Concat(vec![
+            Doc::text("a"),
+            Doc::nest(
+                2,
+                Doc::Concat(vec![
+                    Doc::Line,
+                    Doc::text("b"),
+                    Doc::nest(2, Doc::Concat(vec![Doc::Line, Doc::text("c")])),
+                    Doc::Line,
+                    Doc::text("d"),
+                ]),
+            ),
+            Doc::Line,
+            Doc::text("e"),
+        ]);
+        assert_eq!(doc.render(1, None), " a\n   b\n     c\n   d\n e");
+    }
+}
@@ -0,0 +1,265 @@
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::ResultType;
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+
+/// Class loaders loading dex code, the path or buffer is passed as the first constructor
+/// parameter.
+const DEX_LOADERS: [&str; 5] = [
+    "dalvik.system.DexClassLoader",
+    "dalvik.system.PathClassLoader",
+    "dalvik.system.InMemoryDexClassLoader",
+    "dalvik.system.DelegateLastClassLoader",
+    "dalvik.system.BaseDexClassLoader",
+];
+
+/// Calls opening an asset, the asset name is passed first.
+const ASSET_CALLS: [(&str, &str); 2] = [
+    ("android.content.res.AssetManager", "open"),
+    ("android.content.res.AssetManager", "openFd"),
+];
+
+/// Calls loading a class by name, the class name is passed first.
+const CLASS_LOADING_CALLS: [(&str, &str); 3] = [
+    ("java.lang.ClassLoader", "loadClass"),
+    ("dalvik.system.BaseDexClassLoader", "loadClass"),
+    ("java.lang.Class", "forName"),
+];
+
+/// Creation of a class loader for dex code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DexLoader {
+    pub loader: Type,
+    /// Dex path if it is a constant, this can be a list separated by `:`
+    pub path: Option<String>,
+    /// Assets opened by the same method, likely the source of the dex code
+    pub assets: Vec<String>,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+/// A class loaded by name, its code might not be part of the app.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedClass {
+    pub name: String,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+fn get_string(value: Option<&ResultType>) -> Option<String> {
+    match value {
+        Some(ResultType::Literal(Literal::String(value))) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+impl Method {
+    /// Finds dex class loaders created by the method and classes it loads by name.
+    fn find_dynamic_code(&self, class_type: &Type) -> (Vec<DexLoader>, Vec<LoadedClass>) {
+        let calls = self.get_calls(class_type);
+        let assets = calls
+            .iter()
+            .filter(|call| {
+                ASSET_CALLS.iter().any(|(class, method)| {
+                    call.method.object_type.get_name() == *class
+                        && call.method.method_name == *method
+                })
+            })
+            .filter_map(|call| get_string(call.get_parameter(0)))
+            .collect::<Vec<_>>();
+
+        let mut loaders = Vec::new();
+        let mut classes = Vec::new();
+        for call in &calls {
+            let class_name = call.method.object_type.get_name();
+            if call.method.method_name == "<init>" && DEX_LOADERS.contains(&class_name.as_ref()) {
+                loaders.push(DexLoader {
+                    loader: call.method.object_type.clone(),
+                    path: get_string(call.get_parameter(0)),
+                    assets: assets.clone(),
+                    location: self.get_signature(class_type),
+                    line: call.line,
+                });
+            } else if CLASS_LOADING_CALLS
+                .iter()
+                .any(|(class, method)| class_name == *class && call.method.method_name == *method)
+            {
+                if let Some(name) = get_string(call.get_parameter(0)) {
+                    classes.push(LoadedClass {
+                        name,
+                        location: self.get_signature(class_type),
+                        line: call.line,
+                    });
+                }
+            }
+        }
+        (loaders, classes)
+    }
+}
+
+/// Escapes a node label for the DOT format.
+fn dot_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Places where code is loaded dynamically, meaning that static analysis of the app is
+/// incomplete: dex class loaders with their paths and classes loaded by name.
+#[derive(Debug, Default)]
+pub struct DynamicCodeReport {
+    loaders: Vec<DexLoader>,
+    classes: Vec<LoadedClass>,
+}
+
+impl DynamicCodeReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            let (mut loaders, mut classes) = method.find_dynamic_code(&class.class_type);
+            self.loaders.append(&mut loaders);
+            self.classes.append(&mut classes);
+        }
+    }
+
+    pub fn get_loaders(&self) -> &[DexLoader] {
+        &self.loaders
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        if !self.loaders.is_empty() {
+            writeln!(output, "Dex class loaders:")?;
+        }
+        for loader in &self.loaders {
+            write!(output, "    <{}>", loader.location)?;
+            if let Some(line) = loader.line {
+                write!(output, ", line {line}")?;
+            }
+            write!(output, ": {}", loader.loader)?;
+            match &loader.path {
+                Some(path) => writeln!(output, " loading \"{path}\"")?,
+                None => writeln!(output, " loading unknown path")?,
+            }
+            if !loader.assets.is_empty() {
+                writeln!(
+                    output,
+                    "        assets opened: {}",
+                    loader.assets.join(", ")
+                )?;
+            }
+        }
+
+        if !self.classes.is_empty() {
+            writeln!(output, "Classes loaded by name:")?;
+        }
+        for class in &self.classes {
+            write!(output, "    <{}>", class.location)?;
+            if let Some(line) = class.line {
+                write!(output, ", line {line}")?;
+            }
+            writeln!(output, ": {}", class.name)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the loader graph in DOT format: methods creating class loaders are connected to
+    /// the dynamically loaded code by dashed edges marking the boundary of static analysis.
+    pub fn write_dot(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        writeln!(output, "digraph loaders {{")?;
+        for loader in &self.loaders {
+            let source = match (&loader.path, loader.assets.as_slice()) {
+                (Some(path), _) => path.clone(),
+                (None, []) => "unknown".to_string(),
+                (None, assets) => format!("assets: {}", assets.join(", ")),
+            };
+            writeln!(
+                output,
+                "    \"{}\" -> \"{}\" [style=dashed, label=\"{}\"];",
+                dot_label(&loader.location.to_string()),
+                dot_label(&format!("dynamic code: {source}")),
+                dot_label(&loader.loader.get_name()),
+            )?;
+        }
+        for class in &self.classes {
+            writeln!(
+                output,
+                "    \"{}\" -> \"{}\" [style=dashed, label=\"loadClass\"];",
+                dot_label(&class.location.to_string()),
+                dot_label(&class.name),
+            )?;
+        }
+        writeln!(output, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn dynamic_code_report() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public load(Landroid/content/Context;)V
+                    .locals 5
+                    .line 7
+                    invoke-virtual {p1}, Landroid/content/Context;->getAssets()Landroid/content/res/AssetManager;
+                    move-result-object v0
+                    const-string v1, "plugin.dex"
+                    invoke-virtual {v0, v1}, Landroid/content/res/AssetManager;->open(Ljava/lang/String;)Ljava/io/InputStream;
+                    .line 8
+                    new-instance v0, Ldalvik/system/DexClassLoader;
+                    const-string v1, "/data/data/com.example/files/plugin.dex"
+                    const/4 v2, 0x0
+                    invoke-direct {v0, v1, v2, v2, v2}, Ldalvik/system/DexClassLoader;-><init>(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/ClassLoader;)V
+                    .line 9
+                    const-string v1, "com.example.Plugin"
+                    invoke-virtual {v0, v1}, Ljava/lang/ClassLoader;->loadClass(Ljava/lang/String;)Ljava/lang/Class;
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let mut report = DynamicCodeReport::new();
+        report.add_class(&class);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"Dex class loaders:
+    <void a.load(android.content.Context)>, line 8: dalvik.system.DexClassLoader loading "/data/data/com.example/files/plugin.dex"
+        assets opened: plugin.dex
+Classes loaded by name:
+    <void a.load(android.content.Context)>, line 9: com.example.Plugin
+"#
+        );
+
+        let mut output = Vec::new();
+        report.write_dot(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"digraph loaders {
+    "void a.load(android.content.Context)" -> "dynamic code: /data/data/com.example/files/plugin.dex" [style=dashed, label="dalvik.system.DexClassLoader"];
+    "void a.load(android.content.Context)" -> "com.example.Plugin" [style=dashed, label="loadClass"];
+}
+"#
+        );
+
+        Ok(())
+    }
+}
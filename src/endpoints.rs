@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::annotation::AnnotationParameterValue;
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, Register};
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+
+/// Framework and library methods receiving URLs: class, method name (all overloads), client.
+const URL_SINKS: [(&str, &str, &str); 17] = [
+    ("java.net.URL", "<init>", "HttpURLConnection"),
+    ("java.net.URI", "<init>", "java.net.URI"),
+    ("java.net.URI", "create", "java.net.URI"),
+    ("android.net.Uri", "parse", "android.net.Uri"),
+    ("okhttp3.Request$Builder", "url", "OkHttp"),
+    ("okhttp3.HttpUrl", "get", "OkHttp"),
+    ("okhttp3.HttpUrl", "parse", "OkHttp"),
+    ("okhttp3.HttpUrl$Companion", "get", "OkHttp"),
+    ("okhttp3.HttpUrl$Companion", "parse", "OkHttp"),
+    ("com.squareup.okhttp.Request$Builder", "url", "OkHttp"),
+    ("retrofit2.Retrofit$Builder", "baseUrl", "Retrofit"),
+    ("android.webkit.WebView", "loadUrl", "WebView"),
+    ("android.webkit.WebView", "postUrl", "WebView"),
+    (
+        "org.apache.http.client.methods.HttpGet",
+        "<init>",
+        "Apache HttpClient",
+    ),
+    (
+        "org.apache.http.client.methods.HttpPost",
+        "<init>",
+        "Apache HttpClient",
+    ),
+    (
+        "com.android.volley.toolbox.StringRequest",
+        "<init>",
+        "Volley",
+    ),
+    (
+        "com.android.volley.toolbox.JsonObjectRequest",
+        "<init>",
+        "Volley",
+    ),
+];
+
+/// Methods choosing the HTTP method of a request: class, method name, HTTP method. If the HTTP
+/// method is `None`, it is passed as the last parameter.
+const HTTP_METHOD_CALLS: [(&str, &str, Option<&str>); 9] = [
+    ("java.net.HttpURLConnection", "setRequestMethod", None),
+    ("javax.net.ssl.HttpsURLConnection", "setRequestMethod", None),
+    ("okhttp3.Request$Builder", "method", None),
+    ("okhttp3.Request$Builder", "get", Some("GET")),
+    ("okhttp3.Request$Builder", "head", Some("HEAD")),
+    ("okhttp3.Request$Builder", "post", Some("POST")),
+    ("okhttp3.Request$Builder", "put", Some("PUT")),
+    ("okhttp3.Request$Builder", "patch", Some("PATCH")),
+    ("okhttp3.Request$Builder", "delete", Some("DELETE")),
+];
+
+/// Package of the Retrofit annotations declaring HTTP methods, e.g. `@GET("users")`.
+const RETROFIT_ANNOTATION_PREFIX: &str = "retrofit2.http.";
+const RETROFIT_HTTP_METHODS: [&str; 7] =
+    ["DELETE", "GET", "HEAD", "OPTIONS", "PATCH", "POST", "PUT"];
+
+/// Checks whether a string starts with a URL scheme followed by `://`.
+fn is_url(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    !rest.is_empty()
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn find_api<'a, T>(table: &'a [(&str, &str, T)], api: &MethodSignature) -> Option<&'a T> {
+    let Type::Object(class_name) = &api.object_type else {
+        return None;
+    };
+    table
+        .iter()
+        .find(|(class, name, _)| class == class_name && *name == api.method_name)
+        .map(|(_, _, value)| value)
+}
+
+/// A URL constant along with the client it is passed to and the code location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Endpoint {
+    pub url: String,
+    pub client: Option<&'static str>,
+    pub http_method: Option<String>,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+impl Method {
+    /// Finds URL constants in the method and the client calls receiving them. Values are only
+    /// tracked within straight code, labels reset the state. An HTTP method chosen anywhere in
+    /// the method is assumed to apply to all URLs passed to a client.
+    fn find_endpoints(&self, class_type: &Type) -> Vec<Endpoint> {
+        let location = self.get_signature(class_type);
+        let mut endpoints: Vec<Endpoint> = Vec::new();
+        let mut strings: HashMap<Register, Arc<str>> = HashMap::new();
+        let mut urls: HashMap<Register, usize> = HashMap::new();
+        let mut http_method = None;
+        let mut line = None;
+        for instruction in &self.instructions {
+            let (command, parameters) = match instruction {
+                Instruction::LineNumber(from, _) => {
+                    line = Some(*from);
+                    continue;
+                }
+                Instruction::Label(_) | Instruction::Raw(_) => {
+                    strings.clear();
+                    urls.clear();
+                    continue;
+                }
+                Instruction::Command {
+                    command,
+                    parameters,
+                } => (command, parameters),
+                _ => continue,
+            };
+
+            let used = instruction.get_used_registers();
+            if let (true, Some(CommandParameter::Method(api))) =
+                (command.starts_with("invoke-"), parameters.get(2))
+            {
+                if let Some(client) = find_api(&URL_SINKS, api) {
+                    for register in &used {
+                        if let Some(&index) = urls.get(register) {
+                            endpoints[index].client.get_or_insert(client);
+                        }
+                    }
+                }
+                match find_api(&HTTP_METHOD_CALLS, api) {
+                    Some(Some(method)) => http_method = Some(method.to_string()),
+                    Some(None) => {
+                        if let Some(method) = used.last().and_then(|r| strings.get(r)) {
+                            http_method = Some(method.to_string());
+                        }
+                    }
+                    None => (),
+                }
+            }
+
+            let Some(result) = instruction.get_result_register() else {
+                continue;
+            };
+            let family = instruction.get_command_family().unwrap_or_default();
+            let string = match (family, parameters.get(1)) {
+                ("const-string", Some(CommandParameter::Literal(Literal::String(value)))) => {
+                    Some(value.clone())
+                }
+                ("move-object", _) => used.first().and_then(|r| strings.get(r)).cloned(),
+                _ => None,
+            };
+            let url = match family {
+                "move-object" => used.first().and_then(|r| urls.get(r)).copied(),
+                _ => None,
+            };
+            strings.remove(result);
+            urls.remove(result);
+            if let Some(string) = string {
+                if let Some(url) = url {
+                    urls.insert(result.clone(), url);
+                } else if is_url(&string) {
+                    endpoints.push(Endpoint {
+                        url: string.to_string(),
+                        client: None,
+                        http_method: None,
+                        location: location.clone(),
+                        line,
+                    });
+                    urls.insert(result.clone(), endpoints.len() - 1);
+                }
+                strings.insert(result.clone(), string);
+            }
+        }
+
+        if http_method.is_some() {
+            for endpoint in &mut endpoints {
+                if endpoint.client.is_some() {
+                    endpoint.http_method.clone_from(&http_method);
+                }
+            }
+        }
+        endpoints
+    }
+
+    /// Finds the endpoint declared by Retrofit annotations such as `@GET("users/{id}")`.
+    fn find_retrofit_endpoint(&self, class_type: &Type) -> Option<Endpoint> {
+        self.annotations.iter().find_map(|annotation| {
+            let name = annotation.annotation_type.get_name();
+            let name = name.strip_prefix(RETROFIT_ANNOTATION_PREFIX)?;
+            let http_method = if name == "HTTP" {
+                match annotation.get_parameter("method") {
+                    Some(AnnotationParameterValue::Literal(Literal::String(method))) => {
+                        method.to_string()
+                    }
+                    _ => return None,
+                }
+            } else if RETROFIT_HTTP_METHODS.contains(&name) {
+                name.to_string()
+            } else {
+                return None;
+            };
+            let url = match annotation.get_parameter(if name == "HTTP" { "path" } else { "value" })
+            {
+                Some(AnnotationParameterValue::Literal(Literal::String(url))) => url.to_string(),
+                _ => String::new(),
+            };
+            Some(Endpoint {
+                url,
+                client: Some("Retrofit"),
+                http_method: Some(http_method),
+                location: self.get_signature(class_type),
+                line: None,
+            })
+        })
+    }
+}
+
+/// URL constants and Retrofit endpoints found in the program.
+#[derive(Debug, Default)]
+pub struct EndpointReport {
+    endpoints: Vec<Endpoint>,
+}
+
+impl EndpointReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            self.endpoints
+                .extend(method.find_retrofit_endpoint(&class.class_type));
+            self.endpoints
+                .append(&mut method.find_endpoints(&class.class_type));
+        }
+    }
+
+    pub fn get_endpoints(&self) -> &[Endpoint] {
+        &self.endpoints
+    }
+
+    /// Lists the endpoints grouped by URL.
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut endpoints = self.endpoints.iter().collect::<Vec<_>>();
+        endpoints.sort_by(|a, b| {
+            a.url
+                .cmp(&b.url)
+                .then_with(|| a.location.to_string().cmp(&b.location.to_string()))
+                .then(a.line.cmp(&b.line))
+        });
+
+        let mut previous = None;
+        for endpoint in endpoints {
+            if previous != Some(&endpoint.url) {
+                writeln!(output, "\"{}\"", endpoint.url)?;
+                previous = Some(&endpoint.url);
+            }
+            write!(
+                output,
+                "    {}",
+                endpoint.client.unwrap_or("no known client")
+            )?;
+            if let Some(http_method) = &endpoint.http_method {
+                write!(output, " {http_method}")?;
+            }
+            write!(output, " in <{}>", endpoint.location)?;
+            if let Some(line) = endpoint.line {
+                write!(output, ", line {line}")?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn url_shape() {
+        assert!(is_url("https://example.com/api"));
+        assert!(is_url("wss://example.com"));
+        assert!(is_url("my-app+v1://open"));
+        assert!(!is_url("https://"));
+        assert!(!is_url("://example.com"));
+        assert!(!is_url("see https://example.com"));
+        assert!(!is_url("example.com"));
+    }
+
+    #[test]
+    fn endpoint_report() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static fetch(Lokhttp3/Request$Builder;)V
+                    .locals 2
+                    .line 10
+                    const-string v0, "https://api.example.com/users"
+                    invoke-virtual {p0, v0}, Lokhttp3/Request$Builder;->url(Ljava/lang/String;)Lokhttp3/Request$Builder;
+                    const/4 v1, 0x0
+                    invoke-virtual {p0, v1}, Lokhttp3/Request$Builder;->post(Lokhttp3/RequestBody;)Lokhttp3/Request$Builder;
+                    .line 12
+                    const-string v0, "https://unused.example.com"
+                    return-void
+                .end method
+
+                .method static open()V
+                    .locals 3
+                    const-string v0, "http://legacy.example.com/"
+                    move-object v2, v0
+                    new-instance v1, Ljava/net/URL;
+                    invoke-direct {v1, v2}, Ljava/net/URL;-><init>(Ljava/lang/String;)V
+                    invoke-virtual {v1}, Ljava/net/URL;->openConnection()Ljava/net/URLConnection;
+                    move-result-object v1
+                    check-cast v1, Ljava/net/HttpURLConnection;
+                    const-string v0, "PUT"
+                    invoke-virtual {v1, v0}, Ljava/net/HttpURLConnection;->setRequestMethod(Ljava/lang/String;)V
+                    return-void
+                .end method
+
+                .method public abstract getUser(I)Ljava/lang/Object;
+                    .annotation runtime Lretrofit2/http/GET;
+                        value = "users/{id}"
+                    .end annotation
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let mut report = EndpointReport::new();
+        report.add_class(&class);
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#""http://legacy.example.com/"
+    HttpURLConnection PUT in <void a.open()>
+"https://api.example.com/users"
+    OkHttp POST in <void a.fetch(okhttp3.Request$Builder)>, line 10
+"https://unused.example.com"
+    no known client in <void a.fetch(okhttp3.Request$Builder)>, line 12
+"users/{id}"
+    Retrofit GET in <java.lang.Object a.getUser(int)>
+"#
+        );
+
+        Ok(())
+    }
+}
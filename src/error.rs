@@ -3,11 +3,14 @@ use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+use crate::tokenizer::BOM;
+
 #[derive(Debug, PartialEq)]
 pub enum Error {
     UnrecognizedToken(String),
     ReadFailure(PathBuf),
     Utf8Error(PathBuf),
+    InvalidDex(String),
 }
 
 impl Display for Error {
@@ -20,6 +23,7 @@ impl Display for Error {
                 "Failed to decode file {}, not valid UTF-8",
                 path_to_string(path)
             ),
+            Self::InvalidDex(message) => write!(f, "Invalid dex file: {message}"),
         }
     }
 }
@@ -50,33 +54,74 @@ impl ParseError {
             expected,
         }
     }
-}
 
-impl Display for ParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        let prefix = &self.data[..self.pos];
-        let line = prefix.matches('\n').count() + 1;
-        let col = if let Some(index) = prefix.rfind('\n') {
-            prefix.len() - index
-        } else {
-            prefix.len() + 1
-        };
+    /// Returns the position of the unexpected token, skipping the whitespace preceding it.
+    fn get_token_position(&self) -> usize {
+        let rest = &self.data[self.pos..];
+        self.pos + (rest.len() - rest.trim_start_matches([' ', '\t']).len())
+    }
 
-        let mut token = self.data[self.pos..].trim_start_matches([' ', '\t']);
+    fn get_token(&self) -> &str {
+        let mut token = &self.data[self.get_token_position()..];
         if token.is_empty() {
             token = "<EOF>";
         } else {
-            if let Some(index) = token.find([' ', '\t', '\n']) {
+            if let Some(index) = token.find([' ', '\t', '\r', '\n']) {
                 token = &token[..index];
             }
             if token.is_empty() {
                 token = "<EOL>";
             }
         }
+        token
+    }
+
+    /// Returns the line and column of the unexpected token, both starting at 1.
+    pub fn get_line_column(&self) -> (usize, usize) {
+        let prefix = &self.data[..self.get_token_position()];
+        let line = prefix.matches('\n').count() + 1;
+        let line_start = prefix.rfind('\n').map_or(0, |index| index + 1);
+        let col = prefix[line_start..].trim_start_matches(BOM).chars().count() + 1;
+        (line, col)
+    }
+
+    /// Formats the error as a single `path:line:column: message` line, as understood by
+    /// editors and CI systems.
+    pub fn to_short_string(&self) -> String {
+        let (line, col) = self.get_line_column();
+        format!(
+            "{}:{line}:{col}: unexpected token {}, expected {}",
+            path_to_string(&self.path),
+            self.get_token(),
+            self.expected
+        )
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        // Point to the unexpected token rather than the whitespace preceding it
+        let pos = self.get_token_position();
+        let (line, col) = self.get_line_column();
+        let token = self.get_token();
+
+        let line_start = self.data[..pos].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = self.data[pos..]
+            .find('\n')
+            .map_or(self.data.len(), |index| pos + index);
+        let line_prefix = self.data[line_start..pos].trim_start_matches(BOM);
+        let line_text = self.data[line_start..line_end]
+            .trim_start_matches(BOM)
+            .trim_end_matches('\r');
 
+        // Keep tabs in the caret line, so that it aligns regardless of tab width
+        let indent = line_prefix
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
         write!(
             f,
-            "Unexpected token {token} in {} at {line}:{col}, expected {}",
+            "Unexpected token {token} in {} at {line}:{col}, expected {}\n    {line_text}\n    {indent}^",
             path_to_string(&self.path),
             self.expected
         )
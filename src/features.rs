@@ -0,0 +1,234 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::method::{Complexity, Method};
+use crate::schema::{self, JsonOutput, SchemaVersion};
+
+/// Output format of the features.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FeatureFormat {
+    Csv,
+    Ndjson,
+}
+
+/// Characteristics of a single method, suitable for classification and similarity analysis.
+#[derive(Debug, PartialEq)]
+pub struct MethodFeatures {
+    pub class: String,
+    pub method: String,
+    pub blocks: usize,
+    pub edges: usize,
+    pub complexity: Complexity,
+    pub opcodes: BTreeMap<&'static str, usize>,
+    pub api_calls: Vec<String>,
+    pub string_hashes: Vec<String>,
+}
+
+/// Features of a method written as a line of newline-delimited JSON.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct FeaturesRecord<'a> {
+    schema_version: SchemaVersion<{ JsonOutput::Features.version() }>,
+    class: &'a str,
+    method: &'a str,
+    instructions: usize,
+    blocks: usize,
+    edges: usize,
+    cyclomatic: usize,
+    nesting_depth: usize,
+    /// Number of commands in each command family
+    opcodes: &'a BTreeMap<&'static str, usize>,
+    api_calls: &'a [String],
+    /// Truncated SHA-256 hashes of the string constants
+    string_hashes: &'a [String],
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Hashes string constants, so that they can be compared without revealing their contents.
+fn hash_string(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))[..16].to_string()
+}
+
+impl MethodFeatures {
+    pub fn new(class: &Class, method: &Method) -> Self {
+        let families = Instruction::get_command_families();
+        let mut opcodes = BTreeMap::new();
+        let mut api_calls = Vec::new();
+        let mut string_hashes = Vec::new();
+        for instruction in &method.instructions {
+            let Instruction::Command { parameters, .. } = instruction else {
+                continue;
+            };
+            if let Some(family) = instruction
+                .get_command_family()
+                .and_then(|family| families.iter().find(|known| **known == family))
+            {
+                *opcodes.entry(*family).or_default() += 1;
+            }
+
+            for parameter in parameters {
+                match parameter {
+                    CommandParameter::Method(method) => api_calls.push(method.to_string()),
+                    CommandParameter::Literal(literal) => {
+                        if let Some(value) = literal.get_string() {
+                            string_hashes.push(hash_string(&value));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let blocks = method.get_basic_blocks();
+        Self {
+            class: class.class_type.to_string(),
+            method: method.get_signature(&class.class_type).to_string(),
+            blocks: blocks.len(),
+            edges: blocks.iter().map(|block| block.successors.len()).sum(),
+            complexity: method.get_complexity(),
+            opcodes,
+            api_calls,
+            string_hashes,
+        }
+    }
+
+    pub fn write_csv_header(output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut columns = vec![
+            "class".to_string(),
+            "method".to_string(),
+            "instructions".to_string(),
+            "blocks".to_string(),
+            "edges".to_string(),
+            "cyclomatic".to_string(),
+            "nesting_depth".to_string(),
+        ];
+        columns.extend(
+            Instruction::get_command_families()
+                .into_iter()
+                .map(|family| format!("op:{family}")),
+        );
+        columns.push("api_calls".to_string());
+        columns.push("string_hashes".to_string());
+        writeln!(output, "{}", columns.join(","))
+    }
+
+    pub fn write_csv(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut fields = vec![
+            csv_field(&self.class),
+            csv_field(&self.method),
+            self.complexity.instructions.to_string(),
+            self.blocks.to_string(),
+            self.edges.to_string(),
+            self.complexity.cyclomatic.to_string(),
+            self.complexity.nesting_depth.to_string(),
+        ];
+        fields.extend(
+            Instruction::get_command_families()
+                .into_iter()
+                .map(|family| self.opcodes.get(family).copied().unwrap_or(0).to_string()),
+        );
+        fields.push(csv_field(&self.api_calls.join(";")));
+        fields.push(self.string_hashes.join(";"));
+        writeln!(output, "{}", fields.join(","))
+    }
+
+    pub fn write_json(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        schema::write_json(
+            output,
+            &FeaturesRecord {
+                schema_version: SchemaVersion,
+                class: &self.class,
+                method: &self.method,
+                instructions: self.complexity.instructions,
+                blocks: self.blocks,
+                edges: self.edges,
+                cyclomatic: self.complexity.cyclomatic,
+                nesting_depth: self.complexity.nesting_depth,
+                opcodes: &self.opcodes,
+                api_calls: &self.api_calls,
+                string_hashes: &self.string_hashes,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn method_features() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static run(I)V
+                    .locals 1
+                    if-eqz p0, :cond_0
+                    const-string v0, "a\"b"
+                    invoke-static {v0}, La;->log(Ljava/lang/String;)V
+                    :cond_0
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let features = MethodFeatures::new(&class, &class.methods[0]);
+        assert_eq!(features.blocks, 3);
+        assert_eq!(features.edges, 3);
+        assert_eq!(
+            features.complexity,
+            Complexity {
+                instructions: 4,
+                cyclomatic: 2,
+                nesting_depth: 0,
+            }
+        );
+        assert_eq!(features.opcodes.get("const-string"), Some(&1));
+        assert_eq!(features.opcodes.get("if-eqz"), Some(&1));
+
+        let mut output = Vec::new();
+        features.write_json(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Features.assert_valid(&output);
+        assert_eq!(
+            output,
+            format!(
+                r#"{{"schema_version":1,"class":"a","method":"void a.run(int)","instructions":4,"blocks":3,"edges":3,"cyclomatic":2,"nesting_depth":0,"opcodes":{{"const-string":1,"if-eqz":1,"invoke-static":1,"return-void":1}},"api_calls":["void a.log(java.lang.String)"],"string_hashes":["{}"]}}
+"#,
+                hash_string(r#"a\"b"#)
+            )
+        );
+
+        let mut output = Vec::new();
+        features.write_csv(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("a,void a.run(int),4,3,3,2,0,"));
+        assert!(output.ends_with(&format!(
+            ",void a.log(java.lang.String),{}\n",
+            hash_string(r#"a\"b"#)
+        )));
+
+        Ok(())
+    }
+}
@@ -2,22 +2,32 @@ use std::io::Write;
 
 use super::Field;
 use crate::access_flag::AccessFlag;
+use crate::annotation::Annotation;
+use crate::doc::Doc;
+use crate::output::{jimple_identifier, write_jimple_line, OutputOptions};
 
 impl Field {
-    pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
-        for annotation in &self.annotations {
-            annotation.write_jimple(output, 1)?;
-        }
-
-        write!(output, "    ")?;
-        AccessFlag::write_jimple_list(output, &self.visibility)?;
-        write!(output, "{} {}", self.field_type, self.name)?;
-
+    /// Builds the field declaration, without its annotations.
+    pub fn get_jimple_doc(&self) -> Doc {
+        let mut declaration = format!(
+            "{}{} {}",
+            AccessFlag::get_jimple_list(&self.visibility),
+            self.field_type,
+            jimple_identifier(&self.name)
+        );
         if let Some(initial_value) = &self.initial_value {
-            write!(output, " = {}", initial_value)?;
+            declaration.push_str(&format!(" = {initial_value}"));
         }
-        writeln!(output, ";")?;
+        declaration.push(';');
+        Doc::text(declaration)
+    }
 
-        Ok(())
+    pub fn write_jimple(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        Annotation::write_jimple_list(output, &self.annotations, 1, options)?;
+        write_jimple_line(output, 4, self.get_jimple_doc(), options)
     }
 }
@@ -1,16 +1,43 @@
+use std::io::Write;
+
 use super::Field;
 use crate::access_flag::AccessFlag;
 use crate::annotation::Annotation;
 use crate::error::ParseError;
 use crate::literal::Literal;
 use crate::r#type::Type;
-use crate::tokenizer::Tokenizer;
+use crate::tokenizer::{quote_identifier, Tokenizer};
 
 impl Field {
+    pub fn write_smali(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        write!(output, ".field ")?;
+        for flag in &self.visibility {
+            write!(output, "{flag} ")?;
+        }
+        write!(
+            output,
+            "{}:{}",
+            quote_identifier(&self.name),
+            self.field_type.to_descriptor()
+        )?;
+        if let Some(value) = &self.initial_value {
+            write!(output, " = {}", value.to_smali())?;
+        }
+        writeln!(output)?;
+
+        if !self.annotations.is_empty() {
+            for annotation in &self.annotations {
+                annotation.write_smali(output, "    ")?;
+            }
+            writeln!(output, ".end field")?;
+        }
+        Ok(())
+    }
+
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
         let (input, visibility) = AccessFlag::read_list(input);
 
-        let (input, name) = input.read_keyword()?;
+        let (input, name) = input.read_identifier()?;
         let input = input.expect_char(':')?;
 
         let (mut input, field_type) = Type::read(&input)?;
@@ -27,7 +54,7 @@ impl Field {
         let mut input = input.expect_eol()?;
 
         let mut annotations = Vec::new();
-        if input.expect_directive("annotation").is_ok() {
+        if input.peek_directive() == Some("annotation") {
             while input.expect_directive("end").is_err() {
                 input = input.expect_directive("annotation")?;
 
@@ -59,6 +86,7 @@ mod tests {
     use crate::annotation::{AnnotationParameter, AnnotationParameterValue, AnnotationVisibility};
     use crate::error::ParseErrorDisplayed;
     use crate::literal::Literal;
+    use crate::output::OutputOptions;
     use crate::r#type::Type;
 
     fn tokenizer(data: &str) -> Tokenizer {
@@ -94,7 +122,7 @@ mod tests {
                 name: "description".to_string(),
                 field_type: Type::Object("java.lang.String".to_string()),
                 visibility: vec![AccessFlag::Private, AccessFlag::Final],
-                initial_value: Some(Literal::String("hi".to_string())),
+                initial_value: Some(Literal::String("hi".into())),
                 annotations: Vec::new(),
             }
         );
@@ -129,10 +157,10 @@ mod tests {
                     parameters: vec![AnnotationParameter {
                         name: "value".to_string(),
                         value: AnnotationParameterValue::Array(
-                            vec!["Lnu/b<", "Ljava/lang/String;", ">;"]
+                            ["Lnu/b<", "Ljava/lang/String;", ">;"]
                                 .iter()
                                 .map(|v| AnnotationParameterValue::Literal(Literal::String(
-                                    v.to_string()
+                                    (*v).into()
                                 )))
                                 .collect()
                         ),
@@ -144,4 +172,60 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn read_unusual_names() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+                .field static a-b:I=0x1
+                    .annotation runtime La;
+                        value=.enum Lc;->d-e:Lc;
+                    .end annotation
+                .end field
+            "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("field")?;
+        let (input, field) = Field::read(&input)?;
+        assert_eq!(field.name, "a-b");
+        assert_eq!(field.initial_value, Some(Literal::Int(1)));
+        assert_eq!(
+            field.annotations[0].get_parameter("value"),
+            Some(&AnnotationParameterValue::Enum(
+                Type::Object("c".to_string()),
+                "d-e".to_string()
+            ))
+        );
+        assert!(input.expect_eof().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_names() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(".field static `weird name`:I");
+        let input = input.expect_directive("field")?;
+        let (input, field) = Field::read(&input)?;
+        assert_eq!(field.name, "weird name");
+        assert!(input.expect_eof().is_ok());
+
+        let mut output = Vec::new();
+        field.write_smali(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ".field static `weird name`:I\n"
+        );
+
+        let mut output = Vec::new();
+        field
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "    static int 'weird name';\n"
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,372 @@
+use std::io::Write;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::output::{FileHeader, OutputOptions};
+use crate::schema::{JsonOutput, SchemaVersion};
+
+/// Output format of the decompile command.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Format {
+    /// Decompiled Jimple code
+    #[default]
+    Jimple,
+    /// Class structure as JSON, with the Jimple code of each method
+    Json,
+    /// Smali code as read, without optimizations or annotations applied. Only supported when
+    /// reading dex files directly
+    Smali,
+}
+
+impl Format {
+    pub fn get_writer(self) -> &'static dyn OutputFormat {
+        match self {
+            Self::Jimple => &JimpleWriter,
+            Self::Json => &JsonWriter,
+            Self::Smali => &SmaliWriter,
+        }
+    }
+}
+
+/// Writer producing the files of an output format from classes, shared by the threads
+/// converting files.
+pub trait OutputFormat: std::fmt::Debug + Sync {
+    /// Extension of the output files, without the leading dot.
+    fn extension(&self) -> &'static str;
+
+    /// Checks whether inner classes can be written into the file of their outer class.
+    fn supports_nesting(&self) -> bool {
+        false
+    }
+
+    /// Checks whether the format shows decompiled code. Classes are optimized and annotated
+    /// by the enabled analyses before being written out only if it does.
+    fn is_decompiled(&self) -> bool {
+        true
+    }
+
+    /// Writes the metadata at the top of each file, the class follows immediately.
+    fn write_header(
+        &self,
+        output: &mut dyn Write,
+        header: &FileHeader,
+    ) -> Result<(), std::io::Error>;
+
+    /// Writes a class along with the output of its inner classes if nesting is supported.
+    fn write_class(
+        &self,
+        output: &mut dyn Write,
+        class: &Class,
+        options: &OutputOptions,
+        inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error>;
+
+    /// Writes a class split into several files with at most `methods_per_part` methods each,
+    /// the first one being the main file. Returns `None` if the format doesn't support
+    /// splitting classes.
+    fn write_class_parts(
+        &self,
+        _class: &Class,
+        _options: &OutputOptions,
+        _inner_classes: &[Vec<u8>],
+        _methods_per_part: usize,
+        _file_stem: &str,
+    ) -> Option<Result<Vec<Vec<u8>>, std::io::Error>> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct JimpleWriter;
+
+impl OutputFormat for JimpleWriter {
+    fn extension(&self) -> &'static str {
+        "jimple"
+    }
+
+    fn supports_nesting(&self) -> bool {
+        true
+    }
+
+    fn write_header(
+        &self,
+        output: &mut dyn Write,
+        header: &FileHeader,
+    ) -> Result<(), std::io::Error> {
+        header.write_comments(output, "//")
+    }
+
+    fn write_class(
+        &self,
+        output: &mut dyn Write,
+        class: &Class,
+        options: &OutputOptions,
+        inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error> {
+        class.write_jimple_nested(output, options, inner_classes)
+    }
+
+    fn write_class_parts(
+        &self,
+        class: &Class,
+        options: &OutputOptions,
+        inner_classes: &[Vec<u8>],
+        methods_per_part: usize,
+        file_stem: &str,
+    ) -> Option<Result<Vec<Vec<u8>>, std::io::Error>> {
+        Some(class.write_jimple_parts(options, inner_classes, methods_per_part, file_stem))
+    }
+}
+
+#[derive(Debug)]
+pub struct SmaliWriter;
+
+impl OutputFormat for SmaliWriter {
+    fn extension(&self) -> &'static str {
+        "smali"
+    }
+
+    fn is_decompiled(&self) -> bool {
+        false
+    }
+
+    fn write_header(
+        &self,
+        output: &mut dyn Write,
+        header: &FileHeader,
+    ) -> Result<(), std::io::Error> {
+        header.write_comments(output, "#")
+    }
+
+    fn write_class(
+        &self,
+        output: &mut dyn Write,
+        class: &Class,
+        _options: &OutputOptions,
+        _inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error> {
+        class.write_smali(output)
+    }
+}
+
+/// Metadata preceding the class in the JSON output.
+#[derive(Debug, Serialize, JsonSchema)]
+struct HeaderJson {
+    schema_version: SchemaVersion<{ JsonOutput::Class.version() }>,
+    generated_by: String,
+    input: String,
+    sha256: String,
+    /// Dex file within the APK the class was read from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dex: Option<String>,
+    /// Number of warnings issued while processing the class
+    warnings: usize,
+    options: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct FieldJson {
+    name: String,
+    #[serde(rename = "type")]
+    field_type: String,
+    access: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct MethodJson {
+    signature: String,
+    access: Vec<String>,
+    /// Decompiled method as it would be written to a Jimple file
+    jimple: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct ClassJson {
+    name: String,
+    access: Vec<String>,
+    #[serde(rename = "super", skip_serializing_if = "Option::is_none")]
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    fields: Vec<FieldJson>,
+    methods: Vec<MethodJson>,
+}
+
+/// Class file written with the JSON output format. The header and the class are written
+/// separately, this type only describes their combination.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct ClassFile {
+    #[serde(flatten)]
+    header: HeaderJson,
+    class: ClassJson,
+}
+
+#[derive(Debug)]
+pub struct JsonWriter;
+
+impl JsonWriter {
+    fn flags(flags: &[AccessFlag]) -> Vec<String> {
+        flags.iter().map(AccessFlag::to_string).collect()
+    }
+}
+
+impl OutputFormat for JsonWriter {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    /// Opens the object containing the metadata, the class is its last property.
+    fn write_header(
+        &self,
+        output: &mut dyn Write,
+        header: &FileHeader,
+    ) -> Result<(), std::io::Error> {
+        let header = serde_json::to_string_pretty(&HeaderJson {
+            schema_version: SchemaVersion,
+            generated_by: format!("aarf {}", header.version),
+            input: header.input_path.to_string_lossy().into_owned(),
+            sha256: header.input_hash.clone(),
+            dex: header.dex_file.clone(),
+            warnings: header.warnings,
+            options: header.options.clone(),
+        })?;
+        let header = header.strip_suffix("\n}").unwrap_or(&header);
+        write!(output, "{header},\n  \"class\": ")
+    }
+
+    fn write_class(
+        &self,
+        output: &mut dyn Write,
+        class: &Class,
+        options: &OutputOptions,
+        _inner_classes: &[Vec<u8>],
+    ) -> Result<(), std::io::Error> {
+        let fields = class
+            .fields
+            .iter()
+            .map(|field| FieldJson {
+                name: field.name.clone(),
+                field_type: field.field_type.to_string(),
+                access: Self::flags(&field.visibility),
+                value: field.initial_value.as_ref().map(ToString::to_string),
+            })
+            .collect();
+        let methods = class
+            .methods
+            .iter()
+            .map(|method| {
+                let mut code = Vec::new();
+                method.write_jimple_member(&mut code, options, Some(&class.class_type))?;
+                Ok(MethodJson {
+                    signature: method.get_signature(&class.class_type).to_string(),
+                    access: Self::flags(&method.visibility),
+                    jimple: String::from_utf8_lossy(&code).into_owned(),
+                })
+            })
+            .collect::<Result<_, std::io::Error>>()?;
+        let class = serde_json::to_string_pretty(&ClassJson {
+            name: class.class_type.to_string(),
+            access: Self::flags(&class.access_flags),
+            super_class: class.super_class.as_ref().map(ToString::to_string),
+            interfaces: class.interfaces.iter().map(ToString::to_string).collect(),
+            source: class.source_file.clone(),
+            fields,
+            methods,
+        })?;
+
+        // The class is nested within the object opened by the header
+        writeln!(output, "{}", class.replace('\n', "\n  "))?;
+        writeln!(output, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn output_formats() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field public static final MAX:I = 0x10
+
+                .method public run()V
+                    .locals 0
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, class) = Class::read(&input)?;
+        let header = FileHeader::new(std::path::Path::new("smali/a.smali"), b"", "decompile", 0);
+
+        let mut output = Vec::new();
+        let writer = Format::Jimple.get_writer();
+        writer.write_header(&mut output, &header).unwrap();
+        writer
+            .write_class(&mut output, &class, &OutputOptions::default(), &[])
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("// Generated by aarf "));
+        assert!(output.contains("\npublic class a\n"));
+
+        let mut output = Vec::new();
+        let writer = Format::Json.get_writer();
+        writer.write_header(&mut output, &header).unwrap();
+        writer
+            .write_class(&mut output, &class, &OutputOptions::default(), &[])
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Class.assert_valid(&output);
+        assert!(output.starts_with("{\n  \"schema_version\": 1,\n"));
+        assert!(output.ends_with("\n  }\n}\n"));
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["dex"], "classes.dex");
+        assert_eq!(output["class"]["name"], "a");
+        assert_eq!(
+            output["class"]["fields"],
+            serde_json::json!([{
+                "name": "MAX",
+                "type": "int",
+                "access": ["public", "static", "final"],
+                "value": "0x10"
+            }])
+        );
+        assert_eq!(
+            output["class"]["methods"],
+            serde_json::json!([{
+                "signature": "void a.run()",
+                "access": ["public"],
+                "jimple": "    public void run()\n    {\n        return;\n    }\n"
+            }])
+        );
+
+        let mut output = Vec::new();
+        let writer = Format::Smali.get_writer();
+        assert!(!writer.is_decompiled());
+        writer.write_header(&mut output, &header).unwrap();
+        writer
+            .write_class(&mut output, &class, &OutputOptions::default(), &[])
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("# Generated by aarf "));
+        assert!(output.contains("\n.class public La;\n.super Ljava/lang/Object;\n"));
+        assert!(output.contains("\n.field public static final MAX:I = 0x10\n"));
+
+        Ok(())
+    }
+}
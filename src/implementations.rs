@@ -0,0 +1,422 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::r#type::{CallSignature, MethodSignature, Type};
+
+/// Classes implementing the interfaces declared in the program, along with the methods
+/// constructing their instances. This shows where `invoke-interface` calls can end up, and
+/// calls with only one possible target can be pointed at it.
+#[derive(Debug, Default)]
+pub struct InterfaceIndex {
+    /// Interfaces to be listed, by name
+    interfaces: BTreeMap<String, Type>,
+    /// Superclass and interfaces of each class in the program
+    parents: HashMap<Type, Vec<Type>>,
+    /// Classes and interfaces directly extending or implementing each type
+    children: HashMap<Type, Vec<Type>>,
+    /// Superclass of each class in the program
+    super_classes: HashMap<Type, Type>,
+    /// Methods with code declared by each class, by name and call signature
+    methods: HashMap<Type, Vec<(String, CallSignature)>>,
+    /// Interfaces and abstract classes of the program, these cannot be constructed
+    abstract_types: HashSet<Type>,
+    /// Methods containing `new-instance` commands for each class
+    constructors: HashMap<Type, BTreeSet<String>>,
+}
+
+impl InterfaceIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        self.add_declarations(class);
+        self.add_references(class);
+    }
+
+    /// Records the class if it is an interface to be listed.
+    pub fn add_declarations(&mut self, class: &Class) {
+        if class.access_flags.contains(&AccessFlag::Interface) {
+            self.interfaces
+                .insert(class.class_type.to_string(), class.class_type.clone());
+        }
+    }
+
+    /// Records the position of the class in the hierarchy and the instances its code
+    /// constructs.
+    pub fn add_references(&mut self, class: &Class) {
+        let parents = class
+            .super_class
+            .iter()
+            .chain(&class.interfaces)
+            .cloned()
+            .collect::<Vec<_>>();
+        for parent in &parents {
+            self.children
+                .entry(parent.clone())
+                .or_default()
+                .push(class.class_type.clone());
+        }
+        self.parents.insert(class.class_type.clone(), parents);
+        if let Some(super_class) = &class.super_class {
+            self.super_classes
+                .insert(class.class_type.clone(), super_class.clone());
+        }
+        self.methods.insert(
+            class.class_type.clone(),
+            class
+                .methods
+                .iter()
+                .filter(|method| !method.visibility.contains(&AccessFlag::Abstract))
+                .map(|method| {
+                    let signature = method.get_signature(&class.class_type);
+                    (signature.method_name, signature.call_signature)
+                })
+                .collect(),
+        );
+        if class.access_flags.contains(&AccessFlag::Interface)
+            || class.access_flags.contains(&AccessFlag::Abstract)
+        {
+            self.abstract_types.insert(class.class_type.clone());
+        }
+
+        for method in &class.methods {
+            for instruction in &method.instructions {
+                if let Instruction::Command {
+                    command,
+                    parameters,
+                } = instruction
+                {
+                    if let (
+                        "new-instance",
+                        Some(CommandParameter::Type(constructed @ Type::Object(_))),
+                    ) = (command.as_str(), parameters.get(1))
+                    {
+                        self.constructors
+                            .entry(constructed.clone())
+                            .or_default()
+                            .insert(format!("{}.{}()", class.class_type, method.name));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lists all classes and interfaces extending or implementing a type, directly or
+    /// indirectly.
+    fn get_subtypes(&self, class_type: &Type) -> Vec<&Type> {
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        let mut pending = vec![class_type];
+        while let Some(current) = pending.pop() {
+            for child in self.children.get(current).into_iter().flatten() {
+                if seen.insert(child) {
+                    result.push(child);
+                    pending.push(child);
+                }
+            }
+        }
+        result
+    }
+
+    /// Lists the classes implementing an interface, directly or via superclasses and
+    /// interfaces extending it. Interfaces aren't included, abstract classes are.
+    pub fn get_implementations(&self, interface: &Type) -> Vec<&Type> {
+        let mut result = self
+            .get_subtypes(interface)
+            .into_iter()
+            .filter(|class_type| !self.interfaces.contains_key(&class_type.to_string()))
+            .collect::<Vec<_>>();
+        result.sort_by_key(|class_type| class_type.to_string());
+        result
+    }
+
+    /// Finds the class whose implementation of a method instances of a class use, `None` if
+    /// the implementation is inherited from outside the program.
+    fn resolve_method(
+        &self,
+        class_type: &Type,
+        name: &str,
+        call_signature: &CallSignature,
+    ) -> Option<&Type> {
+        let (mut current, _) = self.parents.get_key_value(class_type)?;
+        loop {
+            let declares = self.methods.get(current).is_some_and(|methods| {
+                methods
+                    .iter()
+                    .any(|(method, signature)| method == name && signature == call_signature)
+            });
+            if declares {
+                return Some(current);
+            }
+            current = self.super_classes.get(current)?;
+            if !self.parents.contains_key(current) {
+                return None;
+            }
+        }
+    }
+
+    /// Determines the implementation of a method that any call via the given signature ends
+    /// up in, if this is a subclass of the type called. This requires all classes that could
+    /// be receivers to be part of the program and to resolve to the same implementation.
+    pub fn get_single_target(&self, method: &MethodSignature) -> Option<&Type> {
+        if !self.parents.contains_key(&method.object_type) {
+            return None;
+        }
+
+        let mut target = None;
+        for class_type in
+            std::iter::once(&method.object_type).chain(self.get_subtypes(&method.object_type))
+        {
+            if !self.is_constructible(class_type) {
+                continue;
+            }
+            let implementation =
+                self.resolve_method(class_type, &method.method_name, &method.call_signature)?;
+            if target.is_some_and(|target| target != implementation) {
+                return None;
+            }
+            target = Some(implementation);
+        }
+        target.filter(|target| *target != &method.object_type)
+    }
+
+    /// Points virtual and interface calls with a single possible target at the implementation,
+    /// with a note next to the call.
+    pub fn devirtualize(&self, class: &mut Class) {
+        for method in &mut class.methods {
+            for instruction in &mut method.instructions {
+                let Instruction::Command {
+                    command,
+                    parameters,
+                } = instruction
+                else {
+                    continue;
+                };
+                if !command.starts_with("invoke-virtual")
+                    && !command.starts_with("invoke-interface")
+                {
+                    continue;
+                }
+                let Some(CommandParameter::Method(called)) = parameters
+                    .iter_mut()
+                    .find(|parameter| matches!(parameter, CommandParameter::Method(_)))
+                else {
+                    continue;
+                };
+                let Some(target) = self.get_single_target(called) else {
+                    continue;
+                };
+                called.object_type = target.clone();
+                parameters.push(CommandParameter::Comment("devirtualized".to_string()));
+            }
+        }
+    }
+
+    /// Checks whether a class can be constructed, as opposed to interfaces and abstract
+    /// classes.
+    pub fn is_constructible(&self, class_type: &Type) -> bool {
+        !self.abstract_types.contains(class_type)
+    }
+
+    /// Lists the methods constructing instances of a class, sorted.
+    pub fn get_constructors(&self, class_type: &Type) -> Vec<&str> {
+        self.constructors
+            .get(class_type)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        for (name, interface) in &self.interfaces {
+            writeln!(output, "{name}")?;
+            let implementations = self.get_implementations(interface);
+            if implementations.is_empty() {
+                writeln!(output, "    no implementations found")?;
+            }
+            for class_type in implementations {
+                if !self.is_constructible(class_type) {
+                    writeln!(output, "    {class_type} (abstract)")?;
+                    continue;
+                }
+                writeln!(output, "    {class_type}")?;
+                let constructors = self.get_constructors(class_type);
+                if constructors.is_empty() {
+                    writeln!(output, "        never constructed")?;
+                }
+                for constructor in constructors {
+                    writeln!(output, "        constructed in {constructor}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn interface_index() -> Result<(), ParseErrorDisplayed> {
+        let classes = [
+            r#"
+                .class public interface abstract La;
+                .super Ljava/lang/Object;
+            "#,
+            r#"
+                .class public interface abstract Lb;
+                .super Ljava/lang/Object;
+                .implements La;
+            "#,
+            r#"
+                .class public abstract Lc;
+                .super Ljava/lang/Object;
+                .implements Lb;
+            "#,
+            r#"
+                .class public Ld;
+                .super Lc;
+
+                .method public static create()La;
+                    .locals 1
+                    new-instance v0, Ld;
+                    invoke-direct {v0}, Ld;-><init>()V
+                    return-object v0
+                .end method
+            "#,
+            r#"
+                .class public Le;
+                .super Ljava/lang/Object;
+                .implements Ljava/lang/Runnable;
+                .implements Lb;
+            "#,
+        ];
+
+        let mut index = InterfaceIndex::new();
+        for class in classes {
+            index.add_class(&read_class(class)?);
+        }
+
+        let mut output = Vec::new();
+        index.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"a
+    c (abstract)
+    d
+        constructed in d.create()
+    e
+        never constructed
+b
+    c (abstract)
+    d
+        constructed in d.create()
+    e
+        never constructed
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn devirtualize() -> Result<(), ParseErrorDisplayed> {
+        let classes = [
+            r#"
+                .class public interface abstract Ls;
+                .super Ljava/lang/Object;
+
+                .method public abstract run()V
+                .end method
+            "#,
+            r#"
+                .class public Lt;
+                .super Ljava/lang/Object;
+                .implements Ls;
+
+                .method public run()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+            r#"
+                .class public Lu;
+                .super Lt;
+            "#,
+        ];
+        let caller = r#"
+            .class public Lv;
+            .super Ljava/lang/Object;
+
+            .method public call(Ls;Lt;Ljava/lang/Object;)V
+                .locals 0
+                invoke-interface {p1}, Ls;->run()V
+                invoke-virtual {p2}, Lt;->run()V
+                invoke-virtual {p3}, Ljava/lang/Object;->hashCode()I
+                invoke-virtual {p0}, Lv;->toString()Ljava/lang/String;
+                return-void
+            .end method
+        "#;
+
+        let get_targets = |index: &InterfaceIndex| -> Result<Vec<String>, ParseErrorDisplayed> {
+            let mut class = read_class(caller)?;
+            index.devirtualize(&mut class);
+            Ok(class.methods[0]
+                .instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::Command { parameters, .. } => match parameters.as_slice() {
+                        [_, _, CommandParameter::Method(method), rest @ ..] => Some(format!(
+                            "{}{}",
+                            method.object_type,
+                            if rest.is_empty() {
+                                ""
+                            } else {
+                                " devirtualized"
+                            }
+                        )),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect())
+        };
+
+        let mut index = InterfaceIndex::new();
+        for class in classes {
+            index.add_class(&read_class(class)?);
+        }
+        index.add_class(&read_class(caller)?);
+        assert_eq!(
+            get_targets(&index)?,
+            vec!["t devirtualized", "t", "java.lang.Object", "v"]
+        );
+
+        // A second implementation makes the interface call ambiguous
+        index.add_class(&read_class(
+            r#"
+                .class public Lw;
+                .super Lt;
+
+                .method public run()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?);
+        assert_eq!(
+            get_targets(&index)?,
+            vec!["s", "t", "java.lang.Object", "v"]
+        );
+
+        Ok(())
+    }
+}
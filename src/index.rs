@@ -0,0 +1,317 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Memory used by the on-disk index before it writes its entries out, in bytes.
+pub const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Approximate memory used by an entry in addition to its data.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Distinguishes the directories of multiple indexes in a process.
+static INDEX_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn escape(value: &str, output: &mut String) {
+    for c in value.chars() {
+        match c {
+            '\\' => output.push_str("\\\\"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            c => output.push(c),
+        }
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(c) => result.push(c),
+            None => (),
+        }
+    }
+    result
+}
+
+fn parse_line(line: &str) -> Result<(String, String), std::io::Error> {
+    match line.split_once('\t') {
+        Some((key, value)) => Ok((unescape(key), unescape(value))),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Corrupted index entry: {line}"),
+        )),
+    }
+}
+
+/// Index entries written out to disk, as sorted runs of tab-separated lines. The entries not
+/// written out yet are kept in memory until they exceed the memory limit.
+#[derive(Debug)]
+pub struct DiskIndex {
+    dir: PathBuf,
+    buffer: BTreeMap<String, Vec<String>>,
+    buffer_size: usize,
+    memory_limit: usize,
+    runs: Vec<PathBuf>,
+}
+
+impl DiskIndex {
+    fn new(memory_limit: usize) -> Result<Self, std::io::Error> {
+        let dir = std::env::temp_dir().join(format!(
+            "aarf-index-{}-{}",
+            std::process::id(),
+            INDEX_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            buffer: BTreeMap::new(),
+            buffer_size: 0,
+            memory_limit,
+            runs: Vec::new(),
+        })
+    }
+
+    fn insert(&mut self, key: &str, value: &str) -> Result<(), std::io::Error> {
+        self.buffer_size += key.len() + value.len() + ENTRY_OVERHEAD;
+        self.buffer
+            .entry(key.to_string())
+            .or_default()
+            .push(value.to_string());
+        if self.buffer_size > self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the entries kept in memory into a new run file.
+    fn spill(&mut self) -> Result<(), std::io::Error> {
+        let path = self.dir.join(format!("run{}", self.runs.len()));
+        let mut output = BufWriter::new(File::create(&path)?);
+        let mut line = String::new();
+        for (key, values) in std::mem::take(&mut self.buffer) {
+            for value in values {
+                line.clear();
+                escape(&key, &mut line);
+                line.push('\t');
+                escape(&value, &mut line);
+                line.push('\n');
+                output.write_all(line.as_bytes())?;
+            }
+        }
+        output.flush()?;
+        self.runs.push(path);
+        self.buffer_size = 0;
+        Ok(())
+    }
+
+    fn open_runs(&self) -> Result<Vec<Lines<BufReader<File>>>, std::io::Error> {
+        self.runs
+            .iter()
+            .map(|path| Ok(BufReader::new(File::open(path)?).lines()))
+            .collect()
+    }
+}
+
+impl Drop for DiskIndex {
+    fn drop(&mut self) {
+        // Leftover temporary files are harmless, nothing to do if removing fails
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Multimap from keys to values collected over the whole program. Memory usage of the on-disk
+/// variant is bounded, so that large programs can be processed on modest machines.
+#[derive(Debug)]
+pub enum Index {
+    Memory(BTreeMap<String, Vec<String>>),
+    Disk(DiskIndex),
+}
+
+impl Index {
+    /// Creates an index kept in memory, or on disk if a memory limit is given.
+    pub fn new(memory_limit: Option<usize>) -> Result<Self, std::io::Error> {
+        match memory_limit {
+            Some(memory_limit) => Self::on_disk(memory_limit),
+            None => Ok(Self::in_memory()),
+        }
+    }
+
+    pub fn in_memory() -> Self {
+        Self::Memory(BTreeMap::new())
+    }
+
+    /// Creates an index in a temporary directory that is removed when the index is dropped.
+    pub fn on_disk(memory_limit: usize) -> Result<Self, std::io::Error> {
+        Ok(Self::Disk(DiskIndex::new(memory_limit)?))
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) -> Result<(), std::io::Error> {
+        match self {
+            Self::Memory(entries) => {
+                entries
+                    .entry(key.to_string())
+                    .or_default()
+                    .push(value.to_string());
+                Ok(())
+            }
+            Self::Disk(index) => index.insert(key, value),
+        }
+    }
+
+    /// Returns the values stored for a key, sorted.
+    pub fn get(&self, key: &str) -> Result<Vec<String>, std::io::Error> {
+        let mut result = match self {
+            Self::Memory(entries) => entries.get(key).cloned().unwrap_or_default(),
+            Self::Disk(index) => {
+                let mut result = index.buffer.get(key).cloned().unwrap_or_default();
+                for run in index.open_runs()? {
+                    for line in run {
+                        let (entry_key, value) = parse_line(&line?)?;
+                        match entry_key.as_str().cmp(key) {
+                            std::cmp::Ordering::Less => continue,
+                            std::cmp::Ordering::Equal => result.push(value),
+                            std::cmp::Ordering::Greater => break,
+                        }
+                    }
+                }
+                result
+            }
+        };
+        result.sort();
+        Ok(result)
+    }
+
+    /// Calls the callback for each key in sorted order, passing along its sorted values.
+    pub fn for_each(
+        &self,
+        mut callback: impl FnMut(&str, &[String]),
+    ) -> Result<(), std::io::Error> {
+        let index = match self {
+            Self::Memory(entries) => {
+                for (key, values) in entries {
+                    let mut values = values.clone();
+                    values.sort();
+                    callback(key, &values);
+                }
+                return Ok(());
+            }
+            Self::Disk(index) => index,
+        };
+
+        // Merge the sorted runs and the entries kept in memory
+        let mut runs = index.open_runs()?;
+        let mut heap = BinaryHeap::new();
+        for (source, run) in runs.iter_mut().enumerate() {
+            if let Some(line) = run.next() {
+                let (key, value) = parse_line(&line?)?;
+                heap.push(Reverse((key, value, source)));
+            }
+        }
+        let memory_source = runs.len();
+        let mut memory = index
+            .buffer
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)));
+        if let Some((key, value)) = memory.next() {
+            heap.push(Reverse((key.clone(), value.clone(), memory_source)));
+        }
+
+        let mut current: Option<(String, Vec<String>)> = None;
+        while let Some(Reverse((key, value, source))) = heap.pop() {
+            let next = if source == memory_source {
+                memory
+                    .next()
+                    .map(|(key, value)| (key.clone(), value.clone()))
+            } else {
+                match runs[source].next() {
+                    Some(line) => Some(parse_line(&line?)?),
+                    None => None,
+                }
+            };
+            if let Some((next_key, next_value)) = next {
+                heap.push(Reverse((next_key, next_value, source)));
+            }
+
+            match &mut current {
+                Some((current_key, values)) if *current_key == key => values.push(value),
+                _ => {
+                    if let Some((current_key, mut values)) = current.take() {
+                        // Values of a key can come from multiple sources in any order
+                        values.sort();
+                        callback(&current_key, &values);
+                    }
+                    current = Some((key, vec![value]));
+                }
+            }
+        }
+        if let Some((key, mut values)) = current {
+            values.sort();
+            callback(&key, &values);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(index: &Index) -> Vec<(String, Vec<String>)> {
+        let mut result = Vec::new();
+        index
+            .for_each(|key, values| result.push((key.to_string(), values.to_vec())))
+            .unwrap();
+        result
+    }
+
+    #[test]
+    fn disk_index_matches_memory() {
+        let mut memory = Index::in_memory();
+        // A tiny memory limit forces entries to be written out every few insertions
+        let mut disk = Index::on_disk(150).unwrap();
+        let entries = [
+            ("b", "2"),
+            ("a\tb", "line\nbreak"),
+            ("c", "1"),
+            ("b", "1"),
+            ("a", "x\\y"),
+            ("c", "0"),
+            ("b", "3"),
+        ];
+        for (key, value) in entries {
+            memory.insert(key, value).unwrap();
+            disk.insert(key, value).unwrap();
+        }
+
+        let Index::Disk(inner) = &disk else {
+            panic!("Unexpected index type");
+        };
+        assert!(inner.runs.len() > 1);
+        assert!(!inner.buffer.is_empty());
+
+        assert_eq!(collect(&memory), collect(&disk));
+        assert_eq!(
+            collect(&disk)[1],
+            ("a\tb".to_string(), vec!["line\nbreak".to_string()])
+        );
+        assert_eq!(disk.get("b").unwrap(), vec!["1", "2", "3"]);
+        assert_eq!(memory.get("b").unwrap(), vec!["1", "2", "3"]);
+        assert!(disk.get("d").unwrap().is_empty());
+
+        let dir = inner.dir.clone();
+        drop(disk);
+        assert!(!dir.exists());
+    }
+}
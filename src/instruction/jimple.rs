@@ -1,112 +1,460 @@
+use std::collections::HashMap;
 use std::io::Write;
 
-use super::{CommandData, CommandParameter, Instruction, DEFS};
+use super::{CommandData, CommandParameter, Instruction, Register, Registers, DEFS};
+use crate::diagnostics::warning;
+use crate::doc::Doc;
+use crate::r#type::Type;
 
-fn stringify_parameter(parameter: &CommandParameter) -> String {
-    match parameter {
-        CommandParameter::Result(register)
-        | CommandParameter::DefaultEmptyResult(Some(register))
-        | CommandParameter::Register(register) => register.to_string(),
-        CommandParameter::DefaultEmptyResult(None) => String::new(),
-        CommandParameter::Variable(variable) => variable.to_string(),
-        CommandParameter::Registers(registers) => registers.to_string(false).1,
-        CommandParameter::Literal(literal) => literal.to_string(),
-        CommandParameter::Label(label) => label.clone(),
-        CommandParameter::Type(r#type) => r#type.to_string(),
-        CommandParameter::Field(field) => field.to_string(),
-        CommandParameter::Method(method) => method.to_string(),
-        CommandParameter::CallSite(call_site) => call_site.to_string(),
-        CommandParameter::Data(CommandData::Label(label)) => {
-            eprintln!("Warning: Writing out unresolved command data label {label}");
-            "??<label>??".to_string()
+/// Indentation of continuation lines when wrapping long argument lists.
+const CONTINUATION_INDENT: usize = 8;
+
+/// Rendered expressions of temporaries nested into the instructions using them, along with a
+/// flag indicating whether the expression can be used as an operand without parentheses.
+pub type NestedExpressions = HashMap<Register, (Doc, bool)>;
+
+fn register_doc(register: &Register, expressions: &NestedExpressions, bare: bool) -> Doc {
+    match expressions.get(register) {
+        Some((expression, atomic)) if *atomic || bare => expression.clone(),
+        Some((expression, _)) => {
+            Doc::Concat(vec![Doc::text("("), expression.clone(), Doc::text(")")])
         }
-        CommandParameter::Data(CommandData::PackedSwitch(first_key, targets)) => targets
+        None => Doc::text(register.to_string()),
+    }
+}
+
+/// Renders a register list, preceding arguments by the parameter names given for their
+/// positions in the list. The first register is returned separately if requested.
+fn registers_doc(
+    registers: &Registers,
+    split_first: bool,
+    expressions: &NestedExpressions,
+    names: &[Option<String>],
+) -> (Option<Doc>, Doc) {
+    let list = match registers {
+        Registers::List(list) => list.clone(),
+        Registers::Range(from, to) => match Registers::resolve_range(from, to) {
+            Some(list) => list,
+            None => {
+                let (first, rest) = registers.to_string(split_first);
+                return (first.map(Doc::text), Doc::text(rest));
+            }
+        },
+    };
+
+    let mut list = list.iter().enumerate();
+    let first = if split_first {
+        list.next()
+            .map(|(_, register)| register_doc(register, expressions, false))
+    } else {
+        None
+    };
+    let rest = list
+        .map(|(index, register)| {
+            let value = register_doc(register, expressions, true);
+            match names.get(index) {
+                Some(Some(name)) => Doc::Concat(vec![Doc::text(format!("/* {name}= */ ")), value]),
+                _ => value,
+            }
+        })
+        .collect::<Vec<_>>();
+    (first, Doc::list(rest, CONTINUATION_INDENT))
+}
+
+fn data_doc(data: &CommandData) -> Doc {
+    let lines = match data {
+        CommandData::Label(label) => {
+            warning!("Writing out unresolved command data label {label}");
+            return Doc::text("??<label>??");
+        }
+        CommandData::PackedSwitch(first_key, targets) => targets
             .iter()
             .enumerate()
             .map(|(index, target)| {
                 let key = first_key + (index as i64);
                 format!(
-                    "            case {}{:#x}: goto {target};\n",
+                    "    case {}{:#x}: goto {target};",
                     if key.is_negative() { "-" } else { "" },
                     key.abs_diff(0)
                 )
             })
             .collect(),
-        CommandParameter::Data(CommandData::SparseSwitch(targets)) => targets
+        CommandData::SparseSwitch(targets) => targets
             .iter()
-            .map(|(value, target)| format!("            case {value}: goto {target};\n"))
+            .map(|(value, target)| format!("    case {value}: goto {target};"))
             .collect(),
-        CommandParameter::Data(CommandData::Array(values)) => values
-            .iter()
-            .map(|value| format!("            {value},\n"))
+        CommandData::Array(_, values) => {
+            let mut lines = Vec::new();
+            // Character arrays often hide strings, show these in a readable form
+            let text = values
+                .iter()
+                .map(|value| {
+                    value
+                        .get_char()
+                        .and_then(|c| char::from_u32(c.into()))
+                        .filter(|c| *c == ' ' || c.is_ascii_graphic())
+                })
+                .collect::<Option<String>>();
+            if let Some(text) = text.filter(|text| text.len() > 1) {
+                lines.push(format!("    // {text:?}"));
+            }
+            for value in values {
+                lines.push(format!("    {value},"));
+            }
+            lines
+        }
+    };
+    Doc::join(lines.into_iter().map(Doc::text), &Doc::Line)
+}
+
+fn parameter_doc(
+    parameter: &CommandParameter,
+    expressions: &NestedExpressions,
+    names: &[Option<String>],
+    bare: bool,
+) -> Doc {
+    match parameter {
+        CommandParameter::Result(register)
+        | CommandParameter::DefaultEmptyResult(Some(register)) => Doc::text(register.to_string()),
+        CommandParameter::Register(register) => register_doc(register, expressions, bare),
+        CommandParameter::DefaultEmptyResult(None) => Doc::text(""),
+        CommandParameter::Variable(variable) => Doc::text(variable.to_string()),
+        CommandParameter::Registers(registers) => {
+            registers_doc(registers, false, expressions, names).1
+        }
+        CommandParameter::Literal(literal) => Doc::text(literal.to_string()),
+        CommandParameter::Label(label) => Doc::text(label.clone()),
+        CommandParameter::Type(r#type) => Doc::text(r#type.to_string()),
+        CommandParameter::Field(field) => Doc::text(field.to_string()),
+        CommandParameter::Method(method) => Doc::text(method.to_string()),
+        CommandParameter::CallSite(call_site) => Doc::text(call_site.to_string()),
+        CommandParameter::ArgumentNames(_)
+        | CommandParameter::Warning(_)
+        | CommandParameter::Comment(_)
+        | CommandParameter::Rewrite(_) => Doc::text(""),
+        CommandParameter::Data(data) => data_doc(data),
+    }
+}
+
+/// Splits an instruction format into text and placeholders given as parameter index and
+/// optional field, e.g. `{1}` or `{1.this}`. Braces not forming a placeholder are kept as text.
+fn split_format(format: &str) -> Vec<Result<(usize, Option<&str>), &str>> {
+    let mut result = Vec::new();
+    let mut text_start = 0;
+    let mut position = 0;
+    while let Some(start) = format[position..].find('{').map(|start| position + start) {
+        position = start + 1;
+        let Some(end) = format[position..].find('}').map(|end| position + end) else {
+            break;
+        };
+        let (index, field) = match format[position..end].split_once('.') {
+            Some((index, field)) => (index, Some(field)),
+            None => (&format[position..end], None),
+        };
+        if let Ok(index) = index.parse() {
+            if text_start < start {
+                result.push(Err(&format[text_start..start]));
+            }
+            result.push(Ok((index, field)));
+            position = end + 1;
+            text_start = position;
+        }
+    }
+    if text_start < format.len() {
+        result.push(Err(&format[text_start..]));
+    }
+    result
+}
+
+/// Checks whether an expression contains spaces outside of parentheses, meaning that it needs
+/// to be put in parentheses when used as an operand.
+fn has_top_level_space(expression: &str) -> bool {
+    let mut depth = 0usize;
+    expression.chars().any(|c| {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        c == ' ' && depth == 0
+    })
+}
+
+/// Renders the replacement expression of a rewritten call, filling in the arguments. Wide
+/// arguments occupy two registers but count as one argument.
+fn rewrite_doc(
+    command: &str,
+    replacement: &str,
+    parameters: &[CommandParameter],
+    expressions: &NestedExpressions,
+) -> Doc {
+    let registers = parameters
+        .iter()
+        .find_map(|parameter| match parameter {
+            CommandParameter::Registers(Registers::List(list)) => Some(list.clone()),
+            CommandParameter::Registers(Registers::Range(from, to)) => {
+                Registers::resolve_range(from, to)
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+    let arguments = match parameters.iter().find_map(|parameter| match parameter {
+        CommandParameter::Method(signature) => Some(signature),
+        _ => None,
+    }) {
+        Some(signature) => {
+            let mut registers = registers.into_iter();
+            let mut arguments = Vec::new();
+            if !command.starts_with("invoke-static") {
+                arguments.extend(registers.next());
+            }
+            for parameter_type in &signature.call_signature.parameter_types {
+                arguments.extend(registers.next());
+                for _ in 1..parameter_type.register_count() {
+                    registers.next();
+                }
+            }
+            arguments
+        }
+        None => registers,
+    };
+    Doc::Concat(
+        split_format(replacement)
+            .into_iter()
+            .map(|part| match part {
+                Ok((index, _)) => arguments.get(index).map_or_else(
+                    || Doc::text("???"),
+                    |register| register_doc(register, expressions, true),
+                ),
+                Err(text) => Doc::text(text),
+            })
             .collect(),
+    )
+}
+
+fn command_doc(
+    command: &str,
+    parameters: &[CommandParameter],
+    expressions: &NestedExpressions,
+) -> Result<Doc, std::io::Error> {
+    let defs = DEFS
+        .get(command)
+        .ok_or_else(|| std::io::Error::other("Attempt to write unknown command to Jimple"))?;
+
+    if let Some(replacement) = parameters.iter().find_map(|parameter| match parameter {
+        CommandParameter::Rewrite(replacement) => Some(replacement),
+        _ => None,
+    }) {
+        return Ok(rewrite_doc(command, replacement, parameters, expressions));
+    }
+
+    // Operands don't need parentheses if the command merely passes the value on
+    let bare = defs.format == "{1}"
+        || defs.format.starts_with("return ")
+        || defs.format.starts_with("throw ");
+
+    let names = parameters
+        .iter()
+        .find_map(|parameter| match parameter {
+            CommandParameter::ArgumentNames(names) => Some(names.as_slice()),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    let mut result = Vec::new();
+    for part in split_format(defs.format) {
+        let (index, field) = match part {
+            Ok(placeholder) => placeholder,
+            Err(text) => {
+                result.push(Doc::text(text));
+                continue;
+            }
+        };
+        let Some(parameter) = parameters.get(index) else {
+            continue;
+        };
+        match (parameter, field) {
+            (CommandParameter::Registers(registers), Some(field)) => {
+                let (this, args) = registers_doc(registers, true, expressions, names);
+                if field == "this" {
+                    // Only the argument list of the outermost call is wrapped
+                    result.push(this.map_or_else(|| Doc::text("???"), Doc::flatten));
+                } else {
+                    result.push(args);
+                }
+            }
+            _ => result.push(parameter_doc(parameter, expressions, names, bare)),
+        }
+    }
+    Ok(Doc::Concat(result))
+}
+
+/// Renders commands as a statement, separated by semicolons. `prefix` and `suffix` enclose them
+/// instead of the usual final semicolon.
+fn statement_doc(
+    commands: &[(&str, &[CommandParameter], Option<&Type>)],
+    expressions: &NestedExpressions,
+    (prefix, suffix): (&str, &str),
+) -> Result<Doc, std::io::Error> {
+    let mut result = vec![Doc::text(prefix)];
+    let mut comments = Vec::new();
+    for (index, (command, parameters, local_type)) in commands.iter().enumerate() {
+        if index > 0 {
+            result.push(Doc::text("; "));
+        }
+        if let Some(CommandParameter::Result(result_register))
+        | Some(CommandParameter::DefaultEmptyResult(Some(result_register))) = parameters.first()
+        {
+            if let Some(local_type) = local_type {
+                result.push(Doc::text(format!("{local_type} ")));
+            }
+            result.push(Doc::text(format!("{result_register} = ")));
+        }
+        result.push(command_doc(command, parameters, expressions)?);
+        comments.extend(parameters.iter().filter_map(|parameter| match parameter {
+            CommandParameter::Warning(warning) => Some(format!("warning: {warning}")),
+            CommandParameter::Comment(comment) => Some(comment.clone()),
+            _ => None,
+        }));
+    }
+    result.push(Doc::text(suffix));
+    if !comments.is_empty() {
+        result.push(Doc::text(format!(" // {}", comments.join("; "))));
     }
+    Ok(Doc::Concat(vec![
+        Doc::text("        "),
+        Doc::nest(8, Doc::Concat(result)),
+    ]))
 }
 
 impl Instruction {
+    /// Renders the value computed by a command, so that it can be nested into the expression
+    /// using it. The flag returned indicates whether the expression is atomic.
+    pub fn get_jimple_expression(
+        &self,
+        expressions: &NestedExpressions,
+    ) -> Result<(Doc, bool), std::io::Error> {
+        if let Self::Command {
+            command,
+            parameters,
+        } = self
+        {
+            let expression = command_doc(command, parameters, expressions)?;
+            let rewrite = parameters.iter().find_map(|parameter| match parameter {
+                CommandParameter::Rewrite(replacement) => Some(replacement),
+                _ => None,
+            });
+            let atomic = match rewrite {
+                Some(replacement) => !has_top_level_space(replacement),
+                None => DEFS
+                    .get(command)
+                    .map(|defs| !defs.format.contains(' ') || command.starts_with("invoke-"))
+                    .unwrap_or(false),
+            };
+            Ok((expression, atomic))
+        } else {
+            Err(std::io::Error::other(
+                "Attempt to nest an instruction that isn't a command",
+            ))
+        }
+    }
+
     pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
-        match self {
+        if let Some(doc) = self.get_jimple_doc(&NestedExpressions::new(), None)? {
+            writeln!(output, "{}", doc.render(0, None))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the header of a `try` block closing a resource, with this command initializing the
+    /// resource moved into it, followed by the constructor call if the command merely allocates
+    /// the resource. If a type is given, the resource is declared with this type. Returns `None`
+    /// if the instructions aren't commands.
+    pub fn get_resource_header_doc(
+        &self,
+        constructor: Option<&Self>,
+        expressions: &NestedExpressions,
+        local_type: Option<&Type>,
+    ) -> Result<Option<Doc>, std::io::Error> {
+        let Self::Command {
+            command,
+            parameters,
+        } = self
+        else {
+            return Ok(None);
+        };
+        let mut commands = vec![(command.as_str(), parameters.as_slice(), local_type)];
+        match constructor {
+            Some(Self::Command {
+                command,
+                parameters,
+            }) => commands.push((command, parameters, None)),
+            Some(_) => return Ok(None),
+            None => (),
+        }
+        let header = statement_doc(&commands, expressions, ("try (", ")"))?;
+        Ok(Some(Doc::Concat(vec![header, Doc::text("\n        {")])))
+    }
+
+    /// Builds the document for the statement, with nested expressions filled in. If a type is
+    /// given, a command assigning a register declares the register with this type. Returns
+    /// `None` for instructions without a Jimple representation.
+    pub fn get_jimple_doc(
+        &self,
+        expressions: &NestedExpressions,
+        local_type: Option<&Type>,
+    ) -> Result<Option<Doc>, std::io::Error> {
+        let text = match self {
             Self::LineNumber(from, to) => {
                 if from == to {
-                    writeln!(output, "        // line {from}")
+                    format!("        // line {from}")
                 } else {
-                    writeln!(output, "        // line {from}-{to}")
+                    format!("        // line {from}-{to}")
                 }
             }
-            Self::Label(label) => writeln!(output, "    {label}:"),
+            Self::Label(label) => format!("    {label}:"),
+            Self::Comment(comment) | Self::TrailingComment(comment) => {
+                format!("        // {comment}")
+            }
+            Self::Raw(line) => format!("        // unparsed: {line}"),
             Self::Command {
                 command,
                 parameters,
             } => {
-                let defs = DEFS.get(command).ok_or_else(|| {
-                    std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        "Attempt to write unknown command to Jimple",
-                    )
-                })?;
-
-                write!(output, "        ")?;
-                if let Some(CommandParameter::Result(result))
-                | Some(CommandParameter::DefaultEmptyResult(Some(result))) = parameters.get(0)
-                {
-                    write!(output, "{} = ", result)?;
-                }
-
-                let mut result = defs.format.to_string();
-                for (index, parameter) in parameters.iter().enumerate() {
-                    let placeholder = format!("{{{index}}}");
-                    if result.contains(&placeholder) {
-                        result = result.replace(&placeholder, &stringify_parameter(parameter));
-                    }
-
-                    if let CommandParameter::Registers(registers) = parameter {
-                        let placeholder1 = format!("{{{index}.this}}");
-                        let placeholder2 = format!("{{{index}.args}}");
-                        if result.contains(&placeholder1) || result.contains(&placeholder2) {
-                            let (this, args) = registers.to_string(true);
-                            let this = this.unwrap_or_else(|| "???".to_string());
-                            result = result.replace(&placeholder1, &this);
-                            result = result.replace(&placeholder2, &args);
-                        }
-                    }
+                return statement_doc(&[(command, parameters, local_type)], expressions, ("", ";"))
+                    .map(Some);
+            }
+            Self::AssertNotNull { register, message } => {
+                let mut result = vec![
+                    Doc::text("        assert "),
+                    register_doc(register, expressions, false),
+                    Doc::text(" != null"),
+                ];
+                if let Some(message) = message {
+                    result.push(Doc::text(format!(" : {message}")));
                 }
-                writeln!(output, "{};", result)
+                result.push(Doc::text(";"));
+                return Ok(Some(Doc::Concat(result)));
             }
+            Self::Try => "        try\n        {".to_string(),
+            Self::TryWithResources(resource) => format!("        try ({resource})\n        {{"),
+            Self::Finally => "        finally\n        {".to_string(),
+            Self::BlockEnd => "        }".to_string(),
             Self::Catch {
                 exception,
                 start_label,
                 end_label,
                 target,
-            } => writeln!(
-                output,
+            } => format!(
                 "        catch {} from {start_label} to {end_label} with {target};",
                 exception
                     .as_ref()
                     .map(|t| format!("{}", t))
                     .unwrap_or_else(|| "java.lang.Throwable".to_string())
             ),
-            _ => Ok(()),
-        }
+            _ => return Ok(None),
+        };
+        Ok(Some(Doc::text(text)))
     }
 }
 
@@ -1,8 +1,14 @@
-use itertools::Itertools;
 use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::sync::OnceLock;
 
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::diagnostics::warning;
 use crate::literal::Literal;
 use crate::r#type::{CallSite, FieldSignature, MethodSignature, Type};
+use crate::schema::{write_json_pretty, JsonOutput, SchemaVersion};
 
 mod jimple;
 mod optimization;
@@ -10,7 +16,10 @@ mod parameters_smali;
 mod registers_smali;
 mod smali;
 
-#[derive(Debug, Clone, PartialEq)]
+pub use jimple::NestedExpressions;
+
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum ParameterKind {
     Result,
     DefaultEmptyResult,
@@ -50,6 +59,8 @@ pub enum ResultTypeDef {
 
 #[derive(Debug, Clone, PartialEq)]
 struct InstructionDef {
+    /// Opcode in dex files, `None` for commands without one like `.line`
+    opcode: Option<u8>,
     parameters: &'static [ParameterKind],
     format: &'static str,
     is_moved_result: bool,
@@ -59,6 +70,7 @@ struct InstructionDef {
 impl InstructionDef {
     const fn default() -> Self {
         Self {
+            opcode: None,
             parameters: &[],
             format: "",
             is_moved_result: false,
@@ -70,7 +82,7 @@ impl InstructionDef {
 macro_rules! instructions {
     (
         $(
-            $command:literal => [$($kind:ident)*]
+            $command:literal $(($opcode:literal))? => [$($kind:ident)*]
                 $format:literal
                 $($field:ident = $value:expr)*,
         )*
@@ -78,6 +90,7 @@ macro_rules! instructions {
         phf::phf_map! {
             $(
                 $command => InstructionDef {
+                    $(opcode: Some($opcode),)?
                     parameters: &[$(
                         ParameterKind::$kind,
                     )*],
@@ -92,236 +105,465 @@ macro_rules! instructions {
 
 #[allow(clippy::needless_update)]
 const DEFS: phf::Map<&str, InstructionDef> = instructions!(
-    "nop" => [] "nop",
-    "move" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move/from16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move/16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-wide" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-wide/from16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-wide/16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-object" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-object/from16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-object/16" => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
-    "move-result" => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Int,
-    "move-result-wide" => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Long,
-    "move-result-object" => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Object("java.lang.Object"),
-    "move-exception" => [Result] "move-exception" result_type=ResultTypeDef::Exception,
-    "return-void" => [] "return",
-    "return" => [Register] "return {0}",
-    "return-wide" => [Register] "return {0}",
-    "return-object" => [Register] "return {0}",
-    "const/4" => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
-    "const/16" => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
-    "const" => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
-    "const/high16" => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
-    "const-wide/16" => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
-    "const-wide/32" => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
-    "const-wide" => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
-    "const-wide/high16" => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
-    "const-string" => [Result String] "{1}" result_type=ResultTypeDef::From(1),
-    "const-string/jumbo" => [Result String] "{1}" result_type=ResultTypeDef::From(1),
-    "const-class" => [Result Class] "{1}" result_type=ResultTypeDef::From(1),
-    "monitor-enter" => [Register] "monitor-enter {0}",
-    "monitor-exit" => [Register] "monitor-exit {0}",
-    "check-cast" => [DefaultEmptyResult Register Type] "({2}) {1}" result_type=ResultTypeDef::From(2),
-    "instance-of" => [Result Register Type] "{1} instance-of {2}" result_type=ResultTypeDef::From(2),
-    "array-length" => [Result Register] "array-length {1}" result_type=ResultTypeDef::Int,
-    "new-instance" => [Result Type] "new {1}" result_type=ResultTypeDef::From(1),
-    "new-array" => [Result Register Type] "new {2}[{1}]" result_type=ResultTypeDef::From(2),
-    "filled-new-array" => [DefaultEmptyResult Registers Type] "{{1}}" result_type=ResultTypeDef::From(2),
-    "filled-new-array/range" => [DefaultEmptyResult Registers Type] "{{1}}" result_type=ResultTypeDef::From(2),
-    "fill-array-data" => [Register Data] "{0} = {\n{1}        }",
-    "throw" => [Register] "throw {0}",
-    "goto" => [Label] "goto {0}",
-    "goto/16" => [Label] "goto {0}",
-    "goto/32" => [Label] "goto {0}",
-    "packed-switch" => [Register Data] "switch({0})\n        {\n{1}        }",
-    "sparse-switch" => [Register Data] "switch({0})\n        {\n{1}        }",
-    "cmpl-float" => [Result Register Register] "{1} cmpl {2}" result_type=ResultTypeDef::Bool,
-    "cmpg-float" => [Result Register Register] "{1} cmpg {2}" result_type=ResultTypeDef::Bool,
-    "cmpl-double" => [Result Register Register] "{1} cmpl {2}" result_type=ResultTypeDef::Bool,
-    "cmpg-double" => [Result Register Register] "{1} cmpg {2}" result_type=ResultTypeDef::Bool,
-    "cmp-long" => [Result Register Register] "{1} cmp {2}" result_type=ResultTypeDef::Bool,
-    "if-eq" => [Register Register Label] "if ({0} == {1}) goto {2}",
-    "if-ne" => [Register Register Label] "if ({0} != {1}) goto {2}",
-    "if-lt" => [Register Register Label]  "if ({0} < {1}) goto {2}",
-    "if-ge" => [Register Register Label] "if ({0} >= {1}) goto {2}",
-    "if-gt" => [Register Register Label]  "if ({0} > {1}) goto {2}",
-    "if-le" => [Register Register Label]  "if ({0} <= {1}) goto {2}",
-    "if-eqz" => [Register Label] "if ({0} == 0) goto {1}",
-    "if-nez" => [Register Label] "if ({0} != 0) goto {1}",
-    "if-ltz" => [Register Label] "if ({0} < 0) goto {1}",
-    "if-gez" => [Register Label] "if ({0} >= 0) goto {1}",
-    "if-gtz" => [Register Label] "if ({0} > 0) goto {1}",
-    "if-lez" => [Register Label] "if ({0} <= 0) goto {1}",
-    "aget" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-wide" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-object" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-boolean" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-byte" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-char" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aget-short" => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
-    "aput" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-wide" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-object" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-boolean" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-byte" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-char" => [Register Register Register] "{1}[{2}] = {0}",
-    "aput-short" => [Register Register Register] "{1}[{2}] = {0}",
-    "iget" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-wide" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-object" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-boolean" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-byte" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-char" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iget-short" => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
-    "iput" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-wide" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-object" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-boolean" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-byte" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-char" => [Register Register Field] "{1}.<{2}> = {0}",
-    "iput-short" => [Register Register Field] "{1}.<{2}> = {0}",
-    "sget" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-wide" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-object" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-boolean" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-byte" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-char" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sget-short" => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
-    "sput" => [Register Field] "<{1}> = {0}",
-    "sput-wide" => [Register Field] "<{1}> = {0}",
-    "sput-object" => [Register Field] "<{1}> = {0}",
-    "sput-boolean" => [Register Field] "<{1}> = {0}",
-    "sput-byte" => [Register Field] "<{1}> = {0}",
-    "sput-char" => [Register Field] "<{1}> = {0}",
-    "sput-short" => [Register Field] "<{1}> = {0}",
-    "invoke-virtual" => [DefaultEmptyResult Registers Method] "invoke-virtual {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-super" => [DefaultEmptyResult Registers Method] "invoke-super {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-direct" => [DefaultEmptyResult Registers Method] "invoke-direct {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-static" => [DefaultEmptyResult Registers Method] "invoke-static <{2}>({1})" result_type=ResultTypeDef::From(2),
-    "invoke-interface" => [DefaultEmptyResult Registers Method] "invoke-interface {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-virtual/range" => [DefaultEmptyResult Registers Method] "invoke-virtual {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-super/range" => [DefaultEmptyResult Registers Method] "invoke-super {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-direct/range" => [DefaultEmptyResult Registers Method] "invoke-direct {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "invoke-static/range" => [DefaultEmptyResult Registers Method] "invoke-static <{2}>({1})" result_type=ResultTypeDef::From(2),
-    "invoke-interface/range" => [DefaultEmptyResult Registers Method] "invoke-interface {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
-    "neg-int" => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
-    "not-int" => [Result Register] "~{1}" result_type=ResultTypeDef::From(1),
-    "neg-long" => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
-    "not-long" => [Result Register] "~{1}" result_type=ResultTypeDef::From(1),
-    "neg-float" => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
-    "neg-double" => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
-    "int-to-long" => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
-    "int-to-float" => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
-    "int-to-double" => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
-    "long-to-int" => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
-    "long-to-float" => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
-    "long-to-double" => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
-    "float-to-int" => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
-    "float-to-long" => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
-    "float-to-double" => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
-    "double-to-int" => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
-    "double-to-long" => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
-    "double-to-float" => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
-    "int-to-byte" => [Result Register] "(byte) {1}" result_type=ResultTypeDef::Byte,
-    "int-to-char" => [Result Register] "(char) {1}" result_type=ResultTypeDef::Char,
-    "int-to-short" => [Result Register] "(short) {1}" result_type=ResultTypeDef::Short,
-    "add-int" => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "sub-int" => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
-    "mul-int" => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-int" => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-int" => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "and-int" => [Result Register Register] "{1} & {2}" result_type=ResultTypeDef::From(1),
-    "or-int" => [Result Register Register] "{1} | {2}" result_type=ResultTypeDef::From(1),
-    "xor-int" => [Result Register Register] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
-    "shl-int" => [Result Register Register] "{1} << {2}" result_type=ResultTypeDef::From(1),
-    "shr-int" => [Result Register Register] "{1} >> {2}" result_type=ResultTypeDef::From(1),
-    "ushr-int" => [Result Register Register] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
-    "add-long" => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "sub-long" => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
-    "mul-long" => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-long" => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-long" => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "and-long" => [Result Register Register] "{1} & {2}" result_type=ResultTypeDef::From(1),
-    "or-long" => [Result Register Register] "{1} | {2}" result_type=ResultTypeDef::From(1),
-    "xor-long" => [Result Register Register] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
-    "shl-long" => [Result Register Register] "{1} << {2}" result_type=ResultTypeDef::From(1),
-    "shr-long" => [Result Register Register] "{1} >> {2}" result_type=ResultTypeDef::From(1),
-    "ushr-long" => [Result Register Register] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
-    "add-float" => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "sub-float" => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
-    "mul-float" => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-float" => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-float" => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "add-double" => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "sub-double" => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
-    "mul-double" => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-double" => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-double" => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "add-int/2addr" => [Register Register] "{0} += {1}",
-    "sub-int/2addr" => [Register Register] "{0} -= {1}",
-    "mul-int/2addr" => [Register Register] "{0} *= {1}",
-    "div-int/2addr" => [Register Register] "{0} /= {1}",
-    "rem-int/2addr" => [Register Register] "{0} %= {1}",
-    "and-int/2addr" => [Register Register] "{0} &= {1}",
-    "or-int/2addr" => [Register Register] "{0} |= {1}",
-    "xor-int/2addr" => [Register Register] "{0} ^= {1}",
-    "shl-int/2addr" => [Register Register] "{0} <<= {1}",
-    "shr-int/2addr" => [Register Register] "{0} >>= {1}",
-    "ushr-int/2addr" => [Register Register] "{0} >>>= {1}",
-    "add-long/2addr" => [Register Register] "{0} += {1}",
-    "sub-long/2addr" => [Register Register] "{0} -= {1}",
-    "mul-long/2addr" => [Register Register] "{0} *= {1}",
-    "div-long/2addr" => [Register Register] "{0} /= {1}",
-    "rem-long/2addr" => [Register Register] "{0} %= {1}",
-    "and-long/2addr" => [Register Register] "{0} &= {1}",
-    "or-long/2addr" => [Register Register] "{0} |= {1}",
-    "xor-long/2addr" => [Register Register] "{0} ^= {1}",
-    "shl-long/2addr" => [Register Register] "{0} <<= {1}",
-    "shr-long/2addr" => [Register Register] "{0} >>= {1}",
-    "ushr-long/2addr" => [Register Register] "{0} >>>= {1}",
-    "add-float/2addr" => [Register Register] "{0} += {1}",
-    "sub-float/2addr" => [Register Register] "{0} -= {1}",
-    "mul-float/2addr" => [Register Register] "{0} *= {1}",
-    "div-float/2addr" => [Register Register] "{0} /= {1}",
-    "rem-float/2addr" => [Register Register] "{0} %= {1}",
-    "add-double/2addr" => [Register Register] "{0} += {1}",
-    "sub-double/2addr" => [Register Register] "{0} -= {1}",
-    "mul-double/2addr" => [Register Register] "{0} *= {1}",
-    "div-double/2addr" => [Register Register] "{0} /= {1}",
-    "rem-double/2addr" => [Register Register] "{0} %= {1}",
-    "add-int/lit16" => [Result Register Int] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "rsub-int" => [Result Register Int] "{2} - {1}" result_type=ResultTypeDef::From(1),
-    "mul-int/lit16" => [Result Register Int] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-int/lit16" => [Result Register Int] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-int/lit16" => [Result Register Int] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "and-int/lit16" => [Result Register Int] "{1} & {2}" result_type=ResultTypeDef::From(1),
-    "or-int/lit16" => [Result Register Int] "{1} | {2}" result_type=ResultTypeDef::From(1),
-    "xor-int/lit16" => [Result Register Int] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
-    "add-int/lit8" => [Result Register Int] "{1} + {2}" result_type=ResultTypeDef::From(1),
-    "rsub-int/lit8" => [Result Register Int] "{2} - {1}" result_type=ResultTypeDef::From(1),
-    "mul-int/lit8" => [Result Register Int] "{1} * {2}" result_type=ResultTypeDef::From(1),
-    "div-int/lit8" => [Result Register Int] "{1} / {2}" result_type=ResultTypeDef::From(1),
-    "rem-int/lit8" => [Result Register Int] "{1} % {2}" result_type=ResultTypeDef::From(1),
-    "and-int/lit8" => [Result Register Int] "{1} & {2}" result_type=ResultTypeDef::From(1),
-    "or-int/lit8" => [Result Register Int] "{1} | {2}" result_type=ResultTypeDef::From(1),
-    "xor-int/lit8" => [Result Register Int] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
-    "shl-int/lit8" => [Result Register Int] "{1} << {2}" result_type=ResultTypeDef::From(1),
-    "shr-int/lit8" => [Result Register Int] "{1} >> {2}" result_type=ResultTypeDef::From(1),
-    "ushr-int/lit8" => [Result Register Int] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
-    "invoke-polymorphic" => [DefaultEmptyResult Registers Method MethodType] "invoke-polymorphic {1.this}.<{2}>({1.args}), <{3}>" result_type=ResultTypeDef::ReturnOf(3),
-    "invoke-polymorphic/range" => [DefaultEmptyResult Registers Method MethodType] "invoke-polymorphic {1.this}.<{2}>({1.args}), <{3}>" result_type=ResultTypeDef::ReturnOf(3),
-    "invoke-custom" => [DefaultEmptyResult Registers CallSite] "invoke-custom {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::Object("java.lang.Object"),
-    "invoke-custom/range" => [DefaultEmptyResult Registers CallSite] "invoke-custom {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::Object("java.lang.Object"),
-    "const-method-handle" => [Result MethodHandle] "{1}" result_type=ResultTypeDef::From(1),
-    "const-method-type" => [Result MethodType] "{1}" result_type=ResultTypeDef::From(1),
+    "nop" (0x00) => [] "nop",
+    "move" (0x01) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move/from16" (0x02) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move/16" (0x03) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-wide" (0x04) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-wide/from16" (0x05) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-wide/16" (0x06) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-object" (0x07) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-object/from16" (0x08) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-object/16" (0x09) => [Result Register] "{1}" result_type=ResultTypeDef::From(1),
+    "move-result" (0x0a) => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Int,
+    "move-result-wide" (0x0b) => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Long,
+    "move-result-object" (0x0c) => [Result] "move-result" is_moved_result=true result_type=ResultTypeDef::Object("java.lang.Object"),
+    "move-exception" (0x0d) => [Result] "move-exception" result_type=ResultTypeDef::Exception,
+    "return-void" (0x0e) => [] "return",
+    "return" (0x0f) => [Register] "return {0}",
+    "return-wide" (0x10) => [Register] "return {0}",
+    "return-object" (0x11) => [Register] "return {0}",
+    "const/4" (0x12) => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
+    "const/16" (0x13) => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
+    "const" (0x14) => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
+    "const/high16" (0x15) => [Result Int] "{1}" result_type=ResultTypeDef::From(1),
+    "const-wide/16" (0x16) => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
+    "const-wide/32" (0x17) => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
+    "const-wide" (0x18) => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
+    "const-wide/high16" (0x19) => [Result Long] "{1}" result_type=ResultTypeDef::From(1),
+    "const-string" (0x1a) => [Result String] "{1}" result_type=ResultTypeDef::From(1),
+    "const-string/jumbo" (0x1b) => [Result String] "{1}" result_type=ResultTypeDef::From(1),
+    "const-class" (0x1c) => [Result Class] "{1}" result_type=ResultTypeDef::From(1),
+    "monitor-enter" (0x1d) => [Register] "monitor-enter {0}",
+    "monitor-exit" (0x1e) => [Register] "monitor-exit {0}",
+    "check-cast" (0x1f) => [DefaultEmptyResult Register Type] "({2}) {1}" result_type=ResultTypeDef::From(2),
+    "instance-of" (0x20) => [Result Register Type] "{1} instance-of {2}" result_type=ResultTypeDef::From(2),
+    "array-length" (0x21) => [Result Register] "array-length {1}" result_type=ResultTypeDef::Int,
+    "new-instance" (0x22) => [Result Type] "new {1}" result_type=ResultTypeDef::From(1),
+    "new-array" (0x23) => [Result Register Type] "new {2}[{1}]" result_type=ResultTypeDef::From(2),
+    "filled-new-array" (0x24) => [DefaultEmptyResult Registers Type] "{{1}}" result_type=ResultTypeDef::From(2),
+    "filled-new-array/range" (0x25) => [DefaultEmptyResult Registers Type] "{{1}}" result_type=ResultTypeDef::From(2),
+    "fill-array-data" (0x26) => [Register Data] "{0} = {\n{1}\n}",
+    "throw" (0x27) => [Register] "throw {0}",
+    "goto" (0x28) => [Label] "goto {0}",
+    "goto/16" (0x29) => [Label] "goto {0}",
+    "goto/32" (0x2a) => [Label] "goto {0}",
+    "packed-switch" (0x2b) => [Register Data] "switch({0})\n{\n{1}\n}",
+    "sparse-switch" (0x2c) => [Register Data] "switch({0})\n{\n{1}\n}",
+    "cmpl-float" (0x2d) => [Result Register Register] "{1} cmpl {2}" result_type=ResultTypeDef::Bool,
+    "cmpg-float" (0x2e) => [Result Register Register] "{1} cmpg {2}" result_type=ResultTypeDef::Bool,
+    "cmpl-double" (0x2f) => [Result Register Register] "{1} cmpl {2}" result_type=ResultTypeDef::Bool,
+    "cmpg-double" (0x30) => [Result Register Register] "{1} cmpg {2}" result_type=ResultTypeDef::Bool,
+    "cmp-long" (0x31) => [Result Register Register] "{1} cmp {2}" result_type=ResultTypeDef::Bool,
+    "if-eq" (0x32) => [Register Register Label] "if ({0} == {1}) goto {2}",
+    "if-ne" (0x33) => [Register Register Label] "if ({0} != {1}) goto {2}",
+    "if-lt" (0x34) => [Register Register Label]  "if ({0} < {1}) goto {2}",
+    "if-ge" (0x35) => [Register Register Label] "if ({0} >= {1}) goto {2}",
+    "if-gt" (0x36) => [Register Register Label]  "if ({0} > {1}) goto {2}",
+    "if-le" (0x37) => [Register Register Label]  "if ({0} <= {1}) goto {2}",
+    "if-eqz" (0x38) => [Register Label] "if ({0} == 0) goto {1}",
+    "if-nez" (0x39) => [Register Label] "if ({0} != 0) goto {1}",
+    "if-ltz" (0x3a) => [Register Label] "if ({0} < 0) goto {1}",
+    "if-gez" (0x3b) => [Register Label] "if ({0} >= 0) goto {1}",
+    "if-gtz" (0x3c) => [Register Label] "if ({0} > 0) goto {1}",
+    "if-lez" (0x3d) => [Register Label] "if ({0} <= 0) goto {1}",
+    "aget" (0x44) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-wide" (0x45) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-object" (0x46) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-boolean" (0x47) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-byte" (0x48) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-char" (0x49) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aget-short" (0x4a) => [Result Register Register] "{1}[{2}]" result_type=ResultTypeDef::ElementFrom(1),
+    "aput" (0x4b) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-wide" (0x4c) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-object" (0x4d) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-boolean" (0x4e) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-byte" (0x4f) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-char" (0x50) => [Register Register Register] "{1}[{2}] = {0}",
+    "aput-short" (0x51) => [Register Register Register] "{1}[{2}] = {0}",
+    "iget" (0x52) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-wide" (0x53) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-object" (0x54) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-boolean" (0x55) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-byte" (0x56) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-char" (0x57) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iget-short" (0x58) => [Result Register Field] "{1}.<{2}>" result_type=ResultTypeDef::From(2),
+    "iput" (0x59) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-wide" (0x5a) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-object" (0x5b) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-boolean" (0x5c) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-byte" (0x5d) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-char" (0x5e) => [Register Register Field] "{1}.<{2}> = {0}",
+    "iput-short" (0x5f) => [Register Register Field] "{1}.<{2}> = {0}",
+    "sget" (0x60) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-wide" (0x61) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-object" (0x62) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-boolean" (0x63) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-byte" (0x64) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-char" (0x65) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sget-short" (0x66) => [Result Field] "<{1}>" result_type=ResultTypeDef::From(1),
+    "sput" (0x67) => [Register Field] "<{1}> = {0}",
+    "sput-wide" (0x68) => [Register Field] "<{1}> = {0}",
+    "sput-object" (0x69) => [Register Field] "<{1}> = {0}",
+    "sput-boolean" (0x6a) => [Register Field] "<{1}> = {0}",
+    "sput-byte" (0x6b) => [Register Field] "<{1}> = {0}",
+    "sput-char" (0x6c) => [Register Field] "<{1}> = {0}",
+    "sput-short" (0x6d) => [Register Field] "<{1}> = {0}",
+    "invoke-virtual" (0x6e) => [DefaultEmptyResult Registers Method] "invoke-virtual {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-super" (0x6f) => [DefaultEmptyResult Registers Method] "invoke-super {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-direct" (0x70) => [DefaultEmptyResult Registers Method] "invoke-direct {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-static" (0x71) => [DefaultEmptyResult Registers Method] "invoke-static <{2}>({1})" result_type=ResultTypeDef::From(2),
+    "invoke-interface" (0x72) => [DefaultEmptyResult Registers Method] "invoke-interface {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-virtual/range" (0x74) => [DefaultEmptyResult Registers Method] "invoke-virtual {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-super/range" (0x75) => [DefaultEmptyResult Registers Method] "invoke-super {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-direct/range" (0x76) => [DefaultEmptyResult Registers Method] "invoke-direct {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "invoke-static/range" (0x77) => [DefaultEmptyResult Registers Method] "invoke-static <{2}>({1})" result_type=ResultTypeDef::From(2),
+    "invoke-interface/range" (0x78) => [DefaultEmptyResult Registers Method] "invoke-interface {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::From(2),
+    "neg-int" (0x7b) => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
+    "not-int" (0x7c) => [Result Register] "~{1}" result_type=ResultTypeDef::From(1),
+    "neg-long" (0x7d) => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
+    "not-long" (0x7e) => [Result Register] "~{1}" result_type=ResultTypeDef::From(1),
+    "neg-float" (0x7f) => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
+    "neg-double" (0x80) => [Result Register] "-{1}" result_type=ResultTypeDef::From(1),
+    "int-to-long" (0x81) => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
+    "int-to-float" (0x82) => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
+    "int-to-double" (0x83) => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
+    "long-to-int" (0x84) => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
+    "long-to-float" (0x85) => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
+    "long-to-double" (0x86) => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
+    "float-to-int" (0x87) => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
+    "float-to-long" (0x88) => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
+    "float-to-double" (0x89) => [Result Register] "(double) {1}" result_type=ResultTypeDef::Double,
+    "double-to-int" (0x8a) => [Result Register] "(int) {1}" result_type=ResultTypeDef::Int,
+    "double-to-long" (0x8b) => [Result Register] "(long) {1}" result_type=ResultTypeDef::Long,
+    "double-to-float" (0x8c) => [Result Register] "(float) {1}" result_type=ResultTypeDef::Float,
+    "int-to-byte" (0x8d) => [Result Register] "(byte) {1}" result_type=ResultTypeDef::Byte,
+    "int-to-char" (0x8e) => [Result Register] "(char) {1}" result_type=ResultTypeDef::Char,
+    "int-to-short" (0x8f) => [Result Register] "(short) {1}" result_type=ResultTypeDef::Short,
+    "add-int" (0x90) => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "sub-int" (0x91) => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
+    "mul-int" (0x92) => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-int" (0x93) => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-int" (0x94) => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "and-int" (0x95) => [Result Register Register] "{1} & {2}" result_type=ResultTypeDef::From(1),
+    "or-int" (0x96) => [Result Register Register] "{1} | {2}" result_type=ResultTypeDef::From(1),
+    "xor-int" (0x97) => [Result Register Register] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
+    "shl-int" (0x98) => [Result Register Register] "{1} << {2}" result_type=ResultTypeDef::From(1),
+    "shr-int" (0x99) => [Result Register Register] "{1} >> {2}" result_type=ResultTypeDef::From(1),
+    "ushr-int" (0x9a) => [Result Register Register] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
+    "add-long" (0x9b) => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "sub-long" (0x9c) => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
+    "mul-long" (0x9d) => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-long" (0x9e) => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-long" (0x9f) => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "and-long" (0xa0) => [Result Register Register] "{1} & {2}" result_type=ResultTypeDef::From(1),
+    "or-long" (0xa1) => [Result Register Register] "{1} | {2}" result_type=ResultTypeDef::From(1),
+    "xor-long" (0xa2) => [Result Register Register] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
+    "shl-long" (0xa3) => [Result Register Register] "{1} << {2}" result_type=ResultTypeDef::From(1),
+    "shr-long" (0xa4) => [Result Register Register] "{1} >> {2}" result_type=ResultTypeDef::From(1),
+    "ushr-long" (0xa5) => [Result Register Register] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
+    "add-float" (0xa6) => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "sub-float" (0xa7) => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
+    "mul-float" (0xa8) => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-float" (0xa9) => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-float" (0xaa) => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "add-double" (0xab) => [Result Register Register] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "sub-double" (0xac) => [Result Register Register] "{1} - {2}" result_type=ResultTypeDef::From(1),
+    "mul-double" (0xad) => [Result Register Register] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-double" (0xae) => [Result Register Register] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-double" (0xaf) => [Result Register Register] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "add-int/2addr" (0xb0) => [Register Register] "{0} += {1}",
+    "sub-int/2addr" (0xb1) => [Register Register] "{0} -= {1}",
+    "mul-int/2addr" (0xb2) => [Register Register] "{0} *= {1}",
+    "div-int/2addr" (0xb3) => [Register Register] "{0} /= {1}",
+    "rem-int/2addr" (0xb4) => [Register Register] "{0} %= {1}",
+    "and-int/2addr" (0xb5) => [Register Register] "{0} &= {1}",
+    "or-int/2addr" (0xb6) => [Register Register] "{0} |= {1}",
+    "xor-int/2addr" (0xb7) => [Register Register] "{0} ^= {1}",
+    "shl-int/2addr" (0xb8) => [Register Register] "{0} <<= {1}",
+    "shr-int/2addr" (0xb9) => [Register Register] "{0} >>= {1}",
+    "ushr-int/2addr" (0xba) => [Register Register] "{0} >>>= {1}",
+    "add-long/2addr" (0xbb) => [Register Register] "{0} += {1}",
+    "sub-long/2addr" (0xbc) => [Register Register] "{0} -= {1}",
+    "mul-long/2addr" (0xbd) => [Register Register] "{0} *= {1}",
+    "div-long/2addr" (0xbe) => [Register Register] "{0} /= {1}",
+    "rem-long/2addr" (0xbf) => [Register Register] "{0} %= {1}",
+    "and-long/2addr" (0xc0) => [Register Register] "{0} &= {1}",
+    "or-long/2addr" (0xc1) => [Register Register] "{0} |= {1}",
+    "xor-long/2addr" (0xc2) => [Register Register] "{0} ^= {1}",
+    "shl-long/2addr" (0xc3) => [Register Register] "{0} <<= {1}",
+    "shr-long/2addr" (0xc4) => [Register Register] "{0} >>= {1}",
+    "ushr-long/2addr" (0xc5) => [Register Register] "{0} >>>= {1}",
+    "add-float/2addr" (0xc6) => [Register Register] "{0} += {1}",
+    "sub-float/2addr" (0xc7) => [Register Register] "{0} -= {1}",
+    "mul-float/2addr" (0xc8) => [Register Register] "{0} *= {1}",
+    "div-float/2addr" (0xc9) => [Register Register] "{0} /= {1}",
+    "rem-float/2addr" (0xca) => [Register Register] "{0} %= {1}",
+    "add-double/2addr" (0xcb) => [Register Register] "{0} += {1}",
+    "sub-double/2addr" (0xcc) => [Register Register] "{0} -= {1}",
+    "mul-double/2addr" (0xcd) => [Register Register] "{0} *= {1}",
+    "div-double/2addr" (0xce) => [Register Register] "{0} /= {1}",
+    "rem-double/2addr" (0xcf) => [Register Register] "{0} %= {1}",
+    "add-int/lit16" (0xd0) => [Result Register Int] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "rsub-int" (0xd1) => [Result Register Int] "{2} - {1}" result_type=ResultTypeDef::From(1),
+    "mul-int/lit16" (0xd2) => [Result Register Int] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-int/lit16" (0xd3) => [Result Register Int] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-int/lit16" (0xd4) => [Result Register Int] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "and-int/lit16" (0xd5) => [Result Register Int] "{1} & {2}" result_type=ResultTypeDef::From(1),
+    "or-int/lit16" (0xd6) => [Result Register Int] "{1} | {2}" result_type=ResultTypeDef::From(1),
+    "xor-int/lit16" (0xd7) => [Result Register Int] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
+    "add-int/lit8" (0xd8) => [Result Register Int] "{1} + {2}" result_type=ResultTypeDef::From(1),
+    "rsub-int/lit8" (0xd9) => [Result Register Int] "{2} - {1}" result_type=ResultTypeDef::From(1),
+    "mul-int/lit8" (0xda) => [Result Register Int] "{1} * {2}" result_type=ResultTypeDef::From(1),
+    "div-int/lit8" (0xdb) => [Result Register Int] "{1} / {2}" result_type=ResultTypeDef::From(1),
+    "rem-int/lit8" (0xdc) => [Result Register Int] "{1} % {2}" result_type=ResultTypeDef::From(1),
+    "and-int/lit8" (0xdd) => [Result Register Int] "{1} & {2}" result_type=ResultTypeDef::From(1),
+    "or-int/lit8" (0xde) => [Result Register Int] "{1} | {2}" result_type=ResultTypeDef::From(1),
+    "xor-int/lit8" (0xdf) => [Result Register Int] "{1} ^ {2}" result_type=ResultTypeDef::From(1),
+    "shl-int/lit8" (0xe0) => [Result Register Int] "{1} << {2}" result_type=ResultTypeDef::From(1),
+    "shr-int/lit8" (0xe1) => [Result Register Int] "{1} >> {2}" result_type=ResultTypeDef::From(1),
+    "ushr-int/lit8" (0xe2) => [Result Register Int] "{1} >>> {2}" result_type=ResultTypeDef::From(1),
+    "invoke-polymorphic" (0xfa) => [DefaultEmptyResult Registers Method MethodType] "invoke-polymorphic {1.this}.<{2}>({1.args}), <{3}>" result_type=ResultTypeDef::ReturnOf(3),
+    "invoke-polymorphic/range" (0xfb) => [DefaultEmptyResult Registers Method MethodType] "invoke-polymorphic {1.this}.<{2}>({1.args}), <{3}>" result_type=ResultTypeDef::ReturnOf(3),
+    "invoke-custom" (0xfc) => [DefaultEmptyResult Registers CallSite] "invoke-custom {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::Object("java.lang.Object"),
+    "invoke-custom/range" (0xfd) => [DefaultEmptyResult Registers CallSite] "invoke-custom {1.this}.<{2}>({1.args})" result_type=ResultTypeDef::Object("java.lang.Object"),
+    "const-method-handle" (0xfe) => [Result MethodHandle] "{1}" result_type=ResultTypeDef::From(1),
+    "const-method-type" (0xff) => [Result MethodType] "{1}" result_type=ResultTypeDef::From(1),
 );
 
+/// Returns the command of a dex opcode, `None` for unused opcodes.
+pub(crate) fn get_command(opcode: u8) -> Option<&'static str> {
+    static COMMANDS: OnceLock<[Option<&'static str>; 256]> = OnceLock::new();
+    COMMANDS.get_or_init(|| {
+        let mut commands = [None; 256];
+        for (command, def) in DEFS.entries() {
+            if let Some(opcode) = def.opcode {
+                commands[usize::from(opcode)] = Some(*command);
+            }
+        }
+        commands
+    })[usize::from(opcode)]
+}
+
+/// Returns the parameter kinds of a command in the order of the Smali notation, `None` for
+/// unknown commands.
+pub(crate) fn get_parameter_kinds(command: &str) -> Option<&'static [ParameterKind]> {
+    DEFS.get(command).map(|def| def.parameters)
+}
+
+/// Lists the placeholders in an instruction format as parameter index and optional field,
+/// e.g. `{1}` or `{1.this}`.
+fn get_placeholders(format: &str) -> Vec<(usize, Option<&str>)> {
+    let mut result = Vec::new();
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let (index, field) = match rest[..end].split_once('.') {
+            Some((index, field)) => (index, Some(field)),
+            None => (&rest[..end], None),
+        };
+        if let Ok(index) = index.parse() {
+            result.push((index, field));
+        }
+    }
+    result
+}
+
+impl InstructionDef {
+    /// Checks that the format and result type only refer to existing parameters and that
+    /// results are always the first parameter.
+    fn validate(&self) -> Result<(), String> {
+        for (index, kind) in self.parameters.iter().enumerate() {
+            if index > 0
+                && matches!(
+                    kind,
+                    ParameterKind::Result | ParameterKind::DefaultEmptyResult
+                )
+            {
+                return Err(format!(
+                    "result parameter {index} isn't the first parameter"
+                ));
+            }
+        }
+
+        let placeholders = get_placeholders(self.format);
+        let result_source = match self.result_type {
+            ResultTypeDef::From(index)
+            | ResultTypeDef::ElementFrom(index)
+            | ResultTypeDef::ReturnOf(index) => Some(index),
+            _ => None,
+        };
+        for (index, field) in &placeholders {
+            let Some(kind) = self.parameters.get(*index) else {
+                return Err(format!("format refers to missing parameter {index}"));
+            };
+            match field {
+                None => (),
+                Some("this" | "args") if *kind == ParameterKind::Registers => (),
+                Some(field) => {
+                    return Err(format!(
+                        "format refers to {index}.{field} on a {kind:?} parameter"
+                    ))
+                }
+            }
+        }
+        for (index, kind) in self.parameters.iter().enumerate() {
+            let is_result = matches!(
+                kind,
+                ParameterKind::Result | ParameterKind::DefaultEmptyResult
+            );
+            let is_used = placeholders.iter().any(|(i, _)| *i == index);
+            if is_result && is_used {
+                return Err(format!("format refers to result parameter {index}"));
+            }
+            if !is_result && !is_used && result_source != Some(index) {
+                return Err(format!("parameter {index} is missing from the format"));
+            }
+        }
+
+        match (&self.result_type, result_source) {
+            (_, Some(index)) if index >= self.parameters.len() => {
+                Err(format!("result type refers to missing parameter {index}"))
+            }
+            (ResultTypeDef::None, _) => Ok(()),
+            _ if !matches!(
+                self.parameters.first(),
+                Some(ParameterKind::Result | ParameterKind::DefaultEmptyResult)
+            ) =>
+            {
+                Err("result type given for a command without result".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Checks all instruction definitions for consistency, returning the list of problems found.
+pub fn validate_definitions() -> Vec<String> {
+    DEFS.entries()
+        .filter_map(|(command, defs)| {
+            defs.validate()
+                .err()
+                .map(|error| format!("{command}: {error}"))
+        })
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Result type of a command as listed in the instruction definitions.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ResultTypeJson {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Object {
+        #[serde(rename = "type")]
+        object_type: &'static str,
+    },
+    /// Type of the parameter with the given index
+    From {
+        parameter: usize,
+    },
+    /// Element type of the array parameter with the given index
+    ElementFrom {
+        parameter: usize,
+    },
+    /// Return type of the method parameter with the given index
+    ReturnOf {
+        parameter: usize,
+    },
+    Exception,
+}
+
+impl ResultTypeDef {
+    fn to_json(&self) -> Option<ResultTypeJson> {
+        Some(match self {
+            Self::None => return None,
+            Self::Bool => ResultTypeJson::Boolean,
+            Self::Byte => ResultTypeJson::Byte,
+            Self::Char => ResultTypeJson::Char,
+            Self::Short => ResultTypeJson::Short,
+            Self::Int => ResultTypeJson::Int,
+            Self::Long => ResultTypeJson::Long,
+            Self::Float => ResultTypeJson::Float,
+            Self::Double => ResultTypeJson::Double,
+            Self::Object(object_type) => ResultTypeJson::Object { object_type },
+            Self::From(parameter) => ResultTypeJson::From {
+                parameter: *parameter,
+            },
+            Self::ElementFrom(parameter) => ResultTypeJson::ElementFrom {
+                parameter: *parameter,
+            },
+            Self::ReturnOf(parameter) => ResultTypeJson::ReturnOf {
+                parameter: *parameter,
+            },
+            Self::Exception => ResultTypeJson::Exception,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct DefinitionJson {
+    command: &'static str,
+    /// Opcode in dex files, `null` for commands without one like `.line`
+    opcode: Option<u8>,
+    parameters: &'static [ParameterKind],
+    /// Jimple format with `{N}` placeholders for the parameters
+    format: &'static str,
+    moved_result: bool,
+    result_type: Option<ResultTypeJson>,
+}
+
+/// Supported Smali commands written by the definitions command
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct DefinitionsFile {
+    schema_version: SchemaVersion<{ JsonOutput::Definitions.version() }>,
+    definitions: Vec<DefinitionJson>,
+}
+
+/// Writes the instruction definitions as JSON, sorted by command, so that external
+/// tools can follow the supported commands without parsing this file.
+pub fn write_definitions(output: &mut dyn Write) -> Result<(), std::io::Error> {
+    let definitions = DEFS
+        .entries()
+        .collect::<std::collections::BTreeMap<_, _>>()
+        .into_iter()
+        .map(|(command, def)| DefinitionJson {
+            command,
+            opcode: def.opcode,
+            parameters: def.parameters,
+            format: def.format,
+            moved_result: def.is_moved_result,
+            result_type: def.result_type.to_json(),
+        })
+        .collect();
+    write_json_pretty(
+        output,
+        &DefinitionsFile {
+            schema_version: SchemaVersion,
+            definitions,
+        },
+    )
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub enum Register {
     Parameter(usize),
     Local(usize),
+    /// Value passed from one command to the next one reading it
+    Stack(usize),
+    /// Value read by several commands right after it is computed
+    Temporary(usize),
 }
 
 impl Display for Register {
@@ -329,6 +571,8 @@ impl Display for Register {
         match self {
             Self::Parameter(index) => write!(f, "p{index}"),
             Self::Local(index) => write!(f, "v{index}"),
+            Self::Stack(index) => write!(f, "$stack{index}"),
+            Self::Temporary(index) => write!(f, "$tmp{index}"),
         }
     }
 }
@@ -367,19 +611,26 @@ impl Registers {
         } else if let (Register::Local(from_index), Register::Local(to_index)) = (from, to) {
             Some((*from_index..to_index + 1).map(Register::Local).collect())
         } else {
-            eprintln!("Warning: Invalid parameter range: {from} .. {to}");
+            warning!("Invalid parameter range: {from} .. {to}");
             None
         }
     }
 
     fn stringify_list(list: &[Register], split_first: bool) -> (Option<String>, String) {
         if split_first && !list.is_empty() {
-            (Some(list[0].to_string()), list[1..].iter().join(", "))
+            (Some(list[0].to_string()), Self::join(&list[1..]))
         } else {
-            (None, list.iter().join(", "))
+            (None, Self::join(list))
         }
     }
 
+    fn join(list: &[Register]) -> String {
+        list.iter()
+            .map(Register::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     pub fn to_string(&self, split_first: bool) -> (Option<String>, String) {
         match self {
             Self::List(list) => Self::stringify_list(list, split_first),
@@ -399,7 +650,7 @@ pub enum CommandData {
     Label(String),
     PackedSwitch(i64, Vec<String>),
     SparseSwitch(Vec<(Literal, String)>),
-    Array(Vec<Literal>),
+    Array(usize, Vec<Literal>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -416,9 +667,19 @@ pub enum CommandParameter {
     Method(MethodSignature),
     CallSite(CallSite),
     Data(CommandData),
+    /// Parameter names of the called method for each argument register, added to calls for
+    /// display only
+    ArgumentNames(Vec<Option<String>>),
+    /// Warning to be shown next to the command, added for display only
+    Warning(String),
+    /// Comment to be shown next to the command, added for display only
+    Comment(String),
+    /// Expression to be shown instead of the command, with placeholders like `{0}` for the
+    /// argument registers, added for display only
+    Rewrite(String),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     LineNumber(i64, i64),
     Label(String),
@@ -441,12 +702,66 @@ pub enum Instruction {
         register: String,
     },
     Data(CommandData),
+    Comment(String),
+    /// Comment following the preceding instruction on the same line in Smali code
+    TrailingComment(String),
+    AssertNotNull {
+        register: Register,
+        message: Option<Literal>,
+    },
+    Try,
+    TryWithResources(Register),
+    Finally,
+    BlockEnd,
+    /// Line of method code that failed to parse in lenient mode, kept as is
+    Raw(String),
 }
 
 impl Instruction {
     pub fn is_command(&self) -> bool {
         matches!(self, Instruction::Command { .. })
     }
+
+    /// Lists all known commands without variant suffixes like `/16` or `/range`, sorted.
+    pub fn get_command_families() -> Vec<&'static str> {
+        DEFS.keys()
+            .filter_map(|command| command.split('/').next())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the command name without variant suffixes like `/16` or `/range`.
+    pub fn get_command_family(&self) -> Option<&str> {
+        match self {
+            Instruction::Command { command, .. } => command.split('/').next(),
+            _ => None,
+        }
+    }
+
+    /// Checks whether the instruction merely annotates the code like line numbers and comments
+    /// do, so that recognizing code patterns can skip over it.
+    pub fn is_annotation(&self) -> bool {
+        matches!(
+            self,
+            Instruction::LineNumber(..) | Instruction::Comment(_) | Instruction::TrailingComment(_)
+        )
+    }
+
+    /// Checks whether the instruction is written out as a statement rather than as a label or
+    /// annotation of the code.
+    pub fn is_statement(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Command { .. }
+                | Instruction::AssertNotNull { .. }
+                | Instruction::Try
+                | Instruction::TryWithResources(_)
+                | Instruction::Finally
+                | Instruction::BlockEnd
+                | Instruction::Raw(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -478,3 +793,108 @@ impl From<&Literal> for ResultType {
         Self::Literal(value.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn validate_definitions() {
+        assert_eq!(super::validate_definitions(), Vec::<String>::new());
+
+        let invalid = InstructionDef {
+            parameters: &[ParameterKind::Result, ParameterKind::Register],
+            format: "{1} + {2}",
+            result_type: ResultTypeDef::From(1),
+            ..InstructionDef::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        let invalid = InstructionDef {
+            parameters: &[ParameterKind::Register, ParameterKind::Label],
+            format: "if ({0}) goto {1}",
+            result_type: ResultTypeDef::Int,
+            ..InstructionDef::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn write_definitions() {
+        let mut output = Vec::new();
+        super::write_definitions(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Definitions.assert_valid(&output);
+
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(output["schema_version"], 1);
+        let definitions = output["definitions"].as_array().unwrap();
+        assert_eq!(definitions.len(), DEFS.len());
+        let find = |command: &str| {
+            definitions
+                .iter()
+                .find(|definition| definition["command"] == command)
+                .unwrap()
+        };
+        assert_eq!(
+            find("move-result-object"),
+            &serde_json::json!({
+                "command": "move-result-object",
+                "opcode": 12,
+                "parameters": ["result"],
+                "format": "move-result",
+                "moved_result": true,
+                "result_type": {"kind": "object", "type": "java.lang.Object"}
+            })
+        );
+        assert_eq!(
+            find("xor-long/2addr"),
+            &serde_json::json!({
+                "command": "xor-long/2addr",
+                "opcode": 194,
+                "parameters": ["register", "register"],
+                "format": "{0} ^= {1}",
+                "moved_result": false,
+                "result_type": null
+            })
+        );
+        assert_eq!(
+            find("array-length")["result_type"],
+            serde_json::json!({"kind": "int"})
+        );
+    }
+
+    #[test]
+    fn read_all_parameter_kinds() {
+        let kinds = DEFS
+            .values()
+            .flat_map(|defs| defs.parameters.iter())
+            .collect::<Vec<_>>();
+        for kind in kinds {
+            // Adding a parameter kind requires adding a sample value here
+            let sample = match kind {
+                ParameterKind::Result | ParameterKind::Register => "v0",
+                ParameterKind::DefaultEmptyResult => "",
+                ParameterKind::Registers => "{v0, v1}",
+                ParameterKind::Int => "0x1",
+                ParameterKind::Long => "0x1L",
+                ParameterKind::String => "\"a\"",
+                ParameterKind::Class => "La;",
+                ParameterKind::MethodHandle => "invoke-static@La;->b()V",
+                ParameterKind::MethodType => "(I)V",
+                ParameterKind::Label | ParameterKind::Data => ":label",
+                ParameterKind::Type => "[I",
+                ParameterKind::Field => "La;->b:I",
+                ParameterKind::Method => "La;->b()V",
+                ParameterKind::CallSite => "c(\"a\")@La;->b()V",
+            };
+            let input = Tokenizer::new(sample.to_string(), std::path::Path::new("dummy"));
+            let result = CommandParameter::read(&input, kind);
+            assert!(
+                matches!(&result, Ok((input, _)) if input.expect_eof().is_ok()),
+                "Failed reading {kind:?} parameter {sample}: {result:?}"
+            );
+        }
+    }
+}
@@ -1,11 +1,31 @@
 use std::collections::HashMap;
 
 use super::{
-    CommandData, CommandParameter, Instruction, Register, ResultType, ResultTypeDef, DEFS,
+    CommandData, CommandParameter, Instruction, Register, Registers, ResultType, ResultTypeDef,
+    DEFS,
 };
+use crate::diagnostics::warning;
 use crate::literal::Literal;
 use crate::r#type::{MethodSignature, Type};
 
+/// Methods verifying that their first argument isn't null. The second argument, if any, is the
+/// error message.
+const NULL_CHECKS: [(&str, &str); 7] = [
+    ("kotlin.jvm.internal.Intrinsics", "checkNotNull"),
+    ("kotlin.jvm.internal.Intrinsics", "checkNotNullParameter"),
+    (
+        "kotlin.jvm.internal.Intrinsics",
+        "checkNotNullExpressionValue",
+    ),
+    ("kotlin.jvm.internal.Intrinsics", "checkParameterIsNotNull"),
+    (
+        "kotlin.jvm.internal.Intrinsics",
+        "checkExpressionValueIsNotNull",
+    ),
+    ("java.util.Objects", "requireNonNull"),
+    ("com.google.common.base.Preconditions", "checkNotNull"),
+];
+
 impl Instruction {
     pub fn get_moved_result(&self) -> Option<Register> {
         if let Self::Command {
@@ -18,7 +38,7 @@ impl Instruction {
                 .map(|d| d.is_moved_result)
                 .unwrap_or(false)
             {
-                if let Some(CommandParameter::Result(result)) = parameters.get(0) {
+                if let Some(CommandParameter::Result(result)) = parameters.first() {
                     return Some(result.clone());
                 }
             }
@@ -47,7 +67,7 @@ impl Instruction {
                         if let Some(d) = d.get(label) {
                             *data = d.clone();
                         } else {
-                            eprintln!("Warning: Failed resolving command data {label}");
+                            warning!("Failed resolving command data {label}");
                         }
                     }
                 }
@@ -83,7 +103,7 @@ impl Instruction {
             | CommandParameter::Register(register) => match state.get(register) {
                 Some(r#type) => Some(r#type.clone()),
                 None => {
-                    eprintln!("Warning: Using register {register}, yet its type isn't known yet.");
+                    warning!("Using register {register}, yet its type isn't known yet.");
                     None
                 }
             },
@@ -98,10 +118,12 @@ impl Instruction {
             CommandParameter::Variable(_)
             | CommandParameter::Registers(_)
             | CommandParameter::Label(_)
-            | CommandParameter::Data(_) => {
-                eprintln!(
-                    "Warning: Trying to deduce type from unexpected parameter {parameter:?}."
-                );
+            | CommandParameter::Data(_)
+            | CommandParameter::ArgumentNames(_)
+            | CommandParameter::Warning(_)
+            | CommandParameter::Comment(_)
+            | CommandParameter::Rewrite(_) => {
+                warning!("Trying to deduce type from unexpected parameter {parameter:?}.");
                 None
             }
         }
@@ -132,9 +154,13 @@ impl Instruction {
                 ResultTypeDef::ElementFrom(index) => {
                     match Self::parameter_type(&parameters[*index], state) {
                         None => None,
-                        Some(ResultType::Type(Type::Array(element))) => Some((*element).into()),
+                        Some(ResultType::Type(array_type)) if array_type.dimensions() > 0 => {
+                            array_type.element_type().cloned().map(Into::into)
+                        }
                         other => {
-                            eprintln!("Warning: Trying to deduce element type from non-array parameter {other:?}");
+                            warning!(
+                                "Trying to deduce element type from non-array parameter {other:?}"
+                            );
                             None
                         }
                     }
@@ -154,13 +180,15 @@ impl Instruction {
                             Some((&call_signature.return_type).into())
                         }
                         other => {
-                            eprintln!("Warning: Trying to deduce return type from a non-call parameter {other:?}");
+                            warning!(
+                                "Trying to deduce return type from a non-call parameter {other:?}"
+                            );
                             None
                         }
                     }
                 }
                 ResultTypeDef::Exception => {
-                    Some(Type::Object("java.lang.exception".to_string()).into())
+                    Some(Type::Object("java.lang.Throwable".to_string()).into())
                 }
             }
         } else {
@@ -168,6 +196,285 @@ impl Instruction {
         }
     }
 
+    pub fn get_result_register(&self) -> Option<&Register> {
+        if let Self::Command { parameters, .. } = self {
+            if let Some(CommandParameter::Result(result))
+            | Some(CommandParameter::DefaultEmptyResult(Some(result))) = parameters.first()
+            {
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    /// Checks whether the register is both read and written by the command, as with `/2addr`
+    /// commands.
+    pub fn modifies_in_place(&self, register: &Register) -> bool {
+        if let Self::Command {
+            command,
+            parameters,
+        } = self
+        {
+            if command.ends_with("/2addr") {
+                return matches!(parameters.first(), Some(CommandParameter::Register(r)) if r == register);
+            }
+        }
+        false
+    }
+
+    pub fn count_register_uses(&self, register: &Register) -> usize {
+        let mut result = 0;
+        if let Self::Command { parameters, .. } = self {
+            for parameter in parameters {
+                match parameter {
+                    CommandParameter::Register(r) if r == register => result += 1,
+                    CommandParameter::Registers(Registers::List(list)) => {
+                        result += list.iter().filter(|r| *r == register).count();
+                    }
+                    CommandParameter::Registers(Registers::Range(from, to)) => {
+                        if let Some(list) = Registers::resolve_range(from, to) {
+                            result += list.iter().filter(|r| *r == register).count();
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        result
+    }
+
+    /// Lists registers read by the command, in the order of the command's parameters.
+    pub fn get_used_registers(&self) -> Vec<Register> {
+        let mut result = Vec::new();
+        if let Self::Command { parameters, .. } = self {
+            for parameter in parameters {
+                match parameter {
+                    CommandParameter::Register(r) => result.push(r.clone()),
+                    CommandParameter::Registers(Registers::List(list)) => {
+                        result.extend(list.iter().cloned());
+                    }
+                    CommandParameter::Registers(Registers::Range(from, to)) => {
+                        if let Some(list) = Registers::resolve_range(from, to) {
+                            result.extend(list);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        result
+    }
+
+    /// For invoke commands, returns the called method along with the registers holding its
+    /// arguments, starting with the object for instance calls. Wide arguments occupy two
+    /// registers, only the first one is listed.
+    pub fn get_call_arguments(&self) -> Option<(&MethodSignature, Vec<Register>)> {
+        let Self::Command {
+            command,
+            parameters,
+        } = self
+        else {
+            return None;
+        };
+        let (true, Some(CommandParameter::Method(method))) =
+            (command.starts_with("invoke-"), parameters.get(2))
+        else {
+            return None;
+        };
+
+        let mut types = Vec::new();
+        if !command.starts_with("invoke-static") {
+            types.push(&method.object_type);
+        }
+        types.extend(&method.call_signature.parameter_types);
+        let mut registers = self.get_used_registers().into_iter();
+        let arguments = types
+            .into_iter()
+            .map_while(|r#type| {
+                let register = registers.next();
+                for _ in 1..r#type.register_count() {
+                    registers.next();
+                }
+                register
+            })
+            .collect();
+        Some((method, arguments))
+    }
+
+    /// Checks whether the command can have effects beyond writing its result register, e.g.
+    /// memory access, calls or exceptions. Such commands cannot be reordered.
+    pub fn has_side_effects(&self) -> bool {
+        let Self::Command { command, .. } = self else {
+            return false;
+        };
+
+        let name = command.split('/').next().unwrap_or(command);
+        if name.starts_with("const")
+            || name.starts_with("neg-")
+            || name.starts_with("not-")
+            || name.starts_with("cmp")
+            || name.contains("-to-")
+            || matches!(name, "move" | "move-wide" | "move-object" | "instance-of")
+        {
+            return false;
+        }
+
+        match name.split_once('-') {
+            Some((
+                "add" | "sub" | "rsub" | "mul" | "and" | "or" | "xor" | "shl" | "shr" | "ushr",
+                _,
+            )) => false,
+            // Integer division throws on zero divisor
+            Some(("div" | "rem", "float" | "double")) => false,
+            _ => true,
+        }
+    }
+
+    pub fn rename_result_register(&mut self, to: Register) {
+        if let Self::Command { parameters, .. } = self {
+            if let Some(CommandParameter::Result(result))
+            | Some(CommandParameter::DefaultEmptyResult(Some(result))) = parameters.first_mut()
+            {
+                *result = to;
+            }
+        }
+    }
+
+    pub fn rename_used_register(&mut self, from: &Register, to: &Register) {
+        if let Self::Command { parameters, .. } = self {
+            for parameter in parameters.iter_mut() {
+                match parameter {
+                    CommandParameter::Register(r) if r == from => *r = to.clone(),
+                    CommandParameter::Registers(registers) => {
+                        let list = match registers {
+                            Registers::List(list) => Some(list.clone()),
+                            Registers::Range(start, end) => Registers::resolve_range(start, end),
+                        };
+                        if let Some(list) = list.filter(|list| list.contains(from)) {
+                            *registers = Registers::List(
+                                list.into_iter()
+                                    .map(|r| if &r == from { to.clone() } else { r })
+                                    .collect(),
+                            );
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Recognizes calls to null check helpers with an unused result, returning the register
+    /// being checked and the register containing the error message if any.
+    pub fn get_null_check(&self) -> Option<(Register, Option<Register>)> {
+        let Self::Command {
+            command,
+            parameters,
+        } = self
+        else {
+            return None;
+        };
+        if !command.starts_with("invoke-static") {
+            return None;
+        }
+
+        let (registers, method) = match parameters.as_slice() {
+            [CommandParameter::DefaultEmptyResult(None), CommandParameter::Registers(registers), CommandParameter::Method(method)] => {
+                (registers, method)
+            }
+            _ => return None,
+        };
+        if !NULL_CHECKS.iter().any(|(object_type, method_name)| {
+            method.object_type.get_name() == *object_type && method.method_name == *method_name
+        }) || method.call_signature.parameter_types.first()
+            != Some(&Type::Object("java.lang.Object".to_string()))
+        {
+            return None;
+        }
+
+        let list = match registers {
+            Registers::List(list) => list.clone(),
+            Registers::Range(from, to) => Registers::resolve_range(from, to)?,
+        };
+        let mut list = list.into_iter();
+        Some((list.next()?, list.next()))
+    }
+
+    /// Recognizes a `close()` call, returning the register of the object being closed.
+    pub fn get_close_call(&self) -> Option<Register> {
+        let Self::Command {
+            command,
+            parameters,
+        } = self
+        else {
+            return None;
+        };
+        if !command.starts_with("invoke-virtual") && !command.starts_with("invoke-interface") {
+            return None;
+        }
+
+        match parameters.as_slice() {
+            [CommandParameter::DefaultEmptyResult(None), CommandParameter::Registers(_), CommandParameter::Method(method)]
+                if method.method_name == "close"
+                    && method.call_signature.parameter_types.is_empty() =>
+            {
+                let registers = self.get_used_registers();
+                (registers.len() == 1).then(|| registers[0].clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Lists labels the instruction refers to, e.g. as jump targets or boundaries of a try block.
+    pub fn get_referenced_labels(&self) -> Vec<&str> {
+        match self {
+            Self::Command { parameters, .. } => parameters
+                .iter()
+                .flat_map(|parameter| match parameter {
+                    CommandParameter::Label(label)
+                    | CommandParameter::Data(CommandData::Label(label)) => vec![label.as_str()],
+                    CommandParameter::Data(CommandData::PackedSwitch(_, targets)) => {
+                        targets.iter().map(String::as_str).collect()
+                    }
+                    CommandParameter::Data(CommandData::SparseSwitch(targets)) => {
+                        targets.iter().map(|(_, target)| target.as_str()).collect()
+                    }
+                    _ => Vec::new(),
+                })
+                .collect(),
+            Self::Catch {
+                start_label,
+                end_label,
+                target,
+                ..
+            } => vec![start_label, end_label, target],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Checks whether execution never continues with the next instruction.
+    pub fn exits_method(&self) -> bool {
+        if let Self::Command { command, .. } = self {
+            command.starts_with("return") || command == "throw"
+        } else {
+            false
+        }
+    }
+
+    /// Checks whether the command ends a basic block. Raw instructions might be jumps, so they
+    /// are assumed to end one.
+    pub fn ends_block(&self) -> bool {
+        match self {
+            Self::Command { command, .. } => {
+                self.exits_method()
+                    || self.get_jump_target().is_some()
+                    || command.ends_with("-switch")
+            }
+            Self::Raw(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn get_jump_target(&self) -> Option<String> {
         if let Self::Command { parameters, .. } = self {
             for parameter in parameters {
@@ -267,6 +574,20 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn get_result_type_exception() -> Result<(), ParseErrorDisplayed> {
+        // Catch-all handlers receive any throwable, not just exceptions
+        let (input, instruction) = Instruction::read(&tokenizer("move-exception v3"))?;
+        input.expect_eof()?;
+        assert_eq!(
+            instruction.get_result_type(&HashMap::new()),
+            Some(ResultType::Type(Type::Object(
+                "java.lang.Throwable".to_string()
+            )))
+        );
+        Ok(())
+    }
+
     #[test]
     fn get_jump_target() -> Result<(), ParseErrorDisplayed> {
         let mut input = tokenizer(
@@ -5,6 +5,28 @@ use crate::r#type::{CallSite, FieldSignature, MethodSignature, Type};
 use crate::tokenizer::Tokenizer;
 
 impl CommandParameter {
+    /// Returns the parameter in Smali notation or `None` for parameters that aren't written
+    /// out explicitly.
+    pub fn to_smali(&self) -> Option<String> {
+        Some(match self {
+            Self::DefaultEmptyResult(_) => return None,
+            Self::Result(register) | Self::Register(register) => register.to_string(),
+            Self::Variable(variable) => variable.to_string(),
+            Self::Registers(registers) => registers.to_smali(),
+            Self::Literal(literal) => literal.to_smali(),
+            Self::Label(label) | Self::Data(CommandData::Label(label)) => format!(":{label}"),
+            Self::Type(r#type) => r#type.to_descriptor(),
+            Self::Field(field) => field.to_smali(),
+            Self::Method(method) => method.to_smali(),
+            Self::CallSite(call_site) => call_site.to_smali(),
+            Self::Data(_)
+            | Self::ArgumentNames(_)
+            | Self::Warning(_)
+            | Self::Comment(_)
+            | Self::Rewrite(_) => return None,
+        })
+    }
+
     pub fn read(input: &Tokenizer, kind: &ParameterKind) -> Result<(Tokenizer, Self), ParseError> {
         Ok(match kind {
             ParameterKind::Result => {
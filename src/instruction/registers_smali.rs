@@ -31,7 +31,7 @@ impl Registers {
         while let Ok((i, register)) = Register::read(&input) {
             input = i;
             list.push(register);
-            if input.expect_char('}').is_ok() {
+            if input.peek_char() == Some('}') {
                 break;
             }
             input = input.expect_char(',')?;
@@ -39,6 +39,13 @@ impl Registers {
         Ok((input, Self::List(list)))
     }
 
+    pub fn to_smali(&self) -> String {
+        match self {
+            Self::List(list) => format!("{{{}}}", Self::join(list)),
+            Self::Range(from, to) => format!("{{{from} .. {to}}}"),
+        }
+    }
+
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
         let input = input.expect_char('{')?;
         let (input, result) = if let Ok(result) = Self::read_range(&input) {
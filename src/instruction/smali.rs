@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use super::{CommandData, CommandParameter, Instruction, ParameterKind, DEFS};
 use crate::error::ParseError;
 use crate::literal::Literal;
@@ -10,7 +12,102 @@ pub(crate) fn read_label(input: &Tokenizer) -> Result<(Tokenizer, String), Parse
     Ok((input, label))
 }
 
+impl CommandData {
+    pub fn write_smali(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        match self {
+            Self::Label(label) => writeln!(output, "    :{label}"),
+            Self::PackedSwitch(first_key, targets) => {
+                writeln!(
+                    output,
+                    "    .packed-switch {}",
+                    Literal::Int(*first_key as i32)
+                )?;
+                for target in targets {
+                    writeln!(output, "        :{target}")?;
+                }
+                writeln!(output, "    .end packed-switch")
+            }
+            Self::SparseSwitch(targets) => {
+                writeln!(output, "    .sparse-switch")?;
+                for (value, target) in targets {
+                    writeln!(output, "        {} -> :{target}", value.to_smali())?;
+                }
+                writeln!(output, "    .end sparse-switch")
+            }
+            Self::Array(element_size, elements) => {
+                writeln!(output, "    .array-data {element_size}")?;
+                for element in elements {
+                    writeln!(output, "        {}", element.to_smali())?;
+                }
+                writeln!(output, "    .end array-data")
+            }
+        }
+    }
+}
+
 impl Instruction {
+    /// Writes the instruction back in Smali notation. Only instructions produced by the parser
+    /// are supported, not the ones created by optimization.
+    pub fn write_smali(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        match self {
+            Self::LineNumber(line, _) => writeln!(output, "    .line {line}"),
+            Self::Label(label) => writeln!(output, "    :{label}"),
+            Self::Command {
+                command,
+                parameters,
+            } => {
+                let parameters = parameters
+                    .iter()
+                    .filter_map(CommandParameter::to_smali)
+                    .collect::<Vec<_>>();
+                if parameters.is_empty() {
+                    writeln!(output, "    {command}")
+                } else {
+                    writeln!(output, "    {command} {}", parameters.join(", "))
+                }
+            }
+            Self::Catch {
+                exception,
+                start_label,
+                end_label,
+                target,
+            } => match exception {
+                Some(exception) => writeln!(
+                    output,
+                    "    .catch {} {{:{start_label} .. :{end_label}}} :{target}",
+                    exception.to_descriptor()
+                ),
+                None => writeln!(
+                    output,
+                    "    .catchall {{:{start_label} .. :{end_label}}} :{target}"
+                ),
+            },
+            Self::Local {
+                register,
+                name,
+                local_type,
+            } => writeln!(
+                output,
+                "    .local {register}, {}:{}",
+                name.to_smali(),
+                local_type.to_descriptor()
+            ),
+            Self::LocalRestart { register } => writeln!(output, "    .restart local {register}"),
+            Self::Data(data) => data.write_smali(output),
+            Self::Comment(comment) | Self::TrailingComment(comment) => {
+                writeln!(output, "    # {comment}")
+            }
+            Self::Raw(line) => writeln!(output, "    {line}"),
+            Self::AssertNotNull { .. }
+            | Self::Try
+            | Self::TryWithResources(_)
+            | Self::Finally
+            | Self::BlockEnd => Err(std::io::Error::other(
+                "optimized instructions cannot be written as Smali",
+            )),
+        }
+    }
+
     fn read_directive(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
         let start = input;
         let (input, directive) = input.read_directive()?;
@@ -94,22 +191,31 @@ impl Instruction {
             "array-data" => {
                 let start = &input;
                 let (input, literal) = Literal::read(&input)?;
-                let _element_size = literal
+                let element_size = literal
                     .get_integer()
-                    .ok_or_else(|| start.unexpected("a number".into()))?;
+                    .and_then(|size| usize::try_from(size).ok())
+                    .filter(|size| matches!(size, 1 | 2 | 4 | 8))
+                    .ok_or_else(|| start.unexpected("an element size of 1, 2, 4 or 8".into()))?;
                 let mut input = input.expect_eol()?;
 
                 let mut elements = Vec::new();
                 while input.expect_directive("end").is_err() {
+                    let start = input.clone();
                     let element;
                     (input, element) = Literal::read(&input)?;
+                    let element = element.with_element_size(element_size).ok_or_else(|| {
+                        start.unexpected(format!("a number of size {element_size}").into())
+                    })?;
                     input = input.expect_eol()?;
                     elements.push(element);
                 }
 
                 let input = input.expect_directive("end")?;
                 let input = input.expect_keyword("array-data")?;
-                (input, Self::Data(CommandData::Array(elements)))
+                (
+                    input,
+                    Self::Data(CommandData::Array(element_size, elements)),
+                )
             }
             "local" => {
                 let (input, register) = input.read_keyword()?;
@@ -140,8 +246,16 @@ impl Instruction {
         })
     }
 
+    /// Reads the remainder of the line as a raw instruction, for code that the parser doesn't
+    /// understand.
+    pub(crate) fn read_raw(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        let (input, line) = input.read_to(&['\n']);
+        let input = input.expect_eol()?;
+        Ok((input, Self::Raw(line.trim().to_string())))
+    }
+
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
-        let (input, result) = if input.expect_char('.').is_ok() {
+        let (input, result) = if input.peek_char() == Some('.') {
             Self::read_directive(input)?
         } else if let Ok((input, label)) = read_label(input) {
             (input, Self::Label(label))
@@ -312,7 +426,7 @@ mod tests {
                     CommandParameter::CallSite(CallSite {
                         name: "normallyLinkedCallSite".to_string(),
                         params: vec![
-                            Literal::String("doSomething".to_string()),
+                            Literal::String("doSomething".into()),
                             Literal::MethodType(CallSignature {
                                 parameter_types: vec![
                                     Type::Object("Custom".to_string()),
@@ -320,7 +434,7 @@ mod tests {
                                 ],
                                 return_type: Type::Object("java.lang.String".to_string())
                             }),
-                            Literal::String("just testing".to_string()),
+                            Literal::String("just testing".into()),
                         ],
                         method: MethodSignature {
                             object_type: Type::Object("BootstrapLinker".to_string()),
@@ -356,7 +470,7 @@ mod tests {
                     CommandParameter::CallSite(CallSite {
                         name: "backwardsLinkedCallSite".to_string(),
                         params: vec![
-                            Literal::String("doSomething".to_string()),
+                            Literal::String("doSomething".into()),
                             Literal::MethodType(CallSignature {
                                 parameter_types: vec![
                                     Type::Object("Custom".to_string()),
@@ -364,7 +478,7 @@ mod tests {
                                 ],
                                 return_type: Type::Object("java.lang.String".to_string())
                             }),
-                            Literal::String("just testing".to_string()),
+                            Literal::String("just testing".into()),
                         ],
                         method: MethodSignature {
                             object_type: Type::Object("BootstrapLinker".to_string()),
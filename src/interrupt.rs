@@ -0,0 +1,23 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Makes Ctrl-C request a clean stop: long-running operations finish the file they are working
+/// on and record what has been completed. A second Ctrl-C terminates immediately.
+#[cfg(feature = "cli")]
+pub fn install_handler() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("Interrupted, finishing the current file. Press Ctrl-C again to abort.");
+    });
+    if let Err(error) = result {
+        eprintln!("Failed installing Ctrl-C handler: {error}");
+    }
+}
+
+/// Checks whether the user requested to stop.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,488 @@
+use std::io::Write;
+
+use crate::access_flag::AccessFlag;
+use crate::annotation::Annotation;
+use crate::class::Class;
+use crate::r#type::{FieldSignature, Type};
+use crate::unused::{Member, References};
+
+/// Annotations making the shrinker keep a class or member with the default rules.
+const KEEP_ANNOTATIONS: [&str; 2] = [
+    "androidx.annotation.Keep",
+    "android.support.annotation.Keep",
+];
+
+/// Checks whether a name matches a ProGuard pattern: `**` stands for any sequence of characters,
+/// `*` for any sequence without package separators and `?` for a single character.
+fn matches_pattern(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern {
+        [] => name.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            (0..=name.len()).any(|index| matches_pattern(rest, &name[index..]))
+        }
+        [b'*', rest @ ..] => {
+            let limit = name.iter().position(|c| *c == b'.').unwrap_or(name.len());
+            (0..=limit).any(|index| matches_pattern(rest, &name[index..]))
+        }
+        [b'?', rest @ ..] => {
+            name.first().is_some_and(|c| *c != b'.') && matches_pattern(rest, &name[1..])
+        }
+        [c, rest @ ..] => name.first() == Some(c) && matches_pattern(rest, &name[1..]),
+    }
+}
+
+/// Checks a name against a comma-separated pattern list, the first matching pattern decides.
+/// Patterns starting with `!` exclude names. A lone `*` matches classes in any package.
+fn matches_list(list: &str, name: &str) -> bool {
+    for pattern in list.split(',') {
+        let (negated, pattern) = match pattern.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        if pattern == "*" || matches_pattern(pattern.as_bytes(), name.as_bytes()) {
+            return !negated;
+        }
+    }
+    false
+}
+
+fn has_annotation(annotations: &[Annotation], pattern: &str) -> bool {
+    annotations
+        .iter()
+        .any(|annotation| matches_list(pattern, &annotation.annotation_type.get_name()))
+}
+
+/// Kind of members a member specification applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MemberKind {
+    Field,
+    Method,
+    Any,
+}
+
+/// A member specification within the braces of a keep rule, types are ignored.
+#[derive(Debug, PartialEq)]
+struct MemberSpec {
+    kind: MemberKind,
+    name: String,
+    annotation: Option<String>,
+    /// Access flags required or, if `true` is given, forbidden
+    modifiers: Vec<(bool, AccessFlag)>,
+}
+
+impl MemberSpec {
+    fn parse(spec: &str) -> Option<Self> {
+        let mut words = spec.split_whitespace().collect::<Vec<_>>();
+        let annotation = match words.first()?.strip_prefix('@') {
+            Some(annotation) => {
+                words.remove(0);
+                Some(annotation.to_string())
+            }
+            None => None,
+        };
+        let modifiers = words
+            .iter()
+            .filter_map(|word| {
+                let (negated, word) = match word.strip_prefix('!') {
+                    Some(word) => (true, word),
+                    None => (false, *word),
+                };
+                Some((negated, AccessFlag::try_from(word).ok()?))
+            })
+            .collect();
+
+        let (kind, name) = match spec.split_once('(') {
+            Some((declaration, _)) => (
+                MemberKind::Method,
+                declaration.split_whitespace().last()?.to_string(),
+            ),
+            None => match *words.last()? {
+                "<fields>" => (MemberKind::Field, "*".to_string()),
+                "<methods>" => (MemberKind::Method, "*".to_string()),
+                "*" => (MemberKind::Any, "*".to_string()),
+                name => (MemberKind::Field, name.to_string()),
+            },
+        };
+        Some(Self {
+            kind,
+            name,
+            annotation,
+            modifiers,
+        })
+    }
+
+    fn matches(
+        &self,
+        kind: MemberKind,
+        name: &str,
+        flags: &[AccessFlag],
+        annotations: &[Annotation],
+    ) -> bool {
+        (self.kind == MemberKind::Any || self.kind == kind)
+            && (self.name == name || matches_pattern(self.name.as_bytes(), name.as_bytes()))
+            && self
+                .modifiers
+                .iter()
+                .all(|(negated, flag)| flags.contains(flag) != *negated)
+            && self
+                .annotation
+                .as_ref()
+                .is_none_or(|annotation| has_annotation(annotations, annotation))
+    }
+}
+
+/// A `-keep` rule of a ProGuard or R8 configuration. Only the parts relevant to deciding which
+/// classes and members are kept are considered.
+#[derive(Debug, PartialEq)]
+pub struct KeepRule {
+    /// The rule as written, with whitespace normalized
+    pub text: String,
+    /// Whether the class itself is kept, `-keepclassmembers` only keeps members
+    keeps_class: bool,
+    annotation: Option<String>,
+    class_names: String,
+    /// Superclass or interface required
+    extends: Option<String>,
+    members: Vec<MemberSpec>,
+}
+
+impl KeepRule {
+    /// Parses a single rule, returning `None` for rules that don't prevent shrinking.
+    fn parse(text: &str) -> Option<Self> {
+        let (head, body) = match text.split_once('{') {
+            Some((head, body)) => (head, body.split('}').next().unwrap_or_default()),
+            None => (text, ""),
+        };
+        let head = head.replace(" ,", ",").replace(", ", ",");
+        let mut words = head.split_whitespace();
+        let mut options = words.next()?.split(',');
+        let keeps_class = match options.next()? {
+            "-keep" | "-keepclasseswithmembers" => true,
+            "-keepclassmembers" => false,
+            _ => return None,
+        };
+        if options.any(|option| option == "allowshrinking") {
+            return None;
+        }
+
+        let mut annotation = None;
+        let mut class_names = None;
+        let mut extends = None;
+        while let Some(word) = words.next() {
+            match word {
+                "class" | "interface" | "enum" | "@interface" => {
+                    class_names = Some(words.next()?.to_string())
+                }
+                "extends" | "implements" => {
+                    let mut name = words.next()?;
+                    if name.starts_with('@') {
+                        name = words.next()?;
+                    }
+                    extends = Some(name.to_string());
+                }
+                word if class_names.is_none() && word.starts_with('@') => {
+                    annotation = Some(word[1..].to_string())
+                }
+                _ => (),
+            }
+        }
+
+        Some(Self {
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            keeps_class,
+            annotation,
+            class_names: class_names?,
+            extends,
+            members: body.split(';').filter_map(MemberSpec::parse).collect(),
+        })
+    }
+
+    /// Checks whether the rule applies to a class.
+    pub fn matches_class(&self, class: &Class) -> bool {
+        matches_list(&self.class_names, &class.class_type.get_name())
+            && self
+                .annotation
+                .as_ref()
+                .is_none_or(|annotation| has_annotation(&class.annotations, annotation))
+            && self.extends.as_ref().is_none_or(|extends| {
+                class
+                    .super_class
+                    .iter()
+                    .chain(&class.interfaces)
+                    .any(|parent| matches_list(extends, &parent.get_name()))
+            })
+    }
+
+    fn keeps_member(
+        &self,
+        kind: MemberKind,
+        name: &str,
+        flags: &[AccessFlag],
+        annotations: &[Annotation],
+    ) -> bool {
+        self.members
+            .iter()
+            .any(|spec| spec.matches(kind, name, flags, annotations))
+    }
+}
+
+/// Parses the keep rules from a ProGuard or R8 configuration file, other options are ignored.
+pub fn parse_rules(data: &str) -> Vec<KeepRule> {
+    let mut rules = Vec::new();
+    let mut current = String::new();
+    for line in data.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('-') {
+            rules.extend(KeepRule::parse(&current));
+            current.clear();
+        }
+        current.push_str(line);
+        current.push(' ');
+    }
+    rules.extend(KeepRule::parse(&current));
+    rules
+}
+
+/// Classes and members kept by `@Keep` annotations or keep rules that code never refers to.
+/// These are either accessed via reflection or leftovers of overly broad rules.
+#[derive(Debug, Default)]
+pub struct KeptReport {
+    rules: Vec<KeepRule>,
+    /// Kept classes along with the reason
+    classes: Vec<(Type, String)>,
+    /// Kept members along with their class and the reason
+    members: Vec<(Type, Member, String)>,
+    references: References,
+}
+
+impl KeptReport {
+    pub fn new(rules: Vec<KeepRule>) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        self.add_declarations(class);
+        self.add_references(class);
+    }
+
+    /// Records the kept public and protected members of a class, along with the class itself
+    /// if kept.
+    pub fn add_declarations(&mut self, class: &Class) {
+        let annotated = has_annotation(&class.annotations, &KEEP_ANNOTATIONS.join(","));
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches_class(class))
+            .collect::<Vec<_>>();
+        let class_reason = if annotated {
+            Some("@Keep".to_string())
+        } else {
+            rules
+                .iter()
+                .find(|rule| rule.keeps_class)
+                .map(|rule| rule.text.clone())
+        };
+        if let Some(reason) = class_reason {
+            self.classes.push((class.class_type.clone(), reason));
+        }
+
+        let get_reason = |kind, name: &str, flags: &[AccessFlag], annotations: &[Annotation]| {
+            if flags.contains(&AccessFlag::Private)
+                || flags.contains(&AccessFlag::Synthetic)
+                || name == "<clinit>"
+            {
+                None
+            } else if annotated || has_annotation(annotations, &KEEP_ANNOTATIONS.join(",")) {
+                Some("@Keep".to_string())
+            } else {
+                rules
+                    .iter()
+                    .find(|rule| rule.keeps_member(kind, name, flags, annotations))
+                    .map(|rule| rule.text.clone())
+            }
+        };
+        for field in &class.fields {
+            if let Some(reason) = get_reason(
+                MemberKind::Field,
+                &field.name,
+                &field.visibility,
+                &field.annotations,
+            ) {
+                let member = Member::Field {
+                    signature: FieldSignature {
+                        object_type: class.class_type.clone(),
+                        field_name: field.name.clone(),
+                        field_type: field.field_type.clone(),
+                    },
+                    private: false,
+                };
+                self.members
+                    .push((class.class_type.clone(), member, reason));
+            }
+        }
+        for method in &class.methods {
+            if let Some(reason) = get_reason(
+                MemberKind::Method,
+                &method.name,
+                &method.visibility,
+                &method.annotations,
+            ) {
+                let member = Member::Method {
+                    signature: method.get_signature(&class.class_type),
+                    private: false,
+                };
+                self.members
+                    .push((class.class_type.clone(), member, reason));
+            }
+        }
+    }
+
+    /// Records the classes and members a class refers to.
+    pub fn add_references(&mut self, class: &Class) {
+        self.references.add_class(class);
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let classes = self
+            .classes
+            .iter()
+            .filter(|(class_type, _)| !self.references.contains_type(class_type))
+            .collect::<Vec<_>>();
+        if !classes.is_empty() {
+            writeln!(output, "Kept classes never referenced:")?;
+        }
+        for (class_type, reason) in &classes {
+            writeln!(output, "    {} ({reason})", class_type.get_name())?;
+        }
+
+        // Members of unreferenced classes are unreferenced as well, no point listing them
+        let members = self
+            .members
+            .iter()
+            .filter(|(class_type, member, _)| {
+                !classes.iter().any(|(listed, _)| listed == class_type)
+                    && !self.references.contains_member(member)
+            })
+            .collect::<Vec<_>>();
+        if !members.is_empty() {
+            writeln!(output, "Kept members never referenced:")?;
+        }
+        for (_, member, reason) in members {
+            writeln!(output, "    <{member}> ({reason})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_list("com.example.*", "com.example.Main"));
+        assert!(!matches_list("com.example.*", "com.example.sub.Main"));
+        assert!(matches_list("com.example.**", "com.example.sub.Main"));
+        assert!(matches_list("com.example.Ma?n", "com.example.Main"));
+        assert!(!matches_list(
+            "!com.example.Main,com.example.*",
+            "com.example.Main"
+        ));
+        assert!(matches_list(
+            "!com.example.Main,com.example.*",
+            "com.example.Other"
+        ));
+    }
+
+    #[test]
+    fn kept_report() -> Result<(), ParseErrorDisplayed> {
+        let rules = parse_rules(
+            r#"
+                # Models are serialized via reflection
+                -keep class com.example.model.** { *; }
+                -keepclassmembers class * extends android.app.Activity {
+                    public void on*Click(android.view.View);
+                }
+                -keepnames class com.example.Named
+                -keep,allowshrinking class com.example.Shrunk
+                -dontwarn okhttp3.**
+            "#,
+        );
+        assert_eq!(
+            rules.iter().map(|rule| &rule.text[..]).collect::<Vec<_>>(),
+            vec![
+                "-keep class com.example.model.** { *; }",
+                "-keepclassmembers class * extends android.app.Activity { public void on*Click(android.view.View); }",
+            ]
+        );
+
+        let model = read_optimized_class(
+            r#"
+                .class public Lcom/example/model/User;
+                .super Ljava/lang/Object;
+
+                .field public name:Ljava/lang/String;
+
+                .field public age:I
+            "#,
+        )?;
+        let unused_model = read_optimized_class(
+            r#"
+                .class public Lcom/example/model/Legacy;
+                .super Ljava/lang/Object;
+
+                .field public id:I
+            "#,
+        )?;
+        let activity = read_optimized_class(
+            r#"
+                .class public Lcom/example/MainActivity;
+                .super Landroid/app/Activity;
+
+                .method public onButtonClick(Landroid/view/View;)V
+                    .locals 2
+                    new-instance v0, Lcom/example/model/User;
+                    iget-object v1, v0, Lcom/example/model/User;->name:Ljava/lang/String;
+                    return-void
+                .end method
+
+                .method public onCreate(Landroid/os/Bundle;)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public helper()V
+                    .annotation build Landroidx/annotation/Keep;
+                    .end annotation
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut report = KeptReport::new(rules);
+        report.add_class(&model);
+        report.add_class(&unused_model);
+        report.add_class(&activity);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"Kept classes never referenced:
+    com.example.model.Legacy (-keep class com.example.model.** { *; })
+Kept members never referenced:
+    <int com.example.model.User.age> (-keep class com.example.model.** { *; })
+    <void com.example.MainActivity.onButtonClick(android.view.View)> (-keepclassmembers class * extends android.app.Activity { public void on*Click(android.view.View); })
+    <void com.example.MainActivity.helper()> (@Keep)
+"#
+        );
+
+        Ok(())
+    }
+}
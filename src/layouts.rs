@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::r#type::Type;
+use crate::xml::get_attribute;
+
+/// Type of the single parameter taken by `android:onClick` handlers.
+const VIEW_CLASS: &str = "android.view.View";
+
+/// Tags referencing a class through an attribute rather than the tag name, along with the
+/// attributes to check.
+const CLASS_ATTRIBUTES: [(&str, &[&str]); 3] = [
+    ("view", &["class"]),
+    ("fragment", &["android:name", "class"]),
+    (
+        "androidx.fragment.app.FragmentContainerView",
+        &["android:name", "class"],
+    ),
+];
+
+/// Classes and click handlers referenced from layout files, so that code only invoked via
+/// resources can be connected to the UI.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LayoutReferences {
+    /// Handler method names mapped to the layouts referencing them
+    handlers: BTreeMap<String, BTreeSet<String>>,
+    /// Class names mapped to the layouts referencing them
+    classes: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LayoutReferences {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the layout files decoded by apktool, these are located in the `res/layout*`
+    /// directories. Nothing is found if resources weren't decoded.
+    pub fn read(dir: &Path) -> Self {
+        let mut result = Self::new();
+        let Ok(entries) = std::fs::read_dir(dir.join("res")) else {
+            return result;
+        };
+        let mut layout_dirs = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("layout"))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        layout_dirs.sort();
+
+        for layout_dir in layout_dirs {
+            let Ok(entries) = std::fs::read_dir(&layout_dir) else {
+                continue;
+            };
+            let mut files = entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "xml"))
+                .collect::<Vec<_>>();
+            files.sort();
+            for path in files {
+                match std::fs::read_to_string(&path) {
+                    Ok(data) => {
+                        let relative_path = path.strip_prefix(dir).unwrap_or(&path);
+                        let name = relative_path
+                            .components()
+                            .map(|component| component.as_os_str().to_string_lossy())
+                            .collect::<Vec<_>>()
+                            .join("/");
+                        result.add_layout(&name, &data);
+                    }
+                    Err(error) => eprintln!("Failed reading {}: {error}", path.display()),
+                }
+            }
+        }
+        result
+    }
+
+    /// Records the references found in a layout file.
+    pub fn add_layout(&mut self, name: &str, data: &str) {
+        for tag in data.split('<').skip(1) {
+            let tag = tag.split_once('>').map_or(tag, |(tag, _)| tag);
+            if tag.starts_with(['/', '?', '!']) {
+                continue;
+            }
+            let tag_name = tag
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or_default();
+
+            // Custom views are referenced by their fully qualified class name
+            let mut classes = Vec::new();
+            if tag_name.contains('.') {
+                classes.push(tag_name.to_string());
+            }
+            if let Some((_, attributes)) =
+                CLASS_ATTRIBUTES.iter().find(|(name, _)| *name == tag_name)
+            {
+                classes.extend(
+                    attributes
+                        .iter()
+                        .find_map(|attribute| get_attribute(tag, attribute)),
+                );
+            }
+            for class in classes {
+                self.classes
+                    .entry(class)
+                    .or_default()
+                    .insert(name.to_string());
+            }
+
+            // Data binding expressions like `@{...}` aren't method names
+            if let Some(handler) = get_attribute(tag, "android:onClick") {
+                if !handler.is_empty() && !handler.starts_with('@') {
+                    self.handlers
+                        .entry(handler)
+                        .or_default()
+                        .insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty() && self.classes.is_empty()
+    }
+
+    /// Checks whether applying the references would change the class.
+    pub fn affects(&self, class: &Class) -> bool {
+        self.classes
+            .contains_key(class.class_type.get_name().as_ref())
+            || class
+                .methods
+                .iter()
+                .any(|method| self.handlers.contains_key(&method.name))
+    }
+
+    /// Adds comments to the class and its click handlers if these are referenced from layouts.
+    /// Handlers are matched by name and signature, the layout doesn't say which activity uses
+    /// it.
+    pub fn apply(&self, class: &mut Class) {
+        if let Some(layouts) = self.classes.get(class.class_type.get_name().as_ref()) {
+            class.comments.push(format_comment(layouts));
+        }
+        for method in &mut class.methods {
+            let Some(layouts) = self.handlers.get(&method.name) else {
+                continue;
+            };
+            let is_handler = method.return_type == Type::Void
+                && method.parameters.len() == 1
+                && method.parameters[0].parameter_type.get_name() == VIEW_CLASS
+                && !method.visibility.contains(&AccessFlag::Static);
+            if is_handler {
+                method.comments.push(format_comment(layouts));
+            }
+        }
+    }
+}
+
+fn format_comment(layouts: &BTreeSet<String>) -> String {
+    let layouts = layouts.iter().cloned().collect::<Vec<_>>();
+    format!("referenced from {}", layouts.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn layout_references() -> Result<(), ParseErrorDisplayed> {
+        let mut references = LayoutReferences::new();
+        references.add_layout(
+            "res/layout/activity_main.xml",
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<LinearLayout xmlns:android="http://schemas.android.com/apk/res/android">
+    <!-- Custom views -->
+    <com.example.ui.ChartView android:id="@id/chart" />
+    <view class="com.example.ui.Gauge" />
+    <Button android:onClick="onSubmit" android:text="@string/submit" />
+    <Button android:onClick="@{() -> model.cancel()}" />
+    <fragment android:name="com.example.ui.ListFragment" />
+</LinearLayout>"#,
+        );
+        references.add_layout(
+            "res/layout-land/activity_main.xml",
+            r#"<Button android:onClick="onSubmit"/>"#,
+        );
+
+        let input = Tokenizer::new(
+            r#"
+                .class public Lcom/example/ui/ChartView;
+                .super Landroid/view/View;
+
+                .method public onSubmit(Landroid/view/View;)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public onSubmit(I)V
+                    .locals 0
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        references.apply(&mut class);
+
+        let mut output = Vec::new();
+        class
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"// referenced from res/layout/activity_main.xml
+public class com.example.ui.ChartView extends android.view.View
+{
+    // referenced from res/layout-land/activity_main.xml, res/layout/activity_main.xml
+    public void onSubmit(android.view.View @p0)
+    {
+        return;
+    }
+
+    public void onSubmit(int @p0)
+    {
+        return;
+    }
+}
+"#
+        );
+
+        assert_eq!(
+            references.classes.keys().collect::<Vec<_>>(),
+            vec![
+                "com.example.ui.ChartView",
+                "com.example.ui.Gauge",
+                "com.example.ui.ListFragment"
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Parser for Smali code as produced by apktool and writer turning it into more readable Jimple
+//! code. The command line interface is built on top of this library, other analysis tools can
+//! embed it to work with the same class model.
+//!
+//! ```
+//! use aarf::{Class, OutputOptions, Tokenizer};
+//!
+//! let input = Tokenizer::new(
+//!     ".class public La;\n.super Ljava/lang/Object;".to_string(),
+//!     std::path::Path::new("a.smali"),
+//! );
+//! let (_, mut class) = Class::read(&input).unwrap();
+//! class.optimize();
+//!
+//! let mut output = Vec::new();
+//! class.write_jimple(&mut output, &OutputOptions::default()).unwrap();
+//! assert!(String::from_utf8(output).unwrap().contains("public class a"));
+//! ```
+
+#![deny(elided_lifetimes_in_paths)]
+#![deny(explicit_outlives_requirements)]
+#![deny(keyword_idents)]
+#![deny(meta_variable_misuse)]
+#![deny(missing_debug_implementations)]
+#![deny(non_ascii_idents)]
+#![warn(noop_method_call)]
+#![deny(single_use_lifetimes)]
+#![deny(trivial_casts)]
+#![deny(trivial_numeric_casts)]
+#![deny(unsafe_code)]
+#![warn(unused_crate_dependencies)]
+#![deny(unused_import_braces)]
+#![deny(unused_lifetimes)]
+#![warn(unused_macro_rules)]
+#![deny(variant_size_differences)]
+
+pub mod access_flag;
+pub mod annotation;
+pub mod argument_names;
+pub mod bitmasks;
+pub mod cache;
+pub mod callgraph;
+pub mod class;
+#[cfg(feature = "pipeline")]
+pub mod commands;
+pub mod constants;
+pub mod debug_code;
+pub mod deep_links;
+pub mod dex;
+pub mod dex_budget;
+pub mod diagnostics;
+pub mod doc;
+pub mod dynamic_code;
+pub mod endpoints;
+pub mod error;
+pub mod features;
+pub mod field;
+pub mod format;
+pub mod implementations;
+pub mod index;
+pub mod instruction;
+pub mod interrupt;
+pub mod keep_rules;
+pub mod layouts;
+pub mod literal;
+pub mod method;
+pub mod output;
+pub mod overrides;
+pub mod payload;
+pub mod permissions;
+#[cfg(feature = "pipeline")]
+pub mod pipeline;
+pub mod protections;
+pub mod rewrite_rules;
+pub mod schema;
+pub mod scope;
+pub mod search;
+pub mod sql;
+pub mod stack_trace;
+pub mod stats;
+pub mod strings;
+pub mod suspicious;
+pub mod tags;
+pub mod tokenizer;
+pub mod r#type;
+pub mod unused;
+pub mod webview;
+pub mod xml;
+pub mod xrefs;
+
+pub use class::Class;
+pub use error::ParseError;
+pub use field::Field;
+pub use instruction::{CommandParameter, Instruction, Register};
+pub use literal::Literal;
+pub use method::Method;
+pub use output::OutputOptions;
+pub use r#type::{FieldSignature, MethodSignature, Type};
+pub use tokenizer::Tokenizer;
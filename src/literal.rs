@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::error::ParseError;
 use crate::r#type::{CallSignature, MethodSignature, Type};
@@ -16,7 +17,8 @@ pub enum Literal {
     Long(i64),
     Float(f32),
     Double(f64),
-    String(String),
+    /// String value with Smali escapes
+    String(Arc<str>),
     Class(Type),
     Method(MethodSignature),
     MethodHandle(String, MethodSignature),
@@ -55,7 +57,7 @@ impl Literal {
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
         Ok(if let Ok(input) = input.expect_char('"') {
             let (input, value) = read_escaped(&input, '"')?;
-            (input, Self::String(value))
+            (input, Self::String(value.into()))
         } else if let Ok(input) = input.expect_char('\'') {
             let start = &input;
             let (input, value) = read_escaped(&input, '\'')?;
@@ -70,8 +72,14 @@ impl Literal {
             } else {
                 return Err(start.unexpected("a character literal".into()));
             };
-            (input, Self::Char(char.try_into().map_err(|_| start.unexpected("a character literal".into()))?))
-        } else if input.expect_char('(').is_ok() {
+            (
+                input,
+                Self::Char(
+                    char.try_into()
+                        .map_err(|_| start.unexpected("a character literal".into()))?,
+                ),
+            )
+        } else if input.peek_char() == Some('(') {
             let (input, call) = CallSignature::read(input)?;
             (input, Self::MethodType(call))
         } else {
@@ -132,9 +140,9 @@ impl Literal {
                         .map_err(|_| start.unexpected("an integer literal".into()))?;
                     (input, Self::Int(number))
                 }
-            } else if let Ok((input, method)) = MethodSignature::read(start) {
+            } else if let Some((input, method)) = start.attempt(MethodSignature::read) {
                 (input, Self::Method(method))
-            } else if let Ok((input, class)) = Type::read(start) {
+            } else if let Some((input, class)) = start.attempt(Type::read) {
                 (input, Self::Class(class))
             } else {
                 return Err(start.unexpected("a literal".into()));
@@ -203,7 +211,7 @@ impl Literal {
 
     pub fn get_string(&self) -> Option<String> {
         match self {
-            Self::String(value) => Some(value.clone()),
+            Self::String(value) => Some(value.to_string()),
             _ => None,
         }
     }
@@ -223,6 +231,104 @@ impl Literal {
     pub fn is_method_type(&self) -> bool {
         matches!(self, Self::MethodType(_))
     }
+
+    /// Creates a string literal from a decoded value, escaping it like Smali does.
+    pub fn escaped_string(value: &str) -> Self {
+        let mut result = String::new();
+        for c in value.chars() {
+            match c {
+                '"' => result.push_str("\\\""),
+                '\\' => result.push_str("\\\\"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                ' '..='~' => result.push(c),
+                c => {
+                    let mut buffer = [0; 2];
+                    for unit in c.encode_utf16(&mut buffer) {
+                        result.push_str(&format!("\\u{unit:04x}"));
+                    }
+                }
+            }
+        }
+        Self::String(result.into())
+    }
+
+    /// Converts an `.array-data` element to the integer type matching the element size,
+    /// truncating the value if necessary. Floating point values are only accepted for element
+    /// sizes 4 and 8.
+    pub fn with_element_size(&self, size: usize) -> Option<Self> {
+        if let Some(value) = self.get_integer() {
+            match size {
+                1 => Some(Self::Byte(value as i8)),
+                2 => Some(Self::Short(value as i16)),
+                4 => Some(Self::Int(value as i32)),
+                8 => Some(Self::Long(value)),
+                _ => None,
+            }
+        } else {
+            match (self, size) {
+                (Self::Float(value), 4) => Some(Self::Float(*value)),
+                (Self::Double(value), 8) => Some(Self::Double(*value)),
+                (Self::Float(value), 8) => Some(Self::Double(*value as f64)),
+                (Self::Double(value), 4) => Some(Self::Float(*value as f32)),
+                _ => None,
+            }
+        }
+    }
+
+    /// Reinterprets an `.array-data` element according to the element type of the array it is
+    /// stored in, e.g. bit patterns of floating point values.
+    pub fn with_element_type(&self, element_type: &Type) -> Self {
+        let Some(value) = self.get_integer() else {
+            return self.clone();
+        };
+        match element_type {
+            Type::Bool => Self::Bool(value != 0),
+            Type::Byte => Self::Byte(value as i8),
+            Type::Char => Self::Char(value as u16),
+            Type::Short => Self::Short(value as i16),
+            Type::Int => Self::Int(value as i32),
+            Type::Long => Self::Long(value),
+            Type::Float => Self::Float(f32::from_bits(value as u32)),
+            Type::Double => Self::Double(f64::from_bits(value as u64)),
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Formats a floating point value such that Smali recognizes it, the decimal point is required.
+fn float_to_smali(value: String) -> String {
+    match value.as_str() {
+        "inf" => "Infinity".to_string(),
+        "-inf" => "-Infinity".to_string(),
+        "NaN" => value,
+        _ if value.contains('.') => value,
+        _ => match value.split_once('e') {
+            Some((mantissa, exponent)) => format!("{mantissa}.0e{exponent}"),
+            None => format!("{value}.0"),
+        },
+    }
+}
+
+impl Literal {
+    /// Returns the literal in Smali notation, with type suffixes where necessary.
+    pub fn to_smali(&self) -> String {
+        match self {
+            Self::Byte(_) => format!("{self}t"),
+            Self::Short(_) => format!("{self}s"),
+            Self::Long(_) => format!("{self}L"),
+            Self::Float(value) => float_to_smali(format!("{value:?}")) + "f",
+            Self::Double(value) => float_to_smali(format!("{value:?}")),
+            Self::Class(class) => class.to_descriptor(),
+            Self::Method(method) => method.to_smali(),
+            Self::MethodHandle(invoke_type, method) => {
+                format!("{invoke_type}@{}", method.to_smali())
+            }
+            Self::MethodType(method_type) => method_type.to_descriptor(),
+            _ => self.to_string(),
+        }
+    }
 }
 
 impl Display for Literal {
@@ -318,7 +424,7 @@ mod tests {
     fn read_string() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(r#" "a\"b c\\" "#);
         let (_, literal) = Literal::read(&input)?;
-        assert_eq!(literal, Literal::String(r#"a\"b c\\"#.to_string()));
+        assert_eq!(literal, Literal::String(r#"a\"b c\\"#.into()));
 
         let input = tokenizer(r#" "a\"b c\\ "#);
         assert!(Literal::read(&input).is_err());
@@ -387,6 +493,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn escaped_string() {
+        assert_eq!(
+            Literal::escaped_string("a\"b\\c\n\u{e4}\u{1f600}"),
+            Literal::String(r#"a\"b\\c\n\u00e4\ud83d\ude00"#.into())
+        );
+    }
+
+    #[test]
+    fn array_elements() {
+        assert_eq!(
+            Literal::Int(0xff).with_element_size(1),
+            Some(Literal::Byte(-1))
+        );
+        assert_eq!(
+            Literal::Byte(-1).with_element_size(8),
+            Some(Literal::Long(-1))
+        );
+        assert_eq!(Literal::Int(1).with_element_size(3), None);
+        assert_eq!(Literal::String("a".into()).with_element_size(4), None);
+
+        assert_eq!(
+            Literal::Short(0x61).with_element_type(&Type::Char),
+            Literal::Char('a' as u16)
+        );
+        assert_eq!(
+            Literal::Int(0x3f800000).with_element_type(&Type::Float),
+            Literal::Float(1.0)
+        );
+        assert_eq!(
+            Literal::Long(0x4000000000000000).with_element_type(&Type::Double),
+            Literal::Double(2.0)
+        );
+        assert_eq!(
+            Literal::Byte(1).with_element_type(&Type::Bool),
+            Literal::Bool(true)
+        );
+    }
+
     #[test]
     fn read_float() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(r#" -infinity NANf infinityd .01f 2.3D .x "#);
@@ -454,9 +599,9 @@ mod tests {
         assert_eq!(format!("{}", Literal::Double(5.8)), "5.8");
         assert_eq!(format!("{}", Literal::Double(-0.1)), "-0.1");
 
-        assert_eq!(format!("{}", Literal::String("abc".to_string())), "\"abc\"");
+        assert_eq!(format!("{}", Literal::String("abc".into())), "\"abc\"");
         assert_eq!(
-            format!("{}", Literal::String("a\\tb\\\\c".to_string())),
+            format!("{}", Literal::String("a\\tb\\\\c".into())),
             "\"a\\tb\\\\c\""
         );
     }
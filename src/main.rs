@@ -5,34 +5,34 @@
 #![deny(missing_debug_implementations)]
 #![deny(non_ascii_idents)]
 #![warn(noop_method_call)]
-#![deny(pointer_structural_match)]
 #![deny(single_use_lifetimes)]
 #![deny(trivial_casts)]
 #![deny(trivial_numeric_casts)]
 #![deny(unsafe_code)]
-#![warn(unused_crate_dependencies)]
 #![deny(unused_import_braces)]
 #![deny(unused_lifetimes)]
 #![warn(unused_macro_rules)]
-#![warn(unused_tuple_struct_fields)]
 #![deny(variant_size_differences)]
 
-pub mod access_flag;
-pub mod annotation;
-pub mod class;
-pub mod error;
-pub mod field;
-pub mod instruction;
-pub mod literal;
-pub mod method;
-pub mod tokenizer;
-pub mod r#type;
-
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::class::Class;
-use crate::tokenizer::Tokenizer;
+use aarf::annotation::AnnotationVisibility;
+use aarf::commands::{method, reports, smali};
+use aarf::constants::ConstantFields;
+use aarf::features::FeatureFormat;
+use aarf::format::Format;
+use aarf::method::GraphFormat;
+use aarf::output::{OutputLayout, OutputOptions, ReportFormat};
+use aarf::pipeline::{self, Apktool, PipelineInput, PipelineOptions};
+use aarf::schema::JsonOutput;
+use aarf::scope::Scope;
+use aarf::stack_trace::Mapping;
+use aarf::xrefs::Member;
+use aarf::{diagnostics, index, instruction, interrupt};
 
 #[derive(Parser, Debug)]
 struct Args {
@@ -40,94 +40,939 @@ struct Args {
     #[arg(short, long)]
     apktool_path: Option<String>,
 
+    /// Keep whole-program indexes in temporary files rather than in memory, for very large apps.
+    /// Applies to the caller index, cross-references, the call graph and repeated strings
+    #[arg(long)]
+    index_on_disk: bool,
+
+    /// File listing the classes to process, one class or package pattern like com.example.*
+    /// per line, with ! excluding classes
+    #[arg(long, value_name = "FILE")]
+    scope_file: Option<PathBuf>,
+
+    /// Memory on-disk indexes may use before writing entries out, in megabytes
+    #[arg(long, value_name = "MB", default_value_t = index::DEFAULT_MEMORY_LIMIT >> 20)]
+    index_memory_limit: usize,
+
+    /// Print repeated warnings every time instead of once, without the summary of counts
+    #[arg(long)]
+    show_all_warnings: bool,
+
     #[command(subcommand)]
     command: ArgsCommand,
 }
 
+#[derive(clap::Args, Debug)]
+struct OutputArgs {
+    /// Print system annotations as is instead of folding them into declarations and comments
+    #[arg(long)]
+    show_system_annotations: bool,
+
+    /// Omit null checks collapsed into assert statements
+    #[arg(long)]
+    hide_assertions: bool,
+
+    /// Write inner classes into the file of their outer class instead of separate files
+    #[arg(long)]
+    nest_inner_classes: bool,
+
+    /// Declare local registers with their inferred types in the narrowest enclosing block
+    #[arg(long)]
+    declare_locals: bool,
+
+    /// Copy comments found in Smali code into the output
+    #[arg(long)]
+    show_comments: bool,
+
+    /// Precede each method by a comment with its complexity metrics
+    #[arg(long)]
+    show_complexity: bool,
+
+    /// Wrap statements longer than this many characters, putting call arguments on separate
+    /// lines
+    #[arg(long, value_name = "WIDTH")]
+    max_line_width: Option<usize>,
+
+    /// Keep constructors and static initializers as methods named <init> and <clinit> instead of
+    /// naming them after the class and writing static blocks
+    #[arg(long)]
+    raw_constructor_names: bool,
+
+    /// Omit annotations with the given visibility, e.g. --hide-annotations build,system
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "VISIBILITY")]
+    hide_annotations: Vec<AnnotationVisibility>,
+
+    /// Precede call arguments by the parameter names of the called method if the app declares
+    /// it with debug information (requires an additional pass over all files)
+    #[arg(long)]
+    argument_names: bool,
+
+    /// Precede each method by a comment listing the methods calling it (requires an additional
+    /// pass over all files)
+    #[arg(long)]
+    show_callers: bool,
+
+    /// Precede methods overriding framework callbacks like Activity.onCreate by a comment
+    /// naming the overridden method (requires an additional pass over all files)
+    #[arg(long)]
+    show_overrides: bool,
+
+    /// Point virtual and interface calls at the implementation if only one is possible in the
+    /// program, marking them as devirtualized (requires an additional pass over all files)
+    #[arg(long)]
+    devirtualize: bool,
+
+    /// Follow each method by a comment with its register count, the parameter registers and the
+    /// local registers only used as temporaries, to pick scratch registers when patching Smali
+    #[arg(long)]
+    show_registers: bool,
+
+    /// Precede methods by a comment if they contain code guarded by BuildConfig.DEBUG,
+    /// Log.isLoggable or Debug.isDebuggerConnected checks
+    #[arg(long)]
+    mark_debug_code: bool,
+
+    /// Treat BuildConfig.DEBUG as false, removing the code only running in debug builds
+    #[arg(long)]
+    assume_release: bool,
+
+    /// Append warnings to calls like Runtime.exec or System.loadLibrary, the calls listed by
+    /// the api-report command
+    #[arg(long)]
+    flag_suspicious_calls: bool,
+
+    /// Comment bitwise operations on constants with the unsigned value and, for framework
+    /// flags like Intent.FLAG_*, the flag constants making up the value
+    #[arg(long)]
+    mask_hints: bool,
+
+    /// Write integer constants as decimal instead of hexadecimal numbers
+    #[arg(long)]
+    decimal: bool,
+}
+
+impl From<&OutputArgs> for OutputOptions {
+    fn from(args: &OutputArgs) -> Self {
+        Self {
+            show_system_annotations: args.show_system_annotations,
+            hide_assertions: args.hide_assertions,
+            nest_inner_classes: args.nest_inner_classes,
+            declare_locals: args.declare_locals,
+            show_comments: args.show_comments,
+            show_complexity: args.show_complexity,
+            max_line_width: args.max_line_width,
+            raw_constructor_names: args.raw_constructor_names,
+            hidden_annotations: args.hide_annotations.clone(),
+            argument_names: args.argument_names,
+            show_callers: args.show_callers,
+            show_overrides: args.show_overrides,
+            devirtualize: args.devirtualize,
+            show_registers: args.show_registers,
+            mark_debug_code: args.mark_debug_code,
+            assume_release: args.assume_release,
+            flag_suspicious_calls: args.flag_suspicious_calls,
+            mask_hints: args.mask_hints,
+            decimal: args.decimal,
+            method_time_limit: None,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct PipelineArgs {
+    /// Dump dex and jar files embedded into code or assets into the payloads directory
+    #[arg(long)]
+    extract_payloads: bool,
+
+    /// Decompile extracted payloads as well (implies --extract-payloads)
+    #[arg(long)]
+    decompile_payloads: bool,
+
+    /// Replace constants passed to @IntDef and @StringDef parameters by named fields (requires
+    /// an additional pass over all files)
+    #[arg(long)]
+    resolve_typedefs: bool,
+
+    /// Don't replace literals passed to well-known framework methods (e.g. View.setVisibility
+    /// or Cipher.getInstance) by the names of framework constants
+    #[arg(long)]
+    no_framework_constants: bool,
+
+    /// Replace reads of `static final` fields with known values by the value ("inline") or
+    /// literals by a constant of the same class ("names"), requires an additional pass over
+    /// all files
+    #[arg(long, value_enum, value_name = "DIRECTION")]
+    constant_fields: Option<ConstantFields>,
+
+    /// Convert the Smali files of each dex file while apktool is still disassembling the next one
+    #[arg(long)]
+    overlap_apktool: bool,
+
+    /// Read the dex files of the APK directly instead of running apktool, so that neither
+    /// apktool nor Java are needed. Resources aren't decoded then. Always used for .dex input.
+    #[arg(long)]
+    native_dex: bool,
+
+    /// Don't let apktool decode resources (apktool --no-res)
+    #[arg(long)]
+    no_res: bool,
+
+    /// Don't let apktool disassemble the dex files, leaving no Smali code to convert (apktool
+    /// --no-src)
+    #[arg(long)]
+    no_src: bool,
+
+    /// Directory of the framework files used by apktool (apktool --frame-path)
+    #[arg(long, value_name = "DIR")]
+    frame_path: Option<PathBuf>,
+
+    /// API level of the Smali code produced by apktool (apktool --api-level)
+    #[arg(long, value_name = "LEVEL")]
+    api_level: Option<u32>,
+
+    /// Additional parameter to pass on to apktool decode, e.g. --apktool-arg=--only-main-classes
+    /// (can be repeated)
+    #[arg(long = "apktool-arg", value_name = "ARG", allow_hyphen_values = true)]
+    apktool_args: Vec<String>,
+
+    /// Number of Smali files to convert in parallel, all CPU cores by default
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Continue an interrupted run: keep the apktool output and skip files converted already
+    /// with the same options
+    #[arg(long)]
+    resume: bool,
+
+    /// Continue converting the remaining files when a Smali file fails to parse, summarizing
+    /// the errors at the end
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Keep lines of method code that fail to parse, e.g. unknown commands, as comments in the
+    /// output instead of failing the entire file. Methods containing such lines are left
+    /// unoptimized
+    #[arg(long)]
+    lenient: bool,
+
+    /// Leave methods unoptimized if optimizing them takes longer than this many milliseconds,
+    /// also don't declare locals if inferring their types does, 0 for no limit
+    #[arg(long, value_name = "MS", default_value_t = 10000)]
+    method_time_limit: u64,
+
+    /// TOML file (JSON with the .json extension) with rules replacing calls to matching
+    /// methods by expressions in the output, e.g. string decryption calls by a readable macro.
+    /// Each [[rule]] table has a `match` key with a pattern like
+    /// `com.example.a.b(java.lang.String)` where `*` matches anything and a `replace` key with
+    /// the expression, `{0}`, `{1}` and so on standing for the arguments, preceded by the
+    /// instance for non-static calls
+    #[arg(long, value_name = "FILE")]
+    rewrite_rules: Option<PathBuf>,
+
+    /// Write gzip-compressed output files, e.g. .jimple.gz
+    #[arg(long)]
+    compress: bool,
+
+    /// Split classes with more than this many methods into several files (Foo.jimple with the
+    /// fields and an index, Foo.part1.jimple and so on with the methods)
+    #[arg(long, value_name = "COUNT")]
+    split_methods: Option<usize>,
+
+    /// Remove this package directory from output paths, so that the app's own packages are at
+    /// the top of the tree, e.g. --strip-prefix com/example/app
+    #[arg(long, value_name = "PACKAGE")]
+    strip_prefix: Option<String>,
+
+    /// Directory to cache converted library classes like androidx or Kotlin runtime in, shared
+    /// between runs on different apps
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<PathBuf>,
+
+    /// Format of the output files
+    #[arg(long, value_enum, default_value_t = Format::Jimple)]
+    format: Format,
+
+    /// Write all output files of a dex file into a single directory, named after the full class
+    /// name like com.example.app.MainActivity.jimple
+    #[arg(long)]
+    flatten: bool,
+}
+
 #[derive(Subcommand, Debug)]
 enum ArgsCommand {
     /// Decompile APK into Jimple code
     Decompile {
+        /// APK or dex file, or a directory of Smali files e.g. produced by apktool or baksmali
+        /// earlier
         apk_path: PathBuf,
         output_dir: PathBuf,
+
+        #[command(flatten)]
+        pipeline_args: PipelineArgs,
+
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// Convert Smali files already disassembled, e.g. by an earlier apktool run, into Jimple
+    /// code written next to them
+    Convert {
+        /// Directory produced by apktool, a single Smali file, or - to read Smali code from
+        /// standard input and write the output to standard output
+        path: PathBuf,
+
+        #[command(flatten)]
+        pipeline_args: PipelineArgs,
+
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// Print the Jimple code of a single method from a directory of Smali files
+    ExtractMethod {
+        /// Method signature in Smali or Jimple notation, e.g. Lcom/example/Main;->run(I)V or
+        /// "<void com.example.Main.run(int)>"
+        method: String,
+
+        /// Directory produced by apktool or the decompile command
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// Annotate each frame of a Java stack trace with the decompiled code of the line
+    StackTrace {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// File containing the stack trace, read from standard input if omitted
+        trace: Option<PathBuf>,
+
+        /// R8 or ProGuard mapping file to translate obfuscated names
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// List requested permissions along with the code calling methods that require them
+    Permissions {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List the commands calling a method or accessing a field, along with the method and line
+    /// containing them
+    Xref {
+        /// Method or field signature in Smali or Jimple notation, e.g.
+        /// Lcom/example/Main;->run(I)V or Lcom/example/Main;->count:I
+        member: String,
+
+        /// Directory produced by apktool or the decompile command
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Search the code for calls, field accesses, literals or annotations rather than text,
+    /// listing the class, method and line of each match. Patterns can contain * wildcards.
+    Search {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Find calls of methods matching the pattern, e.g. com.example.Main.run(int) or
+        /// javax.crypto.Cipher.* (can be repeated)
+        #[arg(long = "call", value_name = "PATTERN")]
+        calls: Vec<String>,
+
+        /// Find reads and writes of fields matching the pattern, e.g. com.example.Main.count
+        /// (can be repeated)
+        #[arg(long = "field", value_name = "PATTERN")]
+        fields: Vec<String>,
+
+        /// Find literals matching the regular expression, string literals are matched without
+        /// quotes (can be repeated)
+        #[arg(long = "literal", value_name = "REGEX")]
+        literals: Vec<String>,
+
+        /// Find classes, fields, methods and parameters with annotations matching the pattern
+        /// (can be repeated)
+        #[arg(long = "annotation", value_name = "PATTERN")]
+        annotations: Vec<String>,
+    },
+    /// Print the dominator and post-dominator trees of a method's basic blocks (for debugging)
+    Dominators {
+        /// Method signature in Smali or Jimple notation, e.g. Lcom/example/Main;->run(I)V or
+        /// "<void com.example.Main.run(int)>"
+        method: String,
+
+        /// Directory produced by apktool or the decompile command
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Print the commands of a method as parsed from Smali along with their stable IDs as JSON,
+    /// for patch scripts referring to individual instructions
+    Instructions {
+        /// Method signature in Smali or Jimple notation, e.g. Lcom/example/Main;->run(I)V or
+        /// "<void com.example.Main.run(int)>"
+        method: String,
+
+        /// Directory produced by apktool or the decompile command
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Print the public and protected API of the classes without method bodies
+    Api {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Only include classes from this package and its subpackages (can be repeated)
+        #[arg(long = "package", value_name = "PACKAGE")]
+        packages: Vec<String>,
+
+        #[command(flatten)]
+        output_args: OutputArgs,
+    },
+    /// Write a tags file in the universal-ctags format for the Jimple files produced by the
+    /// decompile command, so that editors can jump to definitions
+    Tags {
+        /// Directory produced by the decompile command, the tags file is written into it
+        dir: PathBuf,
+    },
+    /// Parse Smali files and write them back as Smali without any optimizations, to verify that
+    /// parsing doesn't lose information (for debugging)
+    RoundTrip {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Directory to write the Smali files to
+        output_dir: PathBuf,
+    },
+    /// Rewrite Smali files in canonical formatting, e.g. for hand-written patches
+    Fmt {
+        /// Smali files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Only list the files that aren't formatted canonically, failing if there are any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Parse Smali files without writing any output, listing the files that failed to parse as
+    /// path:line:column: message
+    Check {
+        /// Smali files or directories containing them
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print per-method features (opcode counts, API calls, string hashes, control flow metrics)
+    Features {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = FeatureFormat::Csv)]
+        format: FeatureFormat,
+    },
+    /// List URIs accepted by the app's intent filters along with the handling components, the
+    /// query parameters they read and matching URIs parsed in code
+    DeepLinks {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List URL constants along with the HTTP client calls receiving them and Retrofit endpoints
+    Endpoints {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List JavaScript bridges added to WebViews, the methods they expose and settings enabling
+    /// JavaScript or file access
+    Webview {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List methods implementing certificate pinning, custom certificate validation, root or
+    /// emulator detection
+    Protections {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List SQL queries reconstructed from string concatenation, flagging those containing
+    /// user-controlled data
+    SqlQueries {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List dex class loaders created along with the paths loaded and classes loaded by name,
+    /// places where static analysis of the app ends
+    DynamicCode {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Write the loader graph in DOT format instead of a list
+        #[arg(long)]
+        graph: bool,
+    },
+    /// List calls that warrant a closer look, such as executing shell commands or loading
+    /// native code
+    ApiReport {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// List the classes implementing each interface of the program along with the methods
+    /// constructing them, to follow invoke-interface calls
+    Implementations {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List private and package-private members never referenced, separating out those whose
+    /// name appears in a string and might be used via reflection
+    UnusedMembers {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+    },
+    /// List classes and public members kept by @Keep annotations or shrinker rules yet never
+    /// referenced, telling API used via reflection apart from leftovers of broad keep rules
+    KeptMembers {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// ProGuard or R8 configuration with keep rules, e.g. proguard-rules.pro
+        #[arg(long)]
+        rules: Option<PathBuf>,
+    },
+    /// List the strings loaded by const-string commands along with the method and line loading
+    /// them
+    Strings {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Only list strings matching this regular expression
+        #[arg(long, value_name = "REGEX")]
+        filter: Option<String>,
+    },
+    /// List the string constants repeated most often, frequently encryption keys, endpoints or
+    /// log tags
+    RepeatedStrings {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Maximal number of strings to list
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+    },
+    /// Print the call graph of the program in DOT format, with the methods of each class grouped
+    /// into a cluster, or as JSON
+    Callgraph {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Only include calls between classes from this package and its subpackages (can be
+        /// repeated)
+        #[arg(long = "package", value_name = "PACKAGE")]
+        packages: Vec<String>,
+
+        /// Point virtual and interface calls at the implementation if only one is possible in
+        /// the program (requires an additional pass over all files)
+        #[arg(long)]
+        devirtualize: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+    },
+    /// Print the method and field reference counts of each dex file against the 64K limit, along
+    /// with the packages contributing most references
+    DexBudget {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Number of package name components to group references by
+        #[arg(long, default_value_t = 2)]
+        depth: usize,
+
+        /// Maximal number of packages to list per dex file
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Print counts of classes, members and instructions along with opcode and access flag
+    /// histograms, the largest and the most complex methods, e.g. to assess obfuscation
+    Stats {
+        /// Directory produced by apktool or the decompile command
+        dir: PathBuf,
+
+        /// Maximal number of largest and most complex methods to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Print the supported Smali commands with their opcodes, parameter kinds, Jimple format
+    /// and result type as JSON
+    #[command(hide = true)]
+    Definitions,
+    /// Print the JSON Schema of a machine-readable output, to validate it and detect format
+    /// changes between aarf versions via its schema_version property
+    Schema {
+        /// Output to print the schema of
+        #[arg(value_enum)]
+        output: JsonOutput,
     },
 }
 
-fn locate_apktool(apktool_path: Option<String>) -> std::process::Command {
-    if let Some(apktool_path) = apktool_path {
-        if apktool_path.ends_with(".jar") {
-            if let Ok(java_path) = which::which("java") {
-                let mut command = std::process::Command::new(java_path);
-                command.arg("-jar").arg(apktool_path);
-                command
-            } else {
-                eprintln!("Supposed to run apktool as JAR file, yet Java could not be found. Is it installed?");
-                std::process::exit(1);
-            }
-        } else {
-            std::process::Command::new(apktool_path)
-        }
-    } else if let Ok(apktool_path) = which::which("apktool") {
-        std::process::Command::new(apktool_path)
-    } else {
-        eprintln!("Could not find apktool. If you installed it, please pass --apktool-path command line parameter explicitly.");
-        std::process::exit(1);
+/// Memory limit of whole-program indexes, these are kept in memory if `None`.
+fn get_index_memory_limit(args: &Args) -> Option<usize> {
+    args.index_on_disk.then_some(args.index_memory_limit << 20)
+}
+
+/// Collects the parameters of apktool decode given on the command line.
+fn get_apktool_options(pipeline_args: &PipelineArgs) -> Vec<OsString> {
+    let mut options = Vec::<OsString>::new();
+    if pipeline_args.no_res {
+        options.push("--no-res".into());
+    }
+    if pipeline_args.no_src {
+        options.push("--no-src".into());
+    }
+    if let Some(frame_path) = &pipeline_args.frame_path {
+        options.push("--frame-path".into());
+        options.push(frame_path.into());
+    }
+    if let Some(api_level) = pipeline_args.api_level {
+        options.push("--api-level".into());
+        options.push(api_level.to_string().into());
+    }
+    options.extend(pipeline_args.apktool_args.iter().map(OsString::from));
+    options
+}
+
+/// Translates the command line parameters into the options of the decompile pipeline.
+fn get_pipeline_options(
+    args: &Args,
+    pipeline_args: &PipelineArgs,
+    command_line: String,
+) -> PipelineOptions {
+    PipelineOptions {
+        apktool: Apktool {
+            path: args.apktool_path.clone(),
+            options: get_apktool_options(pipeline_args),
+        },
+        command_line,
+        extract_payloads: pipeline_args.extract_payloads,
+        decompile_payloads: pipeline_args.decompile_payloads,
+        resolve_typedefs: pipeline_args.resolve_typedefs,
+        no_framework_constants: pipeline_args.no_framework_constants,
+        constant_fields: pipeline_args.constant_fields,
+        overlap_apktool: pipeline_args.overlap_apktool,
+        native_dex: pipeline_args.native_dex,
+        jobs: pipeline_args.jobs,
+        resume: pipeline_args.resume,
+        keep_going: pipeline_args.keep_going,
+        lenient: pipeline_args.lenient,
+        method_time_limit: (pipeline_args.method_time_limit > 0)
+            .then(|| Duration::from_millis(pipeline_args.method_time_limit)),
+        rewrite_rules: pipeline_args.rewrite_rules.clone(),
+        compress: pipeline_args.compress,
+        split_methods: pipeline_args.split_methods,
+        layout: OutputLayout::new(pipeline_args.strip_prefix.as_deref(), pipeline_args.flatten),
+        cache_dir: pipeline_args.cache_dir.clone(),
+        format: pipeline_args.format,
+        index_memory_limit: get_index_memory_limit(args),
+    }
+}
+
+/// Command line recorded in the run manifest. Options that don't affect the output shouldn't
+/// prevent resuming.
+fn get_resumable_command_line() -> String {
+    let mut command_line = Vec::new();
+    let mut all_args = std::env::args().skip(1);
+    while let Some(arg) = all_args.next() {
+        if arg == "--jobs" {
+            all_args.next();
+        } else if arg != "--resume" && !arg.starts_with("--jobs=") {
+            command_line.push(arg);
+        }
     }
+    command_line.join(" ")
+}
+
+/// Terminates after a command failed, reporting the error.
+fn exit_with_error(error: String) -> ! {
+    eprintln!("{error}");
+    std::process::exit(1);
 }
 
 fn main() {
+    if cfg!(debug_assertions) {
+        let problems = instruction::validate_definitions();
+        assert!(
+            problems.is_empty(),
+            "Invalid instruction definitions: {problems:?}"
+        );
+    }
+
     let args = Args::parse();
+    diagnostics::show_all_warnings(args.show_all_warnings);
+    let scope = match &args.scope_file {
+        Some(path) => Scope::read(path).unwrap_or_else(|error| exit_with_error(error)),
+        None => Scope::default(),
+    };
+    let output = &mut std::io::stdout().lock();
 
     match &args.command {
         ArgsCommand::Decompile {
             apk_path,
             output_dir,
+            pipeline_args,
+            output_args,
         } => {
-            let status = locate_apktool(args.apktool_path)
-                .arg("decode")
-                .arg("--force")
-                .arg("--output")
-                .arg(output_dir)
-                .arg(apk_path)
-                .spawn()
-                .expect("Failed starting apktool")
-                .wait()
-                .expect("Failed waiting for apktool to finish");
-            if !status.success() {
-                eprintln!("apktool exited with an error code.");
-                std::process::exit(1);
+            // Smali files disassembled earlier don't need apktool
+            let input = if apk_path.is_dir() {
+                PipelineInput::SmaliDir(apk_path)
+            } else {
+                PipelineInput::Package(apk_path)
+            };
+            let pipeline = get_pipeline_options(&args, pipeline_args, get_resumable_command_line());
+            interrupt::install_handler();
+            let success =
+                pipeline::decompile(input, output_dir, &pipeline, &output_args.into(), &scope);
+            diagnostics::print_warning_summary();
+            if !success {
+                std::process::exit(if interrupt::is_interrupted() { 130 } else { 1 });
             }
-
-            println!("Converting Smali files to Jimple...");
-            for entry in walkdir::WalkDir::new(output_dir)
-                .into_iter()
-                .filter_map(Result::ok)
-            {
-                if !entry.file_type().is_file()
-                    || entry.path().extension().filter(|s| *s == "smali").is_none()
-                {
-                    continue;
+        }
+        ArgsCommand::Convert {
+            path,
+            pipeline_args,
+            output_args,
+        } => {
+            if path.as_os_str() == "-" {
+                let command_line = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+                let pipeline = get_pipeline_options(&args, pipeline_args, command_line);
+                let mut content = String::new();
+                if let Err(error) = std::io::stdin().read_to_string(&mut content) {
+                    exit_with_error(format!("Failed reading standard input: {error}"));
+                }
+                let result =
+                    pipeline::convert_text(content, output, &pipeline, &output_args.into(), &scope);
+                diagnostics::print_warning_summary();
+                if let Err(error) = result {
+                    exit_with_error(error);
                 }
+                return;
+            }
 
-                match Tokenizer::from_file(entry.path()) {
-                    Ok(input) => match Class::read(&input) {
-                        Ok((_, mut class)) => {
-                            let target = entry.path().with_extension("jimple");
-                            let mut output =
-                                std::io::BufWriter::new(std::fs::File::create(target).unwrap());
-                            class.optimize();
-                            class.write_jimple(&mut output).unwrap();
-                        }
-                        Err(error) => {
-                            eprintln!("{}", error);
-                            break;
-                        }
-                    },
-                    Err(error) => {
-                        eprintln!("{}", error);
-                        break;
-                    }
+            // A single file is converted within its directory
+            let (input, dir) = if path.is_dir() {
+                (PipelineInput::Smali(None), path.as_path())
+            } else {
+                let dir = path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or(Path::new("."));
+                (PipelineInput::Smali(Some(path.as_path())), dir)
+            };
+            let pipeline = get_pipeline_options(&args, pipeline_args, get_resumable_command_line());
+            interrupt::install_handler();
+            let success = pipeline::decompile(input, dir, &pipeline, &output_args.into(), &scope);
+            diagnostics::print_warning_summary();
+            if !success {
+                std::process::exit(if interrupt::is_interrupted() { 130 } else { 1 });
+            }
+        }
+        ArgsCommand::ExtractMethod {
+            method,
+            dir,
+            output_args,
+        } => {
+            method::extract_method(dir, method, &output_args.into(), output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::StackTrace {
+            dir,
+            trace,
+            mapping,
+            output_args,
+        } => {
+            let trace = match trace {
+                Some(path) => std::fs::read_to_string(path),
+                None => std::io::read_to_string(std::io::stdin()),
+            }
+            .unwrap_or_else(|error| {
+                exit_with_error(format!("Failed reading stack trace: {error}"))
+            });
+            let mapping = match mapping.as_deref().map(std::fs::read_to_string) {
+                Some(Ok(data)) => Mapping::parse(&data),
+                Some(Err(error)) => {
+                    exit_with_error(format!("Failed reading mapping file: {error}"))
                 }
+                None => Mapping::default(),
+            };
+            method::annotate_stack_trace(dir, &trace, &mapping, &output_args.into(), output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Permissions { dir } => {
+            reports::report_permissions(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Xref {
+            member,
+            dir,
+            format,
+        } => {
+            let member = member
+                .parse::<Member>()
+                .unwrap_or_else(|error| exit_with_error(error));
+            let index_memory_limit = get_index_memory_limit(&args);
+            let count =
+                reports::find_xrefs(dir, &scope, &member, *format, index_memory_limit, output)
+                    .unwrap_or_else(|error| exit_with_error(error));
+            if count == 0 {
+                eprintln!("No references to {member} found.");
+            }
+        }
+        ArgsCommand::Search {
+            dir,
+            calls,
+            fields,
+            literals,
+            annotations,
+        } => {
+            let count =
+                reports::search_code(dir, &scope, calls, fields, literals, annotations, output)
+                    .unwrap_or_else(|error| exit_with_error(error));
+            if count == 0 {
+                eprintln!("No matches found.");
+            }
+        }
+        ArgsCommand::Dominators {
+            method,
+            dir,
+            format,
+        } => {
+            method::write_dominators(dir, method, *format, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Instructions { method, dir } => {
+            method::write_instructions(dir, method, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Api {
+            dir,
+            packages,
+            output_args,
+        } => {
+            reports::write_api(dir, &scope, packages, &output_args.into(), output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Tags { dir } => {
+            smali::write_tags(dir).unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::RoundTrip { dir, output_dir } => {
+            if !smali::round_trip(dir, &scope, output_dir) {
+                std::process::exit(1);
+            }
+        }
+        ArgsCommand::Fmt { paths, check } => {
+            if !smali::format_smali(paths, *check) {
+                std::process::exit(1);
+            }
+        }
+        ArgsCommand::Check { paths } => {
+            if !smali::check_smali(paths) {
+                std::process::exit(1);
+            }
+        }
+        ArgsCommand::Features { dir, format } => {
+            reports::write_features(dir, &scope, *format, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::DeepLinks { dir } => {
+            reports::report_deep_links(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Endpoints { dir } => {
+            reports::report_endpoints(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Webview { dir } => {
+            reports::report_webview(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Protections { dir } => {
+            reports::report_protections(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::SqlQueries { dir } => {
+            reports::report_sql_queries(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::DynamicCode { dir, graph } => {
+            reports::report_dynamic_code(dir, &scope, *graph, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::ApiReport { dir, format } => {
+            reports::report_api_calls(dir, &scope, *format, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Implementations { dir } => {
+            reports::report_implementations(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::UnusedMembers { dir } => {
+            reports::report_unused_members(dir, &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::KeptMembers { dir, rules } => {
+            reports::report_kept_members(dir, rules.as_deref(), &scope, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Strings { dir, filter } => {
+            reports::list_strings(dir, &scope, filter.as_deref(), output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::RepeatedStrings { dir, limit } => {
+            let index_memory_limit = get_index_memory_limit(&args);
+            reports::report_repeated_strings(dir, &scope, *limit, index_memory_limit, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Callgraph {
+            dir,
+            packages,
+            devirtualize,
+            format,
+        } => {
+            reports::write_callgraph(
+                dir,
+                &scope,
+                packages,
+                *devirtualize,
+                *format,
+                get_index_memory_limit(&args),
+                output,
+            )
+            .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::DexBudget { dir, depth, limit } => {
+            reports::report_dex_budget(dir, *depth, *limit, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Stats { dir, limit } => {
+            reports::report_stats(dir, &scope, *limit, output)
+                .unwrap_or_else(|error| exit_with_error(error));
+        }
+        ArgsCommand::Schema { output } => {
+            print!("{}", output.get_json_schema());
+        }
+        ArgsCommand::Definitions => {
+            if let Err(error) = instruction::write_definitions(output) {
+                exit_with_error(format!("Failed writing definitions: {error}"));
             }
         }
     }
@@ -0,0 +1,145 @@
+use super::narrowing::TypeNarrowing;
+use super::Method;
+use crate::access_flag::AccessFlag;
+use crate::instruction::{Instruction, Register, ResultType};
+use crate::r#type::{MethodSignature, Type};
+
+/// A method invocation along with the values of its arguments as far as they are known.
+#[derive(Debug)]
+pub struct Call<'a> {
+    /// Index of the invoke instruction
+    pub index: usize,
+    pub method: &'a MethodSignature,
+    /// Type or constant value of each argument, starting with the object for instance calls
+    pub arguments: Vec<Option<ResultType>>,
+    /// Line number of the call if debug information is present
+    pub line: Option<i64>,
+}
+
+impl Call<'_> {
+    /// Returns the argument corresponding to a parameter of the called method, skipping the
+    /// object for instance calls.
+    pub fn get_parameter(&self, index: usize) -> Option<&ResultType> {
+        let offset = self
+            .arguments
+            .len()
+            .checked_sub(self.method.call_signature.parameter_types.len())?;
+        self.arguments.get(offset + index)?.as_ref()
+    }
+
+    /// Returns the object an instance method is called on.
+    pub fn get_object(&self) -> Option<&ResultType> {
+        if self.arguments.len() > self.method.call_signature.parameter_types.len() {
+            self.arguments.first()?.as_ref()
+        } else {
+            None
+        }
+    }
+}
+
+impl Method {
+    /// Lists the calls made by the method. Argument values are tracked in instruction order,
+    /// starting with the declared parameter types and `this` being of the class type.
+    pub fn get_calls(&self, class_type: &Type) -> Vec<Call<'_>> {
+        let mut result = Vec::new();
+        let mut state = self.get_parameter_state();
+        if !self.visibility.contains(&AccessFlag::Static) {
+            state.insert(Register::Parameter(0), ResultType::Type(class_type.clone()));
+        }
+
+        let mut narrowing = TypeNarrowing::new(&self.instructions);
+        let mut line = None;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            narrowing.update(instruction, &mut state);
+            if let Instruction::LineNumber(from, _) = instruction {
+                line = Some(*from);
+                continue;
+            }
+
+            if let Some((method, registers)) = instruction.get_call_arguments() {
+                let arguments = registers
+                    .iter()
+                    .map(|register| state.get(register).cloned())
+                    .collect();
+                result.push(Call {
+                    index,
+                    method,
+                    arguments,
+                    line,
+                });
+            }
+
+            let Some(register) = instruction.get_result_register() else {
+                continue;
+            };
+            let used = instruction.get_used_registers();
+            let result_type = if used.iter().all(|used| state.contains_key(used)) {
+                instruction.get_result_type(&state)
+            } else {
+                None
+            };
+            match result_type {
+                Some(result_type) => state.insert(register.clone(), result_type),
+                None => state.remove(register),
+            };
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::literal::Literal;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn get_calls() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run(Ljava/lang/String;)V
+                .locals 3
+                .line 3
+                const-wide/16 v0, 0x5
+                const-string v2, "a"
+                invoke-static {v0, v1, v2, p0}, La;->b(JLjava/lang/String;Ljava/lang/String;)V
+                new-instance v0, La;
+                invoke-virtual {v0, v2}, La;->c(Ljava/lang/String;)V
+                return-void
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, method) = Method::read(&input)?;
+        let class_type = Type::Object("a".to_string());
+
+        let calls = method.get_calls(&class_type);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].method.method_name, "b");
+        assert_eq!(calls[0].line, Some(3));
+        assert_eq!(calls[0].get_object(), None);
+        assert_eq!(
+            calls[0].get_parameter(0),
+            Some(&ResultType::Literal(Literal::Long(5)))
+        );
+        assert_eq!(
+            calls[0].get_parameter(1),
+            Some(&ResultType::Literal(Literal::String("a".into())))
+        );
+        assert_eq!(
+            calls[0].get_parameter(2),
+            Some(&ResultType::Type(Type::Object(
+                "java.lang.String".to_string()
+            )))
+        );
+        assert_eq!(
+            calls[1].get_object(),
+            Some(&ResultType::Type(class_type.clone()))
+        );
+
+        Ok(())
+    }
+}
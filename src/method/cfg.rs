@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use super::Method;
+use crate::instruction::{CommandData, CommandParameter, Instruction};
+
+/// A sequence of instructions `start..end` executed without branching, along with the indexes
+/// of the blocks control flow can continue with. Exception handlers count as successors of
+/// the blocks they protect.
+#[derive(Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<usize>,
+}
+
+/// Lists the labels an instruction can branch to and whether execution can continue with the
+/// next instruction.
+pub(super) fn get_branch_targets(instruction: &Instruction) -> (Vec<&str>, bool) {
+    let Instruction::Command {
+        command,
+        parameters,
+    } = instruction
+    else {
+        return (Vec::new(), true);
+    };
+
+    if instruction.exits_method() {
+        return (Vec::new(), false);
+    }
+
+    let mut targets = Vec::new();
+    for parameter in parameters {
+        match parameter {
+            CommandParameter::Label(label) => targets.push(label.as_str()),
+            CommandParameter::Data(CommandData::PackedSwitch(_, labels)) => {
+                targets.extend(labels.iter().map(String::as_str));
+            }
+            CommandParameter::Data(CommandData::SparseSwitch(labels)) => {
+                targets.extend(labels.iter().map(|(_, label)| label.as_str()));
+            }
+            _ => (),
+        }
+    }
+    (targets, !command.starts_with("goto"))
+}
+
+impl Method {
+    /// Splits the method's code into basic blocks. Labels only start a new block if the
+    /// current one already contains statements.
+    pub fn get_basic_blocks(&self) -> Vec<BasicBlock> {
+        let mut starts = Vec::new();
+        let mut has_statements = false;
+        let mut ended = true;
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if ended || (matches!(instruction, Instruction::Label(_)) && has_statements) {
+                starts.push(i);
+                has_statements = false;
+            }
+            has_statements |= instruction.is_statement();
+            ended = instruction.ends_block();
+        }
+
+        let mut labels = HashMap::new();
+        for (block, window) in starts.iter().enumerate() {
+            let end = starts
+                .get(block + 1)
+                .copied()
+                .unwrap_or(self.instructions.len());
+            for instruction in &self.instructions[*window..end] {
+                if let Instruction::Label(label) = instruction {
+                    labels.insert(label.as_str(), block);
+                }
+            }
+        }
+
+        let mut blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(block, &start)| {
+                let end = starts
+                    .get(block + 1)
+                    .copied()
+                    .unwrap_or(self.instructions.len());
+                let (targets, falls_through) = self.instructions[start..end]
+                    .iter()
+                    .rev()
+                    .find(|instruction| instruction.is_command())
+                    .map(get_branch_targets)
+                    .unwrap_or((Vec::new(), true));
+
+                let mut successors = targets
+                    .into_iter()
+                    .filter_map(|label| labels.get(label).copied())
+                    .collect::<Vec<_>>();
+                if falls_through && block + 1 < starts.len() {
+                    successors.push(block + 1);
+                }
+                BasicBlock {
+                    start,
+                    end,
+                    successors,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for instruction in &self.instructions {
+            if let Instruction::Catch {
+                start_label,
+                end_label,
+                target,
+                ..
+            } = instruction
+            {
+                let (Some(&from), Some(&to), Some(&handler)) = (
+                    labels.get(start_label.as_str()),
+                    labels.get(end_label.as_str()),
+                    labels.get(target.as_str()),
+                ) else {
+                    continue;
+                };
+
+                // The end label follows the protected code, and only labels can precede it
+                // within its block
+                for block in blocks.iter_mut().take(to).skip(from) {
+                    block.successors.push(handler);
+                }
+            }
+        }
+
+        for block in &mut blocks {
+            block.successors.sort();
+            block.successors.dedup();
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_method;
+
+    #[test]
+    fn basic_blocks() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method static run(I)I
+                .locals 1
+                :try_start_0
+                invoke-static {p0}, La;->check(I)V
+                :try_end_0
+                .catch Ljava/lang/Exception; {:try_start_0 .. :try_end_0} :catch_0
+                if-eqz p0, :cond_0
+                const/4 v0, 0x1
+                goto :goto_0
+                :cond_0
+                :cond_1
+                const/4 v0, 0x2
+                :goto_0
+                return v0
+                :catch_0
+                const/4 v0, 0x0
+                return v0
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        let successors = method
+            .get_basic_blocks()
+            .into_iter()
+            .map(|block| block.successors)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            successors,
+            vec![vec![1, 5], vec![2, 3], vec![4], vec![4], vec![], vec![],]
+        );
+
+        Ok(())
+    }
+}
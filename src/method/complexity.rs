@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use super::Method;
+use crate::instruction::Instruction;
+
+/// Metrics pointing out code that is hard to follow.
+#[derive(Debug, Default, PartialEq)]
+pub struct Complexity {
+    /// Number of commands
+    pub instructions: usize,
+    /// Number of independent paths through the control flow graph, E - N + 2 with all blocks
+    /// leaving the method connected to a single exit node
+    pub cyclomatic: usize,
+    /// Maximal number of loops and try blocks enclosing a command
+    pub nesting_depth: usize,
+}
+
+impl Method {
+    /// Determines how many loops contain each basic block. A loop is formed by all blocks that
+    /// can reach a back edge to the header without passing through the header.
+    fn get_loop_depths(&self) -> Vec<usize> {
+        let trees = self.get_dominator_trees();
+        let dominates = |dominator: usize, mut block: usize| loop {
+            if block == dominator {
+                return true;
+            }
+            match trees.dominators[block] {
+                Some(parent) => block = parent,
+                None => return false,
+            }
+        };
+
+        let mut predecessors = vec![Vec::new(); trees.blocks.len()];
+        let mut back_edges: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (block, data) in trees.blocks.iter().enumerate() {
+            for &successor in &data.successors {
+                predecessors[successor].push(block);
+                if dominates(successor, block) {
+                    back_edges.entry(successor).or_default().push(block);
+                }
+            }
+        }
+
+        let mut depths = vec![0; trees.blocks.len()];
+        for (header, sources) in back_edges {
+            let mut body = HashSet::from([header]);
+            let mut stack = sources;
+            while let Some(block) = stack.pop() {
+                if body.insert(block) {
+                    stack.extend(predecessors[block].iter().copied());
+                }
+            }
+            for block in body {
+                depths[block] += 1;
+            }
+        }
+        depths
+    }
+
+    pub fn get_complexity(&self) -> Complexity {
+        let blocks = self.get_basic_blocks();
+        if blocks.is_empty() {
+            return Complexity::default();
+        }
+
+        let edges = blocks
+            .iter()
+            .map(|block| block.successors.len())
+            .sum::<usize>();
+        // Each return or throw adds an edge to the exit node, which counts as another node
+        let exits = blocks
+            .iter()
+            .filter(|block| block.successors.is_empty())
+            .count();
+        let loop_depths = self.get_loop_depths();
+
+        let mut instructions = 0;
+        let mut nesting_depth = 0;
+        let mut block_depth: usize = 0;
+        for (block, data) in blocks.iter().enumerate() {
+            for instruction in &self.instructions[data.start..data.end] {
+                match instruction {
+                    Instruction::Try | Instruction::TryWithResources(_) | Instruction::Finally => {
+                        block_depth += 1;
+                    }
+                    Instruction::BlockEnd => block_depth = block_depth.saturating_sub(1),
+                    Instruction::Command { .. } => {
+                        instructions += 1;
+                        nesting_depth = nesting_depth.max(block_depth + loop_depths[block]);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        Complexity {
+            instructions,
+            cyclomatic: (edges + exits.max(1) + 1).saturating_sub(blocks.len()),
+            nesting_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn complexity() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static sum(I)I
+                .locals 2
+                const/4 v0, 0x0
+                const/4 v1, 0x0
+                :goto_0
+                if-ge v1, p0, :cond_1
+                const/4 p0, 0x0
+                :goto_1
+                if-ge p0, v1, :cond_0
+                add-int/2addr v0, p0
+                add-int/lit8 p0, p0, 0x1
+                goto :goto_1
+                :cond_0
+                add-int/lit8 v1, v1, 0x1
+                goto :goto_0
+                :cond_1
+                return v0
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        assert_eq!(
+            method.get_complexity(),
+            Complexity {
+                instructions: 11,
+                cyclomatic: 3,
+                nesting_depth: 2,
+            }
+        );
+
+        // Each return leaves the method, a single branch still makes two paths
+        let input = Tokenizer::new(
+            r#"
+            static max(II)I
+                .locals 0
+                if-le p0, p1, :cond_0
+                return p0
+                :cond_0
+                return p1
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        assert_eq!(
+            method.get_complexity(),
+            Complexity {
+                instructions: 3,
+                cyclomatic: 2,
+                nesting_depth: 0,
+            }
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+use std::io::Write;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::{BasicBlock, Method};
+use crate::schema::{write_json, JsonOutput, SchemaVersion};
+
+/// Output format of the dominator trees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GraphFormat {
+    Dot,
+    Json,
+}
+
+/// Immediate dominators and post-dominators of a method's basic blocks. `None` means that the
+/// block is the entry (exit for post-dominators) or unreachable. A post-dominator equal to the
+/// number of blocks stands for the virtual exit node joining all exits.
+#[derive(Debug)]
+pub struct DominatorTrees {
+    pub blocks: Vec<BasicBlock>,
+    pub dominators: Vec<Option<usize>>,
+    pub post_dominators: Vec<Option<usize>>,
+}
+
+/// Parent of a block in a dominator tree.
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(untagged)]
+enum TreeNode {
+    Block(usize),
+    Exit(ExitNode),
+}
+
+/// The virtual exit node joining all exits.
+#[derive(Debug, Serialize, JsonSchema)]
+enum ExitNode {
+    #[serde(rename = "exit")]
+    Exit,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct BlockJson<'a> {
+    id: usize,
+    /// Index of the first instruction in the block
+    start: usize,
+    /// Index after the last instruction in the block
+    end: usize,
+    successors: &'a [usize],
+    dominator: Option<TreeNode>,
+    post_dominator: Option<TreeNode>,
+}
+
+/// Dominator trees of a method written by the dominators command.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct DominatorsFile<'a> {
+    schema_version: SchemaVersion<{ JsonOutput::Dominators.version() }>,
+    blocks: Vec<BlockJson<'a>>,
+}
+
+fn get_reverse_postorder(successors: &[Vec<usize>], entry: usize) -> Vec<usize> {
+    let mut visited = vec![false; successors.len()];
+    let mut order = Vec::new();
+    let mut stack = vec![(entry, 0)];
+    visited[entry] = true;
+    while let Some((node, index)) = stack.pop() {
+        if let Some(&next) = successors[node].get(index) {
+            stack.push((node, index + 1));
+            if !visited[next] {
+                visited[next] = true;
+                stack.push((next, 0));
+            }
+        } else {
+            order.push(node);
+        }
+    }
+    order.reverse();
+    order
+}
+
+/// Computes immediate dominators with the iterative algorithm by Cooper, Harvey and Kennedy.
+fn compute_dominators(successors: &[Vec<usize>], entry: usize) -> Vec<Option<usize>> {
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (node, targets) in successors.iter().enumerate() {
+        for &target in targets {
+            predecessors[target].push(node);
+        }
+    }
+
+    let order = get_reverse_postorder(successors, entry);
+    let mut position = vec![usize::MAX; successors.len()];
+    for (index, &node) in order.iter().enumerate() {
+        position[node] = index;
+    }
+
+    let mut dominators = vec![None; successors.len()];
+    dominators[entry] = Some(entry);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &node in order.iter().skip(1) {
+            let mut new_dominator: Option<usize> = None;
+            for &predecessor in &predecessors[node] {
+                if dominators[predecessor].is_none() {
+                    continue;
+                }
+                new_dominator = Some(match new_dominator {
+                    None => predecessor,
+                    Some(mut other) => {
+                        let mut current = predecessor;
+                        while current != other {
+                            while position[current] > position[other] {
+                                current = dominators[current].unwrap_or(entry);
+                            }
+                            while position[other] > position[current] {
+                                other = dominators[other].unwrap_or(entry);
+                            }
+                        }
+                        current
+                    }
+                });
+            }
+            if new_dominator.is_some() && dominators[node] != new_dominator {
+                dominators[node] = new_dominator;
+                changed = true;
+            }
+        }
+    }
+
+    dominators[entry] = None;
+    dominators
+}
+
+/// Reverses all edges and adds a virtual exit node preceding all nodes without successors.
+fn reverse_with_exit(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let exit = successors.len();
+    let mut reversed = vec![Vec::new(); exit + 1];
+    for (node, targets) in successors.iter().enumerate() {
+        for &target in targets {
+            reversed[target].push(node);
+        }
+        if targets.is_empty() {
+            reversed[exit].push(node);
+        }
+    }
+    reversed
+}
+
+impl Method {
+    pub fn get_dominator_trees(&self) -> DominatorTrees {
+        let blocks = self.get_basic_blocks();
+        if blocks.is_empty() {
+            return DominatorTrees {
+                blocks,
+                dominators: Vec::new(),
+                post_dominators: Vec::new(),
+            };
+        }
+
+        let successors = blocks
+            .iter()
+            .map(|block| block.successors.clone())
+            .collect::<Vec<_>>();
+        let dominators = compute_dominators(&successors, 0);
+
+        let exit = blocks.len();
+        let mut post_dominators = compute_dominators(&reverse_with_exit(&successors), exit);
+        post_dominators.truncate(exit);
+
+        DominatorTrees {
+            blocks,
+            dominators,
+            post_dominators,
+        }
+    }
+}
+
+impl DominatorTrees {
+    fn write_dot_tree(
+        &self,
+        output: &mut dyn Write,
+        name: &str,
+        tree: &[Option<usize>],
+    ) -> Result<(), std::io::Error> {
+        writeln!(output, "digraph {name} {{")?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(
+                output,
+                "    b{index} [label=\"b{index}: {}..{}\"];",
+                block.start, block.end
+            )?;
+        }
+        if tree.contains(&Some(self.blocks.len())) {
+            writeln!(output, "    exit [label=\"exit\"];")?;
+        }
+        for (index, parent) in tree.iter().enumerate() {
+            match parent {
+                Some(parent) if *parent == self.blocks.len() => {
+                    writeln!(output, "    exit -> b{index};")?
+                }
+                Some(parent) => writeln!(output, "    b{parent} -> b{index};")?,
+                None => (),
+            }
+        }
+        writeln!(output, "}}")
+    }
+
+    pub fn write_dot(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.write_dot_tree(output, "dominators", &self.dominators)?;
+        self.write_dot_tree(output, "post_dominators", &self.post_dominators)
+    }
+
+    pub fn write_json(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let node = |parent: Option<usize>| match parent {
+            Some(parent) if parent == self.blocks.len() => Some(TreeNode::Exit(ExitNode::Exit)),
+            Some(parent) => Some(TreeNode::Block(parent)),
+            None => None,
+        };
+
+        let blocks = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(index, block)| BlockJson {
+                id: index,
+                start: block.start,
+                end: block.end,
+                successors: &block.successors,
+                dominator: node(self.dominators[index]),
+                post_dominator: node(self.post_dominators[index]),
+            })
+            .collect();
+        write_json(
+            output,
+            &DominatorsFile {
+                schema_version: SchemaVersion,
+                blocks,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn dominators() {
+        // Diamond with a loop: 0 -> 1, 2; 1 -> 3; 2 -> 3; 3 -> 1, 4
+        let successors = vec![vec![1, 2], vec![3], vec![3], vec![1, 4], vec![]];
+        assert_eq!(
+            compute_dominators(&successors, 0),
+            vec![None, Some(0), Some(0), Some(0), Some(3)]
+        );
+
+        assert_eq!(
+            compute_dominators(&reverse_with_exit(&successors), successors.len()),
+            vec![Some(3), Some(3), Some(3), Some(4), Some(5), None]
+        );
+    }
+
+    #[test]
+    fn write_trees() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run(I)V
+                .locals 0
+                if-eqz p0, :cond_0
+                invoke-static {}, La;->b()V
+                :cond_0
+                return-void
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        let trees = method.get_dominator_trees();
+        let mut output = Vec::new();
+        trees.write_json(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Dominators.assert_valid(&output);
+        assert_eq!(
+            output,
+            r#"{"schema_version":1,"blocks":[{"id":0,"start":0,"end":1,"successors":[1,2],"dominator":null,"post_dominator":2},{"id":1,"start":1,"end":2,"successors":[2],"dominator":0,"post_dominator":2},{"id":2,"start":2,"end":4,"successors":[],"dominator":0,"post_dominator":"exit"}]}
+"#
+        );
+
+        let mut output = Vec::new();
+        trees.write_dot(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"digraph dominators {
+    b0 [label="b0: 0..1"];
+    b1 [label="b1: 1..2"];
+    b2 [label="b2: 2..4"];
+    b0 -> b1;
+    b0 -> b2;
+}
+digraph post_dominators {
+    b0 [label="b0: 0..1"];
+    b1 [label="b1: 1..2"];
+    b2 [label="b2: 2..4"];
+    exit [label="exit"];
+    b2 -> b0;
+    b2 -> b1;
+    exit -> b2;
+}
+"#
+        );
+
+        Ok(())
+    }
+}
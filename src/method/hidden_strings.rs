@@ -0,0 +1,273 @@
+use super::{Deadline, Method};
+use crate::instruction::{CommandData, CommandParameter, Instruction, Register};
+use crate::literal::Literal;
+use crate::r#type::Type;
+
+/// Decodes the contents of a byte or char array the way the corresponding `String`
+/// constructor would, provided that the result is valid text.
+fn decode_array(element_type: &Type, values: &[Literal]) -> Option<String> {
+    match element_type {
+        Type::Byte => {
+            let bytes = values
+                .iter()
+                .map(|value| value.get_integer().map(|value| value as u8))
+                .collect::<Option<Vec<_>>>()?;
+            String::from_utf8(bytes).ok()
+        }
+        Type::Char => {
+            let chars = values
+                .iter()
+                .map(|value| {
+                    value
+                        .get_char()
+                        .or_else(|| value.get_integer().map(|value| value as u16))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            String::from_utf16(&chars).ok()
+        }
+        _ => None,
+    }
+}
+
+/// A string created from a constant array: indexes of the `new-instance`, array size constant,
+/// `new-array`, `fill-array-data` and constructor call instructions along with the decoded value.
+struct HiddenString {
+    instance: usize,
+    size: Option<usize>,
+    array: usize,
+    data: usize,
+    constructor: usize,
+    element_type: Type,
+    values: Vec<Literal>,
+    value: String,
+}
+
+impl Method {
+    /// Finds the last instruction before `i` writing the register, with no other instructions
+    /// accessing the register in between. Labels end the search as control flow could merge
+    /// there.
+    fn find_exclusive_write(&self, i: usize, register: &Register) -> Option<usize> {
+        for j in (0..i).rev() {
+            let instruction = &self.instructions[j];
+            if matches!(instruction, Instruction::Label(_)) {
+                return None;
+            }
+            if instruction.get_result_register() == Some(register) {
+                return Some(j);
+            }
+            if instruction.count_register_uses(register) > 0 {
+                return if instruction.get_command_family() == Some("fill-array-data") {
+                    Some(j)
+                } else {
+                    None
+                };
+            }
+        }
+        None
+    }
+
+    fn match_hidden_string(&self, i: usize) -> Option<HiddenString> {
+        let Instruction::Command {
+            command,
+            parameters,
+        } = &self.instructions[i]
+        else {
+            return None;
+        };
+        let [_, CommandParameter::Registers(_), CommandParameter::Method(method)] =
+            parameters.as_slice()
+        else {
+            return None;
+        };
+        if !command.starts_with("invoke-direct")
+            || method.method_name != "<init>"
+            || method.object_type != Type::Object("java.lang.String".to_string())
+        {
+            return None;
+        }
+        let [array_type] = method.call_signature.parameter_types.as_slice() else {
+            return None;
+        };
+        let element_type = array_type.element_type()?;
+
+        let [instance_register, array_register]: [Register; 2] =
+            self.instructions[i].get_used_registers().try_into().ok()?;
+        let instance = self.find_exclusive_write(i, &instance_register)?;
+        if self.instructions[instance].get_command_family() != Some("new-instance") {
+            return None;
+        }
+
+        let data = self.find_exclusive_write(i, &array_register)?;
+        let Instruction::Command { parameters, .. } = &self.instructions[data] else {
+            return None;
+        };
+        let Some(CommandParameter::Data(CommandData::Array(_, values))) = parameters.get(1) else {
+            return None;
+        };
+        let array = self.find_exclusive_write(data, &array_register)?;
+        if self.instructions[array].get_command_family() != Some("new-array") {
+            return None;
+        }
+
+        let size = match self.instructions[array].get_used_registers().first() {
+            Some(size_register) => self
+                .find_exclusive_write(array, size_register)
+                .filter(|size| {
+                    self.instructions[*size].get_command_family() == Some("const")
+                        && self.find_single_use(*size, size_register) == Some(array)
+                }),
+            None => None,
+        };
+
+        let value = decode_array(element_type, values)?;
+        Some(HiddenString {
+            instance,
+            size,
+            array,
+            data,
+            constructor: i,
+            element_type: element_type.clone(),
+            values: values.clone(),
+            value,
+        })
+    }
+
+    /// Replaces strings constructed from constant byte or char arrays, a common obfuscation
+    /// approach, by string constants. If the array isn't used otherwise, its initialization is
+    /// removed and only kept in a comment.
+    pub(super) fn reconstruct_hidden_strings(&mut self, deadline: Deadline) {
+        let mut i = 0;
+        while i < self.instructions.len() && !deadline.is_reached() {
+            let Some(hidden) = self.match_hidden_string(i) else {
+                i += 1;
+                continue;
+            };
+
+            let register = self.instructions[hidden.instance]
+                .get_result_register()
+                .cloned();
+            let array_register = self.instructions[hidden.array]
+                .get_result_register()
+                .cloned();
+            let (Some(register), Some(array_register)) = (register, array_register) else {
+                i += 1;
+                continue;
+            };
+
+            self.instructions[hidden.instance] = Instruction::Command {
+                command: "const-string".to_string(),
+                parameters: vec![
+                    CommandParameter::Result(register),
+                    CommandParameter::Literal(Literal::escaped_string(&hidden.value)),
+                ],
+            };
+
+            let mut removed = vec![hidden.constructor];
+            if self.find_single_use(hidden.data, &array_register) == Some(hidden.constructor) {
+                removed.push(hidden.data);
+                removed.push(hidden.array);
+                removed.extend(hidden.size);
+            }
+            removed.sort();
+            for index in removed.iter().rev() {
+                self.instructions.remove(*index);
+            }
+
+            let position =
+                hidden.instance - removed.iter().filter(|r| **r < hidden.instance).count();
+            if removed.contains(&hidden.data) {
+                let values = hidden
+                    .values
+                    .iter()
+                    .map(Literal::to_string)
+                    .collect::<Vec<_>>();
+                self.instructions.insert(
+                    position,
+                    Instruction::Comment(format!(
+                        "decoded from new java.lang.String({}[] {{{}}})",
+                        hidden.element_type,
+                        values.join(", ")
+                    )),
+                );
+                i = position + 2;
+            } else {
+                i = position + 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn hidden_strings() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run()V
+                .locals 3
+                const/4 v0, 0x2
+                new-array v0, v0, [B
+                fill-array-data v0, :array_0
+                new-instance v1, Ljava/lang/String;
+                invoke-direct {v1, v0}, Ljava/lang/String;-><init>([B)V
+                invoke-static {v1}, La;->log(Ljava/lang/String;)V
+                const/4 v0, 0x2
+                new-array v0, v0, [C
+                fill-array-data v0, :array_1
+                new-instance v2, Ljava/lang/String;
+                invoke-direct {v2, v0}, Ljava/lang/String;-><init>([C)V
+                invoke-static {v2, v0}, La;->log(Ljava/lang/String;[C)V
+                return-void
+
+                :array_0
+                .array-data 1
+                    0x48t
+                    0x69t
+                .end array-data
+
+                :array_1
+                .array-data 2
+                    0x22s
+                    0x5cs
+                .end array-data
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            show_comments: true,
+            ..Default::default()
+        };
+        method.write_jimple(&mut output, &options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    static void run()
+    {
+        // decoded from new java.lang.String(byte[] {0x48, 0x69})
+        invoke-static <void a.log(java.lang.String)>("Hi");
+        v0 = new char[][0x2];
+        v0 = {
+            // "\"\\"
+            '"',
+            '\\',
+        };
+        invoke-static <void a.log(java.lang.String, char[])>("\"\\", v0);
+        return;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
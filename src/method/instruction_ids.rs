@@ -0,0 +1,166 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use super::Method;
+use crate::r#type::{MethodSignature, Type};
+use crate::schema::{write_json, JsonOutput, SchemaVersion};
+
+/// Stable identifier of a command within the Smali code: the method and the number of commands
+/// preceding it. Labels, line numbers and other directives aren't counted, so the ID survives
+/// re-decompilation with different debug information.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InstructionId {
+    pub method: MethodSignature,
+    pub offset: usize,
+}
+
+impl Display for InstructionId {
+    /// Writes the ID as `La;->b(I)V@3`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}@{}", self.method.to_smali(), self.offset)
+    }
+}
+
+impl FromStr for InstructionId {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        let (method, offset) = data
+            .rsplit_once('@')
+            .ok_or_else(|| format!("Instruction ID {data} lacks an offset"))?;
+        Ok(Self {
+            method: method.parse()?,
+            offset: offset
+                .parse()
+                .map_err(|_| format!("Invalid offset in instruction ID {data}"))?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct InstructionJson {
+    /// Instruction ID in the format `La;->b(I)V@3`
+    id: String,
+    offset: usize,
+    smali: String,
+}
+
+/// Commands of a method written by the instructions command.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct InstructionsFile {
+    schema_version: SchemaVersion<{ JsonOutput::Instructions.version() }>,
+    method: String,
+    instructions: Vec<InstructionJson>,
+}
+
+impl Method {
+    /// Assigns IDs to the commands of the method, other instructions get `None`. IDs are only
+    /// meaningful for the method as parsed, optimization rewrites the instructions.
+    pub fn get_instruction_ids(&self, class_type: &Type) -> Vec<Option<InstructionId>> {
+        let method = self.get_signature(class_type);
+        let mut offset = 0;
+        self.instructions
+            .iter()
+            .map(|instruction| {
+                instruction.is_command().then(|| {
+                    offset += 1;
+                    InstructionId {
+                        method: method.clone(),
+                        offset: offset - 1,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the index of the instruction with the given ID.
+    pub fn find_instruction(&self, class_type: &Type, id: &InstructionId) -> Option<usize> {
+        self.get_instruction_ids(class_type)
+            .iter()
+            .position(|current| current.as_ref() == Some(id))
+    }
+
+    /// Writes the commands of the method along with their IDs as JSON.
+    pub fn write_instructions_json(
+        &self,
+        class_type: &Type,
+        output: &mut dyn Write,
+    ) -> Result<(), std::io::Error> {
+        let mut instructions = Vec::new();
+        for (instruction, id) in self
+            .instructions
+            .iter()
+            .zip(self.get_instruction_ids(class_type))
+        {
+            let Some(id) = id else {
+                continue;
+            };
+            let mut smali = Vec::new();
+            instruction.write_smali(&mut smali)?;
+            instructions.push(InstructionJson {
+                id: id.to_string(),
+                offset: id.offset,
+                smali: String::from_utf8_lossy(&smali).trim().to_string(),
+            });
+        }
+        write_json(
+            output,
+            &InstructionsFile {
+                schema_version: SchemaVersion,
+                method: self.get_signature(class_type).to_smali(),
+                instructions,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn instruction_ids() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run(I)V
+                .locals 0
+                .line 3
+                if-eqz p0, :cond_0
+                invoke-static {}, La;->b()V
+                :cond_0
+                return-void
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, method) = Method::read(&input)?;
+        let class_type = "La;".parse::<Type>().unwrap();
+
+        let mut output = Vec::new();
+        method
+            .write_instructions_json(&class_type, &mut output)
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Instructions.assert_valid(&output);
+        assert_eq!(
+            output,
+            r#"{"schema_version":1,"method":"La;->run(I)V","instructions":[{"id":"La;->run(I)V@0","offset":0,"smali":"if-eqz p0, :cond_0"},{"id":"La;->run(I)V@1","offset":1,"smali":"invoke-static {}, La;->b()V"},{"id":"La;->run(I)V@2","offset":2,"smali":"return-void"}]}
+"#
+        );
+
+        let id = "<void a.run(int)>@2".parse::<InstructionId>().unwrap();
+        assert_eq!(id.to_string(), "La;->run(I)V@2");
+        assert_eq!(method.find_instruction(&class_type, &id), Some(4));
+        assert!("La;->run(I)V".parse::<InstructionId>().is_err());
+
+        Ok(())
+    }
+}
@@ -1,49 +1,319 @@
+use std::collections::HashSet;
 use std::io::Write;
 
-use super::Method;
+use super::locals::LocalDeclarations;
+use super::try_blocks::ResourceInitializer;
+use super::{Deadline, Method};
 use crate::access_flag::AccessFlag;
-use crate::instruction::Instruction;
+use crate::annotation::Annotation;
+use crate::doc::Doc;
+use crate::instruction::{Instruction, NestedExpressions, ResultType};
+use crate::output::{jimple_identifier, write_jimple_line, OutputOptions};
+use crate::r#type::Type;
 
-impl Method {
-    pub fn write_jimple(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
-        for annotation in &self.annotations {
-            annotation.write_jimple(output, 1)?;
-        }
+/// Indentation of continuation lines when wrapping long parameter lists.
+const CONTINUATION_INDENT: usize = 8;
+
+/// Renders a statement within the given number of nested blocks, wrapping it if necessary.
+fn write_statement(
+    output: &mut dyn Write,
+    depth: usize,
+    doc: Doc,
+    options: &OutputOptions,
+) -> Result<(), std::io::Error> {
+    write_jimple_line(output, depth * 4, doc, options)
+}
 
-        write!(output, "    ")?;
-        AccessFlag::write_jimple_list(output, &self.visibility)?;
-        write!(output, "{} {}(", self.return_type, self.name)?;
+impl Method {
+    /// Checks whether the method is declared with code, as opposed to native and abstract
+    /// methods. Code is never dropped however, even if it contradicts the declaration.
+    fn has_body(&self) -> bool {
+        (!self.visibility.contains(&AccessFlag::Native)
+            && !self.visibility.contains(&AccessFlag::Abstract))
+            || self.instructions.iter().any(Instruction::is_command)
+    }
 
-        let mut first = true;
-        for (i, parameter) in self.parameters.iter().enumerate() {
-            if first {
-                first = false;
-            } else {
-                write!(output, ", ")?;
+    fn get_jimple_signature_doc(&self, options: &OutputOptions, class_type: Option<&Type>) -> Doc {
+        let flags = AccessFlag::get_jimple_list(&self.visibility);
+        let name = match class_type {
+            Some(class_type) if self.name == "<init>" => {
+                // Constructors are named after the class, inner classes go by their own name
+                let name = class_type.get_name();
+                let name = name.rsplit(['.', '$']).next().unwrap_or_default();
+                format!("{flags}{}(", jimple_identifier(name))
             }
+            _ => format!(
+                "{flags}{} {}(",
+                self.return_type,
+                jimple_identifier(&self.name)
+            ),
+        };
 
-            for annotation in &parameter.annotations {
-                annotation.write_jimple(output, -1)?;
-                write!(output, " ")?;
+        let parameters = self
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, parameter)| {
+                let mut parts = Vec::new();
+                for annotation in &parameter.annotations {
+                    if options.hidden_annotations.contains(&annotation.visibility) {
+                        continue;
+                    }
+                    parts.push(annotation.get_jimple_doc());
+                    parts.push(Doc::text(" "));
+                }
+                parts.push(Doc::text(format!("{} @p{i}", parameter.parameter_type)));
+                Doc::Concat(parts)
+            })
+            .collect();
+
+        let mut result = vec![
+            Doc::text(name),
+            Doc::list(parameters, CONTINUATION_INDENT),
+            Doc::text(")"),
+        ];
+        let exceptions = Annotation::get_thrown_exceptions(&self.annotations);
+        if !exceptions.is_empty() {
+            let exceptions = exceptions.iter().map(Type::to_string).collect::<Vec<_>>();
+            result.push(Doc::text(format!(" throws {}", exceptions.join(", "))));
+        }
+        Doc::Concat(result)
+    }
+
+    /// Builds the header of a `try` block closing a resource, with the commands initializing
+    /// the resource moved into it. The resource is declared with the inferred type, unless the
+    /// register is declared elsewhere.
+    fn get_resource_header_doc(
+        &self,
+        initializer: &ResourceInitializer,
+        expressions: &NestedExpressions,
+        declarations: &LocalDeclarations,
+        options: &OutputOptions,
+    ) -> Result<Option<Doc>, std::io::Error> {
+        let j = initializer.assignment;
+        let constructor = initializer.constructor.map(|k| &self.instructions[k]);
+        let initializer = &self.instructions[j];
+        let resource_type = if options.declare_locals {
+            declarations.initialized.get(&j).cloned()
+        } else {
+            match initializer.get_result_type(&self.get_parameter_state()) {
+                Some(ResultType::Type(resource_type)) if resource_type != Type::Void => {
+                    Some(resource_type)
+                }
+                _ => None,
             }
+        };
+        initializer.get_resource_header_doc(constructor, expressions, resource_type.as_ref())
+    }
+
+    pub fn write_jimple(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        self.write_jimple_member(output, options, None)
+    }
+
+    /// Writes only the annotations and the signature of a class member, without its code.
+    pub fn write_jimple_declaration(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+        class_type: &Type,
+    ) -> Result<(), std::io::Error> {
+        Annotation::write_jimple_list(output, &self.annotations, 1, options)?;
+        let class_type = Some(class_type).filter(|_| !options.raw_constructor_names);
+        let signature = self.get_jimple_signature_doc(options, class_type);
+        write_jimple_line(
+            output,
+            4,
+            Doc::Concat(vec![signature, Doc::text(";")]),
+            options,
+        )
+    }
+
+    /// Writes the method as a member of the class. Unless raw names are requested, constructors
+    /// are named after the class and static initializers are written as `static` blocks.
+    pub fn write_jimple_member(
+        &self,
+        output: &mut dyn Write,
+        options: &OutputOptions,
+        class_type: Option<&Type>,
+    ) -> Result<(), std::io::Error> {
+        if options.show_complexity {
+            let complexity = self.get_complexity();
+            writeln!(
+                output,
+                "    // complexity: {}, nesting depth: {}, instructions: {}",
+                complexity.cyclomatic, complexity.nesting_depth, complexity.instructions
+            )?;
+        }
+        for comment in &self.comments {
+            writeln!(output, "    // {comment}")?;
+        }
+        Annotation::write_jimple_list(output, &self.annotations, 1, options)?;
 
-            write!(output, "{} @p{i}", parameter.parameter_type)?;
+        let class_type = class_type.filter(|_| !options.raw_constructor_names);
+        if class_type.is_some() && self.name == "<clinit>" {
+            writeln!(output, "    static")?;
+        } else {
+            let signature = self.get_jimple_signature_doc(options, class_type);
+            if !self.has_body() {
+                let signature = Doc::Concat(vec![signature, Doc::text(";")]);
+                return write_jimple_line(output, 4, signature, options);
+            }
+            write_jimple_line(output, 4, signature, options)?;
         }
-        writeln!(output, ")")?;
         writeln!(output, "    {{")?;
 
+        let nested = self.find_nested_expressions();
+        let declarations = if options.declare_locals {
+            let deadline = options
+                .method_time_limit
+                .map_or(Deadline::NONE, Deadline::after);
+            self.find_local_declarations(&nested, deadline)
+        } else {
+            Default::default()
+        };
+        let resource_initializers = self.find_resource_initializers(&nested);
+        let initializers = resource_initializers
+            .values()
+            .flat_map(ResourceInitializer::indexes)
+            .collect::<HashSet<_>>();
+        let mut expressions = NestedExpressions::new();
         let mut had_delimiter = true;
-        for instruction in &self.instructions {
-            if matches!(instruction, Instruction::Command { .. }) {
+        let mut depth: usize = 0;
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            for (register, local_type) in declarations.before.get(&i).into_iter().flatten() {
+                write_statement(
+                    output,
+                    depth,
+                    Doc::text(format!("        {local_type} {register};")),
+                    options,
+                )?;
+                had_delimiter = false;
+            }
+
+            // Resource initialization is written as part of the try block header
+            if initializers.contains(&i) {
+                continue;
+            }
+
+            if nested.contains_key(&i) {
+                if let Some(register) = instruction.get_result_register() {
+                    let expression = instruction.get_jimple_expression(&expressions)?;
+                    expressions.insert(register.clone(), expression);
+                    continue;
+                }
+            }
+
+            if options.hide_assertions && matches!(instruction, Instruction::AssertNotNull { .. }) {
+                continue;
+            }
+            if !options.show_comments
+                && matches!(
+                    instruction,
+                    Instruction::Comment(_) | Instruction::TrailingComment(_)
+                )
+            {
+                continue;
+            }
+
+            if instruction.is_statement() {
                 had_delimiter = false;
             } else if !had_delimiter {
                 writeln!(output)?;
                 had_delimiter = true;
             }
-            instruction.write_jimple(output)?;
+            if matches!(instruction, Instruction::BlockEnd) {
+                depth = depth.saturating_sub(1);
+            }
+            let doc = match resource_initializers.get(&i) {
+                Some(initializer) => {
+                    self.get_resource_header_doc(initializer, &expressions, &declarations, options)?
+                }
+                None => {
+                    instruction.get_jimple_doc(&expressions, declarations.initialized.get(&i))?
+                }
+            };
+            if let Some(doc) = doc {
+                write_statement(output, depth, doc, options)?;
+            }
+            if matches!(
+                instruction,
+                Instruction::Try | Instruction::TryWithResources(_) | Instruction::Finally
+            ) {
+                depth += 1;
+            }
         }
 
         writeln!(output, "    }}")?;
+        if options.show_registers {
+            if let Some(summary) = self.get_register_summary() {
+                writeln!(output, "    // {summary}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::Class;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn write_declarations() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public abstract La;
+                .super Ljava/lang/Object;
+
+                .method public declared-synchronized run()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public native compute(I)I
+                .end method
+
+                .method protected abstract handle()V
+                .end method
+
+                .method public abstract broken()V
+                    .locals 0
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, class) = Class::read(&input)?;
+
+        let mut output = Vec::new();
+        for method in &class.methods {
+            method
+                .write_jimple(&mut output, &OutputOptions::default())
+                .unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    public synchronized void run()
+    {
+        return;
+    }
+    public native int compute(int @p0);
+    protected abstract void handle();
+    public abstract void broken()
+    {
+        return;
+    }
+"#
+        );
 
         Ok(())
     }
@@ -0,0 +1,444 @@
+use std::collections::{HashMap, HashSet};
+
+use super::narrowing::TypeNarrowing;
+use super::{Deadline, Method};
+use crate::access_flag::AccessFlag;
+use crate::diagnostics::warning;
+use crate::instruction::{Instruction, Register, ResultType};
+use crate::literal::Literal;
+use crate::r#type::Type;
+
+/// Placement of local register declarations: registers to be declared before an instruction,
+/// and instructions where the declaration is combined with the initial assignment.
+#[derive(Debug, Default)]
+pub struct LocalDeclarations {
+    pub before: HashMap<usize, Vec<(Register, Type)>>,
+    pub initialized: HashMap<usize, Type>,
+}
+
+/// Types a register was assigned, by the reliability of the source.
+#[derive(Debug, Default)]
+struct TypeCandidates {
+    debug: HashSet<Type>,
+    inferred: HashSet<Type>,
+    literal: HashSet<Type>,
+}
+
+impl TypeCandidates {
+    /// Picks the most reliable type if it is unambiguous. Registers reused for values of
+    /// different types cannot be declared.
+    fn resolve(&self) -> Option<Type> {
+        let candidates = [&self.debug, &self.inferred, &self.literal]
+            .into_iter()
+            .find(|candidates| !candidates.is_empty())?;
+        if candidates.len() == 1 {
+            candidates.iter().next().cloned()
+        } else {
+            None
+        }
+    }
+}
+
+/// Determines the type of a literal. Zero is ambiguous, it is also used for `null` and `false`.
+fn get_literal_type(literal: &Literal) -> Option<Type> {
+    match literal {
+        Literal::Null | Literal::Int(0) | Literal::Long(0) => None,
+        Literal::Bool(_) => Some(Type::Bool),
+        Literal::Char(_) => Some(Type::Char),
+        Literal::Byte(_) => Some(Type::Byte),
+        Literal::Short(_) => Some(Type::Short),
+        Literal::Int(_) => Some(Type::Int),
+        Literal::Long(_) => Some(Type::Long),
+        Literal::Float(_) => Some(Type::Float),
+        Literal::Double(_) => Some(Type::Double),
+        Literal::String(_) => Some(Type::Object("java.lang.String".to_string())),
+        Literal::Class(_) => Some(Type::Object("java.lang.Class".to_string())),
+        Literal::Method(_) | Literal::MethodHandle(..) => {
+            Some(Type::Object("java.lang.invoke.MethodHandle".to_string()))
+        }
+        Literal::MethodType(_) => Some(Type::Object("java.lang.invoke.MethodType".to_string())),
+    }
+}
+
+impl Method {
+    /// Lists local registers referenced by an instruction.
+    fn get_referenced_locals(instruction: &Instruction) -> Vec<Register> {
+        let mut registers = match instruction {
+            Instruction::AssertNotNull { register, .. }
+            | Instruction::TryWithResources(register) => vec![register.clone()],
+            _ => instruction.get_used_registers(),
+        };
+        registers.extend(instruction.get_result_register().cloned());
+        registers.retain(|register| matches!(register, Register::Local(_)));
+        registers
+    }
+
+    /// Maps the parameter registers to the declared parameter types. The `this` register of
+    /// instance methods isn't included.
+    pub fn get_parameter_state(&self) -> HashMap<Register, ResultType> {
+        let mut state = HashMap::new();
+        let mut index = if self.visibility.contains(&AccessFlag::Static) {
+            0
+        } else {
+            1
+        };
+        for parameter in &self.parameters {
+            state.insert(
+                Register::Parameter(index),
+                ResultType::Type(parameter.parameter_type.clone()),
+            );
+            index += parameter.parameter_type.register_count();
+        }
+        state
+    }
+
+    /// Infers the types of local registers from debug information where available, otherwise
+    /// from the values assigned to them. Returns `None` if the deadline is reached.
+    fn infer_local_types(&self, deadline: Deadline) -> Option<HashMap<Register, Type>> {
+        let mut state = self.get_parameter_state();
+        let mut narrowing = TypeNarrowing::new(&self.instructions);
+
+        let mut candidates: HashMap<Register, TypeCandidates> = HashMap::new();
+        for instruction in &self.instructions {
+            if deadline.is_reached() {
+                return None;
+            }
+            narrowing.update(instruction, &mut state);
+            if let Instruction::Local {
+                register,
+                local_type,
+                ..
+            } = instruction
+            {
+                if let Some(register) = Self::find_local_register(register) {
+                    candidates
+                        .entry(register)
+                        .or_default()
+                        .debug
+                        .insert(local_type.clone());
+                }
+                continue;
+            }
+
+            let Some(register) = instruction.get_result_register() else {
+                continue;
+            };
+
+            // Only look up types of registers that have been assigned before to avoid warnings
+            let result_type = if instruction
+                .get_used_registers()
+                .iter()
+                .all(|used| state.contains_key(used))
+            {
+                instruction.get_result_type(&state)
+            } else {
+                None
+            };
+
+            if let Register::Local(_) = register {
+                let entry = candidates.entry(register.clone()).or_default();
+                match &result_type {
+                    Some(ResultType::Type(Type::Void)) | None => (),
+                    Some(ResultType::Type(r#type)) => {
+                        entry.inferred.insert(r#type.clone());
+                    }
+                    Some(ResultType::Literal(literal)) => {
+                        if let Some(r#type) = get_literal_type(literal) {
+                            match literal {
+                                Literal::String(_)
+                                | Literal::Class(_)
+                                | Literal::Method(_)
+                                | Literal::MethodHandle(..)
+                                | Literal::MethodType(_) => entry.inferred.insert(r#type),
+                                _ => entry.literal.insert(r#type),
+                            };
+                        }
+                    }
+                }
+            }
+
+            match result_type {
+                Some(result_type) => state.insert(register.clone(), result_type),
+                None => state.remove(register),
+            };
+        }
+
+        Some(
+            candidates
+                .into_iter()
+                .filter_map(|(register, candidates)| Some((register, candidates.resolve()?)))
+                .collect(),
+        )
+    }
+
+    /// Maps the register name used by debug information to a local register.
+    fn find_local_register(name: &str) -> Option<Register> {
+        name.strip_prefix('v')?.parse().ok().map(Register::Local)
+    }
+
+    /// Lists the blocks enclosing each instruction, identified by the indexes of the
+    /// instructions opening them.
+    fn get_block_paths(&self) -> Vec<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut result = Vec::with_capacity(self.instructions.len());
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if matches!(instruction, Instruction::BlockEnd) {
+                path.pop();
+            }
+            result.push(path.clone());
+            if matches!(
+                instruction,
+                Instruction::Try | Instruction::TryWithResources(_) | Instruction::Finally
+            ) {
+                path.push(i);
+            }
+        }
+        result
+    }
+
+    /// Places declarations of local registers with a known type into the narrowest block
+    /// containing all references to the register, right before the first reference. If the
+    /// first reference assigns the register, the declaration is merged with the assignment.
+    /// Instructions nested into other expressions cannot carry declarations. No locals are
+    /// declared if inferring their types doesn't finish before the deadline.
+    pub fn find_local_declarations(
+        &self,
+        nested: &HashMap<usize, usize>,
+        deadline: Deadline,
+    ) -> LocalDeclarations {
+        let Some(types) = self.infer_local_types(deadline) else {
+            warning!(
+                "Inferring local types of method {} exceeded the time limit, not declaring locals",
+                self.name
+            );
+            return LocalDeclarations::default();
+        };
+        let paths = self.get_block_paths();
+
+        let mut references: HashMap<Register, Vec<usize>> = HashMap::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            for register in Self::get_referenced_locals(instruction) {
+                let entry = references.entry(register).or_default();
+                if entry.last() != Some(&i) {
+                    entry.push(i);
+                }
+            }
+        }
+
+        let mut references = references.into_iter().collect::<Vec<_>>();
+        references.sort_by_key(|(register, indexes)| (indexes[0], register.to_string()));
+
+        let mut result = LocalDeclarations::default();
+        for (register, indexes) in references {
+            let Some(local_type) = types.get(&register) else {
+                continue;
+            };
+
+            let first = indexes[0];
+            let scope = indexes.iter().fold(paths[first].len(), |depth, &i| {
+                paths[first]
+                    .iter()
+                    .zip(&paths[i])
+                    .take(depth)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+
+            if scope < paths[first].len() {
+                result
+                    .before
+                    .entry(paths[first][scope])
+                    .or_default()
+                    .push((register, local_type.clone()));
+                continue;
+            }
+
+            let instruction = &self.instructions[first];
+            if !nested.contains_key(&first)
+                && !result.initialized.contains_key(&first)
+                && instruction.get_result_register() == Some(&register)
+                && instruction.count_register_uses(&register) == 0
+            {
+                result.initialized.insert(first, local_type.clone());
+            } else {
+                result
+                    .before
+                    .entry(first)
+                    .or_default()
+                    .push((register, local_type.clone()));
+            }
+        }
+        result
+    }
+
+    /// Describes the registers of the method as declared in Smali, for picking scratch
+    /// registers when patching it: the count a `.registers` directive would specify, the
+    /// parameter registers and the local registers only ever used as temporaries. Returns
+    /// `None` for methods without code.
+    pub fn get_register_summary(&self) -> Option<String> {
+        let locals = self.locals?;
+        let parameters = usize::from(!self.visibility.contains(&AccessFlag::Static))
+            + self
+                .parameters
+                .iter()
+                .map(|parameter| parameter.parameter_type.register_count())
+                .sum::<usize>();
+        let mut summary = format!(".registers {}, .locals {locals}", locals + parameters);
+        if parameters > 0 {
+            summary.push_str(&format!(
+                ", parameters p0-p{} = v{locals}-v{}",
+                parameters - 1,
+                locals + parameters - 1
+            ));
+        }
+
+        // Registers renamed at some assignments can still hold real values at others
+        let referenced = self
+            .instructions
+            .iter()
+            .flat_map(Self::get_referenced_locals)
+            .collect::<HashSet<_>>();
+        let temporaries = self
+            .temporaries
+            .iter()
+            .filter(|index| !referenced.contains(&Register::Local(**index)))
+            .map(|index| format!("v{index}"))
+            .collect::<Vec<_>>();
+        if !temporaries.is_empty() {
+            summary.push_str(&format!(", temporaries: {}", temporaries.join(", ")));
+        }
+        Some(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::read_method;
+
+    fn stringify(method: &Method) -> String {
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            declare_locals: true,
+            ..Default::default()
+        };
+        method.write_jimple(&mut output, &options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn declare_locals() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method static read(Ljava/io/InputStream;)I
+                .locals 4
+                invoke-static {p0}, La;->wrap(Ljava/io/InputStream;)Ljava/io/Reader;
+                move-result-object v0
+                :try_start_0
+                const/4 v2, 0x1
+                :goto_0
+                invoke-virtual {v0}, Ljava/io/Reader;->read()I
+                move-result v1
+                add-int/2addr v2, v1
+                if-gez v1, :goto_0
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                return v2
+
+                :catchall_0
+                move-exception v3
+                :try_start_1
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                :try_end_1
+                .catchall {:try_start_1 .. :try_end_1} :catchall_1
+                goto :goto_1
+
+                :catchall_1
+                move-exception v2
+                invoke-virtual {v3, v2}, Ljava/lang/Throwable;->addSuppressed(Ljava/lang/Throwable;)V
+
+                :goto_1
+                throw v3
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        assert_eq!(
+            stringify(&method),
+            r#"    static int read(java.io.InputStream @p0)
+    {
+    try_start_0:
+        int v2;
+        try (java.io.Reader v0 = invoke-static <java.io.Reader a.wrap(java.io.InputStream)>(p0))
+        {
+            v2 = 0x1;
+
+        goto_0:
+            $tmp0 = invoke-virtual v0.<int java.io.Reader.read()>();
+            v2 += $tmp0;
+            if ($tmp0 >= 0) goto goto_0;
+        }
+
+    try_end_0:
+        return v2;
+    }
+"#
+        );
+
+        // Local types aren't inferred once the time limit is exceeded, nothing is declared then
+        assert!(method.infer_local_types(Deadline::NONE).is_some());
+        let deadline = Deadline::after(std::time::Duration::ZERO);
+        assert!(method.infer_local_types(deadline).is_none());
+        let mut output = Vec::new();
+        let options = OutputOptions {
+            declare_locals: true,
+            method_time_limit: Some(std::time::Duration::ZERO),
+            ..Default::default()
+        };
+        method.write_jimple(&mut output, &options).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("java.io.Reader v0"));
+        assert!(!output.contains("int v"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn register_summary() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method public format(JLjava/lang/String;)Ljava/lang/String;
+                .locals 2
+                const-string v0, "tag"
+                invoke-static {v0, p3}, Landroid/util/Log;->d(Ljava/lang/String;Ljava/lang/String;)I
+                const/4 v1, 0x1
+                invoke-static {v1, v1}, Ljava/lang/Math;->max(II)I
+                invoke-static {p1, p2}, Ljava/lang/Long;->valueOf(J)Ljava/lang/Long;
+                move-result-object v0
+                invoke-virtual {v0}, Ljava/lang/Object;->toString()Ljava/lang/String;
+                move-result-object v0
+                return-object v0
+            .end method
+            "#,
+        )?;
+        method.optimize();
+        assert_eq!(
+            method.get_register_summary().as_deref(),
+            Some(".registers 6, .locals 2, parameters p0-p3 = v2-v5, temporaries: v0, v1")
+        );
+
+        let method = read_method(
+            r#"
+            .method public abstract run()V
+            .end method
+            "#,
+        )?;
+        assert_eq!(method.get_register_summary(), None);
+
+        Ok(())
+    }
+}
@@ -1,15 +1,37 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, Instant};
+
 use crate::access_flag::AccessFlag;
 use crate::annotation::Annotation;
 use crate::instruction::Instruction;
-use crate::r#type::Type;
+use crate::r#type::{CallSignature, MethodSignature, Type};
+
+pub use calls::Call;
+pub use cfg::BasicBlock;
+pub use complexity::Complexity;
+pub(crate) use dominators::DominatorsFile;
+pub use dominators::{DominatorTrees, GraphFormat};
+pub use instruction_ids::InstructionId;
+pub(crate) use instruction_ids::InstructionsFile;
 
+mod calls;
+mod cfg;
+mod complexity;
+mod dominators;
+mod hidden_strings;
+mod instruction_ids;
 mod jimple;
+mod locals;
+mod narrowing;
 mod optimization;
 mod smali;
+mod try_blocks;
 
 #[derive(Debug, PartialEq)]
 pub struct MethodParameter {
     pub parameter_type: Type,
+    /// Name from the debug information if present
+    pub name: Option<String>,
     pub annotations: Vec<Annotation>,
 }
 
@@ -20,5 +42,47 @@ pub struct Method {
     pub parameters: Vec<MethodParameter>,
     pub return_type: Type,
     pub annotations: Vec<Annotation>,
+    /// Comments to be written above the method declaration
+    pub comments: Vec<String>,
+    /// Number of local registers declared by the `.locals` directive
+    pub locals: Option<usize>,
     pub instructions: Vec<Instruction>,
+    /// Local registers renamed to `$stackN` or `$tmpN` temporaries by the optimizer, at least at
+    /// some of their assignments
+    pub temporaries: BTreeSet<usize>,
+}
+
+/// Point in time after which expensive passes and analyses of a method give up. These check it
+/// within their loops, so that pathological code cannot stall them much longer than the limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    /// Deadline that is never reached.
+    pub const NONE: Self = Self(None);
+
+    pub fn after(limit: Duration) -> Self {
+        Self(Instant::now().checked_add(limit))
+    }
+
+    pub fn is_reached(self) -> bool {
+        self.0.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+impl Method {
+    pub fn get_signature(&self, object_type: &Type) -> MethodSignature {
+        MethodSignature {
+            object_type: object_type.clone(),
+            method_name: self.name.clone(),
+            call_signature: CallSignature {
+                parameter_types: self
+                    .parameters
+                    .iter()
+                    .map(|parameter| parameter.parameter_type.clone())
+                    .collect(),
+                return_type: self.return_type.clone(),
+            },
+        }
+    }
 }
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use super::cfg::get_branch_targets;
+use crate::instruction::{CommandParameter, Instruction, Register, ResultType};
+use crate::literal::Literal;
+use crate::r#type::Type;
+
+/// Refinements of register types, `None` meaning that the type is no longer known.
+type Refinements = Vec<(Register, Option<ResultType>)>;
+
+/// Refines register types by the branch conditions seen during a linear pass over a method's
+/// instructions. Where an `instance-of` check succeeded the object has the checked type, where
+/// a reference compared equal to zero it is `null`. Refinements are reverted where control
+/// flow merges, for branch targets they only apply if the branch is the only way to get there.
+#[derive(Debug, Default)]
+pub(super) struct TypeNarrowing<'a> {
+    /// Number of branches and exception handlers leading to each label
+    references: HashMap<&'a str, usize>,
+    /// Result registers of `instance-of` commands with the object register and checked type
+    checks: HashMap<Register, (Register, Type)>,
+    /// Refinements for the targets of conditional branches
+    pending: HashMap<&'a str, Refinements>,
+    /// Original types of the registers refined at this point
+    replaced: HashMap<Register, Option<ResultType>>,
+    /// Whether the method contains raw instructions, these might jump to any label
+    has_raw: bool,
+    falls_through: bool,
+}
+
+impl<'a> TypeNarrowing<'a> {
+    pub fn new(instructions: &'a [Instruction]) -> Self {
+        let mut references: HashMap<&str, usize> = HashMap::new();
+        let mut has_raw = false;
+        for instruction in instructions {
+            let targets = match instruction {
+                Instruction::Catch { target, .. } => vec![target.as_str()],
+                Instruction::Raw(_) => {
+                    has_raw = true;
+                    continue;
+                }
+                instruction => get_branch_targets(instruction).0,
+            };
+            for target in targets {
+                *references.entry(target).or_default() += 1;
+            }
+        }
+        Self {
+            references,
+            has_raw,
+            falls_through: true,
+            ..Default::default()
+        }
+    }
+
+    /// Updates the register types before an instruction is processed.
+    pub fn update(
+        &mut self,
+        instruction: &'a Instruction,
+        state: &mut HashMap<Register, ResultType>,
+    ) {
+        // Raw instructions might assign any register, so nothing is known about them afterwards
+        if let Instruction::Raw(_) = instruction {
+            state.clear();
+            self.checks.clear();
+            self.pending.clear();
+            self.replaced.clear();
+            self.falls_through = true;
+            return;
+        }
+
+        if let Instruction::Label(label) = instruction {
+            if let Some(&count) = self.references.get(label.as_str()) {
+                self.restore(state);
+                let pending = self.pending.remove(label.as_str());
+                if count == 1 && !self.falls_through && !self.has_raw {
+                    self.apply(pending.unwrap_or_default(), state);
+                }
+            }
+        } else if let Some((label, taken, not_taken)) = self.get_refinements(instruction, state) {
+            self.pending.insert(label, taken);
+            self.apply(not_taken, state);
+        }
+
+        if let Some(register) = instruction.get_result_register() {
+            self.replaced.remove(register);
+            self.checks
+                .retain(|result, (object, _)| result != register && object != register);
+        }
+        if let Instruction::Command {
+            command,
+            parameters,
+        } = instruction
+        {
+            if let (
+                "instance-of",
+                [CommandParameter::Result(result), CommandParameter::Register(object), CommandParameter::Type(checked)],
+            ) = (command.as_str(), parameters.as_slice())
+            {
+                if result != object {
+                    self.checks
+                        .insert(result.clone(), (object.clone(), checked.clone()));
+                }
+            }
+        }
+        if instruction.is_statement() {
+            self.falls_through = get_branch_targets(instruction).1;
+        }
+    }
+
+    /// Determines the refinements implied by a comparison against zero: for the branch
+    /// target and for the next instruction.
+    fn get_refinements(
+        &self,
+        instruction: &'a Instruction,
+        state: &HashMap<Register, ResultType>,
+    ) -> Option<(&'a str, Refinements, Refinements)> {
+        let Instruction::Command {
+            command,
+            parameters,
+        } = instruction
+        else {
+            return None;
+        };
+        let jumps_if_zero = match command.as_str() {
+            "if-eqz" => true,
+            "if-nez" => false,
+            _ => return None,
+        };
+        let [CommandParameter::Register(register), CommandParameter::Label(label)] =
+            parameters.as_slice()
+        else {
+            return None;
+        };
+
+        let (zero, non_zero) = if let Some((object, checked)) = self.checks.get(register) {
+            (
+                Vec::new(),
+                vec![(object.clone(), Some(ResultType::Type(checked.clone())))],
+            )
+        } else {
+            match state.get(register) {
+                Some(ResultType::Type(Type::Object(_) | Type::Array(_))) => (
+                    vec![(register.clone(), Some(ResultType::Literal(Literal::Null)))],
+                    Vec::new(),
+                ),
+                // A null constant cannot be compared unequal, some other value got there
+                Some(ResultType::Literal(Literal::Null)) => {
+                    (Vec::new(), vec![(register.clone(), None)])
+                }
+                _ => return None,
+            }
+        };
+
+        if jumps_if_zero {
+            Some((label, zero, non_zero))
+        } else {
+            Some((label, non_zero, zero))
+        }
+    }
+
+    fn apply(&mut self, refinements: Refinements, state: &mut HashMap<Register, ResultType>) {
+        for (register, refined) in refinements {
+            self.replaced
+                .entry(register.clone())
+                .or_insert_with(|| state.get(&register).cloned());
+            match refined {
+                Some(refined) => state.insert(register, refined),
+                None => state.remove(&register),
+            };
+        }
+    }
+
+    fn restore(&mut self, state: &mut HashMap<Register, ResultType>) {
+        for (register, original) in self.replaced.drain() {
+            match original {
+                Some(original) => state.insert(register, original),
+                None => state.remove(&register),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ParseErrorDisplayed;
+    use crate::instruction::ResultType;
+    use crate::literal::Literal;
+    use crate::method::Method;
+    use crate::r#type::Type;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn narrow_types() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run(Ljava/lang/Object;)V
+                .locals 1
+                instance-of v0, p0, Ljava/lang/String;
+                if-eqz v0, :cond_0
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                :cond_0
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                if-nez p0, :cond_1
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                return-void
+                :cond_1
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                instance-of v0, p0, [I
+                if-nez v0, :cond_2
+                return-void
+                :cond_2
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                return-void
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, method) = Method::read(&input)?;
+
+        let object = ResultType::Type(Type::Object("java.lang.Object".to_string()));
+        let expected = [
+            ResultType::Type(Type::Object("java.lang.String".to_string())),
+            object.clone(),
+            ResultType::Literal(Literal::Null),
+            object,
+            ResultType::Type(Type::Array(Box::new(Type::Int))),
+        ];
+        let calls = method.get_calls(&Type::Object("a".to_string()));
+        assert_eq!(calls.len(), expected.len());
+        for (call, expected) in calls.iter().zip(expected) {
+            assert_eq!(call.get_parameter(0), Some(&expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_instructions() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+            static run(Ljava/lang/Object;)V
+                .locals 1
+                instance-of v0, p0, Ljava/lang/String;
+                if-eqz v0, :cond_0
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                frobnicate p0
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                :cond_0
+                invoke-static {p0}, La;->b(Ljava/lang/Object;)V
+                return-void
+            .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        )
+        .lenient();
+        let (_, method) = Method::read(&input)?;
+
+        let calls = method.get_calls(&Type::Object("a".to_string()));
+        assert_eq!(calls.len(), 3);
+        assert_eq!(
+            calls[0].get_parameter(0),
+            Some(&ResultType::Type(Type::Object(
+                "java.lang.String".to_string()
+            )))
+        );
+        // The raw instruction might have assigned anything to the narrowed register
+        assert_eq!(calls[1].get_parameter(0), None);
+        assert_eq!(calls[2].get_parameter(0), None);
+
+        Ok(())
+    }
+}
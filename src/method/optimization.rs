@@ -1,7 +1,43 @@
-use std::collections::HashMap;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
-use super::Method;
-use crate::instruction::{CommandData, Instruction};
+use super::cfg::BasicBlock;
+use super::{Deadline, Method};
+use crate::diagnostics::warning;
+use crate::instruction::{CommandData, CommandParameter, Instruction, Register};
+use crate::literal::Literal;
+use crate::r#type::Type;
+
+/// Basic blocks and `try` ranges of a method, computed when first needed. These stay valid as
+/// long as no instructions are added or removed.
+#[derive(Debug, Default)]
+struct ControlFlow {
+    data: OnceCell<ControlFlowData>,
+}
+
+#[derive(Debug)]
+struct ControlFlowData {
+    blocks: Vec<BasicBlock>,
+    try_ranges: Vec<(usize, usize)>,
+}
+
+impl ControlFlowData {
+    fn in_try_block(&self, i: usize) -> bool {
+        self.try_ranges
+            .iter()
+            .any(|(start, end)| *start <= i && i <= *end)
+    }
+}
+
+impl ControlFlow {
+    fn get(&self, method: &Method) -> &ControlFlowData {
+        self.data.get_or_init(|| ControlFlowData {
+            blocks: method.get_basic_blocks(),
+            try_ranges: method.get_try_ranges(),
+        })
+    }
+}
 
 impl Method {
     fn extract_data(&mut self) -> HashMap<String, CommandData> {
@@ -18,9 +54,10 @@ impl Method {
                     self.instructions.remove(i - 1);
                     i -= 1;
                 } else {
-                    eprintln!(
-                        "Warning: Data block not preceded by a label in method <{} {}()>",
-                        self.return_type, self.name
+                    warning!(
+                        "Data block not preceded by a label in method <{} {}()>",
+                        self.return_type,
+                        self.name
                     );
                 }
             } else {
@@ -64,19 +101,571 @@ impl Method {
                     return i - 1;
                 }
             }
-            eprintln!(
-                "Warning: Failed inlining result in method <{} {}()>",
-                self.return_type, self.name
+            warning!(
+                "Failed inlining result in method <{} {}()>",
+                self.return_type,
+                self.name
             );
         }
         i
     }
 
-    pub fn optimize(&mut self) {
+    fn get_try_ranges(&self) -> Vec<(usize, usize)> {
+        let labels = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| match instruction {
+                Instruction::Label(label) => Some((label.as_str(), i)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Catch {
+                    start_label,
+                    end_label,
+                    ..
+                } => Some((
+                    *labels.get(start_label.as_str())?,
+                    *labels.get(end_label.as_str())?,
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Checks whether the value of a register after instruction `j` can be read before the
+    /// register is written again, following all paths control flow can take. Writes within
+    /// `try` blocks don't count, an exception could occur before these.
+    fn is_read_after(&self, j: usize, register: &Register, flow: &ControlFlow) -> bool {
+        let flow = flow.get(self);
+        let blocks = &flow.blocks;
+        let Some(block) = blocks
+            .partition_point(|block| block.start <= j)
+            .checked_sub(1)
+        else {
+            return true;
+        };
+        let mut visited = HashSet::new();
+        let mut stack = vec![(block, j + 1)];
+        while let Some((block, start)) = stack.pop() {
+            let data = &blocks[block];
+            let mut overwritten = false;
+            for k in start..data.end {
+                let instruction = &self.instructions[k];
+                if matches!(instruction, Instruction::Raw(_))
+                    || instruction.count_register_uses(register) > 0
+                {
+                    return true;
+                }
+                if instruction.get_result_register() == Some(register) && !flow.in_try_block(k) {
+                    overwritten = true;
+                    break;
+                }
+            }
+            if !overwritten {
+                for &successor in &data.successors {
+                    if visited.insert(successor) {
+                        stack.push((successor, blocks[successor].start));
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Finds the instructions reading the value written to a register by instruction `i`,
+    /// provided that these follow without branches in between and that the value cannot be
+    /// read anywhere else. Returns `None` if the value is read elsewhere or modified in place.
+    fn find_uses(&self, i: usize, register: &Register, flow: &ControlFlow) -> Option<Vec<usize>> {
+        let mut result = Vec::new();
+        for (j, instruction) in self.instructions.iter().enumerate().skip(i + 1) {
+            match instruction {
+                // Raw instructions might use any register
+                Instruction::Raw(_) => return None,
+                // Control flow might merge here
+                Instruction::Label(_) => {
+                    return (!self.is_read_after(j - 1, register, flow)).then_some(result);
+                }
+                Instruction::Command { .. } => {
+                    let uses = instruction.count_register_uses(register);
+                    if uses > 0 {
+                        if instruction.modifies_in_place(register) {
+                            return None;
+                        }
+                        result.extend(std::iter::repeat_n(j, uses));
+                    }
+
+                    if instruction.get_result_register() == Some(register)
+                        || instruction.exits_method()
+                    {
+                        return Some(result);
+                    }
+                    if instruction.ends_block() {
+                        return (!self.is_read_after(j, register, flow)).then_some(result);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Some(result)
+    }
+
+    fn find_single_use_in(
+        &self,
+        i: usize,
+        register: &Register,
+        flow: &ControlFlow,
+    ) -> Option<usize> {
+        match self.find_uses(i, register, flow)?.as_slice() {
+            [j] => Some(*j),
+            _ => None,
+        }
+    }
+
+    /// Finds the only instruction reading the value written to a register by instruction `i`,
+    /// provided that the value cannot be read anywhere else.
+    pub fn find_single_use(&self, i: usize, register: &Register) -> Option<usize> {
+        self.find_single_use_in(i, register, &ControlFlow::default())
+    }
+
+    /// Renames local registers holding intermediate values into temporaries: `$stackN` if the
+    /// value is passed to the next command reading it, `$tmpN` if several commands read it
+    /// before it is discarded. Registers with debug information are considered real locals.
+    fn name_temporaries(&mut self, deadline: Deadline) {
+        let debug_locals =
+            self.instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::Local { register, .. }
+                    | Instruction::LocalRestart { register } => Some(register.clone()),
+                    _ => None,
+                })
+                .collect::<HashSet<_>>();
+        // Renaming registers doesn't change the control flow
+        let flow = ControlFlow::default();
+
+        let mut stack_counter = 0;
+        let mut tmp_counter = 0;
+        for i in 0..self.instructions.len() {
+            if deadline.is_reached() {
+                return;
+            }
+            let register = match self.instructions[i].get_result_register() {
+                Some(register @ Register::Local(_)) => register.clone(),
+                _ => continue,
+            };
+            if debug_locals.contains(&register.to_string()) || flow.get(self).in_try_block(i) {
+                continue;
+            }
+
+            let Some(mut uses) = self.find_uses(i, &register, &flow) else {
+                continue;
+            };
+            uses.dedup();
+            let temporary = match uses.len() {
+                0 => continue,
+                1 if self.instructions[uses[0]].count_register_uses(&register) == 1 => {
+                    stack_counter += 1;
+                    Register::Stack(stack_counter - 1)
+                }
+                // Filling an array is displayed as an assignment, the array is a real local then
+                _ if uses.iter().any(|&j| {
+                    matches!(&self.instructions[j], Instruction::Command { command, .. } if command == "fill-array-data")
+                }) =>
+                {
+                    continue
+                }
+                _ => {
+                    tmp_counter += 1;
+                    Register::Temporary(tmp_counter - 1)
+                }
+            };
+            if let Register::Local(index) = register {
+                self.temporaries.insert(index);
+            }
+            self.instructions[i].rename_result_register(temporary.clone());
+            for j in uses {
+                self.instructions[j].rename_used_register(&register, &temporary);
+            }
+        }
+    }
+
+    /// Finds the instruction reading a `$stackN` temporary defined by instruction `i`. Since
+    /// temporaries are only read once, only the current block needs to be searched.
+    fn find_temporary_use(&self, i: usize, register: &Register) -> Option<usize> {
+        for (j, instruction) in self.instructions.iter().enumerate().skip(i + 1) {
+            match instruction {
+                // Raw instructions might use any register
+                Instruction::Label(_) | Instruction::Raw(_) => return None,
+                Instruction::Command { .. } => {
+                    if instruction.count_register_uses(register) > 0 {
+                        return Some(j);
+                    }
+                    if instruction.ends_block() {
+                        return None;
+                    }
+                }
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// Removes the `const-string` instruction defining a register read by instruction `i`,
+    /// returning the string. This requires the value to be a `$stackN` temporary or to have no
+    /// other use before the register is written again.
+    fn take_string_constant(&mut self, i: usize, register: &Register) -> Option<Literal> {
+        let index = (0..i)
+            .rev()
+            .find(|&j| self.instructions[j].get_result_register() == Some(register))?;
+        if !matches!(register, Register::Stack(_))
+            && (self.instructions[index..i]
+                .iter()
+                .any(|instruction| matches!(instruction, Instruction::Label(_)))
+                || self.find_single_use(index, register) != Some(i))
+        {
+            return None;
+        }
+        if let Instruction::Command {
+            command,
+            parameters,
+        } = &self.instructions[index]
+        {
+            if let (true, Some(CommandParameter::Literal(literal @ Literal::String(_)))) =
+                (command.starts_with("const-string"), parameters.get(1))
+            {
+                let literal = literal.clone();
+                self.instructions.remove(index);
+                return Some(literal);
+            }
+        }
+        None
+    }
+
+    /// Recognizes an `if-nez` check skipping over code throwing `NullPointerException`,
+    /// returning the index of the `throw` command, the register checked and the message.
+    fn match_null_pointer_throw(&self, i: usize) -> Option<(usize, Register, Option<Literal>)> {
+        let (register, label) = match &self.instructions[i] {
+            Instruction::Command {
+                command,
+                parameters,
+            } if command == "if-nez" => match parameters.as_slice() {
+                [CommandParameter::Register(register), CommandParameter::Label(label)] => {
+                    (register.clone(), label)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let mut exception = None;
+        let mut message = None;
+        let mut initialized = false;
+        for (j, instruction) in self.instructions.iter().enumerate().skip(i + 1) {
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                if instruction.is_annotation() {
+                    continue;
+                }
+                return None;
+            };
+
+            match (command.as_str(), parameters.as_slice()) {
+                (
+                    "new-instance",
+                    [CommandParameter::Result(result), CommandParameter::Type(Type::Object(name))],
+                ) if exception.is_none() && name == "java.lang.NullPointerException" => {
+                    exception = Some(result);
+                }
+                (
+                    "const-string" | "const-string/jumbo",
+                    [CommandParameter::Result(_), CommandParameter::Literal(literal)],
+                ) if message.is_none() => {
+                    message = Some(literal.clone());
+                }
+                (
+                    "invoke-direct" | "invoke-direct/range",
+                    [_, CommandParameter::Registers(_), CommandParameter::Method(method)],
+                ) if !initialized
+                    && method.method_name == "<init>"
+                    && exception.is_some()
+                    && instruction.get_used_registers().first() == exception =>
+                {
+                    initialized = true;
+                }
+                ("throw", [CommandParameter::Register(thrown)])
+                    if initialized && Some(thrown) == exception =>
+                {
+                    let next = self.instructions[j + 1..]
+                        .iter()
+                        .find(|instruction| !instruction.is_annotation());
+                    return match next {
+                        Some(Instruction::Label(target)) if target == label => {
+                            Some((j, register, message))
+                        }
+                        _ => None,
+                    };
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Collapses null checks, whether explicit or via helper methods, into assertions.
+    fn collapse_null_checks(&mut self, deadline: Deadline) {
+        let mut i = 0;
+        while i < self.instructions.len() && !deadline.is_reached() {
+            if let Some((register, message)) = self.instructions[i].get_null_check() {
+                let message = match message {
+                    Some(message) => {
+                        let literal = self.take_string_constant(i, &message);
+                        if literal.is_some() {
+                            i -= 1;
+                        }
+                        literal
+                    }
+                    None => None,
+                };
+                self.instructions[i] = Instruction::AssertNotNull { register, message };
+            } else if let Some((end, register, message)) = self.match_null_pointer_throw(i) {
+                self.instructions
+                    .splice(i..=end, [Instruction::AssertNotNull { register, message }]);
+            }
+            i += 1;
+        }
+    }
+
+    /// Finds the element type of the array stored in the register, provided that it has been
+    /// created by a `new-array` command.
+    fn get_array_element_type(&self, i: usize, register: &Register) -> Option<Type> {
+        let instruction = self.instructions[..i]
+            .iter()
+            .rev()
+            .find(|instruction| instruction.get_result_register() == Some(register))?;
+        match instruction {
+            Instruction::Command {
+                command,
+                parameters,
+            } if command == "new-array" => match parameters.get(2) {
+                Some(CommandParameter::Type(array_type)) => array_type.element_type().cloned(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Reinterprets `fill-array-data` elements according to the element type of the array.
+    fn type_array_data(&mut self, deadline: Deadline) {
+        for i in 0..self.instructions.len() {
+            if deadline.is_reached() {
+                return;
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = &self.instructions[i]
+            else {
+                continue;
+            };
+            if command != "fill-array-data" {
+                continue;
+            }
+            let Some(CommandParameter::Register(register)) = parameters.first() else {
+                continue;
+            };
+            let Some(element_type) = self.get_array_element_type(i, register) else {
+                continue;
+            };
+
+            if let Instruction::Command { parameters, .. } = &mut self.instructions[i] {
+                if let Some(CommandParameter::Data(CommandData::Array(size, values))) =
+                    parameters.get_mut(1)
+                {
+                    if element_type.element_size() == Some(*size) {
+                        for value in values.iter_mut() {
+                            *value = value.with_element_type(&element_type);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Determines the type a register value is consumed as by instruction `j`, for values stored
+    /// into typed arrays or fields, passed to a method or returned.
+    fn get_value_context(&self, j: usize, register: &Register) -> Option<Type> {
+        let instruction = &self.instructions[j];
+        if let Some((method, registers)) = instruction.get_call_arguments() {
+            let parameter_types = &method.call_signature.parameter_types;
+            let offset = registers.len().checked_sub(parameter_types.len())?;
+            let index = registers.iter().position(|current| current == register)?;
+            return parameter_types.get(index.checked_sub(offset)?).cloned();
+        }
+
+        let Instruction::Command {
+            command,
+            parameters,
+        } = instruction
+        else {
+            return None;
+        };
+        if parameters.first() != Some(&CommandParameter::Register(register.clone())) {
+            return None;
+        }
+        if command == "return" {
+            return Some(self.return_type.clone());
+        }
+        let (family, suffix) = command.split_once('-')?;
+        if !matches!(family, "aput" | "iput" | "sput") {
+            return None;
+        }
+        match suffix {
+            "boolean" => Some(Type::Bool),
+            "char" => Some(Type::Char),
+            _ => None,
+        }
+    }
+
+    /// Turns integer constants into boolean or character literals if the value is only used
+    /// as such, e.g. stored into a char array or passed as a boolean parameter.
+    fn type_constants(&mut self, deadline: Deadline) {
+        for i in 0..self.instructions.len() {
+            if deadline.is_reached() {
+                return;
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = &self.instructions[i]
+            else {
+                continue;
+            };
+            if !command.starts_with("const/") && command != "const" {
+                continue;
+            }
+            let (
+                Some(CommandParameter::Result(register)),
+                Some(CommandParameter::Literal(literal)),
+            ) = (parameters.first(), parameters.get(1))
+            else {
+                continue;
+            };
+            let Some(value) = literal.get_integer() else {
+                continue;
+            };
+
+            let context = self
+                .find_single_use(i, register)
+                .and_then(|j| self.get_value_context(j, register));
+            let typed = match context {
+                Some(Type::Bool) if value == 0 || value == 1 => Literal::Bool(value == 1),
+                Some(Type::Char) => match u16::try_from(value) {
+                    Ok(value) => Literal::Char(value),
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            if let Instruction::Command { parameters, .. } = &mut self.instructions[i] {
+                parameters[1] = CommandParameter::Literal(typed);
+            }
+        }
+    }
+
+    /// Follows the chain of nested expressions from instruction `i` up to the instruction
+    /// that isn't nested, returning all instructions along the way including `i`.
+    fn get_expression_path(nested: &HashMap<usize, usize>, i: usize) -> Vec<usize> {
+        let mut result = vec![i];
+        let mut current = i;
+        while let Some(&next) = nested.get(&current) {
+            result.push(next);
+            current = next;
+        }
+        result
+    }
+
+    /// Checks whether instruction `k` is evaluated after instruction `i` once both are nested
+    /// into the same expression tree. Arguments are evaluated in the order of the parameters.
+    fn is_evaluated_after(&self, nested: &HashMap<usize, usize>, i: usize, k: usize) -> bool {
+        let path_i = Self::get_expression_path(nested, i);
+        let path_k = Self::get_expression_path(nested, k);
+        if path_i.last() != path_k.last() {
+            return false;
+        }
+
+        let Some(common) = path_k.iter().position(|node| path_i.contains(node)) else {
+            return false;
+        };
+        if common == 0 {
+            // Instruction k is an ancestor of i
+            return true;
+        }
+
+        let parent = path_k[common];
+        let child_i = path_i[path_i.iter().position(|node| *node == parent).unwrap() - 1];
+        let child_k = path_k[common - 1];
+        let operands = self.instructions[parent].get_used_registers();
+        let position = |child: usize| {
+            self.instructions[child]
+                .get_result_register()
+                .and_then(|register| operands.iter().position(|r| r == register))
+        };
+        position(child_k) > position(child_i)
+    }
+
+    /// Builds expression trees by following def-use chains: maps `$stackN` definitions that
+    /// can be nested into the instruction using them to the index of that instruction. Nesting
+    /// is rejected if it would change the order of side effects or if a value read by the
+    /// definition is overwritten before the expression is evaluated.
+    pub fn find_nested_expressions(&self) -> HashMap<usize, usize> {
+        let mut nested = HashMap::new();
+        for i in (0..self.instructions.len()).rev() {
+            let register = match self.instructions[i].get_result_register() {
+                Some(register @ Register::Stack(_)) => register,
+                _ => continue,
+            };
+            let Some(j) = self.find_temporary_use(i, register) else {
+                continue;
+            };
+            let root = *Self::get_expression_path(&nested, j).last().unwrap();
+
+            nested.insert(i, j);
+            let operands = self.instructions[i].get_used_registers();
+            let side_effects = self.instructions[i].has_side_effects();
+            let conflict = (i + 1..root).any(|k| {
+                let instruction = &self.instructions[k];
+                instruction
+                    .get_result_register()
+                    .filter(|register| operands.contains(register))
+                    .is_some()
+                    || operands
+                        .iter()
+                        .any(|register| instruction.modifies_in_place(register))
+                    || (side_effects
+                        && instruction.has_side_effects()
+                        && !self.is_evaluated_after(&nested, i, k))
+            });
+            if conflict {
+                nested.remove(&i);
+            }
+        }
+        nested
+    }
+
+    /// Resolves the data blocks referenced by commands, merges line numbers and inlines
+    /// `move-result` commands into the calls producing the results.
+    fn simplify_commands(&mut self, deadline: Deadline) {
         let command_data = self.extract_data();
 
         let mut i = 0;
-        while i < self.instructions.len() {
+        while i < self.instructions.len() && !deadline.is_reached() {
             self.instructions[i].fix_check_cast();
             self.instructions[i].resolve_data(&command_data);
             i = self.merge_line_numbers(i);
@@ -84,21 +673,78 @@ impl Method {
             i += 1;
         }
     }
+
+    /// Optimization passes in the order they run.
+    const PASSES: [fn(&mut Self, Deadline); 9] = [
+        Self::simplify_commands,
+        Self::replace_exception_jumps,
+        Self::type_array_data,
+        Self::reconstruct_hidden_strings,
+        Self::type_constants,
+        Self::reconstruct_try_with_resources,
+        Self::deduplicate_finally,
+        Self::name_temporaries,
+        Self::collapse_null_checks,
+    ];
+
+    /// Checks whether the method contains raw instructions. The passes cannot know which
+    /// registers these use or assign and where they jump, so such methods are left unoptimized.
+    fn has_raw_instructions(&self) -> bool {
+        self.instructions
+            .iter()
+            .any(|instruction| matches!(instruction, Instruction::Raw(_)))
+    }
+
+    pub fn optimize(&mut self) {
+        if self.has_raw_instructions() {
+            return;
+        }
+        for pass in Self::PASSES {
+            pass(self, Deadline::NONE);
+        }
+    }
+
+    /// Optimizes the method like `optimize` unless this takes longer than the time limit, in
+    /// which case the method is left unoptimized. The passes check the limit as they go and
+    /// give up once it is exceeded, so that code making a pass blow up cannot stall the
+    /// conversion. Returns `false` if the limit was exceeded.
+    pub fn optimize_within(&mut self, limit: Duration) -> bool {
+        if self.has_raw_instructions() {
+            return true;
+        }
+        let deadline = Deadline::after(limit);
+        let original = self.instructions.clone();
+        for pass in Self::PASSES {
+            pass(self, deadline);
+            if deadline.is_reached() {
+                self.instructions = original;
+                self.temporaries.clear();
+                warning!(
+                    "Optimizing method {} exceeded the time limit of {} ms, leaving it unoptimized",
+                    self.name,
+                    limit.as_millis()
+                );
+                return false;
+            }
+        }
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
     use crate::tokenizer::Tokenizer;
 
     fn tokenizer(data: &str) -> Tokenizer {
         Tokenizer::new(data.to_string(), std::path::Path::new("dummy"))
     }
 
-    fn stringify(method: Method) -> String {
+    fn stringify(method: &Method, options: &OutputOptions) -> String {
         let mut cursor = std::io::Cursor::new(Vec::new());
-        method.write_jimple(&mut cursor).unwrap();
+        method.write_jimple(&mut cursor, options).unwrap();
         String::from_utf8_lossy(&cursor.into_inner())
             .split('\n')
             .map(|s| s.trim().to_string())
@@ -201,7 +847,493 @@ mod tests {
         "#.split('\n').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n");
 
         method.optimize();
-        assert_eq!(stringify(method), expected);
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn type_array_data() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static run()V
+                .locals 2
+                const/4 v0, 0x2
+                new-array v1, v0, [C
+                fill-array-data v1, :array_0
+                new-array v1, v0, [F
+                fill-array-data v1, :array_1
+                return-void
+
+                :array_0
+                .array-data 2
+                    0x61s
+                    -0x1s
+                .end array-data
+
+                :array_1
+                .array-data 4
+                    0x3f800000
+                    -0x40800000
+                .end array-data
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        let values = method
+            .instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Command { parameters, .. } => match parameters.get(1) {
+                    Some(CommandParameter::Data(CommandData::Array(size, values))) => {
+                        Some((*size, values.clone()))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                (2, vec![Literal::Char(0x61), Literal::Char(0xffff)]),
+                (4, vec![Literal::Float(1.0), Literal::Float(-1.0)]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn char_literals() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static run()[C
+                .locals 3
+                const/4 v0, 0x3
+                new-array v0, v0, [C
+                fill-array-data v0, :array_0
+                const/4 v1, 0x2
+                const/16 v2, 0x21
+                aput-char v2, v0, v1
+                return-object v0
+
+                :array_0
+                .array-data 2
+                    0x48s
+                    0x69s
+                    0x3fs
+                .end array-data
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        let expected = r#"
+            static char[] run()
+            {
+                v0 = new char[][0x3];
+                v0 = {
+                    // "Hi?"
+                    'H',
+                    'i',
+                    '?',
+                };
+                v0[0x2] = '!';
+                return v0;
+            }
+        "#
+        .split('\n')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn typed_constants() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static run(La;)Z
+                .locals 2
+                const/4 v0, 0x1
+                iput-boolean v0, p0, La;->enabled:Z
+                const/16 v0, 0x2c
+                const/16 v1, 0x10
+                invoke-virtual {p0, v0, v1}, La;->split(CI)V
+                const/4 v0, 0x0
+                return v0
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        method.optimize();
+
+        let expected = r#"
+            static bool run(a @p0)
+            {
+                p0.<bool a.enabled> = true;
+                invoke-virtual p0.<void a.split(char, int)>(',', 0x10);
+                return false;
+            }
+        "#;
+        let expected = expected
+            .split('\n')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        let options = OutputOptions {
+            decimal: true,
+            ..Default::default()
+        };
+        assert_eq!(stringify(&method, &options), expected.replace("0x10", "16"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn name_temporaries() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static test(I)I
+                .locals 4
+                .local v3, "count":I
+                const/4 v3, 0x0
+                const-string v0, "tag"
+                invoke-static {v0}, Ljava/lang/String;->valueOf(Ljava/lang/Object;)Ljava/lang/String;
+                move-result-object v0
+                invoke-static {v0, v0}, Landroid/util/Log;->d(Ljava/lang/String;Ljava/lang/String;)I
+                invoke-virtual {v0}, Ljava/lang/String;->length()I
+                move-result v2
+                if-eqz v2, :cond_0
+                const/4 v1, 0x1
+                add-int/2addr v1, p0
+                add-int/2addr v3, v1
+                :cond_0
+                return v3
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (input, mut method) = Method::read(&input)?;
+        assert!(input.expect_eof().is_ok());
+
+        let expected = r#"
+            static int test(int @p0)
+            {
+                v3 = 0x0;
+                $tmp0 = invoke-static <java.lang.String java.lang.String.valueOf(java.lang.Object)>("tag");
+                invoke-static <int android.util.Log.d(java.lang.String, java.lang.String)>($tmp0, $tmp0);
+                if (invoke-virtual $tmp0.<int java.lang.String.length()>() == 0) goto cond_0;
+                v1 = 0x1;
+                v1 += p0;
+                v3 += v1;
+                cond_0:
+                return v3;
+            }
+        "#.split('\n').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n");
+
+        method.optimize();
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nested_expressions() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static test(II)I
+                .locals 4
+                invoke-static {}, La;->first()I
+                move-result v0
+                invoke-static {}, La;->second()I
+                move-result v1
+                invoke-static {v1, v0}, La;->combine(II)I
+                move-result v2
+                mul-int/lit8 v3, p0, 0x3
+                add-int v2, v2, v3
+                sget v0, La;->field:I
+                add-int/lit8 v1, p1, 0x1
+                const/4 p1, 0x0
+                invoke-static {v0, v1}, La;->combine(II)I
+                move-result v0
+                return v0
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (input, mut method) = Method::read(&input)?;
+        assert!(input.expect_eof().is_ok());
+
+        let expected = r#"
+            static int test(int @p0, int @p1)
+            {
+                $stack0 = invoke-static <int a.first()>();
+                v2 = invoke-static <int a.combine(int, int)>(invoke-static <int a.second()>(), $stack0) + (p0 * 0x3);
+                $stack5 = p1 + 0x1;
+                p1 = 0x0;
+                return invoke-static <int a.combine(int, int)>(<int a.field>, $stack5);
+            }
+        "#.split('\n').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>().join("\n");
+
+        method.optimize();
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn collapse_null_checks() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method public test(Ljava/lang/String;Ljava/lang/Object;)V
+                .locals 2
+                const-string v0, "name"
+                invoke-static {p1, v0}, Lkotlin/jvm/internal/Intrinsics;->checkNotNullParameter(Ljava/lang/Object;Ljava/lang/String;)V
+                const/4 v0, 0x0
+                invoke-static {p2}, Ljava/util/Objects;->requireNonNull(Ljava/lang/Object;)Ljava/lang/Object;
+                if-nez p2, :cond_0
+                .line 5
+                new-instance v0, Ljava/lang/NullPointerException;
+                const-string v1, "value"
+                invoke-direct {v0, v1}, Ljava/lang/NullPointerException;-><init>(Ljava/lang/String;)V
+                # comments don't prevent collapsing the check
+                throw v0
+                :cond_0
+                return-void
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (input, mut method) = Method::read(&input)?;
+        assert!(input.expect_eof().is_ok());
+        method.optimize();
+
+        let expected = r#"
+            public void test(java.lang.String @p0, java.lang.Object @p1)
+            {
+                assert p1 != null : "name";
+                v0 = 0x0;
+                assert p2 != null;
+                assert p2 != null : "value";
+                cond_0:
+                return;
+            }
+        "#
+        .split('\n')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        let options = OutputOptions {
+            hide_assertions: true,
+            ..Default::default()
+        };
+        let expected = r#"
+            public void test(java.lang.String @p0, java.lang.Object @p1)
+            {
+                v0 = 0x0;
+                cond_0:
+                return;
+            }
+        "#
+        .split('\n')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+        assert_eq!(stringify(&method, &options), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn null_check_message_register() -> Result<(), ParseErrorDisplayed> {
+        // The message register isn't a temporary within a try block but the constant is only
+        // used by the check, even though a branch follows.
+        let input = tokenizer(
+            r#"
+            .method public test(Ljava/lang/Object;I)V
+                .locals 1
+                :try_start_0
+                const-string v0, "obj"
+                invoke-static {p1, v0}, Lkotlin/jvm/internal/Intrinsics;->checkNotNullParameter(Ljava/lang/Object;Ljava/lang/String;)V
+                invoke-virtual {p1}, Ljava/lang/Object;->hashCode()I
+                :try_end_0
+                .catch Ljava/lang/RuntimeException; {:try_start_0 .. :try_end_0} :catch_0
+                if-eqz p2, :cond_0
+                const-string v0, "set"
+                invoke-static {v0}, La;->log(Ljava/lang/String;)V
+                :cond_0
+                return-void
+                :catch_0
+                move-exception v0
+                return-void
+            .end method
+        "#
+            .trim(),
+        );
+
+        let input = input.expect_directive("method")?;
+        let (input, mut method) = Method::read(&input)?;
+        assert!(input.expect_eof().is_ok());
+        method.optimize();
+
+        let expected = r#"
+            public void test(java.lang.Object @p0, int @p1)
+            {
+                try_start_0:
+                assert p1 != null : "obj";
+                invoke-virtual p1.<int java.lang.Object.hashCode()>();
+                try_end_0:
+                catch java.lang.RuntimeException from try_start_0 to try_end_0 with catch_0;
+                if (p2 == 0) goto cond_0;
+                invoke-static <void a.log(java.lang.String)>("set");
+                cond_0:
+                return;
+                catch_0:
+                v0 = move-exception;
+                return;
+            }
+        "#
+        .split('\n')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn optimize_within() -> Result<(), ParseErrorDisplayed> {
+        let read = || -> Result<Method, ParseErrorDisplayed> {
+            let input = tokenizer(
+                r#"
+                .method static test()I
+                    .locals 1
+                    const-string v0, "tag"
+                    invoke-static {v0}, Ljava/lang/Integer;->parseInt(Ljava/lang/String;)I
+                    move-result v0
+                    return v0
+                .end method
+            "#
+                .trim(),
+            );
+            let input = input.expect_directive("method")?;
+            let (_, method) = Method::read(&input)?;
+            Ok(method)
+        };
+
+        let unoptimized = stringify(&read()?, &OutputOptions::default());
+        let mut expected = read()?;
+        expected.optimize();
+        let expected = stringify(&expected, &OutputOptions::default());
+        assert_ne!(unoptimized, expected);
+
+        let mut method = read()?;
+        assert!(!method.optimize_within(Duration::ZERO));
+        assert_eq!(stringify(&method, &OutputOptions::default()), unoptimized);
+        assert!(method.temporaries.is_empty());
+
+        let mut method = read()?;
+        assert!(method.optimize_within(Duration::from_secs(3600)));
+        assert_eq!(stringify(&method, &OutputOptions::default()), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn raw_instructions() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static test(Ljava/lang/Object;)V
+                .locals 1
+                instance-of v0, p0, Ljava/lang/String;
+                if-eqz v0, :cond_0
+                check-cast p0, Ljava/lang/String;
+                frobnicate p0
+                invoke-static {p0}, La;->b(Ljava/lang/String;)V
+                :cond_0
+                const/4 v0, 0x0
+                invoke-static {v0}, La;->c(I)V
+                return-void
+            .end method
+        "#
+            .trim(),
+        )
+        .lenient();
+        let input = input.expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        let unoptimized = method.instructions.clone();
+
+        method.optimize();
+        assert_eq!(method.instructions, unoptimized);
+        assert!(method.optimize_within(Duration::from_secs(3600)));
+        assert_eq!(method.instructions, unoptimized);
+        assert!(method.temporaries.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn passes_stop_at_deadline() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+            .method static test([C)V
+                .locals 2
+                const/16 v0, 0x61
+                const/4 v1, 0x0
+                aput-char v0, p0, v1
+                invoke-static {p0}, La;->b([C)V
+                return-void
+            .end method
+        "#
+            .trim(),
+        );
+        let input = input.expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        let unoptimized = method.instructions.clone();
+
+        // Passes checking the deadline give up right away if it has been reached already
+        let reached = Deadline::after(Duration::ZERO);
+        method.type_constants(reached);
+        method.name_temporaries(reached);
+        assert_eq!(method.instructions, unoptimized);
+        assert!(method.temporaries.is_empty());
+
+        method.type_constants(Deadline::NONE);
+        method.name_temporaries(Deadline::NONE);
+        assert_ne!(method.instructions, unoptimized);
+        assert!(!method.temporaries.is_empty());
 
         Ok(())
     }
@@ -1,15 +1,158 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+
 use super::{Method, MethodParameter};
 use crate::access_flag::AccessFlag;
 use crate::annotation::Annotation;
+use crate::diagnostics::warning;
 use crate::error::ParseError;
 use crate::instruction::Instruction;
-use crate::r#type::Type;
-use crate::tokenizer::Tokenizer;
+use crate::literal::Literal;
+use crate::r#type::{CallSignature, Type};
+use crate::tokenizer::{quote_identifier, Tokenizer};
 
 impl Method {
+    pub fn write_smali(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        write!(output, ".method ")?;
+        for flag in &self.visibility {
+            write!(output, "{flag} ")?;
+        }
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| parameter.parameter_type.to_descriptor())
+            .collect::<String>();
+        writeln!(
+            output,
+            "{}({parameters}){}",
+            quote_identifier(&self.name),
+            self.return_type.to_descriptor()
+        )?;
+        if let Some(locals) = self.locals {
+            writeln!(output, "    .locals {locals}")?;
+        }
+
+        // this pointer is an implicit parameter
+        let mut register = usize::from(!self.visibility.contains(&AccessFlag::Static));
+        for parameter in &self.parameters {
+            if parameter.name.is_some() || !parameter.annotations.is_empty() {
+                write!(output, "    .param p{register}")?;
+                if let Some(name) = &parameter.name {
+                    write!(
+                        output,
+                        ", {}",
+                        Literal::String(name.as_str().into()).to_smali()
+                    )?;
+                }
+                writeln!(output)?;
+                for annotation in &parameter.annotations {
+                    annotation.write_smali(output, "        ")?;
+                }
+                writeln!(output, "    .end param")?;
+            }
+            register += parameter.parameter_type.register_count();
+        }
+
+        for annotation in &self.annotations {
+            annotation.write_smali(output, "    ")?;
+        }
+        let mut instructions = self.instructions.iter().peekable();
+        while let Some(instruction) = instructions.next() {
+            if let Some(Instruction::TrailingComment(comment)) = instructions.peek() {
+                // Put the comment at the end of the instruction's first line
+                let mut buffer = Vec::new();
+                instruction.write_smali(&mut buffer)?;
+                let end = buffer
+                    .iter()
+                    .position(|&byte| byte == b'\n')
+                    .unwrap_or(buffer.len());
+                output.write_all(&buffer[..end])?;
+                write!(output, "    # {comment}")?;
+                output.write_all(&buffer[end..])?;
+                instructions.next();
+            } else {
+                instruction.write_smali(output)?;
+            }
+        }
+        writeln!(output, ".end method")
+    }
+
+    /// Reads the name and call signature from the method declaration without consuming input.
+    pub fn read_signature(input: &Tokenizer) -> Result<(String, CallSignature), ParseError> {
+        let (input, _) = AccessFlag::read_list(input);
+        let (input, name) = input.read_identifier()?;
+        let (_, call_signature) = CallSignature::read(&input)?;
+        Ok((name, call_signature))
+    }
+
+    /// Finds the parameter stored in a register, `None` if the register number doesn't match
+    /// the start of a parameter.
+    fn get_parameter_index(
+        register: i64,
+        has_this: bool,
+        parameters: &[MethodParameter],
+    ) -> Option<usize> {
+        // this pointer is an implicit parameter
+        let mut register = register - i64::from(has_this);
+        let mut index = 0;
+        while index < parameters.len() && register > 0 {
+            register -= parameters[index].parameter_type.register_count() as i64;
+            index += 1;
+        }
+        (register == 0 && index < parameters.len()).then_some(index)
+    }
+
+    /// Reads a `.param` directive following the directive name, recording the name and
+    /// annotations of the parameter. Like in smali, `.end param` is optional: without it, any
+    /// annotations following belong to the method.
+    fn read_parameter(
+        input: &Tokenizer,
+        has_this: bool,
+        parameters: &mut [MethodParameter],
+        method_annotations: &mut Vec<Annotation>,
+    ) -> Result<Tokenizer, ParseError> {
+        let start = input.clone();
+        let input = input.expect_char('p')?;
+        let (mut input, register) = input.read_number()?;
+        let index = Self::get_parameter_index(register, has_this, parameters)
+            .ok_or_else(|| start.unexpected("a valid parameter number".into()))?;
+
+        if let Ok(i) = input.expect_char(',') {
+            let name;
+            (input, name) = Literal::read(&i)?;
+            parameters[index].name = name.get_string();
+        }
+        // baksmali adds the parameter type as a comment
+        input = input.expect_eol()?;
+
+        let mut annotations = Vec::new();
+        while input.peek_directive() == Some("annotation") {
+            input = input.expect_directive("annotation")?;
+
+            let annotation;
+            (input, annotation) = Annotation::read(&input, false)?;
+            annotations.push(annotation);
+        }
+
+        match input
+            .expect_directive("end")
+            .and_then(|input| input.expect_keyword("param"))
+        {
+            Ok(i) => {
+                parameters[index].annotations.append(&mut annotations);
+                i.expect_eol()
+            }
+            Err(_) => {
+                method_annotations.append(&mut annotations);
+                Ok(input)
+            }
+        }
+    }
+
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        let start = input;
         let (input, visibility) = AccessFlag::read_list(input);
-        let (input, name) = input.read_keyword()?;
+        let (input, name) = input.read_identifier()?;
 
         let mut input = input.expect_char('(')?;
         let mut parameters = Vec::new();
@@ -18,6 +161,7 @@ impl Method {
             (input, parameter_type) = Type::read(&input)?;
             parameters.push(MethodParameter {
                 parameter_type,
+                name: None,
                 annotations: Vec::new(),
             });
         }
@@ -27,60 +171,63 @@ impl Method {
         let mut input = input.expect_eol()?;
 
         let mut annotations = Vec::new();
-        let mut instructions = Vec::new();
-        while input.expect_directive("end").is_err() {
-            if let Ok(i) = input.expect_directive("annotation") {
-                input = i;
+        let mut locals = None;
+        let mut instructions = input
+            .comments_since(start.checkpoint())
+            .into_iter()
+            .map(Instruction::Comment)
+            .collect::<Vec<_>>();
+        while input.peek_directive() != Some("end") {
+            let start = input.checkpoint();
+            let directive = input.peek_directive().map(str::to_string);
+            if directive.as_deref() == Some("annotation") {
+                input = input.expect_directive("annotation")?;
 
                 let annotation;
                 (input, annotation) = Annotation::read(&input, false)?;
                 annotations.push(annotation);
-            } else if let Ok(i) = input.expect_directive("locals") {
-                input = i;
-
-                (input, _) = input.read_number()?;
-                input = input.expect_eol()?;
-            } else if let Ok(i) = input.expect_directive("param") {
-                input = i;
+            } else if directive.as_deref() == Some("locals") {
+                input = input.expect_directive("locals")?;
 
                 let start = input.clone();
-                input = input.expect_char('p')?;
-
-                let mut index;
-                (input, index) = input.read_number()?;
-                if !visibility.contains(&AccessFlag::Static) {
-                    // this pointer is an implicit parameter
-                    index -= 1;
-                }
-
-                let mut param_index = 0;
-                while param_index < parameters.len() && index > 0 {
-                    index -= parameters[param_index].parameter_type.register_count() as i64;
-                    param_index += 1;
-                }
-
-                if index < 0 || param_index >= parameters.len() {
-                    eprintln!("{index} {param_index} {:?}", parameters);
-                    return Err(start.unexpected("a valid parameter number".into()));
-                }
-
-                (input, _) = input.read_to(&['\n']);
-                input = input.expect_eol()?;
-                while input.expect_directive("end").is_err() {
-                    input = input.expect_directive("annotation")?;
-
-                    let annotation;
-                    (input, annotation) = Annotation::read(&input, false)?;
-                    parameters[param_index].annotations.push(annotation);
-                }
-
-                input = input.expect_directive("end")?;
-                input = input.expect_keyword("param")?;
+                let count;
+                (input, count) = input.read_number()?;
+                locals = Some(
+                    usize::try_from(count)
+                        .map_err(|_| start.unexpected("a register count".into()))?,
+                );
                 input = input.expect_eol()?;
+            } else if directive.as_deref() == Some("param") {
+                input = input.expect_directive("param")?;
+                input = Self::read_parameter(
+                    &input,
+                    !visibility.contains(&AccessFlag::Static),
+                    &mut parameters,
+                    &mut annotations,
+                )?;
             } else {
                 let instruction;
-                (input, instruction) = Instruction::read(&input)?;
+                (input, instruction) = match Instruction::read(&input) {
+                    Ok(result) => result,
+                    Err(error) if input.is_lenient() => {
+                        warning!(
+                            "Keeping code that failed to parse as is: {}",
+                            error.to_short_string()
+                        );
+                        Instruction::read_raw(&input)?
+                    }
+                    Err(error) => return Err(error),
+                };
+                // Raw instructions contain the comment already
+                let is_raw = matches!(instruction, Instruction::Raw(_));
                 instructions.push(instruction);
+                if input.keeps_trailing_comments() && !is_raw {
+                    instructions.extend(
+                        input
+                            .trailing_comment(start)
+                            .map(Instruction::TrailingComment),
+                    );
+                }
             }
 
             while let Ok(i) = input.expect_directive("end") {
@@ -88,16 +235,38 @@ impl Method {
                     // Ignore .end local line, it has no meaning for us
                     (input, _) = i.read_to(&['\n']);
                     input = input.expect_eol()?;
+                } else if input.is_lenient() && i.expect_keyword("method").is_err() {
+                    // End of a block whose start failed to parse
+                    let instruction;
+                    (input, instruction) = Instruction::read_raw(&input)?;
+                    instructions.push(instruction);
                 } else {
                     break;
                 }
             }
+
+            // Comments are attached to the instruction following them
+            instructions.extend(
+                input
+                    .comments_since(start)
+                    .into_iter()
+                    .map(Instruction::Comment),
+            );
         }
 
         let input = input.expect_directive("end")?;
         let input = input.expect_keyword("method")?;
         let input = input.expect_eol()?;
 
+        if let Some(flag) = visibility
+            .iter()
+            .find(|flag| matches!(flag, AccessFlag::Abstract | AccessFlag::Native))
+        {
+            if instructions.iter().any(Instruction::is_command) {
+                warning!("Method {name} is declared {flag} but contains code");
+            }
+        }
+
         Ok((
             input,
             Self {
@@ -106,7 +275,10 @@ impl Method {
                 parameters,
                 return_type,
                 annotations,
+                comments: Vec::new(),
+                locals,
                 instructions,
+                temporaries: BTreeSet::new(),
             },
         ))
     }
@@ -131,7 +303,7 @@ mod tests {
             r#"
                 .method public synthetic constructor <init>(Ldv/a;Ldv/b;)V
                     .locals 1
-                    .param p1    # Ldv/a;
+                    .param p1, "source"    # Ldv/a;
                         .annotation runtime Lz20/t;
                             value = "something"
                         .end annotation
@@ -167,19 +339,21 @@ mod tests {
                 parameters: vec![
                     MethodParameter {
                         parameter_type: Type::Object("dv.a".to_string()),
+                        name: Some("source".to_string()),
                         annotations: vec![Annotation {
                             annotation_type: Type::Object("z20.t".to_string()),
                             visibility: AnnotationVisibility::Runtime,
                             parameters: vec![AnnotationParameter {
                                 name: "value".to_string(),
                                 value: AnnotationParameterValue::Literal(Literal::String(
-                                    "something".to_string()
+                                    "something".into()
                                 )),
                             }],
                         }],
                     },
                     MethodParameter {
                         parameter_type: Type::Object("dv.b".to_string()),
+                        name: None,
                         annotations: Vec::new(),
                     },
                 ],
@@ -190,15 +364,17 @@ mod tests {
                     parameters: vec![AnnotationParameter {
                         name: "value".to_string(),
                         value: AnnotationParameterValue::Array(
-                            vec!["(", "Ldv/a<", "Lqu/x;", ">,Ldv/b;)V"]
+                            ["(", "Ldv/a<", "Lqu/x;", ">,Ldv/b;)V"]
                                 .iter()
                                 .map(|v| AnnotationParameterValue::Literal(Literal::String(
-                                    v.to_string()
+                                    (*v).into()
                                 )))
                                 .collect()
                         ),
                     }],
                 }],
+                comments: Vec::new(),
+                locals: Some(1),
                 instructions: vec![
                     Instruction::Command {
                         command: "invoke-direct".to_string(),
@@ -222,10 +398,180 @@ mod tests {
                         parameters: Vec::new(),
                     }
                 ],
+                temporaries: BTreeSet::new(),
             }
         );
         assert!(input.expect_eof().is_ok());
 
         Ok(())
     }
+
+    #[test]
+    fn read_comments() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+                .method static run()V
+                    # entry point
+                    .locals 0
+
+                    # called for side effects
+                    invoke-static {}, La;->init()V # same line
+                    return-void
+                .end method
+            "#
+            .trim(),
+        );
+        let input = input.expect_directive("method")?;
+        let (_, method) = Method::read(&input)?;
+        assert_eq!(
+            method
+                .instructions
+                .iter()
+                .map(|instruction| match instruction {
+                    Instruction::Comment(comment) => comment.as_str(),
+                    Instruction::Command { command, .. } => command.as_str(),
+                    _ => "?",
+                })
+                .collect::<Vec<_>>(),
+            vec![
+                "entry point",
+                "called for side effects",
+                "invoke-static",
+                "return-void"
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_parameters() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+                .method static run(JLa;I[B)V
+                    .locals 0
+                    .param p0, "time"    # J
+                    .param p2    # La;
+                        # first annotation
+                        .annotation build La/b;
+                        .end annotation
+                        .annotation runtime La/c; # trailing
+                            value = 0x1
+                        .end annotation
+                    .end param
+                    .param p3, "count"
+                    .annotation system Ldalvik/annotation/Throws;
+                        value = {
+                            Ljava/io/IOException;
+                        }
+                    .end annotation
+                    .param p4
+                    return-void
+                .end method
+            "#
+            .trim(),
+        );
+        let input = input.expect_directive("method")?;
+        let (_, method) = Method::read(&input)?;
+        assert_eq!(
+            method
+                .parameters
+                .iter()
+                .map(|parameter| (
+                    parameter.name.as_deref(),
+                    parameter
+                        .annotations
+                        .iter()
+                        .map(|annotation| annotation.annotation_type.to_string())
+                        .collect::<Vec<_>>()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some("time"), vec![]),
+                (None, vec!["a.b".to_string(), "a.c".to_string()]),
+                (Some("count"), vec![]),
+                (None, vec![]),
+            ]
+        );
+        // Without .end param, annotations belong to the method
+        assert_eq!(method.annotations.len(), 1);
+        assert_eq!(
+            method.instructions,
+            vec![
+                Instruction::Comment("first annotation".to_string()),
+                Instruction::Command {
+                    command: "return-void".to_string(),
+                    parameters: Vec::new(),
+                }
+            ]
+        );
+
+        // Registers of wide parameters and this pointer
+        for (declaration, param) in [
+            (".method static run(JI)V", ".param p1"),
+            (".method static run(JI)V", ".param p3"),
+            (".method run(I)V", ".param p0"),
+        ] {
+            let input = tokenizer(&format!(
+                "{declaration}\n    {param}\n    return-void\n.end method"
+            ));
+            let input = input.expect_directive("method")?;
+            assert!(Method::read(&input).is_err(), "{declaration} {param}");
+        }
+
+        let input =
+            tokenizer(".method run(I)V\n    .param p1, \"x\" y\n    return-void\n.end method");
+        let input = input.expect_directive("method")?;
+        assert!(Method::read(&input).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_lenient() -> Result<(), ParseErrorDisplayed> {
+        let data = r#"
+            .method static run(I)I
+                .locals 1
+                const/4 v0, 0x1
+                frobnicate v0, p0
+                .packed-switch bogus
+                    :pswitch_0
+                .end packed-switch
+                add-int/2addr v0, p0
+                return v0
+            .end method
+        "#
+        .trim();
+        let input = tokenizer(data).expect_directive("method")?;
+        assert!(Method::read(&input).is_err());
+
+        let input = tokenizer(data).lenient().expect_directive("method")?;
+        let (_, mut method) = Method::read(&input)?;
+        assert_eq!(
+            method.instructions[1..5],
+            [
+                Instruction::Raw("frobnicate v0, p0".to_string()),
+                Instruction::Raw(".packed-switch bogus".to_string()),
+                Instruction::Label("pswitch_0".to_string()),
+                Instruction::Raw(".end packed-switch".to_string()),
+            ]
+        );
+
+        let mut output = Vec::new();
+        method.write_smali(&mut output).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("\n    frobnicate v0, p0\n    .packed-switch bogus\n"));
+
+        // Methods with raw instructions are left unoptimized, the constant stays in its register
+        method.optimize();
+        let mut output = Vec::new();
+        method
+            .write_jimple(&mut output, &Default::default())
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("        v0 = 0x1;\n        // unparsed: frobnicate v0, p0\n"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,982 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use super::cfg::get_branch_targets;
+use super::{Deadline, Method};
+use crate::instruction::{CommandParameter, Instruction, Register};
+use crate::r#type::Type;
+
+/// Exception handler code closing a resource, as generated by the compiler for
+/// try-with-resources statements.
+struct ResourceHandler {
+    resource: Register,
+    instructions: HashSet<usize>,
+}
+
+fn is_catch_all(exception: &Option<Type>) -> bool {
+    exception
+        .as_ref()
+        .map(|exception| exception.get_name() == "java.lang.Throwable")
+        .unwrap_or(true)
+}
+
+impl Method {
+    fn get_label_indexes(&self) -> HashMap<String, usize> {
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instruction)| match instruction {
+                Instruction::Label(label) => Some((label.clone(), i)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Finds the catch directives with the given handler, returning their indexes and the
+    /// instruction ranges they protect.
+    fn get_protected_ranges(
+        &self,
+        labels: &HashMap<String, usize>,
+        target: &str,
+    ) -> (Vec<usize>, Vec<(usize, usize)>) {
+        let mut catches = Vec::new();
+        let mut ranges = Vec::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::Catch {
+                start_label,
+                end_label,
+                target: catch_target,
+                ..
+            } = instruction
+            {
+                if catch_target == target {
+                    catches.push(i);
+                    if let (Some(&start), Some(&end)) =
+                        (labels.get(start_label), labels.get(end_label))
+                    {
+                        ranges.push((start, end));
+                    }
+                }
+            }
+        }
+        (catches, ranges)
+    }
+
+    /// Checks whether any instruction outside of the given set refers to a label within it.
+    fn has_outside_references(&self, instructions: &HashSet<usize>) -> bool {
+        let labels = instructions
+            .iter()
+            .filter_map(|&i| match &self.instructions[i] {
+                Instruction::Label(label) => Some(label.as_str()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+
+        self.instructions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !instructions.contains(i))
+            .any(|(_, instruction)| {
+                instruction
+                    .get_referenced_labels()
+                    .iter()
+                    .any(|label| labels.contains(label))
+            })
+    }
+
+    /// Follows all code paths starting at the exception handler and verifies that these merely
+    /// close a resource, add exceptions thrown by `close()` to the suppressed list and rethrow
+    /// the original exception.
+    fn match_resource_handler(
+        &self,
+        labels: &HashMap<String, usize>,
+        handler: usize,
+    ) -> Option<ResourceHandler> {
+        let mut exception = None;
+        let mut suppressed = None;
+        let mut null_checked = None;
+        let mut resource = None;
+        let mut visited = HashSet::new();
+        let mut pending = vec![handler];
+        while let Some(mut i) = pending.pop() {
+            loop {
+                if !visited.insert(i) {
+                    break;
+                }
+
+                let instruction = self.instructions.get(i)?;
+                match instruction {
+                    Instruction::Label(_) => (),
+                    _ if instruction.is_annotation() => (),
+                    Instruction::Catch {
+                        exception, target, ..
+                    } if is_catch_all(exception) => pending.push(*labels.get(target)?),
+                    Instruction::Command {
+                        command,
+                        parameters,
+                    } => {
+                        let used = instruction.get_used_registers();
+                        match (command.as_str(), parameters.as_slice()) {
+                            ("move-exception", [CommandParameter::Result(result)]) => {
+                                if exception.is_none() {
+                                    exception = Some(result.clone());
+                                } else if suppressed.is_none() {
+                                    suppressed = Some(result.clone());
+                                } else {
+                                    return None;
+                                }
+                            }
+                            (
+                                "if-eqz",
+                                [CommandParameter::Register(register), CommandParameter::Label(target)],
+                            ) => {
+                                null_checked = Some(register.clone());
+                                pending.push(*labels.get(target)?);
+                            }
+                            ("goto" | "goto/16" | "goto/32", [CommandParameter::Label(target)]) => {
+                                pending.push(*labels.get(target)?);
+                                break;
+                            }
+                            ("throw", _)
+                                if exception.is_some() && used.first() == exception.as_ref() =>
+                            {
+                                break;
+                            }
+                            (_, [_, _, CommandParameter::Method(method)])
+                                if method.method_name == "addSuppressed"
+                                    && used.len() == 2
+                                    && suppressed.is_some()
+                                    && used.first() == exception.as_ref()
+                                    && used.get(1) == suppressed.as_ref() => {}
+                            (_, [_, _, CommandParameter::Method(method)])
+                                if method.method_name == "$closeResource"
+                                    && exception.is_some()
+                                    && used.len() == 2
+                                    && used.first() == exception.as_ref() =>
+                            {
+                                resource = Some(used[1].clone());
+                            }
+                            _ => match instruction.get_close_call() {
+                                Some(closed) if exception.is_some() && resource.is_none() => {
+                                    resource = Some(closed);
+                                }
+                                _ => return None,
+                            },
+                        }
+                    }
+                    _ => return None,
+                }
+                i += 1;
+            }
+        }
+
+        let resource = resource?;
+        if null_checked.is_some() && null_checked.as_ref() != Some(&resource) {
+            return None;
+        }
+        Some(ResourceHandler {
+            resource,
+            instructions: visited,
+        })
+    }
+
+    /// Turns compiler-generated code closing resources on exceptions into `try (resource)`
+    /// blocks, removing the exception handlers and the `close()` call on the normal path.
+    pub(super) fn reconstruct_try_with_resources(&mut self, deadline: Deadline) {
+        let mut handled = HashSet::new();
+        while !deadline.is_reached() {
+            let labels = self.get_label_indexes();
+            let Some((target, handler)) =
+                self.instructions
+                    .iter()
+                    .find_map(|instruction| match instruction {
+                        Instruction::Catch {
+                            exception, target, ..
+                        } if is_catch_all(exception) && !handled.contains(target) => {
+                            let handler = labels.get(target)?;
+                            self.match_resource_handler(&labels, *handler)
+                                .map(|handler| (target.clone(), handler))
+                        }
+                        _ => None,
+                    })
+            else {
+                break;
+            };
+            handled.insert(target.clone());
+
+            let (catches, ranges) = self.get_protected_ranges(&labels, &target);
+            let (Some(start), Some(end)) = (
+                ranges.iter().map(|(start, _)| *start).min(),
+                ranges.iter().map(|(_, end)| *end).max(),
+            ) else {
+                continue;
+            };
+            let mut removed = handler.instructions;
+            if start >= end || removed.iter().any(|i| (start..end).contains(i)) {
+                continue;
+            }
+            removed.extend(catches);
+            if self.has_outside_references(&removed) {
+                continue;
+            }
+
+            // Closing the resource on the normal path is implied by the try block as well
+            if let Some((i, _)) = self
+                .instructions
+                .iter()
+                .enumerate()
+                .skip(end + 1)
+                .find(|(i, instruction)| !removed.contains(i) && instruction.is_command())
+            {
+                if self.instructions[i].get_close_call().as_ref() == Some(&handler.resource) {
+                    removed.insert(i);
+                }
+            }
+
+            let mut instructions = Vec::new();
+            for (i, instruction) in std::mem::take(&mut self.instructions)
+                .into_iter()
+                .enumerate()
+            {
+                if i == end {
+                    instructions.push(Instruction::BlockEnd);
+                }
+                if !removed.contains(&i) {
+                    instructions.push(instruction);
+                }
+                if i == start {
+                    instructions.push(Instruction::TryWithResources(handler.resource.clone()));
+                }
+            }
+            self.instructions = instructions;
+        }
+    }
+}
+
+/// Commands initializing the resource of a `try` block, to be moved into the block header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct ResourceInitializer {
+    /// Command assigning the resource
+    pub assignment: usize,
+    /// Constructor call following a `new-instance` assignment
+    pub constructor: Option<usize>,
+}
+
+impl ResourceInitializer {
+    pub fn indexes(&self) -> impl Iterator<Item = usize> {
+        std::iter::once(self.assignment).chain(self.constructor)
+    }
+}
+
+impl Method {
+    /// Finds the commands initializing the resources of `try` blocks that can be moved into
+    /// the block headers. Such a command assigns the resource right before the block, with
+    /// only annotations and labels that nothing jumps to in between. A resource can also be
+    /// created by a `new-instance` command followed by the constructor call. Returns the
+    /// indexes of the block headers mapped to the commands.
+    pub(super) fn find_resource_initializers(
+        &self,
+        nested: &HashMap<usize, usize>,
+    ) -> HashMap<usize, ResourceInitializer> {
+        let targets = self
+            .instructions
+            .iter()
+            .flat_map(|instruction| match instruction {
+                Instruction::Catch { target, .. } => vec![target.as_str()],
+                instruction => get_branch_targets(instruction).0,
+            })
+            .collect::<HashSet<_>>();
+        let find_previous = |i: usize| {
+            self.instructions[..i]
+                .iter()
+                .rposition(|instruction| match instruction {
+                    Instruction::Label(label) => targets.contains(label.as_str()),
+                    instruction => !instruction.is_annotation(),
+                })
+                .filter(|j| self.instructions[*j].is_command() && !nested.contains_key(j))
+        };
+
+        let mut result = HashMap::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            let Instruction::TryWithResources(resource) = instruction else {
+                continue;
+            };
+            let Some(j) = find_previous(i) else {
+                continue;
+            };
+            if self.instructions[j].get_result_register() == Some(resource) {
+                result.insert(
+                    i,
+                    ResourceInitializer {
+                        assignment: j,
+                        constructor: None,
+                    },
+                );
+                continue;
+            }
+
+            let initializer = &self.instructions[j];
+            let Instruction::Command { parameters, .. } = initializer else {
+                continue;
+            };
+            let is_constructor = initializer.get_command_family() == Some("invoke-direct")
+                && matches!(
+                    parameters.last(),
+                    Some(CommandParameter::Method(method)) if method.method_name == "<init>"
+                )
+                && initializer.get_used_registers().first() == Some(resource);
+            if !is_constructor {
+                continue;
+            }
+            if let Some(k) = find_previous(j).filter(|k| {
+                self.instructions[*k].get_command_family() == Some("new-instance")
+                    && self.instructions[*k].get_result_register() == Some(resource)
+            }) {
+                result.insert(
+                    i,
+                    ResourceInitializer {
+                        assignment: k,
+                        constructor: Some(j),
+                    },
+                );
+            }
+        }
+        result
+    }
+
+    /// Recognizes a catch-all handler that runs some code and rethrows the exception. Returns
+    /// all instructions of the handler and the indexes of the commands running in between.
+    fn match_finally_handler(&self, handler: usize) -> Option<(HashSet<usize>, Vec<usize>)> {
+        let mut instructions = HashSet::from([handler]);
+        let mut exception = None;
+        let mut body = Vec::new();
+        for (i, instruction) in self.instructions.iter().enumerate().skip(handler + 1) {
+            instructions.insert(i);
+            match instruction {
+                _ if instruction.is_annotation() => (),
+                Instruction::Command { command, .. } => match &exception {
+                    None if command == "move-exception" => {
+                        exception = instruction.get_result_register().cloned();
+                    }
+                    Some(exception) if command == "throw" => {
+                        return (!body.is_empty()
+                            && instruction.get_used_registers().first() == Some(exception))
+                        .then_some((instructions, body));
+                    }
+                    Some(exception)
+                        if !instruction.ends_block()
+                            && instruction.count_register_uses(exception) == 0 =>
+                    {
+                        body.push(i);
+                    }
+                    _ => return None,
+                },
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// Finds a copy of the commands in `body` right after instruction `i`.
+    fn find_finally_copy(&self, i: usize, body: &[usize]) -> Option<Vec<usize>> {
+        let mut result = Vec::new();
+        for (j, instruction) in self.instructions.iter().enumerate().skip(i + 1) {
+            if result.len() == body.len() {
+                break;
+            }
+            match instruction {
+                Instruction::Catch { .. } => (),
+                _ if instruction.is_annotation() => (),
+                Instruction::Command { .. }
+                    if *instruction == self.instructions[body[result.len()]] =>
+                {
+                    result.push(j);
+                }
+                _ => return None,
+            }
+        }
+        (result.len() == body.len()).then_some(result)
+    }
+
+    /// Replaces the copies of finally code that the compiler places on each exit of a try
+    /// block and in a catch-all handler by a single `finally` block.
+    pub(super) fn deduplicate_finally(&mut self, deadline: Deadline) {
+        let mut handled = HashSet::new();
+        while !deadline.is_reached() {
+            let labels = self.get_label_indexes();
+            let Some((target, handler, body)) =
+                self.instructions
+                    .iter()
+                    .find_map(|instruction| match instruction {
+                        Instruction::Catch {
+                            exception, target, ..
+                        } if is_catch_all(exception) && !handled.contains(target) => {
+                            let (handler, body) =
+                                self.match_finally_handler(*labels.get(target)?)?;
+                            Some((target.clone(), handler, body))
+                        }
+                        _ => None,
+                    })
+            else {
+                break;
+            };
+            handled.insert(target.clone());
+
+            let (catches, ranges) = self.get_protected_ranges(&labels, &target);
+            let (Some(start), Some(end)) = (
+                ranges.iter().map(|(start, _)| *start).min(),
+                ranges.iter().map(|(_, end)| *end).max(),
+            ) else {
+                continue;
+            };
+            let mut removed = handler;
+            if start >= end || removed.iter().any(|i| (start..end).contains(i)) {
+                continue;
+            }
+            removed.extend(catches);
+
+            let Some(copies) = ranges
+                .iter()
+                .map(|(_, end)| self.find_finally_copy(*end, &body))
+                .collect::<Option<Vec<_>>>()
+            else {
+                continue;
+            };
+
+            // The copy following the try block becomes the finally block, others are dropped
+            let mut finally = Vec::new();
+            for ((_, range_end), copy) in ranges.iter().zip(copies) {
+                if *range_end == end {
+                    finally = copy;
+                } else {
+                    removed.extend(copy);
+                }
+            }
+            let (Some(&finally_start), Some(&finally_end)) = (finally.first(), finally.last())
+            else {
+                continue;
+            };
+            if self.has_outside_references(&removed) {
+                continue;
+            }
+
+            let mut instructions = Vec::new();
+            for (i, instruction) in std::mem::take(&mut self.instructions)
+                .into_iter()
+                .enumerate()
+            {
+                if i == end {
+                    instructions.push(Instruction::BlockEnd);
+                }
+                if i == finally_start {
+                    instructions.push(Instruction::Finally);
+                }
+                if !removed.contains(&i) {
+                    instructions.push(instruction);
+                }
+                if i == start {
+                    instructions.push(Instruction::Try);
+                }
+                if i == finally_end {
+                    instructions.push(Instruction::BlockEnd);
+                }
+            }
+            self.instructions = instructions;
+        }
+    }
+}
+
+impl Method {
+    /// Determines the exception type in a register if every instruction writing the register
+    /// allocates or loads an exception of the same type, as with obfuscators reusing an
+    /// exception object for jumps.
+    fn get_preallocated_exception(&self, register: &Register) -> Option<Type> {
+        let mut result = None;
+        for instruction in &self.instructions {
+            if instruction.get_result_register() != Some(register) {
+                continue;
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            let exception_type = match (command.as_str(), parameters.as_slice()) {
+                ("new-instance", [_, CommandParameter::Type(exception_type)]) => exception_type,
+                ("sget-object" | "iget-object", [_, .., CommandParameter::Field(field)]) => {
+                    &field.field_type
+                }
+                _ => return None,
+            };
+            if result.is_some_and(|result| result != exception_type) {
+                return None;
+            }
+            result = Some(exception_type);
+        }
+        result.cloned()
+    }
+
+    /// Finds the handler within the method that an exception thrown by instruction `i` will
+    /// end up in. Only the first handler covering the instruction is considered as the class
+    /// hierarchy is unknown.
+    fn find_local_handler(
+        &self,
+        labels: &HashMap<String, usize>,
+        i: usize,
+        exception_type: &Type,
+    ) -> Option<String> {
+        self.instructions
+            .iter()
+            .find_map(|instruction| match instruction {
+                Instruction::Catch {
+                    exception,
+                    start_label,
+                    end_label,
+                    target,
+                } if (*labels.get(start_label)?..*labels.get(end_label)?).contains(&i) => {
+                    Some((exception, target))
+                }
+                _ => None,
+            })
+            .filter(|(exception, _)| {
+                is_catch_all(exception) || exception.as_ref() == Some(exception_type)
+            })
+            .map(|(_, target)| target.clone())
+    }
+
+    /// Follows all code paths starting at instruction `i` and checks whether the register is
+    /// overwritten or the method exited before the register's value is read.
+    fn is_value_unused(
+        &self,
+        labels: &HashMap<String, usize>,
+        i: usize,
+        register: &Register,
+    ) -> bool {
+        let mut visited = HashSet::new();
+        let mut pending = vec![i];
+        while let Some(mut i) = pending.pop() {
+            while let Some(instruction) = self.instructions.get(i) {
+                if !visited.insert(i) {
+                    break;
+                }
+                if !instruction.is_command() {
+                    i += 1;
+                    continue;
+                }
+                if instruction.count_register_uses(register) > 0 {
+                    return false;
+                }
+                if instruction.get_result_register() == Some(register) || instruction.exits_method()
+                {
+                    break;
+                }
+                for label in instruction.get_referenced_labels() {
+                    match labels.get(label) {
+                        Some(target) => pending.push(*target),
+                        None => return false,
+                    }
+                }
+                if instruction.get_command_family() == Some("goto") {
+                    break;
+                }
+                i += 1;
+            }
+        }
+        true
+    }
+
+    /// Replaces jumps implemented by throwing a pre-allocated exception into a handler of the
+    /// same method with `goto` commands. The handler has to ignore the exception object, the
+    /// jump skips its `move-exception` command then.
+    pub(super) fn replace_exception_jumps(&mut self, deadline: Deadline) {
+        let labels = self.get_label_indexes();
+        let mut jumps = Vec::new();
+        for (i, instruction) in self.instructions.iter().enumerate() {
+            if deadline.is_reached() {
+                return;
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            let (command, [CommandParameter::Register(register)]) =
+                (command.as_str(), parameters.as_slice())
+            else {
+                continue;
+            };
+            if command != "throw" {
+                continue;
+            }
+            let Some(exception_type) = self.get_preallocated_exception(register) else {
+                continue;
+            };
+            let Some(target) = self.find_local_handler(&labels, i, &exception_type) else {
+                continue;
+            };
+            let Some((handler, instruction)) = self
+                .instructions
+                .iter()
+                .enumerate()
+                .skip(labels[&target])
+                .find(|(_, instruction)| instruction.is_command())
+            else {
+                continue;
+            };
+            let exception = match instruction {
+                Instruction::Command {
+                    command,
+                    parameters,
+                } if command == "move-exception" => match parameters.as_slice() {
+                    [CommandParameter::Result(exception)] => Some(exception),
+                    _ => continue,
+                },
+                _ => None,
+            };
+            match exception {
+                Some(exception) if self.is_value_unused(&labels, handler + 1, exception) => {
+                    jumps.push((i, target, Some(handler)))
+                }
+                Some(_) => (),
+                None => jumps.push((i, target, None)),
+            }
+        }
+
+        // Jumps skipping `move-exception` need a label following it
+        let mut new_labels = HashMap::new();
+        for (i, target, handler) in jumps {
+            let label = match handler {
+                Some(handler) => {
+                    let label = format!("{target}_jump");
+                    new_labels.insert(handler, label.clone());
+                    label
+                }
+                None => target,
+            };
+            self.instructions[i] = Instruction::Command {
+                command: "goto".to_string(),
+                parameters: vec![CommandParameter::Label(label)],
+            };
+        }
+        let mut new_labels = new_labels.into_iter().collect::<Vec<_>>();
+        new_labels.sort_by_key(|(handler, _)| Reverse(*handler));
+        for (handler, label) in new_labels {
+            self.instructions
+                .insert(handler + 1, Instruction::Label(label));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::read_method;
+
+    fn stringify(method: &Method) -> String {
+        let mut output = Vec::new();
+        method
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn try_with_resources() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method static read(Ljava/io/InputStream;)I
+                .locals 3
+                invoke-static {p0}, La;->wrap(Ljava/io/InputStream;)Ljava/io/Reader;
+                move-result-object v0
+                :try_start_0
+                invoke-virtual {v0}, Ljava/io/Reader;->read()I
+                move-result v1
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                return v1
+
+                :catchall_0
+                move-exception v1
+                :try_start_1
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                :try_end_1
+                .catchall {:try_start_1 .. :try_end_1} :catchall_1
+                goto :goto_0
+
+                :catchall_1
+                move-exception v2
+                invoke-virtual {v1, v2}, Ljava/lang/Throwable;->addSuppressed(Ljava/lang/Throwable;)V
+
+                :goto_0
+                throw v1
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        assert_eq!(
+            stringify(&method),
+            r#"    static int read(java.io.InputStream @p0)
+    {
+    try_start_0:
+        try (java.io.Reader v0 = invoke-static <java.io.Reader a.wrap(java.io.InputStream)>(p0))
+        {
+            v1 = invoke-virtual v0.<int java.io.Reader.read()>();
+        }
+
+    try_end_0:
+        return v1;
+    }
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn resource_created_by_constructor() -> Result<(), ParseErrorDisplayed> {
+        // javac allocates the resource and calls the constructor separately, both go into the
+        // header
+        let mut method = read_method(
+            r#"
+            .method static read(Ljava/io/File;)I
+                .locals 3
+                new-instance v0, Ljava/io/FileReader;
+                invoke-direct {v0, p0}, Ljava/io/FileReader;-><init>(Ljava/io/File;)V
+                :try_start_0
+                invoke-virtual {v0}, Ljava/io/Reader;->read()I
+                move-result v1
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                return v1
+
+                :catchall_0
+                move-exception v1
+                :try_start_1
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                :try_end_1
+                .catchall {:try_start_1 .. :try_end_1} :catchall_1
+                goto :goto_0
+
+                :catchall_1
+                move-exception v2
+                invoke-virtual {v1, v2}, Ljava/lang/Throwable;->addSuppressed(Ljava/lang/Throwable;)V
+
+                :goto_0
+                throw v1
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        assert_eq!(
+            stringify(&method),
+            r#"    static int read(java.io.File @p0)
+    {
+    try_start_0:
+        try (java.io.FileReader v0 = new java.io.FileReader; invoke-direct v0.<void java.io.FileReader.<init>(java.io.File)>(p0))
+        {
+            v1 = invoke-virtual v0.<int java.io.Reader.read()>();
+        }
+
+    try_end_0:
+        return v1;
+    }
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn comments_in_handlers() -> Result<(), ParseErrorDisplayed> {
+        // Comments mustn't prevent recognizing the handlers, they go away along with them
+        let code = r#"
+            .method static read(Ljava/io/InputStream;)I
+                .locals 3
+                invoke-static {p0}, La;->wrap(Ljava/io/InputStream;)Ljava/io/Reader;
+                move-result-object v0
+                :try_start_0
+                invoke-virtual {v0}, Ljava/io/Reader;->read()I
+                move-result v1
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                return v1
+
+                :catchall_0
+                move-exception v1
+                # close the reader
+                :try_start_1
+                invoke-virtual {v0}, Ljava/io/Reader;->close()V
+                :try_end_1
+                .catchall {:try_start_1 .. :try_end_1} :catchall_1
+                goto :goto_0
+
+                :catchall_1
+                move-exception v2
+                # keep the original exception
+                invoke-virtual {v1, v2}, Ljava/lang/Throwable;->addSuppressed(Ljava/lang/Throwable;)V
+
+                :goto_0
+                throw v1
+            .end method
+        "#;
+        let mut method = read_method(code)?;
+        method.optimize();
+        assert!(stringify(&method).contains("        try (java.io.Reader v0 = "));
+
+        let code = r#"
+            .method static run(La;)V
+                .locals 1
+                :try_start_0
+                invoke-virtual {p0}, La;->work()V
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                # clean up
+                invoke-virtual {p0}, La;->cleanup()V
+                return-void
+
+                :catchall_0
+                move-exception v0
+                # clean up
+                invoke-virtual {p0}, La;->cleanup()V
+                throw v0
+            .end method
+        "#;
+        let mut method = read_method(code)?;
+        method.optimize();
+        let output = stringify(&method);
+        assert!(output.contains("        finally\n"), "{output}");
+        assert!(!output.contains("move-exception"), "{output}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn exception_jumps() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method static run(I)I
+                .locals 2
+                new-instance v0, Ljava/lang/IllegalStateException;
+                invoke-direct {v0}, Ljava/lang/IllegalStateException;-><init>()V
+                :try_start_0
+                if-eqz p0, :cond_0
+                throw v0
+                :cond_0
+                invoke-static {p0}, La;->check(I)V
+                :try_end_0
+                .catch Ljava/lang/IllegalStateException; {:try_start_0 .. :try_end_0} :catch_0
+                const/4 v1, 0x1
+                return v1
+
+                :catch_0
+                move-exception v1
+                const/4 v1, 0x2
+                return v1
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        assert_eq!(
+            stringify(&method),
+            r#"    static int run(int @p0)
+    {
+        invoke-direct (new java.lang.IllegalStateException).<void java.lang.IllegalStateException.<init>()>();
+
+    try_start_0:
+        if (p0 == 0) goto cond_0;
+        goto catch_0_jump;
+
+    cond_0:
+        invoke-static <void a.check(int)>(p0);
+
+    try_end_0:
+        catch java.lang.IllegalStateException from try_start_0 to try_end_0 with catch_0;
+        return 0x1;
+
+    catch_0:
+        v1 = move-exception;
+
+    catch_0_jump:
+        return 0x2;
+    }
+"#
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn deduplicate_finally() -> Result<(), ParseErrorDisplayed> {
+        let mut method = read_method(
+            r#"
+            .method static run(La;Z)I
+                .locals 2
+                :try_start_0
+                invoke-virtual {p0}, La;->work()V
+                if-eqz p1, :cond_0
+                :try_end_0
+                .catchall {:try_start_0 .. :try_end_0} :catchall_0
+                invoke-virtual {p0}, La;->cleanup()V
+                const/4 v0, 0x1
+                return v0
+                :cond_0
+                :try_start_1
+                invoke-virtual {p0}, La;->work()V
+                :try_end_1
+                .catchall {:try_start_1 .. :try_end_1} :catchall_0
+                invoke-virtual {p0}, La;->cleanup()V
+                const/4 v0, 0x0
+                return v0
+
+                :catchall_0
+                move-exception v1
+                invoke-virtual {p0}, La;->cleanup()V
+                throw v1
+            .end method
+            "#,
+        )?;
+        method.optimize();
+
+        assert_eq!(
+            stringify(&method),
+            r#"    static int run(a @p0, bool @p1)
+    {
+    try_start_0:
+        try
+        {
+            invoke-virtual p0.<void a.work()>();
+            if (p1 == 0) goto cond_0;
+
+        try_end_0:
+            return 0x1;
+
+        cond_0:
+        try_start_1:
+            invoke-virtual p0.<void a.work()>();
+        }
+
+    try_end_1:
+        finally
+        {
+            invoke-virtual p0.<void a.cleanup()>();
+        }
+        return 0x0;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,573 @@
+use flate2::write::GzEncoder;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use crate::annotation::AnnotationVisibility;
+use crate::doc::Doc;
+
+/// Options affecting how code is written out.
+#[derive(Debug, Default, Clone)]
+pub struct OutputOptions {
+    pub show_system_annotations: bool,
+    pub hide_assertions: bool,
+    pub nest_inner_classes: bool,
+    pub declare_locals: bool,
+    pub show_comments: bool,
+    pub show_complexity: bool,
+    pub max_line_width: Option<usize>,
+    pub raw_constructor_names: bool,
+    pub hidden_annotations: Vec<AnnotationVisibility>,
+    pub argument_names: bool,
+    pub show_callers: bool,
+    pub show_overrides: bool,
+    pub devirtualize: bool,
+    pub show_registers: bool,
+    pub mark_debug_code: bool,
+    pub assume_release: bool,
+    pub flag_suspicious_calls: bool,
+    pub mask_hints: bool,
+    pub decimal: bool,
+    /// Time limit for analyses of a method while writing it, like inferring local types
+    pub method_time_limit: Option<Duration>,
+}
+
+/// Renders a document as a line of Jimple output starting with the given indentation,
+/// applying the number format and line width requested by the options.
+pub(crate) fn write_jimple_line(
+    output: &mut dyn Write,
+    indent: usize,
+    doc: Doc,
+    options: &OutputOptions,
+) -> Result<(), std::io::Error> {
+    let doc = if options.decimal {
+        doc.map_text(&|text| decimal_integers(text).into_owned())
+    } else {
+        doc
+    };
+    writeln!(output, "{}", doc.render(indent, options.max_line_width))
+}
+
+/// Checks whether a character would be invisible or break the line in the output.
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || (c.is_whitespace() && c != ' ')
+        || matches!(c, '\u{200b}'..='\u{200f}' | '\u{2060}'..='\u{2064}' | '\u{feff}')
+}
+
+/// Quotes a field or method name with single quotes if it isn't a valid Java identifier, as
+/// obfuscators tend to produce. Invisible characters are escaped within quotes. Constructor and
+/// static initializer names are kept as is.
+pub fn jimple_identifier(name: &str) -> Cow<'_, str> {
+    if name == "<init>"
+        || name == "<clinit>"
+        || (!name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$'))
+    {
+        Cow::Borrowed(name)
+    } else {
+        let mut quoted = String::from("'");
+        for c in name.chars() {
+            match c {
+                '\\' | '\'' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c if is_invisible(c) => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        Cow::Owned(quoted)
+    }
+}
+
+/// Rewrites hexadecimal integers in a line of output as decimal numbers, leaving string and
+/// character literals as well as quoted names unchanged.
+pub fn decimal_integers(line: &str) -> Cow<'_, str> {
+    if !line.contains("0x") {
+        return Cow::Borrowed(line);
+    }
+
+    let mut result = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut previous = ' ';
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+        } else if c == '"' || c == '\'' {
+            quote = Some(c);
+        } else if c == '0'
+            && line[i..].starts_with("0x")
+            && !(previous.is_alphanumeric() || previous == '_' || previous == '$')
+        {
+            let digits = line[i + 2..]
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .map_or(&line[i + 2..], |end| &line[i + 2..i + 2 + end]);
+            if let Ok(value) = u64::from_str_radix(digits, 16) {
+                result.push_str(&value.to_string());
+                for _ in 0..digits.len() + 1 {
+                    chars.next();
+                }
+                previous = '0';
+                continue;
+            }
+        }
+        result.push(c);
+        previous = c;
+    }
+    Cow::Owned(result)
+}
+
+/// Output format of reports listing findings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+/// Metadata written at the top of each output file, allowing to tell how it has been produced
+/// and whether it is still current.
+#[derive(Debug, PartialEq)]
+pub struct FileHeader {
+    pub version: &'static str,
+    pub input_path: PathBuf,
+    pub input_hash: String,
+    pub dex_file: Option<String>,
+    pub warnings: usize,
+    pub options: String,
+}
+
+impl FileHeader {
+    pub fn new(input_path: &Path, data: &[u8], options: &str, warnings: usize) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            input_path: input_path.to_path_buf(),
+            input_hash: format!("{:x}", Sha256::digest(data)),
+            dex_file: Self::dex_file(input_path),
+            warnings,
+            options: options.to_string(),
+        }
+    }
+
+    /// Deduces the dex file name from the apktool output directory, e.g. `smali_classes2`
+    /// contains the code of `classes2.dex`.
+    pub fn dex_file(input_path: &Path) -> Option<String> {
+        input_path
+            .components()
+            .find_map(|component| match component {
+                Component::Normal(name) => {
+                    let name = name.to_str()?;
+                    if name == "smali" {
+                        Some("classes.dex".to_string())
+                    } else {
+                        name.strip_prefix("smali_")
+                            .map(|suffix| format!("{suffix}.dex"))
+                    }
+                }
+                _ => None,
+            })
+    }
+
+    /// Writes the header as comments starting with the given prefix, e.g. `//`.
+    pub fn write_comments(
+        &self,
+        output: &mut dyn Write,
+        prefix: &str,
+    ) -> Result<(), std::io::Error> {
+        writeln!(output, "{prefix} Generated by aarf {}", self.version)?;
+        writeln!(output, "{prefix} input: {}", self.input_path.display())?;
+        writeln!(output, "{prefix} sha256: {}", self.input_hash)?;
+        if let Some(dex_file) = &self.dex_file {
+            writeln!(output, "{prefix} dex: {dex_file}")?;
+        }
+        writeln!(output, "{prefix} warnings: {}", self.warnings)?;
+        writeln!(output, "{prefix} options: {}", self.options)?;
+        writeln!(output)?;
+        Ok(())
+    }
+}
+
+/// Placement of output files relative to the apktool directory tree, which by default mirrors
+/// the Smali files.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OutputLayout {
+    /// Package directory to be removed from paths of classes in this package, e.g.
+    /// `com/example/app`
+    pub strip_prefix: Option<PathBuf>,
+    /// Put all files directly into the dex directory, named after the full class name
+    pub flatten: bool,
+}
+
+impl OutputLayout {
+    pub fn new(strip_prefix: Option<&str>, flatten: bool) -> Self {
+        Self {
+            // Accept package names in Java notation as well
+            strip_prefix: strip_prefix
+                .map(|prefix| prefix.trim_matches('/').replace('.', "/").into()),
+            flatten,
+        }
+    }
+
+    /// Maps the path of a Smali file to the path of the corresponding output file, still with
+    /// the `.smali` extension. Only the part following the `smali` or `smali_*` directory is
+    /// changed, paths without such directory are kept as is.
+    pub fn get_output_path(&self, path: &Path) -> PathBuf {
+        let components = path.components().collect::<Vec<_>>();
+        let Some(position) = components.iter().position(|component| {
+            component
+                .as_os_str()
+                .to_str()
+                .is_some_and(|name| name == "smali" || name.starts_with("smali_"))
+        }) else {
+            return path.to_path_buf();
+        };
+        let root = components[..=position].iter().collect::<PathBuf>();
+        let mut class_path = components[position + 1..].iter().collect::<PathBuf>();
+
+        if let Some(prefix) = &self.strip_prefix {
+            if let Ok(stripped) = class_path.strip_prefix(prefix) {
+                class_path = stripped.to_path_buf();
+            }
+        }
+        if self.flatten {
+            let name = class_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(".");
+            class_path = PathBuf::from(name);
+        }
+        root.join(class_path)
+    }
+}
+
+#[derive(Debug)]
+enum OutputWriter {
+    Plain(BufWriter<File>),
+    Compressed(GzEncoder<BufWriter<File>>),
+}
+
+/// An output file being written, optionally gzip-compressed. The data goes into a temporary
+/// file first that replaces the target once complete, so that aborted runs never leave
+/// truncated output files behind.
+#[derive(Debug)]
+pub struct OutputFile {
+    writer: OutputWriter,
+    temporary: PathBuf,
+    target: PathBuf,
+}
+
+impl OutputFile {
+    /// Returns the path of the output file, with `.gz` appended if compressed.
+    pub fn get_path(path: &Path, compress: bool) -> PathBuf {
+        if compress {
+            let mut path = path.as_os_str().to_owned();
+            path.push(".gz");
+            path.into()
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    pub fn create(path: &Path, compress: bool) -> Result<Self, std::io::Error> {
+        let target = Self::get_path(path, compress);
+        let mut temporary = target.as_os_str().to_owned();
+        temporary.push(".partial");
+        let temporary = PathBuf::from(temporary);
+        let file = BufWriter::new(File::create(&temporary)?);
+        let writer = if compress {
+            OutputWriter::Compressed(GzEncoder::new(file, flate2::Compression::default()))
+        } else {
+            OutputWriter::Plain(file)
+        };
+        Ok(Self {
+            writer,
+            temporary,
+            target,
+        })
+    }
+
+    /// Completes the file and moves it into place.
+    pub fn finish(self) -> Result<(), std::io::Error> {
+        let mut file = match self.writer {
+            OutputWriter::Plain(file) => file,
+            OutputWriter::Compressed(encoder) => encoder.finish()?,
+        };
+        file.flush()?;
+        drop(file);
+        std::fs::rename(&self.temporary, self.target).inspect_err(|_| {
+            let _ = std::fs::remove_file(&self.temporary);
+        })
+    }
+
+    /// Removes the incomplete file, e.g. after writing to it failed.
+    pub fn discard(self) {
+        drop(self.writer);
+        // Nothing to do if the file is gone already
+        let _ = std::fs::remove_file(self.temporary);
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        match &mut self.writer {
+            OutputWriter::Plain(file) => file.write(data),
+            OutputWriter::Compressed(encoder) => encoder.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.writer {
+            OutputWriter::Plain(file) => file.flush(),
+            OutputWriter::Compressed(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Outcome of converting a single input file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionStatus {
+    Converted { input_hash: String },
+    Failed,
+}
+
+/// Record of a conversion run written into the output directory, listing the input files that
+/// have been converted. Written after interrupted runs as well, so that it always describes the
+/// output files present.
+#[derive(Debug, PartialEq)]
+pub struct RunManifest {
+    pub version: &'static str,
+    pub options: String,
+    pub complete: bool,
+    pub files: Vec<(PathBuf, ConversionStatus)>,
+}
+
+impl RunManifest {
+    pub const FILE_NAME: &'static str = "aarf-manifest.txt";
+
+    pub fn new(options: &str) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            options: options.to_string(),
+            complete: false,
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add_converted(&mut self, header: &FileHeader) {
+        self.files.push((
+            header.input_path.clone(),
+            ConversionStatus::Converted {
+                input_hash: header.input_hash.clone(),
+            },
+        ));
+    }
+
+    /// Records a file as failed. This replaces an earlier entry for the file, e.g. if the file
+    /// was converted but writing its output failed later.
+    pub fn add_failed(&mut self, input_path: &Path) {
+        match self.files.iter_mut().find(|(path, _)| path == input_path) {
+            Some((_, status)) => *status = ConversionStatus::Failed,
+            None => self
+                .files
+                .push((input_path.to_path_buf(), ConversionStatus::Failed)),
+        }
+    }
+
+    /// Parses a manifest written by the same version of aarf.
+    pub fn read(data: &str) -> Result<Self, String> {
+        let mut lines = data.lines();
+        let version = lines
+            .next()
+            .and_then(|line| line.strip_prefix("# Generated by aarf "));
+        let mut result = Self::new("");
+        if version != Some(result.version) {
+            return Err(format!(
+                "written by aarf {}, expected {}",
+                version.unwrap_or("unknown"),
+                result.version
+            ));
+        }
+
+        for line in lines {
+            if let Some(options) = line.strip_prefix("# options: ") {
+                result.options = options.to_string();
+            } else if let Some(status) = line.strip_prefix("# status: ") {
+                result.complete = status == "complete";
+            } else {
+                let status = match line.split('\t').collect::<Vec<_>>()[..] {
+                    ["converted", input_hash, path] => (
+                        path,
+                        ConversionStatus::Converted {
+                            input_hash: input_hash.to_string(),
+                        },
+                    ),
+                    ["failed", _, path] => (path, ConversionStatus::Failed),
+                    _ => return Err(format!("unexpected line: {line}")),
+                };
+                result.files.push((PathBuf::from(status.0), status.1));
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        writeln!(output, "# Generated by aarf {}", self.version)?;
+        writeln!(output, "# options: {}", self.options)?;
+        let status = if self.complete {
+            "complete"
+        } else {
+            "interrupted"
+        };
+        writeln!(output, "# status: {status}")?;
+        for (path, status) in &self.files {
+            match status {
+                ConversionStatus::Converted { input_hash } => {
+                    writeln!(output, "converted\t{input_hash}\t{}", path.display())?
+                }
+                ConversionStatus::Failed => writeln!(output, "failed\t\t{}", path.display())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the manifest into a directory, replacing any previous manifest at once.
+    pub fn write_to_dir(&self, dir: &Path) -> Result<(), std::io::Error> {
+        let mut output = OutputFile::create(&dir.join(Self::FILE_NAME), false)?;
+        self.write(&mut output)?;
+        output.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_manifest() {
+        let mut manifest = RunManifest::new("decompile app.apk");
+        manifest.add_converted(&FileHeader::new(
+            Path::new("smali/a.smali"),
+            b"",
+            "decompile app.apk",
+            0,
+        ));
+        manifest.add_failed(Path::new("smali/b.smali"));
+
+        let mut output = Vec::new();
+        manifest.write(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(RunManifest::read(&output), Ok(manifest));
+        assert!(RunManifest::read("# Generated by aarf 0.0.0\n").is_err());
+        assert_eq!(
+            output,
+            format!(
+                "# Generated by aarf {}\n# options: decompile app.apk\n# status: interrupted\n\
+                 converted\te3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\tsmali/a.smali\n\
+                 failed\t\tsmali/b.smali\n",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+
+    #[test]
+    fn compressed_output() {
+        let dir = std::env::temp_dir().join(format!("aarf-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.jimple");
+        let mut output = OutputFile::create(&path, true).unwrap();
+        output.write_all(b"class a\n").unwrap();
+        output.finish().unwrap();
+
+        let compressed = std::fs::read(dir.join("a.jimple.gz")).unwrap();
+        let mut data = String::new();
+        std::io::Read::read_to_string(
+            &mut flate2::read::GzDecoder::new(&compressed[..]),
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(data, "class a\n");
+        assert!(!path.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decimal() {
+        assert_eq!(
+            decimal_integers(r#"        v0 = a.b(-0x1f, 0x0, v0x1, "0x10", '\'', 0xffL);"#),
+            r#"        v0 = a.b(-31, 0, v0x1, "0x10", '\'', 255L);"#
+        );
+        assert!(matches!(decimal_integers("return v0;"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn write_header() {
+        let header = FileHeader::new(
+            Path::new("smali_classes2/com/example/Main.smali"),
+            b"abc",
+            "decompile app.apk out",
+            3,
+        );
+        assert_eq!(header.dex_file, Some("classes2.dex".to_string()));
+
+        let mut cursor = std::io::Cursor::new(Vec::new());
+        header.write_comments(&mut cursor, "//").unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&cursor.into_inner()),
+            format!(
+                r#"// Generated by aarf {}
+// input: smali_classes2/com/example/Main.smali
+// sha256: ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad
+// dex: classes2.dex
+// warnings: 3
+// options: decompile app.apk out
+
+"#,
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        let header = FileHeader::new(Path::new("smali/Main.smali"), b"", "", 0);
+        assert_eq!(header.dex_file, Some("classes.dex".to_string()));
+
+        let header = FileHeader::new(Path::new("Main.smali"), b"", "", 0);
+        assert_eq!(header.dex_file, None);
+    }
+
+    #[test]
+    fn output_layout() {
+        let path = Path::new("out/smali_classes2/com/example/app/ui/Main.smali");
+        assert_eq!(OutputLayout::default().get_output_path(path), path);
+        assert_eq!(
+            OutputLayout::new(Some("com.example.app"), false).get_output_path(path),
+            Path::new("out/smali_classes2/ui/Main.smali")
+        );
+        assert_eq!(
+            OutputLayout::new(Some("com/example/app/"), true).get_output_path(path),
+            Path::new("out/smali_classes2/ui.Main.smali")
+        );
+        assert_eq!(
+            OutputLayout::new(None, true).get_output_path(path),
+            Path::new("out/smali_classes2/com.example.app.ui.Main.smali")
+        );
+        assert_eq!(
+            OutputLayout::new(Some("org/other"), false).get_output_path(path),
+            path
+        );
+    }
+}
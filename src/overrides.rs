@@ -0,0 +1,463 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::method::Method;
+use crate::r#type::Type;
+
+/// Superclass and interfaces of framework classes, as far as needed to connect the classes apps
+/// commonly extend to the classes declaring the callbacks.
+const FRAMEWORK_PARENTS: [(&str, &[&str]); 20] = [
+    (
+        "androidx.appcompat.app.AppCompatActivity",
+        &["androidx.fragment.app.FragmentActivity"],
+    ),
+    (
+        "androidx.fragment.app.FragmentActivity",
+        &["androidx.activity.ComponentActivity"],
+    ),
+    (
+        "androidx.activity.ComponentActivity",
+        &["androidx.core.app.ComponentActivity"],
+    ),
+    (
+        "androidx.core.app.ComponentActivity",
+        &["android.app.Activity"],
+    ),
+    ("android.app.ListActivity", &["android.app.Activity"]),
+    (
+        "android.app.Activity",
+        &[
+            "android.view.ContextThemeWrapper",
+            "android.view.KeyEvent$Callback",
+        ],
+    ),
+    (
+        "android.view.ContextThemeWrapper",
+        &["android.content.ContextWrapper"],
+    ),
+    (
+        "android.app.Application",
+        &["android.content.ContextWrapper"],
+    ),
+    ("android.app.Service", &["android.content.ContextWrapper"]),
+    ("android.app.IntentService", &["android.app.Service"]),
+    (
+        "androidx.fragment.app.DialogFragment",
+        &["androidx.fragment.app.Fragment"],
+    ),
+    (
+        "androidx.appcompat.app.AppCompatDialogFragment",
+        &["androidx.fragment.app.DialogFragment"],
+    ),
+    ("android.app.DialogFragment", &["android.app.Fragment"]),
+    ("android.view.View", &["android.view.KeyEvent$Callback"]),
+    ("android.view.ViewGroup", &["android.view.View"]),
+    ("android.widget.FrameLayout", &["android.view.ViewGroup"]),
+    ("android.widget.LinearLayout", &["android.view.ViewGroup"]),
+    ("android.widget.TextView", &["android.view.View"]),
+    (
+        "android.widget.ArrayAdapter",
+        &["android.widget.BaseAdapter"],
+    ),
+    (
+        "androidx.recyclerview.widget.ListAdapter",
+        &["androidx.recyclerview.widget.RecyclerView$Adapter"],
+    ),
+];
+
+/// Lifecycle methods and callbacks apps commonly override, by the framework class or interface
+/// declaring them. Parameter types are given in Java notation.
+const FRAMEWORK_CALLBACKS: [(&str, &[&str]); 24] = [
+    (
+        "android.app.Activity",
+        &[
+            "onCreate(android.os.Bundle)",
+            "onStart()",
+            "onRestart()",
+            "onResume()",
+            "onPause()",
+            "onStop()",
+            "onDestroy()",
+            "onPostCreate(android.os.Bundle)",
+            "onNewIntent(android.content.Intent)",
+            "onSaveInstanceState(android.os.Bundle)",
+            "onRestoreInstanceState(android.os.Bundle)",
+            "onActivityResult(int,int,android.content.Intent)",
+            "onRequestPermissionsResult(int,java.lang.String[],int[])",
+            "onBackPressed()",
+            "onUserLeaveHint()",
+            "onCreateOptionsMenu(android.view.Menu)",
+            "onOptionsItemSelected(android.view.MenuItem)",
+            "onConfigurationChanged(android.content.res.Configuration)",
+            "onWindowFocusChanged(boolean)",
+        ],
+    ),
+    (
+        "android.content.ContextWrapper",
+        &["attachBaseContext(android.content.Context)"],
+    ),
+    (
+        "android.app.Application",
+        &[
+            "onCreate()",
+            "onTerminate()",
+            "onLowMemory()",
+            "onTrimMemory(int)",
+            "onConfigurationChanged(android.content.res.Configuration)",
+        ],
+    ),
+    (
+        "android.app.Service",
+        &[
+            "onCreate()",
+            "onStartCommand(android.content.Intent,int,int)",
+            "onBind(android.content.Intent)",
+            "onUnbind(android.content.Intent)",
+            "onRebind(android.content.Intent)",
+            "onTaskRemoved(android.content.Intent)",
+            "onDestroy()",
+        ],
+    ),
+    (
+        "android.app.IntentService",
+        &["onHandleIntent(android.content.Intent)"],
+    ),
+    (
+        "android.content.BroadcastReceiver",
+        &["onReceive(android.content.Context,android.content.Intent)"],
+    ),
+    (
+        "android.content.ContentProvider",
+        &[
+            "onCreate()",
+            "query(android.net.Uri,java.lang.String[],java.lang.String,java.lang.String[],java.lang.String)",
+            "insert(android.net.Uri,android.content.ContentValues)",
+            "update(android.net.Uri,android.content.ContentValues,java.lang.String,java.lang.String[])",
+            "delete(android.net.Uri,java.lang.String,java.lang.String[])",
+            "getType(android.net.Uri)",
+            "openFile(android.net.Uri,java.lang.String)",
+        ],
+    ),
+    (
+        "androidx.fragment.app.Fragment",
+        &[
+            "onAttach(android.content.Context)",
+            "onCreate(android.os.Bundle)",
+            "onCreateView(android.view.LayoutInflater,android.view.ViewGroup,android.os.Bundle)",
+            "onViewCreated(android.view.View,android.os.Bundle)",
+            "onStart()",
+            "onResume()",
+            "onPause()",
+            "onStop()",
+            "onDestroyView()",
+            "onDestroy()",
+            "onDetach()",
+            "onSaveInstanceState(android.os.Bundle)",
+            "onActivityResult(int,int,android.content.Intent)",
+        ],
+    ),
+    (
+        "android.app.Fragment",
+        &[
+            "onAttach(android.content.Context)",
+            "onCreate(android.os.Bundle)",
+            "onCreateView(android.view.LayoutInflater,android.view.ViewGroup,android.os.Bundle)",
+            "onViewCreated(android.view.View,android.os.Bundle)",
+            "onStart()",
+            "onResume()",
+            "onPause()",
+            "onStop()",
+            "onDestroyView()",
+            "onDestroy()",
+            "onDetach()",
+        ],
+    ),
+    (
+        "android.view.View",
+        &[
+            "onFinishInflate()",
+            "onAttachedToWindow()",
+            "onDetachedFromWindow()",
+            "onMeasure(int,int)",
+            "onLayout(boolean,int,int,int,int)",
+            "onSizeChanged(int,int,int,int)",
+            "onDraw(android.graphics.Canvas)",
+            "onTouchEvent(android.view.MotionEvent)",
+        ],
+    ),
+    (
+        "android.view.KeyEvent$Callback",
+        &[
+            "onKeyDown(int,android.view.KeyEvent)",
+            "onKeyUp(int,android.view.KeyEvent)",
+        ],
+    ),
+    (
+        "android.view.View$OnClickListener",
+        &["onClick(android.view.View)"],
+    ),
+    (
+        "android.view.View$OnLongClickListener",
+        &["onLongClick(android.view.View)"],
+    ),
+    (
+        "android.view.View$OnTouchListener",
+        &["onTouch(android.view.View,android.view.MotionEvent)"],
+    ),
+    (
+        "android.content.DialogInterface$OnClickListener",
+        &["onClick(android.content.DialogInterface,int)"],
+    ),
+    (
+        "android.widget.AdapterView$OnItemClickListener",
+        &["onItemClick(android.widget.AdapterView,android.view.View,int,long)"],
+    ),
+    (
+        "android.text.TextWatcher",
+        &[
+            "beforeTextChanged(java.lang.CharSequence,int,int,int)",
+            "onTextChanged(java.lang.CharSequence,int,int,int)",
+            "afterTextChanged(android.text.Editable)",
+        ],
+    ),
+    (
+        "android.widget.BaseAdapter",
+        &[
+            "getCount()",
+            "getItem(int)",
+            "getItemId(int)",
+            "getView(int,android.view.View,android.view.ViewGroup)",
+        ],
+    ),
+    (
+        "androidx.recyclerview.widget.RecyclerView$Adapter",
+        &[
+            "onCreateViewHolder(android.view.ViewGroup,int)",
+            "onBindViewHolder(androidx.recyclerview.widget.RecyclerView$ViewHolder,int)",
+            "getItemCount()",
+            "getItemViewType(int)",
+        ],
+    ),
+    ("android.os.Handler", &["handleMessage(android.os.Message)"]),
+    (
+        "android.webkit.WebViewClient",
+        &[
+            "shouldOverrideUrlLoading(android.webkit.WebView,java.lang.String)",
+            "shouldOverrideUrlLoading(android.webkit.WebView,android.webkit.WebResourceRequest)",
+            "shouldInterceptRequest(android.webkit.WebView,android.webkit.WebResourceRequest)",
+            "onPageStarted(android.webkit.WebView,java.lang.String,android.graphics.Bitmap)",
+            "onPageFinished(android.webkit.WebView,java.lang.String)",
+            "onReceivedSslError(android.webkit.WebView,android.webkit.SslErrorHandler,android.net.http.SslError)",
+        ],
+    ),
+    (
+        "android.webkit.WebChromeClient",
+        &[
+            "onProgressChanged(android.webkit.WebView,int)",
+            "onJsAlert(android.webkit.WebView,java.lang.String,java.lang.String,android.webkit.JsResult)",
+            "onConsoleMessage(android.webkit.ConsoleMessage)",
+        ],
+    ),
+    (
+        "android.hardware.SensorEventListener",
+        &[
+            "onSensorChanged(android.hardware.SensorEvent)",
+            "onAccuracyChanged(android.hardware.Sensor,int)",
+        ],
+    ),
+    ("java.lang.Runnable", &["run()"]),
+];
+
+/// Formats a type in Java notation as used by the callback list.
+fn get_java_name(r#type: &Type) -> String {
+    match r#type {
+        Type::Bool => "boolean".to_string(),
+        Type::Array(element_type) => format!("{}[]", get_java_name(element_type)),
+        other => other.get_name().into_owned(),
+    }
+}
+
+/// Class hierarchy of the program, used to find the framework callbacks overridden by its
+/// methods. Without the hierarchy, only callbacks of the direct superclass and interfaces are
+/// recognized.
+#[derive(Debug, Default)]
+pub struct FrameworkOverrides {
+    /// Superclass and interfaces of each class in the program
+    parents: HashMap<Type, Vec<Type>>,
+}
+
+impl FrameworkOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        self.parents
+            .insert(class.class_type.clone(), Self::get_class_parents(class));
+    }
+
+    fn get_class_parents(class: &Class) -> Vec<Type> {
+        class
+            .super_class
+            .iter()
+            .chain(&class.interfaces)
+            .cloned()
+            .collect()
+    }
+
+    fn get_parents(&self, class_type: &Type) -> Vec<Type> {
+        if let Some(parents) = self.parents.get(class_type) {
+            return parents.clone();
+        }
+        let name = class_type.get_name();
+        FRAMEWORK_PARENTS
+            .iter()
+            .find(|(class, _)| *class == name)
+            .map(|(_, parents)| {
+                parents
+                    .iter()
+                    .map(|parent| Type::Object(parent.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Finds the framework class or interface declaring the callback overridden by a method,
+    /// searching the closest ancestors first.
+    pub fn find_overridden(&self, class: &Class, method: &Method) -> Option<Type> {
+        if method.name.starts_with('<')
+            || method
+                .visibility
+                .iter()
+                .any(|flag| matches!(flag, AccessFlag::Static | AccessFlag::Private))
+        {
+            return None;
+        }
+        let parameters = method
+            .parameters
+            .iter()
+            .map(|parameter| get_java_name(&parameter.parameter_type))
+            .collect::<Vec<_>>();
+        let signature = format!("{}({})", method.name, parameters.join(","));
+
+        let mut seen = HashSet::new();
+        let mut queue = Self::get_class_parents(class)
+            .into_iter()
+            .collect::<VecDeque<_>>();
+        while let Some(ancestor) = queue.pop_front() {
+            if !seen.insert(ancestor.clone()) {
+                continue;
+            }
+            let name = ancestor.get_name();
+            let declares = FRAMEWORK_CALLBACKS
+                .iter()
+                .any(|(class, callbacks)| *class == name && callbacks.contains(&&*signature));
+            if declares {
+                return Some(ancestor);
+            }
+            queue.extend(self.get_parents(&ancestor));
+        }
+        None
+    }
+
+    /// Adds a comment naming the overridden framework callback to the methods of the class.
+    pub fn apply(&self, class: &mut Class) {
+        let comments = class
+            .methods
+            .iter()
+            .map(|method| {
+                self.find_overridden(class, method)
+                    .map(|ancestor| format!("overrides {ancestor}.{}", method.name))
+            })
+            .collect::<Vec<_>>();
+        for (method, comment) in class.methods.iter_mut().zip(comments) {
+            method.comments.extend(comment);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn framework_overrides() -> Result<(), ParseErrorDisplayed> {
+        let base = read_class(
+            r#"
+                .class public abstract La;
+                .super Landroidx/appcompat/app/AppCompatActivity;
+            "#,
+        )?;
+        let mut class = read_class(
+            r#"
+                .class public Lb;
+                .super La;
+                .implements Landroid/view/View$OnClickListener;
+
+                .method protected onCreate(Landroid/os/Bundle;)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public onCreate()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public onClick(Landroid/view/View;)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public onWindowFocusChanged(Z)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method private static run()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut overrides = FrameworkOverrides::new();
+        overrides.apply(&mut class);
+        let comments = class
+            .methods
+            .iter()
+            .map(|method| method.comments.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            vec![
+                vec![],
+                vec![],
+                vec!["overrides android.view.View$OnClickListener.onClick".to_string()],
+                vec![],
+                vec![],
+            ]
+        );
+
+        overrides.add_class(&base);
+        for method in &mut class.methods {
+            method.comments.clear();
+        }
+        overrides.apply(&mut class);
+        assert_eq!(
+            class.methods[0].comments,
+            vec!["overrides android.app.Activity.onCreate"]
+        );
+        assert!(class.methods[1].comments.is_empty());
+        assert_eq!(
+            class.methods[3].comments,
+            vec!["overrides android.app.Activity.onWindowFocusChanged"]
+        );
+        assert!(class.methods[4].comments.is_empty());
+
+        Ok(())
+    }
+}
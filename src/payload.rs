@@ -0,0 +1,243 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::class::Class;
+use crate::instruction::{CommandData, CommandParameter, Instruction};
+use crate::literal::Literal;
+
+/// Kind of an embedded payload, recognized by its magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayloadKind {
+    Dex,
+    Zip,
+}
+
+impl PayloadKind {
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.len() >= 8 && data.starts_with(b"dex\n") && data[7] == 0 {
+            Some(Self::Dex)
+        } else if data.starts_with(b"PK\x03\x04") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Dex => "dex",
+            Self::Zip => "jar",
+        }
+    }
+}
+
+/// A dex or jar file embedded into the code as a byte array.
+#[derive(Debug, PartialEq)]
+pub struct Payload {
+    pub name: String,
+    pub kind: PayloadKind,
+    pub data: Vec<u8>,
+}
+
+fn array_to_bytes(values: &[Literal]) -> Option<Vec<u8>> {
+    values
+        .iter()
+        .map(|value| match value {
+            Literal::Byte(value) => Some(*value as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+fn sanitize(name: &str) -> String {
+    name.replace(['<', '>', '/', '\\'], "_")
+}
+
+impl Class {
+    /// Finds byte arrays starting with dex or zip magic bytes in the class's methods.
+    pub fn find_payloads(&self) -> Vec<Payload> {
+        let mut result = Vec::new();
+        for method in &self.methods {
+            for instruction in &method.instructions {
+                let values = match instruction {
+                    Instruction::Data(CommandData::Array(_, values)) => values,
+                    Instruction::Command { parameters, .. } => {
+                        match parameters.iter().find_map(|parameter| match parameter {
+                            CommandParameter::Data(CommandData::Array(_, values)) => Some(values),
+                            _ => None,
+                        }) {
+                            Some(values) => values,
+                            None => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+
+                if let Some(data) = array_to_bytes(values) {
+                    if let Some(kind) = PayloadKind::detect(&data) {
+                        let name = format!(
+                            "{}.{}.{}.{}",
+                            sanitize(&self.class_type.get_name()),
+                            sanitize(&method.name),
+                            result.len(),
+                            kind.extension()
+                        );
+                        result.push(Payload { name, kind, data });
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Lists string constants that look like names of dex or jar files loaded at runtime.
+    pub fn find_referenced_payloads(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        for method in &self.methods {
+            for instruction in &method.instructions {
+                if let Instruction::Command { parameters, .. } = instruction {
+                    for parameter in parameters {
+                        if let CommandParameter::Literal(Literal::String(value)) = parameter {
+                            let lower = value.to_ascii_lowercase();
+                            if [".dex", ".jar", ".apk"]
+                                .iter()
+                                .any(|extension| lower.ends_with(extension))
+                                && !result.iter().any(|known: &String| **known == **value)
+                            {
+                                result.push(value.to_string());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+fn is_payload_file(path: &Path) -> bool {
+    if path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| {
+            ["dex", "jar", "apk"].contains(&extension.to_ascii_lowercase().as_str())
+        })
+        .is_some()
+    {
+        return true;
+    }
+
+    let mut header = [0; 8];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|_| PayloadKind::detect(&header) == Some(PayloadKind::Dex))
+        .unwrap_or(false)
+}
+
+/// Finds dex and jar files shipped along with the code, e.g. in assets. Smali directories are
+/// skipped, these only contain code that has been disassembled already.
+pub fn find_payload_files(dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    let mut directories = vec![dir.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let Ok(entries) = std::fs::read_dir(&directory) else {
+            continue;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                let is_skipped = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.starts_with("smali") || name == "original");
+                if !is_skipped {
+                    directories.push(path);
+                }
+            } else if file_type.is_file() && is_payload_file(&path) {
+                result.push(path);
+            }
+        }
+    }
+    result.sort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    fn tokenizer(data: &str) -> Tokenizer {
+        Tokenizer::new(data.to_string(), std::path::Path::new("dummy"))
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            PayloadKind::detect(b"dex\n035\0rest"),
+            Some(PayloadKind::Dex)
+        );
+        assert_eq!(
+            PayloadKind::detect(b"PK\x03\x04rest"),
+            Some(PayloadKind::Zip)
+        );
+        assert_eq!(PayloadKind::detect(b"dex\n035"), None);
+        assert_eq!(PayloadKind::detect(b"whatever"), None);
+    }
+
+    #[test]
+    fn find_payloads() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(
+            r#"
+                .class public Lcom/example/Loader;
+                .super Ljava/lang/Object;
+
+                .method public static load()V
+                    const-string v0, "classes2.dex"
+                    fill-array-data v1, :array_0
+                    fill-array-data v2, :array_1
+                    return-void
+
+                    :array_0
+                    .array-data 1
+                        0x64t
+                        0x65t
+                        0x78t
+                        0xat
+                        0x30t
+                        0x33t
+                        0x35t
+                        0x0t
+                    .end array-data
+
+                    :array_1
+                    .array-data 1
+                        0x1t
+                        0x2t
+                    .end array-data
+                .end method
+            "#
+            .trim(),
+        );
+
+        let (_, class) = Class::read(&input)?;
+        assert_eq!(
+            class.find_payloads(),
+            vec![Payload {
+                name: "com.example.Loader.load.0.dex".to_string(),
+                kind: PayloadKind::Dex,
+                data: b"dex\n035\0".to_vec(),
+            }]
+        );
+        assert_eq!(
+            class.find_referenced_payloads(),
+            vec!["classes2.dex".to_string()]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::r#type::{MethodSignature, Type};
+
+const PERMISSION_PREFIX: &str = "android.permission.";
+
+/// Framework methods requiring permissions: class, method name (all overloads), permissions.
+/// Where multiple permissions are listed, any of them is sufficient.
+const API_PERMISSIONS: [(&str, &str, &[&str]); 30] = [
+    (
+        "android.telephony.TelephonyManager",
+        "getDeviceId",
+        &["READ_PHONE_STATE"],
+    ),
+    (
+        "android.telephony.TelephonyManager",
+        "getImei",
+        &["READ_PHONE_STATE"],
+    ),
+    (
+        "android.telephony.TelephonyManager",
+        "getSubscriberId",
+        &["READ_PHONE_STATE"],
+    ),
+    (
+        "android.telephony.TelephonyManager",
+        "getSimSerialNumber",
+        &["READ_PHONE_STATE"],
+    ),
+    (
+        "android.telephony.TelephonyManager",
+        "getLine1Number",
+        &["READ_PHONE_STATE", "READ_PHONE_NUMBERS", "READ_SMS"],
+    ),
+    (
+        "android.telephony.SmsManager",
+        "sendTextMessage",
+        &["SEND_SMS"],
+    ),
+    (
+        "android.telephony.SmsManager",
+        "sendMultipartTextMessage",
+        &["SEND_SMS"],
+    ),
+    (
+        "android.telephony.SmsManager",
+        "sendDataMessage",
+        &["SEND_SMS"],
+    ),
+    (
+        "android.location.LocationManager",
+        "getLastKnownLocation",
+        &["ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION"],
+    ),
+    (
+        "android.location.LocationManager",
+        "requestLocationUpdates",
+        &["ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION"],
+    ),
+    (
+        "android.location.LocationManager",
+        "requestSingleUpdate",
+        &["ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION"],
+    ),
+    (
+        "com.google.android.gms.location.FusedLocationProviderClient",
+        "getLastLocation",
+        &["ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION"],
+    ),
+    (
+        "com.google.android.gms.location.FusedLocationProviderClient",
+        "requestLocationUpdates",
+        &["ACCESS_FINE_LOCATION", "ACCESS_COARSE_LOCATION"],
+    ),
+    ("android.hardware.Camera", "open", &["CAMERA"]),
+    (
+        "android.hardware.camera2.CameraManager",
+        "openCamera",
+        &["CAMERA"],
+    ),
+    ("android.media.AudioRecord", "<init>", &["RECORD_AUDIO"]),
+    (
+        "android.media.MediaRecorder",
+        "setAudioSource",
+        &["RECORD_AUDIO"],
+    ),
+    (
+        "android.net.wifi.WifiManager",
+        "getConnectionInfo",
+        &["ACCESS_WIFI_STATE"],
+    ),
+    (
+        "android.net.wifi.WifiManager",
+        "getScanResults",
+        &["ACCESS_WIFI_STATE"],
+    ),
+    (
+        "android.net.wifi.WifiManager",
+        "setWifiEnabled",
+        &["CHANGE_WIFI_STATE"],
+    ),
+    (
+        "android.net.ConnectivityManager",
+        "getActiveNetworkInfo",
+        &["ACCESS_NETWORK_STATE"],
+    ),
+    (
+        "android.net.ConnectivityManager",
+        "getNetworkCapabilities",
+        &["ACCESS_NETWORK_STATE"],
+    ),
+    ("java.net.URL", "openConnection", &["INTERNET"]),
+    ("java.net.Socket", "<init>", &["INTERNET"]),
+    (
+        "android.accounts.AccountManager",
+        "getAccounts",
+        &["GET_ACCOUNTS"],
+    ),
+    (
+        "android.accounts.AccountManager",
+        "getAccountsByType",
+        &["GET_ACCOUNTS"],
+    ),
+    ("android.os.Vibrator", "vibrate", &["VIBRATE"]),
+    (
+        "android.os.PowerManager$WakeLock",
+        "acquire",
+        &["WAKE_LOCK"],
+    ),
+    (
+        "android.bluetooth.BluetoothAdapter",
+        "getBondedDevices",
+        &["BLUETOOTH", "BLUETOOTH_CONNECT"],
+    ),
+    (
+        "android.bluetooth.BluetoothAdapter",
+        "startDiscovery",
+        &["BLUETOOTH_ADMIN", "BLUETOOTH_SCAN"],
+    ),
+];
+
+/// Extracts the permissions requested by a manifest decoded by apktool.
+pub fn read_manifest_permissions(manifest: &str) -> Vec<String> {
+    manifest
+        .split('<')
+        .filter(|tag| tag.starts_with("uses-permission"))
+        .filter_map(|tag| {
+            let (_, value) = tag.split_once("android:name=\"")?;
+            let (value, _) = value.split_once('"')?;
+            Some(value.to_string())
+        })
+        .collect()
+}
+
+fn get_required_permissions(method: &MethodSignature) -> &'static [&'static str] {
+    let Type::Object(class_name) = &method.object_type else {
+        return &[];
+    };
+    API_PERMISSIONS
+        .iter()
+        .find(|(class, name, _)| class == class_name && *name == method.method_name)
+        .map(|(_, _, permissions)| *permissions)
+        .unwrap_or(&[])
+}
+
+/// Calls to permission protected framework methods, grouped by permission.
+#[derive(Debug, Default)]
+pub struct PermissionReport {
+    requested: Vec<String>,
+    uses: BTreeMap<String, Vec<(MethodSignature, MethodSignature)>>,
+}
+
+impl PermissionReport {
+    pub fn new(requested: Vec<String>) -> Self {
+        Self {
+            requested,
+            uses: BTreeMap::new(),
+        }
+    }
+
+    /// Records calls to permission protected methods made by the class.
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            for instruction in &method.instructions {
+                let Instruction::Command {
+                    command,
+                    parameters,
+                } = instruction
+                else {
+                    continue;
+                };
+                if !command.starts_with("invoke-") {
+                    continue;
+                }
+                let Some(CommandParameter::Method(api)) = parameters.get(2) else {
+                    continue;
+                };
+
+                for permission in get_required_permissions(api) {
+                    self.uses
+                        .entry(format!("{PERMISSION_PREFIX}{permission}"))
+                        .or_default()
+                        .push((method.get_signature(&class.class_type), api.clone()));
+                }
+            }
+        }
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut permissions = self.requested.iter().collect::<Vec<_>>();
+        permissions.extend(self.uses.keys());
+        permissions.sort();
+        permissions.dedup();
+
+        for permission in permissions {
+            let requested = self.requested.contains(permission);
+            let uses = self
+                .uses
+                .get(permission)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let status = match (requested, uses.is_empty()) {
+                (true, false) => "requested",
+                (true, true) => "requested, no known uses",
+                (false, _) => "not requested",
+            };
+            writeln!(output, "{permission} ({status})")?;
+            for (caller, api) in uses {
+                writeln!(output, "    <{caller}>")?;
+                writeln!(output, "        calls <{api}>")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn permission_report() -> Result<(), ParseErrorDisplayed> {
+        let manifest = r#"<?xml version="1.0" encoding="utf-8" standalone="no"?>
+<manifest xmlns:android="http://schemas.android.com/apk/res/android" package="com.example">
+    <uses-permission android:name="android.permission.INTERNET"/>
+    <uses-permission android:name="android.permission.CAMERA"/>
+    <uses-permission-sdk-23 android:name="android.permission.READ_PHONE_STATE"/>
+</manifest>"#;
+        let requested = read_manifest_permissions(manifest);
+        assert_eq!(
+            requested,
+            vec![
+                "android.permission.INTERNET",
+                "android.permission.CAMERA",
+                "android.permission.READ_PHONE_STATE"
+            ]
+        );
+
+        let input = Tokenizer::new(
+            r#"
+                .class public Lcom/example/Main;
+                .super Ljava/lang/Object;
+
+                .method public run(Landroid/telephony/TelephonyManager;Ljava/net/URL;)V
+                    .locals 1
+                    invoke-virtual {p1}, Landroid/telephony/TelephonyManager;->getDeviceId()Ljava/lang/String;
+                    invoke-virtual {p2}, Ljava/net/URL;->openConnection()Ljava/net/URLConnection;
+                    const-string v0, "+123"
+                    invoke-static {v0}, Lcom/example/Main;->sendSms(Ljava/lang/String;)V
+                    return-void
+                .end method
+
+                .method public static sendSms(Ljava/lang/String;)V
+                    .locals 2
+                    invoke-static {}, Landroid/telephony/SmsManager;->getDefault()Landroid/telephony/SmsManager;
+                    move-result-object v0
+                    const/4 v1, 0x0
+                    invoke-virtual/range {v0 .. v1}, Landroid/telephony/SmsManager;->sendTextMessage(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Landroid/app/PendingIntent;Landroid/app/PendingIntent;)V
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, class) = Class::read(&input)?;
+
+        let mut report = PermissionReport::new(requested);
+        report.add_class(&class);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"android.permission.CAMERA (requested, no known uses)
+android.permission.INTERNET (requested)
+    <void com.example.Main.run(android.telephony.TelephonyManager, java.net.URL)>
+        calls <java.net.URLConnection java.net.URL.openConnection()>
+android.permission.READ_PHONE_STATE (requested)
+    <void com.example.Main.run(android.telephony.TelephonyManager, java.net.URL)>
+        calls <java.lang.String android.telephony.TelephonyManager.getDeviceId()>
+android.permission.SEND_SMS (not requested)
+    <void com.example.Main.sendSms(java.lang.String)>
+        calls <void android.telephony.SmsManager.sendTextMessage(java.lang.String, java.lang.String, java.lang.String, android.app.PendingIntent, android.app.PendingIntent)>
+"#
+        );
+
+        Ok(())
+    }
+}
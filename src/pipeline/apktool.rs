@@ -0,0 +1,117 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Invocation of apktool disassembling APKs into Smali files and decoding their resources.
+#[derive(Debug, Clone, Default)]
+pub struct Apktool {
+    /// Path to the apktool command or apktool.jar package, looked up in `PATH` if `None`
+    pub path: Option<String>,
+    /// Additional parameters to pass on to apktool decode, e.g. `--no-res`
+    pub options: Vec<OsString>,
+}
+
+impl Apktool {
+    fn command(&self) -> Result<Command, String> {
+        match &self.path {
+            Some(path) if path.ends_with(".jar") => {
+                let java_path = which::which("java").map_err(|_| {
+                    "Supposed to run apktool as JAR file, yet Java could not be found. Is it installed?".to_string()
+                })?;
+                let mut command = Command::new(java_path);
+                command.arg("-jar").arg(path);
+                Ok(command)
+            }
+            Some(path) => Ok(Command::new(path)),
+            None => which::which("apktool").map(Command::new).map_err(|_| {
+                "Could not find apktool. If you installed it, please pass --apktool-path command line parameter explicitly.".to_string()
+            }),
+        }
+    }
+
+    fn decode_command(&self, apk_path: &Path, output_dir: &Path) -> Result<Command, String> {
+        let mut command = self.command()?;
+        command
+            .arg("decode")
+            .arg("--force")
+            .args(&self.options)
+            .arg("--output")
+            .arg(output_dir)
+            .arg(apk_path);
+        Ok(command)
+    }
+
+    /// Runs apktool decode, returning `false` if it exited with an error code.
+    pub fn decode(&self, apk_path: &Path, output_dir: &Path) -> Result<bool, String> {
+        let status = self
+            .decode_command(apk_path, output_dir)?
+            .status()
+            .map_err(|error| format!("Failed running apktool: {error}"))?;
+        Ok(status.success())
+    }
+
+    /// Runs apktool decode, passing each `smali*` directory to the callback as soon as apktool
+    /// moved on to the next dex file, so that conversion overlaps with disassembling. This
+    /// relies on apktool processing dex files one after another. The callback returns `false`
+    /// to stop processing further directories.
+    pub fn decode_overlapped(
+        &self,
+        apk_path: &Path,
+        output_dir: &Path,
+        mut callback: impl FnMut(&Path) -> bool,
+    ) -> Result<bool, String> {
+        let mut command = self.decode_command(apk_path, output_dir)?;
+
+        // apktool --force removes the directory as well, but files left over from a previous run
+        // must not be mistaken for new output before it does
+        let _ = std::fs::remove_dir_all(output_dir);
+
+        let mut child = command
+            .spawn()
+            .map_err(|error| format!("Failed starting apktool: {error}"))?;
+        let mut processed = 0;
+        let mut stopped = false;
+        loop {
+            let status = child
+                .try_wait()
+                .map_err(|error| format!("Failed waiting for apktool to finish: {error}"))?;
+            let dex_dirs = find_dex_dirs(output_dir);
+            // While apktool is running, the last directory is still being written
+            let complete = if status.is_some() {
+                dex_dirs.len()
+            } else {
+                dex_dirs.len().saturating_sub(1)
+            };
+            while processed < complete && !stopped {
+                stopped = !callback(&dex_dirs[processed]);
+                processed += 1;
+            }
+            match status {
+                Some(status) => return Ok(status.success()),
+                None => std::thread::sleep(std::time::Duration::from_millis(100)),
+            }
+        }
+    }
+}
+
+/// Lists the `smali*` directories produced by apktool in the order of the dex files they
+/// correspond to: `smali`, `smali_classes2`, `smali_classes3` and so on.
+fn find_dex_dirs(output_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(output_dir) else {
+        return Vec::new();
+    };
+    let mut result = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let number = match name.to_str()? {
+                "smali" => 1,
+                name => name.strip_prefix("smali_classes")?.parse().ok()?,
+            };
+            Some((number, entry.path()))
+        })
+        .collect::<Vec<(usize, _)>>();
+    result.sort();
+    result.into_iter().map(|(_, path)| path).collect()
+}
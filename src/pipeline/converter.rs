@@ -0,0 +1,782 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::source::{find_smali_files, get_dex_class_path};
+use crate::argument_names::ArgumentNames;
+use crate::cache::ConversionCache;
+use crate::class::Class;
+use crate::constants::ConstantTable;
+use crate::dex::DexFile;
+use crate::diagnostics;
+use crate::format::{JimpleWriter, OutputFormat};
+use crate::implementations::InterfaceIndex;
+use crate::interrupt;
+use crate::layouts::LayoutReferences;
+use crate::output::{
+    ConversionStatus, FileHeader, OutputFile, OutputLayout, OutputOptions, RunManifest,
+};
+use crate::overrides::FrameworkOverrides;
+use crate::r#type::Type;
+use crate::rewrite_rules::RewriteRules;
+use crate::scope::Scope;
+use crate::tokenizer::Tokenizer;
+use crate::xrefs::CallerIndex;
+
+/// Writes the payloads embedded into a class into the payloads directory, returning the paths
+/// of the files written.
+fn write_payloads(class: &Class, payloads_dir: &Path) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    for name in class.find_referenced_payloads() {
+        println!("Class {} references payload file {name}", class.class_type);
+    }
+
+    for payload in class.find_payloads() {
+        if std::fs::create_dir_all(payloads_dir).is_err() {
+            eprintln!(
+                "Failed creating payloads directory {}",
+                payloads_dir.display()
+            );
+            break;
+        }
+
+        let target = payloads_dir.join(&payload.name);
+        if std::fs::write(&target, &payload.data).is_ok() {
+            println!(
+                "Extracted embedded {} payload to {}",
+                payload.kind.extension(),
+                target.display()
+            );
+            result.push(target);
+        } else {
+            eprintln!("Failed writing payload file {}", target.display());
+        }
+    }
+    result
+}
+
+fn write_error(input_path: &Path, error: std::io::Error) -> String {
+    format!(
+        "Failed writing output for {}: {error}",
+        input_path.display()
+    )
+}
+
+/// Converted class that is still waiting to be written out, either into its own file or into
+/// the file of its outer class.
+#[derive(Debug)]
+struct ConvertedClass {
+    format: &'static dyn OutputFormat,
+    path: PathBuf,
+    header: FileHeader,
+    body: Vec<u8>,
+    /// Methods of classes split into several files, these always go into separate files
+    parts: Vec<Vec<u8>>,
+}
+
+impl ConvertedClass {
+    fn write_file(&self, extension: &str, body: &[u8], compress: bool) -> std::io::Result<()> {
+        // Directories might not exist if the output layout differs from the Smali files
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut output = OutputFile::create(&self.path.with_extension(extension), compress)?;
+        let result = self
+            .format
+            .write_header(&mut output, &self.header)
+            .and_then(|_| output.write_all(body));
+        match result {
+            Ok(()) => output.finish(),
+            Err(error) => {
+                output.discard();
+                Err(error)
+            }
+        }
+    }
+
+    fn write_parts(&self, compress: bool) -> std::io::Result<()> {
+        for (index, part) in self.parts.iter().enumerate() {
+            let extension = format!("part{}.{}", index + 1, self.format.extension());
+            self.write_file(&extension, part, compress)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the output file and the parts of split classes, returning an error message
+    /// naming the input file if this fails.
+    fn write(&self, compress: bool) -> Result<(), String> {
+        self.write_file(self.format.extension(), &self.body, compress)
+            .and_then(|_| self.write_parts(compress))
+            .map_err(|error| write_error(&self.header.input_path, error))
+    }
+}
+
+fn build_thread_pool(builder: rayon::ThreadPoolBuilder) -> Result<rayon::ThreadPool, String> {
+    builder
+        .build()
+        .map_err(|error| format!("Failed creating thread pool: {error}"))
+}
+
+/// Number of files each thread prepares before the results are written out, large enough to
+/// keep all threads busy while bounding the memory used by converted classes.
+const FILES_PER_THREAD: usize = 16;
+
+/// Number of errors repeated in the summary when continuing past failed files.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// Class that has been parsed and optimized, still to be written out.
+#[derive(Debug)]
+struct PreparedClass {
+    path: PathBuf,
+    header: FileHeader,
+    content: String,
+    class: Class,
+    payloads: Vec<PathBuf>,
+    /// Output and warning count found in the cache
+    cached: Option<(Vec<u8>, usize)>,
+    optimized: bool,
+    warnings: usize,
+}
+
+/// Result of the parallel part of converting a Smali file.
+#[derive(Debug)]
+enum PreparedFile {
+    /// Out of scope or conversion interrupted
+    Skipped,
+    /// Reading or parsing failed with the given error
+    Failed(PathBuf, String),
+    /// Converted by the previous run being resumed
+    Unchanged(FileHeader),
+    Class(Box<PreparedClass>),
+}
+
+/// Converts Smali files to Jimple, recording the files converted in the run manifest.
+///
+/// Files are processed in sorted order, so that inner classes (`Outer$Inner.smali`) come before
+/// their outer class and can be held back until the outer class is written if requested.
+#[derive(Debug)]
+pub struct DirectoryConverter<'a> {
+    dir: &'a Path,
+    payloads_dir: Option<&'a Path>,
+    constants: Option<&'a ConstantTable>,
+    options: &'a OutputOptions,
+    scope: &'a Scope,
+    layouts: Option<&'a LayoutReferences>,
+    argument_names: Option<&'a ArgumentNames>,
+    callers: Option<&'a CallerIndex>,
+    overrides: Option<&'a FrameworkOverrides>,
+    hierarchy: Option<&'a InterfaceIndex>,
+    rewrite_rules: Option<&'a RewriteRules>,
+    format: &'static dyn OutputFormat,
+    methods_per_file: Option<usize>,
+    layout: OutputLayout,
+    cache: Option<&'a ConversionCache>,
+    command_line: &'a str,
+    compress: bool,
+    pool: rayon::ThreadPool,
+    payloads: Vec<PathBuf>,
+    inner_classes: HashMap<Type, Vec<ConvertedClass>>,
+    manifest: RunManifest,
+    /// Input hashes of the files converted by a previous run that is being resumed
+    previous: HashMap<PathBuf, String>,
+    /// Errors collected while continuing past failed files, `None` if stopping at the first one
+    errors: Option<Vec<String>>,
+    /// Whether method code that fails to parse is kept as raw instructions
+    lenient: bool,
+    /// Time after which optimizing a method is given up, `None` for no limit
+    time_limit: Option<Duration>,
+}
+
+impl<'a> DirectoryConverter<'a> {
+    /// Creates a converter for the Smali files in a directory, writing the output next to them.
+    /// Payloads are extracted if a payloads directory is given. Fails if no thread pool can be
+    /// created.
+    pub fn new(
+        dir: &'a Path,
+        payloads_dir: Option<&'a Path>,
+        constants: Option<&'a ConstantTable>,
+        options: &'a OutputOptions,
+        scope: &'a Scope,
+        command_line: &'a str,
+        compress: bool,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            dir,
+            payloads_dir,
+            constants,
+            options,
+            scope,
+            layouts: None,
+            argument_names: None,
+            callers: None,
+            overrides: None,
+            hierarchy: None,
+            rewrite_rules: None,
+            format: &JimpleWriter,
+            methods_per_file: None,
+            layout: OutputLayout::default(),
+            cache: None,
+            command_line,
+            compress,
+            pool: build_thread_pool(rayon::ThreadPoolBuilder::new())?,
+            payloads: Vec::new(),
+            inner_classes: HashMap::new(),
+            manifest: RunManifest::new(command_line),
+            previous: HashMap::new(),
+            errors: None,
+            lenient: false,
+            time_limit: None,
+        })
+    }
+
+    /// Makes the converter annotate classes and methods referenced from layout files.
+    pub fn annotate_layouts(&mut self, layouts: &'a LayoutReferences) {
+        self.layouts = Some(layouts);
+    }
+
+    /// Makes the converter annotate call arguments with parameter names.
+    pub fn annotate_arguments(&mut self, argument_names: &'a ArgumentNames) {
+        self.argument_names = Some(argument_names);
+    }
+
+    /// Makes the converter list the callers of each method.
+    pub fn annotate_callers(&mut self, callers: &'a CallerIndex) {
+        self.callers = Some(callers);
+    }
+
+    /// Makes the converter name the framework callbacks overridden by methods.
+    pub fn annotate_overrides(&mut self, overrides: &'a FrameworkOverrides) {
+        self.overrides = Some(overrides);
+    }
+
+    /// Makes the converter point calls with a single possible target at the implementation.
+    pub fn devirtualize(&mut self, hierarchy: &'a InterfaceIndex) {
+        self.hierarchy = Some(hierarchy);
+    }
+
+    /// Makes the converter replace calls matching the rules by their replacement expressions.
+    pub fn rewrite_calls(&mut self, rewrite_rules: &'a RewriteRules) {
+        self.rewrite_rules = Some(rewrite_rules);
+    }
+
+    /// Makes the converter write classes in the given format rather than Jimple.
+    pub fn set_format(&mut self, format: &'static dyn OutputFormat) {
+        self.format = format;
+    }
+
+    /// Makes the converter split classes with more methods than the limit into several files.
+    pub fn split_classes(&mut self, methods_per_file: usize) {
+        self.methods_per_file = Some(methods_per_file);
+    }
+
+    /// Makes the converter place output files according to the layout rather than next to the
+    /// Smali files.
+    pub fn set_layout(&mut self, layout: OutputLayout) {
+        self.layout = layout;
+    }
+
+    /// Makes the converter process the given number of files in parallel.
+    pub fn set_jobs(&mut self, jobs: usize) -> Result<(), String> {
+        self.pool = build_thread_pool(rayon::ThreadPoolBuilder::new().num_threads(jobs))?;
+        Ok(())
+    }
+
+    /// Makes the converter take library classes from the cache and store them there.
+    pub fn use_cache(&mut self, cache: &'a ConversionCache) {
+        self.cache = Some(cache);
+    }
+
+    /// Makes the converter continue with the remaining files if a file fails to convert.
+    pub fn keep_going(&mut self) {
+        self.errors = Some(Vec::new());
+    }
+
+    /// Makes the converter keep method code it cannot parse instead of failing the file.
+    pub fn lenient(&mut self) {
+        self.lenient = true;
+    }
+
+    /// Makes the converter leave methods unoptimized if optimizing them exceeds the time limit.
+    pub fn limit_method_time(&mut self, limit: Duration) {
+        self.time_limit = Some(limit);
+    }
+
+    fn tokenizer(&self, input: Tokenizer) -> Tokenizer {
+        if self.lenient {
+            input.lenient()
+        } else {
+            input
+        }
+    }
+
+    /// Makes the converter skip files listed as converted in the manifest of a previous run if
+    /// they are unchanged and their output still exists. Payloads of skipped classes are not
+    /// extracted again.
+    pub fn resume_from(&mut self, previous: RunManifest) {
+        self.previous = previous
+            .files
+            .into_iter()
+            .filter_map(|(path, status)| match status {
+                ConversionStatus::Converted { input_hash } => Some((path, input_hash)),
+                ConversionStatus::Failed => None,
+            })
+            .collect();
+    }
+
+    /// Checks whether a file has been converted by the previous run, so that it can be skipped.
+    fn is_unchanged(&self, relative_path: &Path, header: &FileHeader) -> bool {
+        let Some(previous_hash) = self.previous.get(relative_path) else {
+            return false;
+        };
+        let output_path = self
+            .layout
+            .get_output_path(&self.dir.join(relative_path))
+            .with_extension(self.format.extension());
+        header.input_hash == *previous_hash
+            && OutputFile::get_path(&output_path, self.compress).exists()
+    }
+
+    /// Does the work on a file that doesn't depend on other files: parsing, extracting
+    /// payloads and optimizing the class unless its output is cached. Runs in parallel.
+    fn prepare_file(&self, path: PathBuf, resume: bool) -> PreparedFile {
+        if interrupt::is_interrupted() {
+            return PreparedFile::Skipped;
+        }
+        diagnostics::take_warning_count();
+        let relative_path = path.strip_prefix(self.dir).unwrap_or(&path).to_path_buf();
+        let input = match Tokenizer::from_file(&path) {
+            Ok(input) => self.tokenizer(input),
+            Err(error) => return PreparedFile::Failed(relative_path, error.to_string()),
+        };
+        let header = FileHeader::new(
+            &relative_path,
+            input.content().as_bytes(),
+            self.command_line,
+            0,
+        );
+        if resume && self.is_unchanged(&relative_path, &header) {
+            return PreparedFile::Unchanged(header);
+        }
+        let class = match Class::read(&input) {
+            Ok((_, class)) => class,
+            Err(error) => return PreparedFile::Failed(relative_path, error.to_string()),
+        };
+        self.prepare_class_file(path, header, input.content().to_string(), class)
+    }
+
+    /// Like `prepare_file` but for a class read from a dex file. The class is converted as if
+    /// apktool disassembled it into the Smali file at the given path, this Smali code is only
+    /// generated to identify the input in the manifest and the cache however.
+    fn prepare_dex_class(
+        &self,
+        dex_file: &DexFile<'_>,
+        dex_dir: &Path,
+        index: usize,
+        resume: bool,
+    ) -> PreparedFile {
+        if interrupt::is_interrupted() {
+            return PreparedFile::Skipped;
+        }
+        diagnostics::take_warning_count();
+        let class = match dex_file.read_class(index) {
+            Ok(class) => class,
+            // Skip broken classes rather than the entire app, apktool does the same
+            Err(error) => {
+                eprintln!("Skipping a class of {}: {error}", dex_dir.display());
+                return PreparedFile::Skipped;
+            }
+        };
+        let Some(relative_path) = get_dex_class_path(dex_dir, &class) else {
+            return PreparedFile::Skipped;
+        };
+        if !self.scope.contains_type(&class.class_type) {
+            return PreparedFile::Skipped;
+        }
+
+        let mut content = Vec::new();
+        if let Err(error) = class.write_smali(&mut content) {
+            return PreparedFile::Failed(relative_path, error.to_string());
+        }
+        let content = String::from_utf8_lossy(&content).into_owned();
+        let header = FileHeader::new(&relative_path, content.as_bytes(), self.command_line, 0);
+        if resume && self.is_unchanged(&relative_path, &header) {
+            return PreparedFile::Unchanged(header);
+        }
+        self.prepare_class_file(self.dir.join(relative_path), header, content, class)
+    }
+
+    /// Prepares a parsed class: extracts payloads and optimizes the class unless its output is
+    /// cached.
+    fn prepare_class_file(
+        &self,
+        path: PathBuf,
+        header: FileHeader,
+        content: String,
+        mut class: Class,
+    ) -> PreparedFile {
+        if !self.scope.contains_type(&class.class_type) {
+            return PreparedFile::Skipped;
+        }
+
+        let payloads = match self.payloads_dir {
+            Some(payloads_dir) => write_payloads(&class, payloads_dir),
+            None => Vec::new(),
+        };
+
+        // Output depending on other files can't be cached
+        let cached = self
+            .cache
+            .filter(|_| {
+                ConversionCache::is_shared(&class.class_type)
+                    && !self.layouts.is_some_and(|layouts| layouts.affects(&class))
+            })
+            .and_then(|cache| cache.get(content.as_bytes()));
+        let optimized = cached.is_none();
+        if optimized {
+            self.prepare_class(&mut class);
+        }
+
+        PreparedFile::Class(Box::new(PreparedClass {
+            path,
+            header,
+            content,
+            class,
+            payloads,
+            cached,
+            optimized,
+            warnings: diagnostics::take_warning_count(),
+        }))
+    }
+
+    /// Converts all Smali files in a subdirectory of the output directory. Returns `false` if
+    /// the conversion has been aborted due to an error or interruption.
+    pub fn convert_files(&mut self, subdir: &Path) -> bool {
+        self.convert_batches(find_smali_files(subdir), |this, path, resume| {
+            this.prepare_file(path.clone(), resume)
+        })
+    }
+
+    /// Converts the classes of a dex file, producing the output apktool's Smali files in the
+    /// given subdirectory of the output directory would. Returns `false` if the conversion has
+    /// been aborted due to an error or interruption.
+    pub fn convert_dex(&mut self, dex_file: &DexFile<'_>, dex_dir: &Path) -> bool {
+        // Same order as Smali files, inner classes before their outer class
+        let mut indexes = (0..dex_file.class_count()).collect::<Vec<_>>();
+        indexes.sort_by_cached_key(|index| {
+            dex_file
+                .class_type(*index)
+                .map(Type::to_descriptor)
+                .unwrap_or_default()
+        });
+        self.convert_batches(indexes.into_iter(), |this, index, resume| {
+            this.prepare_dex_class(dex_file, dex_dir, *index, resume)
+        })
+    }
+
+    /// Prepares files or classes in parallel in batches, the results are then written out in
+    /// order.
+    fn convert_batches<T: Sync>(
+        &mut self,
+        mut items: impl Iterator<Item = T>,
+        prepare: impl Fn(&Self, &T, bool) -> PreparedFile + Sync,
+    ) -> bool {
+        let batch_size = self.pool.current_num_threads() * FILES_PER_THREAD;
+        loop {
+            let batch = items.by_ref().take(batch_size).collect::<Vec<_>>();
+            if batch.is_empty() {
+                return true;
+            }
+            let resume = !self.previous.is_empty();
+            let this = &*self;
+            let prepared = self.pool.install(|| {
+                batch
+                    .par_iter()
+                    .map(|item| prepare(this, item, resume))
+                    .collect::<Vec<_>>()
+            });
+
+            for (item, prepared) in batch.iter().zip(prepared) {
+                if interrupt::is_interrupted() {
+                    return false;
+                }
+                let prepared = match prepared {
+                    // Held back inner classes have to be written into the outer class
+                    PreparedFile::Unchanged(_)
+                        if self.options.nest_inner_classes && !self.inner_classes.is_empty() =>
+                    {
+                        prepare(self, item, false)
+                    }
+                    prepared => prepared,
+                };
+                let result = match prepared {
+                    PreparedFile::Skipped => Ok(()),
+                    PreparedFile::Failed(relative_path, error) => Err((relative_path, error)),
+                    PreparedFile::Unchanged(header) => {
+                        self.manifest.add_converted(&header);
+                        Ok(())
+                    }
+                    PreparedFile::Class(prepared) => self.write_prepared(*prepared),
+                };
+                if let Err((relative_path, error)) = result {
+                    if !self.add_failed(&relative_path, error) {
+                        eprintln!("Stopping at the first error, use --keep-going to convert the remaining files.");
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Records a file that failed to convert. Returns `false` if the conversion should stop.
+    fn add_failed(&mut self, relative_path: &Path, error: String) -> bool {
+        eprintln!("{}", error);
+        self.manifest.add_failed(relative_path);
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(error);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes out a prepared class or holds it back until its outer class is written. On failure,
+    /// returns the input path relative to the output directory along with the error message.
+    fn write_prepared(&mut self, prepared: PreparedClass) -> Result<(), (PathBuf, String)> {
+        let PreparedClass {
+            path,
+            mut header,
+            content,
+            mut class,
+            mut payloads,
+            cached,
+            optimized,
+            warnings,
+        } = prepared;
+        self.payloads.append(&mut payloads);
+
+        // Held back inner classes have to be nested into the output
+        let cache = self.cache.filter(|_| {
+            ConversionCache::is_shared(&class.class_type)
+                && !self.layouts.is_some_and(|layouts| layouts.affects(&class))
+                && !self.inner_classes.contains_key(&class.class_type)
+        });
+        let output_path = self.layout.get_output_path(&path);
+        let (body, parts, warnings) = match cached.filter(|_| cache.is_some()) {
+            Some((body, warnings)) => (body, Vec::new(), warnings),
+            None => {
+                diagnostics::take_warning_count();
+                if !optimized {
+                    self.prepare_class(&mut class);
+                }
+                let (body, parts) = self.write_class(&class, &output_path).map_err(|error| {
+                    let error = write_error(&header.input_path, error);
+                    (header.input_path.clone(), error)
+                })?;
+                let warnings = warnings + diagnostics::take_warning_count();
+                if let Some(cache) = cache.filter(|_| parts.is_empty()) {
+                    if let Err(error) = cache.store(content.as_bytes(), &body, warnings) {
+                        eprintln!("Failed writing to cache: {error}");
+                    }
+                }
+                (body, parts, warnings)
+            }
+        };
+
+        header.warnings = warnings;
+        let converted = ConvertedClass {
+            format: self.format,
+            path: output_path,
+            header,
+            body,
+            parts,
+        };
+        match class.get_outer_class() {
+            Some(outer_class)
+                if self.options.nest_inner_classes && self.format.supports_nesting() =>
+            {
+                self.manifest.add_converted(&converted.header);
+                self.inner_classes
+                    .entry(outer_class)
+                    .or_default()
+                    .push(converted);
+            }
+            _ => {
+                converted
+                    .write(self.compress)
+                    .map_err(|error| (converted.header.input_path.clone(), error))?;
+                self.manifest.add_converted(&converted.header);
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts Smali code that doesn't come from a file, e.g. read from standard input. The
+    /// output is written along with its header.
+    pub fn convert_text(&mut self, content: String, output: &mut dyn Write) -> Result<(), String> {
+        let path = Path::new("-");
+        diagnostics::take_warning_count();
+        let input = self.tokenizer(Tokenizer::new(content, path));
+        let (_, mut class) = Class::read(&input).map_err(|error| error.to_string())?;
+        self.prepare_class(&mut class);
+        let (body, _) = self
+            .write_class(&class, path)
+            .map_err(|error| format!("Failed writing output: {error}"))?;
+        let header = FileHeader::new(
+            path,
+            input.content().as_bytes(),
+            self.command_line,
+            diagnostics::take_warning_count(),
+        );
+        self.format
+            .write_header(output, &header)
+            .and_then(|_| output.write_all(&body))
+            .map_err(|error| format!("Failed writing output: {error}"))
+    }
+
+    /// Converts a prepared class to the output format, returning the output and the parts of
+    /// split classes. Held back inner classes are nested into the output.
+    fn write_class(
+        &mut self,
+        class: &Class,
+        output_path: &Path,
+    ) -> std::io::Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let compress = self.compress;
+        let nested = self
+            .inner_classes
+            .remove(&class.class_type)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|inner_class| {
+                inner_class.write_parts(compress)?;
+                Ok(inner_class.body)
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let file_stem = output_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let split = self
+            .methods_per_file
+            .filter(|limit| class.methods.len() > *limit)
+            .and_then(|limit| {
+                self.format
+                    .write_class_parts(class, self.options, &nested, limit, &file_stem)
+            });
+        match split {
+            Some(parts) => {
+                let mut parts = parts?;
+                let body = parts.remove(0);
+                Ok((body, parts))
+            }
+            None => {
+                let mut body = Vec::new();
+                self.format
+                    .write_class(&mut body, class, self.options, &nested)?;
+                Ok((body, Vec::new()))
+            }
+        }
+    }
+
+    /// Optimizes a class and applies the enabled analyses to it, unless the output format
+    /// writes classes as read.
+    fn prepare_class(&self, class: &mut Class) {
+        if !self.format.is_decompiled() {
+            return;
+        }
+        match self.time_limit {
+            Some(limit) => class.optimize_within(limit),
+            None => class.optimize(),
+        }
+        if let Some(hierarchy) = self.hierarchy {
+            hierarchy.devirtualize(class);
+        }
+        if let Some(constants) = self.constants {
+            constants.apply(class);
+        }
+        if let Some(layouts) = self.layouts {
+            layouts.apply(class);
+        }
+        if let Some(argument_names) = self.argument_names {
+            argument_names.apply(class);
+        }
+        if let Some(callers) = self.callers {
+            callers.apply(class);
+        }
+        if let Some(overrides) = self.overrides {
+            overrides.apply(class);
+        }
+        if let Some(rewrite_rules) = self.rewrite_rules {
+            rewrite_rules.apply(class);
+        }
+        if self.options.assume_release {
+            class.assume_release();
+        }
+        if self.options.mark_debug_code {
+            class.mark_debug_code();
+        }
+        if self.options.flag_suspicious_calls {
+            class.flag_suspicious_calls();
+        }
+        if self.options.mask_hints {
+            class.annotate_bitmasks();
+        }
+    }
+
+    /// Writes out the inner classes still held back, e.g. because their outer class is located
+    /// in a different dex file.
+    fn write_held_back(&mut self) {
+        for converted in std::mem::take(&mut self.inner_classes)
+            .into_values()
+            .flatten()
+        {
+            if let Err(error) = converted.write(self.compress) {
+                // Too late to stop, the remaining classes are written regardless
+                self.add_failed(&converted.header.input_path, error);
+            }
+        }
+    }
+
+    /// Writes out the classes still held back and prints a summary of the errors collected
+    /// while continuing past failed files. Returns `false` if any file failed to convert.
+    pub fn report_errors(&mut self) -> bool {
+        self.write_held_back();
+        let failed = self
+            .manifest
+            .files
+            .iter()
+            .filter(|(_, status)| matches!(status, ConversionStatus::Failed))
+            .count();
+        let Some(errors) = self.errors.as_ref().filter(|_| failed > 0) else {
+            return failed == 0;
+        };
+        let converted = self.manifest.files.len() - failed;
+        eprintln!("{converted} files converted, {failed} failed. First errors:");
+        for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+            eprintln!("{error}");
+        }
+        if errors.len() > MAX_REPORTED_ERRORS {
+            eprintln!(
+                "{} more errors, failed files are listed in {}",
+                errors.len() - MAX_REPORTED_ERRORS,
+                self.dir.join(RunManifest::FILE_NAME).display()
+            );
+        }
+        false
+    }
+
+    /// Writes out the remaining classes and the run manifest. Returns the list of extracted
+    /// payload files if payload extraction is enabled.
+    pub fn finish(mut self) -> Vec<PathBuf> {
+        self.write_held_back();
+
+        let mut manifest = self.manifest;
+        manifest.complete = !interrupt::is_interrupted();
+        if let Err(error) = manifest.write_to_dir(self.dir) {
+            eprintln!("Failed writing {}: {error}", RunManifest::FILE_NAME);
+        }
+        self.payloads
+    }
+}
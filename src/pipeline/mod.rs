@@ -0,0 +1,572 @@
+//! Decompile pipeline: disassembles an APK with apktool or reads its dex files directly, runs
+//! the whole-program passes requested and converts all classes in parallel, writing the output
+//! files along with a run manifest.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::argument_names::ArgumentNames;
+use crate::cache::ConversionCache;
+use crate::constants::{ConstantFields, ConstantTable};
+use crate::dex::DexFile;
+use crate::format::Format;
+use crate::implementations::InterfaceIndex;
+use crate::index::Index;
+use crate::interrupt;
+use crate::layouts::LayoutReferences;
+use crate::output::{ConversionStatus, OutputLayout, OutputOptions, RunManifest};
+use crate::overrides::FrameworkOverrides;
+use crate::payload;
+use crate::rewrite_rules::RewriteRules;
+use crate::scope::Scope;
+use crate::xrefs::CallerIndex;
+
+pub use apktool::Apktool;
+pub use converter::DirectoryConverter;
+pub use source::{
+    find_class_file, find_smali_files, for_each_class, for_each_class_file, read_method,
+    ClassSource,
+};
+
+mod apktool;
+mod converter;
+mod source;
+
+/// Limits how deep payloads found within decompiled payloads will be followed.
+const MAX_PAYLOAD_DEPTH: usize = 4;
+
+/// Options of the decompile pipeline, beyond those affecting how code is written.
+#[derive(Debug, Clone, Default)]
+pub struct PipelineOptions {
+    /// How to run apktool if the input needs to be disassembled
+    pub apktool: Apktool,
+    /// Options recorded in the output headers and the run manifest, a previous run is only
+    /// resumed if they are unchanged
+    pub command_line: String,
+    /// Dump dex and jar files embedded into code or assets into the payloads directory
+    pub extract_payloads: bool,
+    /// Decompile extracted payloads as well, implies `extract_payloads`
+    pub decompile_payloads: bool,
+    /// Replace constants passed to @IntDef and @StringDef parameters by named fields
+    pub resolve_typedefs: bool,
+    /// Don't replace literals passed to well-known framework methods by framework constants
+    pub no_framework_constants: bool,
+    /// Translate between reads of constant fields and literals
+    pub constant_fields: Option<ConstantFields>,
+    /// Convert the Smali files of each dex file while apktool is still disassembling the next one
+    pub overlap_apktool: bool,
+    /// Read the dex files of the APK directly instead of running apktool
+    pub native_dex: bool,
+    /// Number of files to convert in parallel, all CPU cores by default
+    pub jobs: Option<usize>,
+    /// Continue an interrupted run, skipping files converted already
+    pub resume: bool,
+    /// Continue with the remaining files when a file fails to parse
+    pub keep_going: bool,
+    /// Keep lines of method code that fail to parse as comments
+    pub lenient: bool,
+    /// Time after which optimizing a method is given up, `None` for no limit
+    pub method_time_limit: Option<Duration>,
+    /// File with rules replacing calls by expressions in the output
+    pub rewrite_rules: Option<PathBuf>,
+    /// Write gzip-compressed output files
+    pub compress: bool,
+    /// Split classes with more methods than this into several files
+    pub split_methods: Option<usize>,
+    /// Placement of the output files relative to the Smali files
+    pub layout: OutputLayout,
+    /// Directory to cache converted library classes in
+    pub cache_dir: Option<PathBuf>,
+    /// Format of the output files
+    pub format: Format,
+    /// Memory whole-program indexes may use before writing entries out to temporary files,
+    /// these are kept in memory if `None`
+    pub index_memory_limit: Option<usize>,
+}
+
+impl PipelineOptions {
+    fn read_rewrite_rules(&self) -> Result<Option<RewriteRules>, String> {
+        self.rewrite_rules
+            .as_deref()
+            .map(RewriteRules::read)
+            .transpose()
+    }
+}
+
+/// Input of the decompile pipeline.
+#[derive(Debug, Clone, Copy)]
+pub enum PipelineInput<'a> {
+    /// APK or dex file to be disassembled into the output directory first
+    Package(&'a Path),
+    /// Smali files disassembled earlier, copied into the output directory first
+    SmaliDir(&'a Path),
+    /// Smali files in the output directory, e.g. from an earlier apktool run, optionally only
+    /// a single file within it
+    Smali(Option<&'a Path>),
+}
+
+/// Sets up the table of well-known framework constants, unless these are disabled. Unlike
+/// the app's own constants, these don't require reading other classes.
+fn build_framework_constants(pipeline: &PipelineOptions) -> ConstantTable {
+    let mut table = ConstantTable::new();
+    if !pipeline.no_framework_constants {
+        table.add_framework_constants();
+    }
+    table
+}
+
+/// Sets up the table of constants to be resolved, optionally reading all classes to collect
+/// constants and typedef annotations.
+fn build_constant_table(source: ClassSource<'_>, pipeline: &PipelineOptions) -> ConstantTable {
+    let mut table = build_framework_constants(pipeline);
+    if let Some(mode) = pipeline.constant_fields {
+        table.set_field_mode(mode);
+    }
+    if !pipeline.resolve_typedefs && pipeline.constant_fields.is_none() {
+        return table;
+    }
+
+    println!("Collecting constants...");
+    // Constants are needed regardless of the scope
+    source.for_each_class(&Scope::default(), |class| table.add_class(&class));
+    table.resolve();
+    table
+}
+
+fn build_argument_names(source: ClassSource<'_>) -> ArgumentNames {
+    println!("Collecting parameter names...");
+    let mut names = ArgumentNames::new();
+    // Calls to methods outside the scope are annotated as well
+    source.for_each_class(&Scope::default(), |class| names.add_class(&class));
+    names
+}
+
+fn build_overrides(source: ClassSource<'_>) -> FrameworkOverrides {
+    println!("Collecting class hierarchy...");
+    let mut overrides = FrameworkOverrides::new();
+    source.for_each_class(&Scope::default(), |class| overrides.add_class(&class));
+    overrides
+}
+
+/// Collects the implementations of all interfaces and classes, e.g. to devirtualize calls.
+pub fn build_hierarchy(source: ClassSource<'_>) -> InterfaceIndex {
+    println!("Collecting class hierarchy...");
+    let mut hierarchy = InterfaceIndex::new();
+    source.for_each_class(&Scope::default(), |class| hierarchy.add_references(&class));
+    hierarchy
+}
+
+fn build_caller_index(
+    index: Index,
+    source: ClassSource<'_>,
+    hierarchy: Option<&InterfaceIndex>,
+) -> Result<CallerIndex, std::io::Error> {
+    println!("Collecting callers...");
+    let mut callers = CallerIndex::new(index);
+    let mut result = Ok(());
+    source.for_each_class(&Scope::default(), |mut class| {
+        if result.is_ok() {
+            class.optimize();
+            // Devirtualized calls are attributed to the implementation they end up in
+            if let Some(hierarchy) = hierarchy {
+                hierarchy.devirtualize(&mut class);
+            }
+            result = callers.add_class(&class);
+        }
+    });
+    result.map(|_| callers)
+}
+
+/// Reads the manifest of a previous run to be resumed. Returns `None` if apktool needs to run
+/// again because there is no usable manifest. If the options changed, the manifest of an
+/// empty run is returned, so that the apktool output is reused but all files are converted.
+fn read_previous_manifest(output_dir: &Path, command_line: &str) -> Option<RunManifest> {
+    let path = output_dir.join(RunManifest::FILE_NAME);
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("Cannot resume, failed reading {}: {error}", path.display());
+            return None;
+        }
+    };
+    match RunManifest::read(&data) {
+        Ok(manifest) if manifest.options == command_line => {
+            let converted = manifest
+                .files
+                .iter()
+                .filter(|(_, status)| matches!(status, ConversionStatus::Converted { .. }))
+                .count();
+            println!("Resuming previous run, {converted} files converted already");
+            Some(manifest)
+        }
+        Ok(_) => {
+            println!("Options changed since the previous run, converting all files again");
+            Some(RunManifest::new(command_line))
+        }
+        Err(error) => {
+            eprintln!("Cannot resume, {} is {error}", path.display());
+            None
+        }
+    }
+}
+
+/// Decompiles the input into the output directory. Errors are reported as they occur, returns
+/// `false` if any file failed to convert or the conversion has been interrupted.
+pub fn decompile(
+    input: PipelineInput<'_>,
+    output_dir: &Path,
+    pipeline: &PipelineOptions,
+    options: &OutputOptions,
+    scope: &Scope,
+) -> bool {
+    decompile_nested(input, output_dir, pipeline, options, scope, 0)
+}
+
+fn decompile_nested(
+    input: PipelineInput<'_>,
+    output_dir: &Path,
+    pipeline: &PipelineOptions,
+    options: &OutputOptions,
+    scope: &Scope,
+    depth: usize,
+) -> bool {
+    let options = &OutputOptions {
+        method_time_limit: pipeline.method_time_limit,
+        ..options.clone()
+    };
+    let (apk_path, smali_file) = match input {
+        PipelineInput::Package(apk_path) => (Some(apk_path), None),
+        PipelineInput::SmaliDir(input_dir) => {
+            if !source::copy_smali_dir(input_dir, output_dir) {
+                return false;
+            }
+            (None, None)
+        }
+        PipelineInput::Smali(smali_file) => (None, smali_file),
+    };
+    let native_dex = pipeline.native_dex || apk_path.is_some_and(source::is_dex_file);
+    if (native_dex || apk_path.is_none()) && !pipeline.apktool.options.is_empty() {
+        eprintln!("Not running apktool for Smali or dex input or with --native-dex, ignoring --no-res, --no-src, --frame-path, --api-level and --apktool-arg.");
+    }
+    // Whole-program information requires reading all files before converting any
+    let overlap = pipeline.overlap_apktool
+        && apk_path.is_some()
+        && !native_dex
+        && !pipeline.resolve_typedefs
+        && pipeline.constant_fields.is_none()
+        && !options.argument_names
+        && !options.show_callers
+        && !options.show_overrides
+        && !options.devirtualize;
+    if pipeline.overlap_apktool && !overlap {
+        eprintln!("Cannot overlap apktool and conversion with --native-dex, --resolve-typedefs, --constant-fields, --argument-names, --show-callers, --show-overrides or --devirtualize, ignoring --overlap-apktool.");
+    }
+    let command_line = pipeline.command_line.as_str();
+    let previous = if pipeline.resume {
+        read_previous_manifest(output_dir, command_line)
+    } else {
+        None
+    };
+    // Dex files are converted directly, also when resuming
+    let dex_files = match apk_path.filter(|_| native_dex).map(source::read_dex_input) {
+        Some(Ok(dex_files)) => Some(dex_files),
+        Some(Err(error)) => {
+            eprintln!("{error}");
+            return false;
+        }
+        None => None,
+    };
+    if dex_files.is_none() && !pipeline.format.get_writer().is_decompiled() {
+        eprintln!("Writing {:?} output requires reading dex files directly, use --native-dex with an APK or a dex file as input.", pipeline.format);
+        return false;
+    }
+    if let Some(apk_path) = apk_path.filter(|_| !native_dex && !overlap && previous.is_none()) {
+        match pipeline.apktool.decode(apk_path, output_dir) {
+            Ok(true) => (),
+            Ok(false) => {
+                if !interrupt::is_interrupted() {
+                    eprintln!("apktool exited with an error code.");
+                }
+                return false;
+            }
+            Err(error) => {
+                eprintln!("{error}");
+                return false;
+            }
+        }
+    }
+    let source = match &dex_files {
+        Some(dex_files) => ClassSource::Dex(dex_files),
+        None => ClassSource::Smali(output_dir),
+    };
+
+    let rewrite_rules = match pipeline.read_rewrite_rules() {
+        Ok(rewrite_rules) => rewrite_rules,
+        Err(error) => {
+            eprintln!("{error}");
+            return false;
+        }
+    };
+    let extract_payloads = pipeline.extract_payloads || pipeline.decompile_payloads;
+    let payloads_dir = output_dir.join("payloads");
+    let constants = build_constant_table(source, pipeline);
+    let argument_names = options.argument_names.then(|| build_argument_names(source));
+    let overrides = options.show_overrides.then(|| build_overrides(source));
+    let hierarchy = options.devirtualize.then(|| build_hierarchy(source));
+    let callers = if options.show_callers {
+        let callers = Index::new(pipeline.index_memory_limit)
+            .and_then(|index| build_caller_index(index, source, hierarchy.as_ref()));
+        match callers {
+            Ok(callers) => Some(callers),
+            Err(error) => {
+                eprintln!("Failed building caller index: {error}");
+                return false;
+            }
+        }
+    } else {
+        None
+    };
+    // Resources aren't decoded yet if apktool is running while converting
+    let layouts = if !overlap || previous.is_some() {
+        LayoutReferences::read(output_dir)
+    } else {
+        LayoutReferences::new()
+    };
+    let converter = DirectoryConverter::new(
+        output_dir,
+        extract_payloads.then_some(payloads_dir.as_path()),
+        Some(&constants),
+        options,
+        scope,
+        command_line,
+        pipeline.compress,
+    );
+    let mut converter = match converter {
+        Ok(converter) => converter,
+        Err(error) => {
+            eprintln!("{error}");
+            return false;
+        }
+    };
+    if !layouts.is_empty() {
+        converter.annotate_layouts(&layouts);
+    }
+    if let Some(argument_names) = &argument_names {
+        converter.annotate_arguments(argument_names);
+    }
+    if let Some(callers) = &callers {
+        converter.annotate_callers(callers);
+    }
+    if let Some(overrides) = &overrides {
+        converter.annotate_overrides(overrides);
+    }
+    if let Some(hierarchy) = &hierarchy {
+        converter.devirtualize(hierarchy);
+    }
+    if let Some(rewrite_rules) = &rewrite_rules {
+        converter.rewrite_calls(rewrite_rules);
+    }
+    if let Some(methods_per_file) = pipeline.split_methods {
+        converter.split_classes(methods_per_file);
+    }
+    if let Some(jobs) = pipeline.jobs {
+        if let Err(error) = converter.set_jobs(jobs) {
+            eprintln!("{error}");
+            return false;
+        }
+    }
+    if pipeline.keep_going {
+        converter.keep_going();
+    }
+    if pipeline.lenient {
+        converter.lenient();
+    }
+    if let Some(limit) = pipeline.method_time_limit {
+        converter.limit_method_time(limit);
+    }
+    converter.set_format(pipeline.format.get_writer());
+    converter.set_layout(pipeline.layout.clone());
+    let cache = pipeline.cache_dir.as_ref().and_then(|cache_dir| {
+        if pipeline.resolve_typedefs
+            || pipeline.constant_fields.is_some()
+            || options.argument_names
+            || options.show_callers
+            || options.show_overrides
+            || options.devirtualize
+        {
+            eprintln!("Cannot cache output with --resolve-typedefs, --constant-fields, --argument-names, --show-callers, --show-overrides or --devirtualize, ignoring --cache-dir.");
+            return None;
+        }
+        // Only options affecting the output of a single class matter
+        let fingerprint = format!(
+            "{options:?} no_framework_constants={} split_methods={:?} format={:?} rewrite_rules={:?}",
+            pipeline.no_framework_constants,
+            pipeline.split_methods,
+            pipeline.format,
+            rewrite_rules
+        );
+        Some(ConversionCache::new(cache_dir, &fingerprint))
+    });
+    if let Some(cache) = &cache {
+        converter.use_cache(cache);
+    }
+    let resuming = previous.is_some();
+    if let Some(previous) = previous {
+        converter.resume_from(previous);
+    }
+    let input_success = match apk_path.filter(|_| overlap && !resuming) {
+        Some(apk_path) => {
+            println!("Converting Smali files to Jimple while apktool is running...");
+            let result = pipeline
+                .apktool
+                .decode_overlapped(apk_path, output_dir, |dex_dir| {
+                    converter.convert_files(dex_dir)
+                });
+            result.unwrap_or_else(|error| {
+                eprintln!("{error}");
+                false
+            })
+        }
+        None => match source {
+            ClassSource::Dex(dex_files) => convert_dex_files(&mut converter, dex_files),
+            ClassSource::Smali(dir) => {
+                println!("Converting Smali files to Jimple...");
+                converter.convert_files(smali_file.unwrap_or(dir));
+                true
+            }
+        },
+    };
+    let success = converter.report_errors();
+    let mut converted_payloads = converter.finish();
+    if !input_success && !interrupt::is_interrupted() {
+        // Errors reading dex files have been reported already
+        if dex_files.is_none() {
+            eprintln!("apktool exited with an error code.");
+        }
+        return false;
+    }
+
+    let mut payloads = Vec::new();
+    if extract_payloads {
+        payloads = payload::find_payload_files(output_dir);
+        for path in &payloads {
+            println!("Found payload file {}", path.display());
+        }
+    }
+    payloads.append(&mut converted_payloads);
+
+    if interrupt::is_interrupted() {
+        eprintln!(
+            "Conversion interrupted, completed files are listed in {}",
+            output_dir.join(RunManifest::FILE_NAME).display()
+        );
+        return false;
+    }
+
+    if pipeline.decompile_payloads {
+        if depth >= MAX_PAYLOAD_DEPTH {
+            eprintln!(
+                "Not decompiling payloads found in {}, maximal nesting depth reached.",
+                output_dir.display()
+            );
+            return success;
+        }
+
+        for path in payloads {
+            let mut target = path.clone().into_os_string();
+            target.push(".decoded");
+            println!("Decompiling payload {}...", path.display());
+            decompile_nested(
+                PipelineInput::Package(&path),
+                Path::new(&target),
+                pipeline,
+                options,
+                scope,
+                depth + 1,
+            );
+        }
+    }
+    success
+}
+
+/// Converts the classes of all dex files. Returns `false` if a dex file couldn't be read.
+fn convert_dex_files(
+    converter: &mut DirectoryConverter<'_>,
+    dex_files: &[(String, Vec<u8>)],
+) -> bool {
+    let mut success = true;
+    for (name, data) in dex_files {
+        let dex_file = match DexFile::read(data) {
+            Ok(dex_file) => dex_file,
+            Err(error) => {
+                eprintln!("Failed reading {name}: {error}");
+                success = false;
+                continue;
+            }
+        };
+        println!(
+            "Converting {} classes from {name} to Jimple...",
+            dex_file.class_count()
+        );
+        if !converter.convert_dex(&dex_file, &source::get_dex_dir(name)) {
+            break;
+        }
+    }
+    success
+}
+
+/// Converts Smali code that doesn't come from a file, e.g. read from standard input, writing
+/// the output along with its header. Analyses requiring the other files of the app are
+/// unavailable.
+pub fn convert_text(
+    content: String,
+    output: &mut dyn Write,
+    pipeline: &PipelineOptions,
+    options: &OutputOptions,
+    scope: &Scope,
+) -> Result<(), String> {
+    let options = &OutputOptions {
+        method_time_limit: pipeline.method_time_limit,
+        ..options.clone()
+    };
+    if pipeline.resolve_typedefs
+        || pipeline.constant_fields.is_some()
+        || options.argument_names
+        || options.show_callers
+        || options.devirtualize
+    {
+        eprintln!("Cannot use --resolve-typedefs, --constant-fields, --argument-names, --show-callers or --devirtualize when converting standard input, ignoring.");
+    }
+    if !pipeline.apktool.options.is_empty() {
+        eprintln!("Not running apktool when converting standard input, ignoring --no-res, --no-src, --frame-path, --api-level and --apktool-arg.");
+    }
+
+    let rewrite_rules = pipeline.read_rewrite_rules()?;
+    let constants = build_framework_constants(pipeline);
+    let mut converter = DirectoryConverter::new(
+        Path::new("."),
+        None,
+        Some(&constants),
+        options,
+        scope,
+        &pipeline.command_line,
+        false,
+    )?;
+    converter.set_format(pipeline.format.get_writer());
+    if pipeline.lenient {
+        converter.lenient();
+    }
+    if let Some(limit) = pipeline.method_time_limit {
+        converter.limit_method_time(limit);
+    }
+    if let Some(rewrite_rules) = &rewrite_rules {
+        converter.rewrite_calls(rewrite_rules);
+    }
+    // Without the other files, only callbacks of the direct superclass and interfaces are found
+    let overrides = FrameworkOverrides::new();
+    if options.show_overrides {
+        converter.annotate_overrides(&overrides);
+    }
+    converter.convert_text(content, output)
+}
@@ -0,0 +1,251 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::class::Class;
+use crate::dex::{self, DexFile};
+use crate::interrupt;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+use crate::scope::Scope;
+use crate::tokenizer::Tokenizer;
+
+/// Where the classes of the app being decompiled come from.
+#[derive(Debug, Clone, Copy)]
+pub enum ClassSource<'a> {
+    /// Smali files in a directory
+    Smali(&'a Path),
+    /// Dex files read directly, along with their names
+    Dex(&'a [(String, Vec<u8>)]),
+}
+
+impl ClassSource<'_> {
+    pub fn for_each_class(self, scope: &Scope, callback: impl FnMut(Class)) {
+        match self {
+            Self::Smali(dir) => for_each_class(dir, scope, callback),
+            Self::Dex(dex_files) => for_each_dex_class(dex_files, scope, callback),
+        }
+    }
+}
+
+/// Lists the Smali files in a directory and its subdirectories, sorted by file name.
+pub fn find_smali_files(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && entry.path().extension().filter(|s| *s == "smali").is_some()
+        })
+        .map(|entry| entry.into_path())
+}
+
+/// Parses all Smali files in a directory, passing each class within the scope to the callback.
+pub fn for_each_class(dir: &Path, scope: &Scope, mut callback: impl FnMut(Class)) {
+    for_each_class_file(dir, scope, |_, class| callback(class));
+}
+
+/// Like `for_each_class`, additionally passing the path of the Smali file to the callback.
+pub fn for_each_class_file(dir: &Path, scope: &Scope, mut callback: impl FnMut(&Path, Class)) {
+    for path in find_smali_files(dir) {
+        if interrupt::is_interrupted() {
+            break;
+        }
+        match Tokenizer::from_file(&path) {
+            Ok(input) => match Class::read(&input) {
+                Ok((_, class)) => {
+                    if scope.contains_type(&class.class_type) {
+                        callback(&path, class);
+                    }
+                }
+                Err(error) => eprintln!("{}", error),
+            },
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+/// Calls the callback for each class in the dex files that is within the scope. Broken classes
+/// are skipped rather than the entire app, apktool does the same.
+fn for_each_dex_class(
+    dex_files: &[(String, Vec<u8>)],
+    scope: &Scope,
+    mut callback: impl FnMut(Class),
+) {
+    for (name, data) in dex_files {
+        let dex_file = match DexFile::read(data) {
+            Ok(dex_file) => dex_file,
+            Err(error) => {
+                eprintln!("Failed reading {name}: {error}");
+                continue;
+            }
+        };
+        for class in dex_file.classes() {
+            if interrupt::is_interrupted() {
+                return;
+            }
+            match class {
+                Ok(class) if scope.contains_type(&class.class_type) => callback(class),
+                Ok(_) => (),
+                Err(error) => eprintln!("Skipping a class of {name}: {error}"),
+            }
+        }
+    }
+}
+
+/// Checks whether a file is a dex file by its extension or its contents.
+pub(super) fn is_dex_file(path: &Path) -> bool {
+    if path.extension().is_some_and(|extension| extension == "dex") {
+        return true;
+    }
+    let mut magic = [0; 4];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| &magic == b"dex\n")
+}
+
+/// Reads the dex files of an APK or a single dex file, returning the file names along with the
+/// data. The classes are converted directly rather than disassembled to Smali files.
+pub(super) fn read_dex_input(input_path: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let data = std::fs::read(input_path)
+        .map_err(|error| format!("Failed reading {}: {error}", input_path.display()))?;
+    if data.starts_with(b"dex\n") {
+        Ok(vec![("classes.dex".to_string(), data)])
+    } else {
+        dex::read_dex_files(&data)
+            .map_err(|error| format!("Failed reading {}: {error}", input_path.display()))
+    }
+}
+
+/// Directory a dex file's classes would be disassembled into by apktool, relative to the
+/// output directory.
+pub(super) fn get_dex_dir(name: &str) -> PathBuf {
+    match name.trim_start_matches("classes").trim_end_matches(".dex") {
+        "" => PathBuf::from("smali"),
+        number => PathBuf::from(format!("smali_classes{number}")),
+    }
+}
+
+/// Path of the Smali file apktool would write a class to, relative to the output directory.
+pub(super) fn get_dex_class_path(dex_dir: &Path, class: &Class) -> Option<PathBuf> {
+    let Type::Object(class_name) = &class.class_type else {
+        return None;
+    };
+    Some(dex_dir.join(format!("{}.smali", class_name.replace('.', "/"))))
+}
+
+/// Copies a directory of Smali files disassembled earlier into the output directory, so that
+/// the conversion leaves the original untouched. Nothing is copied if both are the same.
+pub(super) fn copy_smali_dir(input_dir: &Path, output_dir: &Path) -> bool {
+    // The output directory has to exist to be recognized when located within the input directory
+    if let Err(error) = std::fs::create_dir_all(output_dir) {
+        eprintln!("Failed creating {}: {error}", output_dir.display());
+        return false;
+    }
+    let (resolved_input, resolved_output) = match (
+        input_dir.canonicalize(),
+        output_dir.canonicalize(),
+    ) {
+        (Ok(input), Ok(output)) => (input, output),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!(
+                "Failed resolving {} or {}, cannot tell whether the output directory is located within the input: {error}",
+                input_dir.display(),
+                output_dir.display()
+            );
+            return false;
+        }
+    };
+    if resolved_input == resolved_output {
+        return true;
+    }
+    if resolved_input.starts_with(&resolved_output) {
+        eprintln!(
+            "Input directory {} is located within the output directory {}, refusing to copy.",
+            input_dir.display(),
+            output_dir.display()
+        );
+        return false;
+    }
+
+    println!(
+        "Copying {} to {}...",
+        input_dir.display(),
+        output_dir.display()
+    );
+    // The output directory might be located within the input directory, walking the resolved
+    // path makes entries comparable to it
+    let entries = walkdir::WalkDir::new(&resolved_input)
+        .into_iter()
+        .filter_entry(|entry| entry.path() != resolved_output);
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                eprintln!("Failed reading {}: {error}", input_dir.display());
+                return false;
+            }
+        };
+        let Ok(relative_path) = entry.path().strip_prefix(&resolved_input) else {
+            continue;
+        };
+        let target = output_dir.join(relative_path);
+        let result = if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)
+        } else {
+            std::fs::copy(entry.path(), &target).map(|_| ())
+        };
+        if let Err(error) = result {
+            eprintln!("Failed copying to {}: {error}", target.display());
+            return false;
+        }
+    }
+    true
+}
+
+/// Locates the Smali file declaring a class, either directly in the directory or in one of the
+/// `smali*` directories produced by apktool.
+pub fn find_class_file(dir: &Path, class_type: &Type) -> Option<PathBuf> {
+    let Type::Object(name) = class_type else {
+        return None;
+    };
+    let relative_path = format!("{}.smali", name.replace('.', "/"));
+
+    let mut candidates = vec![dir.to_path_buf()];
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        let mut smali_dirs = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("smali"))
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        smali_dirs.sort();
+        candidates.append(&mut smali_dirs);
+    }
+
+    candidates
+        .into_iter()
+        .map(|candidate| candidate.join(&relative_path))
+        .find(|path| path.is_file())
+}
+
+/// Reads a method from the class files without optimizing it.
+pub fn read_method(dir: &Path, signature: &MethodSignature) -> Result<Method, String> {
+    let path = find_class_file(dir, &signature.object_type).ok_or_else(|| {
+        format!(
+            "Could not find class {} in {}",
+            signature.object_type,
+            dir.display()
+        )
+    })?;
+
+    let input = Tokenizer::from_file(&path).map_err(|error| error.to_string())?;
+    let (_, mut class) = Class::read_filtered(&input, |name, call_signature| {
+        name == signature.method_name && *call_signature == signature.call_signature
+    })
+    .map_err(|error| error.to_string())?;
+
+    class
+        .methods
+        .pop()
+        .ok_or_else(|| format!("Could not find method {signature} in {}", path.display()))
+}
@@ -0,0 +1,360 @@
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, ResultType};
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+
+/// Kind of protection a finding points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Protection {
+    /// Certificate pinning or a custom TLS configuration
+    Pinning,
+    /// Custom certificate or host name validation
+    CertificateValidation,
+    RootDetection,
+    /// Emulator or debugger detection
+    EmulatorDetection,
+}
+
+impl Protection {
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            Protection::Pinning => "Certificate pinning and TLS configuration",
+            Protection::CertificateValidation => "Custom certificate validation",
+            Protection::RootDetection => "Root detection",
+            Protection::EmulatorDetection => "Emulator and debugger detection",
+        }
+    }
+}
+
+/// API calls indicating a protection: class, method name (all overloads), protection.
+const PROTECTION_CALLS: [(&str, &str, Protection); 14] = [
+    (
+        "okhttp3.CertificatePinner$Builder",
+        "add",
+        Protection::Pinning,
+    ),
+    (
+        "com.squareup.okhttp.CertificatePinner$Builder",
+        "add",
+        Protection::Pinning,
+    ),
+    (
+        "okhttp3.OkHttpClient$Builder",
+        "certificatePinner",
+        Protection::Pinning,
+    ),
+    (
+        "okhttp3.OkHttpClient$Builder",
+        "sslSocketFactory",
+        Protection::Pinning,
+    ),
+    (
+        "okhttp3.OkHttpClient$Builder",
+        "hostnameVerifier",
+        Protection::CertificateValidation,
+    ),
+    ("javax.net.ssl.SSLContext", "init", Protection::Pinning),
+    (
+        "javax.net.ssl.HttpsURLConnection",
+        "setSSLSocketFactory",
+        Protection::Pinning,
+    ),
+    (
+        "javax.net.ssl.HttpsURLConnection",
+        "setDefaultSSLSocketFactory",
+        Protection::Pinning,
+    ),
+    (
+        "javax.net.ssl.HttpsURLConnection",
+        "setHostnameVerifier",
+        Protection::CertificateValidation,
+    ),
+    (
+        "javax.net.ssl.HttpsURLConnection",
+        "setDefaultHostnameVerifier",
+        Protection::CertificateValidation,
+    ),
+    (
+        "com.scottyab.rootbeer.RootBeer",
+        "isRooted",
+        Protection::RootDetection,
+    ),
+    (
+        "com.google.android.gms.safetynet.SafetyNetClient",
+        "attest",
+        Protection::RootDetection,
+    ),
+    (
+        "com.google.android.play.core.integrity.IntegrityManager",
+        "requestIntegrityToken",
+        Protection::RootDetection,
+    ),
+    (
+        "android.os.Debug",
+        "isDebuggerConnected",
+        Protection::EmulatorDetection,
+    ),
+];
+
+/// String constants indicating root or emulator checks. Short indicators have to match
+/// exactly, longer ones can be contained in a string.
+const INDICATOR_STRINGS: [(&str, Protection); 24] = [
+    ("su", Protection::RootDetection),
+    ("/system/bin/su", Protection::RootDetection),
+    ("/system/xbin/su", Protection::RootDetection),
+    ("/sbin/su", Protection::RootDetection),
+    ("/system/app/Superuser.apk", Protection::RootDetection),
+    ("Superuser.apk", Protection::RootDetection),
+    ("busybox", Protection::RootDetection),
+    ("test-keys", Protection::RootDetection),
+    ("ro.debuggable", Protection::RootDetection),
+    ("ro.secure", Protection::RootDetection),
+    ("com.topjohnwu.magisk", Protection::RootDetection),
+    ("eu.chainfire.supersu", Protection::RootDetection),
+    ("com.noshufou.android.su", Protection::RootDetection),
+    ("com.koushikdutta.superuser", Protection::RootDetection),
+    ("de.robv.android.xposed", Protection::RootDetection),
+    ("goldfish", Protection::EmulatorDetection),
+    ("ranchu", Protection::EmulatorDetection),
+    ("google_sdk", Protection::EmulatorDetection),
+    ("sdk_gphone", Protection::EmulatorDetection),
+    ("Genymotion", Protection::EmulatorDetection),
+    ("/dev/qemu_pipe", Protection::EmulatorDetection),
+    ("/dev/socket/qemud", Protection::EmulatorDetection),
+    ("ro.kernel.qemu", Protection::EmulatorDetection),
+    ("generic_x86", Protection::EmulatorDetection),
+];
+
+/// Interfaces whose implementations validate certificates or host names: interface, methods
+/// doing the validation.
+const VALIDATION_INTERFACES: [(&str, &str); 3] = [
+    ("javax.net.ssl.X509TrustManager", "checkServerTrusted"),
+    (
+        "javax.net.ssl.X509ExtendedTrustManager",
+        "checkServerTrusted",
+    ),
+    ("javax.net.ssl.HostnameVerifier", "verify"),
+];
+
+fn find_indicator(value: &str) -> Option<Protection> {
+    INDICATOR_STRINGS
+        .iter()
+        .find(|(indicator, _)| {
+            value == *indicator || (indicator.len() > 4 && value.contains(indicator))
+        })
+        .map(|(_, protection)| *protection)
+}
+
+/// A method containing code related to a protection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtectionFinding {
+    pub protection: Protection,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+    pub description: String,
+}
+
+impl Method {
+    /// Checks whether a validation method neither calls anything nor throws, meaning that it
+    /// most likely accepts everything.
+    fn is_trivial_validation(&self) -> bool {
+        self.instructions.iter().all(|instruction| {
+            instruction
+                .get_command_family()
+                .is_none_or(|family| !family.starts_with("invoke-") && family != "throw")
+        })
+    }
+
+    /// Finds protection-related API calls and indicator strings in the method.
+    fn find_protections(&self, class_type: &Type) -> Vec<ProtectionFinding> {
+        let mut result = Vec::new();
+        for call in self.get_calls(class_type) {
+            let class_name = call.method.object_type.get_name();
+            let Some((_, _, protection)) = PROTECTION_CALLS.iter().find(|(class, method, _)| {
+                *class == class_name && *method == call.method.method_name
+            }) else {
+                continue;
+            };
+            let mut description = format!("calls {}.{}", class_name, call.method.method_name);
+            if let Some(ResultType::Literal(Literal::String(host))) = call.get_parameter(0) {
+                description.push_str(&format!(" for \"{host}\""));
+            }
+            result.push(ProtectionFinding {
+                protection: *protection,
+                location: self.get_signature(class_type),
+                line: call.line,
+                description,
+            });
+        }
+
+        let mut line = None;
+        for instruction in &self.instructions {
+            let parameters = match instruction {
+                Instruction::LineNumber(from, _) => {
+                    line = Some(*from);
+                    continue;
+                }
+                Instruction::Command { parameters, .. } => parameters,
+                _ => continue,
+            };
+            for parameter in parameters {
+                let CommandParameter::Literal(Literal::String(value)) = parameter else {
+                    continue;
+                };
+                if let Some(protection) = find_indicator(value) {
+                    result.push(ProtectionFinding {
+                        protection,
+                        location: self.get_signature(class_type),
+                        line,
+                        description: format!("uses \"{value}\""),
+                    });
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Code implementing certificate pinning, custom certificate validation as well as root and
+/// emulator detection. Findings list the exact methods, so that these can be patched.
+#[derive(Debug, Default)]
+pub struct ProtectionReport {
+    findings: Vec<ProtectionFinding>,
+}
+
+impl ProtectionReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        let validation_methods = VALIDATION_INTERFACES
+            .iter()
+            .filter(|(interface, _)| {
+                class
+                    .interfaces
+                    .iter()
+                    .chain(&class.super_class)
+                    .any(|implemented| implemented.get_name() == *interface)
+            })
+            .map(|(interface, method)| (*interface, *method))
+            .collect::<Vec<_>>();
+
+        for method in &class.methods {
+            for (interface, name) in &validation_methods {
+                if method.name == *name && !method.instructions.is_empty() {
+                    let mut description = format!("implements {interface}");
+                    if method.is_trivial_validation() {
+                        description.push_str(", accepts everything");
+                    }
+                    self.findings.push(ProtectionFinding {
+                        protection: Protection::CertificateValidation,
+                        location: method.get_signature(&class.class_type),
+                        line: None,
+                        description,
+                    });
+                }
+            }
+            self.findings
+                .append(&mut method.find_protections(&class.class_type));
+        }
+    }
+
+    pub fn get_findings(&self) -> &[ProtectionFinding] {
+        &self.findings
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut findings = self.findings.iter().collect::<Vec<_>>();
+        findings.sort_by_key(|finding| finding.protection);
+        let mut current = None;
+        for finding in findings {
+            if current != Some(finding.protection) {
+                current = Some(finding.protection);
+                writeln!(output, "{}:", finding.protection.get_title())?;
+            }
+            write!(output, "    <{}>", finding.location)?;
+            if let Some(line) = finding.line {
+                write!(output, ", line {line}")?;
+            }
+            writeln!(output, ": {}", finding.description)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn protection_report() -> Result<(), ParseErrorDisplayed> {
+        let client = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static build()V
+                    .locals 3
+                    .line 4
+                    new-instance v0, Lokhttp3/CertificatePinner$Builder;
+                    invoke-direct {v0}, Lokhttp3/CertificatePinner$Builder;-><init>()V
+                    const-string v1, "example.com"
+                    const/4 v2, 0x0
+                    invoke-virtual {v0, v1, v2}, Lokhttp3/CertificatePinner$Builder;->add(Ljava/lang/String;[Ljava/lang/String;)Lokhttp3/CertificatePinner$Builder;
+                    return-void
+                .end method
+
+                .method static isRooted()Z
+                    .locals 1
+                    .line 9
+                    const-string v0, "/system/xbin/su"
+                    const-string v0, "summary"
+                    const/4 v0, 0x0
+                    return v0
+                .end method
+            "#,
+        )?;
+        let trust_manager = read_optimized_class(
+            r#"
+                .class public Lb;
+                .super Ljava/lang/Object;
+                .implements Ljavax/net/ssl/X509TrustManager;
+
+                .method public checkServerTrusted([Ljava/security/cert/X509Certificate;Ljava/lang/String;)V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method public checkClientTrusted([Ljava/security/cert/X509Certificate;Ljava/lang/String;)V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut report = ProtectionReport::new();
+        report.add_class(&client);
+        report.add_class(&trust_manager);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"Certificate pinning and TLS configuration:
+    <void a.build()>, line 4: calls okhttp3.CertificatePinner$Builder.add for "example.com"
+Custom certificate validation:
+    <void b.checkServerTrusted(java.security.cert.X509Certificate[], java.lang.String)>: implements javax.net.ssl.X509TrustManager, accepts everything
+Root detection:
+    <bool a.isRooted()>, line 9: uses "/system/xbin/su"
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::r#type::{MethodSignature, Type};
+use crate::scope::matches_glob;
+
+/// Name a call is matched by, e.g. `com.example.Strings.decrypt(java.lang.String,int)`.
+pub(crate) fn get_call_name(method: &MethodSignature) -> String {
+    format!(
+        "{}.{}({})",
+        method.object_type,
+        method.method_name,
+        method
+            .call_signature
+            .parameter_types
+            .iter()
+            .map(Type::to_string)
+            .collect::<Vec<_>>()
+            .join(",")
+    )
+}
+
+/// A rule replacing calls to the methods matching a pattern by an expression.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RewriteRule {
+    /// Call name pattern where `*` stands for any sequence of characters
+    #[serde(rename = "match")]
+    pattern: String,
+    /// Expression replacing the call, `{0}`, `{1}` and so on stand for the call arguments in
+    /// the order of the method's parameters, preceded by the instance for non-static calls
+    #[serde(rename = "replace")]
+    replacement: String,
+}
+
+/// Contents of a rules file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RewriteRule>,
+}
+
+/// Rules replacing calls by more readable expressions in the output, e.g. calls to the string
+/// decryption function of an obfuscator by a `DECRYPT(…)` macro. Rules are read from a TOML
+/// file:
+///
+/// ```toml
+/// [[rule]]
+/// match = "com.example.a.b(java.lang.String)"
+/// replace = "DECRYPT({0})"
+/// ```
+///
+/// Files with the `.json` extension are read as JSON with the same structure instead:
+/// `{"rule": [{"match": "…", "replace": "…"}]}`. The first rule matching a call applies.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RewriteRules {
+    rules: Vec<RewriteRule>,
+}
+
+impl RewriteRules {
+    fn from_file(file: RulesFile) -> Self {
+        let rules = file
+            .rule
+            .into_iter()
+            .map(|rule| RewriteRule {
+                // Whitespace in parameter lists is insignificant
+                pattern: rule.pattern.split_whitespace().collect(),
+                ..rule
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Parses the contents of a TOML rules file.
+    pub fn parse(data: &str) -> Result<Self, String> {
+        toml::from_str(data)
+            .map(Self::from_file)
+            .map_err(|error| error.to_string())
+    }
+
+    /// Parses the contents of a JSON rules file.
+    pub fn parse_json(data: &str) -> Result<Self, String> {
+        serde_json::from_str(data)
+            .map(Self::from_file)
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed reading {}: {error}", path.display()))?;
+        let result = if path
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            Self::parse_json(&data)
+        } else {
+            Self::parse(&data)
+        };
+        result.map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    fn get_replacement(&self, method: &MethodSignature) -> Option<&str> {
+        let name = get_call_name(method);
+        self.rules
+            .iter()
+            .find(|rule| matches_glob(&rule.pattern, &name))
+            .map(|rule| rule.replacement.as_str())
+    }
+
+    /// Marks the calls in the class matching a rule to be written as the replacement
+    /// expression.
+    pub fn apply(&self, class: &mut Class) {
+        if self.rules.is_empty() {
+            return;
+        }
+        for method in &mut class.methods {
+            for instruction in &mut method.instructions {
+                let Instruction::Command {
+                    command,
+                    parameters,
+                } = instruction
+                else {
+                    continue;
+                };
+                if !command.starts_with("invoke-") {
+                    continue;
+                }
+                let Some(signature) = parameters.iter().find_map(|parameter| match parameter {
+                    CommandParameter::Method(signature) => Some(signature),
+                    _ => None,
+                }) else {
+                    continue;
+                };
+                if let Some(replacement) = self.get_replacement(signature) {
+                    parameters.push(CommandParameter::Rewrite(replacement.to_string()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn parse_rules() {
+        let rules = RewriteRules::parse(
+            r#"
+            # String decryption
+            [[rule]]
+            match = "com.example.a.b(java.lang.String, int)"
+            replace = "DECRYPT({0}, \"key\")" # the key is always the same
+
+            [[rule]]
+            replace = 'LOG({1})'
+            match = 'com.example.*.log(*)'
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            RewriteRules::parse_json(
+                r#"{"rule": [
+                    {"match": "com.example.a.b(java.lang.String, int)", "replace": "DECRYPT({0}, \"key\")"},
+                    {"match": "com.example.*.log(*)", "replace": "LOG({1})"}
+                ]}"#
+            )
+            .unwrap(),
+            rules
+        );
+        assert_eq!(
+            rules.rules,
+            vec![
+                RewriteRule {
+                    pattern: "com.example.a.b(java.lang.String,int)".to_string(),
+                    replacement: "DECRYPT({0}, \"key\")".to_string(),
+                },
+                RewriteRule {
+                    pattern: "com.example.*.log(*)".to_string(),
+                    replacement: "LOG({1})".to_string(),
+                },
+            ]
+        );
+
+        assert!(RewriteRules::parse("match = \"a.b()\"").is_err());
+        assert!(RewriteRules::parse("[[rule]]\nmatch = \"a.b()\"").is_err());
+        assert!(RewriteRules::parse("[[rule]]\nmatch = a.b()\nreplace = \"x\"").is_err());
+        assert!(RewriteRules::parse("[[rule]]\nmatches = \"a.b()\"").is_err());
+    }
+
+    #[test]
+    fn rewrite_calls() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static run(Lcom/example/Logger;)V
+                    .locals 3
+                    const-string v0, "encrypted"
+                    const/4 v1, 0x3
+                    invoke-static {v0, v1}, Lcom/example/a;->b(Ljava/lang/String;I)Ljava/lang/String;
+                    move-result-object v0
+                    invoke-virtual {p0, v0}, Lcom/example/Logger;->log(Ljava/lang/String;)V
+                    invoke-static {v0, v1}, Lcom/example/a;->b(Ljava/lang/String;J)Ljava/lang/String;
+                    const-wide/16 v1, 0x5
+                    invoke-static {v1, v2, v0}, Lcom/example/a;->c(JLjava/lang/String;)V
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let rules = RewriteRules::parse(
+            r#"
+            [[rule]]
+            match = "com.example.a.b(java.lang.String, int)"
+            replace = "DECRYPT({0}, {1})"
+
+            [[rule]]
+            match = "com.example.*.log(*)"
+            replace = "LOG({1})"
+
+            [[rule]]
+            match = "com.example.a.c(long, java.lang.String)"
+            replace = "C({1}, {0})"
+            "#,
+        )
+        .unwrap();
+        rules.apply(&mut class);
+
+        let mut output = Vec::new();
+        class.methods[0]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    static void run(com.example.Logger @p0)
+    {
+        $tmp0 = 0x3;
+        $tmp1 = DECRYPT("encrypted", $tmp0);
+        LOG($tmp1);
+        invoke-static <java.lang.String com.example.a.b(java.lang.String, long)>($tmp1, $tmp0);
+        C($tmp1, 0x5);
+        return;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
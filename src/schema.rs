@@ -0,0 +1,203 @@
+use std::borrow::Cow;
+use std::io::Write;
+
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema, SchemaGenerator};
+use serde::{Serialize, Serializer};
+
+use crate::callgraph::CallGraphFile;
+use crate::features::FeaturesRecord;
+use crate::format::ClassFile;
+use crate::instruction::DefinitionsFile;
+use crate::method::{DominatorsFile, InstructionsFile};
+use crate::suspicious::ApiReportFile;
+use crate::xrefs::XrefsFile;
+
+/// Machine-readable outputs with a published JSON Schema, generated from the types the output
+/// is serialized from. Each output carries a `schema_version` property, the schemas are also
+/// found in the `schemas` directory of the repository.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum JsonOutput {
+    /// Class files written by the decompile command with --format json
+    Class,
+    /// Method features written by the features command with --format ndjson
+    Features,
+    /// Dominator trees written by the dominators command with --format json
+    Dominators,
+    /// Instruction IDs written by the instructions command
+    Instructions,
+    /// Instruction definitions written by the definitions command
+    Definitions,
+    /// References written by the xref command with --format json
+    Xrefs,
+    /// Call graph written by the callgraph command with --format json
+    Callgraph,
+    /// Suspicious calls written by the api-report command with --format json
+    ApiReport,
+}
+
+impl JsonOutput {
+    pub const ALL: [Self; 8] = [
+        Self::Class,
+        Self::Features,
+        Self::Dominators,
+        Self::Instructions,
+        Self::Definitions,
+        Self::Xrefs,
+        Self::Callgraph,
+        Self::ApiReport,
+    ];
+
+    /// Version of the output format. It is increased with every change that could break
+    /// consumers, such as removing or renaming a property or changing its type. Adding
+    /// properties doesn't change the version.
+    pub const fn version(self) -> u32 {
+        match self {
+            Self::Class => 1,
+            Self::Features => 1,
+            Self::Dominators => 1,
+            Self::Instructions => 1,
+            Self::Definitions => 1,
+            Self::Xrefs => 1,
+            Self::Callgraph => 1,
+            Self::ApiReport => 1,
+        }
+    }
+
+    /// Name of the schema file in the `schemas` directory of the repository.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Class => "class.schema.json",
+            Self::Features => "features.schema.json",
+            Self::Dominators => "dominators.schema.json",
+            Self::Instructions => "instructions.schema.json",
+            Self::Definitions => "definitions.schema.json",
+            Self::Xrefs => "xrefs.schema.json",
+            Self::Callgraph => "callgraph.schema.json",
+            Self::ApiReport => "api-report.schema.json",
+        }
+    }
+
+    fn get_schema(self) -> Schema {
+        // Schemas describe the serialized output, so properties always written are required
+        let generator = SchemaSettings::draft2020_12()
+            .for_serialize()
+            .into_generator();
+        match self {
+            Self::Class => generator.into_root_schema_for::<ClassFile>(),
+            Self::Features => generator.into_root_schema_for::<FeaturesRecord<'_>>(),
+            Self::Dominators => generator.into_root_schema_for::<DominatorsFile<'_>>(),
+            Self::Instructions => generator.into_root_schema_for::<InstructionsFile>(),
+            Self::Definitions => generator.into_root_schema_for::<DefinitionsFile>(),
+            Self::Xrefs => generator.into_root_schema_for::<XrefsFile>(),
+            Self::Callgraph => generator.into_root_schema_for::<CallGraphFile>(),
+            Self::ApiReport => generator.into_root_schema_for::<ApiReportFile>(),
+        }
+    }
+
+    pub fn get_json_schema(self) -> String {
+        let schema = serde_json::to_string_pretty(&self.get_schema())
+            .expect("Schemas should always serialize");
+        format!("{schema}\n")
+    }
+}
+
+/// The `schema_version` property of an output, always serialized as `VERSION`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SchemaVersion<const VERSION: u32>;
+
+impl<const VERSION: u32> Serialize for SchemaVersion<VERSION> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(VERSION)
+    }
+}
+
+impl<const VERSION: u32> JsonSchema for SchemaVersion<VERSION> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("SchemaVersion{VERSION}").into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        schemars::json_schema!({ "const": VERSION })
+    }
+}
+
+/// Writes a value as JSON on a single line, e.g. for newline-delimited JSON.
+pub(crate) fn write_json(output: &mut dyn Write, value: &impl Serialize) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *output, value)?;
+    writeln!(output)
+}
+
+/// Writes a value as indented JSON.
+pub(crate) fn write_json_pretty(
+    output: &mut dyn Write,
+    value: &impl Serialize,
+) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(&mut *output, value)?;
+    writeln!(output)
+}
+
+#[cfg(test)]
+impl JsonOutput {
+    /// Panics with the validation errors if the JSON data doesn't match the schema.
+    pub(crate) fn assert_valid(self, json: &str) {
+        let schema = serde_json::to_value(self.get_schema()).unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+        let instance: serde_json::Value = serde_json::from_str(json).unwrap();
+        let errors = match schema.validate(&instance) {
+            Ok(()) => return,
+            Err(errors) => errors
+                .map(|error| format!("{}: {error}", error.instance_path))
+                .collect::<Vec<_>>(),
+        };
+        panic!(
+            "Output doesn't match {self:?} schema:\n{}",
+            errors.join("\n")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_versions() {
+        for output in JsonOutput::ALL {
+            let schema = serde_json::to_value(output.get_schema()).unwrap();
+            assert_eq!(
+                schema["properties"]["schema_version"]["const"],
+                output.version(),
+                "Schema of {output:?} doesn't match version {}",
+                output.version()
+            );
+        }
+    }
+
+    #[test]
+    fn published_schemas() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("schemas");
+        for output in JsonOutput::ALL {
+            let path = dir.join(output.file_name());
+            let published = std::fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                published == output.get_json_schema(),
+                "{} is outdated, regenerate it with the schema command",
+                path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_output() {
+        let result = std::panic::catch_unwind(|| {
+            JsonOutput::Definitions.assert_valid(r#"{"schema_version": 1, "definitions": [{}]}"#)
+        });
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use crate::r#type::Type;
+
+/// Checks whether a name matches a pattern where `*` stands for any sequence of characters.
+pub(crate) fn matches_glob(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// A scope rule: a class or package pattern, either included or excluded.
+#[derive(Debug, Clone, PartialEq)]
+struct ScopeRule {
+    include: bool,
+    pattern: String,
+}
+
+impl ScopeRule {
+    /// Checks whether a class name matches the pattern. Patterns without wildcards match the
+    /// class along with its inner classes.
+    fn matches(&self, class_name: &str) -> bool {
+        if self.pattern.contains('*') {
+            return matches_glob(&self.pattern, class_name);
+        }
+        class_name
+            .strip_prefix(&self.pattern)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('$'))
+    }
+}
+
+/// Classes to be processed, as listed in a scope file. Each line contains a class or package
+/// pattern like `com.example.Main` or `com.example.*`, lines starting with `!` exclude classes.
+/// Later lines take precedence. If all lines are exclusions, any other class is included.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Scope {
+    rules: Vec<ScopeRule>,
+}
+
+impl Scope {
+    /// Parses the contents of a scope file. Empty lines and lines starting with `#` are
+    /// ignored. Class names can be given in Java or Smali notation.
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (index, line) in data.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (include, pattern) = match line.strip_prefix('!') {
+                Some(pattern) => (false, pattern.trim()),
+                None => (true, line),
+            };
+            let pattern = match pattern.strip_prefix('L').and_then(|p| p.strip_suffix(';')) {
+                Some(pattern) => pattern.replace('/', "."),
+                None => pattern.to_string(),
+            };
+            if pattern.is_empty() || pattern.contains(char::is_whitespace) {
+                return Err(format!("Invalid pattern on line {}: {line}", index + 1));
+            }
+            rules.push(ScopeRule { include, pattern });
+        }
+        Ok(Self { rules })
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed reading {}: {error}", path.display()))?;
+        Self::parse(&data).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    pub fn contains(&self, class_name: &str) -> bool {
+        match self
+            .rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(class_name))
+        {
+            Some(rule) => rule.include,
+            None => self.rules.iter().all(|rule| !rule.include),
+        }
+    }
+
+    pub fn contains_type(&self, class_type: &Type) -> bool {
+        self.contains(&class_type.get_name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scope_rules() {
+        let scope = Scope::parse(
+            "
+            # Engagement scope
+            com.example.*
+            !com.example.thirdparty.*
+            com.example.thirdparty.Wrapper
+            Lorg/partner/Api;
+            ",
+        )
+        .unwrap();
+        assert!(scope.contains("com.example.Main"));
+        assert!(scope.contains("com.example.ui.Screen$1"));
+        assert!(!scope.contains("com.example.thirdparty.Library"));
+        assert!(scope.contains("com.example.thirdparty.Wrapper"));
+        assert!(scope.contains("com.example.thirdparty.Wrapper$Inner"));
+        assert!(!scope.contains("com.example.thirdparty.WrapperFactory"));
+        assert!(scope.contains("org.partner.Api"));
+        assert!(!scope.contains("org.partner.Other"));
+        assert!(!scope.contains("androidx.core.Foo"));
+
+        let exclusions = Scope::parse("!androidx.*\n!kotlin.*").unwrap();
+        assert!(exclusions.contains("com.example.Main"));
+        assert!(!exclusions.contains("kotlin.Unit"));
+
+        assert!(Scope::default().contains("com.example.Main"));
+        assert!(Scope::parse("!").is_err());
+    }
+
+    #[test]
+    fn glob() {
+        assert!(matches_glob("a*c", "abc"));
+        assert!(matches_glob("a*", "a"));
+        assert!(matches_glob("*.R$*", "com.example.R$string"));
+        assert!(!matches_glob("*.R$*", "com.example.Rx"));
+        assert!(!matches_glob("a*b*c", "acb"));
+    }
+}
@@ -0,0 +1,245 @@
+use std::fmt::{Debug, Formatter};
+use std::io::Write;
+
+use crate::annotation::Annotation;
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::literal::Literal;
+use crate::r#type::FieldSignature;
+use crate::rewrite_rules::get_call_name;
+use crate::scope::matches_glob;
+
+/// Criterion code is matched by in a structural search. Patterns can contain `*` standing for
+/// any sequence of characters.
+pub enum Criterion<'a> {
+    /// Calls of methods whose name like `com.example.Main.run(int,java.lang.String)` matches
+    /// the pattern
+    Call(&'a str),
+    /// Reads and writes of fields whose name like `com.example.Main.count` matches the pattern
+    Field(&'a str),
+    /// Literals accepted by the filter, string literals are passed in without quotes
+    Literal(&'a dyn Fn(&str) -> bool),
+    /// Annotations of classes, fields, methods and parameters with a type matching the
+    /// pattern
+    Annotation(&'a str),
+}
+
+impl Debug for Criterion<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Call(pattern) => f.debug_tuple("Call").field(pattern).finish(),
+            Self::Field(pattern) => f.debug_tuple("Field").field(pattern).finish(),
+            Self::Literal(_) => f.write_str("Literal(..)"),
+            Self::Annotation(pattern) => f.debug_tuple("Annotation").field(pattern).finish(),
+        }
+    }
+}
+
+/// A declaration or command matching a search criterion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    /// Class, field or method containing the match
+    pub location: String,
+    pub line: Option<i64>,
+    pub description: String,
+}
+
+fn get_field_name(field: &FieldSignature) -> String {
+    format!("{}.{}", field.object_type, field.field_name)
+}
+
+fn literal_matches(literal: &Literal, filter: &dyn Fn(&str) -> bool) -> bool {
+    match literal {
+        Literal::String(value) => filter(value),
+        literal => filter(&literal.to_string()),
+    }
+}
+
+fn parameter_matches(parameter: &CommandParameter, criterion: &Criterion<'_>) -> bool {
+    match (parameter, criterion) {
+        (CommandParameter::Method(method), Criterion::Call(pattern)) => {
+            matches_glob(pattern, &get_call_name(method))
+        }
+        (CommandParameter::Field(field), Criterion::Field(pattern)) => {
+            matches_glob(pattern, &get_field_name(field))
+        }
+        (CommandParameter::Literal(literal), Criterion::Literal(filter)) => {
+            literal_matches(literal, *filter)
+        }
+        _ => false,
+    }
+}
+
+/// Lists the annotations of a declaration matching an annotation criterion.
+fn find_annotations(
+    location: &str,
+    annotations: &[Annotation],
+    target: &str,
+    criteria: &[Criterion<'_>],
+) -> Vec<Hit> {
+    annotations
+        .iter()
+        .filter(|annotation| {
+            criteria.iter().any(|criterion| match criterion {
+                Criterion::Annotation(pattern) => {
+                    matches_glob(pattern, &annotation.annotation_type.get_name())
+                }
+                _ => false,
+            })
+        })
+        .map(|annotation| Hit {
+            location: location.to_string(),
+            line: None,
+            description: format!("@{} on {target}", annotation.annotation_type),
+        })
+        .collect()
+}
+
+/// Searches a class for declarations and commands matching any of the criteria, in the order
+/// they appear in the class.
+pub fn search(class: &Class, criteria: &[Criterion<'_>]) -> Vec<Hit> {
+    let mut result = find_annotations(
+        &class.class_type.to_string(),
+        &class.annotations,
+        "class",
+        criteria,
+    );
+    for field in &class.fields {
+        let signature = FieldSignature {
+            object_type: class.class_type.clone(),
+            field_name: field.name.clone(),
+            field_type: field.field_type.clone(),
+        };
+        result.extend(find_annotations(
+            &signature.to_string(),
+            &field.annotations,
+            "field",
+            criteria,
+        ));
+    }
+
+    for method in &class.methods {
+        let location = method.get_signature(&class.class_type).to_string();
+        result.extend(find_annotations(
+            &location,
+            &method.annotations,
+            "method",
+            criteria,
+        ));
+        for (index, parameter) in method.parameters.iter().enumerate() {
+            result.extend(find_annotations(
+                &location,
+                &parameter.annotations,
+                &format!("parameter {}", index + 1),
+                criteria,
+            ));
+        }
+
+        let mut line = None;
+        for instruction in &method.instructions {
+            if let Instruction::LineNumber(from, _) = instruction {
+                line = Some(*from);
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            for parameter in parameters {
+                if !criteria
+                    .iter()
+                    .any(|criterion| parameter_matches(parameter, criterion))
+                {
+                    continue;
+                }
+                let operand = match parameter {
+                    CommandParameter::Method(method) => method.to_string(),
+                    CommandParameter::Field(field) => field.to_string(),
+                    CommandParameter::Literal(literal) => literal.to_string(),
+                    _ => continue,
+                };
+                result.push(Hit {
+                    location: location.clone(),
+                    line,
+                    description: format!("{command} {operand}"),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Writes a hit as `<location>, line N: description`.
+pub fn write_hit(output: &mut dyn Write, hit: &Hit) -> std::io::Result<()> {
+    write!(output, "<{}>", hit.location)?;
+    if let Some(line) = hit.line {
+        write!(output, ", line {line}")?;
+    }
+    writeln!(output, ": {}", hit.description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn structural_search() -> Result<(), ParseErrorDisplayed> {
+        let class = read_class(
+            r#"
+                .class public Lcom/example/Main;
+                .super Ljava/lang/Object;
+
+                .annotation runtime Lcom/example/Marker;
+                .end annotation
+
+                .field private token:Ljava/lang/String;
+                    .annotation runtime Lcom/example/Secret;
+                    .end annotation
+                .end field
+
+                .method public login(Ljava/lang/String;)V
+                    .locals 1
+                    .param p1, "password"    # Ljava/lang/String;
+                        .annotation runtime Lcom/example/Secret;
+                        .end annotation
+                    .end param
+                    .line 12
+                    const-string v0, "https://example.com/login"
+                    invoke-static {v0, p1}, Lcom/example/Http;->post(Ljava/lang/String;Ljava/lang/String;)V
+                    .line 13
+                    iput-object p1, p0, Lcom/example/Main;->token:Ljava/lang/String;
+                    const-string v0, "done"
+                    invoke-static {v0}, Landroid/util/Log;->d(Ljava/lang/String;)I
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let urls = |value: &str| value.starts_with("https://");
+        let criteria = [
+            Criterion::Call("com.example.*"),
+            Criterion::Field("*.token"),
+            Criterion::Literal(&urls),
+            Criterion::Annotation("com.example.Secret"),
+        ];
+        let mut output = Vec::new();
+        for hit in search(&class, &criteria) {
+            write_hit(&mut output, &hit).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"<java.lang.String com.example.Main.token>: @com.example.Secret on field
+<void com.example.Main.login(java.lang.String)>: @com.example.Secret on parameter 1
+<void com.example.Main.login(java.lang.String)>, line 12: const-string "https://example.com/login"
+<void com.example.Main.login(java.lang.String)>, line 12: invoke-static void com.example.Http.post(java.lang.String, java.lang.String)
+<void com.example.Main.login(java.lang.String)>, line 13: iput-object java.lang.String com.example.Main.token
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, Register};
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+
+/// Methods executing SQL code: class, method name (all overloads), index of the SQL parameter.
+const SQL_SINKS: [(&str, &str, usize); 8] = [
+    ("android.database.sqlite.SQLiteDatabase", "rawQuery", 0),
+    ("android.database.sqlite.SQLiteDatabase", "execSQL", 0),
+    (
+        "android.database.sqlite.SQLiteDatabase",
+        "compileStatement",
+        0,
+    ),
+    (
+        "android.database.sqlite.SQLiteDatabase",
+        "rawQueryWithFactory",
+        1,
+    ),
+    ("androidx.sqlite.db.SupportSQLiteDatabase", "query", 0),
+    ("androidx.sqlite.db.SupportSQLiteDatabase", "execSQL", 0),
+    (
+        "androidx.sqlite.db.SupportSQLiteDatabase",
+        "compileStatement",
+        0,
+    ),
+    ("net.sqlcipher.database.SQLiteDatabase", "rawQuery", 0),
+];
+
+/// Methods returning user-controlled data: class, method name prefix.
+const USER_INPUT_SOURCES: [(&str, &str); 6] = [
+    ("android.content.Intent", "get"),
+    ("android.os.Bundle", "get"),
+    ("android.net.Uri", "get"),
+    ("android.widget.EditText", "getText"),
+    ("android.widget.TextView", "getText"),
+    ("android.content.ClipData$Item", "getText"),
+];
+
+const STRING_BUILDER_CLASSES: [&str; 2] = ["java.lang.StringBuilder", "java.lang.StringBuffer"];
+
+/// Part of a reconstructed query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryPart {
+    Text(String),
+    /// A value not known statically, named after its type or origin
+    Variable {
+        name: String,
+        user_controlled: bool,
+    },
+}
+
+/// Query template passed to an SQL API, with placeholders for unknown values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlQuery {
+    pub parts: Vec<QueryPart>,
+    /// The method executing the query
+    pub api: String,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+impl SqlQuery {
+    /// Checks whether user-controlled data is concatenated into the query.
+    pub fn is_user_controlled(&self) -> bool {
+        self.parts.iter().any(|part| {
+            matches!(
+                part,
+                QueryPart::Variable {
+                    user_controlled: true,
+                    ..
+                }
+            )
+        })
+    }
+
+    /// Returns the query with placeholders like `{int}` for unknown values.
+    pub fn get_template(&self) -> String {
+        let mut result = String::new();
+        for part in &self.parts {
+            match part {
+                QueryPart::Text(text) => result.push_str(text),
+                QueryPart::Variable { name, .. } => {
+                    result.push('{');
+                    result.push_str(name);
+                    result.push('}');
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Value of a register while reconstructing strings.
+#[derive(Debug, Clone)]
+enum StringValue {
+    Parts(Vec<QueryPart>),
+    /// A `StringBuilder` instance, index into the list of builders
+    Builder(usize),
+}
+
+fn variable(name: String, user_controlled: bool) -> Vec<QueryPart> {
+    vec![QueryPart::Variable {
+        name,
+        user_controlled,
+    }]
+}
+
+fn is_string_type(value_type: &Type) -> bool {
+    matches!(
+        value_type.get_name().as_ref(),
+        "java.lang.String" | "java.lang.CharSequence"
+    )
+}
+
+fn get_simple_name(value_type: &Type) -> String {
+    let name = value_type.to_string();
+    match name.rsplit_once('.') {
+        Some((_, name)) => name.to_string(),
+        None => name,
+    }
+}
+
+/// Returns the known parts of the string in a register.
+fn get_parts(
+    state: &HashMap<Register, StringValue>,
+    builders: &[Vec<QueryPart>],
+    register: Option<&Register>,
+) -> Option<Vec<QueryPart>> {
+    match register.and_then(|register| state.get(register))? {
+        StringValue::Parts(parts) => Some(parts.clone()),
+        StringValue::Builder(index) => Some(builders[*index].clone()),
+    }
+}
+
+impl Method {
+    /// Reconstructs the SQL queries passed to database APIs from string constants, string
+    /// concatenation and `StringBuilder` calls. Values are only tracked within straight code,
+    /// labels reset the state.
+    fn find_sql_queries(&self, class_type: &Type) -> Vec<SqlQuery> {
+        let mut result = Vec::new();
+        let mut state: HashMap<Register, StringValue> = HashMap::new();
+        let mut builders: Vec<Vec<QueryPart>> = Vec::new();
+        let mut pending = None;
+        let mut line = None;
+
+        // String parameters are named like in Jimple
+        let mut register = usize::from(!self.visibility.contains(&AccessFlag::Static));
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            if is_string_type(&parameter.parameter_type) {
+                state.insert(
+                    Register::Parameter(register),
+                    StringValue::Parts(variable(format!("parameter{index}"), false)),
+                );
+            }
+            register += parameter.parameter_type.register_count();
+        }
+
+        for instruction in &self.instructions {
+            let (command, parameters) = match instruction {
+                Instruction::LineNumber(from, _) => {
+                    line = Some(*from);
+                    continue;
+                }
+                Instruction::Label(_) | Instruction::Raw(_) => {
+                    state.clear();
+                    continue;
+                }
+                Instruction::Command {
+                    command,
+                    parameters,
+                } => (command, parameters),
+                _ => continue,
+            };
+
+            let family = instruction.get_command_family().unwrap_or_default();
+            let mut value = None;
+            if let Some((method, arguments)) = instruction.get_call_arguments() {
+                let class_name = method.object_type.get_name();
+                let name = method.method_name.as_str();
+                let is_static = command.starts_with("invoke-static");
+                let object = if is_static { None } else { arguments.first() };
+                let parameter = |index: usize| arguments.get(index + usize::from(!is_static));
+                let parameter_type = method.call_signature.parameter_types.first();
+
+                if let Some((_, _, index)) = SQL_SINKS
+                    .iter()
+                    .find(|(class, method, _)| *class == class_name && *method == name)
+                {
+                    if let Some(parts) = get_parts(&state, &builders, parameter(*index)) {
+                        result.push(SqlQuery {
+                            parts,
+                            api: format!("{}.{name}", get_simple_name(&method.object_type)),
+                            location: self.get_signature(class_type),
+                            line,
+                        });
+                    }
+                }
+
+                let builder = match object.and_then(|register| state.get(register)) {
+                    Some(StringValue::Builder(index)) => Some(*index),
+                    _ => None,
+                };
+                if STRING_BUILDER_CLASSES.contains(&class_name.as_ref()) {
+                    match (name, builder, parameter_type) {
+                        ("<init>", Some(builder), Some(parameter_type))
+                            if is_string_type(parameter_type) =>
+                        {
+                            builders[builder] = get_parts(&state, &builders, parameter(0))
+                                .unwrap_or_else(|| variable(parameter_type.to_string(), false));
+                        }
+                        ("append", Some(builder), Some(parameter_type)) => {
+                            let parts = get_parts(&state, &builders, parameter(0))
+                                .unwrap_or_else(|| variable(parameter_type.to_string(), false));
+                            builders[builder].extend(parts);
+                            value = Some(StringValue::Builder(builder));
+                        }
+                        ("toString", Some(builder), _) => {
+                            value = Some(StringValue::Parts(builders[builder].clone()));
+                        }
+                        _ => (),
+                    }
+                } else if let Some(mut parts) = get_parts(&state, &builders, object) {
+                    // Calls like trim() or toString() keep the value recognizable
+                    if name == "concat" {
+                        parts
+                            .extend(get_parts(&state, &builders, parameter(0)).unwrap_or_else(
+                                || variable("java.lang.String".to_string(), false),
+                            ));
+                    }
+                    if is_string_type(&method.call_signature.return_type) {
+                        value = Some(StringValue::Parts(parts));
+                    }
+                } else if USER_INPUT_SOURCES
+                    .iter()
+                    .any(|(class, prefix)| *class == class_name && name.starts_with(prefix))
+                {
+                    value = Some(StringValue::Parts(variable(
+                        format!("{}.{name}()", get_simple_name(&method.object_type)),
+                        true,
+                    )));
+                } else if class_name == "java.lang.String" && name == "valueOf" {
+                    value = get_parts(&state, &builders, parameter(0)).map(StringValue::Parts);
+                }
+            } else {
+                match (family, parameters.get(1)) {
+                    ("const-string", Some(CommandParameter::Literal(Literal::String(text)))) => {
+                        value = Some(StringValue::Parts(vec![QueryPart::Text(text.to_string())]));
+                    }
+                    ("new-instance", Some(CommandParameter::Type(object_type)))
+                        if STRING_BUILDER_CLASSES.contains(&object_type.get_name().as_ref()) =>
+                    {
+                        builders.push(Vec::new());
+                        value = Some(StringValue::Builder(builders.len() - 1));
+                    }
+                    ("move-object", _) => {
+                        value = instruction
+                            .get_used_registers()
+                            .first()
+                            .and_then(|register| state.get(register))
+                            .cloned();
+                    }
+                    ("move-result", _) => value = pending.take(),
+                    _ => (),
+                }
+            }
+
+            let Some(register) = instruction.get_result_register() else {
+                // The result might be picked up by a move-result instruction
+                if family.starts_with("invoke-") {
+                    pending = value;
+                }
+                continue;
+            };
+            pending = None;
+            match value {
+                Some(value) => state.insert(register.clone(), value),
+                None => state.remove(register),
+            };
+        }
+        result
+    }
+}
+
+/// SQL queries reconstructed from the string operations preceding database calls.
+#[derive(Debug, Default)]
+pub struct SqlReport {
+    queries: Vec<SqlQuery>,
+}
+
+impl SqlReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            self.queries
+                .append(&mut method.find_sql_queries(&class.class_type));
+        }
+    }
+
+    pub fn get_queries(&self) -> &[SqlQuery] {
+        &self.queries
+    }
+
+    /// Lists the queries, those concatenating user-controlled data first.
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let mut queries = self.queries.iter().collect::<Vec<_>>();
+        queries.sort_by_key(|query| !query.is_user_controlled());
+        for query in queries {
+            write!(output, "{} in <{}>", query.api, query.location)?;
+            if let Some(line) = query.line {
+                write!(output, ", line {line}")?;
+            }
+            if query.is_user_controlled() {
+                write!(output, " (user-controlled data concatenated)")?;
+            }
+            writeln!(output)?;
+            writeln!(output, "    \"{}\"", query.get_template())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn sql_report() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static find(Landroid/database/sqlite/SQLiteDatabase;Landroid/content/Intent;I)V
+                    .locals 3
+                    .line 7
+                    new-instance v0, Ljava/lang/StringBuilder;
+                    const-string v1, "SELECT * FROM users WHERE name = '"
+                    invoke-direct {v0, v1}, Ljava/lang/StringBuilder;-><init>(Ljava/lang/String;)V
+                    const-string v1, "name"
+                    invoke-virtual {p1, v1}, Landroid/content/Intent;->getStringExtra(Ljava/lang/String;)Ljava/lang/String;
+                    move-result-object v1
+                    invoke-virtual {v0, v1}, Ljava/lang/StringBuilder;->append(Ljava/lang/String;)Ljava/lang/StringBuilder;
+                    move-result-object v0
+                    const-string v1, "' LIMIT "
+                    invoke-virtual {v0, v1}, Ljava/lang/StringBuilder;->append(Ljava/lang/String;)Ljava/lang/StringBuilder;
+                    invoke-virtual {v0, p2}, Ljava/lang/StringBuilder;->append(I)Ljava/lang/StringBuilder;
+                    invoke-virtual {v0}, Ljava/lang/StringBuilder;->toString()Ljava/lang/String;
+                    move-result-object v0
+                    const/4 v1, 0x0
+                    invoke-virtual {p0, v0, v1}, Landroid/database/sqlite/SQLiteDatabase;->rawQuery(Ljava/lang/String;[Ljava/lang/String;)Landroid/database/Cursor;
+                    .line 8
+                    const-string v0, "DELETE FROM "
+                    invoke-virtual {v0, v2}, Ljava/lang/String;->concat(Ljava/lang/String;)Ljava/lang/String;
+                    move-result-object v0
+                    invoke-virtual {p0, v0}, Landroid/database/sqlite/SQLiteDatabase;->execSQL(Ljava/lang/String;)V
+                    return-void
+                .end method
+
+                .method static drop(Landroid/database/sqlite/SQLiteDatabase;Ljava/lang/String;)V
+                    .locals 1
+                    const-string v0, "DROP TABLE "
+                    invoke-virtual {v0, p1}, Ljava/lang/String;->concat(Ljava/lang/String;)Ljava/lang/String;
+                    move-result-object v0
+                    invoke-virtual {p0, v0}, Landroid/database/sqlite/SQLiteDatabase;->execSQL(Ljava/lang/String;)V
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let mut report = SqlReport::new();
+        report.add_class(&class);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"SQLiteDatabase.rawQuery in <void a.find(android.database.sqlite.SQLiteDatabase, android.content.Intent, int)>, line 7 (user-controlled data concatenated)
+    "SELECT * FROM users WHERE name = '{Intent.getStringExtra()}' LIMIT {int}"
+SQLiteDatabase.execSQL in <void a.find(android.database.sqlite.SQLiteDatabase, android.content.Intent, int)>, line 8
+    "DELETE FROM {java.lang.String}"
+SQLiteDatabase.execSQL in <void a.drop(android.database.sqlite.SQLiteDatabase, java.lang.String)>
+    "DROP TABLE {parameter1}"
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+
+use crate::instruction::Instruction;
+use crate::method::Method;
+
+/// Number of lines shown before the code belonging to a stack frame's line.
+const CONTEXT_LINES: usize = 3;
+
+/// A single `at class.method(Source:line)` line of a Java stack trace.
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    pub class_name: String,
+    pub method_name: String,
+    pub line: Option<i64>,
+}
+
+impl Frame {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim().strip_prefix("at ")?;
+        let (location, source) = line.split_once('(')?;
+        let (class_name, method_name) = location.trim().rsplit_once('.')?;
+        let line = source
+            .strip_suffix(')')?
+            .rsplit_once(':')
+            .and_then(|(_, line)| line.parse().ok());
+        Some(Self {
+            class_name: class_name.to_string(),
+            method_name: method_name.to_string(),
+            line,
+        })
+    }
+}
+
+/// Method entry of an R8/ProGuard mapping file.
+#[derive(Debug)]
+struct MethodMapping {
+    original_name: String,
+    obfuscated_lines: Option<(i64, i64)>,
+    original_lines: Option<(i64, i64)>,
+}
+
+impl MethodMapping {
+    fn matches(&self, line: Option<i64>) -> bool {
+        match (self.obfuscated_lines, line) {
+            (Some((start, end)), Some(line)) => start <= line && line <= end,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+
+    fn map_line(&self, line: i64) -> i64 {
+        match (self.obfuscated_lines, self.original_lines) {
+            (Some((start, end)), Some((original_start, original_end)))
+                if end - start == original_end - original_start =>
+            {
+                original_start + (line - start)
+            }
+            (_, Some((original_start, _))) => original_start,
+            _ => line,
+        }
+    }
+}
+
+/// Class entry of an R8/ProGuard mapping file, keyed by the obfuscated name.
+#[derive(Debug)]
+struct ClassMapping {
+    original_name: String,
+    methods: HashMap<String, Vec<MethodMapping>>,
+}
+
+/// Mapping from obfuscated names to original names, as produced by R8 or ProGuard.
+#[derive(Debug, Default)]
+pub struct Mapping {
+    classes: HashMap<String, ClassMapping>,
+}
+
+fn parse_line_range(range: &str) -> Option<(i64, i64)> {
+    match range.split_once(':') {
+        Some((start, end)) => Some((start.parse().ok()?, end.parse().ok()?)),
+        None => {
+            let line = range.parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+impl Mapping {
+    pub fn parse(data: &str) -> Self {
+        let mut classes = HashMap::new();
+        let mut current = None;
+        for line in data.lines() {
+            if line.trim_start().starts_with('#') {
+                continue;
+            }
+
+            let Some((original, obfuscated)) = line.split_once(" -> ") else {
+                continue;
+            };
+            let obfuscated = obfuscated.trim();
+
+            if !line.starts_with(char::is_whitespace) {
+                let obfuscated = obfuscated.trim_end_matches(':').to_string();
+                classes.insert(
+                    obfuscated.clone(),
+                    ClassMapping {
+                        original_name: original.trim().to_string(),
+                        methods: HashMap::new(),
+                    },
+                );
+                current = Some(obfuscated);
+                continue;
+            }
+
+            // Method entries look like `1:3:void name(int):10:12 -> a`, fields have no parentheses
+            let Some(class) = current.as_ref().and_then(|name| classes.get_mut(name)) else {
+                continue;
+            };
+            let original = original.trim();
+            let Some((before, after)) = original.split_once('(') else {
+                continue;
+            };
+            let (obfuscated_lines, declaration) = match before.rsplit_once(':') {
+                Some((range, declaration)) => (parse_line_range(range), declaration),
+                None => (None, before),
+            };
+            let Some((_, original_name)) = declaration.rsplit_once(' ') else {
+                continue;
+            };
+            let original_lines = after
+                .split_once(')')
+                .and_then(|(_, lines)| lines.strip_prefix(':'))
+                .and_then(parse_line_range);
+
+            // Methods moved from other classes by R8 have fully qualified names, keep the name only
+            let original_name = original_name
+                .rsplit_once('.')
+                .map(|(_, name)| name)
+                .unwrap_or(original_name);
+
+            class
+                .methods
+                .entry(obfuscated.to_string())
+                .or_default()
+                .push(MethodMapping {
+                    original_name: original_name.to_string(),
+                    obfuscated_lines,
+                    original_lines,
+                });
+        }
+        Self { classes }
+    }
+
+    /// Translates a frame into original class name, method name and line number, where known.
+    pub fn deobfuscate(&self, frame: &Frame) -> Option<Frame> {
+        let class = self.classes.get(&frame.class_name)?;
+        let method = class
+            .methods
+            .get(&frame.method_name)
+            .and_then(|methods| methods.iter().find(|method| method.matches(frame.line)));
+        Some(Frame {
+            class_name: class.original_name.clone(),
+            method_name: method
+                .map(|method| method.original_name.clone())
+                .unwrap_or_else(|| frame.method_name.clone()),
+            line: match (method, frame.line) {
+                (Some(method), Some(line)) => Some(method.map_line(line)),
+                _ => frame.line,
+            },
+        })
+    }
+}
+
+impl Method {
+    /// Determines the range of source lines covered by the method's debug information.
+    pub fn get_line_range(&self) -> Option<(i64, i64)> {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::LineNumber(from, to) => Some((*from, *to)),
+                _ => None,
+            })
+            .reduce(|(min, max), (from, to)| (min.min(from), max.max(to)))
+    }
+}
+
+/// Checks whether a line of Jimple code is a line number marker covering the given line.
+fn is_line_marker(code: &str, line: Option<i64>) -> bool {
+    let Some(range) = code.trim().strip_prefix("// line ") else {
+        return false;
+    };
+    let Some(line) = line else {
+        return true;
+    };
+    match parse_line_range(&range.replacen('-', ":", 1)) {
+        Some((from, to)) => from <= line && line <= to,
+        None => false,
+    }
+}
+
+/// Extracts the code belonging to a source line from a method's Jimple code, along with a few
+/// lines of context. Lines belonging to the source line are marked with `>`.
+pub fn excerpt(code: &str, line: i64) -> Option<String> {
+    let lines = code.lines().collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|code| is_line_marker(code, Some(line)))?;
+    let end = lines
+        .iter()
+        .skip(start + 1)
+        .position(|code| is_line_marker(code, None))
+        .map(|position| start + 1 + position)
+        .unwrap_or(lines.len());
+
+    let mut result = String::new();
+    for (i, code) in lines
+        .iter()
+        .enumerate()
+        .take(end)
+        .skip(start.saturating_sub(CONTEXT_LINES))
+    {
+        let marker = if i >= start { '>' } else { ' ' };
+        result.push(marker);
+        result.push_str(code);
+        result.push('\n');
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_frame() {
+        assert_eq!(
+            Frame::parse("\tat com.example.Main.run(Main.java:42)"),
+            Some(Frame {
+                class_name: "com.example.Main".to_string(),
+                method_name: "run".to_string(),
+                line: Some(42),
+            })
+        );
+        assert_eq!(
+            Frame::parse("    at a.b.c(Unknown Source)"),
+            Some(Frame {
+                class_name: "a.b".to_string(),
+                method_name: "c".to_string(),
+                line: None,
+            })
+        );
+        assert_eq!(Frame::parse("Caused by: java.lang.Exception"), None);
+    }
+
+    #[test]
+    fn deobfuscate() {
+        let mapping = Mapping::parse(
+            r#"
+# compiler: R8
+com.example.Main -> a.b:
+    int counter -> a
+    1:3:void run(int):10:12 -> c
+    4:4:void helper():20:20 -> c
+    void other() -> d
+"#,
+        );
+        let frame = |method_name: &str, line| Frame {
+            class_name: "a.b".to_string(),
+            method_name: method_name.to_string(),
+            line,
+        };
+
+        assert_eq!(
+            mapping.deobfuscate(&frame("c", Some(2))),
+            Some(Frame {
+                class_name: "com.example.Main".to_string(),
+                method_name: "run".to_string(),
+                line: Some(11),
+            })
+        );
+        assert_eq!(
+            mapping.deobfuscate(&frame("c", Some(4))),
+            Some(Frame {
+                class_name: "com.example.Main".to_string(),
+                method_name: "helper".to_string(),
+                line: Some(20),
+            })
+        );
+        assert_eq!(
+            mapping.deobfuscate(&frame("d", None)),
+            Some(Frame {
+                class_name: "com.example.Main".to_string(),
+                method_name: "other".to_string(),
+                line: None,
+            })
+        );
+        assert_eq!(
+            mapping.deobfuscate(&Frame {
+                class_name: "x.y".to_string(),
+                method_name: "z".to_string(),
+                line: None,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn excerpt_line() {
+        let code = r#"    void run()
+    {
+        // line 1
+        v0 = 0x1;
+        // line 2-3
+        v1 = 0x2;
+        v2 = 0x3;
+        // line 4
+        return;
+    }"#;
+        assert_eq!(
+            excerpt(code, 3),
+            Some(
+                r#"     {
+         // line 1
+         v0 = 0x1;
+>        // line 2-3
+>        v1 = 0x2;
+>        v2 = 0x3;
+"#
+                .to_string()
+            )
+        );
+        assert_eq!(excerpt(code, 5), None);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::instruction::Instruction;
+use crate::method::Complexity;
+
+/// Counts of the program's classes, members and instructions, giving an impression of its size
+/// and obfuscation before looking at the code.
+#[derive(Debug, Default)]
+pub struct ProgramStats {
+    classes: usize,
+    methods: usize,
+    fields: usize,
+    instructions: usize,
+    /// Number of commands per opcode
+    opcodes: BTreeMap<String, usize>,
+    class_flags: BTreeMap<String, usize>,
+    method_flags: BTreeMap<String, usize>,
+    field_flags: BTreeMap<String, usize>,
+    /// Signatures of all methods with code along with their number of commands
+    method_sizes: Vec<(String, usize)>,
+    /// Signatures of all methods with code along with their complexity after optimization
+    method_complexities: Vec<(String, Complexity)>,
+}
+
+fn count_flags(counts: &mut BTreeMap<String, usize>, flags: &[AccessFlag]) {
+    for flag in flags {
+        *counts.entry(flag.to_string()).or_default() += 1;
+    }
+}
+
+/// Sorts counts with the most frequent entries first, entries with equal counts by name.
+fn sort_counts<'a>(
+    counts: impl IntoIterator<Item = (&'a String, &'a usize)>,
+) -> Vec<(&'a str, usize)> {
+    let mut result = counts
+        .into_iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect::<Vec<_>>();
+    result.sort_by(|(name1, count1), (name2, count2)| count2.cmp(count1).then(name1.cmp(name2)));
+    result
+}
+
+fn write_counts(
+    output: &mut dyn Write,
+    title: &str,
+    counts: &BTreeMap<String, usize>,
+) -> Result<(), std::io::Error> {
+    writeln!(output)?;
+    writeln!(output, "{title}:")?;
+    for (name, count) in sort_counts(counts) {
+        writeln!(output, "{count:>8} {name}")?;
+    }
+    Ok(())
+}
+
+impl ProgramStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        self.classes += 1;
+        count_flags(&mut self.class_flags, &class.access_flags);
+
+        self.fields += class.fields.len();
+        for field in &class.fields {
+            count_flags(&mut self.field_flags, &field.visibility);
+        }
+
+        self.methods += class.methods.len();
+        for method in &class.methods {
+            count_flags(&mut self.method_flags, &method.visibility);
+            let mut size = 0;
+            for instruction in &method.instructions {
+                if let Instruction::Command { command, .. } = instruction {
+                    size += 1;
+                    *self.opcodes.entry(command.clone()).or_default() += 1;
+                }
+            }
+            self.instructions += size;
+            if size > 0 {
+                self.method_sizes
+                    .push((method.get_signature(&class.class_type).to_string(), size));
+            }
+        }
+    }
+
+    /// Records the complexity of the methods of an optimized class. Complexity is measured on
+    /// the control flow recovered by the optimizations, like in the Jimple method headers.
+    pub fn add_complexity(&mut self, class: &Class) {
+        for method in &class.methods {
+            let complexity = method.get_complexity();
+            if complexity.instructions > 0 {
+                self.method_complexities.push((
+                    method.get_signature(&class.class_type).to_string(),
+                    complexity,
+                ));
+            }
+        }
+    }
+
+    /// Returns up to `limit` methods with the highest cyclomatic complexity, most complex first.
+    /// Methods with equal complexity are ordered by nesting depth.
+    pub fn get_most_complex_methods(&self, limit: usize) -> Vec<(&str, &Complexity)> {
+        let mut result = self
+            .method_complexities
+            .iter()
+            .map(|(signature, complexity)| (signature.as_str(), complexity))
+            .collect::<Vec<_>>();
+        result.sort_by(|(signature1, complexity1), (signature2, complexity2)| {
+            complexity2
+                .cyclomatic
+                .cmp(&complexity1.cyclomatic)
+                .then(complexity2.nesting_depth.cmp(&complexity1.nesting_depth))
+                .then(signature1.cmp(signature2))
+        });
+        result.truncate(limit);
+        result
+    }
+
+    /// Returns up to `limit` methods with the most commands, largest first.
+    pub fn get_largest_methods(&self, limit: usize) -> Vec<(&str, usize)> {
+        let mut result = self
+            .method_sizes
+            .iter()
+            .map(|(signature, size)| (signature.as_str(), *size))
+            .collect::<Vec<_>>();
+        result.sort_by(|(signature1, size1), (signature2, size2)| {
+            size2.cmp(size1).then(signature1.cmp(signature2))
+        });
+        result.truncate(limit);
+        result
+    }
+
+    /// Writes the totals followed by the histograms, listing up to `limit` of the largest and
+    /// the most complex methods.
+    pub fn write(&self, output: &mut dyn Write, limit: usize) -> Result<(), std::io::Error> {
+        writeln!(output, "Classes:      {:>8}", self.classes)?;
+        writeln!(output, "Methods:      {:>8}", self.methods)?;
+        writeln!(output, "Fields:       {:>8}", self.fields)?;
+        writeln!(output, "Instructions: {:>8}", self.instructions)?;
+
+        write_counts(output, "Opcodes", &self.opcodes)?;
+        write_counts(output, "Class access flags", &self.class_flags)?;
+        write_counts(output, "Method access flags", &self.method_flags)?;
+        write_counts(output, "Field access flags", &self.field_flags)?;
+
+        writeln!(output)?;
+        writeln!(output, "Largest methods:")?;
+        for (signature, size) in self.get_largest_methods(limit) {
+            writeln!(output, "{size:>8} {signature}")?;
+        }
+
+        writeln!(output)?;
+        writeln!(output, "Most complex methods (complexity, nesting depth):")?;
+        for (signature, complexity) in self.get_most_complex_methods(limit) {
+            writeln!(
+                output,
+                "{:>8} {:>8} {signature}",
+                complexity.cyclomatic, complexity.nesting_depth
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn program_stats() -> Result<(), ParseErrorDisplayed> {
+        let classes = [
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field private static count:I
+
+                .method public static run()V
+                    .locals 1
+                    const/4 v0, 0x1
+                    sput v0, La;->count:I
+                    return-void
+                .end method
+
+                .method public abstract stop()V
+                .end method
+            "#,
+            r#"
+                .class final Lb;
+                .super Ljava/lang/Object;
+
+                .method public constructor <init>()V
+                    .locals 0
+                    invoke-direct {p0}, Ljava/lang/Object;-><init>()V
+                    return-void
+                .end method
+
+                .method static sum(I)I
+                    .locals 2
+                    const/4 v0, 0x0
+                    const/4 v1, 0x0
+                    :goto_0
+                    if-ge v1, p0, :cond_1
+                    const/4 p0, 0x0
+                    :goto_1
+                    if-ge p0, v1, :cond_0
+                    add-int/2addr v0, p0
+                    add-int/lit8 p0, p0, 0x1
+                    goto :goto_1
+                    :cond_0
+                    add-int/lit8 v1, v1, 0x1
+                    goto :goto_0
+                    :cond_1
+                    return v0
+                .end method
+
+                .method static check(I)V
+                    .locals 0
+                    if-eqz p0, :cond_0
+                    invoke-static {}, La;->run()V
+                    :cond_0
+                    return-void
+                .end method
+            "#,
+        ];
+
+        let mut stats = ProgramStats::new();
+        for class in classes {
+            let mut class = read_class(class)?;
+            stats.add_class(&class);
+            class.optimize();
+            stats.add_complexity(&class);
+        }
+
+        let mut output = Vec::new();
+        stats.write(&mut output, 3).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"Classes:             2
+Methods:             5
+Fields:              1
+Instructions:       19
+
+Opcodes:
+       4 const/4
+       3 return-void
+       2 add-int/lit8
+       2 goto
+       2 if-ge
+       1 add-int/2addr
+       1 if-eqz
+       1 invoke-direct
+       1 invoke-static
+       1 return
+       1 sput
+
+Class access flags:
+       1 final
+       1 public
+
+Method access flags:
+       3 public
+       3 static
+       1 abstract
+       1 constructor
+
+Field access flags:
+       1 private
+       1 static
+
+Largest methods:
+      11 int b.sum(int)
+       3 void a.run()
+       3 void b.check(int)
+
+Most complex methods (complexity, nesting depth):
+       3        2 int b.sum(int)
+       2        0 void b.check(int)
+       1        0 void a.run()
+"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::class::Class;
+use crate::index::Index;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::literal::Literal;
+use crate::r#type::MethodSignature;
+
+/// How often a string constant occurs.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StringUsage {
+    /// Number of occurrences in code and field initializers
+    pub occurrences: usize,
+    /// Number of classes containing the string
+    pub classes: usize,
+    /// Index of the last class the string was found in, to count each class only once
+    last_class: Option<usize>,
+}
+
+/// Table of all string constants in the program, storing each distinct string once. The classes
+/// themselves are left alone: both this report and the decompile pipeline drop each class once it
+/// has been processed, so sharing the literals of a class with the table saves no memory.
+#[derive(Debug, Default)]
+pub struct StringTable {
+    strings: HashMap<Arc<str>, StringUsage>,
+    class_count: usize,
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared copy of a string, adding it to the table if it is new. This doesn't
+    /// count as an occurrence.
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        match self.strings.get_key_value(value) {
+            Some((key, _)) => key.clone(),
+            None => {
+                let key: Arc<str> = value.into();
+                self.strings.insert(key.clone(), StringUsage::default());
+                key
+            }
+        }
+    }
+
+    /// Counts an occurrence of the string in the current class.
+    fn record(&mut self, value: &str) {
+        let value = self.intern(value);
+        let class = self.class_count;
+        if let Some(usage) = self.strings.get_mut(&value) {
+            usage.occurrences += 1;
+            if usage.last_class != Some(class) {
+                usage.last_class = Some(class);
+                usage.classes += 1;
+            }
+        }
+    }
+
+    /// Records the string constants used by the code and field initializers of a class.
+    pub fn add_class(&mut self, class: &Class) {
+        self.class_count += 1;
+        for field in &class.fields {
+            if let Some(Literal::String(value)) = &field.initial_value {
+                self.record(value);
+            }
+        }
+        for method in &class.methods {
+            for instruction in &method.instructions {
+                let Instruction::Command { parameters, .. } = instruction else {
+                    continue;
+                };
+                for parameter in parameters {
+                    if let CommandParameter::Literal(Literal::String(value)) = parameter {
+                        self.record(value);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn get_usage(&self, value: &str) -> Option<&StringUsage> {
+        self.strings.get(value)
+    }
+
+    /// Returns the strings occurring more than once, most frequent first.
+    pub fn get_repeated(&self) -> Vec<(&str, &StringUsage)> {
+        let mut result = self
+            .strings
+            .iter()
+            .filter(|(_, usage)| usage.occurrences > 1)
+            .map(|(value, usage)| (value.as_ref(), usage))
+            .collect::<Vec<_>>();
+        result.sort_by(|(value1, usage1), (value2, usage2)| {
+            usage2
+                .occurrences
+                .cmp(&usage1.occurrences)
+                .then(usage2.classes.cmp(&usage1.classes))
+                .then(value1.cmp(value2))
+        });
+        result
+    }
+
+    /// Lists up to `limit` of the most repeated strings.
+    pub fn write_report(&self, output: &mut dyn Write, limit: usize) -> Result<(), std::io::Error> {
+        for (value, usage) in self.get_repeated().into_iter().take(limit) {
+            write_usage(output, value, usage)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_usage(output: &mut dyn Write, value: &str, usage: &StringUsage) -> std::io::Result<()> {
+    writeln!(
+        output,
+        "{:>6} times in {:>4} classes: {}",
+        usage.occurrences,
+        usage.classes,
+        Literal::String(value.into())
+    )
+}
+
+/// Records the string constants of a class in an index mapping each string to the names of the
+/// classes using it. Unlike `StringTable`, this works with indexes kept on disk.
+pub fn add_class_to_index(index: &mut Index, class: &Class) -> Result<(), std::io::Error> {
+    let class_name = class.class_type.to_string();
+    for field in &class.fields {
+        if let Some(Literal::String(value)) = &field.initial_value {
+            index.insert(value, &class_name)?;
+        }
+    }
+    for method in &class.methods {
+        for instruction in &method.instructions {
+            let Instruction::Command { parameters, .. } = instruction else {
+                continue;
+            };
+            for parameter in parameters {
+                if let CommandParameter::Literal(Literal::String(value)) = parameter {
+                    index.insert(value, &class_name)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lists up to `limit` of the most repeated strings recorded in an index, in the same order as
+/// `StringTable::write_report`.
+pub fn write_index_report(
+    index: &Index,
+    output: &mut dyn Write,
+    limit: usize,
+) -> Result<(), std::io::Error> {
+    let mut repeated: Vec<(String, StringUsage)> = Vec::new();
+    let sort = |repeated: &mut Vec<(String, StringUsage)>| {
+        repeated.sort_by(|(value1, usage1), (value2, usage2)| {
+            usage2
+                .occurrences
+                .cmp(&usage1.occurrences)
+                .then(usage2.classes.cmp(&usage1.classes))
+                .then(value1.cmp(value2))
+        });
+        repeated.truncate(limit);
+    };
+    index.for_each(|value, classes| {
+        if classes.len() < 2 {
+            return;
+        }
+        // Class names are sorted, so each class is counted once by skipping duplicates
+        let usage = StringUsage {
+            occurrences: classes.len(),
+            classes: classes.windows(2).filter(|pair| pair[0] != pair[1]).count() + 1,
+            last_class: None,
+        };
+        repeated.push((value.to_string(), usage));
+        if repeated.len() > limit.max(100) * 2 {
+            sort(&mut repeated);
+        }
+    })?;
+    sort(&mut repeated);
+    for (value, usage) in repeated {
+        write_usage(output, &value, &usage)?;
+    }
+    Ok(())
+}
+
+/// A string loaded by a `const-string` command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringConstant {
+    pub value: Arc<str>,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+/// Lists the strings loaded by the `const-string` commands of a class, in code order.
+pub fn find_string_constants(class: &Class) -> Vec<StringConstant> {
+    let mut result = Vec::new();
+    for method in &class.methods {
+        let mut line = None;
+        for instruction in &method.instructions {
+            if let Instruction::LineNumber(from, _) = instruction {
+                line = Some(*from);
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            if !command.starts_with("const-string") {
+                continue;
+            }
+            if let Some(CommandParameter::Literal(Literal::String(value))) = parameters.get(1) {
+                result.push(StringConstant {
+                    value: value.clone(),
+                    location: method.get_signature(&class.class_type),
+                    line,
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Writes a string constant as `<location>, line N: "value"`.
+pub fn write_string_constant(
+    output: &mut dyn Write,
+    constant: &StringConstant,
+) -> std::io::Result<()> {
+    write!(output, "<{}>", constant.location)?;
+    if let Some(line) = constant.line {
+        write!(output, ", line {line}")?;
+    }
+    writeln!(output, ": {}", Literal::String(constant.value.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_class;
+
+    #[test]
+    fn string_table() -> Result<(), ParseErrorDisplayed> {
+        let first = read_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field static final TAG:Ljava/lang/String; = "Main"
+
+                .method static run()V
+                    .locals 1
+                    const-string v0, "Main"
+                    const-string v0, "key"
+                    const-string v0, "once"
+                    const-string v0, "key"
+                    return-void
+                .end method
+            "#,
+        )?;
+        let second = read_class(
+            r#"
+                .class public Lb;
+                .super Ljava/lang/Object;
+
+                .method static run()V
+                    .locals 1
+                    const-string v0, "key"
+                    const-string v0, "say \"hi\"\n"
+                    const-string v0, "say \"hi\"\n"
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut table = StringTable::new();
+        table.add_class(&first);
+        table.add_class(&second);
+
+        let repeated = table
+            .get_repeated()
+            .into_iter()
+            .map(|(value, usage)| (value, usage.occurrences, usage.classes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            repeated,
+            vec![("key", 3, 2), ("Main", 2, 1), ("say \\\"hi\\\"\\n", 2, 1)]
+        );
+        assert_eq!(
+            table.get_usage("once").map(|usage| usage.occurrences),
+            Some(1)
+        );
+
+        // Equal strings share their storage after interning
+        assert!(Arc::ptr_eq(&table.intern("key"), &table.intern("key")));
+
+        let mut output = Vec::new();
+        table.write_report(&mut output, 3).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "     3 times in    2 classes: \"key\"\n",
+                "     2 times in    1 classes: \"Main\"\n",
+                "     2 times in    1 classes: \"say \\\"hi\\\"\\n\"\n",
+            )
+        );
+
+        // The index-based report produces the same output
+        let mut index = Index::on_disk(100).unwrap();
+        add_class_to_index(&mut index, &first).unwrap();
+        add_class_to_index(&mut index, &second).unwrap();
+        let mut index_output = Vec::new();
+        write_index_report(&index, &mut index_output, 10).unwrap();
+        let mut table_output = Vec::new();
+        table.write_report(&mut table_output, 10).unwrap();
+        assert_eq!(
+            String::from_utf8(index_output).unwrap(),
+            String::from_utf8(table_output).unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn string_constants() -> Result<(), ParseErrorDisplayed> {
+        let class = read_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field static final TAG:Ljava/lang/String; = "Main"
+
+                .method static run()V
+                    .locals 1
+                    const-string v0, "first"
+                    .line 7
+                    const-string/jumbo v0, "say \"hi\""
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut output = Vec::new();
+        for constant in find_string_constants(&class) {
+            write_string_constant(&mut output, &constant).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<void a.run()>: \"first\"\n<void a.run()>, line 7: \"say \\\"hi\\\"\"\n"
+        );
+
+        Ok(())
+    }
+}
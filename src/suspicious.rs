@@ -0,0 +1,319 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction, ResultType};
+use crate::literal::Literal;
+use crate::method::{Call, Method};
+use crate::r#type::{MethodSignature, Type};
+use crate::schema::{write_json_pretty, JsonOutput, SchemaVersion};
+
+/// A call worth a closer look during analysis.
+#[derive(Debug)]
+pub struct Rule {
+    pub class: &'static str,
+    pub method: &'static str,
+    /// Only match if the first parameter is `true`, for calls enabling a setting
+    pub requires_true: bool,
+    pub warning: &'static str,
+}
+
+/// Calls flagged in the output and listed by the api-report command.
+pub const RULES: [Rule; 12] = [
+    Rule {
+        class: "java.lang.Runtime",
+        method: "exec",
+        requires_true: false,
+        warning: "executes a shell command",
+    },
+    Rule {
+        class: "java.lang.ProcessBuilder",
+        method: "start",
+        requires_true: false,
+        warning: "starts a process",
+    },
+    Rule {
+        class: "java.lang.System",
+        method: "loadLibrary",
+        requires_true: false,
+        warning: "loads native code",
+    },
+    Rule {
+        class: "java.lang.System",
+        method: "load",
+        requires_true: false,
+        warning: "loads native code",
+    },
+    Rule {
+        class: "java.lang.Runtime",
+        method: "loadLibrary",
+        requires_true: false,
+        warning: "loads native code",
+    },
+    Rule {
+        class: "android.webkit.WebSettings",
+        method: "setJavaScriptEnabled",
+        requires_true: true,
+        warning: "enables JavaScript in a WebView",
+    },
+    Rule {
+        class: "android.webkit.WebView",
+        method: "addJavascriptInterface",
+        requires_true: false,
+        warning: "exposes a Java object to JavaScript",
+    },
+    Rule {
+        class: "dalvik.system.DexClassLoader",
+        method: "<init>",
+        requires_true: false,
+        warning: "loads dex code dynamically",
+    },
+    Rule {
+        class: "dalvik.system.InMemoryDexClassLoader",
+        method: "<init>",
+        requires_true: false,
+        warning: "loads dex code dynamically",
+    },
+    Rule {
+        class: "java.lang.reflect.Method",
+        method: "invoke",
+        requires_true: false,
+        warning: "calls a method via reflection",
+    },
+    Rule {
+        class: "android.telephony.SmsManager",
+        method: "sendTextMessage",
+        requires_true: false,
+        warning: "sends a text message",
+    },
+    Rule {
+        class: "android.content.pm.PackageManager",
+        method: "setComponentEnabledSetting",
+        requires_true: false,
+        warning: "enables or disables an app component, e.g. to hide the launcher icon",
+    },
+];
+
+impl Rule {
+    fn matches(&self, call: &Call<'_>) -> bool {
+        call.method.object_type.get_name() == self.class
+            && call.method.method_name == self.method
+            && (!self.requires_true
+                || matches!(
+                    call.get_parameter(0),
+                    Some(ResultType::Literal(Literal::Bool(true) | Literal::Int(1)))
+                ))
+    }
+}
+
+impl Method {
+    /// Finds calls matching one of the rules, returning the call along with the rule.
+    pub fn find_suspicious_calls(&self, class_type: &Type) -> Vec<(Call<'_>, &'static Rule)> {
+        self.get_calls(class_type)
+            .into_iter()
+            .filter_map(|call| {
+                let rule = RULES.iter().find(|rule| rule.matches(&call))?;
+                Some((call, rule))
+            })
+            .collect()
+    }
+}
+
+impl Class {
+    /// Attaches warnings to calls matching one of the rules, these are shown next to the call.
+    pub fn flag_suspicious_calls(&mut self) {
+        for method in &mut self.methods {
+            let indexes = method
+                .find_suspicious_calls(&self.class_type)
+                .into_iter()
+                .map(|(call, rule)| (call.index, rule))
+                .collect::<Vec<_>>();
+            for (index, rule) in indexes {
+                if let Instruction::Command { parameters, .. } = &mut method.instructions[index] {
+                    parameters.push(CommandParameter::Warning(rule.warning.to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// A call matching one of the rules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspiciousCall {
+    pub warning: &'static str,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+/// Locations of calls matching the rules, grouped by the called method.
+#[derive(Debug, Default)]
+pub struct SuspiciousCallReport {
+    calls: BTreeMap<String, Vec<SuspiciousCall>>,
+}
+
+impl SuspiciousCallReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            for (call, rule) in method.find_suspicious_calls(&class.class_type) {
+                self.calls
+                    .entry(format!("{}.{}", rule.class, rule.method))
+                    .or_default()
+                    .push(SuspiciousCall {
+                        warning: rule.warning,
+                        location: method.get_signature(&class.class_type),
+                        line: call.line,
+                    });
+            }
+        }
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        for (called, calls) in &self.calls {
+            writeln!(output, "{called} ({}):", calls[0].warning)?;
+            for call in calls {
+                write!(output, "    <{}>", call.location)?;
+                if let Some(line) = call.line {
+                    write!(output, ", line {line}")?;
+                }
+                writeln!(output)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_json(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let calls = self
+            .calls
+            .iter()
+            .map(|(called, calls)| CalledMethodJson {
+                method: called.clone(),
+                warning: calls[0].warning,
+                locations: calls
+                    .iter()
+                    .map(|call| LocationJson {
+                        location: call.location.to_string(),
+                        line: call.line,
+                    })
+                    .collect(),
+            })
+            .collect();
+        write_json_pretty(
+            output,
+            &ApiReportFile {
+                schema_version: SchemaVersion,
+                calls,
+            },
+        )
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct LocationJson {
+    /// Signature of the method containing the call
+    location: String,
+    line: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct CalledMethodJson {
+    /// Class and name of the method called
+    method: String,
+    warning: &'static str,
+    locations: Vec<LocationJson>,
+}
+
+/// Suspicious calls written by the api-report command.
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct ApiReportFile {
+    schema_version: SchemaVersion<{ JsonOutput::ApiReport.version() }>,
+    calls: Vec<CalledMethodJson>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::output::OutputOptions;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn suspicious_calls() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public run(Landroid/webkit/WebSettings;)V
+                    .locals 2
+                    .line 5
+                    const-string v0, "native"
+                    invoke-static {v0}, Ljava/lang/System;->loadLibrary(Ljava/lang/String;)V
+                    const/4 v1, 0x0
+                    invoke-virtual {p1, v1}, Landroid/webkit/WebSettings;->setJavaScriptEnabled(Z)V
+                    const/4 v1, 0x1
+                    invoke-virtual {p1, v1}, Landroid/webkit/WebSettings;->setJavaScriptEnabled(Z)V
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, mut class) = Class::read(&input)?;
+        class.optimize();
+
+        let mut report = SuspiciousCallReport::new();
+        report.add_class(&class);
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"android.webkit.WebSettings.setJavaScriptEnabled (enables JavaScript in a WebView):
+    <void a.run(android.webkit.WebSettings)>, line 5
+java.lang.System.loadLibrary (loads native code):
+    <void a.run(android.webkit.WebSettings)>, line 5
+"#
+        );
+
+        let mut output = Vec::new();
+        report.write_json(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::ApiReport.assert_valid(&output);
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            output["calls"][1],
+            serde_json::json!({
+                "method": "java.lang.System.loadLibrary",
+                "warning": "loads native code",
+                "locations": [{"location": "void a.run(android.webkit.WebSettings)", "line": 5}]
+            })
+        );
+
+        class.flag_suspicious_calls();
+        let mut output = Vec::new();
+        class.methods[0]
+            .write_jimple(&mut output, &OutputOptions::default())
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"    public void run(android.webkit.WebSettings @p0)
+    {
+        // line 5
+        invoke-static <void java.lang.System.loadLibrary(java.lang.String)>("native"); // warning: loads native code
+        invoke-virtual p1.<void android.webkit.WebSettings.setJavaScriptEnabled(bool)>(false);
+        invoke-virtual p1.<void android.webkit.WebSettings.setJavaScriptEnabled(bool)>(true); // warning: enables JavaScript in a WebView
+        return;
+    }
+"#
+        );
+
+        Ok(())
+    }
+}
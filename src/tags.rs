@@ -0,0 +1,240 @@
+use std::io::Write;
+
+/// Keywords starting a class declaration in Jimple output, with the corresponding ctags kinds.
+const CLASS_KINDS: [(&str, &str); 4] = [
+    ("class", "c"),
+    ("interface", "i"),
+    ("enum", "g"),
+    ("@interface", "a"),
+];
+
+/// A definition found in a Jimple file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub file: String,
+    /// Line number, starting with 1
+    pub line: usize,
+    /// Single-letter kind as used by universal-ctags for Java
+    pub kind: &'static str,
+    /// Full name of the class containing the definition
+    pub scope: Option<String>,
+}
+
+/// Extracts the identifier ending at the end of the text, keeping quotes around names that
+/// aren't valid identifiers.
+fn last_identifier(text: &str) -> &str {
+    let text = text.trim_end();
+    if text.ends_with('\'') && text.len() > 1 {
+        // Quotes within quoted names are escaped
+        let mut start = text.len() - 1;
+        while let Some(position) = text[..start].rfind('\'') {
+            start = position;
+            if !text[..position].ends_with('\\') {
+                break;
+            }
+        }
+        &text[start..]
+    } else {
+        text.rsplit(' ').next().unwrap_or_default()
+    }
+}
+
+/// Returns the simple name of a class, without package and outer classes.
+fn simple_name(name: &str) -> &str {
+    name.rsplit(['.', '$']).next().unwrap_or(name)
+}
+
+/// Recognizes a class declaration, returning its ctags kind and full name.
+fn parse_class_declaration(line: &str) -> Option<(&'static str, &str)> {
+    let mut words = line.split(' ');
+    while let Some(word) = words.next() {
+        if let Some((_, kind)) = CLASS_KINDS.iter().find(|(keyword, _)| *keyword == word) {
+            return Some((kind, words.next()?));
+        }
+    }
+    None
+}
+
+/// Symbol index of a Jimple tree in the tags format of universal-ctags, allowing editors to
+/// jump to the definitions of classes, methods and fields. Definitions are recognized by
+/// their position in the output, as declarations at the indentation level of class members.
+#[derive(Debug, Default)]
+pub struct TagsFile {
+    tags: Vec<Tag>,
+}
+
+impl TagsFile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the definitions in a Jimple file, `file` being its path relative to the tags file.
+    pub fn add_file(&mut self, file: &str, data: &str) {
+        let lines = data.lines().collect::<Vec<_>>();
+        // Parts of split classes repeat the class declaration, the main file defines the class
+        let is_part = lines
+            .iter()
+            .take_while(|line| line.starts_with("//") || line.is_empty())
+            .any(|line| line.starts_with("// part ") && line.contains(" of "));
+        // Classes currently open along with the indentation of their members
+        let mut classes: Vec<(String, usize)> = Vec::new();
+        let mut index = 0;
+        while index < lines.len() {
+            let line = lines[index];
+            let content = line.trim_start();
+            let indent = line.len() - content.len();
+            let member_indent = classes.last().map_or(0, |(_, indent)| *indent);
+            index += 1;
+
+            if content == "}" && indent + 4 == member_indent {
+                classes.pop();
+                continue;
+            }
+            if indent != member_indent
+                || content.is_empty()
+                || content.starts_with(['@', '/', ')', '}', '{'])
+            {
+                continue;
+            }
+
+            let opens_block = lines
+                .get(index)
+                .is_some_and(|next| next.len() == indent + 1 && next.trim_start() == "{");
+            let scope = classes.last().map(|(name, _)| name.clone());
+            if let Some((kind, name)) = parse_class_declaration(content) {
+                if opens_block {
+                    if !is_part || !classes.is_empty() {
+                        self.tags.push(Tag {
+                            name: simple_name(name).to_string(),
+                            file: file.to_string(),
+                            line: index,
+                            kind,
+                            scope,
+                        });
+                    }
+                    classes.push((name.to_string(), indent + 4));
+                    index += 1;
+                }
+                continue;
+            }
+            if classes.is_empty() {
+                continue;
+            }
+
+            let head = content.split(" = ").next().unwrap_or_default();
+            if let Some((declaration, _)) = head.split_once('(') {
+                self.tags.push(Tag {
+                    name: last_identifier(declaration).to_string(),
+                    file: file.to_string(),
+                    line: index,
+                    kind: "m",
+                    scope,
+                });
+            } else if content.ends_with(';') {
+                self.tags.push(Tag {
+                    name: last_identifier(head.trim_end_matches(';')).to_string(),
+                    file: file.to_string(),
+                    line: index,
+                    kind: "f",
+                    scope,
+                });
+            }
+
+            // Skip method bodies and static blocks, these might contain lines at the same indentation level
+            if opens_block {
+                let end = format!("{}}}", " ".repeat(indent));
+                while index < lines.len() && lines[index] != end {
+                    index += 1;
+                }
+                index += 1;
+            }
+        }
+    }
+
+    pub fn write(&mut self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        self.tags
+            .sort_by(|a, b| (&a.name, &a.file, a.line).cmp(&(&b.name, &b.file, b.line)));
+        writeln!(
+            output,
+            "!_TAG_FILE_FORMAT\t2\t/extended format; --format=1 will not append ;\" to lines/"
+        )?;
+        writeln!(
+            output,
+            "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/"
+        )?;
+        writeln!(output, "!_TAG_PROGRAM_NAME\taarf\t//")?;
+        for tag in &self.tags {
+            write!(
+                output,
+                "{}\t{}\t{};\"\t{}",
+                tag.name, tag.file, tag.line, tag.kind
+            )?;
+            if let Some(scope) = &tag.scope {
+                write!(output, "\tclass:{scope}")?;
+            }
+            writeln!(output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_file() {
+        let mut tags = TagsFile::new();
+        tags.add_file(
+            "smali/com/example/Outer.jimple",
+            r#"// Generated by aarf 0.1.0
+
+// source: Outer.java
+@kotlin.Metadata(k = 0x1)
+public class com.example.Outer
+{
+    public static final java.lang.String TAG = "a(b";
+
+    private int 'odd name';
+
+    static
+    {
+        return;
+    }
+
+    public Outer(int @p0)
+    {
+    loop:
+        goto loop;
+    }
+
+    public abstract void run();
+
+    static class com.example.Outer$Inner
+    {
+        void inner()
+        {
+            return;
+        }
+    }
+}"#,
+        );
+
+        let mut output = Vec::new();
+        tags.write(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().skip(3).collect::<Vec<_>>(),
+            vec![
+                "'odd name'\tsmali/com/example/Outer.jimple\t9;\"\tf\tclass:com.example.Outer",
+                "Inner\tsmali/com/example/Outer.jimple\t24;\"\tc\tclass:com.example.Outer",
+                "Outer\tsmali/com/example/Outer.jimple\t5;\"\tc",
+                "Outer\tsmali/com/example/Outer.jimple\t16;\"\tm\tclass:com.example.Outer",
+                "TAG\tsmali/com/example/Outer.jimple\t7;\"\tf\tclass:com.example.Outer",
+                "inner\tsmali/com/example/Outer.jimple\t26;\"\tm\tclass:com.example.Outer$Inner",
+                "run\tsmali/com/example/Outer.jimple\t22;\"\tm\tclass:com.example.Outer",
+            ]
+        );
+    }
+}
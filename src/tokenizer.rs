@@ -4,36 +4,139 @@ use std::rc::Rc;
 
 use crate::error::{Error, ParseError};
 
+fn is_identifier_end(data: &str, index: usize, c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\t' | '\r' | '\n' | ',' | ':' | '(' | ')' | '{' | '}' | '#' | '@' | ';' | '='
+    ) || data[index..].starts_with("->")
+}
+
+/// Quotes a name with backticks if `read_identifier` couldn't read it otherwise.
+pub fn quote_identifier(name: &str) -> Cow<'_, str> {
+    if name.is_empty()
+        || name.starts_with(['`', '"'])
+        || name
+            .char_indices()
+            .any(|(index, c)| is_identifier_end(name, index, c))
+    {
+        Cow::Owned(format!("`{name}`"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Finds the `#` starting a comment in a line of Smali code, skipping `#` characters in
+/// string and character literals or quoted names.
+fn find_comment(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(q) if c == '\\' && q != '`' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '#' => return Some(index),
+            None if matches!(c, '"' | '\'' | '`') => quote = Some(c),
+            None => (),
+        }
+    }
+    None
+}
+
+/// Lists all comments in Smali code along with their line numbers, whether they occupy entire
+/// lines or follow code.
+pub fn find_comments(data: &str) -> Vec<(usize, &str)> {
+    data.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let start = find_comment(line)?;
+            Some((index + 1, line[start + 1..].trim()))
+        })
+        .collect()
+}
+
+/// A position in the input that parsing can return to, see [`Tokenizer::checkpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Checkpoint(usize);
+
 #[derive(Debug, Clone)]
 pub struct Tokenizer {
     pos: usize,
     data: Rc<String>,
     path: Rc<PathBuf>,
+    /// Whether method code that fails to parse is kept as raw instructions
+    lenient: bool,
+    /// Whether comments following method code on the same line are kept as instructions
+    trailing_comments: bool,
 }
 
+/// Byte order mark some editors put at the start of UTF-8 files.
+pub(crate) const BOM: char = '\u{feff}';
+
 impl Tokenizer {
     pub fn new(data: String, path: &Path) -> Self {
         Self {
-            pos: 0,
+            pos: if data.starts_with(BOM) {
+                BOM.len_utf8()
+            } else {
+                0
+            },
             data: Rc::new(data),
             path: Rc::new(path.to_path_buf()),
+            lenient: false,
+            trailing_comments: false,
+        }
+    }
+
+    /// Switches to lenient mode: lines of method code that fail to parse are kept as
+    /// `Instruction::Raw` rather than producing an error.
+    pub fn lenient(self) -> Self {
+        Self {
+            lenient: true,
+            ..self
         }
     }
 
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+
+    /// Keeps comments following method code on the same line as
+    /// `Instruction::TrailingComment`. This is only useful when writing Smali code back,
+    /// analyzing the code would merely be disturbed by these.
+    pub fn with_trailing_comments(self) -> Self {
+        Self {
+            trailing_comments: true,
+            ..self
+        }
+    }
+
+    pub fn keeps_trailing_comments(&self) -> bool {
+        self.trailing_comments
+    }
+
     pub fn from_file(path: &Path) -> Result<Self, Error> {
         let data = std::fs::read(path).map_err(|_| Error::ReadFailure(path.to_path_buf()))?;
         let data = String::from_utf8(data).map_err(|_| Error::Utf8Error(path.to_path_buf()))?;
         Ok(Self::new(data, path))
     }
 
+    /// Returns the complete input data, regardless of the current position.
+    pub fn content(&self) -> &str {
+        &self.data
+    }
+
     fn data(&self) -> &str {
         &self.data[self.pos..]
     }
 
+    /// Skips spaces and tabs. Carriage returns are skipped as well, so that Windows line endings
+    /// are treated like Unix ones.
     fn skip_whitespace(&self) -> Self {
         let mut input = self.clone();
         for c in self.data().chars() {
-            if c != ' ' && c != '\t' {
+            if c != ' ' && c != '\t' && c != '\r' {
                 break;
             }
             input.pos += 1;
@@ -53,13 +156,50 @@ impl Tokenizer {
     }
 
     fn read_to_whitespace(&self) -> (Self, String) {
-        self.read_to(&[' ', '\t'])
+        self.read_to(&[' ', '\t', '\r'])
     }
 
     pub fn next_char(&self) -> Option<char> {
         self.data().chars().next()
     }
 
+    /// Returns the next character after whitespace without consuming it.
+    pub fn peek_char(&self) -> Option<char> {
+        self.skip_whitespace().next_char()
+    }
+
+    /// Returns the name of the directive at the current position without consuming it. Unlike
+    /// probing with `expect_directive`, no error is produced if there is none.
+    pub fn peek_directive(&self) -> Option<&str> {
+        let data = self.data().trim_start_matches([' ', '\t', '\r']);
+        let data = data.strip_prefix('.')?;
+        let end = data.find([' ', '\t', '\r', '\n']).unwrap_or(data.len());
+        (end > 0).then(|| &data[..end])
+    }
+
+    /// Remembers the current position, so that parsing can return to it via
+    /// [`Tokenizer::rewind`], e.g. after a speculative parsing attempt failed.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.pos)
+    }
+
+    /// Returns to a position previously recorded via [`Tokenizer::checkpoint`].
+    pub fn rewind(&self, checkpoint: Checkpoint) -> Self {
+        Self {
+            pos: checkpoint.0,
+            ..self.clone()
+        }
+    }
+
+    /// Runs a parser speculatively, returning `None` instead of an error if it fails. The input
+    /// is unaffected in that case.
+    pub fn attempt<T>(
+        &self,
+        parser: impl FnOnce(&Self) -> Result<(Self, T), ParseError>,
+    ) -> Option<(Self, T)> {
+        parser(self).ok()
+    }
+
     pub fn read_char(&self) -> Result<(Self, char), ParseError> {
         let mut input = self.skip_whitespace();
         let c = input
@@ -68,7 +208,7 @@ impl Tokenizer {
             .next()
             .ok_or_else(|| input.unexpected("a char".into()))?;
 
-        input.pos += 1;
+        input.pos += c.len_utf8();
         Ok((input, c))
     }
 
@@ -119,9 +259,60 @@ impl Tokenizer {
         Ok(input)
     }
 
+    /// Lists the comments occupying entire lines between an earlier position and the current
+    /// one. The line containing the earlier position is skipped.
+    pub fn comments_since(&self, start: Checkpoint) -> Vec<String> {
+        self.data[start.0..self.pos]
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.trim_start().strip_prefix('#'))
+            .map(|comment| comment.trim().to_string())
+            .collect()
+    }
+
+    /// Returns the comment following code on the line containing an earlier position.
+    pub fn trailing_comment(&self, start: Checkpoint) -> Option<String> {
+        let line = &self.data[start.0..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+        find_comment(line).map(|index| line[index + 1..].trim().to_string())
+    }
+
+    /// Lists the comments occupying entire lines right before the current position, up to the
+    /// closest line with anything else on it.
+    pub fn preceding_comments(&self) -> Vec<String> {
+        let mut comments = self.data[..self.pos]
+            .lines()
+            .rev()
+            .map(str::trim)
+            .take_while(|line| line.is_empty() || line.starts_with('#'))
+            .filter_map(|line| line.strip_prefix('#'))
+            .map(|comment| comment.trim().to_string())
+            .collect::<Vec<_>>();
+        comments.reverse();
+        comments
+    }
+
+    /// Skips all lines up to and including the `.end` line closing the given directive.
+    pub fn skip_block(&self, directive: &str) -> Result<Self, ParseError> {
+        let mut input = self.clone();
+        loop {
+            (input, _) = input.read_to(&['\n']);
+            input = input
+                .expect_char('\n')
+                .map_err(|_| input.unexpected(format!(".end {directive}").into()))?;
+            if let Ok(i) = input
+                .expect_directive("end")
+                .and_then(|i| i.expect_keyword(directive))
+            {
+                return i.expect_eol();
+            }
+        }
+    }
+
     pub fn read_keyword(&self) -> Result<(Self, String), ParseError> {
         let input = self.skip_whitespace();
-        let (input, keyword) = input.read_to(&[' ', '\t', ',', ':', '(', ')', '{', '}', '#', '@']);
+        let (input, keyword) =
+            input.read_to(&[' ', '\t', '\r', ',', ':', '(', ')', '{', '}', '#', '@']);
         if keyword.is_empty() {
             Err(input.unexpected("a keyword".into()))
         } else {
@@ -129,6 +320,97 @@ impl Tokenizer {
         }
     }
 
+    /// Reads the name of a field, method or annotation parameter. Unlike keywords, names also
+    /// end at `;`, `=` and `->`, whereas a `-` not followed by `>` is part of the name. Names
+    /// containing delimiters can be quoted with backticks or double quotes.
+    pub fn read_identifier(&self) -> Result<(Self, String), ParseError> {
+        let mut input = self.skip_whitespace();
+        let data = input.data();
+        if let Some(quote) = data.chars().next().filter(|c| *c == '`' || *c == '"') {
+            let line = &data[1..data.find('\n').unwrap_or(data.len())];
+            let mut identifier = String::new();
+            let mut chars = line.char_indices();
+            while let Some((index, c)) = chars.next() {
+                if c == quote {
+                    input.pos += index + 2;
+                    return Ok((input, identifier));
+                }
+                if c == '\\' && quote == '"' {
+                    if let Some((_, escaped)) = chars.next() {
+                        identifier.push(escaped);
+                        continue;
+                    }
+                }
+                identifier.push(c);
+            }
+            return Err(input.unexpected("a quoted name".into()));
+        }
+
+        let end = data
+            .char_indices()
+            .find(|(index, c)| is_identifier_end(data, *index, *c))
+            .map_or(data.len(), |(index, _)| index);
+        if end == 0 {
+            return Err(input.unexpected("a name".into()));
+        }
+
+        let identifier = data[..end].to_string();
+        input.pos += end;
+        Ok((input, identifier))
+    }
+
+    /// Reads a name as written in Jimple output: a Java identifier, `<init>`, `<clinit>` or a name
+    /// in single quotes with backslash escapes, see [`crate::output::jimple_identifier`].
+    pub fn read_jimple_identifier(&self) -> Result<(Self, String), ParseError> {
+        let mut input = self.skip_whitespace();
+        let data = input.data();
+        if let Some(line) = data.strip_prefix('\'') {
+            let line = &line[..line.find('\n').unwrap_or(line.len())];
+            let mut identifier = String::new();
+            let mut chars = line.char_indices();
+            while let Some((index, c)) = chars.next() {
+                match c {
+                    '\'' => {
+                        input.pos += index + 2;
+                        return Ok((input, identifier));
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'u')) => {
+                            let code = chars.by_ref().take(4).map(|(_, c)| c).collect::<String>();
+                            match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                                Some(c) => identifier.push(c),
+                                None => break,
+                            }
+                        }
+                        Some((_, escaped)) => identifier.push(escaped),
+                        None => break,
+                    },
+                    c => identifier.push(c),
+                }
+            }
+            return Err(input.unexpected("a quoted name".into()));
+        }
+
+        for special in ["<init>", "<clinit>"] {
+            if data.starts_with(special) {
+                input.pos += special.len();
+                return Ok((input, special.to_string()));
+            }
+        }
+
+        let end = data
+            .char_indices()
+            .find(|(_, c)| !c.is_alphanumeric() && *c != '_' && *c != '$')
+            .map_or(data.len(), |(index, _)| index);
+        if end == 0 {
+            return Err(input.unexpected("a name".into()));
+        }
+
+        let identifier = data[..end].to_string();
+        input.pos += end;
+        Ok((input, identifier))
+    }
+
     pub fn expect_keyword(&self, expected: &str) -> Result<Self, ParseError> {
         let (input, keyword) = self
             .read_keyword()
@@ -190,6 +472,37 @@ impl Tokenizer {
     }
 }
 
+/// Parses a class from Smali code in a test.
+#[cfg(test)]
+pub(crate) fn read_class(
+    data: &str,
+) -> Result<crate::class::Class, crate::error::ParseErrorDisplayed> {
+    let input = Tokenizer::new(data.trim().to_string(), Path::new("dummy"));
+    Ok(crate::class::Class::read(&input)?.1)
+}
+
+/// Parses a class from Smali code in a test and optimizes it.
+#[cfg(test)]
+pub(crate) fn read_optimized_class(
+    data: &str,
+) -> Result<crate::class::Class, crate::error::ParseErrorDisplayed> {
+    let mut class = read_class(data)?;
+    class.optimize();
+    Ok(class)
+}
+
+/// Parses a single method from Smali code in a test, starting with its `.method` directive.
+#[cfg(test)]
+pub(crate) fn read_method(
+    data: &str,
+) -> Result<crate::method::Method, crate::error::ParseErrorDisplayed> {
+    let input = Tokenizer::new(data.trim().to_string(), Path::new("dummy"));
+    let input = input.expect_directive("method")?;
+    let (input, method) = crate::method::Method::read(&input)?;
+    assert!(input.expect_eof().is_ok());
+    Ok(method)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -270,6 +583,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn skip_block() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer("a()V\n  .locals 0\n  return-void\n.end method\n\n.end class");
+        let input = input.skip_block("method")?;
+        let input = input.expect_directive("end")?;
+        assert!(input.expect_keyword("class").is_ok());
+
+        assert!(tokenizer("a()V\nreturn-void\n")
+            .skip_block("method")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn comments_since() -> Result<(), ParseErrorDisplayed> {
+        let start = tokenizer(
+            "abc # same line
+  
+ # first
+	#second 
+xyz",
+        );
+        let (input, _) = start.read_keyword()?;
+        let input = input.expect_eol()?;
+        assert_eq!(
+            input.comments_since(start.checkpoint()),
+            vec!["first", "second"]
+        );
+        assert!(input.comments_since(input.checkpoint()).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn trailing_and_preceding_comments() -> Result<(), ParseErrorDisplayed> {
+        let start = tokenizer(
+            r#"const-string v0, "a # b" # string
+    # first
+
+    # second
+.end method"#,
+        );
+        let (input, _) = start.read_to(&['"']);
+        assert_eq!(
+            input.trailing_comment(start.checkpoint()).as_deref(),
+            Some("string")
+        );
+
+        let (input, _) = input.read_to(&['\n']);
+        let input = input.expect_eol()?;
+        assert_eq!(input.peek_directive(), Some("end"));
+        assert_eq!(input.trailing_comment(input.checkpoint()), None);
+        assert_eq!(input.preceding_comments(), vec!["first", "second"]);
+        assert!(start.preceding_comments().is_empty());
+
+        assert_eq!(
+            find_comments("# a\n.field x:C = '#' # b\n.source \"\\\"#\"\n`#`->c # c#d"),
+            vec![(1, "a"), (2, "b"), (4, "c#d")]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn read_keyword() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(" abc, xyz:def ghi\njkl");
@@ -298,6 +675,67 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn read_identifier() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(" a-b->c=d;e:f <init>(\näöü");
+
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "a-b");
+        assert!(input.read_identifier().is_err());
+
+        let input = input.expect_char('-')?;
+        let input = input.expect_char('>')?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "c");
+
+        let input = input.expect_char('=')?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "d");
+
+        let input = input.expect_char(';')?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "e");
+
+        let input = input.expect_char(':')?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "f");
+
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "<init>");
+
+        let input = input.expect_char('(')?;
+        assert!(input.read_identifier().is_err());
+
+        let input = input.expect_eol()?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "äöü");
+        assert!(input.expect_eof().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn quoted_identifier() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer(r#" `weird name`:"a \"b\" c"->`x"#);
+
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, "weird name");
+        assert_eq!(quote_identifier(&identifier), "`weird name`");
+
+        let input = input.expect_char(':')?;
+        let (input, identifier) = input.read_identifier()?;
+        assert_eq!(identifier, r#"a "b" c"#);
+
+        let input = input.expect_char('-')?;
+        let input = input.expect_char('>')?;
+        assert!(input.read_identifier().is_err());
+
+        assert_eq!(quote_identifier("a-b"), "a-b");
+        assert_eq!(quote_identifier("a->b"), "`a->b`");
+
+        Ok(())
+    }
+
     #[test]
     fn read_directive() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(" .abc, .xyz:.def .ghi\n.jkl");
@@ -322,6 +760,82 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn windows_line_endings() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer("\u{feff}.class public La;\r\n# comment\r\n\r\n.super\tLb;\r\n");
+
+        let input = input.expect_directive("class")?;
+        let input = input.expect_keyword("public")?;
+        let (input, keyword) = input.read_keyword()?;
+        assert_eq!(keyword, "La;");
+
+        let input = input.expect_eol()?;
+        let (input, directive) = input.read_directive()?;
+        assert_eq!(directive, "super");
+
+        let (input, keyword) = input.read_keyword()?;
+        assert_eq!(keyword, "Lb;");
+
+        let input = input.expect_eol()?;
+        assert!(input.expect_eof().is_ok());
+
+        let input = tokenizer("\u{feff}abc\r\nxyz");
+        let (input, keyword) = input.read_keyword()?;
+        assert_eq!(keyword, "abc");
+        assert_eq!(
+            input.unexpected("x".into()).to_string(),
+            "Unexpected token <EOL> in dummy at 1:4, expected x\n    abc\n       ^"
+        );
+        assert_eq!(
+            input.unexpected("x".into()).to_short_string(),
+            "dummy:1:4: unexpected token <EOL>, expected x"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_position() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer("\"äöü\"\t€ x\nnext");
+        let input = input.expect_char('"')?;
+        let (input, _) = input.read_to(&['"']);
+        let input = input.expect_char('"')?;
+        let input = input.expect_char('€')?;
+        assert_eq!(
+            input.expect_char('y').unwrap_err().to_string(),
+            "Unexpected token x in dummy at 1:9, expected the character 'y'\n    \"äöü\"\t€ x\n         \t  ^"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn peek_and_rewind() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer("  .enum La;\n.\n  x");
+        assert_eq!(input.peek_char(), Some('.'));
+        assert_eq!(input.peek_directive(), Some("enum"));
+
+        let checkpoint = input.checkpoint();
+        let input = input.expect_directive("enum")?;
+        assert_eq!(input.peek_directive(), None);
+        assert!(input
+            .attempt(|input| input.expect_char('x').map(|input| (input, ())))
+            .is_none());
+        let (input, keyword) = input.read_keyword()?;
+        assert_eq!(keyword, "La;");
+
+        let input = input.rewind(checkpoint);
+        assert_eq!(input.peek_directive(), Some("enum"));
+
+        let input = input.expect_directive("enum")?;
+        let input = input.expect_keyword("La;")?;
+        let input = input.expect_eol()?;
+        assert_eq!(input.peek_char(), Some('.'));
+        assert_eq!(input.peek_directive(), None);
+
+        Ok(())
+    }
+
     #[test]
     fn read_number() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(r#" -5, 0x12 -0x12 0x41t  1234S 12x "#);
@@ -1,11 +1,83 @@
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
 
 use crate::error::ParseError;
 use crate::literal::Literal;
-use crate::tokenizer::Tokenizer;
+use crate::output::jimple_identifier;
+use crate::tokenizer::{quote_identifier, Tokenizer};
+
+/// Parses a complete string with the given reader, e.g. a descriptor passed on the command line.
+fn parse_complete<T>(
+    data: &str,
+    read: fn(&Tokenizer) -> Result<(Tokenizer, T), ParseError>,
+) -> Result<T, String> {
+    let input = Tokenizer::new(data.to_string(), Path::new("<input>"));
+    read(&input)
+        .and_then(|(input, result)| Ok((input.expect_eof()?, result)))
+        .map(|(_, result)| result)
+        .map_err(|error| error.to_string())
+}
 
-#[derive(Debug, Clone, PartialEq)]
+/// Parses a complete string either as a Smali descriptor or in the Jimple notation used by the
+/// output. If both fail, the Jimple error is reported unless the string looks like a descriptor.
+fn parse_either<T>(
+    data: &str,
+    read: fn(&Tokenizer) -> Result<(Tokenizer, T), ParseError>,
+    read_jimple: fn(&Tokenizer) -> Result<(Tokenizer, T), ParseError>,
+) -> Result<T, String> {
+    parse_complete(data, read).or_else(|error| {
+        parse_complete(data, read_jimple).map_err(|jimple_error| {
+            if data.contains(';') {
+                error
+            } else {
+                jimple_error
+            }
+        })
+    })
+}
+
+/// Reads a dotted sequence of Jimple names such as `java.lang.String` or `a.b.<init>`.
+fn read_jimple_path(input: &Tokenizer) -> Result<(Tokenizer, Vec<String>), ParseError> {
+    let (mut input, segment) = input.read_jimple_identifier()?;
+    let mut segments = vec![segment];
+    while input.next_char() == Some('.') {
+        let segment;
+        (input, segment) = input.expect_char('.')?.read_jimple_identifier()?;
+        segments.push(segment);
+    }
+    Ok((input, segments))
+}
+
+/// Reads a Jimple member reference such as `void a.b(int)`, optionally enclosed in angle
+/// brackets. Returns the member type, the type declaring the member and the member name.
+fn read_jimple_member(
+    input: &Tokenizer,
+) -> Result<(Tokenizer, bool, Type, Type, String), ParseError> {
+    let bracketed = input.peek_char() == Some('<');
+    let input = if bracketed {
+        input.expect_char('<')?
+    } else {
+        input.clone()
+    };
+    let (input, member_type) = Type::read_jimple(&input)?;
+    let start = input.clone();
+    let (input, mut segments) = read_jimple_path(&input)?;
+    let name = segments.pop().unwrap_or_default();
+    if segments.is_empty() {
+        return Err(start.unexpected("a member reference".into()));
+    }
+    Ok((
+        input,
+        bracketed,
+        member_type,
+        Type::from_jimple_segments(segments),
+        name,
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Type {
     Bool,
     Byte,
@@ -55,6 +127,38 @@ impl Type {
         })
     }
 
+    /// Reads a type name as it appears in Jimple output, e.g. `int` or `java.lang.String[]`.
+    pub fn read_jimple(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        let (mut input, segments) = read_jimple_path(input)?;
+        let mut result = Self::from_jimple_segments(segments);
+        while input.next_char() == Some('[') {
+            input = input.expect_char('[')?.expect_char(']')?;
+            result = Self::Array(Box::new(result));
+        }
+        Ok((input, result))
+    }
+
+    fn from_jimple_segments(segments: Vec<String>) -> Self {
+        match segments.as_slice() {
+            [name] => match name.as_str() {
+                "bool" | "boolean" => Self::Bool,
+                "byte" => Self::Byte,
+                "char" => Self::Char,
+                "short" => Self::Short,
+                "int" => Self::Int,
+                "long" => Self::Long,
+                "float" => Self::Float,
+                "double" => Self::Double,
+                "void" => Self::Void,
+                "Class" => Self::Class,
+                "MethodHandle" => Self::MethodHandle,
+                "MethodType" => Self::MethodType,
+                _ => Self::Object(segments.join(".")),
+            },
+            _ => Self::Object(segments.join(".")),
+        }
+    }
+
     pub fn get_name(&self) -> Cow<'_, str> {
         match self {
             Self::Bool => "bool".into(),
@@ -67,28 +171,115 @@ impl Type {
             Self::Double => "double".into(),
             Self::Void => "void".into(),
             Self::Object(name) => name.into(),
-            Self::Array(subtype) => subtype.get_name() + "[]",
+            Self::Array(_) => {
+                let mut name = self.base_type().get_name().into_owned();
+                name.push_str(&"[]".repeat(self.dimensions()));
+                name.into()
+            }
             Self::Class => "Class".into(),
             Self::MethodHandle => "MethodHandle".into(),
             Self::MethodType => "MethodType".into(),
         }
     }
 
+    /// Returns the type of the array elements, `None` for non-array types.
+    pub fn element_type(&self) -> Option<&Type> {
+        match self {
+            Self::Array(subtype) => Some(subtype),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of array dimensions, zero for non-array types.
+    pub fn dimensions(&self) -> usize {
+        let mut dimensions = 0;
+        let mut current = self;
+        while let Some(subtype) = current.element_type() {
+            dimensions += 1;
+            current = subtype;
+        }
+        dimensions
+    }
+
+    /// Returns the innermost element type of an array, e.g. `int` for `int[][]`. Non-array
+    /// types are returned as is.
+    pub fn base_type(&self) -> &Type {
+        let mut current = self;
+        while let Some(subtype) = current.element_type() {
+            current = subtype;
+        }
+        current
+    }
+
     pub fn register_count(&self) -> usize {
         match self {
             Self::Long | Self::Double => 2,
             _ => 1,
         }
     }
+
+    /// Returns the type descriptor as used in Smali code, e.g. `[Ljava/lang/String;`.
+    pub fn to_descriptor(&self) -> String {
+        match self {
+            Self::Bool => "Z".to_string(),
+            Self::Byte => "B".to_string(),
+            Self::Char => "C".to_string(),
+            Self::Short => "S".to_string(),
+            Self::Int => "I".to_string(),
+            Self::Long => "J".to_string(),
+            Self::Float => "F".to_string(),
+            Self::Double => "D".to_string(),
+            Self::Void => "V".to_string(),
+            Self::Object(name) => format!("L{};", name.replace('.', "/")),
+            Self::Array(_) => format!(
+                "{}{}",
+                "[".repeat(self.dimensions()),
+                self.base_type().to_descriptor()
+            ),
+            Self::Class => "Ljava/lang/Class;".to_string(),
+            Self::MethodHandle => "Ljava/lang/invoke/MethodHandle;".to_string(),
+            Self::MethodType => "Ljava/lang/invoke/MethodType;".to_string(),
+        }
+    }
+
+    /// Size of a primitive value in bytes as used by `.array-data` directives.
+    pub fn element_size(&self) -> Option<usize> {
+        match self {
+            Self::Bool | Self::Byte => Some(1),
+            Self::Char | Self::Short => Some(2),
+            Self::Int | Self::Float => Some(4),
+            Self::Long | Self::Double => Some(8),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Type {
+    type Err = String;
+
+    /// Parses a type descriptor such as `[Ljava/lang/String;` or a Jimple type name such as
+    /// `java.lang.String[]`.
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        parse_either(data, Self::read, Self::read_jimple)
+    }
 }
 
 impl Display for Type {
+    /// Writes the Jimple type name. Package and class names that aren't valid identifiers are
+    /// quoted individually, unicode letters are kept as is.
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        write!(f, "{}", self.get_name())
+        match self {
+            Self::Object(name) => {
+                let segments = name.split('.').map(jimple_identifier).collect::<Vec<_>>();
+                write!(f, "{}", segments.join("."))
+            }
+            Self::Array(_) => write!(f, "{}{}", self.base_type(), "[]".repeat(self.dimensions())),
+            _ => write!(f, "{}", self.get_name()),
+        }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FieldSignature {
     pub object_type: Type,
     pub field_name: String,
@@ -100,7 +291,7 @@ impl FieldSignature {
         let (input, object_type) = Type::read(input)?;
         let input = input.expect_char('-')?;
         let input = input.expect_char('>')?;
-        let (input, field_name) = input.read_keyword()?;
+        let (input, field_name) = input.read_identifier()?;
         let input = input.expect_char(':')?;
         let (input, field_type) = Type::read(&input)?;
         Ok((
@@ -114,17 +305,58 @@ impl FieldSignature {
     }
 }
 
+impl FieldSignature {
+    /// Reads a field reference in Jimple notation, e.g. `<int a.b>`. The angle brackets are
+    /// optional.
+    pub fn read_jimple(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        let (input, bracketed, field_type, object_type, field_name) = read_jimple_member(input)?;
+        let input = if bracketed {
+            input.expect_char('>')?
+        } else {
+            input
+        };
+        Ok((
+            input,
+            Self {
+                object_type,
+                field_name,
+                field_type,
+            },
+        ))
+    }
+
+    /// Returns the field reference in Smali notation, e.g. `La;->b:I`.
+    pub fn to_smali(&self) -> String {
+        format!(
+            "{}->{}:{}",
+            self.object_type.to_descriptor(),
+            quote_identifier(&self.field_name),
+            self.field_type.to_descriptor()
+        )
+    }
+}
+
+impl FromStr for FieldSignature {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        parse_either(data, Self::read, Self::read_jimple)
+    }
+}
+
 impl Display for FieldSignature {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(
             f,
             "{} {}.{}",
-            self.field_type, self.object_type, self.field_name
+            self.field_type,
+            self.object_type,
+            jimple_identifier(&self.field_name)
         )
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CallSignature {
     pub parameter_types: Vec<Type>,
     pub return_type: Type,
@@ -153,19 +385,31 @@ impl CallSignature {
     }
 }
 
+impl CallSignature {
+    /// Returns the signature in Smali notation, e.g. `(ILjava/lang/String;)V`.
+    pub fn to_descriptor(&self) -> String {
+        let params = self
+            .parameter_types
+            .iter()
+            .map(Type::to_descriptor)
+            .collect::<String>();
+        format!("({params}){}", self.return_type.to_descriptor())
+    }
+}
+
 impl Display for CallSignature {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let params = self
             .parameter_types
             .iter()
-            .map(Type::get_name)
+            .map(Type::to_string)
             .collect::<Vec<_>>()
             .join(", ");
         write!(f, "{} ({params})", self.return_type)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MethodSignature {
     pub object_type: Type,
     pub method_name: String,
@@ -177,7 +421,7 @@ impl MethodSignature {
         let (input, object_type) = Type::read(input)?;
         let input = input.expect_char('-')?;
         let input = input.expect_char('>')?;
-        let (input, method_name) = input.read_keyword()?;
+        let (input, method_name) = input.read_identifier()?;
         let (input, call_signature) = CallSignature::read(&input)?;
         Ok((
             input,
@@ -190,19 +434,77 @@ impl MethodSignature {
     }
 }
 
+impl MethodSignature {
+    /// Reads a method reference in Jimple notation, e.g. `<void a.b(int, java.lang.String)>`.
+    /// The angle brackets are optional.
+    pub fn read_jimple(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
+        let (input, bracketed, return_type, object_type, method_name) = read_jimple_member(input)?;
+        let mut input = input.expect_char('(')?;
+        let mut parameter_types = Vec::new();
+        if input.peek_char() != Some(')') {
+            loop {
+                let parameter_type;
+                (input, parameter_type) = Type::read_jimple(&input)?;
+                parameter_types.push(parameter_type);
+                if input.peek_char() != Some(',') {
+                    break;
+                }
+                input = input.expect_char(',')?;
+            }
+        }
+        let input = input.expect_char(')')?;
+        let input = if bracketed {
+            input.expect_char('>')?
+        } else {
+            input
+        };
+        Ok((
+            input,
+            Self {
+                object_type,
+                method_name,
+                call_signature: CallSignature {
+                    parameter_types,
+                    return_type,
+                },
+            },
+        ))
+    }
+
+    /// Returns the method reference in Smali notation, e.g. `La;->b(I)V`.
+    pub fn to_smali(&self) -> String {
+        format!(
+            "{}->{}{}",
+            self.object_type.to_descriptor(),
+            quote_identifier(&self.method_name),
+            self.call_signature.to_descriptor()
+        )
+    }
+}
+
+impl FromStr for MethodSignature {
+    type Err = String;
+
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        parse_either(data, Self::read, Self::read_jimple)
+    }
+}
+
 impl Display for MethodSignature {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let params = self
             .call_signature
             .parameter_types
             .iter()
-            .map(Type::get_name)
+            .map(Type::to_string)
             .collect::<Vec<_>>()
             .join(", ");
         write!(
             f,
             "{} {}.{}({params})",
-            self.call_signature.return_type, self.object_type, self.method_name
+            self.call_signature.return_type,
+            self.object_type,
+            jimple_identifier(&self.method_name)
         )
     }
 }
@@ -216,7 +518,7 @@ pub struct CallSite {
 
 impl CallSite {
     pub fn read(input: &Tokenizer) -> Result<(Tokenizer, Self), ParseError> {
-        let (input, name) = input.read_keyword()?;
+        let (input, name) = input.read_identifier()?;
         let mut input = input.expect_char('(')?;
         let mut params = Vec::new();
         while input.expect_char(')').is_err() {
@@ -243,6 +545,23 @@ impl CallSite {
     }
 }
 
+impl CallSite {
+    /// Returns the call site in Smali notation, e.g. `name("a")@La;->b()V`.
+    pub fn to_smali(&self) -> String {
+        let params = self
+            .params
+            .iter()
+            .map(Literal::to_smali)
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "{}({params})@{}",
+            quote_identifier(&self.name),
+            self.method.to_smali()
+        )
+    }
+}
+
 impl Display for CallSite {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
         let params = self
@@ -251,7 +570,12 @@ impl Display for CallSite {
             .map(Literal::to_string)
             .collect::<Vec<_>>()
             .join(", ");
-        write!(f, "{}({params})@{}", self.name, self.method)
+        write!(
+            f,
+            "{}({params})@{}",
+            jimple_identifier(&self.name),
+            self.method
+        )
     }
 }
 
@@ -282,6 +606,97 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn unicode_types() -> Result<(), ParseErrorDisplayed> {
+        let input = tokenizer("Lкот/äö$Ω;[Lp/a\u{200b}b/c-d;");
+
+        let (input, r#type) = Type::read(&input)?;
+        assert_eq!(r#type, Type::Object("кот.äö$Ω".to_string()));
+        assert_eq!(r#type.to_string(), "кот.äö$Ω");
+        assert_eq!(r#type.to_descriptor(), "Lкот/äö$Ω;");
+
+        let (input, r#type) = Type::read(&input)?;
+        assert_eq!(
+            r#type,
+            Type::Array(Box::new(Type::Object("p.a\u{200b}b.c-d".to_string())))
+        );
+        assert_eq!(r#type.to_string(), "p.'a\\u200bb'.'c-d'[]");
+        assert_eq!(r#type.to_descriptor(), "[Lp/a\u{200b}b/c-d;");
+        assert!(input.expect_eof().is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn array_accessors() -> Result<(), ParseErrorDisplayed> {
+        let (_, r#type) = Type::read(&tokenizer("[[[Ljava/lang/String;"))?;
+        assert_eq!(r#type.dimensions(), 3);
+        assert_eq!(
+            r#type.base_type(),
+            &Type::Object("java.lang.String".to_string())
+        );
+        assert_eq!(r#type.element_type().map(Type::dimensions), Some(2));
+        assert_eq!(r#type.to_string(), "java.lang.String[][][]");
+        assert_eq!(r#type.to_descriptor(), "[[[Ljava/lang/String;");
+
+        assert_eq!(Type::Int.dimensions(), 0);
+        assert_eq!(Type::Int.base_type(), &Type::Int);
+        assert_eq!(Type::Int.element_type(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn descriptor_round_trip() {
+        for descriptor in ["Z", "J", "[D", "Ljava/lang/String;", "[[La/b$c;"] {
+            let parsed = descriptor.parse::<Type>().unwrap();
+            assert_eq!(parsed.to_descriptor(), descriptor);
+        }
+        assert!("Ljava/lang/String".parse::<Type>().is_err());
+        assert!("[X".parse::<Type>().is_err());
+
+        let signature = "La;->b(I[Ljava/lang/String;)V"
+            .parse::<MethodSignature>()
+            .unwrap();
+        assert_eq!(signature.to_smali(), "La;->b(I[Ljava/lang/String;)V");
+        let signature = "La;->b:J".parse::<FieldSignature>().unwrap();
+        assert_eq!(signature.to_smali(), "La;->b:J");
+    }
+
+    #[test]
+    fn read_jimple_signatures() {
+        assert_eq!(
+            "java.lang.String[][]".parse::<Type>(),
+            Ok(Type::Array(Box::new(Type::Array(Box::new(Type::Object(
+                "java.lang.String".to_string()
+            ))))))
+        );
+        assert_eq!("boolean".parse::<Type>(), Ok(Type::Bool));
+
+        for (jimple, smali) in [
+            (
+                "<void a.b.c(int, java.lang.String[])>",
+                "La/b;->c(I[Ljava/lang/String;)V",
+            ),
+            ("void a.<init>()", "La;-><init>()V"),
+            ("<int a.'b-c'('x y'.z)>", "La;->`b-c`(Lx y/z;)I"),
+        ] {
+            let parsed = jimple.parse::<MethodSignature>().unwrap();
+            assert_eq!(parsed, smali.parse::<MethodSignature>().unwrap());
+            assert_eq!(format!("<{parsed}>").parse::<MethodSignature>(), Ok(parsed));
+        }
+
+        let field = "<java.lang.String a$b.'\\u200bc'>"
+            .parse::<FieldSignature>()
+            .unwrap();
+        assert_eq!(field.object_type, Type::Object("a$b".to_string()));
+        assert_eq!(field.field_name, "\u{200b}c");
+        assert_eq!(field.to_string().parse::<FieldSignature>(), Ok(field));
+
+        assert!("<void a.b(int>".parse::<MethodSignature>().is_err());
+        assert!("<void b()>".parse::<MethodSignature>().is_err());
+    }
+
     #[test]
     fn read_field_signature() -> Result<(), ParseErrorDisplayed> {
         let input = tokenizer(" Lev/n;->g:Ljava/lang/String;");
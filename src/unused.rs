@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use crate::access_flag::AccessFlag;
+use crate::class::Class;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::literal::Literal;
+use crate::r#type::{CallSignature, FieldSignature, MethodSignature, Type};
+
+/// Members used implicitly by Java serialization.
+const SERIALIZATION_MEMBERS: [&str; 6] = [
+    "serialVersionUID",
+    "writeObject",
+    "readObject",
+    "readObjectNoData",
+    "writeReplace",
+    "readResolve",
+];
+
+fn is_candidate(flags: &[AccessFlag], name: &str) -> bool {
+    !flags.contains(&AccessFlag::Public)
+        && !flags.contains(&AccessFlag::Protected)
+        && !flags.contains(&AccessFlag::Synthetic)
+        && name != "<clinit>"
+        && !SERIALIZATION_MEMBERS.contains(&name)
+}
+
+/// A private or package-private class member.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Field {
+        signature: FieldSignature,
+        private: bool,
+    },
+    Method {
+        signature: MethodSignature,
+        private: bool,
+    },
+}
+
+impl Member {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Field { signature, .. } => &signature.field_name,
+            Self::Method { signature, .. } => &signature.method_name,
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        match self {
+            Self::Field { private, .. } | Self::Method { private, .. } => *private,
+        }
+    }
+}
+
+impl std::fmt::Display for Member {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let signature = match self {
+            Self::Field { signature, .. } => signature.to_string(),
+            Self::Method { signature, .. } => signature.to_string(),
+        };
+        if self.is_private() {
+            write!(f, "private {signature}")
+        } else {
+            write!(f, "{signature}")
+        }
+    }
+}
+
+/// Members and classes referred to by code, along with the string constants.
+#[derive(Debug, Default)]
+pub struct References {
+    methods: HashSet<MethodSignature>,
+    /// Method names and signatures called on any type, non-private methods can be called via
+    /// subclasses
+    method_names: HashSet<(String, CallSignature)>,
+    fields: HashSet<FieldSignature>,
+    field_names: HashSet<(String, Type)>,
+    /// Types referred to by other classes, including superclasses and interfaces
+    types: HashSet<Type>,
+    strings: HashSet<String>,
+}
+
+impl References {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add_method(&mut self, method: &MethodSignature) {
+        self.method_names
+            .insert((method.method_name.clone(), method.call_signature.clone()));
+        self.methods.insert(method.clone());
+    }
+
+    /// Records the members and classes a class refers to, along with its string constants.
+    pub fn add_class(&mut self, class: &Class) {
+        let mut types = HashSet::new();
+        types.extend(class.super_class.iter().cloned());
+        types.extend(class.interfaces.iter().cloned());
+        for method in &class.methods {
+            for instruction in &method.instructions {
+                let Instruction::Command { parameters, .. } = instruction else {
+                    continue;
+                };
+                for parameter in parameters {
+                    match parameter {
+                        CommandParameter::Method(method)
+                        | CommandParameter::Literal(Literal::MethodHandle(_, method)) => {
+                            types.insert(method.object_type.clone());
+                            self.add_method(method);
+                        }
+                        CommandParameter::CallSite(call_site) => self.add_method(&call_site.method),
+                        CommandParameter::Field(field) => {
+                            self.field_names
+                                .insert((field.field_name.clone(), field.field_type.clone()));
+                            types.insert(field.object_type.clone());
+                            self.fields.insert(field.clone());
+                        }
+                        CommandParameter::Type(parameter_type) => {
+                            let mut element_type = parameter_type;
+                            while let Some(inner) = element_type.element_type() {
+                                element_type = inner;
+                            }
+                            types.insert(element_type.clone());
+                        }
+                        CommandParameter::Literal(Literal::String(value)) => {
+                            self.strings.insert(value.to_string());
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        types.remove(&class.class_type);
+        self.types.extend(types);
+    }
+
+    pub fn contains_member(&self, member: &Member) -> bool {
+        match member {
+            Member::Field {
+                signature: field, ..
+            } => {
+                self.fields.contains(field)
+                    || (!member.is_private()
+                        && self
+                            .field_names
+                            .contains(&(field.field_name.clone(), field.field_type.clone())))
+            }
+            Member::Method {
+                signature: method, ..
+            } => {
+                self.methods.contains(method)
+                    || (!member.is_private()
+                        && self
+                            .method_names
+                            .contains(&(method.method_name.clone(), method.call_signature.clone())))
+            }
+        }
+    }
+
+    /// Checks whether a type is referred to, array types count as references to their elements.
+    pub fn contains_type(&self, class_type: &Type) -> bool {
+        self.types.contains(class_type)
+    }
+
+    pub fn contains_string(&self, value: &str) -> bool {
+        self.strings.contains(value)
+    }
+}
+
+/// Private and package-private members that code never refers to. Members whose name appears
+/// in a string constant are listed separately, these might be accessed via reflection.
+#[derive(Debug, Default)]
+pub struct UnusedMemberReport {
+    members: Vec<Member>,
+    references: References,
+}
+
+impl UnusedMemberReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        self.add_declarations(class);
+        self.add_references(class);
+    }
+
+    /// Records the members of a class that might be unused.
+    pub fn add_declarations(&mut self, class: &Class) {
+        for field in &class.fields {
+            if is_candidate(&field.visibility, &field.name) {
+                self.members.push(Member::Field {
+                    signature: FieldSignature {
+                        object_type: class.class_type.clone(),
+                        field_name: field.name.clone(),
+                        field_type: field.field_type.clone(),
+                    },
+                    private: field.visibility.contains(&AccessFlag::Private),
+                });
+            }
+        }
+        for method in &class.methods {
+            if is_candidate(&method.visibility, &method.name) {
+                self.members.push(Member::Method {
+                    signature: method.get_signature(&class.class_type),
+                    private: method.visibility.contains(&AccessFlag::Private),
+                });
+            }
+        }
+    }
+
+    /// Records the members a class refers to, along with its string constants.
+    pub fn add_references(&mut self, class: &Class) {
+        self.references.add_class(class);
+    }
+
+    /// Returns the members never referenced and those referenced by name only.
+    pub fn get_unused(&self) -> (Vec<&Member>, Vec<&Member>) {
+        self.members
+            .iter()
+            .filter(|member| !self.references.contains_member(member))
+            .partition(|member| !self.references.contains_string(member.get_name()))
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        let (unused, by_name) = self.get_unused();
+        if !unused.is_empty() {
+            writeln!(output, "Never referenced:")?;
+        }
+        for member in unused {
+            writeln!(output, "    <{member}>")?;
+        }
+        if !by_name.is_empty() {
+            writeln!(output, "Referenced by name only, possibly via reflection:")?;
+        }
+        for member in by_name {
+            writeln!(output, "    <{member}>")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn unused_members() -> Result<(), ParseErrorDisplayed> {
+        let class = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field private static final serialVersionUID:J = 0x1L
+
+                .field private count:I
+
+                .field private unused:Ljava/lang/String;
+
+                .field key:Ljava/lang/String;
+
+                .method public run()V
+                    .locals 1
+                    iget v0, p0, La;->count:I
+                    invoke-direct {p0}, La;->helper()V
+                    const-string v0, "hidden"
+                    return-void
+                .end method
+
+                .method private helper()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method private decoy()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method private hidden()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method inherited()V
+                    .locals 0
+                    return-void
+                .end method
+            "#,
+        )?;
+        let subclass = read_optimized_class(
+            r#"
+                .class public Lb;
+                .super La;
+
+                .method public run()V
+                    .locals 1
+                    invoke-virtual {p0}, Lb;->inherited()V
+                    iget-object v0, p0, Lb;->key:Ljava/lang/String;
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut report = UnusedMemberReport::new();
+        report.add_class(&class);
+        report.add_class(&subclass);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"Never referenced:
+    <private java.lang.String a.unused>
+    <private void a.decoy()>
+Referenced by name only, possibly via reflection:
+    <private void a.hidden()>
+"#
+        );
+
+        Ok(())
+    }
+}
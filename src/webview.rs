@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::class::Class;
+use crate::instruction::ResultType;
+use crate::literal::Literal;
+use crate::method::Method;
+use crate::r#type::{MethodSignature, Type};
+
+const WEBVIEW_CLASS: &str = "android.webkit.WebView";
+const WEBSETTINGS_CLASS: &str = "android.webkit.WebSettings";
+const JAVASCRIPT_INTERFACE_ANNOTATION: &str = "android.webkit.JavascriptInterface";
+
+/// `WebSettings` methods that widen the attack surface when passed `true`.
+const RISKY_SETTINGS: [&str; 5] = [
+    "setJavaScriptEnabled",
+    "setAllowFileAccess",
+    "setAllowContentAccess",
+    "setAllowFileAccessFromFileURLs",
+    "setAllowUniversalAccessFromFileURLs",
+];
+
+/// A call exposing a Java object to the JavaScript code of a WebView.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JavascriptBridge {
+    /// Name of the object in JavaScript if it is a constant
+    pub name: Option<String>,
+    /// Type of the exposed object if known
+    pub interface_type: Option<Type>,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+/// A `WebSettings` call enabling JavaScript or file access.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskySetting {
+    pub setting: String,
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+}
+
+fn is_true(value: Option<&ResultType>) -> bool {
+    matches!(
+        value,
+        Some(ResultType::Literal(Literal::Bool(true) | Literal::Int(1)))
+    )
+}
+
+impl Method {
+    /// Finds `addJavascriptInterface` calls and risky `WebSettings` calls in the method, using
+    /// the known argument values to determine the exposed object type, its name and the settings
+    /// values.
+    fn find_webview_calls(&self, class_type: &Type) -> (Vec<JavascriptBridge>, Vec<RiskySetting>) {
+        let mut bridges = Vec::new();
+        let mut settings = Vec::new();
+        for call in self.get_calls(class_type) {
+            let class_name = call.method.object_type.get_name();
+            if class_name == WEBVIEW_CLASS && call.method.method_name == "addJavascriptInterface" {
+                bridges.push(JavascriptBridge {
+                    name: match call.get_parameter(1) {
+                        Some(ResultType::Literal(Literal::String(name))) => Some(name.to_string()),
+                        _ => None,
+                    },
+                    interface_type: match call.get_parameter(0) {
+                        Some(ResultType::Type(object_type)) => Some(object_type.clone()),
+                        _ => None,
+                    },
+                    location: self.get_signature(class_type),
+                    line: call.line,
+                });
+            } else if class_name == WEBSETTINGS_CLASS
+                && RISKY_SETTINGS.contains(&call.method.method_name.as_str())
+                && is_true(call.get_parameter(0))
+            {
+                settings.push(RiskySetting {
+                    setting: call.method.method_name.clone(),
+                    location: self.get_signature(class_type),
+                    line: call.line,
+                });
+            }
+        }
+        (bridges, settings)
+    }
+
+    fn is_javascript_interface(&self) -> bool {
+        self.annotations.iter().any(|annotation| {
+            annotation.annotation_type.get_name() == JAVASCRIPT_INTERFACE_ANNOTATION
+        })
+    }
+}
+
+/// The attack surface exposed through WebViews: JavaScript bridges, the methods callable from
+/// JavaScript and settings enabling JavaScript or file access.
+#[derive(Debug, Default)]
+pub struct WebViewReport {
+    bridges: Vec<JavascriptBridge>,
+    exposed: BTreeMap<String, (Type, Vec<MethodSignature>)>,
+    settings: Vec<RiskySetting>,
+}
+
+impl WebViewReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_class(&mut self, class: &Class) {
+        for method in &class.methods {
+            if method.is_javascript_interface() {
+                self.exposed
+                    .entry(class.class_type.to_string())
+                    .or_insert_with(|| (class.class_type.clone(), Vec::new()))
+                    .1
+                    .push(method.get_signature(&class.class_type));
+            }
+
+            let (mut bridges, mut settings) = method.find_webview_calls(&class.class_type);
+            self.bridges.append(&mut bridges);
+            self.settings.append(&mut settings);
+        }
+    }
+
+    pub fn get_bridges(&self) -> &[JavascriptBridge] {
+        &self.bridges
+    }
+
+    fn write_exposed(
+        output: &mut dyn Write,
+        methods: Option<&(Type, Vec<MethodSignature>)>,
+    ) -> Result<(), std::io::Error> {
+        match methods {
+            Some((_, methods)) => {
+                for method in methods {
+                    writeln!(output, "        <{method}>")?;
+                }
+            }
+            None => writeln!(output, "        no @JavascriptInterface methods found")?,
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, output: &mut dyn Write) -> Result<(), std::io::Error> {
+        writeln!(output, "JavaScript bridges:")?;
+        for bridge in &self.bridges {
+            let name = match &bridge.name {
+                Some(name) => format!("\"{name}\""),
+                None => "unknown name".to_string(),
+            };
+            let interface_type = match &bridge.interface_type {
+                Some(interface_type) => interface_type.to_string(),
+                None => "unknown type".to_string(),
+            };
+            write!(
+                output,
+                "    {name} ({interface_type}) added in <{}>",
+                bridge.location
+            )?;
+            if let Some(line) = bridge.line {
+                write!(output, ", line {line}")?;
+            }
+            writeln!(output)?;
+            if bridge.interface_type.is_some() {
+                Self::write_exposed(output, self.exposed.get(&interface_type))?;
+            }
+        }
+
+        let unmatched = self
+            .exposed
+            .iter()
+            .filter(|(_, (class_type, _))| {
+                !self
+                    .bridges
+                    .iter()
+                    .any(|bridge| bridge.interface_type.as_ref() == Some(class_type))
+            })
+            .collect::<Vec<_>>();
+        if !unmatched.is_empty() {
+            writeln!(output, "Classes with @JavascriptInterface methods:")?;
+            for (name, methods) in unmatched {
+                writeln!(output, "    {name}")?;
+                Self::write_exposed(output, Some(methods))?;
+            }
+        }
+
+        if !self.settings.is_empty() {
+            writeln!(output, "WebView settings enabled:")?;
+            for setting in &self.settings {
+                write!(
+                    output,
+                    "    {}(true) in <{}>",
+                    setting.setting, setting.location
+                )?;
+                if let Some(line) = setting.line {
+                    write!(output, ", line {line}")?;
+                }
+                writeln!(output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_optimized_class;
+
+    #[test]
+    fn webview_report() -> Result<(), ParseErrorDisplayed> {
+        let main = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method public init(Landroid/webkit/WebView;)V
+                    .locals 2
+                    .line 5
+                    invoke-virtual {p1}, Landroid/webkit/WebView;->getSettings()Landroid/webkit/WebSettings;
+                    move-result-object v0
+                    const/4 v1, 0x1
+                    invoke-virtual {v0, v1}, Landroid/webkit/WebSettings;->setJavaScriptEnabled(Z)V
+                    const/4 v1, 0x0
+                    invoke-virtual {v0, v1}, Landroid/webkit/WebSettings;->setAllowFileAccess(Z)V
+                    .line 6
+                    new-instance v0, La$Bridge;
+                    invoke-direct {v0}, La$Bridge;-><init>()V
+                    const-string v1, "Android"
+                    invoke-virtual {p1, v0, v1}, Landroid/webkit/WebView;->addJavascriptInterface(Ljava/lang/Object;Ljava/lang/String;)V
+                    return-void
+                .end method
+            "#,
+        )?;
+        let bridge = read_optimized_class(
+            r#"
+                .class public La$Bridge;
+                .super Ljava/lang/Object;
+
+                .method public getToken()Ljava/lang/String;
+                    .locals 1
+                    .annotation runtime Landroid/webkit/JavascriptInterface;
+                    .end annotation
+                    const-string v0, "secret"
+                    return-object v0
+                .end method
+
+                .method public internal()V
+                    .locals 1
+                    return-void
+                .end method
+            "#,
+        )?;
+        let unused = read_optimized_class(
+            r#"
+                .class public Lb;
+                .super Ljava/lang/Object;
+
+                .method public run()V
+                    .locals 1
+                    .annotation runtime Landroid/webkit/JavascriptInterface;
+                    .end annotation
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut report = WebViewReport::new();
+        report.add_class(&main);
+        report.add_class(&bridge);
+        report.add_class(&unused);
+
+        let mut output = Vec::new();
+        report.write(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"JavaScript bridges:
+    "Android" (a$Bridge) added in <void a.init(android.webkit.WebView)>, line 6
+        <java.lang.String a$Bridge.getToken()>
+Classes with @JavascriptInterface methods:
+    b
+        <void b.run()>
+WebView settings enabled:
+    setJavaScriptEnabled(true) in <void a.init(android.webkit.WebView)>, line 5
+"#
+        );
+
+        Ok(())
+    }
+}
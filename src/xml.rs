@@ -0,0 +1,24 @@
+/// Decodes the XML entities apktool produces in attribute values.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Extracts an attribute value from the text of an XML tag.
+pub fn get_attribute(tag: &str, name: &str) -> Option<String> {
+    let pattern = format!("{name}=\"");
+    let mut rest = tag;
+    while let Some(index) = rest.find(&pattern) {
+        let preceded_by_space = rest[..index].ends_with(char::is_whitespace);
+        rest = &rest[index + pattern.len()..];
+        if preceded_by_space {
+            let (value, _) = rest.split_once('"')?;
+            return Some(unescape(value));
+        }
+    }
+    None
+}
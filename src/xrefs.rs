@@ -0,0 +1,404 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+use schemars::JsonSchema;
+use serde::ser::{Error, SerializeSeq};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::class::Class;
+use crate::index::Index;
+use crate::instruction::{CommandParameter, Instruction};
+use crate::method::Method;
+use crate::r#type::{FieldSignature, MethodSignature};
+use crate::schema::{write_json_pretty, JsonOutput, SchemaVersion};
+
+/// Number of callers listed in method headers, others are merely counted.
+const MAX_LISTED_CALLERS: usize = 3;
+
+/// Whole-program index of the methods calling each method, as far as calls name the method
+/// directly. Calls via subclasses or interfaces are attributed to the type named in the call.
+#[derive(Debug)]
+pub struct CallerIndex {
+    index: Index,
+}
+
+impl CallerIndex {
+    pub fn new(index: Index) -> Self {
+        Self { index }
+    }
+
+    pub fn add_class(&mut self, class: &Class) -> Result<(), std::io::Error> {
+        for method in &class.methods {
+            let caller = format!("{}.{}()", class.class_type, method.name);
+            for call in method.get_calls(&class.class_type) {
+                self.index.insert(&call.method.to_string(), &caller)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the methods calling the given method, sorted and without duplicates.
+    pub fn get_callers(&self, signature: &MethodSignature) -> Result<Vec<String>, std::io::Error> {
+        let mut callers = self.index.get(&signature.to_string())?;
+        callers.dedup();
+        Ok(callers)
+    }
+
+    fn get_header(&self, method: &Method, signature: &MethodSignature) -> Option<String> {
+        let callers = match self.get_callers(signature) {
+            Ok(callers) => callers,
+            Err(error) => {
+                eprintln!("Failed reading caller index: {error}");
+                return None;
+            }
+        };
+        if callers.is_empty() {
+            // Static initializers are never called explicitly
+            return (method.name != "<clinit>").then(|| "no callers found".to_string());
+        }
+        let mut header = format!(
+            "called from: {}",
+            callers
+                .iter()
+                .take(MAX_LISTED_CALLERS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if callers.len() > MAX_LISTED_CALLERS {
+            header.push_str(&format!(" (+{} more)", callers.len() - MAX_LISTED_CALLERS));
+        }
+        Some(header)
+    }
+
+    /// Adds a comment listing the callers to each method of the class.
+    pub fn apply(&self, class: &mut Class) {
+        for method in &mut class.methods {
+            let signature = method.get_signature(&class.class_type);
+            if let Some(header) = self.get_header(method, &signature) {
+                method.comments.push(header);
+            }
+        }
+    }
+}
+
+/// Method or field whose references are looked up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Method(MethodSignature),
+    Field(FieldSignature),
+}
+
+impl FromStr for Member {
+    type Err = String;
+
+    /// Parses a method or field signature in Smali or Jimple notation.
+    fn from_str(data: &str) -> Result<Self, Self::Err> {
+        data.parse().map(Self::Method).or_else(|method_error| {
+            data.parse().map(Self::Field).map_err(|field_error| {
+                if data.contains('(') {
+                    method_error
+                } else {
+                    field_error
+                }
+            })
+        })
+    }
+}
+
+impl Display for Member {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Self::Method(method) => write!(f, "{method}"),
+            Self::Field(field) => write!(f, "{field}"),
+        }
+    }
+}
+
+/// A command calling a method or accessing a field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub location: MethodSignature,
+    pub line: Option<i64>,
+    pub command: String,
+}
+
+/// Lists the commands of a class referencing a member, e.g. the calls of a method or the reads
+/// and writes of a field. Like with the caller index, references via subclasses aren't found.
+pub fn find_references(class: &Class, member: &Member) -> Vec<Reference> {
+    let mut result = Vec::new();
+    for method in &class.methods {
+        let mut line = None;
+        for instruction in &method.instructions {
+            if let Instruction::LineNumber(from, _) = instruction {
+                line = Some(*from);
+            }
+            let Instruction::Command {
+                command,
+                parameters,
+            } = instruction
+            else {
+                continue;
+            };
+            let matches = parameters
+                .iter()
+                .any(|parameter| match (parameter, member) {
+                    (CommandParameter::Method(called), Member::Method(method)) => called == method,
+                    (CommandParameter::Field(accessed), Member::Field(field)) => accessed == field,
+                    _ => false,
+                });
+            if matches {
+                result.push(Reference {
+                    location: method.get_signature(&class.class_type),
+                    line,
+                    command: command.clone(),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Writes a reference as `<location>, line N: command`.
+pub fn write_reference(output: &mut dyn Write, reference: &Reference) -> std::io::Result<()> {
+    write!(output, "<{}>", reference.location)?;
+    if let Some(line) = reference.line {
+        write!(output, ", line {line}")?;
+    }
+    writeln!(output, ": {}", reference.command)
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct ReferenceJson {
+    /// Signature of the method containing the command
+    location: String,
+    line: Option<i64>,
+    command: String,
+}
+
+impl From<&Reference> for ReferenceJson {
+    fn from(reference: &Reference) -> Self {
+        Self {
+            location: reference.location.to_string(),
+            line: reference.line,
+            command: reference.command.clone(),
+        }
+    }
+}
+
+/// References to a member written by the xref command.
+#[derive(Debug, Serialize, JsonSchema)]
+#[schemars(rename = "XrefsFile")]
+pub(crate) struct XrefsFile<R = Vec<ReferenceJson>> {
+    schema_version: SchemaVersion<{ JsonOutput::Xrefs.version() }>,
+    member: String,
+    references: R,
+}
+
+/// References recorded in an index, serialized as a list without reading them into memory.
+#[derive(Debug)]
+struct IndexedReferences<'a>(&'a Index);
+
+impl Serialize for IndexedReferences<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        let mut result = Ok(());
+        self.0
+            .for_each(|_, values| {
+                for value in values {
+                    if result.is_err() {
+                        return;
+                    }
+                    result = serde_json::from_str::<ReferenceJson>(value)
+                        .map_err(S::Error::custom)
+                        .and_then(|reference| seq.serialize_element(&reference));
+                }
+            })
+            .map_err(S::Error::custom)?;
+        result?;
+        seq.end()
+    }
+}
+
+/// Writes the references to a member as JSON.
+pub fn write_references_json(
+    output: &mut dyn Write,
+    member: &Member,
+    references: &[Reference],
+) -> std::io::Result<()> {
+    let references: Vec<_> = references.iter().map(ReferenceJson::from).collect();
+    write_json_pretty(
+        output,
+        &XrefsFile {
+            schema_version: SchemaVersion,
+            member: member.to_string(),
+            references,
+        },
+    )
+}
+
+/// Records a reference in an index, to be written by `write_indexed_references_json`. The
+/// references are written in the order of their numbers.
+pub fn add_reference_to_index(
+    index: &mut Index,
+    number: usize,
+    reference: &Reference,
+) -> std::io::Result<()> {
+    let value = serde_json::to_string(&ReferenceJson::from(reference))?;
+    index.insert(&format!("{number:020}"), &value)
+}
+
+/// Writes the references recorded in an index as JSON, in the same format as
+/// `write_references_json`.
+pub fn write_indexed_references_json(
+    output: &mut dyn Write,
+    member: &Member,
+    index: &Index,
+) -> std::io::Result<()> {
+    write_json_pretty(
+        output,
+        &XrefsFile {
+            schema_version: SchemaVersion,
+            member: member.to_string(),
+            references: IndexedReferences(index),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorDisplayed;
+    use crate::tokenizer::read_optimized_class;
+    use crate::tokenizer::Tokenizer;
+
+    #[test]
+    fn caller_comments() -> Result<(), ParseErrorDisplayed> {
+        let mut class = read_optimized_class(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .method static target()V
+                    .locals 0
+                    return-void
+                .end method
+
+                .method static b()V
+                    .locals 0
+                    invoke-static {}, La;->target()V
+                    invoke-static {}, La;->target()V
+                    return-void
+                .end method
+
+                .method static c()V
+                    .locals 0
+                    invoke-static {}, La;->target()V
+                    invoke-static {}, La;->b()V
+                    return-void
+                .end method
+
+                .method static d()V
+                    .locals 0
+                    invoke-static {}, La;->target()V
+                    return-void
+                .end method
+
+                .method static e()V
+                    .locals 0
+                    invoke-static {}, La;->target()V
+                    return-void
+                .end method
+            "#,
+        )?;
+
+        let mut callers = CallerIndex::new(Index::in_memory());
+        callers.add_class(&class).unwrap();
+        callers.apply(&mut class);
+
+        assert_eq!(
+            class.methods[0].comments,
+            vec!["called from: a.b(), a.c(), a.d() (+1 more)"]
+        );
+        assert_eq!(class.methods[1].comments, vec!["called from: a.c()"]);
+        assert_eq!(class.methods[2].comments, vec!["no callers found"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn member_references() -> Result<(), ParseErrorDisplayed> {
+        let input = Tokenizer::new(
+            r#"
+                .class public La;
+                .super Ljava/lang/Object;
+
+                .field static count:I
+
+                .method static run()V
+                    .locals 1
+                    .line 10
+                    sget v0, La;->count:I
+                    add-int/lit8 v0, v0, 0x1
+                    .line 11
+                    sput v0, La;->count:I
+                    invoke-static {}, La;->run()V
+                    return-void
+                .end method
+            "#
+            .trim()
+            .to_string(),
+            std::path::Path::new("dummy"),
+        );
+        let (_, class) = Class::read(&input)?;
+
+        let member = "La;->count:I".parse::<Member>().unwrap();
+        assert_eq!(member.to_string(), "int a.count");
+        let mut output = Vec::new();
+        for reference in find_references(&class, &member) {
+            write_reference(&mut output, &reference).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<void a.run()>, line 10: sget\n<void a.run()>, line 11: sput\n"
+        );
+
+        let mut output = Vec::new();
+        write_references_json(&mut output, &member, &find_references(&class, &member)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        JsonOutput::Xrefs.assert_valid(&output);
+        let output: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            output,
+            serde_json::json!({
+                "schema_version": 1,
+                "member": "int a.count",
+                "references": [
+                    {"location": "void a.run()", "line": 10, "command": "sget"},
+                    {"location": "void a.run()", "line": 11, "command": "sput"}
+                ]
+            })
+        );
+
+        // References written out to disk come back in their original order
+        let mut index = Index::on_disk(0).unwrap();
+        for (number, reference) in find_references(&class, &member).iter().enumerate().rev() {
+            add_reference_to_index(&mut index, number, reference).unwrap();
+        }
+        let mut indexed = Vec::new();
+        write_indexed_references_json(&mut indexed, &member, &index).unwrap();
+        let mut expected = Vec::new();
+        write_references_json(&mut expected, &member, &find_references(&class, &member)).unwrap();
+        assert_eq!(String::from_utf8(indexed), String::from_utf8(expected));
+
+        let member = "<void a.run()>".parse::<Member>().unwrap();
+        let references = find_references(&class, &member);
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].command, "invoke-static");
+
+        assert!("La;->run(".parse::<Member>().is_err());
+
+        Ok(())
+    }
+}
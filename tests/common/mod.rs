@@ -0,0 +1,76 @@
+//! Helpers shared by the integration tests running the aarf binary on the Smali corpus.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Returns the directory of the Smali corpus, laid out like apktool output.
+pub fn corpus_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join("input")
+}
+
+/// Returns a directory for test output, removing anything left there by a previous run.
+pub fn output_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Creates a command running the aarf binary.
+pub fn aarf() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_aarf"))
+}
+
+pub fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the files with the given extension in a directory and its subdirectories, sorted by
+/// path.
+pub fn find_files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    fn find(dir: &Path, extension: &str, result: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.is_dir() {
+                find(&path, extension, result);
+            } else if path.extension().is_some_and(|e| e == extension) {
+                result.push(path);
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    find(dir, extension, &mut result);
+    result.sort();
+    result
+}
+
+/// Prints the first line that differs between the expected and the actual file contents.
+pub fn print_difference(expected: &str, actual: &str) {
+    for (line, (expected, actual)) in expected.lines().zip(actual.lines()).enumerate() {
+        if expected != actual {
+            eprintln!("  line {}:\n  - {expected}\n  + {actual}", line + 1);
+            return;
+        }
+    }
+    eprintln!(
+        "  expected {} lines, got {}",
+        expected.lines().count(),
+        actual.lines().count()
+    );
+}
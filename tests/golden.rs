@@ -0,0 +1,565 @@
+//! Decompiles the Smali corpus in `tests/golden/input` and compares the result to the Jimple
+//! files in `tests/golden/expected`, the output of the stats command to `stats.txt` there. Run
+//! `cargo test --test golden -- --bless` to update the expected output after intentional changes.
+//!
+//! Further checks run the other ways of converting the corpus and compare their results to the
+//! same output. Each check reports its own failures, a failing check doesn't prevent the others
+//! from running.
+//!
+//! The test binary doubles as a stand-in for apktool: decoding a stand-in package, a file
+//! containing the path of the corpus, merely copies the corpus.
+
+mod common;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{ExitCode, Stdio};
+
+use common::{aarf, copy_dir, corpus_dir, find_files, output_dir, print_difference};
+
+/// Emulates `apktool decode --force --output <output> <input>` for a stand-in package.
+fn fake_apktool(args: &[String]) -> ExitCode {
+    let output = args
+        .iter()
+        .position(|arg| arg == "--output")
+        .and_then(|index| args.get(index + 1));
+    let (Some(output), Some(package)) = (output, args.last()) else {
+        eprintln!("Unexpected apktool parameters: {args:?}");
+        return ExitCode::FAILURE;
+    };
+    let Ok(input) = std::fs::read_to_string(package) else {
+        eprintln!("Failed reading package {package}");
+        return ExitCode::FAILURE;
+    };
+
+    // The parameters are recorded for checking the options passed on
+    let _ = std::fs::remove_dir_all(output);
+    match copy_dir(Path::new(&input), Path::new(output))
+        .and_then(|_| std::fs::write(Path::new(output).join("apktool-args.txt"), args.join("\n")))
+    {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Failed copying {input} to {output}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Removes the file header, it contains paths and versions that differ between runs.
+fn strip_header(data: &str) -> String {
+    match data.split_once("\n\n") {
+        Some((header, rest)) if header.lines().all(|line| line.starts_with("// ")) => {
+            rest.to_string()
+        }
+        _ => data.to_string(),
+    }
+}
+
+/// Runs the decompile command on a stand-in package for the corpus, using this binary as
+/// apktool.
+fn decompile(input: &Path, output: &Path, extra_args: &[&str]) -> bool {
+    let apktool = std::env::current_exe().expect("Failed locating test binary");
+    let package = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!(
+        "{}.apk",
+        input.file_name().unwrap().to_string_lossy()
+    ));
+    std::fs::write(&package, input.to_string_lossy().as_bytes())
+        .expect("Failed writing stand-in package");
+    aarf()
+        .arg("--apktool-path")
+        .arg(apktool)
+        .arg("decompile")
+        .args(extra_args)
+        .arg(package)
+        .arg(output)
+        .status()
+        .expect("Failed running aarf")
+        .success()
+}
+
+/// Copies the corpus into a fresh directory, returning the Smali files of the copy.
+fn copy_corpus(dir: &Path) -> Vec<PathBuf> {
+    copy_dir(&corpus_dir(), dir).expect("Failed copying the corpus");
+    find_files(dir, "smali")
+}
+
+/// Output of decompiling the corpus, the reference for the other checks.
+struct Reference {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl Reference {
+    /// Compares the Jimple files in a directory to the reference output, returning the number
+    /// of failures.
+    fn compare(&self, dir: &Path, description: &str) -> usize {
+        let files = find_files(dir, "jimple");
+        let mut failures = 0;
+        if files.len() != self.files.len() {
+            failures += 1;
+            eprintln!(
+                "Expected {} files {description}, got {}",
+                self.files.len(),
+                files.len()
+            );
+        }
+        for path in &files {
+            let relative = path.strip_prefix(dir).unwrap();
+            let expected = std::fs::read_to_string(self.dir.join(relative)).unwrap_or_default();
+            if strip_header(&std::fs::read_to_string(path).unwrap()) != strip_header(&expected) {
+                failures += 1;
+                eprintln!("Output differs {description} for {}", relative.display());
+            }
+        }
+        failures
+    }
+}
+
+/// Returns the directory of the expected output.
+fn expected_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join("expected")
+}
+
+/// Compares the output of decompiling the corpus to the expected files, or replaces the expected
+/// files by the output when blessing.
+fn check_expected_output(reference: &Reference, bless: bool) -> usize {
+    let expected_dir = expected_dir();
+
+    let mut failures = 0;
+    for actual_path in &reference.files {
+        let relative = actual_path.strip_prefix(&reference.dir).unwrap();
+        let expected_path = expected_dir.join(relative);
+        let actual = strip_header(&std::fs::read_to_string(actual_path).unwrap());
+        if bless {
+            std::fs::create_dir_all(expected_path.parent().unwrap()).unwrap();
+            std::fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        match std::fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => (),
+            Ok(expected) => {
+                failures += 1;
+                eprintln!("Output differs for {}:", relative.display());
+                print_difference(&expected, &actual);
+            }
+            Err(_) => {
+                failures += 1;
+                eprintln!("No expected output for {}", relative.display());
+            }
+        }
+    }
+
+    for expected_path in find_files(&expected_dir, "jimple") {
+        let relative = expected_path.strip_prefix(&expected_dir).unwrap();
+        if !reference.dir.join(relative).exists() {
+            if bless {
+                std::fs::remove_file(&expected_path).unwrap();
+            } else {
+                failures += 1;
+                eprintln!("Missing output for {}", relative.display());
+            }
+        }
+    }
+    failures
+}
+
+/// Compares the output of the stats command for the corpus to the expected file, or replaces the
+/// expected file by the output when blessing.
+fn check_stats(bless: bool) -> usize {
+    let expected_path = expected_dir().join("stats.txt");
+    let result = aarf()
+        .arg("stats")
+        .arg(corpus_dir())
+        .output()
+        .expect("Failed running aarf");
+    if !result.status.success() {
+        eprintln!("Running the stats command on the corpus failed");
+        return 1;
+    }
+    let actual = String::from_utf8_lossy(&result.stdout);
+    if bless {
+        std::fs::write(&expected_path, actual.as_bytes()).unwrap();
+        return 0;
+    }
+
+    match std::fs::read_to_string(&expected_path) {
+        Ok(expected) if expected == actual => 0,
+        Ok(expected) => {
+            eprintln!("Output differs for stats.txt:");
+            print_difference(&expected, &actual);
+            1
+        }
+        Err(_) => {
+            eprintln!("No expected output for stats.txt");
+            1
+        }
+    }
+}
+
+/// The run manifest marks the conversion as complete and no temporary files are left behind.
+fn check_manifest(reference: &Reference) -> usize {
+    let mut failures = 0;
+    let manifest =
+        std::fs::read_to_string(reference.dir.join("aarf-manifest.txt")).unwrap_or_default();
+    if !manifest.contains("\n# status: complete\n") {
+        failures += 1;
+        eprintln!("The run manifest doesn't mark the conversion as complete:\n{manifest}");
+    }
+    let partial_files = find_files(&reference.dir, "partial");
+    if !partial_files.is_empty() {
+        failures += 1;
+        eprintln!("Temporary files left behind: {partial_files:?}");
+    }
+    failures
+}
+
+/// Resuming keeps converted files and only recreates the missing one.
+fn check_resume(reference: &Reference) -> usize {
+    let [removed_path, kept_path, ..] = reference.files.as_slice() else {
+        eprintln!("Not enough files to check resuming");
+        return 1;
+    };
+
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let kept_time = modified(kept_path);
+    let removed = std::fs::read_to_string(removed_path).unwrap();
+    std::fs::remove_file(removed_path).unwrap();
+    if !decompile(&corpus_dir(), &reference.dir, &["--resume"]) {
+        // Restore the file for the checks comparing to the reference output
+        std::fs::write(removed_path, removed).unwrap();
+        eprintln!("Resuming the conversion failed");
+        return 1;
+    }
+
+    let mut failures = 0;
+    if std::fs::read_to_string(removed_path).ok().as_ref() != Some(&removed) {
+        failures += 1;
+        eprintln!("Resuming didn't recreate {}", removed_path.display());
+    }
+    if modified(kept_path) != kept_time {
+        failures += 1;
+        eprintln!("Resuming converted {} again", kept_path.display());
+    }
+    failures
+}
+
+/// Converting while apktool runs has to produce the same files.
+fn check_overlap_apktool(reference: &Reference) -> usize {
+    let output = output_dir("golden-overlapped");
+    if !decompile(&corpus_dir(), &output, &["--overlap-apktool"]) {
+        eprintln!("Decompiling the corpus with --overlap-apktool failed");
+        return 1;
+    }
+    reference.compare(&output, "with --overlap-apktool")
+}
+
+/// apktool options have to be passed on.
+fn check_apktool_options(_reference: &Reference) -> usize {
+    let output = output_dir("golden-passthrough");
+    let extra_args = [
+        "--no-res",
+        "--api-level",
+        "28",
+        "--apktool-arg=--only-main-classes",
+    ];
+    if !decompile(&corpus_dir(), &output, &extra_args) {
+        eprintln!("Decompiling the corpus with apktool options failed");
+        return 1;
+    }
+    let recorded = std::fs::read_to_string(output.join("apktool-args.txt")).unwrap_or_default();
+    let recorded = recorded.lines().collect::<Vec<_>>();
+    if !recorded.starts_with(&[
+        "decode",
+        "--force",
+        "--no-res",
+        "--api-level",
+        "28",
+        "--only-main-classes",
+        "--output",
+    ]) {
+        eprintln!("Unexpected apktool parameters: {recorded:?}");
+        return 1;
+    }
+    0
+}
+
+/// Decompiling a Smali directory mustn't run apktool, it has to produce the same files without
+/// modifying the directory.
+fn check_smali_directory(reference: &Reference) -> usize {
+    let input = corpus_dir();
+    let output = output_dir("golden-from-dir");
+    let result = aarf()
+        .args([
+            "--apktool-path",
+            "/nonexistent/apktool",
+            "decompile",
+            "--no-res",
+        ])
+        .arg(&input)
+        .arg(&output)
+        .output()
+        .expect("Failed running aarf");
+    if !result.status.success() {
+        eprintln!("Decompiling the corpus directory failed");
+        return 1;
+    }
+
+    let mut failures = 0;
+    if !String::from_utf8_lossy(&result.stderr).contains("ignoring --no-res") {
+        failures += 1;
+        eprintln!("Decompiling the corpus directory didn't warn about ignored apktool options");
+    }
+    let input_outputs = find_files(&input, "jimple");
+    if !input_outputs.is_empty() {
+        failures += 1;
+        eprintln!("Decompiling the corpus directory wrote into it: {input_outputs:?}");
+    }
+    failures + reference.compare(&output, "from the corpus directory")
+}
+
+/// An output directory within the Smali directory mustn't be copied into itself.
+fn check_nested_output(reference: &Reference) -> usize {
+    let input = output_dir("golden-nested");
+    copy_corpus(&input);
+    let output = input.join("out");
+    let status = aarf()
+        .args(["--apktool-path", "/nonexistent/apktool", "decompile"])
+        .arg(&input)
+        .arg(&output)
+        .status()
+        .expect("Failed running aarf");
+    if !status.success() {
+        eprintln!("Decompiling into a directory within the corpus directory failed");
+        return 1;
+    }
+
+    let mut failures = 0;
+    if output.join("out").exists() {
+        failures += 1;
+        eprintln!(
+            "Decompiling into {} copied it into itself",
+            output.display()
+        );
+    }
+    failures + reference.compare(&output, "in the directory within the corpus directory")
+}
+
+/// Converting existing Smali files without apktool has to produce the same files.
+fn check_convert(reference: &Reference) -> usize {
+    let dir = output_dir("golden-converted");
+    copy_corpus(&dir);
+    let status = aarf()
+        .arg("convert")
+        .arg(&dir)
+        .status()
+        .expect("Failed running aarf");
+    if !status.success() {
+        eprintln!("Converting the corpus with the convert command failed");
+        return 1;
+    }
+    reference.compare(&dir, "with the convert command")
+}
+
+/// Piping a file through the convert command has to produce the same output, including
+/// framework constants that don't depend on other files.
+fn check_convert_stdin(reference: &Reference) -> usize {
+    let input = corpus_dir();
+    let mut failures = 0;
+    for path in find_files(&input, "smali") {
+        let relative = path.strip_prefix(&input).unwrap().with_extension("jimple");
+        let mut child = aarf()
+            .args(["convert", "--api-level", "28", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Failed running aarf");
+        let smali = std::fs::read(&path).unwrap();
+        child.stdin.take().unwrap().write_all(&smali).unwrap();
+        let piped = child.wait_with_output().unwrap();
+        let expected = std::fs::read_to_string(reference.dir.join(&relative)).unwrap_or_default();
+        if !piped.status.success()
+            || strip_header(&String::from_utf8_lossy(&piped.stdout)) != strip_header(&expected)
+        {
+            failures += 1;
+            eprintln!("Output differs when piping {}", relative.display());
+        }
+        if !String::from_utf8_lossy(&piped.stderr).contains("ignoring --no-res") {
+            failures += 1;
+            eprintln!("Piping a file didn't warn about ignored apktool options");
+        }
+    }
+    failures
+}
+
+/// Appends garbage to the first Smali file of a copy of the corpus, returning all Smali files
+/// of the copy.
+fn break_corpus(dir: &Path) -> Vec<PathBuf> {
+    let input_files = copy_corpus(dir);
+    let mut data = std::fs::read_to_string(&input_files[0]).unwrap();
+    data.push_str("garbage\n");
+    std::fs::write(&input_files[0], data).unwrap();
+    input_files
+}
+
+/// A broken file stops the conversion unless --keep-going is given.
+fn check_broken_file(_reference: &Reference) -> usize {
+    let input = output_dir("golden-broken-input");
+    let output = output_dir("golden-broken");
+    let input_files = break_corpus(&input);
+
+    let mut failures = 0;
+    if decompile(&input, &output, &[]) {
+        failures += 1;
+        eprintln!("Decompiling a broken file didn't fail");
+    }
+    if decompile(&input, &output, &["--keep-going"]) {
+        failures += 1;
+        eprintln!("Decompiling a broken file with --keep-going didn't fail");
+    }
+    let output_files = find_files(&output, "jimple");
+    if output_files.len() + 1 != input_files.len() {
+        failures += 1;
+        eprintln!(
+            "Expected {} files with --keep-going, got {}",
+            input_files.len() - 1,
+            output_files.len()
+        );
+    }
+    let manifest = std::fs::read_to_string(output.join("aarf-manifest.txt")).unwrap_or_default();
+    if manifest.matches("\nfailed\t").count() != 1 {
+        failures += 1;
+        eprintln!("The run manifest doesn't list the broken file:\n{manifest}");
+    }
+    failures
+}
+
+/// The check command lists the broken file only.
+fn check_check_command(_reference: &Reference) -> usize {
+    let input = output_dir("golden-checked");
+    let input_files = break_corpus(&input);
+    let checked = aarf()
+        .arg("check")
+        .arg(&input)
+        .output()
+        .expect("Failed running aarf");
+    let expected = format!("{}:", input_files[0].display());
+    let listed = String::from_utf8_lossy(&checked.stdout).to_string();
+    if checked.status.success() || listed.lines().count() != 1 || !listed.starts_with(&expected) {
+        eprintln!("The check command didn't list the broken file only:\n{listed}");
+        return 1;
+    }
+    0
+}
+
+/// An output file that cannot be written is listed as failed with --keep-going.
+fn check_unwritable_output(_reference: &Reference) -> usize {
+    let dir = output_dir("golden-unwritable");
+    let input_files = copy_corpus(&dir);
+    std::fs::create_dir(input_files[0].with_extension("jimple")).unwrap();
+
+    let status = aarf()
+        .args(["convert", "--keep-going"])
+        .arg(&dir)
+        .status()
+        .expect("Failed running aarf");
+    let mut failures = 0;
+    if status.success() {
+        failures += 1;
+        eprintln!("Converting with an unwritable output file didn't fail");
+    }
+    let mut written_files = find_files(&dir, "jimple");
+    written_files.retain(|path| path.is_file());
+    if written_files.len() + 1 != input_files.len() {
+        failures += 1;
+        eprintln!(
+            "Expected {} files with an unwritable output file, got {}",
+            input_files.len() - 1,
+            written_files.len()
+        );
+    }
+    let manifest = std::fs::read_to_string(dir.join("aarf-manifest.txt")).unwrap_or_default();
+    if manifest.matches("\nfailed\t").count() != 1 {
+        failures += 1;
+        eprintln!("The run manifest doesn't list the unwritable file:\n{manifest}");
+    }
+    let partial_files = find_files(&dir, "partial");
+    if !partial_files.is_empty() {
+        failures += 1;
+        eprintln!("Temporary files left behind: {partial_files:?}");
+    }
+    failures
+}
+
+/// A check returning the number of failures it found.
+type Check = fn(&Reference) -> usize;
+
+/// Checks comparing other ways of converting the corpus to the reference output, in the order
+/// they run.
+const CHECKS: [(&str, Check); 11] = [
+    ("manifest", check_manifest),
+    ("resume", check_resume),
+    ("overlap-apktool", check_overlap_apktool),
+    ("apktool-options", check_apktool_options),
+    ("smali-directory", check_smali_directory),
+    ("nested-output", check_nested_output),
+    ("convert", check_convert),
+    ("convert-stdin", check_convert_stdin),
+    ("broken-file", check_broken_file),
+    ("check-command", check_check_command),
+    ("unwritable-output", check_unwritable_output),
+];
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|arg| arg == "decode") {
+        return fake_apktool(&args);
+    }
+    let bless = args.iter().any(|arg| arg == "--bless");
+
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    if !decompile(&corpus_dir(), &output, &[]) {
+        eprintln!("Decompiling the corpus failed");
+        return ExitCode::FAILURE;
+    }
+    let reference = Reference {
+        files: find_files(&output, "jimple"),
+        dir: output,
+    };
+
+    // The Jimple files along with the stats output
+    let golden_files = reference.files.len() + 1;
+    let failures = check_expected_output(&reference, bless) + check_stats(bless);
+    if bless {
+        println!("Updated expected output for {golden_files} files");
+        return ExitCode::SUCCESS;
+    }
+    if failures > 0 {
+        eprintln!("{failures} of {golden_files} golden files failed, run `cargo test --test golden -- --bless` to update them if the changes are intentional");
+    } else {
+        println!("{golden_files} golden files match");
+    }
+
+    let mut failed_checks = Vec::new();
+    for (name, check) in CHECKS {
+        let failures = check(&reference);
+        if failures > 0 {
+            eprintln!("Check {name} failed with {failures} failures");
+            failed_checks.push(name);
+        }
+    }
+    if !failed_checks.is_empty() {
+        eprintln!("Failed checks: {}", failed_checks.join(", "));
+    }
+
+    if failures > 0 || !failed_checks.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        println!("{} checks passed", CHECKS.len());
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,165 @@
+//! Parses the Smali corpus in `tests/golden/input` and writes it back as Smali, verifying that
+//! writing the result again produces identical output.
+//!
+//! If `AARF_SMALI_JAR` and `AARF_BAKSMALI_JAR` point to the smali and baksmali packages, both the
+//! original and the round-tripped corpus are additionally assembled into dex files and
+//! disassembled again. The disassembly is canonical, so any difference means that aarf lost or
+//! altered information while parsing.
+
+mod common;
+
+use std::path::Path;
+use std::process::{Command, ExitCode};
+
+use common::{aarf, copy_dir, corpus_dir, find_files, output_dir, print_difference};
+
+/// Compares the files in two directories, returns the number of differing files.
+fn compare_dirs(expected_dir: &Path, actual_dir: &Path, extension: &str) -> usize {
+    let expected_files = find_files(expected_dir, extension);
+    let actual_files = find_files(actual_dir, extension);
+
+    let mut failures = 0;
+    for expected_path in &expected_files {
+        let relative = expected_path.strip_prefix(expected_dir).unwrap();
+        let expected = std::fs::read_to_string(expected_path).unwrap();
+        match std::fs::read_to_string(actual_dir.join(relative)) {
+            Ok(actual) if actual == expected => (),
+            Ok(actual) => {
+                failures += 1;
+                eprintln!("Output differs for {}:", relative.display());
+                print_difference(&expected, &actual);
+            }
+            Err(_) => {
+                failures += 1;
+                eprintln!("Missing output for {}", relative.display());
+            }
+        }
+    }
+    if actual_files.len() > expected_files.len() {
+        failures += actual_files.len() - expected_files.len();
+        eprintln!(
+            "Expected {} files in {}, got {}",
+            expected_files.len(),
+            actual_dir.display(),
+            actual_files.len()
+        );
+    }
+    failures
+}
+
+fn format(dir: &Path, check: bool) -> bool {
+    let mut command = aarf();
+    command.arg("fmt").arg(dir);
+    if check {
+        command.arg("--check");
+    }
+    command.status().expect("Failed running aarf").success()
+}
+
+fn round_trip(input: &Path, output: &Path) -> bool {
+    let _ = std::fs::remove_dir_all(output);
+    aarf()
+        .arg("round-trip")
+        .arg(input)
+        .arg(output)
+        .status()
+        .expect("Failed running aarf")
+        .success()
+}
+
+fn run_java(jar: &str, args: &[&Path]) -> bool {
+    Command::new("java")
+        .arg("-jar")
+        .arg(jar)
+        .args(args)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Assembles each `smali*` directory into a dex file and disassembles it into `output`.
+fn reassemble(smali_jar: &str, baksmali_jar: &str, input: &Path, output: &Path) -> bool {
+    let _ = std::fs::remove_dir_all(output);
+    std::fs::create_dir_all(output).unwrap();
+    let mut dirs = std::fs::read_dir(input)
+        .unwrap()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().starts_with("smali"))
+        })
+        .collect::<Vec<_>>();
+    dirs.sort();
+
+    for dir in dirs {
+        let name = dir.file_name().unwrap();
+        let dex = output.join(Path::new(name).with_extension("dex"));
+        if !run_java(
+            smali_jar,
+            &[Path::new("assemble"), Path::new("--output"), &dex, &dir],
+        ) || !run_java(
+            baksmali_jar,
+            &[
+                Path::new("disassemble"),
+                Path::new("--output"),
+                &output.join(name),
+                &dex,
+            ],
+        ) {
+            eprintln!("Failed reassembling {}", dir.display());
+            return false;
+        }
+    }
+    true
+}
+
+fn main() -> ExitCode {
+    let input = corpus_dir();
+    let output = output_dir("roundtrip");
+    let first = output.join("first");
+    let second = output.join("second");
+
+    if !round_trip(&input, &first) || !round_trip(&first, &second) {
+        eprintln!("Writing the corpus back as Smali failed");
+        return ExitCode::FAILURE;
+    }
+    let mut failures = compare_dirs(&first, &second, "smali");
+
+    // Formatting in place produces the same files, which then pass the check
+    let formatted = output.join("formatted");
+    copy_dir(&input, &formatted).unwrap();
+    if !format(&formatted, false) || !format(&formatted, true) || !format(&first, true) {
+        eprintln!("Formatting the corpus failed");
+        return ExitCode::FAILURE;
+    }
+    failures += compare_dirs(&first, &formatted, "smali");
+
+    match (
+        std::env::var("AARF_SMALI_JAR"),
+        std::env::var("AARF_BAKSMALI_JAR"),
+    ) {
+        (Ok(smali_jar), Ok(baksmali_jar)) => {
+            let original = output.join("original-dex");
+            let round_tripped = output.join("round-tripped-dex");
+            if !reassemble(&smali_jar, &baksmali_jar, &input, &original)
+                || !reassemble(&smali_jar, &baksmali_jar, &first, &round_tripped)
+            {
+                return ExitCode::FAILURE;
+            }
+            failures += compare_dirs(&original, &round_tripped, "smali");
+        }
+        _ => println!(
+            "AARF_SMALI_JAR or AARF_BAKSMALI_JAR not set, skipping comparison of assembled dex files"
+        ),
+    }
+
+    if failures > 0 {
+        eprintln!("{failures} files differ after writing them back as Smali");
+        ExitCode::FAILURE
+    } else {
+        println!("Smali round trip preserves the corpus");
+        ExitCode::SUCCESS
+    }
+}